@@ -0,0 +1,216 @@
+//! Cost of the ways a swap can fail, run with `cargo bench --bench failure_paths`.
+//!
+//! Aggregators probe pools with instructions that often fail, and pay for the
+//! compute spent before the failure.  Wall time of the processor off-chain is
+//! a stand-in for the compute units it burns on-chain: a bad account should
+//! cost a fraction of a swap that runs the curve and then misses its
+//! slippage limit.
+//!
+//! The authority's program address is the exception: off-chain it costs
+//! about as much as the whole swap, while on-chain `create_program_address`
+//! is a fixed price syscall.  It's checked last for that reason.
+
+use rebuild_token_swap::{
+    curve::{base::SwapCurve, fees::Fees},
+    instruction::{self, Swap},
+    processor::Processor,
+    state::{SwapV2, SwapVersion},
+};
+use solana_program::{
+    account_info::AccountInfo,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+};
+use spl_token::state::{Account, AccountState, Mint};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 20_000;
+const RESERVE: u64 = 1_000_000_000_000;
+
+/// Keeps the processor's logs out of the results
+struct QuietSyscallStubs;
+
+impl SyscallStubs for QuietSyscallStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, _fields: &[&[u8]]) {}
+}
+
+struct BenchAccount {
+    key: Pubkey,
+    lamports: u64,
+    data: Vec<u8>,
+    owner: Pubkey,
+}
+
+impl BenchAccount {
+    fn new(key: Pubkey, data: Vec<u8>, owner: Pubkey) -> Self {
+        Self {
+            key,
+            lamports: 1_000_000_000,
+            data,
+            owner,
+        }
+    }
+
+    fn token(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Self {
+        let account = Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        account.pack_into_slice(&mut data);
+        Self::new(Pubkey::new_unique(), data, spl_token::id())
+    }
+
+    fn mint(key: Pubkey, authority: &Pubkey, supply: u64) -> Self {
+        let mint = Mint {
+            mint_authority: COption::Some(*authority),
+            supply,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut data = vec![0; Mint::LEN];
+        mint.pack_into_slice(&mut data);
+        Self::new(key, data, spl_token::id())
+    }
+}
+
+/// Average time of a swap instruction that fails, checking it fails as expected
+fn time_failure(program_id: &Pubkey, accounts: &mut [BenchAccount], data: &[u8]) -> Duration {
+    let infos: Vec<AccountInfo> = accounts
+        .iter_mut()
+        .map(|account| {
+            AccountInfo::new(
+                &account.key,
+                false,
+                true,
+                &mut account.lamports,
+                &mut account.data,
+                &account.owner,
+                false,
+                0,
+            )
+        })
+        .collect();
+    assert!(Processor::process(program_id, &infos, data).is_err());
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(Processor::process(
+            black_box(program_id),
+            black_box(&infos),
+            black_box(data),
+        ))
+        .unwrap_err();
+    }
+    start.elapsed() / ITERATIONS
+}
+
+fn main() {
+    program_stubs::set_syscall_stubs(Box::new(QuietSyscallStubs));
+
+    let program_id = Pubkey::new_unique();
+    let swap_key = Pubkey::new_unique();
+    let (authority, nonce) = Pubkey::find_program_address(&[swap_key.as_ref()], &program_id);
+    let user = Pubkey::new_unique();
+    let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+    let pool_mint = BenchAccount::mint(Pubkey::new_unique(), &authority, RESERVE);
+    let pool_fee = BenchAccount::token(&pool_mint.key, &user, 0);
+    let token_a = BenchAccount::token(&mint_a, &authority, RESERVE);
+    let token_b = BenchAccount::token(&mint_b, &authority, RESERVE);
+
+    let swap_v2 = SwapV2 {
+        is_initialized: true,
+        nonce,
+        token_program_id: spl_token::id(),
+        token_a: token_a.key,
+        token_b: token_b.key,
+        pool_mint: pool_mint.key,
+        token_a_mint: mint_a,
+        token_b_mint: mint_b,
+        pool_fee_account: pool_fee.key,
+        fees: Fees::standard_30bps(),
+        swap_curve: SwapCurve::default(),
+        pool_mint_decimals: 6,
+        ..SwapV2::default()
+    };
+    let mut swap_data = vec![0; SwapVersion::LATEST_LEN];
+    SwapVersion::pack(SwapVersion::SwapV2(Box::new(swap_v2)), &mut swap_data).unwrap();
+
+    let mut accounts = vec![
+        BenchAccount::new(swap_key, swap_data, program_id),
+        BenchAccount::new(authority, vec![], Pubkey::default()),
+        BenchAccount::new(user, vec![], Pubkey::default()),
+        BenchAccount::token(&mint_a, &user, RESERVE),
+        token_a,
+        token_b,
+        BenchAccount::token(&mint_b, &user, 0),
+        pool_mint,
+        pool_fee,
+        BenchAccount::new(spl_token::id(), vec![], Pubkey::default()),
+    ];
+    let data = |minimum_amount_out| {
+        let swap = instruction::swap(
+            &program_id,
+            &spl_token::id(),
+            &swap_key,
+            &authority,
+            &user,
+            &accounts[3].key,
+            &accounts[4].key,
+            &accounts[5].key,
+            &accounts[6].key,
+            &accounts[7].key,
+            &accounts[8].key,
+            None,
+            None,
+            Swap {
+                amount_in: 1_000_000,
+                minimum_amount_out,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        );
+        swap.unwrap().data
+    };
+    let (slippage, valid) = (data(u64::MAX), data(0));
+
+    // fails on the curve's result, after all the math
+    let exceeded_slippage = time_failure(&program_id, &mut accounts, &slippage);
+
+    // fails on a key comparison, before any token account is unpacked
+    let fee_key = accounts[8].key;
+    accounts[8].key = Pubkey::new_unique();
+    let wrong_fee_account = time_failure(&program_id, &mut accounts, &valid);
+    accounts[8].key = fee_key;
+
+    // fails on the authority's program address, the last account check
+    let authority_key = accounts[1].key;
+    accounts[1].key = Pubkey::new_unique();
+    let wrong_authority = time_failure(&program_id, &mut accounts, &valid);
+    accounts[1].key = authority_key;
+
+    println!(
+        "swap failing on {:<20} {:>10?}",
+        "exceeded slippage", exceeded_slippage
+    );
+    for (failure, time) in [
+        ("wrong fee account", wrong_fee_account),
+        ("wrong authority", wrong_authority),
+    ] {
+        println!(
+            "swap failing on {:<20} {:>10?}  ({:.0}% of exceeded slippage)",
+            failure,
+            time,
+            100.0 * time.as_secs_f64() / exceeded_slippage.as_secs_f64()
+        );
+    }
+}
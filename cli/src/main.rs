@@ -0,0 +1,1914 @@
+//! Admin command line for token swap pools, built with `--features cli`
+//!
+//! Every command sends its transactions signed by the `--keypair` wallet,
+//! which pays for them, owns the user token accounts and, for admin commands,
+//! owns the pool fee account.
+
+use rebuild_token_swap::{
+    analysis,
+    client::PoolSummary,
+    compute_budget,
+    constants::MAX_FEE_TIERS,
+    curve::{
+        base::{CurveType, SwapCurve},
+        calculator::TradeDirection,
+        concentrated::ConcentratedCurve,
+        constant_price::ConstantPriceCurve,
+        constant_product::ConstantProductCurve,
+        fees::{FeeTier, FeeTiers, Fees},
+        offset::OffsetCurve,
+        stable::StableCurve,
+        weighted::WeightedCurve,
+    },
+    instruction::{
+        self, ConfigureLiquiditySnapshot, DepositAllTokenTypes, FeeConversionAccounts,
+        RegisterKeeper, SetBondedKeepersOnly, SetEmergency, SetFeeConversion, SetFeeTiers, SetGate,
+        SetGovernanceProgram, SetKeeperConfig, SetNestedPool, SetOwnerFeeAccounts,
+        SetOwnerFeeFallback, SetPaused, SetPoolTemplate, SetSandwichGuard,
+        SetSingleWithdrawFeeToLps, SetStakePool, SetWithdrawCooldown, SlashKeeper, Swap,
+        WithdrawAllTokenTypes, WithdrawMax, SLASH_STALE_ORACLE,
+    },
+    lookup_table,
+    pool_builder::InitializePoolBuilder,
+    pricing,
+    shadow::{self, ObservedSwap},
+    snapshot::{self, AccountFixture, PoolSnapshot},
+    state::{
+        Gate, KeeperBond, LiquiditySnapshot, OracleConfig, PoolTemplate, ReferredDeposits,
+        SwapState, SwapV1, SwapVersion,
+    },
+};
+use serde_json::json;
+use solana_client::{
+    rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient},
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcTransactionConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    address_lookup_table::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, write_keypair_file, Keypair, Signature, Signer},
+    system_instruction,
+    transaction::{Transaction, VersionedTransaction},
+};
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, UiLoadedAddresses, UiTransactionEncoding,
+    UiTransactionTokenBalance,
+};
+use spl_token::state::{Account, Mint};
+use std::{collections::HashMap, convert::TryFrom, env, fs, path::Path, process, str::FromStr};
+
+const USAGE: &str = "\
+usage: rebuild-balancer-cli [--url <rpc>] [--keypair <path>] --program-id <pubkey> <command>
+
+commands:
+  create-pool  --mint-a <pubkey> --mint-b <pubkey> --source-a <account> --source-b <account>
+               --amount-a <n> --amount-b <n>
+               [--token-b-price <n> | --amp <n> | --token-b-offset <n> [--buy-only]
+                | --weights <a>/<b> | --price-range <min>:<max>]
+               [--trade-fee-bps <n>] [--owner-fee-bps <n>] [--fee-holiday-slots <n>]
+               [--template <n>] [--oracle <account> --price-band-bps <n> [--oracle-offset <n>]]
+               [--swap-keypair <path>]
+               the pool template of index <n>, see pool-template, sets the fees
+               and curve in place of the fee and price options, --amp makes a StableSwap
+               pool with that amplification coefficient, --token-b-offset a pool opening
+               with token A alone, priced as if it held that much more token B and
+               taking no deposits, and with --buy-only one that only sells token A,
+               --weights a pool holding its value at those
+               weights, 80/20 say, --price-range a pool whose liquidity is only
+               active between those prices of token B, each <n> or <num>/<den> raw
+               token A; with --oracle the pool
+               is only created if the amounts price token B within <n> bps of the
+               oracle's u64 price, in raw token A per raw token B, at the offset.
+               The pool's keypair is saved to <path>, swap-<pubkey>.json by default,
+               before the vaults are funded, so a failed run doesn't strand them
+  swap         --pool <pubkey> --source <account> --destination <account>
+               --amount-in <n> [--minimum-out <n>] [--min-price <num>/<den>]
+               [--lookup-table <pubkey>] [--gate-account <account>]
+               the limit price is in raw destination per raw source token, pools
+               priced by a stake pool get their rate refreshed in the same transaction
+  deposit      --pool <pubkey> --source-a <account> --source-b <account>
+               --destination <account> --pool-tokens <n> [--gate-account <account>]
+               [--referrer <wallet>]
+               on a gated pool, the wallet's account of the gating token; the
+               wallet's credential is found without it.  --referrer attributes the
+               deposit to that wallet's registered referrer
+  referrals    --pool <pubkey> [--json]
+               deposits each registered referrer brought into the pool, to settle
+               referral campaigns against
+  withdraw     --pool <pubkey> --source <account> --destination-a <account>
+               --destination-b <account> (--pool-tokens <n> | --all)
+               --all burns the source's whole balance, as it is when the withdrawal lands
+  show-pool    --pool <pubkey> [--size <n>] [--minimums] [--json]
+               with --size, also the mid price, the bid and ask for <n> raw token B
+               and the depth within 1% of the mid price, as the program computes them
+  list-pools   [--json]
+  update-fees  --pool <pubkey> [--tier <threshold>:<trade bps>:<owner bps>]...
+               replaces the size tiers, the base fees are fixed at creation
+  pause        --pool <pubkey> [--off]
+               halts swaps and deposits during an incident, withdrawals still go
+               through, or resumes them
+  emergency    --pool <pubkey> [--off]
+               puts the pool in emergency mode, or takes it back out
+  set-fee-account --pool <pubkey> --fee-account <account>
+               moves the pool's fees, and its admin rights, to another pool token account
+  split-fees   --pool <pubkey> [--fee-account-a <account>] [--fee-account-b <account>]
+               sends the owner fee of A to B and of B to A trades to their own pool
+               token accounts, a side left out going back to the pool fee account
+  convert-fees --pool <pubkey> [--conversion-pool <pubkey> --fee-account <account>
+               --max-slippage-bps <n>]
+               sells the owner fee paid in the token the conversion pool shares with the
+               pool for its other token, the fee mint, paid to the fee mint account, at
+               most <n> bps below the conversion pool's spot price;
+               without --conversion-pool owner fees are minted as pool tokens again
+  withdraw-cooldown --pool <pubkey> --slots <n>
+               slots single-sided withdrawals wait after the pool's last single-sided
+               deposit, 0 to turn the cooldown off
+  gate         --pool <pubkey> [--mint <pubkey> | --issuer <pubkey>]
+               only wallets holding the mint's token, or with a credential from the
+               issuing program, can swap or deposit; with neither the pool is open again
+  withdraw-fee-to-lps --pool <pubkey> [--off]
+               leaves the owner's fee on single-sided withdrawals in the pool, for the
+               LPs who stay, or pays it to the owner again
+  sandwich-guard --pool <pubkey> [--off]
+               refuses swaps in transactions where another wallet swaps the pool both
+               ways, or stops checking
+  owner-fee-fallback --pool <pubkey> [--off]
+               lets swaps whose owner fee can't be converted into pool tokens through
+               without it, leaving the fee with the LPs, or has them fail again
+  bonded-keepers-only --pool <pubkey> [--off]
+               only takes oracle price and stake pool rate cranks signed by keepers
+               bonded in the keeper config, or takes them from anyone again
+  keeper-config --min-bond <lamports> --unbonding-slots <n>
+               sets the terms keepers bond on, signed by the program's upgrade
+               authority as documented in the program config
+  governance-program [--governance-program <pubkey>]
+               lets governances of an SPL Governance program be pool admins, their
+               native treasury signing as a proposal executes, or none without
+               --governance-program; signed by the program's upgrade authority
+  pool-template --index <n> [--token-b-price <n> | --amp <n> | --token-b-offset <n> [--buy-only]
+               | --weights <a>/<b> | --price-range <min>:<max>]
+               [--trade-fee-bps <n>] [--owner-fee-bps <n>]
+               sets the fees and curve of the pool template of index <n>, as create-pool
+               takes them, for create-pool --template; signed by the program's upgrade
+               authority
+  bond-keeper  --amount <lamports>
+               bonds the wallet as a keeper, or adds to its bond
+  unbond-keeper [--withdraw]
+               stops the wallet's cranks and starts unbonding; with --withdraw, pays
+               the bond back once the unbonding slots have passed
+  slash-keeper --keeper <wallet> --amount <lamports> [--reason <n>]
+               takes lamports of a keeper's bond, into the keeper config, for its
+               last oracle price crank, 0 for a stale oracle push by default; only
+               lands in the crank's slot, once the oracle holds another price
+  liquidity-snapshot --pool <pubkey> [--slot <n>]
+               with --slot, sets the earliest slot of the pool's snapshot; without,
+               takes it, recording the pool token supply and reserves for airdrops
+               to weigh LP balances against
+  resize-pool  --pool <pubkey>
+               grows a pool made by an older program version to the latest layout, in
+               place, migrating V1 pools to V2
+  link-stake-pool --pool <pubkey> [--stake-pool <pubkey>]
+               prices a wrapped SOL - LST constant price pool at the stake pool's
+               rate, or back at its last price without --stake-pool
+  nest-pool    --pool <pubkey> [--nested-pool <pubkey>]
+               marks the pool's token B as the nested pool's pool token, checking
+               its mint, so show-pool values it from the nested pool's reserves;
+               without --nested-pool the link is removed
+  create-lookup-table --pool <pubkey>...
+               puts the pools' static accounts in a new address lookup table, for
+               v0 transactions routing through them
+  export-pool  --pool <pubkey> --out <file>
+               saves the pool's state, vaults, mints and fee account, byte for byte
+  import-pool  --snapshot <file> --out <dir>
+               writes an exported pool's accounts as solana-test-validator fixtures
+  simulate-pool --prices <file> --amount-a <n> --amount-b <n>
+               [--token-b-price <n> | --amp <n> | --token-b-offset <n> [--buy-only]
+                | --weights <a>/<b> | --price-range <min>:<max>]
+               [--trade-fee-bps <n>] [--owner-fee-bps <n>] [--json]
+               replays a price series, the last column of each line in raw token A
+               per raw token B, against the pool create-pool would make
+  shadow-test  --pool <pubkey> [--limit <n>] [--trade-fee-bps <n>] [--owner-fee-bps <n>]
+               [--token-b-price <n>] [--json]
+               replays the pool's last <n> swaps, 100 by default, through this build's
+               curve and fee math with the given fees or price, and reports how the
+               traders' output would change.  Only swaps sent straight to the program,
+               one per transaction, are replayed";
+
+/// Options that don't take a value
+const SWITCHES: &[&str] = &["all", "buy-only", "json", "minimums", "off", "withdraw"];
+
+/// Decimals of the pool tokens minted by `create-pool`
+const POOL_DECIMALS: u8 = 6;
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let (command, options) = parse_args(args)?;
+    // offline, no wallet or rpc needed
+    match command.as_str() {
+        "simulate-pool" => return simulate_pool(&options),
+        "import-pool" => return import_pool(&options),
+        _ => {}
+    }
+    let config = Config::new(&options)?;
+    match command.as_str() {
+        "create-pool" => create_pool(&config, &options),
+        "swap" => swap(&config, &options),
+        "deposit" => deposit(&config, &options),
+        "referrals" => referrals(&config, &options),
+        "withdraw" => withdraw(&config, &options),
+        "show-pool" => show_pool(&config, &options),
+        "list-pools" => list_pools(&config, &options),
+        "update-fees" => update_fees(&config, &options),
+        "pause" => pause(&config, &options),
+        "emergency" => emergency(&config, &options),
+        "set-fee-account" => set_fee_account(&config, &options),
+        "split-fees" => split_fees(&config, &options),
+        "convert-fees" => convert_fees(&config, &options),
+        "shadow-test" => shadow_test(&config, &options),
+        "withdraw-cooldown" => withdraw_cooldown(&config, &options),
+        "gate" => gate(&config, &options),
+        "withdraw-fee-to-lps" => withdraw_fee_to_lps(&config, &options),
+        "sandwich-guard" => sandwich_guard(&config, &options),
+        "owner-fee-fallback" => owner_fee_fallback(&config, &options),
+        "bonded-keepers-only" => bonded_keepers_only(&config, &options),
+        "keeper-config" => keeper_config(&config, &options),
+        "governance-program" => governance_program(&config, &options),
+        "pool-template" => pool_template(&config, &options),
+        "bond-keeper" => bond_keeper(&config, &options),
+        "unbond-keeper" => unbond_keeper(&config, &options),
+        "slash-keeper" => slash_keeper(&config, &options),
+        "liquidity-snapshot" => liquidity_snapshot(&config, &options),
+        "resize-pool" => resize_pool(&config, &options),
+        "link-stake-pool" => link_stake_pool(&config, &options),
+        "nest-pool" => nest_pool(&config, &options),
+        "create-lookup-table" => create_lookup_table(&config, &options),
+        "export-pool" => export_pool(&config, &options),
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// ----------------------------------------------------------------------------- arguments
+
+/// `--name value` options by name, repeated options keep every value
+struct Options(HashMap<String, Vec<String>>);
+
+fn parse_args(args: &[String]) -> Result<(String, Options), String> {
+    let mut command = None;
+    let mut options: HashMap<String, Vec<String>> = HashMap::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.strip_prefix("--") {
+            Some(name) if SWITCHES.contains(&name) => {
+                options.entry(name.to_string()).or_default();
+            }
+            Some(name) => {
+                let value = args
+                    .next()
+                    .ok_or_else(|| format!("--{} needs a value", name))?;
+                options
+                    .entry(name.to_string())
+                    .or_default()
+                    .push(value.clone());
+            }
+            None if command.is_none() => command = Some(arg.clone()),
+            None => return Err(USAGE.to_string()),
+        }
+    }
+    let command = command.ok_or_else(|| USAGE.to_string())?;
+    Ok((command, Options(options)))
+}
+
+impl Options {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.0
+            .get(name)
+            .and_then(|values| values.last())
+            .map(String::as_str)
+    }
+
+    fn all(&self, name: &str) -> &[String] {
+        self.0.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    fn required(&self, name: &str) -> Result<&str, String> {
+        self.get(name)
+            .ok_or_else(|| format!("missing --{}\n\n{}", name, USAGE))
+    }
+
+    fn pubkey(&self, name: &str) -> Result<Pubkey, String> {
+        let value = self.required(name)?;
+        Pubkey::from_str(value).map_err(|err| format!("--{} {}: {}", name, value, err))
+    }
+
+    fn amount(&self, name: &str) -> Result<u64, String> {
+        parse_amount(name, self.required(name)?)
+    }
+
+    fn amount_or(&self, name: &str, default: u64) -> Result<u64, String> {
+        self.get(name)
+            .map_or(Ok(default), |value| parse_amount(name, value))
+    }
+}
+
+fn parse_amount(name: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|err| format!("--{} {}: {}", name, value, err))
+}
+
+/// A price of `<n>` or `<num>/<den>`, as numerator and denominator
+fn parse_price(name: &str, value: &str) -> Result<(u64, u64), String> {
+    match value.split_once('/') {
+        Some((numerator, denominator)) => Ok((
+            parse_amount(name, numerator)?,
+            parse_amount(name, denominator)?,
+        )),
+        None => Ok((parse_amount(name, value)?, 1)),
+    }
+}
+
+// ----------------------------------------------------------------------------- rpc
+
+struct Config {
+    rpc: RpcClient,
+    payer: Keypair,
+    program_id: Pubkey,
+}
+
+impl Config {
+    fn new(options: &Options) -> Result<Self, String> {
+        let url = options.get("url").unwrap_or("http://localhost:8899");
+        let keypair = match options.get("keypair") {
+            Some(path) => path.to_string(),
+            None => format!(
+                "{}/.config/solana/id.json",
+                env::var("HOME").map_err(|_| "--keypair not given and HOME not set")?
+            ),
+        };
+        let payer =
+            read_keypair_file(&keypair).map_err(|err| format!("reading {}: {}", keypair, err))?;
+        Ok(Self {
+            rpc: RpcClient::new_with_commitment(url.to_string(), CommitmentConfig::confirmed()),
+            payer,
+            program_id: options.pubkey("program-id")?,
+        })
+    }
+
+    /// Sends the instructions in one transaction, paid for by the wallet
+    fn send(&self, instructions: &[Instruction], signers: &[&Keypair]) -> Result<(), String> {
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+        let blockhash = self
+            .rpc
+            .get_latest_blockhash()
+            .map_err(|err| err.to_string())?;
+        let transaction = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            blockhash,
+        );
+        let signature = self
+            .rpc
+            .send_and_confirm_transaction(&transaction)
+            .map_err(|err| err.to_string())?;
+        println!("signature {}", signature);
+        Ok(())
+    }
+
+    /// Sends the instructions in one v0 transaction, naming the accounts
+    /// found in `tables` through them
+    fn send_v0(
+        &self,
+        instructions: &[Instruction],
+        tables: &[AddressLookupTableAccount],
+    ) -> Result<(), String> {
+        let blockhash = self
+            .rpc
+            .get_latest_blockhash()
+            .map_err(|err| err.to_string())?;
+        let message =
+            lookup_table::compile_v0(&self.payer.pubkey(), instructions, tables, blockhash)
+                .map_err(|err| err.to_string())?;
+        let transaction = VersionedTransaction::try_new(message, &[&self.payer])
+            .map_err(|err| err.to_string())?;
+        let signature = self
+            .rpc
+            .send_and_confirm_transaction(&transaction)
+            .map_err(|err| err.to_string())?;
+        println!("signature {}", signature);
+        Ok(())
+    }
+
+    fn lookup_table(&self, key: &Pubkey) -> Result<AddressLookupTableAccount, String> {
+        let data = self
+            .rpc
+            .get_account_data(key)
+            .map_err(|err| format!("lookup table {}: {}", key, err))?;
+        lookup_table::account(*key, &data).map_err(|err| format!("lookup table {}: {}", key, err))
+    }
+
+    fn pool(&self, pool: &Pubkey) -> Result<SwapVersion, String> {
+        let account = self
+            .rpc
+            .get_account(pool)
+            .map_err(|err| format!("pool {}: {}", pool, err))?;
+        if account.owner != self.program_id {
+            return Err(format!("pool {} isn't owned by the program", pool));
+        }
+        SwapVersion::unpack_versioned(&account.data)
+            .map_err(|err| format!("pool {}: {}", pool, err))
+    }
+
+    /// A pool's state even if it predates the latest layout, see
+    /// `SwapVersion::unpack_outdated`
+    fn outdated_pool(&self, pool: &Pubkey) -> Result<SwapVersion, String> {
+        let account = self
+            .rpc
+            .get_account(pool)
+            .map_err(|err| format!("pool {}: {}", pool, err))?;
+        if account.owner != self.program_id {
+            return Err(format!("pool {} isn't owned by the program", pool));
+        }
+        SwapVersion::unpack_outdated(&account.data).map_err(|err| format!("pool {}: {}", pool, err))
+    }
+
+    fn token_account(&self, key: &Pubkey) -> Result<Account, String> {
+        let data = self
+            .rpc
+            .get_account_data(key)
+            .map_err(|err| format!("token account {}: {}", key, err))?;
+        Account::unpack(&data).map_err(|err| format!("token account {}: {}", key, err))
+    }
+
+    fn mint(&self, key: &Pubkey) -> Result<Mint, String> {
+        let data = self
+            .rpc
+            .get_account_data(key)
+            .map_err(|err| format!("mint {}: {}", key, err))?;
+        Mint::unpack(&data).map_err(|err| format!("mint {}: {}", key, err))
+    }
+
+    fn mint_decimals(&self, key: &Pubkey) -> Result<u8, String> {
+        self.mint(key).map(|mint| mint.decimals)
+    }
+
+    fn authority(&self, pool: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[&pool.to_bytes()[..]], &self.program_id)
+    }
+
+    /// Instructions creating an account owned by `owner`, rent exempt
+    fn create_account(
+        &self,
+        account: &Keypair,
+        len: usize,
+        owner: &Pubkey,
+    ) -> Result<Instruction, String> {
+        let lamports = self
+            .rpc
+            .get_minimum_balance_for_rent_exemption(len)
+            .map_err(|err| err.to_string())?;
+        Ok(system_instruction::create_account(
+            &self.payer.pubkey(),
+            &account.pubkey(),
+            lamports,
+            len as u64,
+            owner,
+        ))
+    }
+
+    fn create_token_account(
+        &self,
+        account: &Keypair,
+        mint: &Pubkey,
+        owner: &Pubkey,
+    ) -> Result<Vec<Instruction>, String> {
+        Ok(vec![
+            self.create_account(account, Account::LEN, &spl_token::id())?,
+            spl_token::instruction::initialize_account(
+                &spl_token::id(),
+                &account.pubkey(),
+                mint,
+                owner,
+            )
+            .map_err(|err| err.to_string())?,
+        ])
+    }
+}
+
+// ----------------------------------------------------------------------------- commands
+
+/// Fees and curve of a new pool, shared by `create-pool` and `simulate-pool`
+fn pool_parameters(options: &Options) -> Result<(Fees, SwapCurve), String> {
+    let mut fees = Fees::standard_30bps();
+    fees.trade_fee_numerator = options.amount_or("trade-fee-bps", fees.trade_fee_numerator)?;
+    fees.owner_trade_fee_numerator =
+        options.amount_or("owner-fee-bps", fees.owner_trade_fee_numerator)?;
+    let curves = [
+        "token-b-price",
+        "amp",
+        "token-b-offset",
+        "weights",
+        "price-range",
+    ];
+    if curves.iter().filter(|name| options.has(name)).count() > 1 {
+        return Err(
+            "pass one of --token-b-price, --amp, --token-b-offset, --weights or --price-range"
+                .to_string(),
+        );
+    }
+    let swap_curve = match (
+        options.get("token-b-price"),
+        options.get("amp"),
+        options.get("token-b-offset"),
+        options.get("weights"),
+        options.get("price-range"),
+    ) {
+        (Some(price), _, _, _, _) => SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price: parse_amount("token-b-price", price)?,
+                token_b_price_denominator: 0,
+            }),
+        },
+        (_, Some(amp), _, _, _) => SwapCurve {
+            curve_type: CurveType::Stable,
+            calculator: Box::new(StableCurve {
+                amp: parse_amount("amp", amp)?,
+            }),
+        },
+        (_, _, Some(offset), _, _) => SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Box::new(OffsetCurve {
+                token_b_offset: parse_amount("token-b-offset", offset)?,
+                buy_only: options.has("buy-only"),
+            }),
+        },
+        (_, _, _, Some(weights), _) => match weights.split('/').collect::<Vec<_>>().as_slice() {
+            [token_a_weight, token_b_weight] => SwapCurve {
+                curve_type: CurveType::Weighted,
+                calculator: Box::new(WeightedCurve {
+                    token_a_weight: parse_amount("weights", token_a_weight)?,
+                    token_b_weight: parse_amount("weights", token_b_weight)?,
+                }),
+            },
+            _ => return Err(format!("--weights {}: expected a/b, e.g. 80/20", weights)),
+        },
+        (_, _, _, _, Some(range)) => match range.split(':').collect::<Vec<_>>().as_slice() {
+            [min, max] => {
+                let (min_token_b_price, min_token_b_price_denominator) =
+                    parse_price("price-range", min)?;
+                let (max_token_b_price, max_token_b_price_denominator) =
+                    parse_price("price-range", max)?;
+                SwapCurve {
+                    curve_type: CurveType::Concentrated,
+                    calculator: Box::new(ConcentratedCurve {
+                        min_token_b_price,
+                        min_token_b_price_denominator,
+                        max_token_b_price,
+                        max_token_b_price_denominator,
+                    }),
+                }
+            }
+            _ => return Err(format!("--price-range {}: expected <min>:<max>", range)),
+        },
+        (None, None, None, None, None) => SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve),
+        },
+    };
+    Ok((fees, swap_curve))
+}
+
+fn create_pool(config: &Config, options: &Options) -> Result<(), String> {
+    let (mint_a, mint_b) = (options.pubkey("mint-a")?, options.pubkey("mint-b")?);
+    let (source_a, source_b) = (options.pubkey("source-a")?, options.pubkey("source-b")?);
+    let (amount_a, amount_b) = (options.amount("amount-a")?, options.amount("amount-b")?);
+    let (fees, swap_curve) = pool_parameters(options)?;
+    let template = match options.get("template") {
+        Some(index) => {
+            let index = index
+                .parse()
+                .map_err(|err| format!("--template {}: {}", index, err))?;
+            let (template_key, _) = PoolTemplate::find_address(index, &config.program_id);
+            let template = config
+                .rpc
+                .get_account_data(&template_key)
+                .map_err(|err| format!("pool template {}: {}", index, err))
+                .and_then(|data| {
+                    PoolTemplate::unpack(&data)
+                        .map_err(|err| format!("pool template {}: {}", index, err))
+                })?;
+            Some(template)
+        }
+        None => None,
+    };
+    let fee_holiday_end_slot = match options.get("fee-holiday-slots") {
+        Some(_) => {
+            let slot = config.rpc.get_slot().map_err(|err| err.to_string())?;
+            slot + options.amount("fee-holiday-slots")?
+        }
+        None => 0,
+    };
+
+    // saved before anything is funded: the vaults belong to an authority
+    // derived from the swap account, which a failed run could otherwise
+    // never initialize to release them
+    let swap = Keypair::new();
+    let swap_keypair = match options.get("swap-keypair") {
+        Some(path) => path.to_string(),
+        None => format!("swap-{}.json", swap.pubkey()),
+    };
+    if Path::new(&swap_keypair).exists() {
+        return Err(format!("{} already exists", swap_keypair));
+    }
+    write_keypair_file(&swap, &swap_keypair)
+        .map_err(|err| format!("writing {}: {}", swap_keypair, err))?;
+    println!("swap keypair {}", swap_keypair);
+    let builder = InitializePoolBuilder::new(&config.program_id, &swap.pubkey());
+    let (authority, _) = builder.authority();
+    let payer = config.payer.pubkey();
+
+    // the pool mint and the vaults, funded with the initial liquidity
+    let (pool_mint, token_a, token_b) = (Keypair::new(), Keypair::new(), Keypair::new());
+    let mut instructions = vec![
+        config.create_account(&pool_mint, Mint::LEN, &spl_token::id())?,
+        spl_token::instruction::initialize_mint(
+            &spl_token::id(),
+            &pool_mint.pubkey(),
+            &authority,
+            None,
+            POOL_DECIMALS,
+        )
+        .map_err(|err| err.to_string())?,
+    ];
+    instructions.extend(config.create_token_account(&token_a, &mint_a, &authority)?);
+    instructions.extend(config.create_token_account(&token_b, &mint_b, &authority)?);
+    for (source, vault, amount) in [
+        (source_a, token_a.pubkey(), amount_a),
+        (source_b, token_b.pubkey(), amount_b),
+    ] {
+        instructions.push(
+            spl_token::instruction::transfer(
+                &spl_token::id(),
+                &source,
+                &vault,
+                &payer,
+                &[],
+                amount,
+            )
+            .map_err(|err| err.to_string())?,
+        );
+    }
+    config.send(&instructions, &[&pool_mint, &token_a, &token_b])?;
+
+    // the wallet's pool token accounts
+    let (fee_account, destination) = (Keypair::new(), Keypair::new());
+    let mut instructions =
+        config.create_token_account(&fee_account, &pool_mint.pubkey(), &payer)?;
+    instructions.extend(config.create_token_account(&destination, &pool_mint.pubkey(), &payer)?);
+    config.send(&instructions, &[&fee_account, &destination])?;
+
+    // then the pool itself, once its accounts check out
+    let builder = builder
+        .token_a(
+            (token_a.pubkey(), config.token_account(&token_a.pubkey())?),
+            (mint_a, config.mint(&mint_a)?),
+        )
+        .token_b(
+            (token_b.pubkey(), config.token_account(&token_b.pubkey())?),
+            (mint_b, config.mint(&mint_b)?),
+        )
+        .pool_mint((pool_mint.pubkey(), config.mint(&pool_mint.pubkey())?))
+        .fee_account((
+            fee_account.pubkey(),
+            config.token_account(&fee_account.pubkey())?,
+        ))
+        .destination((
+            destination.pubkey(),
+            config.token_account(&destination.pubkey())?,
+        ))
+        .fee_holiday_end_slot(fee_holiday_end_slot);
+    let initialize = match (template, options.get("oracle")) {
+        (Some(_), Some(_)) => return Err("--oracle can't be used with --template".to_string()),
+        (Some(template), None) => builder.template(template).build(),
+        (None, _) => builder.fees_and_curve(fees, swap_curve).build(),
+    };
+    let mut initialize = initialize.map_err(|err| err.to_string())?;
+    if options.get("oracle").is_some() {
+        let price_offset = u32::try_from(options.amount_or("oracle-offset", 0)?)
+            .map_err(|_| "--oracle-offset out of range".to_string())?;
+        let band_bps = u16::try_from(options.amount("price-band-bps")?)
+            .map_err(|_| "--price-band-bps out of range".to_string())?;
+        initialize = instruction::with_initial_price_oracle(
+            initialize,
+            &options.pubkey("oracle")?,
+            price_offset,
+            band_bps,
+        )
+        .map_err(|err| err.to_string())?;
+    }
+    let instructions = vec![
+        config.create_account(&swap, SwapVersion::LATEST_LEN, &config.program_id)?,
+        initialize,
+    ];
+    config.send(&instructions, &[&swap])?;
+
+    println!("pool {}", swap.pubkey());
+    println!("pool tokens {}", destination.pubkey());
+    Ok(())
+}
+
+fn swap(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let (source, destination) = (options.pubkey("source")?, options.pubkey("destination")?);
+    let pool = config.pool(&pool_key)?;
+    let (trade_direction, swap_source, swap_destination) =
+        if config.token_account(&source)?.mint == *pool.token_a_mint() {
+            (
+                TradeDirection::AtoB,
+                pool.token_a_account(),
+                pool.token_b_account(),
+            )
+        } else {
+            (
+                TradeDirection::BtoA,
+                pool.token_b_account(),
+                pool.token_a_account(),
+            )
+        };
+    let (min_price_numerator, min_price_denominator) = match options.get("min-price") {
+        Some(price) => match price.split('/').collect::<Vec<_>>().as_slice() {
+            [numerator, denominator] => (
+                parse_amount("min-price", numerator)?,
+                parse_amount("min-price", denominator)?,
+            ),
+            _ => {
+                return Err(format!(
+                    "--min-price {}: expected numerator/denominator",
+                    price
+                ))
+            }
+        },
+        None => (0, 0),
+    };
+    // the pool sells this side's owner fee through its fee conversion pool
+    let fee_conversion = match pool.fee_conversion(trade_direction) {
+        Some((fee_conversion_pool, fee_conversion_account)) => {
+            let conversion_pool = config.pool(&fee_conversion_pool)?;
+            let source_mint = config.token_account(&source)?.mint;
+            let (fee_conversion_source, fee_conversion_destination) =
+                if *conversion_pool.token_a_mint() == source_mint {
+                    (
+                        conversion_pool.token_a_account(),
+                        conversion_pool.token_b_account(),
+                    )
+                } else {
+                    (
+                        conversion_pool.token_b_account(),
+                        conversion_pool.token_a_account(),
+                    )
+                };
+            Some(FeeConversionAccounts {
+                fee_conversion_pool,
+                fee_conversion_authority: config.authority(&fee_conversion_pool).0,
+                fee_conversion_source: *fee_conversion_source,
+                fee_conversion_destination: *fee_conversion_destination,
+                fee_conversion_pool_mint: *conversion_pool.pool_mint(),
+                fee_conversion_account,
+            })
+        }
+        None => None,
+    };
+    let swap = instruction::swap_with_fee_conversion(
+        &config.program_id,
+        pool.token_program_id(),
+        &pool_key,
+        &config.authority(&pool_key).0,
+        &config.payer.pubkey(),
+        &source,
+        swap_source,
+        swap_destination,
+        &destination,
+        pool.pool_mint(),
+        pool.owner_fee_account(trade_direction),
+        fee_conversion.as_ref(),
+        None,
+        None,
+        Swap {
+            amount_in: options.amount("amount-in")?,
+            minimum_amount_out: options.amount_or("minimum-out", 0)?,
+            min_price_numerator,
+            min_price_denominator,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    let swap = if pool.sandwich_guard() {
+        instruction::with_instructions_sysvar(swap).map_err(|err| err.to_string())?
+    } else {
+        swap
+    };
+    let swap = through_gate(config, options, &pool, swap)?;
+    let mut instructions = vec![];
+    if let Some(stake_pool) = pool.stake_pool() {
+        let refresh =
+            instruction::refresh_stake_pool_rate(&config.program_id, &pool_key, &stake_pool)
+                .map_err(|err| err.to_string())?;
+        // the wallet has to be a bonded keeper itself
+        let refresh = if pool.bonded_keepers_only() {
+            instruction::with_keeper(refresh, &config.program_id, &config.payer.pubkey())
+                .map_err(|err| err.to_string())?
+        } else {
+            refresh
+        };
+        instructions.push(refresh);
+    }
+    instructions.push(swap);
+    let instructions = compute_budget::with_compute_unit_limit(
+        &config.program_id,
+        pool.swap_curve().curve_type,
+        instructions,
+    );
+    match options.get("lookup-table") {
+        Some(_) => {
+            let table = config.lookup_table(&options.pubkey("lookup-table")?)?;
+            config.send_v0(&instructions, &[table])
+        }
+        None => config.send(&instructions, &[]),
+    }
+}
+
+fn deposit(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let deposit = instruction::deposit_all_token_types(
+        &config.program_id,
+        pool.token_program_id(),
+        &pool_key,
+        &config.authority(&pool_key).0,
+        &config.payer.pubkey(),
+        &options.pubkey("source-a")?,
+        &options.pubkey("source-b")?,
+        pool.token_a_account(),
+        pool.token_b_account(),
+        pool.pool_mint(),
+        &options.pubkey("destination")?,
+        DepositAllTokenTypes {
+            pool_token_amount: options.amount("pool-tokens")?,
+            maximum_token_a_amount: options.amount_or("maximum-a", u64::MAX)?,
+            maximum_token_b_amount: options.amount_or("maximum-b", u64::MAX)?,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    let deposit = through_gate(config, options, &pool, deposit)?;
+    let deposit = match options.get("referrer") {
+        Some(_) => instruction::with_referrer(
+            deposit,
+            &config.program_id,
+            &options.pubkey("referrer")?,
+            &config.payer.pubkey(),
+        )
+        .map_err(|err| err.to_string())?,
+        None => deposit,
+    };
+    config.send(
+        &compute_budget::with_compute_unit_limit(
+            &config.program_id,
+            pool.swap_curve().curve_type,
+            vec![deposit],
+        ),
+        &[],
+    )
+}
+
+fn referrals(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let accounts = config
+        .rpc
+        .get_program_accounts_with_config(
+            &config.program_id,
+            RpcProgramAccountsConfig {
+                filters: Some(vec![
+                    RpcFilterType::DataSize(ReferredDeposits::LEN as u64),
+                    RpcFilterType::Memcmp(Memcmp::new_base58_encoded(2, pool_key.as_ref())),
+                ]),
+                account_config: RpcAccountInfoConfig::default(),
+                ..RpcProgramAccountsConfig::default()
+            },
+        )
+        .map_err(|err| err.to_string())?;
+    let mut referrals: Vec<ReferredDeposits> = accounts
+        .into_iter()
+        .filter_map(|(_, account)| ReferredDeposits::unpack(&account.data).ok())
+        .collect();
+    // biggest referrers first
+    referrals.sort_by_key(|referred| std::cmp::Reverse(referred.pool_token_amount));
+    for referred in referrals {
+        if options.has("json") {
+            println!(
+                "{}",
+                json!({
+                    "referrer": referred.referrer.to_string(),
+                    "deposits": referred.deposits,
+                    "token_a_amount": referred.token_a_amount,
+                    "token_b_amount": referred.token_b_amount,
+                    "pool_token_amount": referred.pool_token_amount,
+                })
+            );
+        } else {
+            println!(
+                "{}: {} deposits of {} token A and {} token B, minting {} pool tokens",
+                referred.referrer,
+                referred.deposits,
+                referred.token_a_amount,
+                referred.token_b_amount,
+                referred.pool_token_amount
+            );
+        }
+    }
+    Ok(())
+}
+
+fn withdraw(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let (authority, source) = (config.authority(&pool_key).0, options.pubkey("source")?);
+    let (destination_a, destination_b) = (
+        options.pubkey("destination-a")?,
+        options.pubkey("destination-b")?,
+    );
+    let minimum_token_a_amount = options.amount_or("minimum-a", 0)?;
+    let minimum_token_b_amount = options.amount_or("minimum-b", 0)?;
+    let withdraw = if options.has("all") {
+        instruction::withdraw_max(
+            &config.program_id,
+            pool.token_program_id(),
+            &pool_key,
+            &authority,
+            &config.payer.pubkey(),
+            pool.pool_mint(),
+            pool.pool_fee_account(),
+            &source,
+            pool.token_a_account(),
+            pool.token_b_account(),
+            &destination_a,
+            &destination_b,
+            WithdrawMax {
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            },
+        )
+    } else {
+        instruction::withdraw_all_token_types(
+            &config.program_id,
+            pool.token_program_id(),
+            &pool_key,
+            &authority,
+            &config.payer.pubkey(),
+            pool.pool_mint(),
+            pool.pool_fee_account(),
+            &source,
+            pool.token_a_account(),
+            pool.token_b_account(),
+            &destination_a,
+            &destination_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: options.amount("pool-tokens")?,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            },
+        )
+    }
+    .map_err(|err| err.to_string())?;
+    config.send(
+        &compute_budget::with_compute_unit_limit(
+            &config.program_id,
+            pool.swap_curve().curve_type,
+            vec![withdraw],
+        ),
+        &[],
+    )
+}
+
+/// A pool's one line summary, or its full state as JSON, with its reserves
+fn describe_pool(
+    config: &Config,
+    key: &Pubkey,
+    pool: &SwapVersion,
+    json: bool,
+) -> Result<String, String> {
+    let reserve_a = config.token_account(pool.token_a_account())?.amount;
+    let reserve_b = config.token_account(pool.token_b_account())?.amount;
+    if json {
+        let value = json!({
+            "address": key.to_string(),
+            "state": pool,
+            "reserve_a": reserve_a,
+            "reserve_b": reserve_b,
+        });
+        return Ok(value.to_string());
+    }
+    // mints have no on-chain symbol, the start of their address stands in
+    let (symbol_a, symbol_b) = (
+        pool.token_a_mint().to_string(),
+        pool.token_b_mint().to_string(),
+    );
+    // V1 pools don't record their tokens' decimals, only those need the mints
+    let (decimals_a, decimals_b) = match pool.token_decimals() {
+        Some(decimals) => decimals,
+        None => (
+            config.mint_decimals(pool.token_a_mint())?,
+            config.mint_decimals(pool.token_b_mint())?,
+        ),
+    };
+    let summary = PoolSummary {
+        pool,
+        reserve_a,
+        decimals_a,
+        symbol_a: &symbol_a[..4],
+        reserve_b,
+        decimals_b,
+        symbol_b: &symbol_b[..4],
+    };
+    Ok(format!("{}  {}\n    {}", key, summary, pool))
+}
+
+fn show_pool(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    println!(
+        "{}",
+        describe_pool(config, &pool_key, &pool, options.has("json"))?
+    );
+    if options.get("size").is_some() {
+        let reserve_a = config.token_account(pool.token_a_account())?.amount;
+        let reserve_b = config.token_account(pool.token_b_account())?.amount;
+        let slot = config.rpc.get_slot().map_err(|err| err.to_string())?;
+        let prices =
+            pricing::pool_prices(&pool, reserve_a, reserve_b, options.amount("size")?, slot)
+                .ok_or("the pool's curve can't price its reserves")?;
+        if options.has("json") {
+            println!("{}", json!(prices));
+        } else {
+            let quote =
+                |price: Option<f64>| price.map_or("-".to_string(), |price| price.to_string());
+            println!(
+                "    mid {} / bid {} / ask {} / depth {} B down, {} A up",
+                prices.mid_price,
+                quote(prices.bid),
+                quote(prices.ask),
+                prices.bid_depth,
+                prices.ask_depth,
+            );
+        }
+    }
+    if options.has("minimums") {
+        let reserve_a = config.token_account(pool.token_a_account())?.amount;
+        let reserve_b = config.token_account(pool.token_b_account())?.amount;
+        let pool_supply = config.mint(pool.pool_mint())?.supply;
+        let minimums = pricing::minimum_amounts(&pool, reserve_a, reserve_b, pool_supply)
+            .map_err(|err| err.to_string())?;
+        if options.has("json") {
+            println!("{}", json!(minimums));
+        } else {
+            let single =
+                |amount: Option<u64>| amount.map_or("-".to_string(), |amount| amount.to_string());
+            println!(
+                "    minimum deposit {} pool tokens, {} A or {} B single-sided / withdraw {} pool tokens",
+                minimums.deposit_pool_tokens,
+                single(minimums.deposit_single_token_a),
+                single(minimums.deposit_single_token_b),
+                minimums.withdraw_pool_tokens,
+            );
+        }
+    }
+    if let Some(nested_key) = pool.nested_pool() {
+        // token B is the nested pool's pool token, worth its share of that
+        // pool's reserves
+        let nested = config.pool(&nested_key)?;
+        let reserve_a = config.token_account(nested.token_a_account())?.amount;
+        let reserve_b = config.token_account(nested.token_b_account())?.amount;
+        let pool_supply = config.mint(nested.pool_mint())?.supply;
+        let price = pricing::pool_token_price(&nested, reserve_a, reserve_b, pool_supply)
+            .ok_or("the nested pool's curve can't price its reserves")?;
+        if options.has("json") {
+            println!(
+                "{}",
+                json!({ "nested_pool": nested_key.to_string(), "pool_token_price": price })
+            );
+        } else {
+            println!(
+                "    token B is the pool token of {}, worth {} of its raw token A {}",
+                nested_key,
+                price,
+                nested.token_a_mint(),
+            );
+        }
+    }
+    Ok(())
+}
+
+fn list_pools(config: &Config, options: &Options) -> Result<(), String> {
+    // pools of every version, told apart from the program's other accounts
+    // by their size
+    let mut pools = vec![];
+    for len in [SwapVersion::LATEST_LEN, 1 + SwapV1::LEN] {
+        let accounts = config
+            .rpc
+            .get_program_accounts_with_config(
+                &config.program_id,
+                RpcProgramAccountsConfig {
+                    filters: Some(vec![RpcFilterType::DataSize(len as u64)]),
+                    account_config: RpcAccountInfoConfig::default(),
+                    ..RpcProgramAccountsConfig::default()
+                },
+            )
+            .map_err(|err| err.to_string())?;
+        pools.extend(accounts);
+    }
+    for (key, account) in pools {
+        match SwapVersion::unpack_versioned(&account.data) {
+            Ok(pool) if pool.is_initialized() => {
+                println!(
+                    "{}",
+                    describe_pool(config, &key, &pool, options.has("json"))?
+                )
+            }
+            _ => continue,
+        }
+    }
+    Ok(())
+}
+
+fn update_fees(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let tiers = options.all("tier");
+    if tiers.len() > MAX_FEE_TIERS {
+        return Err(format!("at most {} --tier", MAX_FEE_TIERS));
+    }
+    // tier numerators are over the base schedule's denominators
+    let fees = pool.fees();
+    let mut fee_tiers = FeeTiers::default();
+    for (tier, value) in fee_tiers.tiers.iter_mut().zip(tiers) {
+        let parts: Vec<&str> = value.split(':').collect();
+        let (threshold, trade_bps, owner_bps) = match parts.as_slice() {
+            [threshold, trade_bps, owner_bps] => (
+                parse_amount("tier", threshold)?,
+                parse_amount("tier", trade_bps)?,
+                parse_amount("tier", owner_bps)?,
+            ),
+            _ => return Err(format!("--tier {}: expected threshold:trade:owner", value)),
+        };
+        *tier = FeeTier {
+            threshold,
+            trade_fee_numerator: trade_bps * fees.trade_fee_denominator / 10_000,
+            owner_trade_fee_numerator: owner_bps * fees.owner_trade_fee_denominator / 10_000,
+        };
+    }
+    let set_fee_tiers = instruction::set_fee_tiers(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetFeeTiers { fee_tiers },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_fee_tiers], &[])
+}
+
+fn pause(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_paused = instruction::set_paused(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetPaused {
+            paused: !options.has("off"),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_paused], &[])
+}
+
+fn emergency(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_emergency = instruction::set_emergency(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetEmergency {
+            enabled: !options.has("off"),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_emergency], &[])
+}
+
+fn set_fee_account(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_fee_account = instruction::set_fee_account(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        &options.pubkey("fee-account")?,
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_fee_account], &[])
+}
+
+fn split_fees(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let owner_fee_account = |name| match options.get(name) {
+        Some(_) => options.pubkey(name),
+        None => Ok(Pubkey::default()),
+    };
+    let set_owner_fee_accounts = instruction::set_owner_fee_accounts(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetOwnerFeeAccounts {
+            owner_fee_account_a: owner_fee_account("fee-account-a")?,
+            owner_fee_account_b: owner_fee_account("fee-account-b")?,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_owner_fee_accounts], &[])
+}
+
+fn convert_fees(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let (fee_conversion_pool, fee_conversion_account, max_slippage_bps) =
+        match options.get("conversion-pool") {
+            Some(_) => (
+                options.pubkey("conversion-pool")?,
+                options.pubkey("fee-account")?,
+                u16::try_from(options.amount("max-slippage-bps")?)
+                    .map_err(|_| "--max-slippage-bps out of range".to_string())?,
+            ),
+            None => (Pubkey::default(), Pubkey::default(), 0),
+        };
+    let set_fee_conversion = instruction::set_fee_conversion(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetFeeConversion {
+            fee_conversion_pool,
+            fee_conversion_account,
+            max_slippage_bps,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_fee_conversion], &[])
+}
+
+fn withdraw_cooldown(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_withdraw_cooldown = instruction::set_withdraw_cooldown(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetWithdrawCooldown {
+            cooldown_slots: options.amount("slots")?,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_withdraw_cooldown], &[])
+}
+
+fn gate(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let (gate, credential) = match (options.get("mint"), options.get("issuer")) {
+        (Some(_), Some(_)) => return Err("--mint and --issuer are exclusive".to_string()),
+        (Some(_), None) => (options.pubkey("mint")?, false),
+        (None, Some(_)) => (options.pubkey("issuer")?, true),
+        (None, None) => (Pubkey::default(), false),
+    };
+    let set_gate = instruction::set_gate(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetGate { gate, credential },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_gate], &[])
+}
+
+fn withdraw_fee_to_lps(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_single_withdraw_fee_to_lps = instruction::set_single_withdraw_fee_to_lps(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetSingleWithdrawFeeToLps {
+            to_lps: !options.has("off"),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_single_withdraw_fee_to_lps], &[])
+}
+
+fn sandwich_guard(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_sandwich_guard = instruction::set_sandwich_guard(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetSandwichGuard {
+            enabled: !options.has("off"),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_sandwich_guard], &[])
+}
+
+fn owner_fee_fallback(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_owner_fee_fallback = instruction::set_owner_fee_fallback(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetOwnerFeeFallback {
+            enabled: !options.has("off"),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_owner_fee_fallback], &[])
+}
+
+fn bonded_keepers_only(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let set_bonded_keepers_only = instruction::set_bonded_keepers_only(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetBondedKeepersOnly {
+            enabled: !options.has("off"),
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_bonded_keepers_only], &[])
+}
+
+fn governance_program(config: &Config, options: &Options) -> Result<(), String> {
+    let governance_program = match options.get("governance-program") {
+        Some(_) => options.pubkey("governance-program")?,
+        None => Pubkey::default(),
+    };
+    let set_governance_program = instruction::set_governance_program(
+        &config.program_id,
+        &config.payer.pubkey(),
+        &config.payer.pubkey(),
+        SetGovernanceProgram { governance_program },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_governance_program], &[])
+}
+
+fn pool_template(config: &Config, options: &Options) -> Result<(), String> {
+    let index = options.amount("index")?;
+    let index = u8::try_from(index).map_err(|_| format!("--index {}: out of range", index))?;
+    let (fees, swap_curve) = pool_parameters(options)?;
+    let set_pool_template = instruction::set_pool_template(
+        &config.program_id,
+        &config.payer.pubkey(),
+        &config.payer.pubkey(),
+        SetPoolTemplate {
+            index,
+            fees,
+            swap_curve,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_pool_template], &[])
+}
+
+fn keeper_config(config: &Config, options: &Options) -> Result<(), String> {
+    let set_keeper_config = instruction::set_keeper_config(
+        &config.program_id,
+        &config.payer.pubkey(),
+        &config.payer.pubkey(),
+        SetKeeperConfig {
+            min_bond: options.amount("min-bond")?,
+            unbonding_slots: options.amount("unbonding-slots")?,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_keeper_config], &[])
+}
+
+fn bond_keeper(config: &Config, options: &Options) -> Result<(), String> {
+    let register_keeper = instruction::register_keeper(
+        &config.program_id,
+        &config.payer.pubkey(),
+        RegisterKeeper {
+            amount: options.amount("amount")?,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[register_keeper], &[])
+}
+
+fn unbond_keeper(config: &Config, options: &Options) -> Result<(), String> {
+    let keeper = config.payer.pubkey();
+    let instruction = if options.has("withdraw") {
+        instruction::withdraw_keeper_bond(&config.program_id, &keeper)
+    } else {
+        instruction::unbond_keeper(&config.program_id, &keeper)
+    }
+    .map_err(|err| err.to_string())?;
+    config.send(&[instruction], &[])
+}
+
+fn slash_keeper(config: &Config, options: &Options) -> Result<(), String> {
+    let keeper = options.pubkey("keeper")?;
+    let (keeper_bond_key, _) = KeeperBond::find_address(&keeper, &config.program_id);
+    let keeper_bond = config
+        .rpc
+        .get_account_data(&keeper_bond_key)
+        .map_err(|err| err.to_string())
+        .and_then(|data| KeeperBond::unpack(&data).map_err(|err| err.to_string()))?;
+    let swap = keeper_bond.last_crank_swap;
+    let (oracle_config_key, _) = OracleConfig::find_address(&swap, &config.program_id);
+    let oracle_config = config
+        .rpc
+        .get_account_data(&oracle_config_key)
+        .map_err(|err| err.to_string())
+        .and_then(|data| OracleConfig::unpack(&data).map_err(|err| err.to_string()))?;
+    let reason = match options.get("reason") {
+        Some(reason) => reason
+            .parse()
+            .map_err(|err| format!("--reason {}: {}", reason, err))?,
+        None => SLASH_STALE_ORACLE,
+    };
+    let slash_keeper = instruction::slash_keeper(
+        &config.program_id,
+        &config.payer.pubkey(),
+        &keeper,
+        &swap,
+        &oracle_config.oracle,
+        SlashKeeper {
+            amount: options.amount("amount")?,
+            reason,
+        },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[slash_keeper], &[])
+}
+
+fn liquidity_snapshot(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    if options.get("slot").is_some() {
+        let configure = instruction::configure_liquidity_snapshot(
+            &config.program_id,
+            &pool_key,
+            &config.payer.pubkey(),
+            pool.pool_fee_account(),
+            &config.payer.pubkey(),
+            ConfigureLiquiditySnapshot {
+                slot: options.amount("slot")?,
+            },
+        )
+        .map_err(|err| err.to_string())?;
+        return config.send(&[configure], &[]);
+    }
+
+    let crank = instruction::crank_liquidity_snapshot(
+        &config.program_id,
+        &pool_key,
+        pool.token_a_account(),
+        pool.token_b_account(),
+        pool.pool_mint(),
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[crank], &[])?;
+
+    let (snapshot_key, _) = LiquiditySnapshot::find_address(&pool_key, &config.program_id);
+    let snapshot = config
+        .rpc
+        .get_account_data(&snapshot_key)
+        .map_err(|err| err.to_string())
+        .and_then(|data| LiquiditySnapshot::unpack(&data).map_err(|err| err.to_string()))?;
+    println!(
+        "at slot {}: {} pool tokens, {} A, {} B",
+        snapshot.taken_slot,
+        snapshot.pool_token_supply,
+        snapshot.token_a_amount,
+        snapshot.token_b_amount
+    );
+    Ok(())
+}
+
+/// The instruction presenting the wallet's gate account, when the pool is
+/// gated
+fn through_gate(
+    config: &Config,
+    options: &Options,
+    pool: &SwapVersion,
+    instruction: Instruction,
+) -> Result<Instruction, String> {
+    let gate_account = match pool.gate() {
+        Some(Gate::Token(mint)) => match options.get("gate-account") {
+            Some(_) => options.pubkey("gate-account")?,
+            None => {
+                return Err(format!(
+                    "the pool is gated, --gate-account of {} needed",
+                    mint
+                ))
+            }
+        },
+        Some(Gate::Credential(issuer)) => {
+            Gate::find_credential_address(&issuer, &config.payer.pubkey()).0
+        }
+        None => return Ok(instruction),
+    };
+    instruction::with_gate_account(instruction, &gate_account).map_err(|err| err.to_string())
+}
+
+fn resize_pool(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.outdated_pool(&pool_key)?;
+    let mints = match &pool {
+        SwapVersion::SwapV1(_) => {
+            Some((pool.pool_mint(), pool.token_a_mint(), pool.token_b_mint()))
+        }
+        SwapVersion::SwapV2(_) => None,
+    };
+    let resize_swap_account = instruction::resize_swap_account(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        &config.payer.pubkey(),
+        mints,
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[resize_swap_account], &[])
+}
+
+fn link_stake_pool(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let stake_pool = match options.get("stake-pool") {
+        Some(_) => options.pubkey("stake-pool")?,
+        None => Pubkey::default(),
+    };
+    let set_stake_pool = instruction::set_stake_pool(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        SetStakePool { stake_pool },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_stake_pool], &[])
+}
+
+fn nest_pool(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let nested_pool = match options.get("nested-pool") {
+        Some(_) => options.pubkey("nested-pool")?,
+        None => Pubkey::default(),
+    };
+    let set_nested_pool = instruction::set_nested_pool(
+        &config.program_id,
+        &pool_key,
+        &config.payer.pubkey(),
+        pool.pool_fee_account(),
+        pool.token_b_mint(),
+        SetNestedPool { nested_pool },
+    )
+    .map_err(|err| err.to_string())?;
+    config.send(&[set_nested_pool], &[])
+}
+
+fn create_lookup_table(config: &Config, options: &Options) -> Result<(), String> {
+    let mut addresses = vec![];
+    for pool in options.all("pool") {
+        let pool_key = Pubkey::from_str(pool).map_err(|err| format!("--pool {}: {}", pool, err))?;
+        let pool = config.pool(&pool_key)?;
+        addresses.extend(
+            lookup_table::pool_addresses(&config.program_id, &pool_key, &pool)
+                .map_err(|err| err.to_string())?,
+        );
+    }
+    if addresses.is_empty() {
+        return Err(USAGE.to_string());
+    }
+    let payer = config.payer.pubkey();
+    // the address is derived from a slot the cluster still remembers
+    let recent_slot = config.rpc.get_slot().map_err(|err| err.to_string())?;
+    let (create, table) = lookup_table::create(&payer, &payer, recent_slot);
+    config.send(&[create], &[])?;
+    let empty = AddressLookupTableAccount {
+        key: table,
+        addresses: vec![],
+    };
+    for extend in lookup_table::extend(&empty, &payer, &payer, &addresses) {
+        config.send(&[extend], &[])?;
+    }
+    println!("lookup table {}, usable from the next slot", table);
+    Ok(())
+}
+
+fn export_pool(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let required = snapshot::pool_accounts(&pool_key, &pool);
+    let keys: Vec<Pubkey> = required
+        .iter()
+        .chain(&snapshot::derived_accounts(&config.program_id, &pool_key))
+        .copied()
+        .collect();
+    // one request, so every account is read at the same slot
+    let response = config
+        .rpc
+        .get_multiple_accounts_with_commitment(&keys, config.rpc.commitment())
+        .map_err(|err| err.to_string())?;
+    let mut accounts = vec![];
+    for (key, account) in keys.iter().zip(response.value) {
+        match account {
+            Some(account) => accounts.push(AccountFixture::new(
+                key,
+                account.lamports,
+                &account.data,
+                &account.owner,
+                account.executable,
+                account.rent_epoch,
+            )),
+            None if required.contains(key) => return Err(format!("account {} not found", key)),
+            None => continue,
+        }
+    }
+    let snapshot = PoolSnapshot {
+        program_id: config.program_id.to_string(),
+        swap: pool_key.to_string(),
+        slot: response.context.slot,
+        accounts,
+    };
+    let path = options.required("out")?;
+    let json = serde_json::to_string_pretty(&snapshot).map_err(|err| err.to_string())?;
+    fs::write(path, json).map_err(|err| format!("writing {}: {}", path, err))?;
+    println!(
+        "{} accounts of pool {} at slot {} saved to {}",
+        snapshot.accounts.len(),
+        pool_key,
+        snapshot.slot,
+        path
+    );
+    Ok(())
+}
+
+fn import_pool(options: &Options) -> Result<(), String> {
+    let path = options.required("snapshot")?;
+    let json = fs::read_to_string(path).map_err(|err| format!("reading {}: {}", path, err))?;
+    let snapshot: PoolSnapshot =
+        serde_json::from_str(&json).map_err(|err| format!("reading {}: {}", path, err))?;
+    snapshot
+        .pool()
+        .map_err(|err| format!("pool {}: {}", snapshot.swap, err))?;
+    let dir = Path::new(options.required("out")?);
+    fs::create_dir_all(dir).map_err(|err| format!("creating {}: {}", dir.display(), err))?;
+    // the pool's authority is derived from the program id, so the program
+    // has to be deployed under the same one
+    let mut args = vec![format!(
+        "--bpf-program {} <program .so>",
+        snapshot.program_id
+    )];
+    for account in &snapshot.accounts {
+        let file = dir.join(format!("{}.json", account.pubkey));
+        let json = serde_json::to_string_pretty(account).map_err(|err| err.to_string())?;
+        fs::write(&file, json).map_err(|err| format!("writing {}: {}", file.display(), err))?;
+        args.push(format!("--account {} {}", account.pubkey, file.display()));
+    }
+    println!(
+        "pool {} as of slot {}, start a validator with it:\n\nsolana-test-validator \\\n    {}",
+        snapshot.swap,
+        snapshot.slot,
+        args.join(" \\\n    ")
+    );
+    Ok(())
+}
+
+fn simulate_pool(options: &Options) -> Result<(), String> {
+    let (fees, swap_curve) = pool_parameters(options)?;
+    let path = options.required("prices")?;
+    let prices = fs::read_to_string(path).map_err(|err| format!("reading {}: {}", path, err))?;
+    // csv exports keep their timestamps in front, and a header
+    let prices: Vec<f64> = prices
+        .lines()
+        .filter_map(|line| line.rsplit(',').next()?.trim().parse().ok())
+        .collect();
+    if prices.is_empty() {
+        return Err(format!("no prices in {}", path));
+    }
+    let report = analysis::simulate(
+        &swap_curve,
+        &fees,
+        &FeeTiers::default(),
+        options.amount("amount-a")?,
+        options.amount("amount-b")?,
+        &prices,
+    )
+    .ok_or("the pool can't trade these reserves")?;
+    if options.has("json") {
+        let mut value = json!(report);
+        value["pnl"] = json!(report.pnl());
+        value["impermanent_loss"] = json!(report.impermanent_loss());
+        println!("{}", value);
+    } else {
+        println!("{}", report);
+    }
+    Ok(())
+}
+
+fn shadow_test(config: &Config, options: &Options) -> Result<(), String> {
+    let pool_key = options.pubkey("pool")?;
+    let pool = config.pool(&pool_key)?;
+    let limit = options.amount_or("limit", 100)? as usize;
+    let statuses = config
+        .rpc
+        .get_signatures_for_address_with_config(
+            &pool_key,
+            GetConfirmedSignaturesForAddress2Config {
+                limit: Some(limit),
+                ..GetConfirmedSignaturesForAddress2Config::default()
+            },
+        )
+        .map_err(|err| err.to_string())?;
+    // oldest first, the order the pool traded in
+    let mut observed = vec![];
+    for status in statuses.iter().rev().filter(|status| status.err.is_none()) {
+        let signature = Signature::from_str(&status.signature).map_err(|err| err.to_string())?;
+        let transaction = config
+            .rpc
+            .get_transaction_with_config(
+                &signature,
+                RpcTransactionConfig {
+                    encoding: Some(UiTransactionEncoding::Base64),
+                    commitment: None,
+                    max_supported_transaction_version: Some(0),
+                },
+            )
+            .map_err(|err| format!("transaction {}: {}", signature, err))?;
+        observed.extend(observed_swap(
+            &config.program_id,
+            &pool_key,
+            &pool,
+            &status.signature,
+            transaction,
+        ));
+    }
+
+    let report = shadow::replay(&candidate_pool(pool, options)?, observed);
+    if options.has("json") {
+        println!("{}", json!(report));
+    } else {
+        println!("{}", report);
+    }
+    Ok(())
+}
+
+/// The pool's swap in a transaction, read from its instruction and the token
+/// balances around it.  Only a transaction with a single swap of the pool,
+/// sent straight to the program, can be read: the balances of several swaps,
+/// or of one routed through another program, can't be told apart.
+fn observed_swap(
+    program_id: &Pubkey,
+    swap: &Pubkey,
+    pool: &SwapVersion,
+    signature: &str,
+    confirmed: EncodedConfirmedTransactionWithStatusMeta,
+) -> Option<ObservedSwap> {
+    let transaction = confirmed.transaction.transaction.decode()?;
+    let meta = confirmed.transaction.meta?;
+    // v0 transactions load the rest of their accounts from lookup tables
+    let mut keys = transaction.message.static_account_keys().to_vec();
+    if let Some(loaded) = Option::<UiLoadedAddresses>::from(meta.loaded_addresses) {
+        for key in loaded.writable.iter().chain(&loaded.readonly) {
+            keys.push(Pubkey::from_str(key).ok()?);
+        }
+    }
+
+    let mut swaps: Vec<_> = transaction
+        .message
+        .instructions()
+        .iter()
+        .filter(|instruction| {
+            keys.get(usize::from(instruction.program_id_index)) == Some(program_id)
+        })
+        .filter_map(|instruction| {
+            let accounts: Vec<Pubkey> = instruction
+                .accounts
+                .iter()
+                .filter_map(|&index| keys.get(usize::from(index)).copied())
+                .collect();
+            let (trade_direction, amount_in) =
+                shadow::decode_swap(swap, pool, &instruction.data, &accounts)?;
+            Some((trade_direction, amount_in, accounts))
+        })
+        .collect();
+    if swaps.len() != 1 {
+        return None;
+    }
+    let (trade_direction, amount_in, accounts) = swaps.remove(0);
+
+    let pre_balances = Option::<Vec<UiTransactionTokenBalance>>::from(meta.pre_token_balances)?;
+    let post_balances = Option::<Vec<UiTransactionTokenBalance>>::from(meta.post_token_balances)?;
+    let token_amount = |balances: &[UiTransactionTokenBalance], account: &Pubkey| {
+        let index = keys.iter().position(|key| key == account)?;
+        balances
+            .iter()
+            .find(|balance| usize::from(balance.account_index) == index)?
+            .ui_token_amount
+            .amount
+            .parse::<u64>()
+            .ok()
+    };
+    // accounts 4 and 5 are the vaults paid into and out of, 6 the trader's
+    // destination, which may only be created in the same transaction
+    let swap_source_amount = token_amount(&pre_balances, accounts.get(4)?)?;
+    let swap_destination_amount = token_amount(&pre_balances, accounts.get(5)?)?;
+    let (reserve_a, reserve_b) = match trade_direction {
+        TradeDirection::AtoB => (swap_source_amount, swap_destination_amount),
+        TradeDirection::BtoA => (swap_destination_amount, swap_source_amount),
+    };
+    let destination = accounts.get(6)?;
+    let amount_out = token_amount(&post_balances, destination)?
+        .checked_sub(token_amount(&pre_balances, destination).unwrap_or(0))?;
+
+    Some(ObservedSwap {
+        signature: signature.to_string(),
+        slot: confirmed.slot,
+        trade_direction,
+        amount_in,
+        reserve_a,
+        reserve_b,
+        amount_out,
+    })
+}
+
+/// The pool with the fees and price the options give, unchanged without them
+fn candidate_pool(mut pool: SwapVersion, options: &Options) -> Result<SwapVersion, String> {
+    let (fees, swap_curve) = match &mut pool {
+        SwapVersion::SwapV1(state) => (&mut state.fees, &mut state.swap_curve),
+        SwapVersion::SwapV2(state) => (&mut state.fees, &mut state.swap_curve),
+    };
+    if options.has("trade-fee-bps") {
+        fees.trade_fee_numerator = options.amount("trade-fee-bps")?;
+        fees.trade_fee_denominator = 10_000;
+    }
+    if options.has("owner-fee-bps") {
+        fees.owner_trade_fee_numerator = options.amount("owner-fee-bps")?;
+        fees.owner_trade_fee_denominator = 10_000;
+    }
+    if options.has("token-b-price") {
+        *swap_curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price: options.amount("token-b-price")?,
+                token_b_price_denominator: 0,
+            }),
+        };
+    }
+    Ok(pool)
+}
@@ -0,0 +1,273 @@
+//! Reference keeper for the pools' permissionless cranks, built with
+//! `--features keeper`
+//!
+//! Every `--interval` it asks each `Crank` which instructions are due and
+//! sends them one transaction each, retrying RPC failures with backoff.  The
+//! oracle peg of constant price pools is the only crank the program has so
+//! far; DCA tranches or fee buybacks would plug in as further `Crank`s.
+//!
+//! Transactions are paid for and signed by any `Signer`.  This binary reads
+//! a `--keypair` file, an operator wanting a remote or hardware signer only
+//! swaps what `run` hands to `Keeper`.  Pools taking bonded keepers only get
+//! their cranks signed as the keeper, so the signer must have bonded first,
+//! with the CLI's `bond-keeper`.
+
+use rebuild_token_swap::{
+    curve::{base::CurveType, constant_price::ConstantPriceCurve},
+    instruction,
+    state::{OracleConfig, SwapState, SwapVersion},
+};
+use solana_client::{
+    rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig},
+    rpc_filter::RpcFilterType,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::{read_keypair_file, Signature, Signer},
+    transaction::Transaction,
+};
+use std::{env, process, str::FromStr, thread, time::Duration};
+
+const USAGE: &str = "\
+usage: rebuild-balancer-keeper [--url <rpc>] [--keypair <path>] --program-id <pubkey>
+                               [--interval <seconds>] [--retries <n>] [--once]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let mut url = "http://localhost:8899".to_string();
+    let mut keypair = None;
+    let mut program_id = None;
+    let mut interval = 10;
+    let mut retries = 3;
+    let mut once = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().cloned().ok_or_else(|| USAGE.to_string());
+        match arg.as_str() {
+            "--url" => url = value()?,
+            "--keypair" => keypair = Some(value()?),
+            "--program-id" => {
+                let value = value()?;
+                program_id = Some(
+                    Pubkey::from_str(&value)
+                        .map_err(|err| format!("--program-id {}: {}", value, err))?,
+                );
+            }
+            "--interval" => interval = parse_number("interval", &value()?)?,
+            "--retries" => retries = parse_number("retries", &value()?)? as u32,
+            "--once" => once = true,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    let keypair = match keypair {
+        Some(path) => path,
+        None => format!(
+            "{}/.config/solana/id.json",
+            env::var("HOME").map_err(|_| "--keypair not given and HOME not set")?
+        ),
+    };
+    let signer =
+        read_keypair_file(&keypair).map_err(|err| format!("reading {}: {}", keypair, err))?;
+
+    let keeper = Keeper {
+        rpc: RpcClient::new_with_commitment(url, CommitmentConfig::confirmed()),
+        signer,
+        program_id: program_id.ok_or_else(|| USAGE.to_string())?,
+        retries,
+    };
+    let cranks: Vec<Box<dyn Crank>> = vec![Box::new(OraclePeg)];
+    loop {
+        for crank in &cranks {
+            if let Err(err) = keeper.run_crank(crank.as_ref()) {
+                eprintln!("{}: {}", crank.name(), err);
+            }
+        }
+        if once {
+            return Ok(());
+        }
+        thread::sleep(Duration::from_secs(interval));
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<u64, String> {
+    value
+        .parse()
+        .map_err(|err| format!("--{} {}: {}", name, value, err))
+}
+
+// ----------------------------------------------------------------------------- keeper
+
+/// A permissionless crank the keeper keeps turning
+trait Crank {
+    fn name(&self) -> &'static str;
+
+    /// Instructions due now, each with a description for the log, signed by
+    /// `keeper` on pools taking bonded keepers only
+    fn due(
+        &self,
+        rpc: &RpcClient,
+        program_id: &Pubkey,
+        keeper: &Pubkey,
+    ) -> Result<Vec<(String, Instruction)>, String>;
+}
+
+struct Keeper<S: Signer> {
+    rpc: RpcClient,
+    /// Pays for and signs every crank
+    signer: S,
+    program_id: Pubkey,
+    /// Attempts after the first, for each RPC round trip
+    retries: u32,
+}
+
+impl<S: Signer> Keeper<S> {
+    fn run_crank(&self, crank: &dyn Crank) -> Result<(), String> {
+        let keeper = self.signer.pubkey();
+        let due = self.with_retries(|| crank.due(&self.rpc, &self.program_id, &keeper))?;
+        for (description, instruction) in due {
+            // one failing instruction doesn't hold up the rest
+            match self.with_retries(|| self.send(&instruction)) {
+                Ok(signature) => println!("{} {}: {}", crank.name(), description, signature),
+                Err(err) => eprintln!("{} {}: {}", crank.name(), description, err),
+            }
+        }
+        Ok(())
+    }
+
+    fn send(&self, instruction: &Instruction) -> Result<Signature, String> {
+        let blockhash = self
+            .rpc
+            .get_latest_blockhash()
+            .map_err(|err| err.to_string())?;
+        let transaction = Transaction::new_signed_with_payer(
+            std::slice::from_ref(instruction),
+            Some(&self.signer.pubkey()),
+            &[&self.signer],
+            blockhash,
+        );
+        self.rpc
+            .send_and_confirm_transaction(&transaction)
+            .map_err(|err| err.to_string())
+    }
+
+    /// Retries with exponential backoff from half a second, RPC nodes drop
+    /// requests and blockhashes expire under load
+    fn with_retries<T>(&self, mut attempt: impl FnMut() -> Result<T, String>) -> Result<T, String> {
+        let mut backoff = Duration::from_millis(500);
+        let mut retries = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(err) if retries < self.retries => {
+                    eprintln!("retrying in {:?}: {}", backoff, err);
+                    thread::sleep(backoff);
+                    backoff *= 2;
+                    retries += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- cranks
+
+/// Steps each oracle pegged pool's price toward its oracle, skipping pools
+/// whose crank wouldn't move the price
+struct OraclePeg;
+
+impl Crank for OraclePeg {
+    fn name(&self) -> &'static str {
+        "oracle-peg"
+    }
+
+    fn due(
+        &self,
+        rpc: &RpcClient,
+        program_id: &Pubkey,
+        keeper: &Pubkey,
+    ) -> Result<Vec<(String, Instruction)>, String> {
+        // told apart from the program's other accounts by their size
+        let configs = rpc
+            .get_program_accounts_with_config(
+                program_id,
+                RpcProgramAccountsConfig {
+                    filters: Some(vec![RpcFilterType::DataSize(OracleConfig::LEN as u64)]),
+                    account_config: RpcAccountInfoConfig::default(),
+                    ..RpcProgramAccountsConfig::default()
+                },
+            )
+            .map_err(|err| err.to_string())?;
+        let slot = rpc.get_slot().map_err(|err| err.to_string())?;
+
+        let mut due = vec![];
+        for (_, account) in configs {
+            let config = match OracleConfig::unpack(&account.data) {
+                Ok(config) => config,
+                Err(_) => continue,
+            };
+            let accounts = rpc
+                .get_multiple_accounts(&[config.swap, config.oracle])
+                .map_err(|err| err.to_string())?;
+            let (pool, oracle) = match accounts.as_slice() {
+                [Some(pool), Some(oracle)] => (pool, oracle),
+                _ => {
+                    eprintln!("pool {}: pool or oracle account missing", config.swap);
+                    continue;
+                }
+            };
+            let pool = match SwapVersion::unpack_versioned(&pool.data) {
+                Ok(pool) if pool.swap_curve().curve_type == CurveType::ConstantPrice => pool,
+                _ => continue,
+            };
+            let mut calculator = [0u8; ConstantPriceCurve::LEN];
+            pool.swap_curve()
+                .calculator
+                .pack_into_slice(&mut calculator);
+            let current_price = ConstantPriceCurve::unpack_from_slice(&calculator)
+                .map_err(|err| err.to_string())?
+                .token_b_price;
+
+            let next_price = config
+                .read_price(&oracle.data)
+                .and_then(|oracle_price| config.next_price(current_price, oracle_price, slot));
+            match next_price {
+                Some(next_price) if next_price != current_price => {
+                    let crank =
+                        instruction::crank_oracle_price(program_id, &config.swap, &config.oracle)
+                            .map_err(|err| err.to_string())?;
+                    let crank = if pool.bonded_keepers_only() {
+                        instruction::with_keeper(crank, program_id, keeper)
+                            .map_err(|err| err.to_string())?
+                    } else {
+                        crank
+                    };
+                    due.push((
+                        format!(
+                            "pool {} ({}) price {} -> {}",
+                            config.swap,
+                            pool.swap_curve(),
+                            current_price,
+                            next_price
+                        ),
+                        crank,
+                    ))
+                }
+                Some(_) => {}
+                None => eprintln!("pool {}: no price in oracle {}", config.swap, config.oracle),
+            }
+        }
+        Ok(due)
+    }
+}
@@ -0,0 +1,76 @@
+//! Token quantities, typed by what they count.  Pool tokens and trading
+//! tokens (A or B) are both plain integers to the curves, mixing them up in
+//! the processor's fee paths is a type error instead.
+
+use crate::error::SwapError;
+use std::convert::TryFrom;
+
+/// Amount of token A or B
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct TokenAmount(u128);
+
+/// Amount of pool tokens
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PoolTokenAmount(u128);
+
+macro_rules! impl_amount {
+    ($amount:ident) => {
+        impl $amount {
+            pub const ZERO: Self = Self(0);
+
+            /// Wraps a curve result, in the curve's u128 precision
+            pub fn new(amount: u128) -> Self {
+                Self(amount)
+            }
+
+            /// The raw amount, to pass into the curve calculators
+            pub fn get(self) -> u128 {
+                self.0
+            }
+
+            /// The amount as token accounts hold it, fails when it doesn't fit
+            pub fn to_u64(self) -> Result<u64, SwapError> {
+                u64::try_from(self.0).map_err(|_| SwapError::ConversionFailure)
+            }
+
+            pub fn is_zero(self) -> bool {
+                self.0 == 0
+            }
+
+            pub fn checked_add(self, other: Self) -> Option<Self> {
+                self.0.checked_add(other.0).map(Self)
+            }
+
+            pub fn checked_sub(self, other: Self) -> Option<Self> {
+                self.0.checked_sub(other.0).map(Self)
+            }
+        }
+
+        impl From<u64> for $amount {
+            fn from(amount: u64) -> Self {
+                Self(amount.into())
+            }
+        }
+    };
+}
+
+impl_amount!(TokenAmount);
+impl_amount!(PoolTokenAmount);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn amounts_convert_back_to_u64_only_when_they_fit() {
+        assert_eq!(TokenAmount::from(u64::MAX).to_u64(), Ok(u64::MAX));
+        let too_big = PoolTokenAmount::from(u64::MAX)
+            .checked_add(PoolTokenAmount::from(1))
+            .unwrap();
+        assert_eq!(too_big.to_u64(), Err(SwapError::ConversionFailure));
+        assert_eq!(
+            PoolTokenAmount::ZERO.checked_sub(PoolTokenAmount::from(1)),
+            None
+        );
+    }
+}
@@ -0,0 +1,249 @@
+//! Associated token accounts for what swaps, deposits and withdrawals pay
+//! out, behind the `client` feature.
+//!
+//! An instruction paying into an account that isn't there fails with
+//! `AccountNotFound`, the failure integrators run into most, typically a
+//! wallet swapping into a token it has never held.  `with_output_accounts`
+//! finds the accounts the program's instructions pay out to and leads them
+//! with the associated token program's idempotent create for each that is
+//! the wallet's associated token account and isn't there yet.  Idempotent,
+//! so a create racing another, or an account `exists` wrongly reported
+//! missing, costs a little compute rather than the transaction.
+
+use crate::{instruction::SwapInstruction, state::SwapState};
+use solana_program::{
+    instruction::{AccountMeta, Instruction},
+    pubkey::Pubkey,
+    system_program,
+};
+
+solana_program::declare_id!("ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL");
+
+/// Tag of `AssociatedTokenAccountInstruction::CreateIdempotent`
+const CREATE_IDEMPOTENT: u8 = 1;
+
+/// Address of the wallet's associated token account of the mint
+pub fn associated_token_address(
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Pubkey {
+    let (address, _bump_seed) = Pubkey::find_program_address(
+        &[wallet.as_ref(), token_program_id.as_ref(), mint.as_ref()],
+        &id(),
+    );
+    address
+}
+
+/// Creates a 'create_idempotent' instruction of the associated token program,
+/// which does nothing if the account is already there.
+pub fn create_idempotent(
+    payer: &Pubkey,
+    wallet: &Pubkey,
+    mint: &Pubkey,
+    token_program_id: &Pubkey,
+) -> Instruction {
+    Instruction {
+        program_id: id(),
+        accounts: vec![
+            AccountMeta::new(*payer, true),
+            AccountMeta::new(
+                associated_token_address(wallet, mint, token_program_id),
+                false,
+            ),
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new_readonly(*mint, false),
+            AccountMeta::new_readonly(system_program::id(), false),
+            AccountMeta::new_readonly(*token_program_id, false),
+        ],
+        data: vec![CREATE_IDEMPOTENT],
+    }
+}
+
+/// Accounts an instruction of the program pays out to: a swap's destination,
+/// a deposit's pool token account, a withdrawal's token accounts.  None for
+/// other instructions, or other programs'.
+pub fn output_accounts(program_id: &Pubkey, instruction: &Instruction) -> Vec<Pubkey> {
+    if instruction.program_id != *program_id {
+        return vec![];
+    }
+    let indexes: &[usize] = match SwapInstruction::unpack(&instruction.data) {
+        Ok(SwapInstruction::Swap(_)) | Ok(SwapInstruction::RebalanceSwap(_)) => &[6],
+        Ok(SwapInstruction::DepositAllTokenTypes(_)) => &[8],
+        Ok(SwapInstruction::DepositAndLockLiquidity(_)) => &[13],
+        Ok(SwapInstruction::DepositSingleTokenTypeExactAmountIn(_))
+        | Ok(SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_)) => &[7],
+        Ok(SwapInstruction::WithdrawAllTokenTypes(_))
+        | Ok(SwapInstruction::WithdrawMax(_))
+        | Ok(SwapInstruction::EmergencyWithdraw(_)) => &[7, 8],
+        _ => &[],
+    };
+    indexes
+        .iter()
+        .filter_map(|index| instruction.accounts.get(*index))
+        .map(|account| account.pubkey)
+        .collect()
+}
+
+/// The instructions, led by an idempotent create of each of the wallet's
+/// associated token accounts they pay out to that `exists` says isn't there,
+/// once each.  Instructions are matched to their pool by the swap account
+/// they name first, among `pools`, for the mints an output can be of.
+/// Outputs that aren't the wallet's associated token accounts are left to
+/// the caller, as are instructions on pools not given.
+pub fn with_output_accounts(
+    program_id: &Pubkey,
+    payer: &Pubkey,
+    wallet: &Pubkey,
+    pools: &[(Pubkey, &dyn SwapState)],
+    instructions: Vec<Instruction>,
+    exists: impl Fn(&Pubkey) -> bool,
+) -> Vec<Instruction> {
+    let mut creates: Vec<Instruction> = vec![];
+    let mut created: Vec<Pubkey> = vec![];
+    for instruction in &instructions {
+        let pool = instruction.accounts.first().and_then(|swap| {
+            pools
+                .iter()
+                .find(|(key, _)| *key == swap.pubkey)
+                .map(|(_, pool)| *pool)
+        });
+        let pool = match pool {
+            Some(pool) => pool,
+            None => continue,
+        };
+        let token_program_id = pool.token_program_id();
+        for output in output_accounts(program_id, instruction) {
+            if created.contains(&output) || exists(&output) {
+                continue;
+            }
+            let mint = [pool.token_a_mint(), pool.token_b_mint(), pool.pool_mint()]
+                .iter()
+                .copied()
+                .find(|mint| associated_token_address(wallet, mint, token_program_id) == output);
+            if let Some(mint) = mint {
+                creates.push(create_idempotent(payer, wallet, mint, token_program_id));
+                created.push(output);
+            }
+        }
+    }
+    creates.extend(instructions);
+    creates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instruction::{self, Swap, WithdrawAllTokenTypes},
+        state::SwapV2,
+    };
+
+    #[test]
+    fn missing_outputs_are_created_once_ahead_of_the_instructions() {
+        let program_id = Pubkey::new_unique();
+        let (payer, wallet) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let swap_key = Pubkey::new_unique();
+        let pool = SwapV2 {
+            is_initialized: true,
+            token_program_id: spl_token::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+            pool_fee_account: Pubkey::new_unique(),
+            ..SwapV2::default()
+        };
+        let ata = |mint| associated_token_address(&wallet, mint, &spl_token::id());
+        let (ata_a, ata_b) = (ata(&pool.token_a_mint), ata(&pool.token_b_mint));
+        // the wallet holds token A and pool tokens, not token B
+        let pool_tokens = ata(&pool.pool_mint);
+        let exists = |key: &Pubkey| *key == ata_a || *key == pool_tokens;
+
+        let swap = instruction::swap(
+            &program_id,
+            &spl_token::id(),
+            &swap_key,
+            &Pubkey::new_unique(),
+            &wallet,
+            &ata_a,
+            &pool.token_a,
+            &pool.token_b,
+            &ata_b,
+            &pool.pool_mint,
+            &pool.pool_fee_account,
+            None,
+            None,
+            Swap {
+                amount_in: 1,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(output_accounts(&program_id, &swap), [ata_b]);
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id,
+            &spl_token::id(),
+            &swap_key,
+            &Pubkey::new_unique(),
+            &wallet,
+            &pool.pool_mint,
+            &pool.pool_fee_account,
+            &pool_tokens,
+            &pool.token_a,
+            &pool.token_b,
+            &ata_a,
+            &ata_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: 1,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(output_accounts(&program_id, &withdraw), [ata_a, ata_b]);
+        // another program's instruction, and a swap into someone else's account
+        let other =
+            Instruction::new_with_bytes(Pubkey::new_unique(), &swap.data, swap.accounts.clone());
+        assert!(output_accounts(&program_id, &other).is_empty());
+        let mut elsewhere = swap.clone();
+        elsewhere.accounts[6].pubkey = Pubkey::new_unique();
+
+        let pools: [(Pubkey, &dyn SwapState); 1] = [(swap_key, &pool)];
+        let bundled = with_output_accounts(
+            &program_id,
+            &payer,
+            &wallet,
+            &pools,
+            vec![
+                swap.clone(),
+                withdraw.clone(),
+                elsewhere.clone(),
+                other.clone(),
+            ],
+            exists,
+        );
+        let create_b = create_idempotent(&payer, &wallet, &pool.token_b_mint, &spl_token::id());
+        assert_eq!(
+            bundled,
+            [create_b.clone(), swap.clone(), withdraw, elsewhere, other]
+        );
+        // the encoding of AssociatedTokenAccountInstruction::CreateIdempotent
+        assert_eq!(create_b.data, [1]);
+        assert_eq!(create_b.accounts[1].pubkey, ata_b);
+
+        // nothing to create for a pool not given
+        let bundled = with_output_accounts(
+            &program_id,
+            &payer,
+            &wallet,
+            &[],
+            vec![swap.clone()],
+            exists,
+        );
+        assert_eq!(bundled, [swap]);
+    }
+}
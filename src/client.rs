@@ -0,0 +1,488 @@
+//! Human-readable formatting of pools for CLI tools and dashboards, behind
+//! the `client` feature.
+//!
+//! `Display` gives one line per pool, `30bps / ConstantProduct / ...`, and
+//! `serde::Serialize` gives the same state as JSON with pubkeys in base58.
+//! Reserves aren't part of the pool's state, `PoolSummary` adds them from the
+//! vault balances the caller fetched.
+
+use crate::curve::{
+    base::{CurveType, SwapCurve},
+    concentrated::ConcentratedCurve,
+    constant_price::ConstantPriceCurve,
+    constant_product::ConstantProductCurve,
+    fees::Fees,
+    offset::OffsetCurve,
+    stable::StableCurve,
+    weighted::WeightedCurve,
+};
+use crate::state::{Gate, SwapState, SwapV1, SwapV2, SwapVersion};
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use solana_program::{hash::Hash, program_pack::Pack, pubkey::Pubkey};
+use std::fmt;
+
+// ----------------------------------------------------------------------------- amounts
+
+/// Formats a raw token amount in whole tokens, shortened with K / M / B
+/// suffixes past a thousand, `1_234_567_000_000` at 6 decimals is `1.2M`
+pub fn format_amount(amount: u64, decimals: u8) -> String {
+    let tokens = amount as f64 / 10f64.powi(decimals.into());
+    let (value, suffix, precision) = if tokens >= 1e9 {
+        (tokens / 1e9, "B", 1)
+    } else if tokens >= 1e6 {
+        (tokens / 1e6, "M", 1)
+    } else if tokens >= 1e3 {
+        (tokens / 1e3, "K", 1)
+    } else {
+        (tokens, "", std::cmp::min(decimals, 4) as usize)
+    };
+    let value = format!("{:.*}", precision, value);
+    let value = if value.contains('.') {
+        value.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &value
+    };
+    format!("{}{}", value, suffix)
+}
+
+/// A fee fraction in basis points, fractional bps kept to two decimals
+fn bps(numerator: u64, denominator: u64) -> f64 {
+    if numerator == 0 || denominator == 0 {
+        return 0.0;
+    }
+    let bps = numerator as f64 * 10_000.0 / denominator as f64;
+    (bps * 100.0).round() / 100.0
+}
+
+// ----------------------------------------------------------------------------- fees and curves
+
+impl fmt::Display for Fees {
+    /// Total trade fee first, `30bps (25bps LP, 5bps owner), withdraw 10bps, host 20%`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let trade = bps(self.trade_fee_numerator, self.trade_fee_denominator);
+        let owner = bps(
+            self.owner_trade_fee_numerator,
+            self.owner_trade_fee_denominator,
+        );
+        write!(
+            f,
+            "{}bps ({}bps LP, {}bps owner), withdraw {}bps, host {}%",
+            trade + owner,
+            trade,
+            owner,
+            bps(
+                self.owner_withdraw_fee_numerator,
+                self.owner_withdraw_fee_denominator
+            ),
+            bps(self.host_fee_numerator, self.host_fee_denominator) / 100.0,
+        )
+    }
+}
+
+impl fmt::Display for CurveType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Display for ConstantProductCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "x * y = k")
+    }
+}
+
+impl fmt::Display for StableCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "A = {}", self.amp)
+    }
+}
+
+impl fmt::Display for OffsetCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "x * (y + {}) = k", self.token_b_offset)?;
+        if self.buy_only {
+            write!(f, ", buy only")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WeightedCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{} weights", self.token_a_weight, self.token_b_weight)
+    }
+}
+
+impl fmt::Display for ConcentratedCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}/{} to {}/{} A per B",
+            self.min_token_b_price,
+            self.min_token_b_price_denominator,
+            self.max_token_b_price,
+            self.max_token_b_price_denominator
+        )
+    }
+}
+
+impl fmt::Display for ConstantPriceCurve {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.token_b_price_denominator {
+            0 | 1 => write!(f, "1 B = {} A", self.token_b_price),
+            denominator => write!(f, "1 B = {}/{} A", self.token_b_price, denominator),
+        }
+    }
+}
+
+/// A swap curve's calculator, recovered from its packed parameters since
+/// `SwapCurve` only holds it as a trait object
+#[derive(serde::Serialize)]
+#[serde(tag = "curve_type")]
+enum Calculator {
+    ConstantProduct(ConstantProductCurve),
+    ConstantPrice(ConstantPriceCurve),
+    Stable(StableCurve),
+    Offset(OffsetCurve),
+    Weighted(WeightedCurve),
+    Concentrated(ConcentratedCurve),
+}
+
+impl Calculator {
+    fn of(swap_curve: &SwapCurve) -> Self {
+        let mut packed = [0u8; SwapCurve::LEN];
+        swap_curve.pack_into_slice(&mut packed);
+        let parameters = &packed[1..];
+        match swap_curve.curve_type {
+            CurveType::ConstantProduct => Self::ConstantProduct(ConstantProductCurve),
+            CurveType::ConstantPrice => Self::ConstantPrice(
+                ConstantPriceCurve::unpack_from_slice(parameters).unwrap_or_default(),
+            ),
+            CurveType::Stable => {
+                Self::Stable(StableCurve::unpack_from_slice(parameters).unwrap_or_default())
+            }
+            CurveType::Offset => {
+                Self::Offset(OffsetCurve::unpack_from_slice(parameters).unwrap_or_default())
+            }
+            CurveType::Weighted => {
+                Self::Weighted(WeightedCurve::unpack_from_slice(parameters).unwrap_or_default())
+            }
+            CurveType::Concentrated => Self::Concentrated(
+                ConcentratedCurve::unpack_from_slice(parameters).unwrap_or_default(),
+            ),
+        }
+    }
+}
+
+impl fmt::Display for SwapCurve {
+    /// The curve type, with its parameters when it has any
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match Calculator::of(self) {
+            Calculator::ConstantProduct(_) => write!(f, "{}", self.curve_type),
+            Calculator::ConstantPrice(curve) => write!(f, "{} ({})", self.curve_type, curve),
+            Calculator::Stable(curve) => write!(f, "{} ({})", self.curve_type, curve),
+            Calculator::Offset(curve) => write!(f, "{} ({})", self.curve_type, curve),
+            Calculator::Weighted(curve) => write!(f, "{} ({})", self.curve_type, curve),
+            Calculator::Concentrated(curve) => write!(f, "{} ({})", self.curve_type, curve),
+        }
+    }
+}
+
+impl Serialize for SwapCurve {
+    /// The curve type, flattened with its parameters
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        Calculator::of(self).serialize(serializer)
+    }
+}
+
+// ----------------------------------------------------------------------------- pools
+
+/// Serializes a pubkey as base58 rather than 32 numbers
+struct Base58<'a>(&'a Pubkey);
+
+impl Serialize for Base58<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self.0)
+    }
+}
+
+/// Total trade fee and curve, the part of a pool's line shared by all versions
+fn write_pool(f: &mut fmt::Formatter, pool: &dyn SwapState) -> fmt::Result {
+    let fees = pool.fees();
+    let trade_fee = bps(fees.trade_fee_numerator, fees.trade_fee_denominator)
+        + bps(
+            fees.owner_trade_fee_numerator,
+            fees.owner_trade_fee_denominator,
+        );
+    write!(f, "{}bps / {}", trade_fee, pool.swap_curve())
+}
+
+impl fmt::Display for SwapV1 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_pool(f, self)?;
+        write!(f, " / {} - {}", self.token_a_mint, self.token_b_mint)
+    }
+}
+
+impl fmt::Display for SwapV2 {
+    /// Like V1, followed by whatever sets the pool apart from a plain one
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_pool(f, self)?;
+        write!(f, " / {} - {}", self.token_a_mint, self.token_b_mint)?;
+        if self.emergency {
+            write!(f, " / emergency")?;
+        }
+        if self.paused {
+            write!(f, " / paused")?;
+        }
+        if self.max_slot_volume != 0 {
+            write!(f, " / max {} A per slot", self.max_slot_volume)?;
+        }
+        if self.exact_input {
+            write!(f, " / exact input")?;
+        }
+        if self.fee_holiday_end_slot != 0 {
+            write!(f, " / fee holiday until slot {}", self.fee_holiday_end_slot)?;
+        }
+        if self.stake_pool != Pubkey::default() {
+            write!(f, " / priced by stake pool {}", self.stake_pool)?;
+        }
+        match self.gate() {
+            Some(Gate::Token(mint)) => write!(f, " / gated to holders of {}", mint)?,
+            Some(Gate::Credential(issuer)) => write!(f, " / gated to credentials of {}", issuer)?,
+            None => {}
+        }
+        if self.single_withdraw_fee_to_lps {
+            write!(f, " / single-sided withdraw fees to LPs")?;
+        }
+        if self.sandwich_guard {
+            write!(f, " / guarded against sandwiches")?;
+        }
+        if self.owner_fee_fallback {
+            write!(f, " / owner fee fallback")?;
+        }
+        if self.nested_pool != Pubkey::default() {
+            write!(f, " / token B is the pool token of {}", self.nested_pool)?;
+        }
+        if self.bonded_keepers_only {
+            write!(f, " / bonded keepers only")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for SwapVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SwapVersion::SwapV1(swap) => swap.fmt(f),
+            SwapVersion::SwapV2(swap) => swap.fmt(f),
+        }
+    }
+}
+
+impl Serialize for SwapV1 {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("SwapV1", 13)?;
+        state.serialize_field("is_initialized", &self.is_initialized)?;
+        state.serialize_field("nonce", &self.nonce)?;
+        state.serialize_field("token_program_id", &Base58(&self.token_program_id))?;
+        state.serialize_field("token_a", &Base58(&self.token_a))?;
+        state.serialize_field("token_b", &Base58(&self.token_b))?;
+        state.serialize_field("pool_mint", &Base58(&self.pool_mint))?;
+        state.serialize_field("token_a_mint", &Base58(&self.token_a_mint))?;
+        state.serialize_field("token_b_mint", &Base58(&self.token_b_mint))?;
+        state.serialize_field("pool_fee_account", &Base58(&self.pool_fee_account))?;
+        state.serialize_field("fees", &self.fees)?;
+        state.serialize_field("swap_curve", &self.swap_curve)?;
+        state.serialize_field("capabilities", &self.swap_curve.calculator.capabilities())?;
+        state.serialize_field(
+            "allowed_directions",
+            &self.swap_curve.calculator.allowed_directions(),
+        )?;
+        state.end()
+    }
+}
+
+impl Serialize for SwapV2 {
+    /// Unused fee exempt slots are left out
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let fee_exempt: Vec<Base58> = self
+            .fee_exempt
+            .iter()
+            .filter(|account| **account != Pubkey::default())
+            .map(Base58)
+            .collect();
+        let mut state = serializer.serialize_struct("SwapV2", 60)?;
+        state.serialize_field("is_initialized", &self.is_initialized)?;
+        state.serialize_field("nonce", &self.nonce)?;
+        state.serialize_field("token_program_id", &Base58(&self.token_program_id))?;
+        state.serialize_field("token_a", &Base58(&self.token_a))?;
+        state.serialize_field("token_b", &Base58(&self.token_b))?;
+        state.serialize_field("pool_mint", &Base58(&self.pool_mint))?;
+        state.serialize_field("token_a_mint", &Base58(&self.token_a_mint))?;
+        state.serialize_field("token_b_mint", &Base58(&self.token_b_mint))?;
+        state.serialize_field("pool_fee_account", &Base58(&self.pool_fee_account))?;
+        state.serialize_field("fees", &self.fees)?;
+        state.serialize_field("swap_curve", &self.swap_curve)?;
+        state.serialize_field("capabilities", &self.swap_curve.calculator.capabilities())?;
+        state.serialize_field(
+            "allowed_directions",
+            &self.swap_curve.calculator.allowed_directions(),
+        )?;
+        state.serialize_field("pool_mint_decimals", &self.pool_mint_decimals)?;
+        state.serialize_field("max_slot_volume", &self.max_slot_volume)?;
+        state.serialize_field("last_slot", &self.last_slot)?;
+        state.serialize_field("slot_volume", &self.slot_volume)?;
+        state.serialize_field("locked_pool_tokens", &self.locked_pool_tokens)?;
+        state.serialize_field("zero_fee", &self.zero_fee)?;
+        state.serialize_field("rebalancer", &Base58(&self.rebalancer))?;
+        state.serialize_field("emergency", &self.emergency)?;
+        state.serialize_field("bonus_start_slot", &self.bonus_start_slot)?;
+        state.serialize_field("bonus_end_slot", &self.bonus_end_slot)?;
+        state.serialize_field("bonus_bps", &self.bonus_bps)?;
+        state.serialize_field("bonus_lockup_slots", &self.bonus_lockup_slots)?;
+        state.serialize_field("fee_exempt", &fee_exempt)?;
+        state.serialize_field(
+            "withdraw_fee_in_underlying",
+            &self.withdraw_fee_in_underlying,
+        )?;
+        state.serialize_field("withdraw_fee_vault_a", &Base58(&self.withdraw_fee_vault_a))?;
+        state.serialize_field("withdraw_fee_vault_b", &Base58(&self.withdraw_fee_vault_b))?;
+        state.serialize_field("fee_tiers", &self.fee_tiers)?;
+        state.serialize_field("exact_input", &self.exact_input)?;
+        state.serialize_field("fee_holiday_end_slot", &self.fee_holiday_end_slot)?;
+        state.serialize_field("stake_pool", &Base58(&self.stake_pool))?;
+        state.serialize_field("rate_update_slot", &self.rate_update_slot)?;
+        state.serialize_field("last_trade_slot", &self.last_trade_slot)?;
+        state.serialize_field(
+            "default_host_fee_account",
+            &Base58(&self.default_host_fee_account),
+        )?;
+        state.serialize_field("unclaimed_host_fees", &self.unclaimed_host_fees)?;
+        state.serialize_field("token_a_decimals", &self.token_a_decimals)?;
+        state.serialize_field("token_b_decimals", &self.token_b_decimals)?;
+        state.serialize_field("interactions", &self.interactions)?;
+        state.serialize_field("trade_fees_a", &self.trade_fees_a)?;
+        state.serialize_field("trade_fees_b", &self.trade_fees_b)?;
+        state.serialize_field("withdraw_fees", &self.withdraw_fees)?;
+        state.serialize_field(
+            "last_checkpoint_hash",
+            &Hash::new_from_array(self.last_checkpoint_hash).to_string(),
+        )?;
+        state.serialize_field("owner_fee_account_a", &Base58(&self.owner_fee_account_a))?;
+        state.serialize_field("owner_fee_account_b", &Base58(&self.owner_fee_account_b))?;
+        state.serialize_field("fee_conversion_pool", &Base58(&self.fee_conversion_pool))?;
+        state.serialize_field(
+            "fee_conversion_account",
+            &Base58(&self.fee_conversion_account),
+        )?;
+        state.serialize_field(
+            "fee_conversion_from_token_b",
+            &self.fee_conversion_from_token_b,
+        )?;
+        state.serialize_field(
+            "fee_conversion_max_slippage_bps",
+            &self.fee_conversion_max_slippage_bps,
+        )?;
+        state.serialize_field("withdraw_cooldown_slots", &self.withdraw_cooldown_slots)?;
+        state.serialize_field("gate", &Base58(&self.gate))?;
+        state.serialize_field("gate_credential", &self.gate_credential)?;
+        state.serialize_field(
+            "single_withdraw_fee_to_lps",
+            &self.single_withdraw_fee_to_lps,
+        )?;
+        state.serialize_field("sandwich_guard", &self.sandwich_guard)?;
+        state.serialize_field("owner_fee_fallback", &self.owner_fee_fallback)?;
+        state.serialize_field("nested_pool", &Base58(&self.nested_pool))?;
+        state.serialize_field("bonded_keepers_only", &self.bonded_keepers_only)?;
+        state.serialize_field("paused", &self.paused)?;
+        state.end()
+    }
+}
+
+impl Serialize for SwapVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            SwapVersion::SwapV1(swap) => swap.serialize(serializer),
+            SwapVersion::SwapV2(swap) => swap.serialize(serializer),
+        }
+    }
+}
+
+/// One line summary of a pool with its reserves, which live in the token A
+/// and B vaults rather than the pool's state
+pub struct PoolSummary<'a> {
+    pub pool: &'a dyn SwapState,
+    pub reserve_a: u64,
+    pub decimals_a: u8,
+    pub symbol_a: &'a str,
+    pub reserve_b: u64,
+    pub decimals_b: u8,
+    pub symbol_b: &'a str,
+}
+
+impl fmt::Display for PoolSummary<'_> {
+    /// `30bps / ConstantProduct / reserves 1.2M USDC, 5.4K SOL`
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_pool(f, self.pool)?;
+        write!(
+            f,
+            " / reserves {} {}, {} {}",
+            format_amount(self.reserve_a, self.decimals_a),
+            self.symbol_a,
+            format_amount(self.reserve_b, self.decimals_b),
+            self.symbol_b,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pools_print_readably() {
+        let pool = SwapV2 {
+            fees: Fees::standard_30bps(),
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve),
+            },
+            ..SwapV2::default()
+        };
+        let summary = PoolSummary {
+            pool: &pool,
+            reserve_a: 1_234_567_000_000,
+            decimals_a: 6,
+            symbol_a: "USDC",
+            reserve_b: 5_432_100_000_000,
+            decimals_b: 9,
+            symbol_b: "SOL",
+        };
+        assert_eq!(
+            summary.to_string(),
+            "30bps / ConstantProduct / reserves 1.2M USDC, 5.4K SOL"
+        );
+        assert_eq!(format_amount(1_500_000, 6), "1.5");
+        assert_eq!(format_amount(999, 0), "999");
+
+        let curve = SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price: 7,
+                token_b_price_denominator: 0,
+            }),
+        };
+        assert_eq!(curve.to_string(), "ConstantPrice (1 B = 7 A)");
+        assert_eq!(
+            serde_json::to_string(&curve).unwrap(),
+            r#"{"curve_type":"ConstantPrice","token_b_price":7,"token_b_price_denominator":0}"#
+        );
+        let json = serde_json::to_value(&pool).unwrap();
+        assert_eq!(json["token_a"], Pubkey::default().to_string());
+        assert_eq!(json["swap_curve"]["curve_type"], "ConstantProduct");
+        assert_eq!(json["capabilities"]["supports_deposits"], true);
+        assert_eq!(json["capabilities"]["needs_oracle"], false);
+        assert_eq!(json["allowed_directions"]["b_to_a"], true);
+    }
+}
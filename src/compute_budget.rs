@@ -0,0 +1,179 @@
+//! Compute unit limits for transactions calling the program.
+//!
+//! The runtime gives a transaction 200k compute units per instruction unless
+//! it asks for a limit.  Curves whose math iterates can run past that, and a
+//! limit far above what's used makes priority fees dearer, so clients ask for
+//! the units the program's instructions take on the pool's curve, from the
+//! table below.  The limit covers the whole transaction, other programs'
+//! instructions keep the default allowance.
+
+use crate::curve::base::CurveType;
+use crate::instruction::SwapInstruction;
+use solana_program::{instruction::Instruction, pubkey::Pubkey};
+
+solana_program::declare_id!("ComputeBudget111111111111111111111111111111");
+
+/// Units the runtime allows an instruction that doesn't ask for a limit
+pub const DEFAULT_INSTRUCTION_UNITS: u32 = 200_000;
+
+/// Most units a transaction can ask for
+pub const MAX_TRANSACTION_UNITS: u32 = 1_400_000;
+
+/// Tag of `ComputeBudgetInstruction::SetComputeUnitLimit`
+const SET_COMPUTE_UNIT_LIMIT: u8 = 2;
+
+/// Creates a 'set_compute_unit_limit' instruction of the compute budget program.
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![SET_COMPUTE_UNIT_LIMIT];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction {
+        program_id: id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Units an instruction of the program takes on a pool with the given curve,
+/// with headroom for the accounts it's given.  Keep in step with the curves'
+/// math: single-sided operations take a square root on the constant product
+/// curve, swaps leave room to convert owner fees through a second pool.
+pub fn instruction_units(instruction: &SwapInstruction, curve_type: CurveType) -> u32 {
+    match (instruction, curve_type) {
+        (SwapInstruction::Swap(_), CurveType::ConstantProduct)
+        | (SwapInstruction::RebalanceSwap(_), CurveType::ConstantProduct)
+        | (SwapInstruction::Swap(_), CurveType::Offset)
+        | (SwapInstruction::RebalanceSwap(_), CurveType::Offset) => 90_000,
+        (SwapInstruction::Swap(_), CurveType::ConstantPrice)
+        | (SwapInstruction::RebalanceSwap(_), CurveType::ConstantPrice) => 80_000,
+        // Newton's method for the invariant, then for the new reserve
+        (SwapInstruction::Swap(_), CurveType::Stable)
+        | (SwapInstruction::RebalanceSwap(_), CurveType::Stable) => 120_000,
+        // a fractional power, by its series
+        (SwapInstruction::Swap(_), CurveType::Weighted)
+        | (SwapInstruction::RebalanceSwap(_), CurveType::Weighted) => 130_000,
+        // square roots for the range and the liquidity
+        (SwapInstruction::Swap(_), CurveType::Concentrated)
+        | (SwapInstruction::RebalanceSwap(_), CurveType::Concentrated) => 140_000,
+        (SwapInstruction::SimulateSwap(_), _) => 50_000,
+        // the borrower's callback is on top
+        (SwapInstruction::FlashSwap(_), _) => 150_000,
+        (SwapInstruction::DepositSingleTokenTypeExactAmountIn(_), CurveType::ConstantProduct)
+        | (SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_), CurveType::ConstantProduct)
+        | (SwapInstruction::DepositSingleTokenTypeExactAmountIn(_), CurveType::Offset)
+        | (SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_), CurveType::Offset) => 110_000,
+        (SwapInstruction::DepositSingleTokenTypeExactAmountIn(_), CurveType::ConstantPrice)
+        | (SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_), CurveType::ConstantPrice) => {
+            90_000
+        }
+        (SwapInstruction::DepositSingleTokenTypeExactAmountIn(_), CurveType::Stable)
+        | (SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_), CurveType::Stable) => 130_000,
+        (SwapInstruction::DepositSingleTokenTypeExactAmountIn(_), CurveType::Weighted)
+        | (SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_), CurveType::Weighted) => {
+            140_000
+        }
+        // the liquidity before and after
+        (SwapInstruction::DepositSingleTokenTypeExactAmountIn(_), CurveType::Concentrated)
+        | (SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_), CurveType::Concentrated) => {
+            170_000
+        }
+        (SwapInstruction::DepositAllTokenTypes(_), _)
+        | (SwapInstruction::DepositAndLockLiquidity(_), _)
+        | (SwapInstruction::WithdrawAllTokenTypes(_), _)
+        | (SwapInstruction::WithdrawMax(_), _)
+        | (SwapInstruction::AdminDepositAllTokenTypes(_), _)
+        | (SwapInstruction::AdminWithdrawAllTokenTypes(_), _)
+        | (SwapInstruction::EmergencyWithdraw(_), _) => 70_000,
+        (SwapInstruction::Initialize(_), _) | (SwapInstruction::InitializeFromTemplate(_), _) => {
+            80_000
+        }
+        // reads and admin writes, no curve math
+        _ => 40_000,
+    }
+}
+
+/// The instructions of a transaction on a pool with the given curve, led by a
+/// limit covering them.  Instructions of other programs, or that don't
+/// unpack, keep the default allowance.
+pub fn with_compute_unit_limit(
+    program_id: &Pubkey,
+    curve_type: CurveType,
+    instructions: Vec<Instruction>,
+) -> Vec<Instruction> {
+    let units = instructions
+        .iter()
+        .map(|instruction| {
+            if instruction.program_id != *program_id {
+                return DEFAULT_INSTRUCTION_UNITS;
+            }
+            SwapInstruction::unpack(&instruction.data)
+                .map_or(DEFAULT_INSTRUCTION_UNITS, |swap_instruction| {
+                    instruction_units(&swap_instruction, curve_type)
+                })
+        })
+        .fold(0u32, u32::saturating_add)
+        .min(MAX_TRANSACTION_UNITS);
+    let mut limited = Vec::with_capacity(instructions.len() + 1);
+    limited.push(set_compute_unit_limit(units));
+    limited.extend(instructions);
+    limited
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{self, Swap};
+
+    #[test]
+    fn limits_cover_the_whole_transaction() {
+        let program_id = Pubkey::new_unique();
+        let keys: Vec<Pubkey> = (0..9).map(|_| Pubkey::new_unique()).collect();
+        let swap = instruction::swap(
+            &program_id,
+            &spl_token::id(),
+            &keys[0],
+            &keys[1],
+            &keys[2],
+            &keys[3],
+            &keys[4],
+            &keys[5],
+            &keys[6],
+            &keys[7],
+            &keys[8],
+            None,
+            None,
+            Swap {
+                amount_in: 1,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let other = Instruction {
+            program_id: Pubkey::new_unique(),
+            accounts: vec![],
+            data: vec![],
+        };
+
+        let limited = with_compute_unit_limit(
+            &program_id,
+            CurveType::ConstantProduct,
+            vec![other.clone(), swap.clone()],
+        );
+        assert_eq!(limited.len(), 3);
+        assert_eq!(limited[0].program_id, id());
+        assert_eq!(limited[1..], [other, swap]);
+        // the encoding of ComputeBudgetInstruction::SetComputeUnitLimit
+        let units = DEFAULT_INSTRUCTION_UNITS + 90_000;
+        let mut data = vec![2];
+        data.extend_from_slice(&units.to_le_bytes());
+        assert_eq!(limited[0].data, data);
+
+        let many = vec![Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![]); 10];
+        let limited = with_compute_unit_limit(&program_id, CurveType::ConstantPrice, many);
+        assert_eq!(
+            limited[0].data[1..],
+            MAX_TRANSACTION_UNITS.to_le_bytes()[..]
+        );
+    }
+}
@@ -0,0 +1,103 @@
+//! Seeds, sizes and amounts fixed by the program.
+//!
+//! Off-chain code deriving the program's addresses, or sizing its accounts
+//! and instructions, imports them from here rather than copying the values.
+//! Account sizes are the `Pack::LEN` of the account's type, the swap
+//! account's `SwapVersion::LATEST_LEN` counting its version byte.
+
+// ----------------------------------------------------------------------------- seeds
+
+/// Seed used to derive a referrer's program address, together with the wallet
+/// that owns the referrer's host fee accounts
+pub const REFERRER_SEED: &[u8] = b"referrer";
+
+/// Seed used to derive the deposits a referrer brought into a pool, together
+/// with the swap account and the referrer's wallet
+pub const REFERRAL_SEED: &[u8] = b"referral";
+
+/// Seed used to derive a pool's vesting program address, together with the
+/// swap account
+pub const VESTING_SEED: &[u8] = b"vesting";
+
+/// Seed used to derive a liquidity lock's program address, together with the
+/// swap account, the lock owner and the unlock slot
+pub const LOCK_SEED: &[u8] = b"lock";
+
+/// Seed used to derive an LP position's program address, together with the
+/// swap account and the pool token account holding the position
+pub const LP_POSITION_SEED: &[u8] = b"lp_position";
+
+/// Seed used to derive a pool's oracle config program address, together with
+/// the swap account
+pub const ORACLE_SEED: &[u8] = b"oracle";
+
+/// Seed used to derive a pool's metadata program address, together with the
+/// swap account
+pub const METADATA_SEED: &[u8] = b"metadata";
+
+/// Seed used to derive the program's config address
+pub const PROGRAM_CONFIG_SEED: &[u8] = b"config";
+
+/// Seed used to derive the program's keeper config address
+pub const KEEPER_CONFIG_SEED: &[u8] = b"keeper_config";
+
+/// Seed used to derive a pool template's program address, together with its
+/// index
+pub const POOL_TEMPLATE_SEED: &[u8] = b"pool_template";
+
+/// Seed used to derive a keeper's bond program address, together with the
+/// keeper's wallet
+pub const KEEPER_SEED: &[u8] = b"keeper";
+
+/// Seed used to derive a pool's liquidity snapshot program address, together
+/// with the swap account
+pub const SNAPSHOT_SEED: &[u8] = b"snapshot";
+
+/// Seed of a trader's credential, at the program address of the issuing
+/// program for `[CREDENTIAL_SEED, trader]`
+pub const CREDENTIAL_SEED: &[u8] = b"credential";
+
+/// Seed of a governance's native treasury, at the program address of the
+/// governance program for `[NATIVE_TREASURY_SEED, governance]`
+pub const NATIVE_TREASURY_SEED: &[u8] = b"native-treasury";
+
+// ----------------------------------------------------------------------------- amounts
+
+/// Pool tokens minted to the creator of a pool
+pub const INITIAL_SWAP_POOL_AMOUNT: u128 = 1_000_000_000;
+
+/// Tokens traded by a pool
+pub const TOKENS_IN_POOL: u128 = 2;
+
+/// Most decimals a trading token mint can have.  Curves work in raw units,
+/// so a pair of 0 and 18 decimal tokens prices raw units 18 orders of
+/// magnitude apart, and the math is checked down to that ratio
+pub const MAX_TOKEN_DECIMALS: u8 = 18;
+
+/// Swaps, deposits and withdrawals from one checkpoint to the next
+pub const CHECKPOINT_INTERVAL: u64 = 100;
+
+/// Most of a keeper's bond a single bad crank can cost it, in basis points
+pub const MAX_SLASH_BPS: u64 = 5_000;
+
+// ----------------------------------------------------------------------------- sizes
+
+/// Number of fee exempt accounts a pool can hold
+pub const MAX_FEE_EXEMPT: usize = 2;
+
+/// Number of size tiers above the base schedule, which is the first tier
+pub const MAX_FEE_TIERS: usize = 2;
+
+/// Most signers of an admin multisig, as for spl-token multisigs
+pub const MAX_ADMIN_SIGNERS: usize = 11;
+
+/// Most pools reported by a single `GetMultiplePoolStates`, bounded by the
+/// size of the transaction return data
+pub const MAX_POOL_STATE_VIEWS: usize = 7;
+
+/// Longest pool name, in bytes
+pub const MAX_NAME_LEN: usize = 32;
+/// Longest metadata URI, in bytes
+pub const MAX_URI_LEN: usize = 128;
+/// Number of tag bytes
+pub const TAGS_LEN: usize = 8;
@@ -8,6 +8,21 @@ pub struct SwapConstraints<'a> {
     //owner of the ctr
     pub valid_curve_types: &'a [CurveType],
     pub fees: &'a Fees, //fee schedule
+    // ceilings on the same fees, so a pool sharing the program id can't charge 99%
+    pub max_fees: &'a Fees,
+    // upper bound on the share of the owner fee a registered referrer can carry
+    pub max_referrer_host_fee_numerator: u64,
+    pub max_referrer_host_fee_denominator: u64,
+    // decimals every pool mint must be created with
+    pub pool_mint_decimals: u8,
+    // lets fully zero-fee pools through the fee floor, for protocol-internal routing pools
+    pub allow_zero_fee_pools: bool,
+    // refuse new pools while the program's upgrade authority isn't recorded in its config
+    pub require_documented_upgrade_authority: bool,
+    // longest fee holiday a new pool can start with, in slots
+    pub max_fee_holiday_slots: u64,
+    // refuse new pools not created from one of the program's pool templates
+    pub require_pool_template: bool,
 }
 
 const OWNER_KEY: &str = "AFe99p6byLxYfEV9E1nNumSeKdtgXm2HL5Gy5dN6icj9";
@@ -27,6 +42,37 @@ const FEES: &Fees = &Fees {
     host_fee_denominator: 100,
 };
 
+// the most a pool can charge, checked against the fraction whatever its denominator
+const MAX_FEES: &Fees = &Fees {
+    // at most 1% to the LPs
+    trade_fee_numerator: 100,
+    trade_fee_denominator: 10000,
+    // at most 0.5% to the owner, on trades and on withdrawals
+    owner_trade_fee_numerator: 50,
+    owner_trade_fee_denominator: 10000,
+    owner_withdraw_fee_numerator: 50,
+    owner_withdraw_fee_denominator: 10000,
+    // unused, the host fee is pinned by FEES
+    host_fee_numerator: 0,
+    host_fee_denominator: 0,
+};
+
+// referrers can negotiate up to 50% of the owner fee
+const MAX_REFERRER_HOST_FEE_NUMERATOR: u64 = 50;
+const MAX_REFERRER_HOST_FEE_DENOMINATOR: u64 = 100;
+
+// same as SOL, so pool token amounts read the same across all pools
+const POOL_MINT_DECIMALS: u8 = 9;
+
+const ALLOW_ZERO_FEE_POOLS: bool = false;
+
+const REQUIRE_DOCUMENTED_UPGRADE_AUTHORITY: bool = true;
+
+// about two weeks of 400ms slots
+const MAX_FEE_HOLIDAY_SLOTS: u64 = 3_024_000;
+
+const REQUIRE_POOL_TEMPLATE: bool = true;
+
 pub const SWAP_CONSTRAINTS: Option<SwapConstraints> = {
     #[cfg(feature = "production")]
     {
@@ -34,6 +80,14 @@ pub const SWAP_CONSTRAINTS: Option<SwapConstraints> = {
             owner_key: OWNER_KEY,
             valid_curve_types: VALID_CURVE_TYPES,
             fees: FEES,
+            max_fees: MAX_FEES,
+            max_referrer_host_fee_numerator: MAX_REFERRER_HOST_FEE_NUMERATOR,
+            max_referrer_host_fee_denominator: MAX_REFERRER_HOST_FEE_DENOMINATOR,
+            pool_mint_decimals: POOL_MINT_DECIMALS,
+            allow_zero_fee_pools: ALLOW_ZERO_FEE_POOLS,
+            require_documented_upgrade_authority: REQUIRE_DOCUMENTED_UPGRADE_AUTHORITY,
+            max_fee_holiday_slots: MAX_FEE_HOLIDAY_SLOTS,
+            require_pool_template: REQUIRE_POOL_TEMPLATE,
         })
     }
     #[cfg(not(feature = "production"))]
@@ -42,7 +96,14 @@ pub const SWAP_CONSTRAINTS: Option<SwapConstraints> = {
     }
 };
 
-const VALID_CURVE_TYPES: &[CurveType] = &[CurveType::ConstantProduct, CurveType::ConstantPrice];
+const VALID_CURVE_TYPES: &[CurveType] = &[
+    CurveType::ConstantProduct,
+    CurveType::ConstantPrice,
+    CurveType::Stable,
+    CurveType::Offset,
+    CurveType::Weighted,
+    CurveType::Concentrated,
+];
 
 impl<'a> SwapConstraints<'a> {
     pub fn validate_curve(&self, swap_curve: &SwapCurve) -> Result<(), ProgramError> {
@@ -58,6 +119,27 @@ impl<'a> SwapConstraints<'a> {
     }
 
     pub fn validate_fees(&self, fees: &Fees) -> Result<(), ProgramError> {
+        if self.allow_zero_fee_pools && fees.is_zero() {
+            return Ok(());
+        }
+        at_most(
+            fees.trade_fee_numerator,
+            fees.trade_fee_denominator,
+            self.max_fees.trade_fee_numerator,
+            self.max_fees.trade_fee_denominator,
+        )?;
+        at_most(
+            fees.owner_trade_fee_numerator,
+            fees.owner_trade_fee_denominator,
+            self.max_fees.owner_trade_fee_numerator,
+            self.max_fees.owner_trade_fee_denominator,
+        )?;
+        at_most(
+            fees.owner_withdraw_fee_numerator,
+            fees.owner_withdraw_fee_denominator,
+            self.max_fees.owner_withdraw_fee_numerator,
+            self.max_fees.owner_withdraw_fee_denominator,
+        )?;
         if fees.trade_fee_numerator >= self.fees.trade_fee_numerator
             && fees.trade_fee_denominator == self.fees.trade_fee_denominator
             && fees.owner_trade_fee_numerator >= self.fees.owner_trade_fee_numerator
@@ -72,4 +154,150 @@ impl<'a> SwapConstraints<'a> {
             Err(SwapError::InvalidFee.into())
         }
     }
+
+    pub fn validate_fee_holiday(&self, end_slot: u64, slot: u64) -> Result<(), ProgramError> {
+        if end_slot.saturating_sub(slot) <= self.max_fee_holiday_slots {
+            Ok(())
+        } else {
+            Err(SwapError::InvalidFee.into())
+        }
+    }
+
+    pub fn validate_pool_mint_decimals(&self, decimals: u8) -> Result<(), ProgramError> {
+        if decimals == self.pool_mint_decimals {
+            Ok(())
+        } else {
+            Err(SwapError::InvalidPoolMintDecimals.into())
+        }
+    }
+
+    pub fn validate_template(&self, from_template: bool) -> Result<(), ProgramError> {
+        if !self.require_pool_template || from_template {
+            Ok(())
+        } else {
+            Err(SwapError::InvalidPoolTemplate.into())
+        }
+    }
+
+    pub fn validate_referrer_fee(
+        &self,
+        host_fee_numerator: u64,
+        host_fee_denominator: u64,
+    ) -> Result<(), ProgramError> {
+        at_most(
+            host_fee_numerator,
+            host_fee_denominator,
+            self.max_referrer_host_fee_numerator,
+            self.max_referrer_host_fee_denominator,
+        )
+    }
+}
+
+// a referrer registers its own share of the owner fee, so without constraints
+// it's still held to the default ceiling
+pub fn validate_default_referrer_fee(
+    host_fee_numerator: u64,
+    host_fee_denominator: u64,
+) -> Result<(), ProgramError> {
+    at_most(
+        host_fee_numerator,
+        host_fee_denominator,
+        MAX_REFERRER_HOST_FEE_NUMERATOR,
+        MAX_REFERRER_HOST_FEE_DENOMINATOR,
+    )
+}
+
+// numerator / denominator <= max_numerator / max_denominator, compared by
+// cross-multiplying to avoid any truncation
+fn at_most(
+    numerator: u64,
+    denominator: u64,
+    max_numerator: u64,
+    max_denominator: u64,
+) -> Result<(), ProgramError> {
+    let share = u128::from(numerator) * u128::from(max_denominator);
+    let max_share = u128::from(max_numerator) * u128::from(denominator);
+    if share <= max_share {
+        Ok(())
+    } else {
+        Err(SwapError::InvalidFee.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn constraints() -> SwapConstraints<'static> {
+        SwapConstraints {
+            owner_key: OWNER_KEY,
+            valid_curve_types: VALID_CURVE_TYPES,
+            fees: FEES,
+            max_fees: MAX_FEES,
+            max_referrer_host_fee_numerator: MAX_REFERRER_HOST_FEE_NUMERATOR,
+            max_referrer_host_fee_denominator: MAX_REFERRER_HOST_FEE_DENOMINATOR,
+            pool_mint_decimals: POOL_MINT_DECIMALS,
+            allow_zero_fee_pools: ALLOW_ZERO_FEE_POOLS,
+            require_documented_upgrade_authority: REQUIRE_DOCUMENTED_UPGRADE_AUTHORITY,
+            max_fee_holiday_slots: MAX_FEE_HOLIDAY_SLOTS,
+            require_pool_template: REQUIRE_POOL_TEMPLATE,
+        }
+    }
+
+    fn fees(trade_fee_numerator: u64, owner_trade_fee_numerator: u64) -> Fees {
+        Fees {
+            trade_fee_numerator,
+            owner_trade_fee_numerator,
+            ..FEES.clone()
+        }
+    }
+
+    #[test]
+    fn fees_are_capped_at_the_ceilings() {
+        let constraints = constraints();
+        let invalid = Err(SwapError::InvalidFee.into());
+
+        // exactly 1% to the LPs and 0.5% to the owner is allowed, a unit more isn't
+        assert_eq!(constraints.validate_fees(&fees(100, 50)), Ok(()));
+        assert_eq!(constraints.validate_fees(&fees(101, 50)), invalid);
+        assert_eq!(constraints.validate_fees(&fees(100, 51)), invalid);
+        assert_eq!(constraints.validate_fees(&fees(9_900, 5)), invalid);
+
+        // the floors still hold
+        assert_eq!(constraints.validate_fees(&fees(0, 5)), Ok(()));
+        assert_eq!(constraints.validate_fees(&fees(0, 4)), invalid);
+    }
+
+    #[test]
+    fn pools_come_from_templates() {
+        let constraints = constraints();
+        assert_eq!(constraints.validate_template(true), Ok(()));
+        assert_eq!(
+            constraints.validate_template(false),
+            Err(SwapError::InvalidPoolTemplate.into())
+        );
+    }
+
+    #[test]
+    fn ceilings_compare_fractions() {
+        // 0.5% on withdrawals, whatever denominator it's written over
+        assert_eq!(at_most(50, 10_000, 50, 10_000), Ok(()));
+        assert_eq!(at_most(5, 1_000, 50, 10_000), Ok(()));
+        assert_eq!(
+            at_most(51, 10_000, 50, 10_000),
+            Err(SwapError::InvalidFee.into())
+        );
+        assert_eq!(
+            at_most(1, 199, 50, 10_000),
+            Err(SwapError::InvalidFee.into())
+        );
+        assert_eq!(
+            at_most(u64::MAX, u64::MAX, 50, 10_000),
+            Err(SwapError::InvalidFee.into())
+        );
+        // no fee is always under the ceiling
+        assert_eq!(at_most(0, 10_000, 0, 10_000), Ok(()));
+        assert_eq!(at_most(0, 0, 50, 10_000), Ok(()));
+        assert_eq!(at_most(7, 0, 50, 10_000), Err(SwapError::InvalidFee.into()));
+    }
 }
@@ -1,17 +1,27 @@
 use crate::curve::calculator::{CurveCalculator, SwapWithoutFeesResult, TradeDirection};
+use crate::curve::concentrated::ConcentratedCurve;
 use crate::curve::constant_price::ConstantPriceCurve;
 use crate::curve::constant_product::ConstantProductCurve;
-use crate::curve::fees::Fees;
+use crate::curve::fees::{FeeTiers, Fees, TradeFees};
+use crate::curve::offset::OffsetCurve;
+use crate::curve::stable::StableCurve;
+use crate::curve::weighted::WeightedCurve;
+use crate::error::SwapError;
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::program_error::ProgramError;
 use solana_program::program_pack::{Pack, Sealed};
 use std::convert::{TryFrom, TryInto};
 
 //list of possible curves
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CurveType {
     ConstantProduct,
     ConstantPrice,
+    Stable,
+    Offset,
+    Weighted,
+    Concentrated,
 }
 
 //chooses one curve and links the relevant Calculator trait implementation
@@ -29,14 +39,20 @@ impl SwapCurve {
         swap_destination_amount: u128,
         trade_direction: TradeDirection,
         fees: &Fees,
+        fee_tiers: &FeeTiers,
     ) -> Option<SwapResult> {
-        // calc the fees
-        let trade_fee = fees.trading_fee(source_amount)?; //to LPs
-        let owner_fee = fees.owner_trading_fee(source_amount)?; //to owner
-        let total_fees = trade_fee.checked_add(owner_fee)?; //to LPs + to owner
+        // large trades pay the fee of the highest size tier they reach
+        let fees = fee_tiers.fees_for(fees, source_amount);
 
-        // debit the fees out of the source token swap amount
-        let source_amount_less_fees = source_amount.checked_sub(total_fees)?;
+        // calc the fees and debit them out of the source token swap amount.
+        // Fees round up, so a dust trade can't dodge them, and one too small
+        // to pay them is refused here.
+        let TradeFees {
+            trade_fee,
+            owner_fee,
+            amount_less_fees: source_amount_less_fees,
+        } = fees.apply(source_amount)?;
+        let total_fees = trade_fee.checked_add(owner_fee)?; //to LPs + to owner
 
         // calculate the swap = CORE
         // the actual amounts that get swapped might be slightly different to requestd ones, due to how division works
@@ -66,6 +82,46 @@ impl SwapCurve {
         })
     }
 
+    /// Refuses a trade in a direction the curve doesn't take
+    pub fn check_direction(&self, trade_direction: TradeDirection) -> Result<(), SwapError> {
+        if self.calculator.allowed_directions().allows(trade_direction) {
+            Ok(())
+        } else {
+            Err(SwapError::DirectionDisabled)
+        }
+    }
+
+    /// Refuses a trade of `source_amount` the reserves can't fill, without
+    /// running the swap itself.  A trade too small to pay its fees passes,
+    /// for `swap` to refuse.
+    pub fn check_trade_size(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+        fee_tiers: &FeeTiers,
+    ) -> Result<(), SwapError> {
+        let source_amount_less_fees =
+            match fee_tiers.fees_for(fees, source_amount).apply(source_amount) {
+                Some(trade_fees) => trade_fees.amount_less_fees,
+                None => return Ok(()),
+            };
+        let max_swappable = self
+            .calculator
+            .max_swappable(swap_source_amount, swap_destination_amount, trade_direction)
+            .ok_or(SwapError::CalculationFailure)?;
+        if source_amount_less_fees > max_swappable {
+            return Err(match self.curve_type {
+                // the trade runs into the edge of the range before the reserves
+                CurveType::Concentrated => SwapError::PriceOutOfRange,
+                _ => SwapError::TradeTooLarge,
+            });
+        }
+        Ok(())
+    }
+
     // subtracts the fee then passes down to calculate the amount of POOL tokens to withdraw
     pub fn withdraw_single_token_type_exact_out(
         &self,
@@ -80,13 +136,43 @@ impl SwapCurve {
             return Some(0);
         }
 
-        // calc and sub the trading fee on half the tokens
+        // calc and add the trading fee on half the tokens, which in effect
+        // get swapped out of the other side.  the user pays it in pool tokens,
+        // the way a deposit pays it in the tokens deposited
         let half_source_amount = std::cmp::max(1, source_amount.checked_div(2)?);
         let trade_fee = fees.trading_fee(half_source_amount)?;
-        let source_amount = source_amount.checked_sub(trade_fee)?;
+        let source_amount = source_amount.checked_add(trade_fee)?;
+
+        self.calculator.withdraw_single_token_type_exact_out(
+            source_amount, //source tokens withdrawn PLUS FEE
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+        )
+    }
+
+    /// Pool tokens minted for an owner fee already swapped into the reserves,
+    /// what a single sided withdrawal of the fee would burn, less the trade
+    /// fee on the half of the fee that is in effect traded for the other side
+    pub fn owner_fee_to_pool_tokens(
+        &self,
+        owner_fee: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        fees: &Fees,
+    ) -> Option<u128> {
+        if owner_fee == 0 {
+            return Some(0);
+        }
+        let half_owner_fee = std::cmp::max(1, owner_fee.checked_div(2)?);
+        let trade_fee = fees.trading_fee(half_owner_fee)?;
+        let owner_fee = owner_fee.checked_sub(trade_fee)?;
 
         self.calculator.withdraw_single_token_type_exact_out(
-            source_amount, //source tokens that go to the OWNER as a fee for executing the trade LESS FEE
+            owner_fee,
             swap_token_a_amount,
             swap_token_b_amount,
             pool_supply,
@@ -176,8 +262,10 @@ impl TryFrom<u8> for CurveType {
         match curve_type {
             0 => Ok(CurveType::ConstantProduct),
             1 => Ok(CurveType::ConstantPrice),
-            // 2 => Ok(CurveType::Stable),
-            // 3 => Ok(CurveType::Offset),
+            2 => Ok(CurveType::Stable),
+            3 => Ok(CurveType::Offset),
+            4 => Ok(CurveType::Weighted),
+            5 => Ok(CurveType::Concentrated),
             _ => Err(ProgramError::InvalidAccountData),
         }
     }
@@ -208,8 +296,12 @@ impl Pack for SwapCurve {
                 CurveType::ConstantPrice => {
                     Box::new(ConstantPriceCurve::unpack_from_slice(calculator)?)
                 }
-                // CurveType::Stable => Box::new(StableCurve::unpack_from_slice(calculator)?),
-                // CurveType::Offset => Box::new(OffsetCurve::unpack_from_slice(calculator)?),
+                CurveType::Stable => Box::new(StableCurve::unpack_from_slice(calculator)?),
+                CurveType::Offset => Box::new(OffsetCurve::unpack_from_slice(calculator)?),
+                CurveType::Weighted => Box::new(WeightedCurve::unpack_from_slice(calculator)?),
+                CurveType::Concentrated => {
+                    Box::new(ConcentratedCurve::unpack_from_slice(calculator)?)
+                }
             },
         })
     }
@@ -240,3 +332,21 @@ pub struct SwapResult {
     /// Amount of source tokens going to owner
     pub owner_fee: u128,
 }
+
+impl SwapResult {
+    /// Trade and owner fee together, in source tokens
+    pub fn total_fees(&self) -> Option<u128> {
+        self.trade_fee.checked_add(self.owner_fee)
+    }
+
+    /// Takes all of `source_amount` for the same destination amount, the
+    /// source tokens the curve's rounding left unused going to the reserves
+    pub fn with_exact_input(self, source_amount: u128) -> Option<Self> {
+        let dust = source_amount.checked_sub(self.source_amount_swapped)?;
+        Some(Self {
+            new_swap_source_amount: self.new_swap_source_amount.checked_add(dust)?,
+            source_amount_swapped: source_amount,
+            ..self
+        })
+    }
+}
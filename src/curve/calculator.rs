@@ -1,10 +1,9 @@
+use crate::constants::INITIAL_SWAP_POOL_AMOUNT;
 use crate::error::SwapError;
+use borsh::{BorshDeserialize, BorshSerialize};
+use spl_math::precise_number::PreciseNumber;
 use std::fmt::Debug;
 
-pub const INITIAL_SWAP_POOL_AMOUNT: u128 = 1_000_000_000;
-
-pub const TOKENS_IN_POOL: u128 = 2;
-
 pub trait DynPack {
     /// Only required function is to pack given a trait object
     fn pack_into_slice(&self, dst: &mut [u8]);
@@ -18,10 +17,24 @@ pub fn map_zero_to_none(x: u128) -> Option<u128> {
     }
 }
 
+pub(crate) mod sealed {
+    /// Keeps `CurveCalculator` implementations inside this crate, the program
+    /// only unpacks the curve types it knows about
+    pub trait Sealed {}
+}
+
 // this will be implemented by each curve slightly differently
 // by using a trait we can sub any curve that we like
-pub trait CurveCalculator: Debug + DynPack {
-    fn validate(&self) -> Result<(), SwapError>;
+//
+// a new curve only has to implement the core math: `swap_without_fees`,
+// `withdraw_single_token_type_exact_out` and `pool_tokens_to_trading_tokens`.
+// everything else has a default, which curves override where they can do
+// better, e.g. the exact single sided deposit of the constant product curve
+pub trait CurveCalculator: Debug + DynPack + sealed::Sealed {
+    /// Checks the curve parameters, curves without any are always valid
+    fn validate(&self) -> Result<(), SwapError> {
+        Ok(())
+    }
 
     fn validate_supply(&self, token_a_amount: u64, token_b_amount: u64) -> Result<(), SwapError> {
         if token_a_amount == 0 {
@@ -43,6 +56,20 @@ pub trait CurveCalculator: Debug + DynPack {
         trade_direction: TradeDirection,
     ) -> Option<SwapWithoutFeesResult>;
 
+    /// Largest source amount, after fees, the curve can swap against the
+    /// reserves, so routers can size trades and the program can refuse
+    /// bigger ones before doing the math.  By default only the new source
+    /// reserve has to fit, curves that can run out of the destination token
+    /// bound it further.
+    fn max_swappable(
+        &self,
+        swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        u128::MAX.checked_sub(swap_source_amount)
+    }
+
     //essentially performs a withdrawal followed by a swap in order to balance the pool back
     fn withdraw_single_token_type_exact_out(
         &self,
@@ -61,6 +88,25 @@ pub trait CurveCalculator: Debug + DynPack {
         true
     }
 
+    /// What the curve supports, for routers and UIs to adapt to a pool
+    /// without knowing its curve type.  Defaults to a curve whose deposits,
+    /// when it allows them, can be single sided, priced from the reserves
+    /// alone.
+    fn capabilities(&self) -> CurveCapabilities {
+        CurveCapabilities {
+            supports_deposits: self.allows_deposits(),
+            supports_single_sided: self.allows_deposits(),
+            time_dependent: false,
+            needs_oracle: false,
+        }
+    }
+
+    /// Directions the curve takes trades in, both by default.  One way
+    /// curves, like a sale that can't be sold back into, close the other.
+    fn allowed_directions(&self) -> AllowedDirections {
+        AllowedDirections::BOTH
+    }
+
     /// Get the amount of trading tokens for the given amount of pool tokens,
     /// provided the total trading tokens and supply of pool tokens.
     fn pool_tokens_to_trading_tokens(
@@ -72,7 +118,23 @@ pub trait CurveCalculator: Debug + DynPack {
         round_direction: RoundDirection,
     ) -> Option<TradingTokenResult>;
 
-    // essentially performs a swap followed by a deposit
+    /// Marginal price before any trade, in destination tokens per source
+    /// token. Defaults to the ratio of the reserves, which is exact for the
+    /// constant product curve.
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        PreciseNumber::new(swap_destination_amount)?
+            .checked_div(&PreciseNumber::new(swap_source_amount)?)
+    }
+
+    // essentially performs a swap followed by a deposit, and by default does
+    // exactly that: swaps half the source amount, then deposits both sides at
+    // the reserves' new ratio.  rounds down, and is exact enough for deposits
+    // that are small compared to the pool
     fn deposit_single_token_type(
         &self,
         source_amount: u128,
@@ -80,10 +142,39 @@ pub trait CurveCalculator: Debug + DynPack {
         swap_token_b_amount: u128,
         pool_supply: u128,
         trade_direction: TradeDirection,
-    ) -> Option<u128>;
+    ) -> Option<u128> {
+        let (swap_source_amount, swap_destination_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_token_a_amount, swap_token_b_amount),
+            TradeDirection::BtoA => (swap_token_b_amount, swap_token_a_amount),
+        };
+        let swapped = self.swap_without_fees(
+            source_amount / 2,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+        )?;
+        let new_swap_source_amount =
+            swap_source_amount.checked_add(swapped.source_amount_swapped)?;
+        let new_swap_destination_amount =
+            swap_destination_amount.checked_sub(swapped.destination_amount_swapped)?;
+
+        let source_amount = source_amount.checked_sub(swapped.source_amount_swapped)?;
+        let pool_tokens_from_source = pool_supply
+            .checked_mul(source_amount)?
+            .checked_div(new_swap_source_amount)?;
+        let pool_tokens_from_destination = pool_supply
+            .checked_mul(swapped.destination_amount_swapped)?
+            .checked_div(new_swap_destination_amount)?;
+        Some(std::cmp::min(
+            pool_tokens_from_source,
+            pool_tokens_from_destination,
+        ))
+    }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[cfg_attr(all(feature = "python", not(target_arch = "bpf")), pyo3::pyclass)]
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq)]
 pub enum TradeDirection {
     AtoB,
     BtoA,
@@ -100,12 +191,101 @@ impl TradeDirection {
     }
 }
 
+#[cfg_attr(all(feature = "python", not(target_arch = "bpf")), pyo3::pyclass)]
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum RoundDirection {
     Floor,
     Ceiling,
 }
 
+impl RoundDirection {
+    /// Rounding the other way, for the parts of a calculation that have to
+    /// round against the result
+    pub fn opposite(&self) -> RoundDirection {
+        match self {
+            RoundDirection::Floor => RoundDirection::Ceiling,
+            RoundDirection::Ceiling => RoundDirection::Floor,
+        }
+    }
+}
+
+/// What a curve supports, packed into a single byte of flags in the views
+/// the program returns
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CurveCapabilities {
+    /// Takes deposits after initialization
+    pub supports_deposits: bool,
+    /// Takes single sided deposits and withdrawals
+    pub supports_single_sided: bool,
+    /// Prices move with the clock, not just with trades
+    pub time_dependent: bool,
+    /// Prices follow an oracle, which has to be cranked
+    pub needs_oracle: bool,
+}
+
+impl CurveCapabilities {
+    const SUPPORTS_DEPOSITS: u8 = 1;
+    const SUPPORTS_SINGLE_SIDED: u8 = 1 << 1;
+    const TIME_DEPENDENT: u8 = 1 << 2;
+    const NEEDS_ORACLE: u8 = 1 << 3;
+
+    /// Packs the capabilities into a byte of flags
+    pub fn to_byte(self) -> u8 {
+        [
+            (self.supports_deposits, Self::SUPPORTS_DEPOSITS),
+            (self.supports_single_sided, Self::SUPPORTS_SINGLE_SIDED),
+            (self.time_dependent, Self::TIME_DEPENDENT),
+            (self.needs_oracle, Self::NEEDS_ORACLE),
+        ]
+        .iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |byte, (_, flag)| byte | flag)
+    }
+
+    /// Unpacks a byte of flags, ignoring the ones it doesn't know
+    pub fn from_byte(byte: u8) -> Self {
+        Self {
+            supports_deposits: byte & Self::SUPPORTS_DEPOSITS != 0,
+            supports_single_sided: byte & Self::SUPPORTS_SINGLE_SIDED != 0,
+            time_dependent: byte & Self::TIME_DEPENDENT != 0,
+            needs_oracle: byte & Self::NEEDS_ORACLE != 0,
+        }
+    }
+}
+
+/// Trade directions a curve takes swaps in
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AllowedDirections {
+    /// Token A in, token B out
+    pub a_to_b: bool,
+    /// Token B in, token A out
+    pub b_to_a: bool,
+}
+
+impl AllowedDirections {
+    pub const BOTH: Self = Self {
+        a_to_b: true,
+        b_to_a: true,
+    };
+
+    /// Only trades in the given direction
+    pub fn only(trade_direction: TradeDirection) -> Self {
+        Self {
+            a_to_b: trade_direction == TradeDirection::AtoB,
+            b_to_a: trade_direction == TradeDirection::BtoA,
+        }
+    }
+
+    pub fn allows(&self, trade_direction: TradeDirection) -> bool {
+        match trade_direction {
+            TradeDirection::AtoB => self.a_to_b,
+            TradeDirection::BtoA => self.b_to_a,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct SwapWithoutFeesResult {
     pub source_amount_swapped: u128,
@@ -122,6 +302,8 @@ pub struct TradingTokenResult {
 #[cfg(test)]
 pub mod test {
     use super::*;
+    use crate::curve::base::SwapCurve;
+    use crate::curve::fees::Fees;
     use proptest::prelude::*;
     use spl_math::uint::U256;
 
@@ -220,4 +402,230 @@ pub mod test {
             difference
         );
     }
+
+    /// Fee schedules the round trip is checked under: none, the standard
+    /// trade fee, and the standard trade fee with a withdraw fee on top
+    pub fn round_trip_fee_settings() -> [Fees; 3] {
+        [
+            Fees::default(),
+            Fees::standard_30bps(),
+            Fees {
+                owner_withdraw_fee_numerator: 1,
+                owner_withdraw_fee_denominator: 600,
+                ..Fees::standard_30bps()
+            },
+        ]
+    }
+
+    /// Test function to check that depositing a single token, then
+    /// withdrawing as much of the same token as the pool tokens pay for,
+    /// never gives back more than was deposited.
+    /// The deposit and the withdrawal each charge the trade fee on the half
+    /// they swap, and the withdrawal charges the withdraw fee, so the round
+    /// trip loses at most those fees on the amount plus the curve's epsilon,
+    /// and what a pool token is worth for each of the two roundings to whole
+    /// pool tokens.
+    #[allow(clippy::too_many_arguments)]
+    pub fn check_single_sided_round_trip(
+        swap_curve: &SwapCurve,
+        fees: &Fees,
+        source_token_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+        pool_supply: u128,
+        epsilon_in_basis_points: u128,
+    ) {
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (swap_source_amount, swap_destination_amount),
+            TradeDirection::BtoA => (swap_destination_amount, swap_source_amount),
+        };
+        let pool_tokens = swap_curve
+            .deposit_single_token_type(
+                source_token_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                pool_supply,
+                trade_direction,
+                fees,
+            )
+            .unwrap();
+
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_token_a_amount + source_token_amount,
+                swap_token_b_amount,
+            ),
+            TradeDirection::BtoA => (
+                swap_token_a_amount,
+                swap_token_b_amount + source_token_amount,
+            ),
+        };
+        let pool_supply = pool_supply + pool_tokens;
+        // pool tokens burned plus the withdraw fee, none when the reserves
+        // can't pay the amount out
+        let cost = |amount: u128| {
+            swap_curve
+                .withdraw_single_token_type_exact_out(
+                    amount,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    pool_supply,
+                    trade_direction,
+                    fees,
+                )
+                .and_then(|burn| burn.checked_add(fees.owner_withdraw_fee(burn)?))
+        };
+        // the most the pool tokens withdraw, searching up to the whole reserve
+        let (mut low, mut high) = (0, swap_source_amount + source_token_amount);
+        while low < high {
+            let mid = low + (high - low) / 2 + 1;
+            match cost(mid) {
+                Some(cost) if cost <= pool_tokens => low = mid,
+                _ => high = mid - 1,
+            }
+        }
+        let withdrawn = low;
+
+        assert!(
+            withdrawn <= source_token_amount,
+            "deposited {}, withdrew {}",
+            source_token_amount,
+            withdrawn
+        );
+        // a deposit too small for a single pool token is lost to rounding
+        let pool_token_worth = match pool_tokens {
+            0 => source_token_amount,
+            _ => source_token_amount / pool_tokens + 1,
+        };
+        let epsilon = fees.trading_fee(source_token_amount).unwrap()
+            + fees.owner_withdraw_fee(source_token_amount).unwrap()
+            + source_token_amount * epsilon_in_basis_points / 10000
+            + 2 * pool_token_worth;
+        assert!(
+            source_token_amount - withdrawn <= epsilon,
+            "round trip expected to lose at most {}, actually {}",
+            epsilon,
+            source_token_amount - withdrawn
+        );
+    }
+
+    /// Implements only the required methods, to exercise the defaults
+    #[derive(Debug)]
+    struct CoreOnlyCurve;
+
+    impl sealed::Sealed for CoreOnlyCurve {}
+
+    impl DynPack for CoreOnlyCurve {
+        fn pack_into_slice(&self, _output: &mut [u8]) {}
+    }
+
+    impl CurveCalculator for CoreOnlyCurve {
+        fn swap_without_fees(
+            &self,
+            source_amount: u128,
+            swap_source_amount: u128,
+            swap_destination_amount: u128,
+            _trade_direction: TradeDirection,
+        ) -> Option<SwapWithoutFeesResult> {
+            crate::curve::constant_product::swap(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+            )
+        }
+
+        fn withdraw_single_token_type_exact_out(
+            &self,
+            source_amount: u128,
+            swap_token_a_amount: u128,
+            swap_token_b_amount: u128,
+            pool_supply: u128,
+            trade_direction: TradeDirection,
+        ) -> Option<u128> {
+            crate::curve::constant_product::withdraw_single_token_type_exact_out(
+                source_amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                pool_supply,
+                trade_direction,
+                RoundDirection::Ceiling,
+            )
+        }
+
+        fn pool_tokens_to_trading_tokens(
+            &self,
+            pool_tokens: u128,
+            pool_token_supply: u128,
+            swap_token_a_amount: u128,
+            swap_token_b_amount: u128,
+            round_direction: RoundDirection,
+        ) -> Option<TradingTokenResult> {
+            crate::curve::constant_product::pool_tokens_to_trading_tokens(
+                pool_tokens,
+                pool_token_supply,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                round_direction,
+            )
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn default_deposit_meets_conversion_guarantee(
+            // the default deposit is only that close to a swap + deposit when
+            // it's at most ~1% of the pool, as it is for the reserves here
+            source_token_amount in 1_000..1_000_000u64,
+            swap_source_amount in 100_000_000..u64::MAX,
+            swap_destination_amount in 100_000_000..u64::MAX,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            // and when the half it swaps is worth enough destination tokens
+            // that truncating one is well within the guarantee
+            prop_assume!(
+                source_token_amount as u128 * swap_destination_amount as u128
+                    / swap_source_amount as u128
+                    >= 10_000
+            );
+            let curve = CoreOnlyCurve;
+            assert_eq!(curve.validate(), Ok(()));
+            assert!(curve.allows_deposits());
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                check_deposit_token_conversion(
+                    &curve,
+                    source_token_amount as u128,
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128,
+                    trade_direction,
+                    pool_supply,
+                    CONVERSION_BASIS_POINTS_GUARANTEE,
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn capabilities_pack_into_a_byte() {
+        let capabilities = CoreOnlyCurve.capabilities();
+        assert!(capabilities.supports_deposits && capabilities.supports_single_sided);
+        assert!(!capabilities.time_dependent && !capabilities.needs_oracle);
+        assert_eq!(capabilities.to_byte(), 0b0011);
+        assert_eq!(CurveCapabilities::from_byte(0b0011), capabilities);
+
+        let all = CurveCapabilities::from_byte(u8::MAX);
+        assert_eq!(all.to_byte(), 0b1111);
+        assert!(all.time_dependent && all.needs_oracle);
+    }
+
+    #[test]
+    fn curves_trade_both_ways_by_default() {
+        let allowed = CoreOnlyCurve.allowed_directions();
+        assert_eq!(allowed, AllowedDirections::BOTH);
+        assert!(allowed.allows(TradeDirection::AtoB) && allowed.allows(TradeDirection::BtoA));
+
+        let one_way = AllowedDirections::only(TradeDirection::BtoA);
+        assert!(!one_way.allows(TradeDirection::AtoB));
+        assert!(one_way.allows(TradeDirection::BtoA));
+    }
 }
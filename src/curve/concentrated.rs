@@ -0,0 +1,686 @@
+//! Concentrated liquidity curve, a single position between two prices
+//!
+//! The pool's liquidity `L` is only active between a lower and an upper price
+//! of token B in token A.  In between it trades as the constant product of
+//! virtual reserves, `(a + L * √p_min) * (b + L / √p_max) = L^2`: the real
+//! reserves plus what a constant product pool would hold outside the range.
+//! At the lower price the pool holds token B alone, at the upper price token
+//! A alone, and a trade pushing the price past either is refused with
+//! `PriceOutOfRange`.  A narrow range around the market price gives LPs the
+//! depth of a much larger constant product pool.
+//!
+//! `L` grows in proportion to the reserves, so pool tokens are shares of it,
+//! and single-sided deposits and withdrawals mint and burn by how much they
+//! move it.
+use {
+    crate::{
+        curve::{
+            calculator::{
+                map_zero_to_none, sealed, CurveCalculator, DynPack, RoundDirection,
+                SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+            },
+            constant_product::pool_tokens_to_trading_tokens,
+        },
+        error::SwapError,
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+    },
+    spl_math::{precise_number::PreciseNumber, uint::U256},
+};
+
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConcentratedCurve {
+    /// Lowest price the liquidity is active at, in token A per token B
+    pub min_token_b_price: u64,
+    /// Denominator of `min_token_b_price`
+    pub min_token_b_price_denominator: u64,
+    /// Highest price the liquidity is active at, in token A per token B
+    pub max_token_b_price: u64,
+    /// Denominator of `max_token_b_price`
+    pub max_token_b_price_denominator: u64,
+}
+
+/// `√(numerator / denominator)`
+fn sqrt_price(numerator: u64, denominator: u64) -> Option<PreciseNumber> {
+    PreciseNumber::new(u128::from(numerator))?
+        .checked_div(&PreciseNumber::new(u128::from(denominator))?)?
+        .sqrt()
+}
+
+/// `|L' - L| / L` is only good to about a billionth, the precision of the
+/// square root in the liquidity
+const LIQUIDITY_TOLERANCE: u128 = 1_000_000_000;
+
+/// The square roots of the range's bounds, and `q = 1 - √(p_min / p_max)`
+struct Range {
+    sqrt_min: PreciseNumber,
+    sqrt_max: PreciseNumber,
+    spread: PreciseNumber,
+}
+
+impl Range {
+    /// `β = a / √p_max + b * √p_min`
+    fn linear_term(&self, token_a_amount: u128, token_b_amount: u128) -> Option<PreciseNumber> {
+        PreciseNumber::new(token_a_amount)?
+            .checked_div(&self.sqrt_max)?
+            .checked_add(&PreciseNumber::new(token_b_amount)?.checked_mul(&self.sqrt_min)?)
+    }
+
+    /// The liquidity of the reserves, the root of `q L^2 - β L - a b = 0`,
+    /// taken as `β (1 + √(1 + 4 q a b / β^2)) / 2q` for the square root to
+    /// stay small
+    fn liquidity(&self, token_a_amount: u128, token_b_amount: u128) -> Option<PreciseNumber> {
+        let linear = self.linear_term(token_a_amount, token_b_amount)?;
+        if linear == PreciseNumber::new(0)? {
+            return Some(linear);
+        }
+        let one = PreciseNumber::new(1)?;
+        let discriminant = PreciseNumber::new(4)?
+            .checked_mul(&self.spread)?
+            .checked_mul(&PreciseNumber::new(token_a_amount)?.checked_div(&linear)?)?
+            .checked_mul(&PreciseNumber::new(token_b_amount)?.checked_div(&linear)?)?
+            .checked_add(&one)?;
+        linear
+            .checked_mul(&one.checked_add(&discriminant.sqrt()?)?)?
+            .checked_div(&PreciseNumber::new(2)?.checked_mul(&self.spread)?)
+    }
+
+    /// The real reserves of token A and B plus the range's offsets,
+    /// `L * √p_min` and `L / √p_max`
+    fn virtual_reserves(&self, token_a_amount: u128, token_b_amount: u128) -> Option<(u128, u128)> {
+        let liquidity = self.liquidity(token_a_amount, token_b_amount)?;
+        let token_a_offset = liquidity
+            .checked_mul(&self.sqrt_min)?
+            .floor()?
+            .to_imprecise()?;
+        let token_b_offset = liquidity
+            .checked_div(&self.sqrt_max)?
+            .floor()?
+            .to_imprecise()?;
+        Some((
+            token_a_amount.checked_add(token_a_offset)?,
+            token_b_amount.checked_add(token_b_offset)?,
+        ))
+    }
+
+    /// Pool tokens for the share of the liquidity `amount` of one token adds
+    /// or takes out, `supply * |L' - L| / L`.  Subtracting the quadratics of
+    /// both reserves gives `(L' - L) (q (L + L') - β) = Δa (L' / √p_max + b)`
+    /// for token A, and `Δb (L' √p_min + a)` for token B, free of the
+    /// rounding `L' - L` would carry over from both.  The supply multiplies
+    /// in before dividing, to keep the precision of every pool token, and the
+    /// result gives up the liquidity's tolerance to the pool whichever way it
+    /// rounds.
+    fn pool_tokens_moved(
+        &self,
+        pool_supply: u128,
+        amount: u128,
+        (token_a_amount, token_b_amount): (u128, u128),
+        (new_token_a_amount, new_token_b_amount): (u128, u128),
+        trade_direction: TradeDirection,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        let liquidity = self.liquidity(token_a_amount, token_b_amount)?;
+        let new_liquidity = self.liquidity(new_token_a_amount, new_token_b_amount)?;
+        let weight = match trade_direction {
+            TradeDirection::AtoB => new_liquidity
+                .checked_div(&self.sqrt_max)?
+                .checked_add(&PreciseNumber::new(token_b_amount)?)?,
+            TradeDirection::BtoA => new_liquidity
+                .checked_mul(&self.sqrt_min)?
+                .checked_add(&PreciseNumber::new(token_a_amount)?)?,
+        };
+        let slope = liquidity
+            .checked_add(&new_liquidity)?
+            .checked_mul(&self.spread)?
+            .checked_sub(&self.linear_term(token_a_amount, token_b_amount)?)?;
+        let pool_tokens = PreciseNumber::new(amount)?
+            .checked_mul(&weight)?
+            .checked_mul(&PreciseNumber::new(pool_supply)?)?
+            .checked_div(&slope)?
+            .checked_div(&liquidity)?;
+        let margin = pool_tokens.checked_div(&PreciseNumber::new(LIQUIDITY_TOLERANCE)?)?;
+        match round_direction {
+            RoundDirection::Floor => pool_tokens.checked_sub(&margin)?.floor()?,
+            RoundDirection::Ceiling => pool_tokens.checked_add(&margin)?.ceiling()?,
+        }
+        .to_imprecise()
+    }
+}
+
+impl ConcentratedCurve {
+    fn range(&self) -> Option<Range> {
+        let sqrt_min = sqrt_price(self.min_token_b_price, self.min_token_b_price_denominator)?;
+        let sqrt_max = sqrt_price(self.max_token_b_price, self.max_token_b_price_denominator)?;
+        let spread = PreciseNumber::new(1)?.checked_sub(&sqrt_min.checked_div(&sqrt_max)?)?;
+        Some(Range {
+            sqrt_min,
+            sqrt_max,
+            spread,
+        })
+    }
+
+    /// The source and destination reserves the constant product runs on
+    fn virtual_reserves(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<(u128, u128)> {
+        let range = self.range()?;
+        match trade_direction {
+            TradeDirection::AtoB => {
+                range.virtual_reserves(swap_source_amount, swap_destination_amount)
+            }
+            TradeDirection::BtoA => range
+                .virtual_reserves(swap_destination_amount, swap_source_amount)
+                .map(|(token_a_amount, token_b_amount)| (token_b_amount, token_a_amount)),
+        }
+    }
+}
+
+impl sealed::Sealed for ConcentratedCurve {}
+
+impl CurveCalculator for ConcentratedCurve {
+    fn validate(&self) -> Result<(), SwapError> {
+        let zero = PreciseNumber::new(0).ok_or(SwapError::InvalidCurve)?;
+        match self.range() {
+            Some(range)
+                if range.sqrt_min.greater_than(&zero) && range.spread.greater_than(&zero) =>
+            {
+                Ok(())
+            }
+            _ => Err(SwapError::InvalidCurve),
+        }
+    }
+
+    /// A pool opening at either end of its range holds one token alone
+    fn validate_supply(&self, token_a_amount: u64, token_b_amount: u64) -> Result<(), SwapError> {
+        if token_a_amount == 0 && token_b_amount == 0 {
+            return Err(SwapError::EmptySupply);
+        }
+        Ok(())
+    }
+
+    /// Constant product of the virtual reserves, paying out of the real one
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let (virtual_source_amount, virtual_destination_amount) =
+            self.virtual_reserves(swap_source_amount, swap_destination_amount, trade_direction)?;
+        let invariant = U256::from(virtual_source_amount)
+            .checked_mul(U256::from(virtual_destination_amount))?;
+        let new_virtual_source_amount =
+            U256::from(virtual_source_amount.checked_add(source_amount)?);
+        // the destination reserve left rounds up, against the trader
+        let new_virtual_destination_amount = invariant
+            .checked_add(new_virtual_source_amount.checked_sub(U256::one())?)?
+            .checked_div(new_virtual_source_amount)?;
+        let destination_amount_swapped =
+            U256::from(virtual_destination_amount).checked_sub(new_virtual_destination_amount)?;
+        if destination_amount_swapped > U256::from(swap_destination_amount) {
+            return None;
+        }
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped: map_zero_to_none(destination_amount_swapped.as_u128())?,
+        })
+    }
+
+    /// Up to the edge of the range, where the destination's real reserve
+    /// runs out: `ceil(k / (s + in)) >= offset`
+    fn max_swappable(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let unbounded = u128::MAX.checked_sub(swap_source_amount);
+        let (virtual_source_amount, virtual_destination_amount) =
+            self.virtual_reserves(swap_source_amount, swap_destination_amount, trade_direction)?;
+        let destination_offset = virtual_destination_amount.checked_sub(swap_destination_amount)?;
+        if destination_offset <= 1 {
+            return unbounded;
+        }
+        let invariant = U256::from(virtual_source_amount)
+            .checked_mul(U256::from(virtual_destination_amount))?;
+        let max = invariant
+            .checked_sub(U256::one())?
+            .checked_div(U256::from(destination_offset - 1))?
+            .saturating_sub(U256::from(virtual_source_amount));
+        if max > U256::from(u128::MAX) {
+            unbounded
+        } else {
+            Some(max.as_u128())
+        }
+    }
+
+    /// `pool = supply * (L' - L) / L`
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let new_reserves = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_token_a_amount.checked_add(source_amount)?,
+                swap_token_b_amount,
+            ),
+            TradeDirection::BtoA => (
+                swap_token_a_amount,
+                swap_token_b_amount.checked_add(source_amount)?,
+            ),
+        };
+        self.range()?.pool_tokens_moved(
+            pool_supply,
+            source_amount,
+            (swap_token_a_amount, swap_token_b_amount),
+            new_reserves,
+            trade_direction,
+            RoundDirection::Floor,
+        )
+    }
+
+    /// `pool = supply * (L - L') / L`
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let new_reserves = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_token_a_amount.checked_sub(source_amount)?,
+                swap_token_b_amount,
+            ),
+            TradeDirection::BtoA => (
+                swap_token_a_amount,
+                swap_token_b_amount.checked_sub(source_amount)?,
+            ),
+        };
+        self.range()?.pool_tokens_moved(
+            pool_supply,
+            source_amount,
+            (swap_token_a_amount, swap_token_b_amount),
+            new_reserves,
+            trade_direction,
+            RoundDirection::Ceiling,
+        )
+    }
+
+    /// Both tokens in proportion to the reserves, which keeps the price
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// The ratio of the virtual reserves
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let (virtual_source_amount, virtual_destination_amount) =
+            self.virtual_reserves(swap_source_amount, swap_destination_amount, trade_direction)?;
+        PreciseNumber::new(virtual_destination_amount)?
+            .checked_div(&PreciseNumber::new(virtual_source_amount)?)
+    }
+}
+
+// ----------------------------------------------------------------------------- program pack
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for ConcentratedCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for ConcentratedCurve {}
+impl Pack for ConcentratedCurve {
+    const LEN: usize = 32;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<ConcentratedCurve, ProgramError> {
+        let input = array_ref![input, 0, 32];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            min_token_b_price,
+            min_token_b_price_denominator,
+            max_token_b_price,
+            max_token_b_price_denominator,
+        ) = array_refs![input, 8, 8, 8, 8];
+        Ok(Self {
+            min_token_b_price: u64::from_le_bytes(*min_token_b_price),
+            min_token_b_price_denominator: u64::from_le_bytes(*min_token_b_price_denominator),
+            max_token_b_price: u64::from_le_bytes(*max_token_b_price),
+            max_token_b_price_denominator: u64::from_le_bytes(*max_token_b_price_denominator),
+        })
+    }
+}
+
+impl DynPack for ConcentratedCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 32];
+        let (
+            min_token_b_price,
+            min_token_b_price_denominator,
+            max_token_b_price,
+            max_token_b_price_denominator,
+        ) = mut_array_refs![output, 8, 8, 8, 8];
+        *min_token_b_price = self.min_token_b_price.to_le_bytes();
+        *min_token_b_price_denominator = self.min_token_b_price_denominator.to_le_bytes();
+        *max_token_b_price = self.max_token_b_price.to_le_bytes();
+        *max_token_b_price_denominator = self.max_token_b_price_denominator.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::INITIAL_SWAP_POOL_AMOUNT;
+    use crate::curve::base::{CurveType, SwapCurve};
+    use crate::curve::calculator::test::{
+        check_deposit_token_conversion, check_single_sided_round_trip, round_trip_fee_settings,
+        CONVERSION_BASIS_POINTS_GUARANTEE,
+    };
+    use crate::curve::calculator::TradeDirection;
+    use crate::curve::constant_product::ConstantProductCurve;
+    use crate::error::SwapError;
+    use crate::processor::test::{Pool, World, INITIAL_RESERVE, INITIAL_USER_BALANCE};
+    use proptest::prelude::*;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    /// Between `1 / spread` and `spread` token A per token B
+    fn around_one(spread: u64) -> ConcentratedCurve {
+        ConcentratedCurve {
+            min_token_b_price: 1,
+            min_token_b_price_denominator: spread,
+            max_token_b_price: spread,
+            max_token_b_price_denominator: 1,
+        }
+    }
+
+    #[test]
+    fn ranges_must_hold_a_price() {
+        assert_eq!(around_one(2).validate(), Ok(()));
+        let curve = |min_token_b_price, max_token_b_price| ConcentratedCurve {
+            min_token_b_price,
+            min_token_b_price_denominator: 1,
+            max_token_b_price,
+            max_token_b_price_denominator: 1,
+        };
+        for (min, max) in [(0, 1), (2, 2), (3, 2)] {
+            assert_eq!(curve(min, max).validate(), Err(SwapError::InvalidCurve));
+        }
+        let no_denominator = ConcentratedCurve {
+            min_token_b_price_denominator: 0,
+            ..around_one(2)
+        };
+        assert_eq!(no_denominator.validate(), Err(SwapError::InvalidCurve));
+        assert_eq!(around_one(2).validate_supply(0, 1), Ok(()));
+        assert_eq!(around_one(2).validate_supply(1, 0), Ok(()));
+        assert_eq!(
+            around_one(2).validate_supply(0, 0),
+            Err(SwapError::EmptySupply)
+        );
+    }
+
+    #[test]
+    fn narrower_ranges_slip_less() {
+        let reserve = 1_000_000_000_000;
+        let amount = reserve / 100;
+        let out = |curve: &dyn CurveCalculator| {
+            curve
+                .swap_without_fees(amount, reserve, reserve, TradeDirection::AtoB)
+                .unwrap()
+                .destination_amount_swapped
+        };
+        let constant_product = out(&ConstantProductCurve);
+        let wide = out(&around_one(100));
+        let narrow = out(&around_one(2));
+        assert!(constant_product < wide && wide < narrow && narrow < amount);
+
+        let price = around_one(2)
+            .spot_price(reserve, reserve, TradeDirection::AtoB)
+            .unwrap();
+        assert!(price.almost_eq(&PreciseNumber::new(1).unwrap(), U256::from(1_000u64)));
+    }
+
+    #[test]
+    fn the_reserves_run_out_at_the_edges_of_the_range() {
+        let curve = ConcentratedCurve {
+            min_token_b_price: 1,
+            min_token_b_price_denominator: 2,
+            max_token_b_price: 4,
+            max_token_b_price_denominator: 1,
+        };
+        let reserve = 1_000_000_000;
+        let price = |token_a_amount, token_b_amount| {
+            curve
+                .spot_price(token_b_amount, token_a_amount, TradeDirection::BtoA)
+                .unwrap()
+        };
+        let precision = U256::from(1_000_000u64);
+        assert!(price(0, reserve).almost_eq(
+            &PreciseNumber::new(1)
+                .unwrap()
+                .checked_div(&PreciseNumber::new(2).unwrap())
+                .unwrap(),
+            precision
+        ));
+        assert!(price(reserve, 0).almost_eq(&PreciseNumber::new(4).unwrap(), precision));
+
+        for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+            let max = curve
+                .max_swappable(reserve, reserve, trade_direction)
+                .unwrap();
+            let paid = curve
+                .swap_without_fees(max, reserve, reserve, trade_direction)
+                .unwrap()
+                .destination_amount_swapped;
+            assert!(paid <= reserve && paid > reserve - reserve / 1_000);
+            assert_eq!(
+                curve.swap_without_fees(max + 1, reserve, reserve, trade_direction),
+                None
+            );
+        }
+        // nothing to pay token B out of at the top of the range
+        assert_eq!(
+            curve.swap_without_fees(1_000, reserve, 0, TradeDirection::AtoB),
+            None
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn swaps_never_shrink_the_liquidity(
+            spread in 2..1_000u64,
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1_000_000..u64::MAX,
+            swap_destination_amount in 1_000_000..u64::MAX,
+        ) {
+            let curve = around_one(spread);
+            let range = curve.range().unwrap();
+            let (source_amount, swap_source_amount, swap_destination_amount) = (
+                source_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+            );
+            if let Some(result) = curve.swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ) {
+                prop_assert!(result.destination_amount_swapped <= swap_destination_amount);
+                let liquidity = range
+                    .liquidity(swap_source_amount, swap_destination_amount)
+                    .unwrap();
+                let new_liquidity = range
+                    .liquidity(
+                        swap_source_amount + result.source_amount_swapped,
+                        swap_destination_amount - result.destination_amount_swapped,
+                    )
+                    .unwrap();
+                // within a part in 1e10, the precision of the square roots
+                let margin = liquidity
+                    .checked_div(&PreciseNumber::new(10_000_000_000).unwrap())
+                    .unwrap();
+                prop_assert!(new_liquidity
+                    .checked_add(&margin)
+                    .unwrap()
+                    .greater_than_or_equal(&liquidity));
+            }
+        }
+
+        #[test]
+        fn deposit_token_conversion(
+            spread in 2..1_000u64,
+            source_token_bps in 1..100u128,
+            swap_source_amount in 1_000_000_000..1_000_000_000_000u64,
+            swap_destination_amount in 1_000_000_000..1_000_000_000_000u64,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            let curve = around_one(spread);
+            let source_token_amount = swap_source_amount as u128 * source_token_bps / 10_000;
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                // half the deposit may not swap within the range
+                if curve
+                    .swap_without_fees(
+                        source_token_amount / 2,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                    )
+                    .is_none()
+                {
+                    continue;
+                }
+                check_deposit_token_conversion(
+                    &curve,
+                    source_token_amount,
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128,
+                    trade_direction,
+                    pool_supply,
+                    CONVERSION_BASIS_POINTS_GUARANTEE,
+                );
+            }
+        }
+
+        #[test]
+        fn single_sided_round_trip(
+            spread in 2..1_000u64,
+            source_token_bps in 1..100u128,
+            swap_source_amount in 1_000_000_000..1_000_000_000_000u64,
+            swap_destination_amount in 1_000_000_000..1_000_000_000_000u64,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            let curve = around_one(spread);
+            let source_token_amount = swap_source_amount as u128 * source_token_bps / 10_000;
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                // half the deposit may not swap within the range
+                if curve
+                    .swap_without_fees(
+                        source_token_amount / 2,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                    )
+                    .is_none()
+                {
+                    continue;
+                }
+                let swap_curve = SwapCurve {
+                    curve_type: CurveType::Concentrated,
+                    calculator: Box::new(curve.clone()),
+                };
+                for fees in round_trip_fee_settings().iter() {
+                    check_single_sided_round_trip(
+                        &swap_curve,
+                        fees,
+                        source_token_amount,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                        pool_supply,
+                        CONVERSION_BASIS_POINTS_GUARANTEE,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn concentrated_pools_slip_less_and_refuse_trades_out_of_range() {
+        let mut world = World::new();
+        let reserve = INITIAL_RESERVE / 200;
+        for curve_type in [CurveType::ConstantProduct, CurveType::Concentrated] {
+            world
+                .add_pool(
+                    world.mints[0],
+                    world.mints[1],
+                    curve_type,
+                    0,
+                    (reserve, reserve),
+                )
+                .unwrap();
+        }
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let user = &world.users[0];
+        let swap = |pool: &Pool, amount_in| pool.swap(user, TradeDirection::AtoB, amount_in);
+        let pools = &world.pools[world.pools.len() - 2..];
+        let destination = user.tokens[&pools[0].mint_b];
+        let amount_in = reserve / 100;
+        let mut received = vec![];
+        for pool in pools {
+            let before = world.bank.token_account(&destination).amount;
+            world.bank.process(&swap(pool, amount_in)).unwrap();
+            received.push(world.bank.token_account(&destination).amount - before);
+        }
+        let (constant_product, concentrated) = (received[0], received[1]);
+        assert!(constant_product < concentrated && concentrated < amount_in);
+
+        // the pool runs out of token B at the top of the range, 1.2 token A
+        assert_eq!(
+            world
+                .bank
+                .process(&swap(&pools[1], INITIAL_USER_BALANCE / 10 * 9)),
+            Err(SwapError::PriceOutOfRange.into())
+        );
+        world.assert_conserved(&trading_supplies);
+    }
+}
@@ -1,16 +1,14 @@
 //! Simple constant price swap curve, set at init
-use std::u128::MAX;
 use {
     crate::{
         curve::calculator::{
-            map_zero_to_none, CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult,
-            TradeDirection, TradingTokenResult,
+            map_zero_to_none, sealed, CurveCalculator, DynPack, RoundDirection,
+            SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
         },
         error::SwapError,
     },
-    arrayref::{array_mut_ref, array_ref},
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
     solana_program::{
-        msg,
         program_error::ProgramError,
         program_pack::{IsInitialized, Pack, Sealed},
     },
@@ -18,7 +16,7 @@ use {
 };
 
 pub fn trading_tokens_to_pool_tokens(
-    token_b_price: u64, //(measured in tokens a)
+    (token_b_price, token_b_price_denominator): (u128, u128), //(measured in tokens a)
     source_amount: u128,
     swap_token_a_amount: u128,
     swap_token_b_amount: u128,
@@ -27,47 +25,58 @@ pub fn trading_tokens_to_pool_tokens(
     round_direction: RoundDirection,
 ) -> Option<u128> {
     let token_b_price = U256::from(token_b_price);
+    let token_b_price_denominator = U256::from(token_b_price_denominator);
 
-    //source amount measured in A tokens
+    //source amount measured in A tokens, times the price denominator
     let given_value = match trade_direction {
-        TradeDirection::AtoB => U256::from(source_amount),
+        TradeDirection::AtoB => U256::from(source_amount).checked_mul(token_b_price_denominator)?,
         TradeDirection::BtoA => U256::from(source_amount).checked_mul(token_b_price)?, //constant price
     };
 
-    //swap's current B balance measured in A tokens + A balance (so total balance)
+    //swap's current B balance measured in A tokens + A balance (so total balance), same units
     let total_value = U256::from(swap_token_b_amount)
         .checked_mul(token_b_price)?
-        .checked_add(U256::from(swap_token_a_amount))?;
+        .checked_add(U256::from(swap_token_a_amount).checked_mul(token_b_price_denominator)?)?;
 
     let pool_supply = U256::from(pool_supply);
 
     match round_direction {
-        RoundDirection::Floor => Some(
+        RoundDirection::Floor => to_u128(
             //pool supply multiplied by
             pool_supply
                 //ratio of given value / current value in exchange (both measured in A tokens)
                 .checked_mul(given_value)?
-                .checked_div(total_value)?
-                .as_u128(),
+                .checked_div(total_value)?,
         ),
-        RoundDirection::Ceiling => Some(
+        RoundDirection::Ceiling => to_u128(
             pool_supply
                 .checked_mul(given_value)?
                 .checked_ceil_div(total_value)?
-                .0
-                .as_u128(),
+                .0,
         ),
     }
 }
 
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ConstantPriceCurve {
     /// Amount of token A required to get 1 token B
     pub token_b_price: u64,
+    /// Denominator of `token_b_price`, for prices that aren't a whole amount
+    /// of token A.  0 reads as 1, as in curves packed before it existed
+    pub token_b_price_denominator: u64,
 }
 
 // (a_token + b_token * b_token_price) / 2
 impl ConstantPriceCurve {
+    /// Price of 1 token B in token A, as (numerator, denominator)
+    pub fn price(&self) -> (u128, u128) {
+        (
+            u128::from(self.token_b_price),
+            u128::from(self.token_b_price_denominator.max(1)),
+        )
+    }
+
     /// The total normalized value of the constant price curve adds the total
     /// value of the token B side to the token A side.
     ///
@@ -76,29 +85,37 @@ impl ConstantPriceCurve {
     /// ie. `token_a + token_b`.
     ///
     /// At the end, we divide by 2 to normalize the value between the two token
-    /// types.
+    /// types.  The value is in token A times the price denominator.
     fn normalized_value(
         &self,
         swap_token_a_amount: u128,
         swap_token_b_amount: u128,
     ) -> Option<PreciseNumber> {
-        let swap_token_b_value = swap_token_b_amount.checked_mul(self.token_b_price as u128)?;
-        // special logic in case we're close to the limits, avoid overflowing u128
-        let value = if swap_token_b_value.saturating_sub(std::u64::MAX.into())
-            > (MAX.saturating_sub(std::u64::MAX.into()))
-        {
-            swap_token_b_value
-                .checked_div(2)?
-                .checked_add(swap_token_a_amount.checked_div(2)?)?
-        } else {
-            swap_token_a_amount
-                .checked_add(swap_token_b_value)?
-                .checked_div(2)?
-        };
-        PreciseNumber::new(value)
+        // a reserve of 18 decimal token B at a price quoted in 0 decimal
+        // token A units is the widest value, sum it in U256 and only narrow
+        // once halved
+        let (token_b_price, token_b_price_denominator) = self.price();
+        let value = U256::from(swap_token_b_amount)
+            .checked_mul(U256::from(token_b_price))?
+            .checked_add(
+                U256::from(swap_token_a_amount)
+                    .checked_mul(U256::from(token_b_price_denominator))?,
+            )?
+            .checked_div(U256::from(2))?;
+        PreciseNumber::new(to_u128(value)?)
     }
 }
 
+fn to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+impl sealed::Sealed for ConstantPriceCurve {}
+
 impl CurveCalculator for ConstantPriceCurve {
     fn validate(&self) -> Result<(), SwapError> {
         if self.token_b_price == 0 {
@@ -116,21 +133,27 @@ impl CurveCalculator for ConstantPriceCurve {
         _swap_destination_amount: u128,
         trade_direction: TradeDirection,
     ) -> Option<SwapWithoutFeesResult> {
-        let token_b_price = self.token_b_price as u128;
-        msg!("token b prices is {}", token_b_price);
+        let (token_b_price, token_b_price_denominator) = self.price();
 
         let (source_amount_swapped, destination_amount_swapped) = match trade_direction {
-            TradeDirection::BtoA => (source_amount, source_amount.checked_mul(token_b_price)?),
+            TradeDirection::BtoA => (
+                source_amount,
+                source_amount
+                    .checked_mul(token_b_price)?
+                    .checked_div(token_b_price_denominator)?,
+            ),
             TradeDirection::AtoB => {
-                let destination_amount_swapped = source_amount.checked_div(token_b_price)?;
-                let mut source_amount_swapped = source_amount;
+                let destination_amount_swapped = source_amount
+                    .checked_mul(token_b_price_denominator)?
+                    .checked_div(token_b_price)?;
 
-                // if there is a remainder from buying token B, floor
-                // token_a_amount to avoid taking too many tokens, but
-                // don't recalculate the fees
-                let remainder = source_amount_swapped.checked_rem(token_b_price)?;
-                if remainder > 0 {
-                    source_amount_swapped = source_amount.checked_sub(remainder)?;
+                // if there is a remainder from buying token B, only take the
+                // token A the bought tokens cost, rounded up, but don't
+                // recalculate the fees
+                let cost = destination_amount_swapped.checked_mul(token_b_price)?;
+                let mut source_amount_swapped = cost.checked_div(token_b_price_denominator)?;
+                if cost.checked_rem(token_b_price_denominator)? > 0 {
+                    source_amount_swapped = source_amount_swapped.checked_add(1)?;
                 }
 
                 (source_amount_swapped, destination_amount_swapped)
@@ -144,6 +167,28 @@ impl CurveCalculator for ConstantPriceCurve {
         })
     }
 
+    /// The price never moves, so a big enough trade empties the destination.
+    /// The most that pays out no more than the reserve is the amount just
+    /// short of paying out one token more.
+    fn max_swappable(
+        &self,
+        _swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let (token_b_price, token_b_price_denominator) = self.price();
+        let (numerator, denominator) = match trade_direction {
+            TradeDirection::AtoB => (token_b_price, token_b_price_denominator),
+            TradeDirection::BtoA => (token_b_price_denominator, token_b_price),
+        };
+        let max = U256::from(swap_destination_amount)
+            .checked_add(U256::from(1))?
+            .checked_mul(U256::from(numerator))?
+            .checked_sub(U256::from(1))?
+            .checked_div(U256::from(denominator))?;
+        Some(to_u128(max).unwrap_or(u128::MAX))
+    }
+
     // Used when calculating owner/host fees.
     fn pool_tokens_to_trading_tokens(
         &self,
@@ -153,38 +198,43 @@ impl CurveCalculator for ConstantPriceCurve {
         swap_token_b_amount: u128,
         round_direction: RoundDirection,
     ) -> Option<TradingTokenResult> {
-        let token_b_price = self.token_b_price as u128;
+        let (token_b_price, token_b_price_denominator) = self.price();
         // sum of the two tokens denominated in A token, divided by 2
         let total_value = self
             .normalized_value(swap_token_a_amount, swap_token_b_amount)?
             .to_imprecise()?;
+        // the pool's value can take most of a u128 by itself, so the share
+        // of it is taken in U256
+        let pool_value = U256::from(pool_tokens).checked_mul(U256::from(total_value))?;
+        let pool_token_supply = U256::from(pool_token_supply);
+        let token_b_price = U256::from(token_b_price);
+        let token_b_price_denominator = U256::from(token_b_price_denominator);
 
         let (token_a_amount, token_b_amount) = match round_direction {
             RoundDirection::Floor => {
-                let token_a_amount = pool_tokens
-                    .checked_mul(total_value)? //this is half the total amount
-                    .checked_div(pool_token_supply)?;
-                let token_b_amount = pool_tokens
-                    .checked_mul(total_value)?
+                let token_a_amount = pool_value //this is half the total amount
+                    .checked_div(pool_token_supply)?
+                    .checked_div(token_b_price_denominator)?; //convert back into original a tokens by taking out the price denominator
+                let token_b_amount = pool_value
                     .checked_div(token_b_price)? //convert back into original b tokens by taking out the price
                     .checked_div(pool_token_supply)?;
                 (token_a_amount, token_b_amount)
             }
             RoundDirection::Ceiling => {
-                let (token_a_amount, _) = pool_tokens
-                    .checked_mul(total_value)? //this is half the total amount
-                    .checked_ceil_div(pool_token_supply)?;
-                let (pool_value_as_token_b, _) = pool_tokens
-                    .checked_mul(total_value)?
-                    .checked_ceil_div(token_b_price)?; //convert back into original b tokens by taking out the price
+                let (pool_value_as_token_a, _) =
+                    pool_value //this is half the total amount
+                        .checked_ceil_div(pool_token_supply)?;
+                let (token_a_amount, _) =
+                    pool_value_as_token_a.checked_ceil_div(token_b_price_denominator)?; //convert back into original a tokens by taking out the price denominator
+                let (pool_value_as_token_b, _) = pool_value.checked_ceil_div(token_b_price)?; //convert back into original b tokens by taking out the price
                 let (token_b_amount, _) =
                     pool_value_as_token_b.checked_ceil_div(pool_token_supply)?;
                 (token_a_amount, token_b_amount)
             }
         };
         Some(TradingTokenResult {
-            token_a_amount,
-            token_b_amount,
+            token_a_amount: to_u128(token_a_amount)?,
+            token_b_amount: to_u128(token_b_amount)?,
         })
     }
 
@@ -197,7 +247,7 @@ impl CurveCalculator for ConstantPriceCurve {
         trade_direction: TradeDirection,
     ) -> Option<u128> {
         trading_tokens_to_pool_tokens(
-            self.token_b_price,
+            self.price(),
             source_amount,
             swap_token_a_amount,
             swap_token_b_amount,
@@ -216,7 +266,7 @@ impl CurveCalculator for ConstantPriceCurve {
         trade_direction: TradeDirection,
     ) -> Option<u128> {
         trading_tokens_to_pool_tokens(
-            self.token_b_price,
+            self.price(),
             source_amount,
             swap_token_a_amount,
             swap_token_b_amount,
@@ -226,6 +276,22 @@ impl CurveCalculator for ConstantPriceCurve {
         )
     }
 
+    /// The price never moves, regardless of reserves
+    fn spot_price(
+        &self,
+        _swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let (token_b_price, token_b_price_denominator) = self.price();
+        let token_b_price = PreciseNumber::new(token_b_price)?;
+        let token_b_price_denominator = PreciseNumber::new(token_b_price_denominator)?;
+        match trade_direction {
+            TradeDirection::AtoB => token_b_price_denominator.checked_div(&token_b_price),
+            TradeDirection::BtoA => token_b_price.checked_div(&token_b_price_denominator),
+        }
+    }
+
     fn validate_supply(&self, token_a_amount: u64, _token_b_amount: u64) -> Result<(), SwapError> {
         if token_a_amount == 0 {
             return Err(SwapError::EmptySupply);
@@ -242,22 +308,115 @@ impl IsInitialized for ConstantPriceCurve {
 }
 impl Sealed for ConstantPriceCurve {}
 impl Pack for ConstantPriceCurve {
-    const LEN: usize = 8;
+    const LEN: usize = 16;
     fn pack_into_slice(&self, output: &mut [u8]) {
         (self as &dyn DynPack).pack_into_slice(output);
     }
 
     fn unpack_from_slice(input: &[u8]) -> Result<ConstantPriceCurve, ProgramError> {
-        let token_b_price = array_ref![input, 0, 8];
+        let input = array_ref![input, 0, 16];
+        let (token_b_price, token_b_price_denominator) = array_refs![input, 8, 8];
         Ok(Self {
             token_b_price: u64::from_le_bytes(*token_b_price),
+            token_b_price_denominator: u64::from_le_bytes(*token_b_price_denominator),
         })
     }
 }
 
 impl DynPack for ConstantPriceCurve {
     fn pack_into_slice(&self, output: &mut [u8]) {
-        let token_b_price = array_mut_ref![output, 0, 8];
+        let output = array_mut_ref![output, 0, 16];
+        let (token_b_price, token_b_price_denominator) = mut_array_refs![output, 8, 8];
         *token_b_price = self.token_b_price.to_le_bytes();
+        *token_b_price_denominator = self.token_b_price_denominator.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::INITIAL_SWAP_POOL_AMOUNT;
+    use crate::curve::base::{CurveType, SwapCurve};
+    use crate::curve::calculator::test::{check_single_sided_round_trip, round_trip_fee_settings};
+    use proptest::prelude::*;
+
+    #[test]
+    fn widest_reserves_convert_without_overflow() {
+        // 18 decimal token B priced in 0 decimal token A units, every amount
+        // at the u64 limit
+        let max = u64::MAX as u128;
+        let curve = ConstantPriceCurve {
+            token_b_price: u64::MAX,
+            token_b_price_denominator: 0,
+        };
+        let supply = 1_000_000_000;
+        let result = curve
+            .pool_tokens_to_trading_tokens(supply, supply, max, max, RoundDirection::Floor)
+            .unwrap();
+        // half the pool's value, (max * max + max) / 2, in each token
+        assert_eq!(result.token_a_amount, max << 63);
+        assert_eq!(result.token_b_amount, 1 << 63);
+    }
+
+    #[test]
+    fn max_swappable_pays_out_at_most_the_reserve() {
+        // 1 token B costs 3/2 token A
+        let curve = ConstantPriceCurve {
+            token_b_price: 3,
+            token_b_price_denominator: 2,
+        };
+        let reserve = 1_000;
+        for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+            let max = curve.max_swappable(0, reserve, trade_direction).unwrap();
+            let paid = |amount| {
+                curve
+                    .swap_without_fees(amount, 0, reserve, trade_direction)
+                    .unwrap()
+                    .destination_amount_swapped
+            };
+            assert_eq!(paid(max), reserve);
+            assert!(paid(max + 1) > reserve);
+        }
+        assert_eq!(
+            curve.max_swappable(0, reserve, TradeDirection::AtoB),
+            Some(1_501)
+        );
+        assert_eq!(
+            curve.max_swappable(0, reserve, TradeDirection::BtoA),
+            Some(667)
+        );
+    }
+    proptest! {
+        #[test]
+        fn single_sided_round_trip(
+            token_b_price in 1..1_000u64,
+            token_b_price_denominator in 1..1_000u64,
+            source_token_amount in 1..u64::MAX / 4,
+            swap_source_amount in 1..u64::MAX / 4,
+            swap_destination_amount in 1..u64::MAX / 4,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve {
+                    token_b_price,
+                    token_b_price_denominator,
+                }),
+            };
+            for fees in round_trip_fee_settings().iter() {
+                for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                    check_single_sided_round_trip(
+                        &swap_curve,
+                        fees,
+                        source_token_amount as u128,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                        pool_supply,
+                        0,
+                    );
+                }
+            }
+        }
     }
 }
@@ -1,22 +1,20 @@
 use crate::curve::calculator::{
-    map_zero_to_none, CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult,
+    map_zero_to_none, sealed, CurveCalculator, DynPack, RoundDirection, SwapWithoutFeesResult,
     TradeDirection, TradingTokenResult,
 };
-use crate::error::SwapError;
 use solana_program::program_error::ProgramError;
 use solana_program::program_pack::{IsInitialized, Pack, Sealed};
 use spl_math::checked_ceil_div::CheckedCeilDiv;
-use spl_math::precise_number::PreciseNumber;
+use spl_math::uint::U256;
 
 // this is the struct that's going to implement the Calculator trait
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct ConstantProductCurve;
 
-impl CurveCalculator for ConstantProductCurve {
-    fn validate(&self) -> Result<(), SwapError> {
-        Ok(())
-    }
+impl sealed::Sealed for ConstantProductCurve {}
 
+impl CurveCalculator for ConstantProductCurve {
     // constant product swap, x * y = constant
     fn swap_without_fees(
         &self,
@@ -119,6 +117,10 @@ pub fn swap(
 }
 
 /// based on this -> https://balancer.finance/whitepaper/#single-asset-withdrawal
+///
+/// Balancer's single-asset withdrawal, `A_t = B_t * (1 - (1 - P_redeemed / P_supply)^(1 / W_t))`,
+/// solved for the pool tokens redeemed with both weights at 1/2:
+/// `P_redeemed = P_supply * (1 - √(1 - A_t / B_t))`
 pub fn withdraw_single_token_type_exact_out(
     source_amount: u128, //source tokens that go to the OWNER as a fee for executing the trade LESS FEE. this will be the numerator
     swap_token_a_amount: u128,
@@ -127,30 +129,19 @@ pub fn withdraw_single_token_type_exact_out(
     trade_direction: TradeDirection,
     round_direction: RoundDirection,
 ) -> Option<u128> {
-    //this will be the denominator
     let swap_source_amount = match trade_direction {
         TradeDirection::AtoB => swap_token_a_amount,
         TradeDirection::BtoA => swap_token_b_amount,
     };
-    // Struct encapsulating a fixed-point number that allows for decimal calculations
-    // https://docs.rs/spl-math/0.1.0/spl_math/precise_number/struct.PreciseNumber.html
-    let swap_source_amount = PreciseNumber::new(swap_source_amount)?;
-    let source_amount = PreciseNumber::new(source_amount)?;
-    let ratio = source_amount.checked_div(&swap_source_amount)?; //fees in X token / total X tokens in pool
-    let one = PreciseNumber::new(1)?;
-
-    //the math from balancer paper - we rebalance the pool after subtracting one token
-    let base = one.checked_sub(&ratio)?; //1-r
-    let root = one.checked_sub(&base.sqrt()?)?; //1 - √(1-r)
-    let pool_supply = PreciseNumber::new(pool_supply)?;
-
-    //we're multiplying the POOL token supply by the ratio, coz we want to withdraw POOL tokens as fee, not actual X tokens
-    let pool_tokens = pool_supply.checked_mul(&root)?;
-
-    match round_direction {
-        RoundDirection::Floor => pool_tokens.floor()?.to_imprecise(), //back to u128
-        RoundDirection::Ceiling => pool_tokens.ceiling()?.to_imprecise(), //back to u128
-    }
+    // the supply left is `√(P_supply² * (B_t - A_t) / B_t)`, rounded the other
+    // way to the pool tokens redeemed
+    let remaining_supply = supply_after(
+        pool_supply,
+        swap_source_amount,
+        swap_source_amount.checked_sub(source_amount)?,
+        round_direction.opposite(),
+    )?;
+    pool_supply.checked_sub(remaining_supply)
 }
 
 pub fn pool_tokens_to_trading_tokens(
@@ -198,6 +189,8 @@ pub fn pool_tokens_to_trading_tokens(
     })
 }
 
+/// Balancer's single-asset deposit, `P_issued = P_supply * ((1 + A_t / B_t)^W_t - 1)`,
+/// with both weights at 1/2: `P_issued = P_supply * (√(1 + A_t / B_t) - 1)`
 pub fn deposit_single_token_type(
     source_amount: u128,
     swap_token_a_amount: u128,
@@ -211,18 +204,52 @@ pub fn deposit_single_token_type(
         TradeDirection::BtoA => swap_token_b_amount,
     };
     //the reverse of withdrawal - https://balancer.fi/whitepaper.pdf
-    let swap_source_amount = PreciseNumber::new(swap_source_amount)?;
-    let source_amount = PreciseNumber::new(source_amount)?;
-    let one = PreciseNumber::new(1)?;
-    let pool_supply = PreciseNumber::new(pool_supply)?;
-
-    let ratio = source_amount.checked_div(&swap_source_amount)?; // r = deposit / existing
-    let base = one.checked_add(&ratio)?; // 1+r
-    let root = base.sqrt()?.checked_sub(&one)?; // √(1+r) - 1
-    let pool_tokens = pool_supply.checked_mul(&root)?; // pool x (√(1+r) - 1)
-    match round_direction {
-        RoundDirection::Floor => pool_tokens.floor()?.to_imprecise(),
-        RoundDirection::Ceiling => pool_tokens.ceiling()?.to_imprecise(),
+    // the new supply is `√(P_supply² * (B_t + A_t) / B_t)`
+    let new_supply = supply_after(
+        pool_supply,
+        swap_source_amount,
+        swap_source_amount.checked_add(source_amount)?,
+        round_direction,
+    )?;
+    new_supply.checked_sub(pool_supply)
+}
+
+/// Pool supply once the reserve of a single token moves from
+/// `swap_source_amount` to `new_swap_source_amount`, `√(P_supply² * new / old)`.
+/// Worked out in whole numbers, as a fixed point square root loses tokens
+/// that are small next to the reserve, and rounds the way it's asked to at
+/// each step.
+fn supply_after(
+    pool_supply: u128,
+    swap_source_amount: u128,
+    new_swap_source_amount: u128,
+    round_direction: RoundDirection,
+) -> Option<u128> {
+    let old = U256::from(swap_source_amount);
+    let squared = U256::from(pool_supply)
+        .checked_mul(U256::from(pool_supply))?
+        .checked_mul(U256::from(new_swap_source_amount))?;
+    let squared = match round_direction {
+        RoundDirection::Floor => squared.checked_div(old)?,
+        RoundDirection::Ceiling => squared
+            .checked_add(old.checked_sub(U256::one())?)?
+            .checked_div(old)?,
+    };
+    let root = squared.integer_sqrt();
+    let root = match round_direction {
+        RoundDirection::Ceiling if root.checked_mul(root)? < squared => {
+            root.checked_add(U256::one())?
+        }
+        _ => root,
+    };
+    to_u128(root)
+}
+
+fn to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
     }
 }
 
@@ -253,13 +280,214 @@ impl DynPack for ConstantProductCurve {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::constants::INITIAL_SWAP_POOL_AMOUNT;
+    use crate::curve::base::SwapCurve;
     use crate::curve::calculator::{
-        test::{check_deposit_token_conversion, CONVERSION_BASIS_POINTS_GUARANTEE},
-        RoundDirection, INITIAL_SWAP_POOL_AMOUNT,
+        test::{
+            check_deposit_token_conversion, check_single_sided_round_trip, round_trip_fee_settings,
+            CONVERSION_BASIS_POINTS_GUARANTEE,
+        },
+        RoundDirection,
     };
+    use crate::curve::fees::{FeeTiers, Fees};
     use proptest::prelude::*;
 
+    #[test]
+    fn exact_input_keeps_the_rounding_dust() {
+        let curve = SwapCurve::default();
+        let result = curve
+            .swap(
+                1_400,
+                1_000,
+                10,
+                TradeDirection::AtoB,
+                &Fees::default(),
+                &FeeTiers::default(),
+            )
+            .unwrap();
+        // 1_000 buys the same 5 tokens, the rest stays with the user
+        assert_eq!(result.source_amount_swapped, 1_000);
+        assert_eq!(result.destination_amount_swapped, 5);
+        assert_eq!(result.new_swap_source_amount, 2_000);
+
+        let result = result.with_exact_input(1_400).unwrap();
+        assert_eq!(result.source_amount_swapped, 1_400);
+        assert_eq!(result.destination_amount_swapped, 5);
+        assert_eq!(result.new_swap_source_amount, 2_400);
+        assert_eq!(result.new_swap_destination_amount, 5);
+    }
+
+    // ------------------------------------------------------------------------- balancer formulas
+
+    /// Weight of each token in a constant product pool
+    const WEIGHT: f64 = 0.5;
+
+    /// Balancer's single-asset deposit, `P_issued = P_supply * ((1 + A_t / B_t)^W_t - 1)`,
+    /// with the swap fee charged on the `1 - W_t` share of the deposit that's
+    /// in effect traded for the other token
+    fn balancer_deposit(supply: f64, balance: f64, amount_in: f64, swap_fee: f64) -> f64 {
+        let amount_in = amount_in * (1.0 - (1.0 - WEIGHT) * swap_fee);
+        supply * ((1.0 + amount_in / balance).powf(WEIGHT) - 1.0)
+    }
+
+    /// Balancer's single-asset withdrawal,
+    /// `A_t = B_t * (1 - (1 - P_redeemed / P_supply)^(1 / W_t))`, solved for
+    /// `P_redeemed`
+    fn balancer_withdrawal(supply: f64, balance: f64, amount_out: f64) -> f64 {
+        supply * (1.0 - (1.0 - amount_out / balance).powf(WEIGHT))
+    }
+
+    /// Floor and ceiling results bracket the exact value and are at most one
+    /// pool token apart
+    fn assert_brackets(floor: u128, ceiling: u128, exact: u128) {
+        assert!(
+            floor <= exact && exact <= ceiling,
+            "{} <= {} <= {}",
+            floor,
+            exact,
+            ceiling
+        );
+        assert!(ceiling - floor <= 1);
+    }
+
+    #[test]
+    fn single_asset_deposit_matches_balancer() {
+        let supply = 1_000_000_000u128;
+        // (deposit, reserve of the token deposited, pool tokens issued), each
+        // with a square root that comes out exact
+        for (amount_in, balance, issued) in [
+            // √1.0201 = 1.01
+            (20_100u128, 1_000_000u128, 10_000_000u128),
+            // √1.44 = 1.2
+            (440_000, 1_000_000, 200_000_000),
+            // √4 = 2, tripling the reserve doubles the supply
+            (3_000_000, 1_000_000, 1_000_000_000),
+            (12, 4, 1_000_000_000),
+        ] {
+            let exact = balancer_deposit(supply as f64, balance as f64, amount_in as f64, 0.0);
+            assert_eq!(exact.round() as u128, issued);
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+                    TradeDirection::AtoB => (balance, u128::MAX),
+                    TradeDirection::BtoA => (u128::MAX, balance),
+                };
+                let deposit = |round_direction| {
+                    deposit_single_token_type(
+                        amount_in,
+                        swap_token_a_amount,
+                        swap_token_b_amount,
+                        supply,
+                        trade_direction,
+                        round_direction,
+                    )
+                    .unwrap()
+                };
+                assert_brackets(
+                    deposit(RoundDirection::Floor),
+                    deposit(RoundDirection::Ceiling),
+                    issued,
+                );
+            }
+        }
+
+        // a 20% fee on the half traded leaves 396_000 of 440_000 deposited,
+        // 0.44 of the reserve of 900_000
+        let fees = Fees::builder().trade_fee(20, 100).build().unwrap();
+        let exact = balancer_deposit(supply as f64, 900_000.0, 440_000.0, 0.2);
+        assert_eq!(exact.round() as u128, 200_000_000);
+        let issued = SwapCurve::default()
+            .deposit_single_token_type(
+                440_000,
+                900_000,
+                900_000,
+                supply,
+                TradeDirection::AtoB,
+                &fees,
+            )
+            .unwrap();
+        // rounded down
+        assert!((199_999_999..=200_000_000).contains(&issued));
+    }
+
+    #[test]
+    fn single_asset_withdrawal_matches_balancer() {
+        let supply = 1_000_000_000u128;
+        // (withdrawal, reserve, pool tokens redeemed)
+        for (amount_out, balance, redeemed) in [
+            // √0.9801 = 0.99
+            (19_900u128, 1_000_000u128, 10_000_000u128),
+            // √0.64 = 0.8
+            (360_000, 1_000_000, 200_000_000),
+            // √0.25 = 0.5, three quarters of the reserve takes half the supply
+            (750_000, 1_000_000, 500_000_000),
+            (3, 4, 500_000_000),
+        ] {
+            let exact = balancer_withdrawal(supply as f64, balance as f64, amount_out as f64);
+            assert_eq!(exact.round() as u128, redeemed);
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+                    TradeDirection::AtoB => (balance, u128::MAX),
+                    TradeDirection::BtoA => (u128::MAX, balance),
+                };
+                let withdraw = |round_direction| {
+                    withdraw_single_token_type_exact_out(
+                        amount_out,
+                        swap_token_a_amount,
+                        swap_token_b_amount,
+                        supply,
+                        trade_direction,
+                        round_direction,
+                    )
+                    .unwrap()
+                };
+                assert_brackets(
+                    withdraw(RoundDirection::Floor),
+                    withdraw(RoundDirection::Ceiling),
+                    redeemed,
+                );
+            }
+        }
+    }
+
     proptest! {
+        #[test]
+        fn single_asset_formulas_track_balancer(
+            amount in 1..u64::MAX,
+            balance in 3..u64::MAX / 2,
+            supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            // Balancer's own limits, a deposit of at most half the reserve and
+            // a withdrawal of at most a third
+            let (amount, balance) = (1 + amount as u128 % (balance as u128 / 2), balance as u128);
+            // within a millionth of the floating point formula, or two tokens
+            let tolerance = |exact: f64| (exact / 1_000_000.0).max(2.0);
+
+            let issued = deposit_single_token_type(
+                amount,
+                balance,
+                balance,
+                supply,
+                TradeDirection::AtoB,
+                RoundDirection::Floor,
+            )
+            .unwrap();
+            let exact = balancer_deposit(supply as f64, balance as f64, amount as f64, 0.0);
+            prop_assert!((issued as f64 - exact).abs() <= tolerance(exact));
+
+            let amount = amount.min(balance / 3);
+            let redeemed = withdraw_single_token_type_exact_out(
+                amount,
+                balance,
+                balance,
+                supply,
+                TradeDirection::AtoB,
+                RoundDirection::Ceiling,
+            )
+            .unwrap();
+            let exact = balancer_withdrawal(supply as f64, balance as f64, amount as f64);
+            prop_assert!((redeemed as f64 - exact).abs() <= tolerance(exact));
+        }
+
         #[test]
         fn deposit_token_conversion(
             // in the pool token conversion calcs, we simulate trading half of
@@ -290,5 +518,29 @@ mod tests {
                 CONVERSION_BASIS_POINTS_GUARANTEE,
             );
         }
+
+        #[test]
+        fn single_sided_round_trip(
+            source_token_amount in 1..u64::MAX / 4,
+            swap_source_amount in 1..u64::MAX / 4,
+            swap_destination_amount in 1..u64::MAX / 4,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            let swap_curve = SwapCurve::default();
+            for fees in round_trip_fee_settings().iter() {
+                for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                    check_single_sided_round_trip(
+                        &swap_curve,
+                        fees,
+                        source_token_amount as u128,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                        pool_supply,
+                        0,
+                    );
+                }
+            }
+        }
     }
 }
@@ -1,9 +1,11 @@
+use crate::constants::MAX_FEE_TIERS;
 use crate::error::SwapError;
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use solana_program::program_error::ProgramError;
 use solana_program::program_pack::{IsInitialized, Pack, Sealed};
 use std::convert::TryFrom;
 
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
 #[derive(Clone, Debug, Default, PartialEq)]
 pub struct Fees {
     //to LPs - EXCLUDING THE OWNER FEES, the two are calced and handled separately
@@ -21,7 +23,108 @@ pub struct Fees {
     pub host_fee_denominator: u64,
 }
 
+/// Fees taken out of the source amount of a single trade
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TradeFees {
+    /// Amount of source tokens going to pool holders
+    pub trade_fee: u128,
+    /// Amount of source tokens going to owner
+    pub owner_fee: u128,
+    /// Source amount left to be swapped once both fees are debited
+    pub amount_less_fees: u128,
+}
+
+/// Lower trade fees for trades of at least `threshold` source tokens.  The
+/// numerators are over the base schedule's denominators.
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeTier {
+    /// Smallest source amount the tier applies to, zero leaves it unused
+    pub threshold: u64,
+    pub trade_fee_numerator: u64,
+    pub owner_trade_fee_numerator: u64,
+}
+
+/// Size tiers of a pool's trade fee, by increasing threshold
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FeeTiers {
+    pub tiers: [FeeTier; MAX_FEE_TIERS],
+}
+
 impl Fees {
+    /// Start building a fee schedule, all fees default to zero
+    pub fn builder() -> FeesBuilder {
+        FeesBuilder::default()
+    }
+
+    /// 0.30% per trade, split 0.25% to LPs and 0.05% to the owner, modelled on
+    /// uniswap. 20% of the owner fee goes to the host.
+    pub fn standard_30bps() -> Self {
+        Self {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        }
+    }
+
+    /// No fee is ever charged, on trades or withdrawals
+    pub fn is_zero(&self) -> bool {
+        self.trade_fee_numerator == 0
+            && self.owner_trade_fee_numerator == 0
+            && self.owner_withdraw_fee_numerator == 0
+    }
+
+    /// Same schedule with the owner's trade and withdraw fees waived, the LP
+    /// trade fee still applies
+    pub fn without_owner_fees(&self) -> Self {
+        Self {
+            owner_trade_fee_numerator: 0,
+            owner_withdraw_fee_numerator: 0,
+            ..self.clone()
+        }
+    }
+
+    /// Same schedule with every trade fee waived, LP and owner, the withdraw
+    /// fee still applies
+    pub fn without_trade_fees(&self) -> Self {
+        Self {
+            trade_fee_numerator: 0,
+            owner_trade_fee_numerator: 0,
+            ..self.clone()
+        }
+    }
+
+    /// Total fee charged on a trade (LP + owner) in basis points, rounded down
+    pub fn total_trade_fee_bps(&self) -> Option<u64> {
+        let trade_fee_bps = fraction_to_bps(self.trade_fee_numerator, self.trade_fee_denominator)?;
+        let owner_fee_bps = fraction_to_bps(
+            self.owner_trade_fee_numerator,
+            self.owner_trade_fee_denominator,
+        )?;
+        trade_fee_bps.checked_add(owner_fee_bps)
+    }
+
+    /// Debit both trading fees out of a source amount, exactly as done on-chain
+    /// before the curve calculates the swap
+    pub fn apply(&self, source_amount: u128) -> Option<TradeFees> {
+        let trade_fee = self.trading_fee(source_amount)?; //to LPs
+        let owner_fee = self.owner_trading_fee(source_amount)?; //to owner
+        let amount_less_fees = source_amount
+            .checked_sub(trade_fee)?
+            .checked_sub(owner_fee)?;
+        Some(TradeFees {
+            trade_fee,
+            owner_fee,
+            amount_less_fees,
+        })
+    }
+
     pub fn validate(&self) -> Result<(), SwapError> {
         validate_fraction(self.trade_fee_numerator, self.trade_fee_denominator)?;
         validate_fraction(
@@ -74,7 +177,63 @@ impl Fees {
     }
 }
 
-// fee = amount * numerator / denominator
+impl FeeTiers {
+    /// Highest tier the source amount reaches, if any
+    pub fn tier(&self, source_amount: u128) -> Option<&FeeTier> {
+        self.tiers
+            .iter()
+            .rev()
+            .find(|tier| tier.threshold != 0 && u128::from(tier.threshold) <= source_amount)
+    }
+
+    /// Schedule charged on a trade of `source_amount`.  A tier only ever
+    /// lowers a fee, so waived owner fees stay waived.
+    pub fn fees_for(&self, fees: &Fees, source_amount: u128) -> Fees {
+        match self.tier(source_amount) {
+            Some(tier) => Fees {
+                trade_fee_numerator: fees.trade_fee_numerator.min(tier.trade_fee_numerator),
+                owner_trade_fee_numerator: fees
+                    .owner_trade_fee_numerator
+                    .min(tier.owner_trade_fee_numerator),
+                ..fees.clone()
+            },
+            None => fees.clone(),
+        }
+    }
+
+    /// Used tiers come first with strictly increasing thresholds, and their
+    /// fees never rise with the trade size
+    pub fn validate(&self, fees: &Fees) -> Result<(), SwapError> {
+        let mut previous = FeeTier {
+            threshold: 0,
+            trade_fee_numerator: fees.trade_fee_numerator,
+            owner_trade_fee_numerator: fees.owner_trade_fee_numerator,
+        };
+        let mut unused = false;
+        for tier in self.tiers.iter() {
+            if tier.threshold == 0 {
+                if *tier != FeeTier::default() {
+                    return Err(SwapError::InvalidFee);
+                }
+                unused = true;
+                continue;
+            }
+            if unused
+                || tier.threshold <= previous.threshold
+                || tier.trade_fee_numerator > previous.trade_fee_numerator
+                || tier.owner_trade_fee_numerator > previous.owner_trade_fee_numerator
+            {
+                return Err(SwapError::InvalidFee);
+            }
+            previous = *tier;
+        }
+        Ok(())
+    }
+}
+
+// fee = amount * numerator / denominator, rounded up so that splitting an
+// amount never costs less in fees than moving it all at once.  Any amount
+// charged a fee at all pays at least one token of it.
 pub fn calculate_fee(
     token_amount: u128,
     fee_numerator: u128,
@@ -83,18 +242,22 @@ pub fn calculate_fee(
     if fee_numerator == 0 || token_amount == 0 {
         Some(0)
     } else {
-        let fee = token_amount
+        token_amount
             .checked_mul(fee_numerator)?
-            .checked_div(fee_denominator)?;
-        if fee == 0 {
-            Some(1) // minimum fee of one token
-        } else {
-            Some(fee)
-        }
+            .checked_add(fee_denominator.checked_sub(1)?)?
+            .checked_div(fee_denominator)
+    }
+}
+
+fn fraction_to_bps(numerator: u64, denominator: u64) -> Option<u64> {
+    if numerator == 0 {
+        Some(0)
+    } else {
+        numerator.checked_mul(10000)?.checked_div(denominator)
     }
 }
 
-fn validate_fraction(numerator: u64, denominator: u64) -> Result<(), SwapError> {
+pub fn validate_fraction(numerator: u64, denominator: u64) -> Result<(), SwapError> {
     if denominator == 0 && numerator == 0 {
         Ok(())
     } else if numerator >= denominator {
@@ -104,6 +267,45 @@ fn validate_fraction(numerator: u64, denominator: u64) -> Result<(), SwapError>
     }
 }
 
+// ----------------------------------------------------------------------------- builder
+
+/// Builds a [Fees](struct.Fees.html) schedule, validating it on `build()`
+#[derive(Clone, Debug, Default)]
+pub struct FeesBuilder {
+    fees: Fees,
+}
+
+impl FeesBuilder {
+    pub fn trade_fee(mut self, numerator: u64, denominator: u64) -> Self {
+        self.fees.trade_fee_numerator = numerator;
+        self.fees.trade_fee_denominator = denominator;
+        self
+    }
+
+    pub fn owner_trade_fee(mut self, numerator: u64, denominator: u64) -> Self {
+        self.fees.owner_trade_fee_numerator = numerator;
+        self.fees.owner_trade_fee_denominator = denominator;
+        self
+    }
+
+    pub fn owner_withdraw_fee(mut self, numerator: u64, denominator: u64) -> Self {
+        self.fees.owner_withdraw_fee_numerator = numerator;
+        self.fees.owner_withdraw_fee_denominator = denominator;
+        self
+    }
+
+    pub fn host_fee(mut self, numerator: u64, denominator: u64) -> Self {
+        self.fees.host_fee_numerator = numerator;
+        self.fees.host_fee_denominator = denominator;
+        self
+    }
+
+    pub fn build(self) -> Result<Fees, SwapError> {
+        self.fees.validate()?;
+        Ok(self.fees)
+    }
+}
+
 // ----------------------------------------------------------------------------- program pack
 
 /// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
@@ -163,3 +365,259 @@ impl Pack for Fees {
         })
     }
 }
+
+impl IsInitialized for FeeTiers {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+
+impl Sealed for FeeTiers {}
+impl Pack for FeeTiers {
+    const LEN: usize = 24 * MAX_FEE_TIERS;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        for (output, tier) in output[..Self::LEN]
+            .chunks_exact_mut(24)
+            .zip(self.tiers.iter())
+        {
+            let output = array_mut_ref![output, 0, 24];
+            let (threshold, trade_fee_numerator, owner_trade_fee_numerator) =
+                mut_array_refs![output, 8, 8, 8];
+            *threshold = tier.threshold.to_le_bytes();
+            *trade_fee_numerator = tier.trade_fee_numerator.to_le_bytes();
+            *owner_trade_fee_numerator = tier.owner_trade_fee_numerator.to_le_bytes();
+        }
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<FeeTiers, ProgramError> {
+        let mut tiers = [FeeTier::default(); MAX_FEE_TIERS];
+        for (input, tier) in input[..Self::LEN].chunks_exact(24).zip(tiers.iter_mut()) {
+            let input = array_ref![input, 0, 24];
+            let (threshold, trade_fee_numerator, owner_trade_fee_numerator) =
+                array_refs![input, 8, 8, 8];
+            *tier = FeeTier {
+                threshold: u64::from_le_bytes(*threshold),
+                trade_fee_numerator: u64::from_le_bytes(*trade_fee_numerator),
+                owner_trade_fee_numerator: u64::from_le_bytes(*owner_trade_fee_numerator),
+            };
+        }
+        Ok(Self { tiers })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn builder_validates() {
+        let fees = Fees::builder()
+            .trade_fee(25, 10000)
+            .owner_trade_fee(5, 10000)
+            .host_fee(20, 100)
+            .build()
+            .unwrap();
+        assert_eq!(fees, Fees::standard_30bps());
+
+        let err = Fees::builder().trade_fee(10000, 10000).build().unwrap_err();
+        assert_eq!(err, SwapError::InvalidFee);
+        let err = Fees::builder().host_fee(1, 0).build().unwrap_err();
+        assert_eq!(err, SwapError::InvalidFee);
+    }
+
+    #[test]
+    fn total_trade_fee_bps() {
+        assert_eq!(Fees::standard_30bps().total_trade_fee_bps(), Some(30));
+        assert_eq!(Fees::default().total_trade_fee_bps(), Some(0));
+    }
+
+    #[test]
+    fn apply_debits_both_fees() {
+        let fees = Fees::standard_30bps();
+        let result = fees.apply(1_000_000).unwrap();
+        assert_eq!(result.trade_fee, 2_500);
+        assert_eq!(result.owner_fee, 500);
+        assert_eq!(result.amount_less_fees, 997_000);
+
+        // each fee rounds up to at least one token
+        let result = fees.apply(10).unwrap();
+        assert_eq!(result.trade_fee, 1);
+        assert_eq!(result.owner_fee, 1);
+        assert_eq!(result.amount_less_fees, 8);
+    }
+
+    #[test]
+    fn dust_trades_pay_the_fee_rate() {
+        // what a fee truncated towards zero would charge
+        fn truncated_fee(amount: u128, numerator: u64, denominator: u64) -> u128 {
+            amount * u128::from(numerator) / u128::from(denominator)
+        }
+        let fees = Fees::standard_30bps();
+        let (trades, amount) = (2_500u128, 399u128);
+
+        // one trade of 997_500 pays 2_993 in fees, 2_494 + 499
+        let whole = fees.apply(trades * amount).unwrap();
+        assert_eq!(whole.trade_fee + whole.owner_fee, 2_993);
+
+        // split into trades of 399, each under the 400 and 2_000 it takes
+        // for the LP and owner fees to reach one token, truncated fees
+        // charge nothing at all
+        let truncated = truncated_fee(amount, 25, 10000) + truncated_fee(amount, 5, 10000);
+        assert_eq!(truncated * trades, 0);
+
+        // rounded up they pay a token of each, 5_000 over the same volume
+        let dust = fees.apply(amount).unwrap();
+        assert_eq!((dust.trade_fee, dust.owner_fee), (1, 1));
+        assert_eq!((dust.trade_fee + dust.owner_fee) * trades, 5_000);
+
+        // and a trade that would be all fees swaps nothing, it's refused
+        assert_eq!(fees.apply(1), None);
+    }
+
+    #[test]
+    fn fee_tiers_lower_fees_for_large_trades() {
+        let fees = Fees::standard_30bps();
+        let tiers = FeeTiers {
+            tiers: [
+                FeeTier {
+                    threshold: 1_000_000,
+                    trade_fee_numerator: 15,
+                    owner_trade_fee_numerator: 5,
+                },
+                FeeTier {
+                    threshold: 10_000_000,
+                    trade_fee_numerator: 5,
+                    owner_trade_fee_numerator: 0,
+                },
+            ],
+        };
+        assert_eq!(tiers.validate(&fees), Ok(()));
+        assert_eq!(tiers.fees_for(&fees, 999_999), fees);
+        assert_eq!(
+            tiers.fees_for(&fees, 1_000_000).total_trade_fee_bps(),
+            Some(20)
+        );
+        assert_eq!(
+            tiers.fees_for(&fees, 50_000_000).total_trade_fee_bps(),
+            Some(5)
+        );
+        // waived owner fees stay waived
+        let exempt = fees.without_owner_fees();
+        assert_eq!(
+            tiers.fees_for(&exempt, 1_000_000).owner_trade_fee_numerator,
+            0
+        );
+
+        let mut packed = [0u8; FeeTiers::LEN];
+        tiers.pack_into_slice(&mut packed);
+        assert_eq!(FeeTiers::unpack_from_slice(&packed), Ok(tiers));
+
+        let mut rising = tiers;
+        rising.tiers[1].trade_fee_numerator = 20;
+        assert_eq!(rising.validate(&fees), Err(SwapError::InvalidFee));
+        let mut gap = tiers;
+        gap.tiers[0] = FeeTier::default();
+        assert_eq!(gap.validate(&fees), Err(SwapError::InvalidFee));
+    }
+
+    prop_compose! {
+        fn fraction()(denominator in 1..=100_000u64)(
+            numerator in 0..denominator,
+            denominator in Just(denominator),
+        ) -> (u64, u64) {
+            (numerator, denominator)
+        }
+    }
+
+    prop_compose! {
+        fn fees()(
+            trade_fee in fraction(),
+            owner_trade_fee in fraction(),
+            owner_withdraw_fee in fraction(),
+            host_fee in fraction(),
+        ) -> Fees {
+            Fees::builder()
+                .trade_fee(trade_fee.0, trade_fee.1)
+                .owner_trade_fee(owner_trade_fee.0, owner_trade_fee.1)
+                .owner_withdraw_fee(owner_withdraw_fee.0, owner_withdraw_fee.1)
+                .host_fee(host_fee.0, host_fee.1)
+                .build()
+                .unwrap()
+        }
+    }
+
+    /// Both trade fees together, as charged on a swap
+    fn total_trade_fee(fees: &Fees, amount: u128) -> u128 {
+        fees.trading_fee(amount).unwrap() + fees.owner_trading_fee(amount).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn fees_are_monotonic_in_amount(
+            fees in fees(),
+            smaller in 0..u64::MAX,
+            larger in 0..u64::MAX,
+        ) {
+            let (smaller, larger) = (smaller.min(larger) as u128, smaller.max(larger) as u128);
+            prop_assert!(total_trade_fee(&fees, smaller) <= total_trade_fee(&fees, larger));
+            prop_assert!(
+                fees.owner_withdraw_fee(smaller).unwrap()
+                    <= fees.owner_withdraw_fee(larger).unwrap()
+            );
+        }
+
+        #[test]
+        fn fees_never_exceed_the_input(fees in fees(), amount in 0..u64::MAX) {
+            let amount = amount as u128;
+            let (trade_fee, owner_fee) = (
+                fees.trading_fee(amount).unwrap(),
+                fees.owner_trading_fee(amount).unwrap(),
+            );
+            prop_assert!(trade_fee <= amount);
+            prop_assert!(owner_fee <= amount);
+            prop_assert!(fees.owner_withdraw_fee(amount).unwrap() <= amount);
+            // a trade too small to pay both fees is refused outright
+            match fees.apply(amount) {
+                Some(result) => prop_assert_eq!(
+                    result.trade_fee + result.owner_fee + result.amount_less_fees,
+                    amount
+                ),
+                None => prop_assert!(trade_fee + owner_fee > amount),
+            }
+        }
+
+        #[test]
+        fn host_fee_never_exceeds_owner_fee(fees in fees(), amount in 0..u64::MAX) {
+            let owner_fee = fees.owner_trading_fee(amount as u128).unwrap();
+            prop_assert!(fees.host_fee(owner_fee).unwrap() <= owner_fee);
+        }
+
+        #[test]
+        fn splitting_a_trade_never_saves_fees(
+            fees in fees(),
+            first in 0..u64::MAX / 2,
+            second in 0..u64::MAX / 2,
+        ) {
+            let (first, second) = (first as u128, second as u128);
+            prop_assert!(
+                total_trade_fee(&fees, first) + total_trade_fee(&fees, second)
+                    >= total_trade_fee(&fees, first + second)
+            );
+            prop_assert!(
+                fees.owner_withdraw_fee(first).unwrap() + fees.owner_withdraw_fee(second).unwrap()
+                    >= fees.owner_withdraw_fee(first + second).unwrap()
+            );
+        }
+
+        #[test]
+        fn no_amount_dodges_a_nonzero_fee(fees in fees(), amount in 1..u64::MAX) {
+            let amount = amount as u128;
+            prop_assert_eq!(fees.trading_fee(amount).unwrap() > 0, fees.trade_fee_numerator > 0);
+            prop_assert_eq!(
+                fees.owner_trading_fee(amount).unwrap() > 0,
+                fees.owner_trade_fee_numerator > 0
+            );
+        }
+    }
+}
@@ -1,5 +1,11 @@
 pub mod base;
 pub mod calculator;
+pub mod concentrated;
 pub mod constant_price;
 pub mod constant_product;
 pub mod fees;
+pub mod offset;
+#[cfg(any(test, feature = "fuzz"))]
+pub mod reference;
+pub mod stable;
+pub mod weighted;
@@ -0,0 +1,542 @@
+//! Constant product curve with a virtual token B reserve, for a project to
+//! open a pool with token A alone
+//!
+//! `x * (y + token_b_offset) = k`: the offset prices token A as if the pool
+//! held that much more token B, and traders buy token A with token B from the
+//! start.  Since the offset is value no one deposited, deposits after
+//! initialization would hand a share of it to every depositor at the
+//! creator's expense, or the other way around, so the curve refuses them.
+//!
+//! A buy only pool is a one way sale: traders buy token A with token B, and
+//! can't sell it back into the pool.
+use {
+    crate::{
+        curve::{
+            calculator::{
+                sealed, AllowedDirections, CurveCalculator, DynPack, RoundDirection,
+                SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+            },
+            constant_product::{
+                deposit_single_token_type, pool_tokens_to_trading_tokens, swap,
+                withdraw_single_token_type_exact_out,
+            },
+        },
+        error::SwapError,
+    },
+    arrayref::{array_ref, array_refs},
+    solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+    },
+    spl_math::{precise_number::PreciseNumber, uint::U256},
+};
+
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OffsetCurve {
+    /// Token B the pool prices as if it held on top of its reserve
+    pub token_b_offset: u64,
+    /// Only takes trades buying token A with token B
+    pub buy_only: bool,
+}
+
+impl OffsetCurve {
+    /// The reserves the constant product runs on, token B's with the offset
+    fn virtual_reserves(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<(u128, u128)> {
+        let token_b_offset = u128::from(self.token_b_offset);
+        match trade_direction {
+            TradeDirection::AtoB => Some((
+                swap_source_amount,
+                swap_destination_amount.checked_add(token_b_offset)?,
+            )),
+            TradeDirection::BtoA => Some((
+                swap_source_amount.checked_add(token_b_offset)?,
+                swap_destination_amount,
+            )),
+        }
+    }
+}
+
+impl sealed::Sealed for OffsetCurve {}
+
+impl CurveCalculator for OffsetCurve {
+    fn validate(&self) -> Result<(), SwapError> {
+        if self.token_b_offset == 0 {
+            Err(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Token B is made up by the offset, only token A has to be supplied
+    fn validate_supply(&self, token_a_amount: u64, _token_b_amount: u64) -> Result<(), SwapError> {
+        if token_a_amount == 0 {
+            return Err(SwapError::EmptySupply);
+        }
+        Ok(())
+    }
+
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let (swap_source_amount, swap_destination_amount) =
+            self.virtual_reserves(swap_source_amount, swap_destination_amount, trade_direction)?;
+        swap(source_amount, swap_source_amount, swap_destination_amount)
+    }
+
+    /// Selling token A pays out of the virtual reserve, bound it by the real
+    /// one: the product has to leave at least the offset in the pool,
+    /// `ceil(a * (b + offset) / (a + s)) >= offset`
+    fn max_swappable(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let unbounded = u128::MAX.checked_sub(swap_source_amount);
+        if trade_direction == TradeDirection::BtoA || self.token_b_offset <= 1 {
+            return unbounded;
+        }
+        let (swap_source_amount, swap_destination_amount) =
+            self.virtual_reserves(swap_source_amount, swap_destination_amount, trade_direction)?;
+        let invariant =
+            U256::from(swap_source_amount).checked_mul(U256::from(swap_destination_amount))?;
+        let max = invariant
+            .checked_sub(U256::one())?
+            .checked_div(U256::from(self.token_b_offset - 1))?
+            .saturating_sub(U256::from(swap_source_amount));
+        if max > U256::from(u128::MAX) {
+            unbounded
+        } else {
+            Some(max.as_u128())
+        }
+    }
+
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        withdraw_single_token_type_exact_out(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount.checked_add(u128::from(self.token_b_offset))?,
+            pool_supply,
+            trade_direction,
+            RoundDirection::Ceiling,
+        )
+    }
+
+    /// The offset is no one's to deposit against
+    fn allows_deposits(&self) -> bool {
+        false
+    }
+
+    fn allowed_directions(&self) -> AllowedDirections {
+        if self.buy_only {
+            AllowedDirections::only(TradeDirection::BtoA)
+        } else {
+            AllowedDirections::BOTH
+        }
+    }
+
+    /// Pool tokens are a share of the real reserves, the offset isn't paid out
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// The ratio of the virtual reserves
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let (swap_source_amount, swap_destination_amount) =
+            self.virtual_reserves(swap_source_amount, swap_destination_amount, trade_direction)?;
+        PreciseNumber::new(swap_destination_amount)?
+            .checked_div(&PreciseNumber::new(swap_source_amount)?)
+    }
+
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        deposit_single_token_type(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount.checked_add(u128::from(self.token_b_offset))?,
+            pool_supply,
+            trade_direction,
+            RoundDirection::Floor,
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------- program pack
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for OffsetCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for OffsetCurve {}
+impl Pack for OffsetCurve {
+    const LEN: usize = 9;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<OffsetCurve, ProgramError> {
+        let input = array_ref![input, 0, 9];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_b_offset, buy_only) = array_refs![input, 8, 1];
+        Ok(Self {
+            token_b_offset: u64::from_le_bytes(*token_b_offset),
+            buy_only: match buy_only {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+        })
+    }
+}
+
+impl DynPack for OffsetCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        output[..8].copy_from_slice(&self.token_b_offset.to_le_bytes());
+        output[8] = self.buy_only as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::base::CurveType;
+    use crate::curve::constant_product::ConstantProductCurve;
+    use crate::error::SwapError;
+    use crate::instruction::{self, DepositAllTokenTypes};
+    use crate::processor::test::{program_id, World, INITIAL_RESERVE};
+    use crate::state::SwapVersion;
+    use proptest::prelude::*;
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    #[test]
+    fn pools_open_with_token_a_alone() {
+        let curve = OffsetCurve {
+            token_b_offset: 1_000,
+            buy_only: false,
+        };
+        assert_eq!(curve.validate(), Ok(()));
+        assert_eq!(curve.validate_supply(1_000, 0), Ok(()));
+        assert_eq!(curve.validate_supply(0, 1_000), Err(SwapError::EmptySupply));
+        assert_eq!(
+            OffsetCurve {
+                token_b_offset: 0,
+                buy_only: false
+            }
+            .validate(),
+            Err(SwapError::InvalidCurve)
+        );
+        assert!(!curve.allows_deposits());
+        assert!(!curve.capabilities().supports_single_sided);
+
+        // token B buys token A as from a pool holding the offset
+        let bought = curve
+            .swap_without_fees(100, 0, 10_000, TradeDirection::BtoA)
+            .unwrap();
+        let constant_product = ConstantProductCurve
+            .swap_without_fees(100, 1_000, 10_000, TradeDirection::BtoA)
+            .unwrap();
+        assert_eq!(bought, constant_product);
+        // 10 token A per token B, the ratio of the virtual reserves
+        let price = curve.spot_price(0, 10_000, TradeDirection::BtoA).unwrap();
+        assert_eq!(price, PreciseNumber::new(10).unwrap());
+        // with no token B in the pool, there's nothing to sell token A for
+        let max = curve
+            .max_swappable(10_000, 0, TradeDirection::AtoB)
+            .unwrap();
+        assert_eq!(
+            curve.swap_without_fees(max, 10_000, 0, TradeDirection::AtoB),
+            None
+        );
+    }
+
+    #[test]
+    fn buy_only_pools_sell_token_a_alone() {
+        let curve = OffsetCurve {
+            token_b_offset: 1_000,
+            buy_only: true,
+        };
+        let allowed = curve.allowed_directions();
+        assert!(allowed.allows(TradeDirection::BtoA));
+        assert!(!allowed.allows(TradeDirection::AtoB));
+        assert_eq!(
+            OffsetCurve {
+                buy_only: false,
+                ..curve.clone()
+            }
+            .allowed_directions(),
+            AllowedDirections::BOTH
+        );
+
+        let mut packed = [0u8; OffsetCurve::LEN];
+        Pack::pack_into_slice(&curve, &mut packed);
+        assert_eq!(OffsetCurve::unpack_from_slice(&packed).unwrap(), curve);
+        packed[8] = 2;
+        assert_eq!(
+            OffsetCurve::unpack_from_slice(&packed),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn max_swappable_pays_out_at_most_the_reserve(
+            token_b_offset in 2..u64::MAX / 2,
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 0..u64::MAX / 2,
+        ) {
+            let curve = OffsetCurve { token_b_offset, buy_only: false };
+            let (swap_token_a_amount, swap_token_b_amount) =
+                (swap_token_a_amount as u128, swap_token_b_amount as u128);
+            let max = curve
+                .max_swappable(swap_token_a_amount, swap_token_b_amount, TradeDirection::AtoB)
+                .unwrap();
+            prop_assume!(max < u128::MAX - swap_token_a_amount);
+            let paid = |amount| {
+                curve
+                    .swap_without_fees(
+                        amount,
+                        swap_token_a_amount,
+                        swap_token_b_amount,
+                        TradeDirection::AtoB,
+                    )
+                    .map_or(0, |result| result.destination_amount_swapped)
+            };
+            prop_assert!(paid(max) <= swap_token_b_amount);
+            prop_assert!(paid(max + 1) > swap_token_b_amount);
+        }
+    }
+
+    #[test]
+    fn offset_pools_open_with_token_a_alone_and_refuse_deposits() {
+        let mut world = World::new();
+        world
+            .add_pool(
+                world.mints[0],
+                world.mints[1],
+                CurveType::Offset,
+                0,
+                (INITIAL_RESERVE, 0),
+            )
+            .unwrap();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = world.pools.last().unwrap();
+        let user = &world.users[0];
+        let (user_a, user_b) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let user_pool = world
+            .bank
+            .add_token_account(&pool.pool_mint, &user.wallet, 0);
+        let swap = |source, swap_source, swap_destination, destination| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                source,
+                swap_source,
+                swap_destination,
+                destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: INITIAL_RESERVE / 100,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let deposits = [
+            instruction::deposit_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user_a,
+                &user_b,
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user_pool,
+                DepositAllTokenTypes {
+                    pool_token_amount: 1,
+                    maximum_token_a_amount: u64::MAX,
+                    maximum_token_b_amount: u64::MAX,
+                },
+            )
+            .unwrap(),
+            instruction::deposit_single_token_type_exact_amount_in(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user_a,
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user_pool,
+                instruction::DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount: INITIAL_RESERVE / 100,
+                    minimum_pool_token_amount: 0,
+                },
+            )
+            .unwrap(),
+        ];
+        let sell_a = swap(&user_a, &pool.token_a, &pool.token_b, &user_b);
+        let buy_a = swap(&user_b, &pool.token_b, &pool.token_a, &user_a);
+
+        // nothing to pay token A out with until someone buys it with token B
+        assert!(world.bank.process(&sell_a).is_err());
+        assert_eq!(world.bank.process(&buy_a), Ok(()));
+        assert!(world.bank.token_account(&pool.token_b).amount > 0);
+        for deposit in deposits {
+            assert_eq!(
+                world.bank.process(&deposit),
+                Err(SwapError::UnsupportedCurveOperation.into())
+            );
+        }
+        world.assert_conserved(&trading_supplies);
+    }
+
+    #[test]
+    fn buy_only_pools_refuse_trades_selling_token_a() {
+        let mut world = World::new();
+        world
+            .add_pool(
+                world.mints[0],
+                world.mints[1],
+                CurveType::Offset,
+                0,
+                (INITIAL_RESERVE, 0),
+            )
+            .unwrap();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = world.pools.last().unwrap();
+        let user = &world.users[0];
+        let (user_a, user_b) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let swap_key = pool.swap;
+        let set_buy_only = |world: &mut World, buy_only| {
+            let data = &mut world.bank.accounts.get_mut(&swap_key).unwrap().data;
+            let mut state = match SwapVersion::unpack_versioned(data).unwrap() {
+                SwapVersion::SwapV2(state) => state,
+                _ => unreachable!(),
+            };
+            state.swap_curve.calculator = Box::new(OffsetCurve {
+                token_b_offset: INITIAL_RESERVE,
+                buy_only,
+            });
+            SwapVersion::pack(SwapVersion::SwapV2(state), data).unwrap();
+        };
+        let swap = |source, swap_source, swap_destination, destination, amount_in| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                source,
+                swap_source,
+                swap_destination,
+                destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        // a sale small enough for the token B bought to pay out
+        let sell_a = swap(
+            &user_a,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            INITIAL_RESERVE / 1_000,
+        );
+        let buy_a = swap(
+            &user_b,
+            &pool.token_b,
+            &pool.token_a,
+            &user_a,
+            INITIAL_RESERVE / 100,
+        );
+        let token_b = pool.token_b;
+
+        set_buy_only(&mut world, true);
+        assert_eq!(world.bank.process(&buy_a), Ok(()));
+        // the pool holds token B now, but won't pay it out for token A
+        assert!(world.bank.token_account(&token_b).amount > 0);
+        assert_eq!(
+            world.bank.process(&sell_a),
+            Err(SwapError::DirectionDisabled.into())
+        );
+
+        set_buy_only(&mut world, false);
+        assert_eq!(world.bank.process(&sell_a), Ok(()));
+        world.assert_conserved(&trading_supplies);
+    }
+}
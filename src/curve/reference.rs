@@ -0,0 +1,758 @@
+//! Exact rational reference implementations of the curve math, used to
+//! differentially test the on-chain integer implementations.
+//!
+//! Every quantity here is kept as a fraction of two U256 values, so nothing is
+//! ever truncated. The on-chain math is then checked against it: results must
+//! be within one unit of the exact value, and any rounding must favor the pool.
+//!
+//! The stable invariant has no closed form, so it is bracketed instead: the
+//! sign of the invariant's polynomial is computed exactly, and bisection finds
+//! the whole numbers either side of the root.
+
+use crate::curve::calculator::{RoundDirection, TradeDirection};
+use spl_math::uint::U256;
+use std::cmp::Ordering;
+
+/// Non-negative rational number, numerator / denominator
+#[derive(Clone, Copy, Debug)]
+pub struct Ratio {
+    pub numerator: U256,
+    pub denominator: U256,
+}
+
+impl Ratio {
+    pub fn new(numerator: U256, denominator: U256) -> Self {
+        Self {
+            numerator,
+            denominator,
+        }
+    }
+
+    pub fn from_u128(value: u128) -> Self {
+        Self::new(U256::from(value), U256::one())
+    }
+
+    /// Compares against a whole number without any truncation
+    pub fn cmp_u128(&self, value: u128) -> Ordering {
+        self.numerator
+            .cmp(&U256::from(value).checked_mul(self.denominator).unwrap())
+    }
+
+    /// Exact distance to a whole number, as a ratio
+    pub fn distance_to(&self, value: u128) -> Ratio {
+        let value = U256::from(value).checked_mul(self.denominator).unwrap();
+        let numerator = if self.numerator >= value {
+            self.numerator - value
+        } else {
+            value - self.numerator
+        };
+        Ratio::new(numerator, self.denominator)
+    }
+
+    /// Is the value strictly below one
+    pub fn is_less_than_one(&self) -> bool {
+        self.numerator < self.denominator
+    }
+}
+
+// ----------------------------------------------------------------------------- constant product
+
+/// Exact output of a constant product swap, `y * dx / (x + dx)`
+pub fn constant_product_swap(
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+) -> Ratio {
+    Ratio::new(
+        U256::from(swap_destination_amount) * U256::from(source_amount),
+        U256::from(swap_source_amount) + U256::from(source_amount),
+    )
+}
+
+/// Exact pro-rata share of one side of the pool, `pool_tokens * reserve / supply`
+pub fn pool_tokens_to_trading_tokens(
+    pool_tokens: u128,
+    pool_token_supply: u128,
+    swap_token_amount: u128,
+) -> Ratio {
+    Ratio::new(
+        U256::from(pool_tokens) * U256::from(swap_token_amount),
+        U256::from(pool_token_supply),
+    )
+}
+
+// ----------------------------------------------------------------------------- constant price
+
+/// Exact output of a constant price swap, where `token_b_price /
+/// token_b_price_denominator` is the amount of token A required to get 1
+/// token B
+pub fn constant_price_swap(
+    source_amount: u128,
+    (token_b_price, token_b_price_denominator): (u128, u128),
+    trade_direction: TradeDirection,
+) -> Ratio {
+    match trade_direction {
+        TradeDirection::AtoB => Ratio::new(
+            U256::from(source_amount) * U256::from(token_b_price_denominator),
+            U256::from(token_b_price),
+        ),
+        TradeDirection::BtoA => Ratio::new(
+            U256::from(source_amount) * U256::from(token_b_price),
+            U256::from(token_b_price_denominator),
+        ),
+    }
+}
+
+// ----------------------------------------------------------------------------- offset
+
+/// Exact output of an offset swap, constant product on the reserves with
+/// `token_b_offset` added to token B's
+pub fn offset_swap(
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    token_b_offset: u128,
+    trade_direction: TradeDirection,
+) -> Ratio {
+    match trade_direction {
+        TradeDirection::AtoB => constant_product_swap(
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount + token_b_offset,
+        ),
+        TradeDirection::BtoA => constant_product_swap(
+            source_amount,
+            swap_source_amount + token_b_offset,
+            swap_destination_amount,
+        ),
+    }
+}
+
+// ----------------------------------------------------------------------------- stable
+
+/// Whether reserves `x` and `y` hold an invariant of at least `d`.  The
+/// invariant only shrinks as `D` grows past the root, so this is the sign of
+/// `4xy * (A * n^n * (x + y) + D - A * n^n * D) - D^(n + 1)`, with `n = 2`,
+/// compared as its positive and negative halves to stay unsigned
+pub fn stable_holds_invariant(amp: u64, x: u128, y: u128, d: u128) -> bool {
+    let (amp, d) = (U256::from(amp), U256::from(d));
+    let xy4 = U256::from(4) * U256::from(x) * U256::from(y);
+    let leverage = amp * U256::from(4);
+    let positive = xy4 * (leverage * (U256::from(x) + U256::from(y)) + d);
+    let negative = xy4 * leverage * d + d * d * d;
+    positive >= negative
+}
+
+/// Exact invariant of the reserves, rounded down, found by bisection rather
+/// than Newton's method so it can't fail to converge.  It lies between 0 and
+/// `x + y`, where the curve meets constant sum
+pub fn stable_invariant(amp: u64, x: u128, y: u128) -> u128 {
+    let (mut low, mut high) = (0, x + y);
+    while low < high {
+        let middle = low + (high - low).div_ceil(2);
+        if stable_holds_invariant(amp, x, y, middle) {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    low
+}
+
+/// Most of the destination reserve a swap can pay out with the pool still
+/// holding an invariant of at least `d`
+pub fn stable_swap(
+    amp: u64,
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    d: u128,
+) -> u128 {
+    let new_swap_source_amount = swap_source_amount + source_amount;
+    let (mut low, mut high) = (0, swap_destination_amount - 1);
+    while low < high {
+        let middle = low + (high - low).div_ceil(2);
+        if stable_holds_invariant(
+            amp,
+            new_swap_source_amount,
+            swap_destination_amount - middle,
+            d,
+        ) {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    low
+}
+
+// ----------------------------------------------------------------------------- weighted
+
+// The weighted and concentrated curves take fractional powers and square
+// roots, so there is no exact value to hold them to.  These are plain f64
+// instead, some six orders of magnitude finer than the curves' own
+// precision, and the curves are held to within that precision of them.
+
+/// Output of a weighted swap, `out = B_o * (1 - (B_i / (B_i + A_i))^(w_i / w_o))`
+pub fn weighted_swap(
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    (source_weight, destination_weight): (u64, u64),
+) -> f64 {
+    let base = swap_source_amount as f64 / (swap_source_amount + source_amount) as f64;
+    let exponent = source_weight as f64 / destination_weight as f64;
+    swap_destination_amount as f64 * -(exponent * base.ln()).exp_m1()
+}
+
+/// Pool tokens a weighted pool mints or burns for `amount` in or out of a
+/// reserve, `supply * |(1 ± amount / reserve)^(w / W) - 1|`
+pub fn weighted_pool_tokens(
+    amount: u128,
+    swap_amount: u128,
+    pool_supply: u128,
+    (weight, other_weight): (u64, u64),
+    deposit: bool,
+) -> f64 {
+    let ratio = amount as f64 / swap_amount as f64;
+    let ratio = if deposit { ratio } else { -ratio };
+    let exponent = weight as f64 / (weight + other_weight) as f64;
+    pool_supply as f64 * (exponent * ratio.ln_1p()).exp_m1().abs()
+}
+
+// ----------------------------------------------------------------------------- concentrated
+
+/// Liquidity of a concentrated position between `p_min` and `p_max` token A
+/// per token B, the root of `q L^2 - β L - a b = 0`
+pub fn concentrated_liquidity(
+    token_a_amount: u128,
+    token_b_amount: u128,
+    (min_price, max_price): (f64, f64),
+) -> f64 {
+    let (sqrt_min, sqrt_max) = (min_price.sqrt(), max_price.sqrt());
+    let (a, b) = (token_a_amount as f64, token_b_amount as f64);
+    let spread = 1.0 - sqrt_min / sqrt_max;
+    let linear = a / sqrt_max + b * sqrt_min;
+    (linear + (linear * linear + 4.0 * spread * a * b).sqrt()) / (2.0 * spread)
+}
+
+/// Output of a concentrated swap, constant product on the reserves plus
+/// `L * √p_min` of token A and `L / √p_max` of token B
+pub fn concentrated_swap(
+    source_amount: u128,
+    swap_source_amount: u128,
+    swap_destination_amount: u128,
+    (min_price, max_price): (f64, f64),
+    trade_direction: TradeDirection,
+) -> f64 {
+    let (token_a_amount, token_b_amount) = match trade_direction {
+        TradeDirection::AtoB => (swap_source_amount, swap_destination_amount),
+        TradeDirection::BtoA => (swap_destination_amount, swap_source_amount),
+    };
+    let liquidity = concentrated_liquidity(token_a_amount, token_b_amount, (min_price, max_price));
+    let (token_a_offset, token_b_offset) =
+        (liquidity * min_price.sqrt(), liquidity / max_price.sqrt());
+    let (source_offset, destination_offset) = match trade_direction {
+        TradeDirection::AtoB => (token_a_offset, token_b_offset),
+        TradeDirection::BtoA => (token_b_offset, token_a_offset),
+    };
+    let virtual_source_amount = swap_source_amount as f64 + source_offset;
+    let virtual_destination_amount = swap_destination_amount as f64 + destination_offset;
+    virtual_destination_amount * source_amount as f64
+        / (virtual_source_amount + source_amount as f64)
+}
+
+/// Pool tokens a concentrated pool mints or burns for `amount` in or out of
+/// a reserve, `supply * |L' - L| / L`
+pub fn concentrated_pool_tokens(
+    (token_a_amount, token_b_amount): (u128, u128),
+    (new_token_a_amount, new_token_b_amount): (u128, u128),
+    pool_supply: u128,
+    range: (f64, f64),
+) -> f64 {
+    let liquidity = concentrated_liquidity(token_a_amount, token_b_amount, range);
+    let new_liquidity = concentrated_liquidity(new_token_a_amount, new_token_b_amount, range);
+    pool_supply as f64 * (new_liquidity - liquidity).abs() / liquidity
+}
+
+/// Asserts that `actual` is within one unit of `exact`, rounded in the given
+/// direction. Floor means the pool pays out at most the exact value, ceiling
+/// means the pool takes in at least the exact value.
+pub fn assert_rounded(actual: u128, exact: &Ratio, round_direction: RoundDirection) {
+    match round_direction {
+        RoundDirection::Floor => assert_ne!(
+            exact.cmp_u128(actual),
+            Ordering::Less,
+            "{} rounds against the pool, exact value {:?}",
+            actual,
+            exact
+        ),
+        RoundDirection::Ceiling => assert_ne!(
+            exact.cmp_u128(actual),
+            Ordering::Greater,
+            "{} rounds against the pool, exact value {:?}",
+            actual,
+            exact
+        ),
+    }
+    assert!(
+        exact.distance_to(actual).is_less_than_one(),
+        "{} is more than one unit away from exact value {:?}",
+        actual,
+        exact
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::calculator::CurveCalculator;
+    use crate::curve::concentrated::ConcentratedCurve;
+    use crate::curve::constant_price::ConstantPriceCurve;
+    use crate::curve::constant_product::{self, ConstantProductCurve};
+    use crate::curve::offset::OffsetCurve;
+    use crate::curve::stable::{self, StableCurve, MAX_AMP, MIN_AMP};
+    use crate::curve::weighted::{WeightedCurve, MAX_RATIO_DENOMINATOR, MAX_RATIO_NUMERATOR};
+    use proptest::prelude::*;
+
+    /// `pool_supply * numerator / denominator`, exactly
+    fn share(pool_supply: u128, numerator: u128, denominator: u128) -> Ratio {
+        Ratio::new(
+            U256::from(pool_supply) * U256::from(numerator),
+            U256::from(denominator),
+        )
+    }
+
+    #[test]
+    fn stable_newton_method_can_fail_to_converge() {
+        // Newton's integer steps bounce between two values for a pool this
+        // lopsided, though the invariant is there to be found
+        let curve = StableCurve { amp: 1 };
+        assert_eq!(stable::compute_d(1, 30_000, 1), None);
+        let d = stable_invariant(1, 30_000, 1);
+        assert!(stable_holds_invariant(1, 30_000, 1, d));
+        assert!(!stable_holds_invariant(1, 30_000, 1, d + 1));
+
+        // the pool refuses to trade or take deposits rather than guess
+        for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+            assert_eq!(curve.swap_without_fees(1, 30_000, 1, trade_direction), None);
+            assert_eq!(
+                curve.deposit_single_token_type(1_000, 30_000, 1, 1_000, trade_direction),
+                None
+            );
+        }
+        // and won't trade into such reserves either, though 998 tokens
+        // could be paid out
+        let d = stable_invariant(1, 1_000, 1_000);
+        assert_eq!(stable_swap(1, 16_384, 1_000, 1_000, d), 998);
+        assert_eq!(stable::compute_d(1, 1_000 + 16_384, 2), None);
+        assert_eq!(
+            curve.swap_without_fees(16_384, 1_000, 1_000, TradeDirection::AtoB),
+            None
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn constant_product_swap_differential(
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1..u64::MAX,
+            swap_destination_amount in 1..u64::MAX,
+        ) {
+            let source_amount = source_amount as u128;
+            let swap_source_amount = swap_source_amount as u128;
+            let swap_destination_amount = swap_destination_amount as u128;
+            let exact = constant_product_swap(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+            );
+            match constant_product::swap(source_amount, swap_source_amount, swap_destination_amount) {
+                Some(result) => {
+                    assert_rounded(result.destination_amount_swapped, &exact, RoundDirection::Floor);
+                    assert!(result.source_amount_swapped <= source_amount);
+
+                    // the invariant can only ever grow in the pool's favor
+                    let invariant = U256::from(swap_source_amount) * U256::from(swap_destination_amount);
+                    let new_invariant = U256::from(swap_source_amount + result.source_amount_swapped)
+                        * U256::from(swap_destination_amount - result.destination_amount_swapped);
+                    assert!(new_invariant >= invariant);
+                }
+                // only acceptable when the exact output is below one token
+                None => assert!(exact.is_less_than_one()),
+            }
+        }
+
+        #[test]
+        fn constant_product_pool_tokens_differential(
+            pool_tokens in 1..u64::MAX,
+            pool_token_supply in 1..u64::MAX,
+            swap_token_a_amount in 1..u64::MAX,
+            swap_token_b_amount in 1..u64::MAX,
+        ) {
+            let pool_tokens = std::cmp::min(pool_tokens, pool_token_supply) as u128;
+            let pool_token_supply = pool_token_supply as u128;
+            let curve = ConstantProductCurve {};
+            for round_direction in [RoundDirection::Floor, RoundDirection::Ceiling].iter() {
+                let results = curve
+                    .pool_tokens_to_trading_tokens(
+                        pool_tokens,
+                        pool_token_supply,
+                        swap_token_a_amount as u128,
+                        swap_token_b_amount as u128,
+                        *round_direction,
+                    )
+                    .unwrap();
+                for (actual, reserve) in [
+                    (results.token_a_amount, swap_token_a_amount),
+                    (results.token_b_amount, swap_token_b_amount),
+                ]
+                .iter()
+                {
+                    let exact =
+                        pool_tokens_to_trading_tokens(pool_tokens, pool_token_supply, *reserve as u128);
+                    // ceiling deliberately returns 0 for sub-token amounts, to be rejected later
+                    if *actual != 0 || *round_direction == RoundDirection::Floor {
+                        assert_rounded(*actual, &exact, *round_direction);
+                    } else {
+                        assert!(exact.is_less_than_one());
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn stable_swap_differential(
+            amp in MIN_AMP..=MAX_AMP,
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1_000..u64::MAX / 4,
+            swap_destination_amount in 1_000..u64::MAX / 4,
+        ) {
+            let (source_amount, swap_source_amount, swap_destination_amount) = (
+                source_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+            );
+            let curve = StableCurve { amp };
+            let d = stable_invariant(amp, swap_source_amount, swap_destination_amount);
+            // never past the invariant, rounded down, of the reserves
+            let most = stable_swap(amp, source_amount, swap_source_amount, swap_destination_amount, d);
+            // and at least what keeps the invariant a token over it
+            let least = stable_swap(amp, source_amount, swap_source_amount, swap_destination_amount, d + 1);
+            match curve.swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ) {
+                Some(result) => {
+                    prop_assert_eq!(result.source_amount_swapped, source_amount);
+                    prop_assert!(
+                        result.destination_amount_swapped <= most,
+                        "{} pays past the invariant, at most {}", result.destination_amount_swapped, most
+                    );
+                    prop_assert!(
+                        result.destination_amount_swapped + 2 >= least,
+                        "{} is short of {}", result.destination_amount_swapped, least
+                    );
+                }
+                // Newton's method not converging on the reserves before or
+                // after, or a trade too small to pay out
+                None => prop_assert!(
+                    most <= 2
+                        || stable::compute_d(amp, swap_source_amount, swap_destination_amount).is_none()
+                        || stable::compute_d(
+                            amp,
+                            swap_source_amount + source_amount,
+                            swap_destination_amount - most,
+                        )
+                        .is_none()
+                ),
+            }
+        }
+
+        #[test]
+        fn stable_deposit_and_withdraw_differential(
+            amp in MIN_AMP..=MAX_AMP,
+            source_amount in 1..u64::MAX / 4,
+            swap_source_amount in 1_000_000_000..u64::MAX / 40,
+            reserve_ratio in 1..=100u64,
+            pool_supply in 1_000_000..u64::MAX / 4,
+        ) {
+            // the reserves within a factor of 10 of each other, where the
+            // curve trades near its peg
+            let swap_source_amount = swap_source_amount as u128;
+            let swap_destination_amount = swap_source_amount * reserve_ratio as u128 / 10;
+            // a pool token worth at least a token, so the bracket around the
+            // exact invariants stays within a few pool tokens
+            let pool_supply = (pool_supply as u128).min(swap_source_amount.min(swap_destination_amount));
+            let source_amount = (source_amount as u128).min(swap_source_amount - 1);
+            let curve = StableCurve { amp };
+            let d = stable_invariant(amp, swap_source_amount, swap_destination_amount);
+
+            // minted for at most the exact growth of the invariant, which
+            // lies between its rounded down values less and plus one
+            let grown = stable_invariant(amp, swap_source_amount + source_amount, swap_destination_amount);
+            if let Some(minted) = curve.deposit_single_token_type(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                pool_supply,
+                TradeDirection::AtoB,
+            ) {
+                let most = share(pool_supply, grown + 1 - d, d);
+                prop_assert_ne!(most.cmp_u128(minted), Ordering::Less);
+                let least = share(pool_supply, grown.saturating_sub(d + 1), d + 1);
+                prop_assert_ne!(least.cmp_u128(minted + 2), Ordering::Greater);
+            }
+
+            // burnt for at least the exact shrinking of the invariant
+            let shrunk = stable_invariant(amp, swap_source_amount - source_amount, swap_destination_amount);
+            if let Some(burnt) = curve.withdraw_single_token_type_exact_out(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                pool_supply,
+                TradeDirection::AtoB,
+            ) {
+                let least = share(pool_supply, d.saturating_sub(shrunk + 1), d + 1);
+                prop_assert_ne!(least.cmp_u128(burnt), Ordering::Greater);
+                let most = share(pool_supply, d + 1 - shrunk, d);
+                prop_assert_ne!(most.cmp_u128(burnt.saturating_sub(2)), Ordering::Less);
+            }
+        }
+
+        #[test]
+        fn offset_swap_differential(
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1..u64::MAX,
+            swap_destination_amount in 1..u64::MAX,
+            token_b_offset in 1..u64::MAX,
+        ) {
+            let (source_amount, swap_source_amount, swap_destination_amount) = (
+                source_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+            );
+            let curve = OffsetCurve { token_b_offset, buy_only: false };
+            let token_b_offset = token_b_offset as u128;
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                let exact = offset_swap(
+                    source_amount,
+                    swap_source_amount,
+                    swap_destination_amount,
+                    token_b_offset,
+                    trade_direction,
+                );
+                match curve.swap_without_fees(
+                    source_amount,
+                    swap_source_amount,
+                    swap_destination_amount,
+                    trade_direction,
+                ) {
+                    Some(result) => {
+                        assert_rounded(result.destination_amount_swapped, &exact, RoundDirection::Floor);
+                        prop_assert!(result.source_amount_swapped <= source_amount);
+                    }
+                    // the offset reserves' product past u128, or a trade too
+                    // small to pay out
+                    None => prop_assert!(
+                        exact.is_less_than_one()
+                            || (swap_source_amount + token_b_offset)
+                                .checked_mul(swap_destination_amount + token_b_offset)
+                                .is_none()
+                    ),
+                }
+            }
+        }
+
+        #[test]
+        fn weighted_differential(
+            token_a_weight in 1..=99u64,
+            token_b_weight in 1..=99u64,
+            amount_ratio in 1..=1_000_000u64,
+            swap_source_amount in 1_000_000..u64::MAX / 4,
+            swap_destination_amount in 1_000_000..u64::MAX / 4,
+            pool_supply in 1_000_000..u64::MAX / 4,
+        ) {
+            let curve = WeightedCurve { token_a_weight, token_b_weight };
+            let (swap_source_amount, swap_destination_amount, pool_supply) = (
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+                pool_supply as u128,
+            );
+            // up to the share of the reserve a single operation may move
+            let amount = swap_source_amount * MAX_RATIO_NUMERATOR / MAX_RATIO_DENOMINATOR
+                * amount_ratio as u128
+                / 1_000_000;
+            prop_assume!(amount > 0);
+            let weights = (token_a_weight, token_b_weight);
+
+            // short of the reference by at most the powers' margin of 5e-10
+            // and their own error, and never over it
+            let reference = weighted_swap(amount, swap_source_amount, swap_destination_amount, weights);
+            if let Some(result) = curve.swap_without_fees(
+                amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ) {
+                let paid = result.destination_amount_swapped as f64;
+                prop_assert!(paid <= reference * (1.0 + 1e-12) + 1.0, "{} over {}", paid, reference);
+                prop_assert!(
+                    paid >= reference - swap_destination_amount as f64 * 1e-9 - 1.0,
+                    "{} short of {}", paid, reference
+                );
+            } else {
+                prop_assert!(reference < 1.0 + swap_destination_amount as f64 * 1e-9);
+            }
+
+            let reference = weighted_pool_tokens(amount, swap_source_amount, pool_supply, weights, true);
+            let minted = curve
+                .deposit_single_token_type(
+                    amount,
+                    swap_source_amount,
+                    swap_destination_amount,
+                    pool_supply,
+                    TradeDirection::AtoB,
+                )
+                .unwrap() as f64;
+            prop_assert!(minted <= reference * (1.0 + 1e-12) + 1.0, "{} over {}", minted, reference);
+            prop_assert!(
+                minted >= reference - pool_supply as f64 * 3e-9 - 1.0,
+                "{} short of {}", minted, reference
+            );
+
+            let reference = weighted_pool_tokens(amount, swap_source_amount, pool_supply, weights, false);
+            let burnt = curve
+                .withdraw_single_token_type_exact_out(
+                    amount,
+                    swap_source_amount,
+                    swap_destination_amount,
+                    pool_supply,
+                    TradeDirection::AtoB,
+                )
+                .unwrap() as f64;
+            prop_assert!(burnt >= reference * (1.0 - 1e-12) - 1.0, "{} short of {}", burnt, reference);
+            prop_assert!(
+                burnt <= reference + pool_supply as f64 * 1e-9 + 1.0,
+                "{} over {}", burnt, reference
+            );
+        }
+
+        #[test]
+        fn concentrated_differential(
+            spread in 2..1_000u64,
+            amount_ratio in 1..=1_000u64,
+            swap_token_a_amount in 1_000_000_000..u64::MAX / 4,
+            swap_token_b_amount in 1_000_000_000..u64::MAX / 4,
+            pool_supply in 1_000_000..u64::MAX / 4,
+        ) {
+            // between `1 / spread` and `spread` token A per token B
+            let curve = ConcentratedCurve {
+                min_token_b_price: 1,
+                min_token_b_price_denominator: spread,
+                max_token_b_price: spread,
+                max_token_b_price_denominator: 1,
+            };
+            let range = (1.0 / spread as f64, spread as f64);
+            let (swap_token_a_amount, swap_token_b_amount, pool_supply) = (
+                swap_token_a_amount as u128,
+                swap_token_b_amount as u128,
+                pool_supply as u128,
+            );
+            // a thousandth to all of a tenth of the reserve
+            let amount = swap_token_a_amount / 10_000 * amount_ratio as u128;
+
+            // within the liquidity's precision of a billionth
+            let reference = concentrated_swap(
+                amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                range,
+                TradeDirection::AtoB,
+            );
+            if let Some(result) = curve.swap_without_fees(
+                amount,
+                swap_token_a_amount,
+                swap_token_b_amount,
+                TradeDirection::AtoB,
+            ) {
+                let paid = result.destination_amount_swapped as f64;
+                prop_assert!(paid <= reference * (1.0 + 2e-9) + 1.0, "{} over {}", paid, reference);
+                prop_assert!(paid >= reference * (1.0 - 2e-9) - 1.0, "{} short of {}", paid, reference);
+            } else {
+                // past the top of the range
+                prop_assert!(reference >= swap_token_b_amount as f64 * (1.0 - 2e-9));
+            }
+
+            // the liquidity's tolerance goes to the pool either way
+            let reserves = (swap_token_a_amount, swap_token_b_amount);
+            let reference = concentrated_pool_tokens(
+                reserves,
+                (swap_token_a_amount + amount, swap_token_b_amount),
+                pool_supply,
+                range,
+            );
+            let minted = curve
+                .deposit_single_token_type(
+                    amount,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    pool_supply,
+                    TradeDirection::AtoB,
+                )
+                .unwrap() as f64;
+            prop_assert!(minted <= reference * (1.0 + 1e-11) + 1.0, "{} over {}", minted, reference);
+            prop_assert!(minted >= reference * (1.0 - 3e-9) - 1.0, "{} short of {}", minted, reference);
+
+            let reference = concentrated_pool_tokens(
+                reserves,
+                (swap_token_a_amount - amount, swap_token_b_amount),
+                pool_supply,
+                range,
+            );
+            let burnt = curve
+                .withdraw_single_token_type_exact_out(
+                    amount,
+                    swap_token_a_amount,
+                    swap_token_b_amount,
+                    pool_supply,
+                    TradeDirection::AtoB,
+                )
+                .unwrap() as f64;
+            prop_assert!(burnt >= reference * (1.0 - 1e-11) - 1.0, "{} short of {}", burnt, reference);
+            prop_assert!(burnt <= reference * (1.0 + 3e-9) + 1.0, "{} over {}", burnt, reference);
+        }
+
+        #[test]
+        fn constant_price_swap_differential(
+            source_amount in 1..u64::MAX,
+            token_b_price in 1..u64::MAX,
+            token_b_price_denominator in 0..u64::MAX,
+        ) {
+            let source_amount = source_amount as u128;
+            let curve = ConstantPriceCurve { token_b_price, token_b_price_denominator };
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA].iter() {
+                let exact = constant_price_swap(source_amount, curve.price(), *trade_direction);
+                match curve.swap_without_fees(source_amount, 0, 0, *trade_direction) {
+                    Some(result) => {
+                        assert_rounded(result.destination_amount_swapped, &exact, RoundDirection::Floor);
+                        assert!(result.source_amount_swapped <= source_amount);
+                        // the source actually taken must pay for the output in full
+                        let paid = constant_price_swap(
+                            result.source_amount_swapped,
+                            curve.price(),
+                            *trade_direction,
+                        );
+                        assert_ne!(
+                            paid.cmp_u128(result.destination_amount_swapped),
+                            Ordering::Less
+                        );
+                    }
+                    None => assert!(exact.is_less_than_one()),
+                }
+            }
+        }
+    }
+}
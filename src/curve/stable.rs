@@ -0,0 +1,524 @@
+//! StableSwap curve, Curve's invariant for pairs trading around 1:1
+//!
+//! `A * n^n * (x + y) + D = A * D * n^n + D^(n + 1) / (n^n * x * y)`, with
+//! `n = 2` tokens.  The amplification coefficient `A` bends the curve from
+//! constant product, at 0, towards constant sum as it grows, so trades near
+//! the peg slip far less than on `x * y = k` while the price still runs away
+//! as a reserve empties.
+use {
+    crate::{
+        curve::{
+            calculator::{
+                map_zero_to_none, sealed, CurveCalculator, DynPack, RoundDirection,
+                SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+            },
+            constant_product::pool_tokens_to_trading_tokens,
+        },
+        error::SwapError,
+    },
+    arrayref::array_ref,
+    solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+    },
+    spl_math::{precise_number::PreciseNumber, uint::U256},
+};
+
+/// Least amplification coefficient
+pub const MIN_AMP: u64 = 1;
+/// Most amplification coefficient, past which the curve is constant sum
+/// until a reserve is all but empty
+pub const MAX_AMP: u64 = 1_000_000;
+
+/// Tokens in the pool, `n` in the invariant
+const N_COINS: u64 = 2;
+
+/// Newton's method steps before giving up on converging, as Curve does.  It
+/// takes a handful from the starting guesses below, but with one reserve
+/// orders of magnitude past the other the integer steps can bounce between
+/// two values forever
+const ITERATIONS: usize = 255;
+
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StableCurve {
+    /// Amplification coefficient, between `MIN_AMP` and `MAX_AMP`
+    pub amp: u64,
+}
+
+/// `A * n^n`
+fn leverage(amp: u64) -> Option<U256> {
+    U256::from(amp).checked_mul(U256::from(N_COINS * N_COINS))
+}
+
+fn to_u128(value: U256) -> Option<u128> {
+    if value > U256::from(u128::MAX) {
+        None
+    } else {
+        Some(value.as_u128())
+    }
+}
+
+/// Whether two steps of Newton's method are within a token of each other
+fn converged(next: U256, previous: U256) -> bool {
+    let difference = if next > previous {
+        next - previous
+    } else {
+        previous - next
+    };
+    difference <= U256::one()
+}
+
+/// The invariant `D` of the given reserves, the total the pool would hold
+/// with both reserves equal.  `None` if either reserve is empty or Newton's
+/// method doesn't converge.
+pub fn compute_d(amp: u64, swap_token_a_amount: u128, swap_token_b_amount: u128) -> Option<u128> {
+    let (token_a, token_b) = (
+        U256::from(swap_token_a_amount),
+        U256::from(swap_token_b_amount),
+    );
+    let n_coins = U256::from(N_COINS);
+    let sum = token_a.checked_add(token_b)?;
+    let leverage = leverage(amp)?;
+    let mut d = sum;
+    for _ in 0..ITERATIONS {
+        // D^(n + 1) / (n^n * x * y), a division at a time to stay in range
+        let d_product = d
+            .checked_mul(d)?
+            .checked_div(token_a.checked_mul(n_coins)?)?
+            .checked_mul(d)?
+            .checked_div(token_b.checked_mul(n_coins)?)?;
+        // D = (A * n^n * S + n * D_P) * D / ((A * n^n - 1) * D + (n + 1) * D_P)
+        let numerator = leverage
+            .checked_mul(sum)?
+            .checked_add(d_product.checked_mul(n_coins)?)?
+            .checked_mul(d)?;
+        let denominator = leverage
+            .checked_sub(U256::one())?
+            .checked_mul(d)?
+            .checked_add(d_product.checked_mul(n_coins.checked_add(U256::one())?)?)?;
+        let previous = d;
+        d = numerator.checked_div(denominator)?;
+        if converged(d, previous) {
+            return to_u128(d);
+        }
+    }
+    None
+}
+
+/// The other reserve that keeps the invariant at `d` with `new_source_amount`
+/// in the pool, solving `y^2 + (x + D / (A * n^n) - D) * y = D^(n + 1) / (n^(2n) * x * A)`
+/// for `y`
+pub fn compute_new_destination_amount(amp: u64, new_source_amount: u128, d: u128) -> Option<u128> {
+    let (source, d) = (U256::from(new_source_amount), U256::from(d));
+    let n_coins = U256::from(N_COINS);
+    let leverage = leverage(amp)?;
+    let c = d
+        .checked_mul(d)?
+        .checked_div(source.checked_mul(n_coins)?)?
+        .checked_mul(d)?
+        .checked_div(leverage.checked_mul(n_coins)?)?;
+    let b = source.checked_add(d.checked_div(leverage)?)?;
+    let mut y = d;
+    for _ in 0..ITERATIONS {
+        // y = (y^2 + c) / (2y + b - D)
+        let previous = y;
+        y = y
+            .checked_mul(y)?
+            .checked_add(c)?
+            .checked_div(y.checked_mul(n_coins)?.checked_add(b)?.checked_sub(d)?)?;
+        if converged(y, previous) {
+            return to_u128(y);
+        }
+    }
+    None
+}
+
+impl StableCurve {
+    /// Pool tokens the change of the invariant from `d` to `new_d` is worth,
+    /// in proportion to the supply
+    fn pool_tokens_for(
+        d: u128,
+        new_d: u128,
+        pool_supply: u128,
+        round_direction: RoundDirection,
+    ) -> Option<u128> {
+        let change = new_d.max(d) - new_d.min(d);
+        let value = U256::from(pool_supply).checked_mul(U256::from(change))?;
+        let d = U256::from(d);
+        let pool_tokens = match round_direction {
+            RoundDirection::Floor => value.checked_div(d)?,
+            RoundDirection::Ceiling => value
+                .checked_add(d.checked_sub(U256::one())?)?
+                .checked_div(d)?,
+        };
+        to_u128(pool_tokens)
+    }
+}
+
+impl sealed::Sealed for StableCurve {}
+
+impl CurveCalculator for StableCurve {
+    fn validate(&self) -> Result<(), SwapError> {
+        if (MIN_AMP..=MAX_AMP).contains(&self.amp) {
+            Ok(())
+        } else {
+            Err(SwapError::InvalidCurve)
+        }
+    }
+
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        let d = compute_d(self.amp, swap_source_amount, swap_destination_amount)?;
+        let new_swap_source_amount = swap_source_amount.checked_add(source_amount)?;
+        // a token over the root Newton's method lands on, so the rounding
+        // never pays out past the invariant
+        let new_swap_destination_amount =
+            compute_new_destination_amount(self.amp, new_swap_source_amount, d)?.checked_add(1)?;
+        let destination_amount_swapped =
+            map_zero_to_none(swap_destination_amount.checked_sub(new_swap_destination_amount)?)?;
+        // refuse trades into reserves the invariant can't be found for, which
+        // would leave the pool unable to price anything after
+        compute_d(
+            self.amp,
+            new_swap_source_amount,
+            new_swap_destination_amount,
+        )?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Pool tokens are minted for the growth of the invariant
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let d = compute_d(self.amp, swap_token_a_amount, swap_token_b_amount)?;
+        let (new_swap_token_a_amount, new_swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_token_a_amount.checked_add(source_amount)?,
+                swap_token_b_amount,
+            ),
+            TradeDirection::BtoA => (
+                swap_token_a_amount,
+                swap_token_b_amount.checked_add(source_amount)?,
+            ),
+        };
+        let new_d = compute_d(self.amp, new_swap_token_a_amount, new_swap_token_b_amount)?;
+        Self::pool_tokens_for(d, new_d, pool_supply, RoundDirection::Floor)
+    }
+
+    /// Pool tokens are burnt for the shrinking of the invariant
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let d = compute_d(self.amp, swap_token_a_amount, swap_token_b_amount)?;
+        let (new_swap_token_a_amount, new_swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                swap_token_a_amount.checked_sub(source_amount)?,
+                swap_token_b_amount,
+            ),
+            TradeDirection::BtoA => (
+                swap_token_a_amount,
+                swap_token_b_amount.checked_sub(source_amount)?,
+            ),
+        };
+        let new_d = compute_d(self.amp, new_swap_token_a_amount, new_swap_token_b_amount)?;
+        Self::pool_tokens_for(d, new_d, pool_supply, RoundDirection::Ceiling)
+    }
+
+    /// Both tokens in proportion to the reserves, as for constant product
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// The slope of the invariant, `-dy/dx`: with `x` the source reserve and
+    /// `y` the destination, `(A * n^n * x * y + D^3 / (n^n * x)) / (A * n^n * x * y + D^3 / (n^n * y))`
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let d = U256::from(compute_d(
+            self.amp,
+            swap_source_amount,
+            swap_destination_amount,
+        )?);
+        let (source, destination) = (
+            U256::from(swap_source_amount),
+            U256::from(swap_destination_amount),
+        );
+        let n_n = U256::from(N_COINS * N_COINS);
+        let d_cubed = d.checked_mul(d)?.checked_mul(d)?;
+        let leveraged = leverage(self.amp)?
+            .checked_mul(source)?
+            .checked_mul(destination)?;
+        let mut numerator =
+            leveraged.checked_add(d_cubed.checked_div(n_n.checked_mul(source)?)?)?;
+        let mut denominator =
+            leveraged.checked_add(d_cubed.checked_div(n_n.checked_mul(destination)?)?)?;
+        // only the ratio matters, keep both where the division stays precise
+        while numerator.bits() > 96 || denominator.bits() > 96 {
+            numerator >>= 1;
+            denominator >>= 1;
+        }
+        PreciseNumber::new(to_u128(numerator)?)?
+            .checked_div(&PreciseNumber::new(to_u128(denominator)?)?)
+    }
+}
+
+// ----------------------------------------------------------------------------- program pack
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for StableCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for StableCurve {}
+impl Pack for StableCurve {
+    const LEN: usize = 8;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<StableCurve, ProgramError> {
+        let amp = array_ref![input, 0, 8];
+        Ok(Self {
+            amp: u64::from_le_bytes(*amp),
+        })
+    }
+}
+
+impl DynPack for StableCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        output[..8].copy_from_slice(&self.amp.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::INITIAL_SWAP_POOL_AMOUNT;
+    use crate::curve::base::{CurveType, SwapCurve};
+    use crate::curve::calculator::test::{
+        check_deposit_token_conversion, check_single_sided_round_trip, round_trip_fee_settings,
+    };
+    use crate::curve::calculator::TradeDirection;
+    use crate::curve::constant_product::ConstantProductCurve;
+    use crate::processor::test::{World, INITIAL_RESERVE, INITIAL_USER_BALANCE};
+    use proptest::prelude::*;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    #[test]
+    fn trades_near_the_peg_slip_less_than_constant_product() {
+        let reserve = 1_000_000_000_000;
+        let amount = reserve / 100;
+        let out = |curve: &dyn CurveCalculator| {
+            curve
+                .swap_without_fees(amount, reserve, reserve, TradeDirection::AtoB)
+                .unwrap()
+                .destination_amount_swapped
+        };
+        let constant_product = out(&ConstantProductCurve);
+        let low = out(&StableCurve { amp: 1 });
+        let high = out(&StableCurve { amp: 1_000 });
+        assert!(constant_product < low && low < high && high < amount);
+        // within a basis point of 1:1
+        assert!(high > amount - amount / 10_000);
+
+        let price = StableCurve { amp: 100 }
+            .spot_price(reserve, reserve, TradeDirection::AtoB)
+            .unwrap();
+        assert!(price.almost_eq(&PreciseNumber::new(1).unwrap(), U256::from(1_000u64)));
+    }
+
+    #[test]
+    fn amplification_is_bounded() {
+        assert_eq!(
+            StableCurve { amp: 0 }.validate(),
+            Err(SwapError::InvalidCurve)
+        );
+        assert_eq!(StableCurve { amp: MIN_AMP }.validate(), Ok(()));
+        assert_eq!(StableCurve { amp: MAX_AMP }.validate(), Ok(()));
+        assert_eq!(
+            StableCurve { amp: MAX_AMP + 1 }.validate(),
+            Err(SwapError::InvalidCurve)
+        );
+    }
+
+    #[test]
+    fn price_runs_away_as_a_reserve_empties() {
+        let curve = StableCurve { amp: 100 };
+        let price = |source: u128, destination: u128| {
+            curve
+                .spot_price(source, destination, TradeDirection::AtoB)
+                .unwrap()
+        };
+        // selling into a pool long on the source token pays less and less
+        let balanced = price(1_000_000, 1_000_000);
+        let skewed = price(1_900_000, 100_000);
+        let drained = price(1_999_000, 1_000);
+        assert!(balanced.greater_than(&skewed) && skewed.greater_than(&drained));
+        // and the pool can't be emptied
+        if let Some(result) =
+            curve.swap_without_fees(u64::MAX as u128, 1_000_000, 1_000_000, TradeDirection::AtoB)
+        {
+            assert!(result.destination_amount_swapped < 1_000_000);
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn swaps_never_shrink_the_invariant(
+            amp in MIN_AMP..=MAX_AMP,
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1_000..u64::MAX,
+            swap_destination_amount in 1_000..u64::MAX,
+        ) {
+            let curve = StableCurve { amp };
+            let (source_amount, swap_source_amount, swap_destination_amount) = (
+                source_amount as u128,
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+            );
+            let d = compute_d(amp, swap_source_amount, swap_destination_amount).unwrap();
+            if let Some(result) = curve.swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ) {
+                let new_d = compute_d(
+                    amp,
+                    swap_source_amount + result.source_amount_swapped,
+                    swap_destination_amount - result.destination_amount_swapped,
+                )
+                .unwrap();
+                // the invariant is itself a token out, either way
+                prop_assert!(new_d + 1 >= d);
+            }
+        }
+
+        #[test]
+        fn deposit_token_conversion(
+            amp in MIN_AMP..=1_000,
+            source_token_amount in 1_000..1_000_000u64,
+            swap_source_amount in 1_000_000_000..u64::MAX / 4,
+            swap_destination_amount in 1_000_000_000..u64::MAX / 4,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            // both reserves within a factor of 10 of each other, where the
+            // curve trades near its peg
+            prop_assume!(swap_source_amount / 10 <= swap_destination_amount);
+            prop_assume!(swap_destination_amount / 10 <= swap_source_amount);
+            let curve = StableCurve { amp };
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                check_deposit_token_conversion(
+                    &curve,
+                    source_token_amount as u128,
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128,
+                    trade_direction,
+                    pool_supply,
+                    // the invariant rounds a token either way on each step
+                    100,
+                );
+            }
+        }
+
+        #[test]
+        fn single_sided_round_trip(
+            amp in MIN_AMP..=1_000,
+            source_token_amount in 1_000..1_000_000u64,
+            swap_source_amount in 1_000_000_000..u64::MAX / 4,
+            swap_destination_amount in 1_000_000_000..u64::MAX / 4,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            prop_assume!(swap_source_amount / 10 <= swap_destination_amount);
+            prop_assume!(swap_destination_amount / 10 <= swap_source_amount);
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Stable,
+                calculator: Box::new(StableCurve { amp }),
+            };
+            for fees in round_trip_fee_settings().iter() {
+                for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                    check_single_sided_round_trip(
+                        &swap_curve,
+                        fees,
+                        source_token_amount as u128,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                        pool_supply,
+                        100,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn stable_pools_slip_less_than_constant_product_near_the_peg() {
+        let mut world = World::new();
+        world
+            .add_pool(
+                world.mints[0],
+                world.mints[1],
+                CurveType::Stable,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE),
+            )
+            .unwrap();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let amount_in = INITIAL_USER_BALANCE / 2;
+        let mut received = vec![];
+        for pool_index in [0, world.pools.len() - 1] {
+            let pool = &world.pools[pool_index];
+            let user = &world.users[0];
+            let destination = user.tokens[&pool.mint_b];
+            let before = world.bank.token_account(&destination).amount;
+            let swap = pool.swap(user, TradeDirection::AtoB, amount_in);
+            world.bank.process(&swap).unwrap();
+            received.push(world.bank.token_account(&destination).amount - before);
+        }
+        let (constant_product, stable) = (received[0], received[1]);
+        assert!(constant_product < stable && stable < amount_in);
+        world.assert_conserved(&trading_supplies);
+    }
+}
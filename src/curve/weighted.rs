@@ -0,0 +1,646 @@
+//! Weighted curve, Balancer's value function for two tokens
+//!
+//! `A^w_a * B^w_b = k`: the pool holds its reserves at a fixed share of
+//! value, `w_a / (w_a + w_b)` in token A, so an 80/20 pool is long token A
+//! and an LP position in it loses less to token A rising.  Equal weights are
+//! the constant product curve.
+//!
+//! Prices take fractional powers, approximated by a series around 1 that
+//! only converges for bases between 0 and 2 and slows down towards either
+//! end.  As in Balancer, trades and single-sided deposits and withdrawals
+//! are kept to `MAX_RATIO_NUMERATOR / MAX_RATIO_DENOMINATOR` of the reserve
+//! they move, which keeps the bases between 0.7 and 1.
+use {
+    crate::{
+        curve::{
+            calculator::{
+                map_zero_to_none, sealed, CurveCalculator, DynPack, RoundDirection,
+                SwapWithoutFeesResult, TradeDirection, TradingTokenResult,
+            },
+            constant_product::pool_tokens_to_trading_tokens,
+        },
+        error::SwapError,
+    },
+    arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs},
+    solana_program::{
+        program_error::ProgramError,
+        program_pack::{IsInitialized, Pack, Sealed},
+    },
+    spl_math::precise_number::PreciseNumber,
+};
+
+/// Most one weight can be of the other, 99/1
+pub const MAX_WEIGHT_RATIO: u64 = 99;
+
+/// Most of a reserve a single operation moves, in or out
+pub const MAX_RATIO_NUMERATOR: u128 = 3;
+pub const MAX_RATIO_DENOMINATOR: u128 = 10;
+
+/// Powers are rounded by this much, 5e-10, in the pool's favour.  The
+/// series is cut off at the first term under 1e-10, leaving a tail of one
+/// sign worth up to that term over `1 - 0.3` for bases kept within 0.3 of 1.
+/// Each term, and each multiplication of the whole power, rounds the last
+/// digit of the 1e-12 precision, some 1e-10 more over the series' twenty-odd
+/// terms and a whole power of up to 99
+const POW_ERROR_INVERSE: u128 = 2_000_000_000;
+
+/// The series stops at its first term under `1 / SERIES_CUTOFF_INVERSE`
+const SERIES_CUTOFF_INVERSE: u128 = 10_000_000_000;
+
+/// Most terms the series is taken to, well past the twenty-odd bases within
+/// 0.3 of 1 need
+const SERIES_MAX_TERMS: u128 = 100;
+
+#[cfg_attr(feature = "client", derive(serde::Serialize))]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WeightedCurve {
+    /// Weight of token A, relative to token B's
+    pub token_a_weight: u64,
+    /// Weight of token B, relative to token A's
+    pub token_b_weight: u64,
+}
+
+/// Most of `reserve` a single operation may move
+fn max_ratio_of(reserve: u128) -> Option<u128> {
+    reserve
+        .checked_mul(MAX_RATIO_NUMERATOR)?
+        .checked_div(MAX_RATIO_DENOMINATOR)
+}
+
+/// `base^exponent` for a base between 0 and 1: the whole part of the
+/// exponent by multiplication, its fraction `r` by the binomial series
+/// `(1 - y)^r = 1 - r y - r (1 - r) y^2 / 2 - ...` with `y = 1 - base`, every
+/// term of which is subtracted
+fn checked_pow(base: &PreciseNumber, exponent: &PreciseNumber) -> Option<PreciseNumber> {
+    let one = PreciseNumber::new(1)?;
+    let whole_exponent = exponent.floor()?;
+    let fraction = exponent.checked_sub(&whole_exponent)?;
+    let whole_power = base.checked_pow(whole_exponent.to_imprecise()?)?;
+    let distance = one.checked_sub(base)?;
+    let cutoff = one.checked_div(&PreciseNumber::new(SERIES_CUTOFF_INVERSE)?)?;
+    let mut term = fraction.checked_mul(&distance)?;
+    let mut fraction_power = one.checked_sub(&term)?;
+    for k in 2..SERIES_MAX_TERMS {
+        term = term
+            .checked_mul(&distance)?
+            .checked_mul(&PreciseNumber::new(k - 1)?.checked_sub(&fraction)?)?
+            .checked_div(&PreciseNumber::new(k)?)?;
+        if term.less_than(&cutoff) {
+            return whole_power.checked_mul(&fraction_power);
+        }
+        fraction_power = fraction_power.checked_sub(&term)?;
+    }
+    None
+}
+
+/// `base^exponent` for a base between 0 and 1, bounded below or above by the
+/// approximation's error
+fn pow(
+    base: &PreciseNumber,
+    exponent: &PreciseNumber,
+    bound: RoundDirection,
+) -> Option<PreciseNumber> {
+    let (zero, one) = (PreciseNumber::new(0)?, PreciseNumber::new(1)?);
+    if base.less_than_or_equal(&zero) || base.greater_than(&one) {
+        return None;
+    }
+    let power = checked_pow(base, exponent)?;
+    let error = one.checked_div(&PreciseNumber::new(POW_ERROR_INVERSE)?)?;
+    match bound {
+        RoundDirection::Floor if power.greater_than(&error) => power.checked_sub(&error),
+        RoundDirection::Floor => Some(zero),
+        RoundDirection::Ceiling => power.checked_add(&error),
+    }
+}
+
+/// `amount * fraction`, rounded
+fn scale(amount: u128, fraction: &PreciseNumber, round_direction: RoundDirection) -> Option<u128> {
+    let scaled = PreciseNumber::new(amount)?.checked_mul(fraction)?;
+    match round_direction {
+        RoundDirection::Floor => scaled.floor()?,
+        RoundDirection::Ceiling => scaled.ceiling()?,
+    }
+    .to_imprecise()
+}
+
+impl WeightedCurve {
+    /// Weights of the source and destination tokens
+    fn weights(&self, trade_direction: TradeDirection) -> (u128, u128) {
+        let (token_a_weight, token_b_weight) = (
+            u128::from(self.token_a_weight),
+            u128::from(self.token_b_weight),
+        );
+        match trade_direction {
+            TradeDirection::AtoB => (token_a_weight, token_b_weight),
+            TradeDirection::BtoA => (token_b_weight, token_a_weight),
+        }
+    }
+
+    /// Share of the pool's value held in the source token
+    fn normalized_weight(&self, trade_direction: TradeDirection) -> Option<PreciseNumber> {
+        let (source_weight, destination_weight) = self.weights(trade_direction);
+        PreciseNumber::new(source_weight)?.checked_div(&PreciseNumber::new(
+            source_weight.checked_add(destination_weight)?,
+        )?)
+    }
+}
+
+impl sealed::Sealed for WeightedCurve {}
+
+impl CurveCalculator for WeightedCurve {
+    fn validate(&self) -> Result<(), SwapError> {
+        let (lighter, heavier) = (
+            self.token_a_weight.min(self.token_b_weight),
+            self.token_a_weight.max(self.token_b_weight),
+        );
+        if lighter == 0 || heavier / lighter > MAX_WEIGHT_RATIO {
+            Err(SwapError::InvalidCurve)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// `out = B_o * (1 - (B_i / (B_i + A_i))^(w_i / w_o))`
+    fn swap_without_fees(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapWithoutFeesResult> {
+        if source_amount > max_ratio_of(swap_source_amount)? {
+            return None;
+        }
+        let (source_weight, destination_weight) = self.weights(trade_direction);
+        let base = PreciseNumber::new(swap_source_amount)?.checked_div(&PreciseNumber::new(
+            swap_source_amount.checked_add(source_amount)?,
+        )?)?;
+        let exponent = PreciseNumber::new(source_weight)?
+            .checked_div(&PreciseNumber::new(destination_weight)?)?;
+        let kept = pow(&base, &exponent, RoundDirection::Ceiling)?;
+        let paid = PreciseNumber::new(1)?.checked_sub(&kept)?;
+        let destination_amount_swapped = map_zero_to_none(scale(
+            swap_destination_amount,
+            &paid,
+            RoundDirection::Floor,
+        )?)?;
+        Some(SwapWithoutFeesResult {
+            source_amount_swapped: source_amount,
+            destination_amount_swapped,
+        })
+    }
+
+    /// Within the share of the source reserve a trade may add
+    fn max_swappable(
+        &self,
+        swap_source_amount: u128,
+        _swap_destination_amount: u128,
+        _trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        max_ratio_of(swap_source_amount)
+    }
+
+    /// `pool = supply * ((1 + A_i / B_i)^(w_i / W) - 1)`, through the inverse
+    /// power so the base stays below 1
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_token_a_amount,
+            TradeDirection::BtoA => swap_token_b_amount,
+        };
+        if source_amount > max_ratio_of(swap_source_amount)? {
+            return None;
+        }
+        let base = PreciseNumber::new(swap_source_amount)?.checked_div(&PreciseNumber::new(
+            swap_source_amount.checked_add(source_amount)?,
+        )?)?;
+        let power = pow(
+            &base,
+            &self.normalized_weight(trade_direction)?,
+            RoundDirection::Ceiling,
+        )?;
+        // a deposit within the power's margin mints nothing
+        let one = PreciseNumber::new(1)?;
+        if power.greater_than_or_equal(&one) {
+            return Some(0);
+        }
+        let growth = one.checked_sub(&power)?.checked_div(&power)?;
+        scale(pool_supply, &growth, RoundDirection::Floor)
+    }
+
+    /// `pool = supply * (1 - ((B_o - A_o) / B_o)^(w_o / W))`
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<u128> {
+        let swap_source_amount = match trade_direction {
+            TradeDirection::AtoB => swap_token_a_amount,
+            TradeDirection::BtoA => swap_token_b_amount,
+        };
+        if source_amount > max_ratio_of(swap_source_amount)? {
+            return None;
+        }
+        let base = PreciseNumber::new(swap_source_amount.checked_sub(source_amount)?)?
+            .checked_div(&PreciseNumber::new(swap_source_amount)?)?;
+        let power = pow(
+            &base,
+            &self.normalized_weight(trade_direction)?,
+            RoundDirection::Floor,
+        )?;
+        let shrink = PreciseNumber::new(1)?.checked_sub(&power)?;
+        scale(pool_supply, &shrink, RoundDirection::Ceiling)
+    }
+
+    /// Both tokens in proportion to the reserves, as for constant product
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<TradingTokenResult> {
+        pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    }
+
+    /// The reserves' ratio weighted, `(B_o / w_o) / (B_i / w_i)`
+    fn spot_price(
+        &self,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+    ) -> Option<PreciseNumber> {
+        let (source_weight, destination_weight) = self.weights(trade_direction);
+        PreciseNumber::new(swap_destination_amount.checked_mul(source_weight)?)?.checked_div(
+            &PreciseNumber::new(swap_source_amount.checked_mul(destination_weight)?)?,
+        )
+    }
+}
+
+// ----------------------------------------------------------------------------- program pack
+
+/// IsInitialized is required to use `Pack::pack` and `Pack::unpack`
+impl IsInitialized for WeightedCurve {
+    fn is_initialized(&self) -> bool {
+        true
+    }
+}
+impl Sealed for WeightedCurve {}
+impl Pack for WeightedCurve {
+    const LEN: usize = 16;
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        (self as &dyn DynPack).pack_into_slice(output);
+    }
+
+    fn unpack_from_slice(input: &[u8]) -> Result<WeightedCurve, ProgramError> {
+        let input = array_ref![input, 0, 16];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (token_a_weight, token_b_weight) = array_refs![input, 8, 8];
+        Ok(Self {
+            token_a_weight: u64::from_le_bytes(*token_a_weight),
+            token_b_weight: u64::from_le_bytes(*token_b_weight),
+        })
+    }
+}
+
+impl DynPack for WeightedCurve {
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 16];
+        let (token_a_weight, token_b_weight) = mut_array_refs![output, 8, 8];
+        *token_a_weight = self.token_a_weight.to_le_bytes();
+        *token_b_weight = self.token_b_weight.to_le_bytes();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::INITIAL_SWAP_POOL_AMOUNT;
+    use crate::curve::base::{CurveType, SwapCurve};
+    use crate::curve::calculator::test::{
+        check_deposit_token_conversion, check_single_sided_round_trip, round_trip_fee_settings,
+    };
+    use crate::curve::constant_product::ConstantProductCurve;
+    use crate::instruction;
+    use crate::processor::test::{program_id, World, INITIAL_RESERVE, INITIAL_USER_BALANCE};
+    use proptest::prelude::*;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    #[test]
+    fn equal_weights_trade_as_constant_product() {
+        let reserve = 1_000_000_000_000;
+        let curve = WeightedCurve {
+            token_a_weight: 50,
+            token_b_weight: 50,
+        };
+        for amount in [1_000, reserve / 1_000, reserve / 10] {
+            let out = |curve: &dyn CurveCalculator| {
+                curve
+                    .swap_without_fees(amount, reserve, reserve, TradeDirection::AtoB)
+                    .unwrap()
+                    .destination_amount_swapped
+            };
+            let (weighted, constant_product) = (out(&curve), out(&ConstantProductCurve));
+            // the power's margin, and never in the trader's favour
+            assert!(weighted <= constant_product);
+            assert!(constant_product - weighted <= reserve / POW_ERROR_INVERSE + 1);
+        }
+    }
+
+    #[test]
+    fn weights_are_bounded() {
+        let curve = |token_a_weight, token_b_weight| WeightedCurve {
+            token_a_weight,
+            token_b_weight,
+        };
+        assert_eq!(curve(80, 20).validate(), Ok(()));
+        assert_eq!(curve(1, MAX_WEIGHT_RATIO).validate(), Ok(()));
+        assert_eq!(curve(MAX_WEIGHT_RATIO, 1).validate(), Ok(()));
+        for (token_a_weight, token_b_weight) in [(0, 1), (1, 0), (0, 0), (1, 100), (100, 1)] {
+            assert_eq!(
+                curve(token_a_weight, token_b_weight).validate(),
+                Err(SwapError::InvalidCurve)
+            );
+        }
+    }
+
+    #[test]
+    fn operations_past_the_ratio_limit_are_refused() {
+        let curve = WeightedCurve {
+            token_a_weight: 80,
+            token_b_weight: 20,
+        };
+        let reserve = 1_000_000;
+        let max = curve
+            .max_swappable(reserve, reserve, TradeDirection::AtoB)
+            .unwrap();
+        assert_eq!(max, reserve * 3 / 10);
+        assert!(curve
+            .swap_without_fees(max, reserve, reserve, TradeDirection::AtoB)
+            .is_some());
+        assert_eq!(
+            curve.swap_without_fees(max + 1, reserve, reserve, TradeDirection::AtoB),
+            None
+        );
+        assert_eq!(
+            curve.deposit_single_token_type(
+                max + 1,
+                reserve,
+                reserve,
+                reserve,
+                TradeDirection::AtoB
+            ),
+            None
+        );
+        assert_eq!(
+            curve.withdraw_single_token_type_exact_out(
+                max + 1,
+                reserve,
+                reserve,
+                reserve,
+                TradeDirection::AtoB
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn spot_price_weighs_the_reserves() {
+        let curve = WeightedCurve {
+            token_a_weight: 80,
+            token_b_weight: 20,
+        };
+        // a quarter the token B prices the tokens 1:1
+        let price = curve
+            .spot_price(4_000_000, 1_000_000, TradeDirection::AtoB)
+            .unwrap();
+        assert_eq!(price, PreciseNumber::new(1).unwrap());
+        let amount = 1_000;
+        let out = curve
+            .swap_without_fees(amount, 4_000_000, 1_000_000, TradeDirection::AtoB)
+            .unwrap()
+            .destination_amount_swapped;
+        assert!(out < amount && out >= amount - 2);
+    }
+
+    proptest! {
+        #[test]
+        fn swaps_never_shrink_the_invariant(
+            token_a_weight in 1..=MAX_WEIGHT_RATIO,
+            token_b_weight in 1..=MAX_WEIGHT_RATIO,
+            source_amount in 1..u64::MAX,
+            swap_source_amount in 1_000_000..u64::MAX,
+            swap_destination_amount in 1_000_000..u64::MAX,
+        ) {
+            let curve = WeightedCurve { token_a_weight, token_b_weight };
+            let (source_amount, swap_source_amount, swap_destination_amount) = (
+                source_amount as u128 % (swap_source_amount as u128 * 3 / 10 + 1),
+                swap_source_amount as u128,
+                swap_destination_amount as u128,
+            );
+            let value = |source: u128, destination: u128| {
+                token_a_weight as f64 * (source as f64).ln()
+                    + token_b_weight as f64 * (destination as f64).ln()
+            };
+            if let Some(result) = curve.swap_without_fees(
+                source_amount,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            ) {
+                prop_assert!(result.destination_amount_swapped < swap_destination_amount);
+                let before = value(swap_source_amount, swap_destination_amount);
+                let after = value(
+                    swap_source_amount + result.source_amount_swapped,
+                    swap_destination_amount - result.destination_amount_swapped,
+                );
+                // within what f64 tells apart
+                prop_assert!(after >= before - before.abs() * 1e-14);
+            }
+        }
+
+        #[test]
+        fn deposit_token_conversion(
+            token_a_weight in 1..=10u64,
+            token_b_weight in 1..=10u64,
+            source_token_bps in 1..100u128,
+            swap_source_amount in 1_000_000_000..1_000_000_000_000u64,
+            swap_destination_amount in 1_000_000_000..1_000_000_000_000u64,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            // deposits of up to 1% of reserves within a factor of 10 of each
+            // other, well clear of the powers' margin and the ratio limit
+            prop_assume!(swap_source_amount / 10 <= swap_destination_amount);
+            prop_assume!(swap_destination_amount / 10 <= swap_source_amount);
+            let curve = WeightedCurve { token_a_weight, token_b_weight };
+            let source_token_amount = swap_source_amount as u128 * source_token_bps / 10_000;
+            for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                check_deposit_token_conversion(
+                    &curve,
+                    source_token_amount,
+                    swap_source_amount as u128,
+                    swap_destination_amount as u128,
+                    trade_direction,
+                    pool_supply,
+                    // the powers' margin weighs on small deposits
+                    100,
+                );
+            }
+        }
+
+        #[test]
+        fn single_sided_round_trip(
+            token_a_weight in 1..=10u64,
+            token_b_weight in 1..=10u64,
+            source_token_bps in 1..100u128,
+            swap_source_amount in 1_000_000_000..1_000_000_000_000u64,
+            swap_destination_amount in 1_000_000_000..1_000_000_000_000u64,
+            pool_supply in INITIAL_SWAP_POOL_AMOUNT..u64::MAX as u128,
+        ) {
+            prop_assume!(swap_source_amount / 10 <= swap_destination_amount);
+            prop_assume!(swap_destination_amount / 10 <= swap_source_amount);
+            let swap_curve = SwapCurve {
+                curve_type: CurveType::Weighted,
+                calculator: Box::new(WeightedCurve { token_a_weight, token_b_weight }),
+            };
+            let source_token_amount = swap_source_amount as u128 * source_token_bps / 10_000;
+            for fees in round_trip_fee_settings().iter() {
+                for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+                    check_single_sided_round_trip(
+                        &swap_curve,
+                        fees,
+                        source_token_amount,
+                        swap_source_amount as u128,
+                        swap_destination_amount as u128,
+                        trade_direction,
+                        pool_supply,
+                        100,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn weighted_pools_trade_and_take_single_sided_liquidity_at_their_weights() {
+        let mut world = World::new();
+        // 80/20, with a quarter the token B: one token A is one token B
+        world
+            .add_pool(
+                world.mints[0],
+                world.mints[1],
+                CurveType::Weighted,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE / 4),
+            )
+            .unwrap();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = world.pools.last().unwrap();
+        let user = &world.users[0];
+        let (user_a, user_b) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let user_pool = world
+            .bank
+            .add_token_account(&pool.pool_mint, &user.wallet, 0);
+        let amount_in = INITIAL_USER_BALANCE / 10;
+        let swap = |source, swap_source, swap_destination, destination| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                source,
+                swap_source,
+                swap_destination,
+                destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        for (source, swap_source, swap_destination, destination) in [
+            (&user_a, &pool.token_a, &pool.token_b, &user_b),
+            (&user_b, &pool.token_b, &pool.token_a, &user_a),
+        ] {
+            let before = world.bank.token_account(destination).amount;
+            world
+                .bank
+                .process(&swap(source, swap_source, swap_destination, destination))
+                .unwrap();
+            // near the spot price, less the 0.3% fee and a little slippage
+            let received = world.bank.token_account(destination).amount - before;
+            assert!(received < amount_in && received > amount_in / 100 * 99);
+        }
+
+        let deposit = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_b,
+            &pool.token_a,
+            &pool.token_b,
+            &pool.pool_mint,
+            &user_pool,
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: amount_in,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&deposit).unwrap();
+        let minted = world.bank.token_account(&user_pool).amount;
+        assert!(minted > 0);
+        // the fees of both ways are well under 1%
+        let withdraw = instruction::withdraw_single_token_type_exact_amount_out(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &pool.pool_mint,
+            &pool.fee_account,
+            &user_pool,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            instruction::WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount: amount_in / 100 * 99,
+                maximum_pool_token_amount: minted,
+            },
+        )
+        .unwrap();
+        assert_eq!(world.bank.process(&withdraw), Ok(()));
+        world.assert_conserved(&trading_supplies);
+    }
+}
@@ -3,6 +3,7 @@ use crate::processor::Processor;
 use solana_program::account_info::AccountInfo;
 use solana_program::entrypoint;
 use solana_program::entrypoint::ProgramResult;
+use solana_program::msg;
 use solana_program::program_error::PrintProgramError;
 use solana_program::pubkey::Pubkey;
 
@@ -14,6 +15,16 @@ fn process_instruction(
 ) -> ProgramResult {
     if let Err(e) = Processor::process(program_id, accounts, instruction_data) {
         e.print::<SwapError>();
+        // one line to triage from: the instruction tag, the account it
+        // acted on (the pool for all but a few) and the error code
+        msg!(
+            "Failed: tag={} pool={} code={}",
+            instruction_data.first().map_or(-1, |tag| i16::from(*tag)),
+            accounts
+                .first()
+                .map_or(Pubkey::default(), |account| *account.key),
+            u64::from(e.clone())
+        );
         return Err(e);
     }
     Ok(())
@@ -102,6 +102,138 @@ pub enum SwapError {
     /// The operation cannot be performed on the given curve
     #[error("The operation cannot be performed on the given curve")]
     UnsupportedCurveOperation,
+    /// The provided referrer account is not the one registered for the host
+    #[error("The provided referrer account is invalid")]
+    InvalidReferrer,
+    /// The pool token mint does not have the decimals required by the program owner
+    #[error("The pool token mint has invalid decimals")]
+    InvalidPoolMintDecimals,
+
+    // 30.
+    /// The swap would take the pool over its volume cap for the current slot
+    #[error("Pool volume cap for this slot exceeded")]
+    RateLimited,
+    /// The liquidity lock has not reached its unlock slot yet
+    #[error("Liquidity is still locked")]
+    LiquidityLocked,
+    /// The oracle account does not match the pool's oracle config, or holds no price
+    #[error("The provided oracle account is invalid")]
+    InvalidOracle,
+    /// The pool admin hasn't put the pool in emergency mode
+    #[error("Pool is not in emergency mode")]
+    NotInEmergency,
+    /// The program's config doesn't record its current upgrade authority
+    #[error("Program upgrade authority is not documented")]
+    UndocumentedUpgradeAuthority,
+    /// Returned by `SimulateSwap` once the quote is in the return data, so
+    /// the transaction never lands
+    #[error("Swap simulated, the quote is in the return data")]
+    SimulationComplete,
+    /// A user token account is frozen by its mint's freeze authority
+    #[error("Token account is frozen")]
+    AccountFrozen,
+    /// The callback of a flash swap didn't pay back what the swap costs
+    #[error("Flash swap was not repaid with its fees")]
+    FlashSwapNotRepaid,
+    /// A trading token mint has more decimals than the curves can scale
+    #[error("Token mint has more decimals than supported")]
+    UnsupportedMintDecimals,
+    /// The user's source account doesn't hold the token the swap takes
+    #[error("Source account mint doesn't match the pool's input token")]
+    IncorrectSourceMint,
+    /// The user's destination account doesn't hold the token the swap pays out
+    #[error("Destination account mint doesn't match the pool's output token")]
+    IncorrectDestinationMint,
+    /// The pool prices off a stake pool and its rate wasn't refreshed this slot
+    #[error("Stake pool rate not refreshed this slot")]
+    StaleRate,
+    /// The pool token mint can be minted by someone other than the swap
+    #[error("Pool token mint authority is not the swap authority")]
+    InvalidMintAuthority,
+    /// The instruction was given an amount of zero to swap, deposit or withdraw
+    #[error("Instruction amount is zero")]
+    ZeroAmount,
+    /// The trade would take more than the pool can pay out of its reserves
+    #[error("Trade is larger than the pool can fill")]
+    TradeTooLarge,
+    /// The transfer authority neither owns the user's account nor is its delegate
+    #[error("Transfer authority is not the account's owner or delegate")]
+    IncorrectDelegate,
+    /// The transfer authority is the account's delegate, for less than the instruction moves
+    #[error("Delegated amount is less than the instruction moves")]
+    InsufficientDelegatedAmount,
+    /// A single-sided withdrawal comes before the cooldown after the pool's last single-sided deposit
+    #[error("Single-sided withdrawal is cooling down after a single-sided deposit")]
+    WithdrawCooldown,
+    /// Fewer of a multisig admin's signers signed than it requires
+    #[error("Multisig admin is missing signatures")]
+    MissingAdminSignatures,
+    /// The pool template index is unknown, or the pool's fees and curve
+    /// aren't one of the templates
+    #[error("Pool parameters don't match a pool template")]
+    InvalidPoolTemplate,
+    /// The amount is too small to come to a whole token
+    #[error("Amount too small, it rounds to zero tokens")]
+    AmountTooSmall,
+    /// The pool holds none of a token it needs
+    #[error("Pool holds none of the token")]
+    EmptyPool,
+    /// The trader doesn't hold the gated pool's token or credential
+    #[error("Trader doesn't pass the pool's gate")]
+    GateNotPassed,
+    /// Other traders trade the pool both ways in the same transaction, as a
+    /// sandwich would
+    #[error("Other traders trade the pool both ways in this transaction")]
+    SandwichSuspected,
+    /// The deposits open the pool too far from the oracle's price
+    #[error("Initial price is outside the oracle price band")]
+    InitialPriceOutsideBand,
+    /// The liquidity snapshot's slot hasn't been reached yet
+    #[error("Liquidity snapshot slot not reached")]
+    SnapshotNotDue,
+    /// The liquidity snapshot was taken already
+    #[error("Liquidity snapshot is finalized")]
+    SnapshotFinalized,
+    /// No longer returned, kept so the errors after it keep their codes
+    #[error("LP position out of order for the liquidity snapshot")]
+    PositionOutOfOrder,
+    /// The nested pool isn't another pool of this program minting token B,
+    /// or is nested itself
+    #[error("Invalid nested pool")]
+    InvalidNestedPool,
+    /// The instruction is given more accounts than it takes
+    #[error("Too many accounts for the instruction")]
+    TooManyAccounts,
+    /// The instruction is invoked too deep for its CPIs to run
+    #[error("Instruction invoked too deep for its CPIs")]
+    CallTooDeep,
+    /// The swap would push the price out of the curve's range
+    #[error("Price out of range")]
+    PriceOutOfRange,
+    /// The crank isn't signed by a keeper bonded in the keeper config
+    #[error("Keeper not bonded")]
+    KeeperNotBonded,
+    /// The keeper hasn't unbonded, or its unbonding slot isn't reached
+    #[error("Keeper bond is locked")]
+    KeeperBondLocked,
+    /// The curve doesn't take trades in this direction
+    #[error("Trade direction disabled")]
+    DirectionDisabled,
+    /// The pool admin has halted trades and deposits
+    #[error("Pool is paused")]
+    PoolPaused,
+    /// A user instruction names the swap authority as the transfer authority,
+    /// or moves tokens out of an account the swap authority owns
+    #[error("Transfer authority or source account is the swap authority's")]
+    SwapAuthorityTransfer,
+    /// The pool admin has put the pool in emergency mode, its curve taking
+    /// no trades or deposits
+    #[error("Pool is in emergency mode")]
+    PoolInEmergency,
+    /// The keeper's last crank read the price its oracle still holds, or
+    /// didn't read an oracle, or was in another slot
+    #[error("Oracle doesn't contradict the keeper's last crank")]
+    UnprovenSlash,
 }
 
 impl From<SwapError> for ProgramError {
@@ -179,6 +311,83 @@ impl PrintProgramError for SwapError {
             SwapError::UnsupportedCurveOperation => {
                 msg!("Error: The operation cannot be performed on the given curve")
             }
+            SwapError::InvalidReferrer => {
+                msg!("Error: The provided referrer account is invalid")
+            }
+            SwapError::InvalidPoolMintDecimals => {
+                msg!("Error: The pool token mint has invalid decimals")
+            }
+            SwapError::RateLimited => msg!("Error: Pool volume cap for this slot exceeded"),
+            SwapError::LiquidityLocked => msg!("Error: Liquidity is still locked"),
+            SwapError::InvalidOracle => msg!("Error: The provided oracle account is invalid"),
+            SwapError::NotInEmergency => msg!("Error: Pool is not in emergency mode"),
+            SwapError::UndocumentedUpgradeAuthority => {
+                msg!("Error: Program upgrade authority is not documented")
+            }
+            SwapError::SimulationComplete => {
+                msg!("Error: Swap simulated, the quote is in the return data")
+            }
+            SwapError::AccountFrozen => msg!("Error: Token account is frozen"),
+            SwapError::FlashSwapNotRepaid => msg!("Error: Flash swap was not repaid with its fees"),
+            SwapError::UnsupportedMintDecimals => {
+                msg!("Error: Token mint has more decimals than supported")
+            }
+            SwapError::IncorrectSourceMint => {
+                msg!("Error: Source account mint doesn't match the pool's input token")
+            }
+            SwapError::IncorrectDestinationMint => {
+                msg!("Error: Destination account mint doesn't match the pool's output token")
+            }
+            SwapError::StaleRate => msg!("Error: Stake pool rate not refreshed this slot"),
+            SwapError::InvalidMintAuthority => {
+                msg!("Error: Pool token mint authority is not the swap authority")
+            }
+            SwapError::ZeroAmount => msg!("Error: Instruction amount is zero"),
+            SwapError::TradeTooLarge => msg!("Error: Trade is larger than the pool can fill"),
+            SwapError::IncorrectDelegate => {
+                msg!("Error: Transfer authority is not the account's owner or delegate")
+            }
+            SwapError::InsufficientDelegatedAmount => {
+                msg!("Error: Delegated amount is less than the instruction moves")
+            }
+            SwapError::WithdrawCooldown => {
+                msg!("Error: Single-sided withdrawal is cooling down after a single-sided deposit")
+            }
+            SwapError::MissingAdminSignatures => {
+                msg!("Error: Multisig admin is missing signatures")
+            }
+            SwapError::InvalidPoolTemplate => {
+                msg!("Error: Pool parameters don't match a pool template")
+            }
+            SwapError::AmountTooSmall => msg!("Error: Amount too small, it rounds to zero tokens"),
+            SwapError::EmptyPool => msg!("Error: Pool holds none of the token"),
+            SwapError::GateNotPassed => msg!("Error: Trader doesn't pass the pool's gate"),
+            SwapError::SandwichSuspected => {
+                msg!("Error: Other traders trade the pool both ways in this transaction")
+            }
+            SwapError::InitialPriceOutsideBand => {
+                msg!("Error: Initial price is outside the oracle price band")
+            }
+            SwapError::SnapshotNotDue => msg!("Error: Liquidity snapshot slot not reached"),
+            SwapError::SnapshotFinalized => msg!("Error: Liquidity snapshot is finalized"),
+            SwapError::PositionOutOfOrder => {
+                msg!("Error: LP position out of order for the liquidity snapshot")
+            }
+            SwapError::InvalidNestedPool => msg!("Error: Invalid nested pool"),
+            SwapError::TooManyAccounts => msg!("Error: Too many accounts for the instruction"),
+            SwapError::CallTooDeep => msg!("Error: Instruction invoked too deep for its CPIs"),
+            SwapError::PriceOutOfRange => msg!("Error: Price out of range"),
+            SwapError::KeeperNotBonded => msg!("Error: Keeper not bonded"),
+            SwapError::KeeperBondLocked => msg!("Error: Keeper bond is locked"),
+            SwapError::DirectionDisabled => msg!("Error: Trade direction disabled"),
+            SwapError::PoolPaused => msg!("Error: Pool is paused"),
+            SwapError::SwapAuthorityTransfer => {
+                msg!("Error: Transfer authority or source account is the swap authority's")
+            }
+            SwapError::PoolInEmergency => msg!("Error: Pool is in emergency mode"),
+            SwapError::UnprovenSlash => {
+                msg!("Error: Oracle doesn't contradict the keeper's last crank")
+            }
         }
     }
 }
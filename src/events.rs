@@ -0,0 +1,869 @@
+//! Structured events logged by the processor, so indexers and analytics
+//! dashboards share one definition of each derived number.
+//!
+//! Every event is logged as `Program data: <base64>`, the payload being the
+//! `EVENT_VERSION` byte followed by the Borsh encoding of [Event](enum.Event.html).
+//! Variants are only ever appended, so a decoder keeps working for the events
+//! it knows about as new instructions land; `parse_events` is the one decoding
+//! path clients should use.
+
+use crate::constants::{MAX_FEE_EXEMPT, MAX_FEE_TIERS};
+use crate::curve::base::SwapResult;
+use crate::curve::calculator::TradeDirection;
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_program::{hash::hashv, log::sol_log_data, pubkey::Pubkey};
+use spl_math::precise_number::PreciseNumber;
+use std::convert::TryInto;
+
+/// Version of the event encoding, bumped on any incompatible change
+pub const EVENT_VERSION: u8 = 1;
+
+/// Fixed-point scale of all prices carried by events
+pub const PRICE_SCALE: u128 = 1_000_000_000_000;
+
+/// Every event the program logs, discriminated by the Borsh variant index
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub enum Event {
+    PoolInitialized(PoolInitializedEvent),
+    Swap(SwapEvent),
+    Deposit(DepositEvent),
+    Withdraw(WithdrawEvent),
+    ReferrerRegistered(ReferrerRegisteredEvent),
+    VestedTokensClaimed(VestedTokensClaimedEvent),
+    CurveReinitialized(CurveReinitializedEvent),
+    LiquidityLocked(LiquidityLockEvent),
+    LiquidityUnlocked(LiquidityLockEvent),
+    OraclePriceUpdated(OraclePriceUpdatedEvent),
+    AdminLiquidityMoved(AdminLiquidityMovedEvent),
+    RebalancerSet(RebalancerSetEvent),
+    PoolMetadataSet(PoolMetadataSetEvent),
+    EmergencySet(EmergencySetEvent),
+    EmergencyWithdraw(WithdrawEvent),
+    DepositBonusSet(DepositBonusSetEvent),
+    DepositBonusPaid(DepositBonusPaidEvent),
+    FeeExemptSet(FeeExemptSetEvent),
+    UpgradeAuthorityDocumented(UpgradeAuthorityDocumentedEvent),
+    WithdrawFeeDestinationSet(WithdrawFeeDestinationSetEvent),
+    WithdrawFeeConverted(WithdrawFeeConvertedEvent),
+    FeeTiersSet(FeeTiersSetEvent),
+    FlashSwap(FlashSwapEvent),
+    ExactInputSet(ExactInputSetEvent),
+    StakePoolSet(StakePoolSetEvent),
+    StakePoolRateRefreshed(StakePoolRateRefreshedEvent),
+    DefaultHostSet(DefaultHostSetEvent),
+    HostFeesClaimed(HostFeesClaimedEvent),
+    FeeAccountSet(FeeAccountSetEvent),
+    Checkpoint(CheckpointEvent),
+    OwnerFeeAccountsSet(OwnerFeeAccountsSetEvent),
+    SwapAccountResized(SwapAccountResizedEvent),
+    FeeConversionSet(FeeConversionSetEvent),
+    OwnerFeeConverted(OwnerFeeConvertedEvent),
+    WithdrawCooldownSet(WithdrawCooldownSetEvent),
+    AdminMultisigInitialized(AdminMultisigInitializedEvent),
+    GateSet(GateSetEvent),
+    SingleWithdrawFeeToLpsSet(SingleWithdrawFeeToLpsSetEvent),
+    SandwichGuardSet(SandwichGuardSetEvent),
+    InitialPrice(InitialPriceEvent),
+    OwnerFeeFallbackSet(OwnerFeeFallbackSetEvent),
+    OwnerFeeSkipped(OwnerFeeSkippedEvent),
+    LiquiditySnapshotConfigured(LiquiditySnapshotConfiguredEvent),
+    LiquiditySnapshotFinalized(LiquiditySnapshotFinalizedEvent),
+    NestedPoolSet(NestedPoolSetEvent),
+    DepositReferred(DepositReferredEvent),
+    KeeperConfigSet(KeeperConfigSetEvent),
+    KeeperBonded(KeeperBondedEvent),
+    KeeperUnbonding(KeeperUnbondingEvent),
+    KeeperBondWithdrawn(KeeperBondWithdrawnEvent),
+    KeeperSlashed(KeeperSlashedEvent),
+    KeeperCranked(KeeperCrankedEvent),
+    BondedKeepersOnlySet(BondedKeepersOnlySetEvent),
+    PausedSet(PausedSetEvent),
+    GovernanceProgramSet(GovernanceProgramSetEvent),
+    PoolTemplateSet(PoolTemplateSetEvent),
+}
+
+impl Event {
+    /// Versioned encoding of the event, as logged
+    pub fn pack(&self) -> Vec<u8> {
+        let mut data = vec![EVENT_VERSION];
+        // writing into a Vec can't fail
+        self.serialize(&mut data).unwrap();
+        data
+    }
+
+    /// Decodes a logged event, `None` for other versions or unknown events
+    pub fn unpack(data: &[u8]) -> Option<Self> {
+        let (&version, rest) = data.split_first()?;
+        if version != EVENT_VERSION {
+            return None;
+        }
+        Self::try_from_slice(rest).ok()
+    }
+
+    pub fn emit(&self) {
+        sol_log_data(&[&self.pack()]);
+    }
+}
+
+/// Decodes all the events logged by `program_id` in a transaction's logs.
+/// Data logged by other programs, including ones invoked by or invoking this
+/// program, is skipped.
+pub fn parse_events<S: AsRef<str>>(program_id: &Pubkey, logs: &[S]) -> Vec<Event> {
+    let program_id = program_id.to_string();
+    let mut invoke_stack: Vec<bool> = vec![];
+    let mut events = vec![];
+    for log in logs {
+        let log = log.as_ref();
+        if let Some(data) = log.strip_prefix("Program data: ") {
+            if invoke_stack.last() == Some(&true) {
+                events.extend(
+                    data.split(' ')
+                        .filter_map(|field| base64::decode(field).ok())
+                        .filter_map(|field| Event::unpack(&field)),
+                );
+            }
+        } else if let Some(rest) = log.strip_prefix("Program ") {
+            let mut words = rest.split(' ');
+            let invoked_program = words.next();
+            match words.next() {
+                Some("invoke") => invoke_stack.push(invoked_program == Some(&program_id)),
+                Some("success") | Some("failed:") => {
+                    invoke_stack.pop();
+                }
+                _ => {}
+            }
+        }
+    }
+    events
+}
+
+/// Emitted when a pool is created
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PoolInitializedEvent {
+    pub swap: Pubkey,
+    pub pool_mint: Pubkey,
+    /// Initial reserves
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    /// Initial pool token supply
+    pub pool_token_amount: u64,
+}
+
+/// Emitted at the end of every successful swap
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SwapEvent {
+    /// Pool the swap went through
+    pub swap: Pubkey,
+    pub trade_direction: TradeDirection,
+    /// Amount of source token swapped (includes fees)
+    pub source_amount_swapped: u128,
+    /// Amount of destination token swapped
+    pub destination_amount_swapped: u128,
+    /// Amount of source tokens going to pool holders
+    pub trade_fee: u128,
+    /// Amount of source tokens going to owner
+    pub owner_fee: u128,
+    /// Raw destination token units received per raw source token unit paid
+    /// (fees included), scaled by `PRICE_SCALE`.  It's a ratio of raw
+    /// amounts, not a price: multiply by `10^(source decimals - destination
+    /// decimals)` for one.  Ratios below `1 / PRICE_SCALE` read 0, as does
+    /// one that can't be computed
+    pub raw_effective_price: u128,
+    /// How far the execution price (fees excluded) fell below the pre-trade
+    /// spot price, in basis points, 0 when it can't be computed
+    pub price_impact_bps: u64,
+}
+
+impl SwapEvent {
+    /// Describes a swap from its result and the pool's spot price before it.
+    /// The derived prices read 0 rather than failing when they can't be
+    /// computed, so logging the event never reverts the swap
+    pub fn new(
+        swap: Pubkey,
+        trade_direction: TradeDirection,
+        result: &SwapResult,
+        spot_price: Option<&PreciseNumber>,
+    ) -> Self {
+        Self {
+            swap,
+            trade_direction,
+            source_amount_swapped: result.source_amount_swapped,
+            destination_amount_swapped: result.destination_amount_swapped,
+            trade_fee: result.trade_fee,
+            owner_fee: result.owner_fee,
+            raw_effective_price: Self::raw_effective_price(result).unwrap_or(0),
+            price_impact_bps: spot_price
+                .and_then(|spot_price| Self::price_impact_bps(result, spot_price))
+                .unwrap_or(0),
+        }
+    }
+
+    fn raw_effective_price(result: &SwapResult) -> Option<u128> {
+        let source_amount = PreciseNumber::new(result.source_amount_swapped)?;
+        let destination_amount = PreciseNumber::new(result.destination_amount_swapped)?;
+        to_scaled_price(&destination_amount.checked_div(&source_amount)?)
+    }
+
+    fn price_impact_bps(result: &SwapResult, spot_price: &PreciseNumber) -> Option<u64> {
+        // spot prices below PreciseNumber's 12 decimals, e.g. an 18 decimal
+        // token priced in a 0 decimal one, round to 0 and have nothing to
+        // measure against
+        if *spot_price == PreciseNumber::new(0)? {
+            return Some(0);
+        }
+        let source_amount_less_fees = PreciseNumber::new(
+            result
+                .source_amount_swapped
+                .checked_sub(result.total_fees()?)?,
+        )?;
+        let destination_amount = PreciseNumber::new(result.destination_amount_swapped)?;
+        let execution_price = destination_amount.checked_div(&source_amount_less_fees)?;
+
+        // the curve rounds in the pool's favor, so execution can't beat spot
+        // by more than rounding; clamp that case to no impact
+        match spot_price.checked_sub(&execution_price) {
+            Some(price_difference) => price_difference
+                .checked_mul(&PreciseNumber::new(10000)?)?
+                .checked_div(spot_price)?
+                .floor()?
+                .to_imprecise()?
+                .try_into()
+                .ok(),
+            None => Some(0),
+        }
+    }
+}
+
+/// Emitted by both deposit instructions, single-sided deposits leaving one
+/// token amount at 0
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct DepositEvent {
+    pub swap: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    /// Pool tokens minted to the depositor
+    pub pool_token_amount: u64,
+}
+
+/// Emitted by both withdraw instructions, single-sided withdrawals leaving
+/// one token amount at 0
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct WithdrawEvent {
+    pub swap: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    /// Pool tokens burned
+    pub pool_token_amount: u64,
+    /// Pool tokens paid to the pool fee account on top
+    pub withdraw_fee: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ReferrerRegisteredEvent {
+    /// Wallet owning the referrer's host fee accounts
+    pub owner: Pubkey,
+    pub host_fee_numerator: u64,
+    pub host_fee_denominator: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct VestedTokensClaimedEvent {
+    pub swap: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CurveReinitializedEvent {
+    pub swap: Pubkey,
+    /// `CurveType` of the new curve
+    pub curve_type: u8,
+}
+
+/// Emitted when pool tokens are locked, or unlocked with the lock's full amount
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LiquidityLockEvent {
+    pub swap: Pubkey,
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub unlock_slot: u64,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OraclePriceUpdatedEvent {
+    pub swap: Pubkey,
+    pub oracle_price: u64,
+    /// Price set by the crank, within the oracle config's bounds
+    pub token_b_price: u64,
+}
+
+/// Audit trail of an admin deposit or withdrawal that skipped the slippage
+/// bounds, logged right after its regular `Deposit` / `Withdraw` event
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct AdminLiquidityMovedEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// Deposit when true, withdrawal otherwise
+    pub deposit: bool,
+    pub pool_token_amount: u64,
+}
+
+/// Emitted when the pool admin sets the rebalancer, a default key removing it
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct RebalancerSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub rebalancer: Pubkey,
+}
+
+/// Emitted when a pool's metadata is created or updated, read the metadata
+/// account for the new contents
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PoolMetadataSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+}
+
+/// Emitted when the pool admin puts the pool in or takes it out of emergency mode
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct EmergencySetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub enabled: bool,
+}
+
+/// Emitted when the pool admin configures the deposit bonus window
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct DepositBonusSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub start_slot: u64,
+    pub end_slot: u64,
+    pub bonus_bps: u16,
+    pub lockup_slots: u64,
+}
+
+/// Emitted after a deposit and lock, when pool tokens from the fee account
+/// topped the lock up
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct DepositBonusPaidEvent {
+    pub swap: Pubkey,
+    /// Vault holding the lock's pool tokens
+    pub destination: Pubkey,
+    pub pool_token_amount: u64,
+}
+
+/// Emitted when the pool admin replaces the pool's fee exempt accounts
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FeeExemptSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub fee_exempt: [Pubkey; MAX_FEE_EXEMPT],
+}
+
+/// Emitted when the program config records the program's upgrade authority,
+/// the default key once the program is immutable
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct UpgradeAuthorityDocumentedEvent {
+    pub upgrade_authority: Pubkey,
+}
+
+/// Emitted when the pool admin chooses how withdraw fees are paid, the vaults
+/// being default keys while they're paid as pool tokens
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct WithdrawFeeDestinationSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub fee_vault_a: Pubkey,
+    pub fee_vault_b: Pubkey,
+}
+
+/// Emitted by withdrawals when the withdraw fee is burned and paid out in
+/// token A and B to the fee vaults
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct WithdrawFeeConvertedEvent {
+    pub swap: Pubkey,
+    /// Pool tokens of the withdraw fee, burned
+    pub pool_token_amount: u64,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+}
+
+/// Emitted when the pool admin replaces the size tiers of the trade fee, by
+/// increasing threshold, zeroed for unused tiers
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FeeTiersSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub thresholds: [u64; MAX_FEE_TIERS],
+    pub trade_fee_numerators: [u64; MAX_FEE_TIERS],
+    pub owner_trade_fee_numerators: [u64; MAX_FEE_TIERS],
+}
+
+/// Emitted by flash swaps once the callback repaid the pool, with the net
+/// change of each vault
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FlashSwapEvent {
+    pub swap: Pubkey,
+    pub trade_direction: TradeDirection,
+    /// Destination tokens paid out before the callback
+    pub amount_out: u64,
+    /// Source tokens the callback paid into the pool
+    pub source_amount_paid: u64,
+    /// Destination tokens that left the pool net of any repaid in kind, 0
+    /// when repaid in kind
+    pub destination_amount_swapped: u64,
+}
+
+/// Emitted when the pool admin chooses whether swaps consume exactly their
+/// `amount_in`
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct ExactInputSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub exact_input: bool,
+}
+
+/// Emitted when the pool admin pegs the pool to a stake pool, or unlinks it
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct StakePoolSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// `Pubkey::default()` when unlinked
+    pub stake_pool: Pubkey,
+}
+
+/// Emitted when a pegged pool's price is refreshed from its stake pool, the
+/// price being `total_lamports / pool_token_supply` token A per token B
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct StakePoolRateRefreshedEvent {
+    pub swap: Pubkey,
+    pub stake_pool: Pubkey,
+    pub total_lamports: u64,
+    pub pool_token_supply: u64,
+}
+
+/// Emitted when the pool admin sets or removes the default host
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct DefaultHostSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// `Pubkey::default()` when removed
+    pub host_fee_account: Pubkey,
+}
+
+/// Emitted when the default host's accrued fees are minted to it
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct HostFeesClaimedEvent {
+    pub swap: Pubkey,
+    pub host_fee_account: Pubkey,
+    pub pool_token_amount: u64,
+}
+
+/// Emitted when the pool admin moves the pool's fees to a new fee account
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FeeAccountSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub old_fee_account: Pubkey,
+    pub new_fee_account: Pubkey,
+}
+
+/// Emitted after every `CHECKPOINT_INTERVAL` swaps, deposits and withdrawals,
+/// with the pool's reserves and supply as they are after it.  Each checkpoint
+/// carries the hash of the one before, and the pool's state the hash of the
+/// last, so a gap or an edit in the stream of checkpoints shows against the
+/// state without replaying the transactions in between.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct CheckpointEvent {
+    pub swap: Pubkey,
+    /// Swaps, deposits and withdrawals so far, this checkpoint's number
+    /// times `CHECKPOINT_INTERVAL`
+    pub interactions: u64,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    pub pool_token_supply: u64,
+    /// Fee counters since the pool was created, see `SwapV2`
+    pub trade_fees_a: u64,
+    pub trade_fees_b: u64,
+    pub withdraw_fees: u64,
+    /// `hash` of the previous checkpoint, all zeroes for the first
+    pub previous_hash: [u8; 32],
+}
+
+impl CheckpointEvent {
+    /// SHA-256 of the checkpoint's Borsh encoding, what the next checkpoint
+    /// and the pool's `last_checkpoint_hash` refer to it by
+    pub fn hash(&self) -> [u8; 32] {
+        // writing into a Vec can't fail
+        hashv(&[&self.try_to_vec().unwrap()]).to_bytes()
+    }
+}
+
+/// Emitted when the pool admin sets or removes the per-direction owner fee
+/// accounts
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OwnerFeeAccountsSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// `Pubkey::default()` when the pool fee account takes that side's fees
+    pub owner_fee_account_a: Pubkey,
+    pub owner_fee_account_b: Pubkey,
+}
+
+/// Emitted when a swap account is grown to the latest layout
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SwapAccountResizedEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// Size of the account before, in bytes
+    pub old_len: u64,
+    pub new_len: u64,
+    /// Version of the state before, 1 for a pool migrated to V2
+    pub old_version: u8,
+}
+
+/// Emitted when the pool admin sets or stops the conversion of owner fees
+/// into a fee mint
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct FeeConversionSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// `Pubkey::default()` when owner fees are minted as pool tokens again
+    pub fee_conversion_pool: Pubkey,
+    pub fee_conversion_account: Pubkey,
+    pub fee_conversion_from_token_b: bool,
+    pub max_slippage_bps: u16,
+}
+
+/// Emitted by swaps when the owner's share of the fee is sold through the
+/// fee conversion pool and paid out in the fee mint
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OwnerFeeConvertedEvent {
+    pub swap: Pubkey,
+    pub fee_conversion_pool: Pubkey,
+    /// Owner fee sold, in the swap's source token
+    pub source_amount: u64,
+    /// Fee mint tokens paid to the fee conversion account
+    pub fee_mint_amount: u64,
+}
+
+/// Emitted when the pool admin sets the single-sided withdraw cooldown
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct WithdrawCooldownSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// 0 when the cooldown is off
+    pub cooldown_slots: u64,
+}
+
+/// Emitted when an M-of-N admin multisig is written
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct AdminMultisigInitializedEvent {
+    pub multisig: Pubkey,
+    pub m: u8,
+    pub signers: Vec<Pubkey>,
+}
+
+/// Emitted when the pool admin gates or opens the pool
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct GateSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// Gating mint or credential issuer, `Pubkey::default()` when opened
+    pub gate: Pubkey,
+    pub credential: bool,
+}
+
+/// Emitted when the pool admin routes the owner's fee on single-sided
+/// withdrawals to the remaining LPs, or back to the owner
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SingleWithdrawFeeToLpsSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub to_lps: bool,
+}
+
+/// Emitted with `PoolInitialized`, the price the creator's deposits open the
+/// pool at
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct InitialPriceEvent {
+    pub swap: Pubkey,
+    /// Raw token A per raw token B, scaled by `PRICE_SCALE`
+    pub price: u128,
+    /// Oracle price it was checked against, 0 when it wasn't
+    pub oracle_price: u64,
+}
+
+impl InitialPriceEvent {
+    pub fn new(swap: Pubkey, price: &PreciseNumber, oracle_price: u64) -> Option<Self> {
+        Some(Self {
+            swap,
+            price: to_scaled_price(price)?,
+            oracle_price,
+        })
+    }
+}
+
+/// Emitted when the pool admin turns the sandwich guard on or off
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct SandwichGuardSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub enabled: bool,
+}
+
+/// Emitted when the pool admin turns the owner fee fallback on or off
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OwnerFeeFallbackSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub enabled: bool,
+}
+
+/// Emitted before the `Swap` of a trade whose owner fee couldn't be
+/// converted into pool tokens, and went through without minting it
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct OwnerFeeSkippedEvent {
+    pub swap: Pubkey,
+    pub trade_direction: TradeDirection,
+    /// Owner fee left in the pool, in source tokens
+    pub owner_fee: u128,
+}
+
+/// Emitted when the pool admin sets the earliest slot of the pool's
+/// liquidity snapshot
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LiquiditySnapshotConfiguredEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the pool's liquidity snapshot is taken, with the supply and
+/// reserves airdrops weigh their claims against
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct LiquiditySnapshotFinalizedEvent {
+    pub swap: Pubkey,
+    /// Slot the snapshot was taken in
+    pub slot: u64,
+    pub pool_token_supply: u64,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+}
+
+/// Emitted when the pool admin links token B to the pool token of another
+/// pool, or unlinks it
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct NestedPoolSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    /// `Pubkey::default()` when unlinked
+    pub nested_pool: Pubkey,
+}
+
+/// Emitted after a deposit presenting a registered referrer, attributing the
+/// deposit to it.  The amounts are the deposit's, the counts the referrer's
+/// running totals in the pool
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct DepositReferredEvent {
+    pub swap: Pubkey,
+    /// User transfer authority of the deposit
+    pub depositor: Pubkey,
+    /// Wallet of the registered referrer
+    pub referrer: Pubkey,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    pub pool_token_amount: u64,
+    /// Deposits the referrer brought into the pool, this one included
+    pub deposits: u64,
+    /// Pool tokens minted to them
+    pub total_pool_token_amount: u64,
+}
+
+/// Emitted when the upgrade authority sets the keepers' bonding terms
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct KeeperConfigSetEvent {
+    pub authority: Pubkey,
+    pub min_bond: u64,
+    pub unbonding_slots: u64,
+}
+
+/// Emitted when a keeper bonds lamports, or adds to its bond
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct KeeperBondedEvent {
+    pub keeper: Pubkey,
+    /// Lamports added
+    pub amount: u64,
+    /// Lamports bonded, the amount included
+    pub bond: u64,
+}
+
+/// Emitted when a keeper stops cranking and starts unbonding
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct KeeperUnbondingEvent {
+    pub keeper: Pubkey,
+    pub bond: u64,
+    /// Slot from which the bond can be withdrawn
+    pub unbonding_slot: u64,
+}
+
+/// Emitted when an unbonded keeper withdraws its bond
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct KeeperBondWithdrawnEvent {
+    pub keeper: Pubkey,
+    pub amount: u64,
+}
+
+/// Emitted when the keeper config authority slashes a keeper for a bad crank
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct KeeperSlashedEvent {
+    pub keeper: Pubkey,
+    pub authority: Pubkey,
+    /// Lamports taken from the bond
+    pub amount: u64,
+    /// Why, as given by the authority, `instruction::SLASH_STALE_ORACLE` for a stale
+    /// oracle push
+    pub reason: u8,
+    /// Pool and slot of the keeper's last crank
+    pub last_crank_swap: Pubkey,
+    pub last_crank_slot: u64,
+    /// Oracle price the crank read, and the price the oracle holds in the
+    /// same slot
+    pub crank_oracle_price: u64,
+    pub oracle_price: u64,
+}
+
+/// Receipt of a crank signed by a bonded keeper, logged right before the
+/// crank's own `OraclePriceUpdated` or `StakePoolRateRefreshed` event
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct KeeperCrankedEvent {
+    pub keeper: Pubkey,
+    pub swap: Pubkey,
+    pub slot: u64,
+}
+
+/// Emitted when the pool admin restricts its cranks to bonded keepers, or
+/// opens them to anyone again
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct BondedKeepersOnlySetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub enabled: bool,
+}
+
+/// Emitted when the pool admin pauses or unpauses the pool
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PausedSetEvent {
+    pub swap: Pubkey,
+    pub admin: Pubkey,
+    pub paused: bool,
+}
+
+/// Emitted when the upgrade authority sets the governance program whose
+/// governances can sign as pool admins, a default key for none
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct GovernanceProgramSetEvent {
+    pub upgrade_authority: Pubkey,
+    pub governance_program: Pubkey,
+}
+
+/// Emitted when the upgrade authority sets the fees and curve of a pool
+/// template
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, PartialEq)]
+pub struct PoolTemplateSetEvent {
+    pub upgrade_authority: Pubkey,
+    pub template: Pubkey,
+    pub index: u8,
+}
+
+fn to_scaled_price(price: &PreciseNumber) -> Option<u128> {
+    let scaled = price
+        .checked_mul(&PreciseNumber::new(PRICE_SCALE)?)?
+        .floor()?;
+    // to_imprecise panics rather than fail past u128
+    if !scaled.less_than(&PreciseNumber::new(u128::MAX)?) {
+        return None;
+    }
+    scaled.to_imprecise()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        base::SwapCurve,
+        fees::{FeeTiers, Fees},
+    };
+
+    #[test]
+    fn swap_event_prices() {
+        let curve = SwapCurve::default();
+        let fees = Fees::standard_30bps();
+        let (swap_source_amount, swap_destination_amount) = (1_000_000, 1_000_000);
+        let result = curve
+            .swap(
+                10_000,
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+                &fees,
+                &FeeTiers::default(),
+            )
+            .unwrap();
+        let spot_price = curve
+            .calculator
+            .spot_price(
+                swap_source_amount,
+                swap_destination_amount,
+                TradeDirection::AtoB,
+            )
+            .unwrap();
+        let event = SwapEvent::new(
+            Pubkey::default(),
+            TradeDirection::AtoB,
+            &result,
+            Some(&spot_price),
+        );
+
+        // 9_871 out for 10_000 in
+        assert_eq!(result.destination_amount_swapped, 9_871);
+        assert_eq!(event.raw_effective_price, 987_100_000_000);
+        // 9_970 in after fees, against 1m reserves, moves the price by ~1%
+        assert_eq!(event.price_impact_bps, 99);
+
+        // amounts too large for the prices, or no spot price, still log
+        let result = SwapResult {
+            source_amount_swapped: 1,
+            destination_amount_swapped: u128::MAX,
+            ..result
+        };
+        let event = SwapEvent::new(Pubkey::default(), TradeDirection::AtoB, &result, None);
+        assert_eq!((event.raw_effective_price, event.price_impact_bps), (0, 0));
+        assert_eq!(event.destination_amount_swapped, u128::MAX);
+    }
+
+    #[test]
+    fn parse_events_skips_other_programs() {
+        let program_id = Pubkey::new_unique();
+        let other_program_id = Pubkey::new_unique();
+        let event = Event::Deposit(DepositEvent {
+            swap: Pubkey::new_unique(),
+            token_a_amount: 1,
+            token_b_amount: 2,
+            pool_token_amount: 3,
+        });
+        let data = format!("Program data: {}", base64::encode(event.pack()));
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            data.clone(),
+            format!("Program {} invoke [2]", other_program_id),
+            data.clone(),
+            format!("Program {} success", other_program_id),
+            "Program data: bm90IGFuIGV2ZW50".to_string(),
+            format!("Program {} success", program_id),
+            data,
+        ];
+        assert_eq!(parse_events(&program_id, &logs), vec![event]);
+    }
+}
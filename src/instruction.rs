@@ -2,14 +2,27 @@
 
 #![allow(clippy::too_many_arguments)]
 
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::constants::{
+    MAX_ADMIN_SIGNERS, MAX_FEE_EXEMPT, MAX_NAME_LEN, MAX_POOL_STATE_VIEWS, MAX_URI_LEN,
+    NATIVE_TREASURY_SEED, TAGS_LEN,
+};
+use crate::curve::{
+    base::SwapCurve,
+    fees::{FeeTiers, Fees},
+};
 use crate::error::SwapError;
+use crate::state::{
+    KeeperBond, KeeperConfig, LiquidityLock, LiquiditySnapshot, LpPosition, OracleConfig,
+    PoolMetadata, PoolTemplate, ProgramConfig, ReferredDeposits, Referrer, Vesting,
+};
 use solana_program::{
+    bpf_loader_upgradeable,
     instruction::{AccountMeta, Instruction},
     msg,
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
+    system_program, sysvar,
 };
 use std::convert::TryInto;
 use std::mem::size_of;
@@ -28,6 +41,21 @@ pub struct Initialize {
     /// swap curve info for pool, including CurveType and anything
     /// else that may be required
     pub swap_curve: SwapCurve,
+    /// number of slots over which the initial pool tokens vest linearly to
+    /// the creator, 0 mints them to the creator straight away
+    pub vesting_slots: u64,
+    /// most token A the pool trades within a single slot, counting both
+    /// directions and the token A single-sided deposits and withdrawals
+    /// move, token B valued at the reserves' ratio, 0 for no cap
+    pub max_slot_volume: u64,
+    /// slot until which trades pay no fees, exclusive, 0 for no fee holiday
+    pub fee_holiday_end_slot: u64,
+    /// farthest the price the deposits open the pool at can be from the
+    /// oracle's, in basis points of the oracle price, 0 for no oracle check
+    pub initial_price_band_bps: u16,
+    /// byte offset of the little-endian u64 price in the oracle account, in
+    /// raw token A per raw token B
+    pub oracle_price_offset: u32,
 }
 
 /// Swap instruction data
@@ -39,6 +67,11 @@ pub struct Swap {
     pub amount_in: u64,
     /// Minimum amount of DESTINATION token to output, prevents excessive slippage
     pub minimum_amount_out: u64,
+    /// Minimum price paid, in DESTINATION per SOURCE token, as a limit price
+    /// holding for any `amount_in`.  A zero denominator sets no limit
+    pub min_price_numerator: u64,
+    /// Denominator of the minimum price
+    pub min_price_denominator: u64,
 }
 
 /// DepositAllTokenTypes instruction data
@@ -93,6 +126,403 @@ pub struct WithdrawSingleTokenTypeExactAmountOut {
     pub maximum_pool_token_amount: u64,
 }
 
+/// RegisterReferrer instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegisterReferrer {
+    /// Share of the owner fee going to the referrer, replaces the pool's
+    /// host fee for swaps routed through this referrer's host fee accounts
+    pub host_fee_numerator: u64,
+    pub host_fee_denominator: u64,
+}
+
+/// ReinitializeCurve instruction data
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct ReinitializeCurve {
+    /// new swap curve info for the pool, including CurveType and anything
+    /// else that may be required
+    pub swap_curve: SwapCurve,
+}
+
+/// LockLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct LockLiquidity {
+    /// Pool tokens to lock
+    pub amount: u64,
+    /// First slot at which the tokens can be unlocked
+    pub unlock_slot: u64,
+}
+
+/// DepositAndLockLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DepositAndLockLiquidity {
+    /// Pool token amount to deposit for, as in `DepositAllTokenTypes`
+    pub pool_token_amount: u64,
+    /// Maximum token A amount to deposit, prevents excessive slippage
+    pub maximum_token_a_amount: u64,
+    /// Maximum token B amount to deposit, prevents excessive slippage
+    pub maximum_token_b_amount: u64,
+    /// First slot at which the minted tokens can be unlocked
+    pub unlock_slot: u64,
+}
+
+/// UnlockLiquidity instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct UnlockLiquidity {
+    /// Unlock slot of the lock to release, part of its address
+    pub unlock_slot: u64,
+}
+
+/// SetOracleConfig instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetOracleConfig {
+    /// Byte offset of the little-endian u64 price in the oracle account
+    pub price_offset: u32,
+    /// Lowest price the crank may set
+    pub min_price: u64,
+    /// Highest price the crank may set
+    pub max_price: u64,
+    /// Largest price move per slot, in basis points of the current price
+    pub max_step_bps: u16,
+}
+
+/// AdminDepositAllTokenTypes / AdminWithdrawAllTokenTypes instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct AdminMoveLiquidity {
+    /// Pool token amount to mint or burn, at whatever token amounts that takes
+    pub pool_token_amount: u64,
+}
+
+/// SetRebalancer instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetRebalancer {
+    /// New rebalancer of the pool, `Pubkey::default()` to remove it
+    pub rebalancer: Pubkey,
+}
+
+/// SetPoolMetadata instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetPoolMetadata {
+    /// Short display name, UTF-8, zero padded
+    pub name: [u8; MAX_NAME_LEN],
+    /// Link to further details, UTF-8, zero padded
+    pub uri: [u8; MAX_URI_LEN],
+    /// Free-form tag bytes
+    pub tags: [u8; TAGS_LEN],
+}
+
+/// SetEmergency instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetEmergency {
+    /// Put the pool in emergency mode, or take it back out
+    pub enabled: bool,
+}
+
+/// SetDepositBonus instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetDepositBonus {
+    /// First slot locks earn the bonus
+    pub start_slot: u64,
+    /// Slot the bonus ends, exclusive, equal to `start_slot` to turn it off
+    pub end_slot: u64,
+    /// Bonus in basis points of the pool tokens locked, at most 10_000
+    pub bonus_bps: u16,
+    /// Slots pool tokens must stay locked for to earn the bonus, nonzero
+    /// with a bonus
+    pub lockup_slots: u64,
+}
+
+/// SetFeeExempt instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetFeeExempt {
+    /// Accounts exempt from the owner's fees, `Pubkey::default()` for an
+    /// unused slot
+    pub fee_exempt: [Pubkey; MAX_FEE_EXEMPT],
+}
+
+/// SetWithdrawFeeDestination instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetWithdrawFeeDestination {
+    /// Pay withdraw fees in token A and B to the fee vaults, instead of as
+    /// pool tokens to the pool fee account
+    pub underlying: bool,
+}
+
+/// SetFeeTiers instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetFeeTiers {
+    /// Size tiers of the trade fee, zeroed to charge the base fees on every
+    /// trade
+    pub fee_tiers: FeeTiers,
+}
+
+/// SimulateSwap instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SimulateSwap {
+    /// SOURCE amount to quote the swap for
+    pub amount_in: u64,
+}
+
+/// FlashSwap instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlashSwap {
+    /// DESTINATION amount paid out before the callback
+    pub amount_out: u64,
+    /// Instruction data of the callback, the rest of the instruction
+    pub callback_data: Vec<u8>,
+}
+
+/// SetExactInput instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetExactInput {
+    /// Swaps consume all of `amount_in`, the rounding dust going to the
+    /// reserves
+    pub exact_input: bool,
+}
+
+/// SetStakePool instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetStakePool {
+    /// Stake pool whose rate becomes the pool's price, `Pubkey::default()`
+    /// to go back to admin set prices
+    pub stake_pool: Pubkey,
+}
+
+/// SetDefaultHost instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetDefaultHost {
+    /// Pool token account earning the host fee of swaps without one,
+    /// `Pubkey::default()` to leave that share with the owner
+    pub host_fee_account: Pubkey,
+}
+
+/// SetOwnerFeeAccounts instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetOwnerFeeAccounts {
+    /// Pool token account the owner fee of A to B trades is minted to,
+    /// `Pubkey::default()` for the pool fee account
+    pub owner_fee_account_a: Pubkey,
+    /// Pool token account the owner fee of B to A trades is minted to,
+    /// `Pubkey::default()` for the pool fee account
+    pub owner_fee_account_b: Pubkey,
+}
+
+/// SetFeeConversion instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetFeeConversion {
+    /// Pool of this program trading one of the pool's tokens for the fee
+    /// mint, `Pubkey::default()` to stop converting owner fees
+    pub fee_conversion_pool: Pubkey,
+    /// Fee mint token account the converted owner fees are paid to
+    pub fee_conversion_account: Pubkey,
+    /// Most the sale of an owner fee may get below the conversion pool's
+    /// spot price, in basis points of it
+    pub max_slippage_bps: u16,
+}
+
+/// InitializeAdminMultisig instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitializeAdminMultisig {
+    /// Signatures required
+    pub m: u8,
+    /// Keys that can sign, `Pubkey::default()` for the unused slots at the end
+    pub signers: [Pubkey; MAX_ADMIN_SIGNERS],
+}
+
+/// InitializeFromTemplate instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct InitializeFromTemplate {
+    /// nonce used to create valid program address
+    pub nonce: u8,
+    /// index of the pool template giving the fees and curve
+    pub template: u8,
+    /// number of slots over which the initial pool tokens vest linearly to
+    /// the creator, 0 mints them to the creator straight away
+    pub vesting_slots: u64,
+    /// most token A the pool trades within a single slot, counting both
+    /// directions and the token A single-sided deposits and withdrawals
+    /// move, token B valued at the reserves' ratio, 0 for no cap
+    pub max_slot_volume: u64,
+    /// slot until which trades pay no fees, exclusive, 0 for no fee holiday
+    pub fee_holiday_end_slot: u64,
+}
+
+/// SetWithdrawCooldown instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetWithdrawCooldown {
+    /// Slots after a single-sided deposit into a pool token account before
+    /// it can be withdrawn from single-sided, 0 to turn the cooldown off
+    pub cooldown_slots: u64,
+}
+
+/// SetGate instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetGate {
+    /// Mint of the token traders must hold, or the program issuing the
+    /// credentials they must have, `Pubkey::default()` to open the pool
+    pub gate: Pubkey,
+    /// `gate` is a credential issuing program rather than a mint
+    pub credential: bool,
+}
+
+/// SetSingleWithdrawFeeToLps instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetSingleWithdrawFeeToLps {
+    /// Leave the owner's fee on single-sided withdrawals in the pool, for the
+    /// remaining LPs, instead of paying it to the owner
+    pub to_lps: bool,
+}
+
+/// SetSandwichGuard instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetSandwichGuard {
+    /// Refuse swaps in transactions where another trader swaps the pool
+    /// both ways
+    pub enabled: bool,
+}
+
+/// SetOwnerFeeFallback instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetOwnerFeeFallback {
+    /// Let swaps whose owner fee can't be converted into pool tokens go
+    /// through without it
+    pub enabled: bool,
+}
+
+/// WithdrawMax instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithdrawMax {
+    /// Minimum amount of token A to receive, prevents excessive slippage
+    pub minimum_token_a_amount: u64,
+    /// Minimum amount of token B to receive, prevents excessive slippage
+    pub minimum_token_b_amount: u64,
+}
+
+/// ConfigureLiquiditySnapshot instruction data
+#[cfg_attr(feature = "fuzz", derive(Arbitrary))]
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ConfigureLiquiditySnapshot {
+    /// Earliest slot the snapshot can be taken in
+    pub slot: u64,
+}
+
+/// SetNestedPool instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetNestedPool {
+    /// Pool of this program whose pool token is token B,
+    /// `Pubkey::default()` to unlink it
+    pub nested_pool: Pubkey,
+}
+
+/// SetKeeperConfig instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetKeeperConfig {
+    /// Lamports a keeper must have bonded to crank
+    pub min_bond: u64,
+    /// Slots between a keeper unbonding and withdrawing its bond
+    pub unbonding_slots: u64,
+}
+
+/// RegisterKeeper instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct RegisterKeeper {
+    /// Lamports added to the bond
+    pub amount: u64,
+}
+
+/// Slashing reason of a keeper that pushed an oracle price it should have
+/// known was stale
+pub const SLASH_STALE_ORACLE: u8 = 0;
+
+/// SlashKeeper instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlashKeeper {
+    /// Lamports taken from the bond, at most `MAX_SLASH_BPS` of it
+    pub amount: u64,
+    /// Why, `SLASH_STALE_ORACLE` or a code of the authority's own, logged
+    pub reason: u8,
+}
+
+/// SetBondedKeepersOnly instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetBondedKeepersOnly {
+    /// Refuse cranks not signed by a bonded keeper
+    pub enabled: bool,
+}
+
+/// SetPaused instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetPaused {
+    /// Halt trades and deposits
+    pub paused: bool,
+}
+
+/// SetGovernanceProgram instruction data
+#[repr(C)]
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetGovernanceProgram {
+    /// SPL Governance program whose governances can be pool admins,
+    /// `Pubkey::default()` for none
+    pub governance_program: Pubkey,
+}
+
+/// SetPoolTemplate instruction data
+#[repr(C)]
+#[derive(Debug, PartialEq)]
+pub struct SetPoolTemplate {
+    /// Index pools pick the template by
+    pub index: u8,
+    /// Fees of the pools created from it
+    pub fees: Fees,
+    /// Curve of the pools created from it
+    pub swap_curve: SwapCurve,
+}
+
 /// Instructions supported by the token swap program.
 #[repr(C)]
 #[derive(Debug, PartialEq)]
@@ -109,11 +539,32 @@ pub enum SwapInstruction {
     ///   6. `[writable]` Pool Token Account to deposit the initial pool token
     ///   supply.  Must be empty, not owned by swap authority.
     ///   7. '[]` Token program id
+    ///   8. `[]` token_a Mint, with at most 18 decimals.
+    ///   9. `[]` token_b Mint, with at most 18 decimals.
+    ///
+    ///   With `vesting_slots > 0`, account 6 must be owned by the swap authority
+    ///   and holds the initial supply until it vests, plus:
+    ///
+    ///   10. `[writable]` Vesting account, derived from `[VESTING_SEED, Token-swap account]`
+    ///   11. `[]` Pool Token Account of the creator, receiving the vested tokens
+    ///   12. `[writable, signer]` Payer funding the vesting account
+    ///   13. `[]` System program
+    ///
+    ///   With `initial_price_band_bps > 0`, the oracle account comes next, see
+    ///   `with_initial_price_oracle`.  The price the deposits open the pool
+    ///   at, token A per token B, must be within the band of its price.
+    ///
+    ///   Production builds then take the program config account, derived from
+    ///   `[PROGRAM_CONFIG_SEED]`, and the program data account of this program.
     Initialize(Initialize),
 
     ///   Swap the tokens in the pool.
     ///
-    ///   0. `[]` Token-swap
+    ///   The Token-swap account is writable, for V2 pools, where earlier
+    ///   versions of the program took it read-only: clients building the
+    ///   accounts themselves must mark it writable or V2 swaps fail.
+    ///
+    ///   0. `[writable]` Token-swap, tracking the volume traded in the current slot
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
@@ -123,14 +574,32 @@ pub enum SwapInstruction {
     ///   7. `[writable]` Pool token mint, to generate trading fees
     ///   8. `[writable]` Fee account, to receive trading fees
     ///   9. '[]` Token program id
-    ///   10 `[optional, writable]` Host fee account to receive additional trading fees
+    ///   10 `[optional, writable]` Host fee account to receive additional trading fees,
+    ///      the pool's default host earning them when left out
+    ///   11 `[optional]` Referrer account registered for the host fee account's owner,
+    ///      overriding the pool's host fee share
+    ///
+    ///   When the pool converts owner fees paid in the SOURCE token, the fee
+    ///   conversion accounts come before the host fee account, at 10-15:
+    ///
+    ///   10 `[writable]` Fee conversion pool, counting the conversion towards its volume cap
+    ///   11 `[]` Fee conversion pool's swap authority
+    ///   12 `[writable]` Fee conversion pool's account of the SOURCE token
+    ///   13 `[writable]` Fee conversion pool's account of the fee mint
+    ///   14 `[]` Fee conversion pool's pool mint
+    ///   15 `[writable]` Fee conversion account, to receive the converted owner fees
+    ///
+    ///   When the pool is gated, the user transfer authority's gate account
+    ///   comes before all of them, at 10, moving the rest up one.  When the
+    ///   pool guards against sandwiches, the instructions sysvar comes next,
+    ///   moving the rest up another.
     Swap(Swap),
 
     ///   Deposit both types of tokens into the pool.  The output is a "pool"
     ///   token representing ownership in the pool. Inputs are converted to
     ///   the current ratio.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, counting the interaction towards its next checkpoint
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_a user transfer authority can transfer amount,
@@ -140,13 +609,18 @@ pub enum SwapInstruction {
     ///   7. `[writable]` Pool MINT account, swap authority is the owner.
     ///   8. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
     ///   9. '[]` Token program id
+    ///   10 `[]` Gate account of the user transfer authority, when the pool is gated
+    ///   11 `[optional]` Referrer account registered for the wallet the deposit is attributed to
+    ///   12 `[writable]` Referrer's deposits into the pool, derived from `[REFERRAL_SEED, swap, referrer wallet]`, with a referrer
+    ///   13 `[writable, signer]` Payer funding the referrer's deposits account, with a referrer
+    ///   14 `[]` System program, with a referrer
     DepositAllTokenTypes(DepositAllTokenTypes),
 
     ///   Withdraw both types of tokens from the pool at the current ratio, given
     ///   pool tokens.  The pool tokens are burned in exchange for an equivalent
     ///   amount of token A and B.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, counting the interaction towards its next checkpoint
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` Pool mint account, swap authority is the owner
@@ -157,13 +631,15 @@ pub enum SwapInstruction {
     ///   8. `[writable]` token_b user Account to credit.
     ///   9. `[writable]` Fee account, to receive withdrawal fees
     ///   10 '[]` Token program id
+    ///   11 `[optional, writable]` token_a fee vault, required when the pool pays withdraw fees in underlying tokens
+    ///   12 `[optional, writable]` token_b fee vault, required when the pool pays withdraw fees in underlying tokens
     WithdrawAllTokenTypes(WithdrawAllTokenTypes),
 
     ///   Deposit one type of tokens into the pool.  The output is a "pool" token
     ///   representing ownership into the pool. Input token is converted as if
     ///   a swap and deposit all token types were performed.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, counting the interaction towards its next checkpoint
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` token_(A|B) SOURCE Account, amount is transferable by user transfer authority,
@@ -172,12 +648,20 @@ pub enum SwapInstruction {
     ///   6. `[writable]` Pool MINT account, swap authority is the owner.
     ///   7. `[writable]` Pool Account to deposit the generated tokens, user is the owner.
     ///   8. '[]` Token program id
+    ///   9. `[]` Gate account of the user transfer authority, when the pool is gated
+    ///   10. `[writable]` LP position of the Pool Account, derived from `[LP_POSITION_SEED, swap, pool account]`, when the pool has a withdraw cooldown
+    ///   11. `[writable, signer]` Payer funding the LP position account, when the pool has a withdraw cooldown
+    ///   12. `[]` System program, when the pool has a withdraw cooldown
+    ///   13. `[optional]` Referrer account registered for the wallet the deposit is attributed to
+    ///   14. `[writable]` Referrer's deposits into the pool, derived from `[REFERRAL_SEED, swap, referrer wallet]`, with a referrer
+    ///   15. `[writable, signer]` Payer funding the referrer's deposits account, with a referrer
+    ///   16. `[]` System program, with a referrer
     DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn),
 
     ///   Withdraw one token type from the pool at the current ratio given the
     ///   exact amount out expected.
     ///
-    ///   0. `[]` Token-swap
+    ///   0. `[writable]` Token-swap, counting the interaction towards its next checkpoint
     ///   1. `[]` swap authority
     ///   2. `[]` user transfer authority
     ///   3. `[writable]` Pool mint account, swap authority is the owner
@@ -187,68 +671,720 @@ pub enum SwapInstruction {
     ///   7. `[writable]` token_(A|B) User Account to credit
     ///   8. `[writable]` Fee account, to receive withdrawal fees
     ///   9. '[]` Token program id
+    ///   10 `[]` LP position of the SOURCE Pool account, derived from `[LP_POSITION_SEED, swap, pool account]`, when the pool has a withdraw cooldown
+    ///   11 `[optional, writable]` token_a fee vault, required when the pool pays withdraw fees in underlying tokens
+    ///   12 `[optional, writable]` token_b fee vault, required when the pool pays withdraw fees in underlying tokens
     WithdrawSingleTokenTypeExactAmountOut(WithdrawSingleTokenTypeExactAmountOut),
-}
 
-impl SwapInstruction {
-    /// Unpacks a byte buffer into a [SwapInstruction](enum.SwapInstruction.html).
-    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
-        let (&tag, rest) = input.split_first().ok_or(SwapError::InvalidInstruction)?;
-        Ok(match tag {
-            0 => {
-                let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
-                if rest.len() >= Fees::LEN {
-                    let (fees, rest) = rest.split_at(Fees::LEN);
-                    let fees = Fees::unpack_unchecked(fees)?;
-                    let swap_curve = SwapCurve::unpack_unchecked(rest)?;
+    ///   Registers a referrer, or updates the share of an existing one.  The
+    ///   share is capped by the swap constraints, or by the default referrer
+    ///   ceiling of half the owner fee on a program without them.
+    ///
+    ///   0. `[writable]` Referrer account, derived from `[REFERRER_SEED, owner]`
+    ///   1. `[signer]` Referrer owner, owning the host fee accounts
+    ///   2. `[writable, signer]` Payer funding the referrer account
+    ///   3. `[]` System program
+    ///   4. `[optional, signer]` Program owner, required when the program has swap constraints
+    RegisterReferrer(RegisterReferrer),
 
-                    msg!("swap curve is: {:?}", swap_curve);
+    ///   Releases the pool tokens vested so far to the creator. Anyone can
+    ///   crank this, the tokens only ever go to the recorded beneficiary.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Vesting account
+    ///   3. `[writable]` Pool Token Account holding the unvested supply
+    ///   4. `[writable]` Pool Token Account of the creator
+    ///   5. '[]` Token program id
+    ClaimVestedPoolTokens,
 
-                    Self::Initialize(Initialize {
-                        nonce,
-                        fees,
-                        swap_curve,
-                    })
-                } else {
-                    return Err(SwapError::InvalidInstruction.into());
-                }
-            }
-            1 => {
-                let (amount_in, rest) = Self::unpack_u64(rest)?;
-                let (minimum_amount_out, _rest) = Self::unpack_u64(rest)?;
-                Self::Swap(Swap {
-                    amount_in,
-                    minimum_amount_out,
-                })
-            }
-            2 => {
-                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
-                let (maximum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositAllTokenTypes(DepositAllTokenTypes {
-                    pool_token_amount,
-                    maximum_token_a_amount,
-                    maximum_token_b_amount,
-                })
-            }
-            3 => {
-                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
-                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
-                    pool_token_amount,
-                    minimum_token_a_amount,
-                    minimum_token_b_amount,
-                })
-            }
-            4 => {
-                let (source_token_amount, rest) = Self::unpack_u64(rest)?;
-                let (minimum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
-                Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
-                    source_token_amount,
-                    minimum_pool_token_amount,
-                })
-            }
+    ///   Replaces the curve of an empty pool, keeping the pool address.  The
+    ///   pool is empty when no pool tokens are outstanding, and gets a fresh
+    ///   initial supply.  The new curve is validated exactly as on initialize.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` Pool admin, owner of the pool fee account
+    ///   3. `[]` Pool fee account
+    ///   4. `[]` token_a Swap Account
+    ///   5. `[]` token_b Swap Account
+    ///   6. `[writable]` Pool token mint
+    ///   7. `[writable]` Pool Token Account receiving the fresh initial supply
+    ///   8. '[]` Token program id
+    ReinitializeCurve(ReinitializeCurve),
+
+    ///   Reports the reserves, pool token supply and fees of up to
+    ///   `MAX_POOL_STATE_VIEWS` pools through the return data, one packed
+    ///   `PoolStateView` per pool, in account order.  Nothing is written, so
+    ///   it is meant to be simulated rather than sent.
+    ///
+    ///   For each pool:
+    ///   0. `[]` Token-swap
+    ///   1. `[]` token_a Swap Account
+    ///   2. `[]` token_b Swap Account
+    ///   3. `[]` Pool token mint
+    GetMultiplePoolStates,
+
+    ///   Escrows pool tokens until `unlock_slot`.  The lock account is the
+    ///   owner's non-transferable receipt, locking again with the same unlock
+    ///   slot tops it up.  Each lock holds its tokens in a vault of its own,
+    ///   which must be empty when the lock is created.  Locking pool tokens
+    ///   held already earns no deposit bonus, see `DepositAndLockLiquidity`.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Lock account, derived from `[LOCK_SEED, Token-swap, owner, unlock_slot]`
+    ///   3. `[signer]` Lock owner, with the right to move the source tokens
+    ///   4. `[writable]` Pool Token Account to lock the tokens from
+    ///   5. `[writable]` Pool Token Account owned by the swap authority, holding only this lock's tokens
+    ///   6. `[writable, signer]` Payer funding the lock account
+    ///   7. `[]` System program
+    ///   8. '[]` Token program id
+    LockLiquidity(LockLiquidity),
+
+    ///   Releases all the pool tokens of a lock once its unlock slot is
+    ///   reached, and closes the lock account and its emptied vault.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Lock account
+    ///   3. `[writable, signer]` Lock owner, receiving the lock and emptied vault's rent
+    ///   4. `[writable]` Pool Token Account holding the locked tokens
+    ///   5. `[writable]` Pool Token Account to release the tokens to
+    ///   6. '[]` Token program id
+    UnlockLiquidity(UnlockLiquidity),
+
+    ///   Pegs a constant price pool to an oracle, or updates its bounds.
+    ///
+    ///   0. `[]` Token-swap, using the constant price curve
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[writable]` Oracle config account, derived from `[ORACLE_SEED, Token-swap]`
+    ///   4. `[]` Oracle account publishing the price
+    ///   5. `[writable, signer]` Payer funding the oracle config account
+    ///   6. `[]` System program
+    SetOracleConfig(SetOracleConfig),
+
+    ///   Moves the pool's `token_b_price` toward the oracle price, within the
+    ///   bounds of its oracle config.  Anyone can crank it, unless the pool
+    ///   takes cranks from bonded keepers only.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[writable]` Oracle config account
+    ///   2. `[]` Oracle account
+    ///   3. `[]` Keeper config account, for a pool taking bonded keepers only
+    ///   4. `[writable]` Keeper bond account, likewise
+    ///   5. `[signer]` Keeper, likewise
+    CrankOraclePrice,
+
+    ///   `DepositAllTokenTypes` without the maximum token amounts, for
+    ///   protocol-coordinated migrations of pool-owned liquidity.  Only the
+    ///   pool admin can use it, and an audit event is logged.
+    ///
+    ///   0. `[signer]` Pool admin, owner of the pool fee account
+    ///   1. `[]` Pool fee account
+    ///   2. .. the `DepositAllTokenTypes` accounts
+    AdminDepositAllTokenTypes(AdminMoveLiquidity),
+
+    ///   `WithdrawAllTokenTypes` without the minimum token amounts, see
+    ///   `AdminDepositAllTokenTypes`.
+    ///
+    ///   0. `[signer]` Pool admin, owner of the pool fee account
+    ///   1. `[]` Pool fee account
+    ///   2. .. the `WithdrawAllTokenTypes` accounts
+    AdminWithdrawAllTokenTypes(AdminMoveLiquidity),
+
+    ///   Swap by the pool's rebalancer.  Only the LP trade fee is charged,
+    ///   and it stays in the reserves, so no pool tokens are minted and the
+    ///   fee accounts aren't needed.
+    ///
+    ///   0. `[writable]` Token-swap, tracking the volume traded in the current slot
+    ///   1. `[]` swap authority
+    ///   2. `[signer]` Rebalancer, with the right to move the SOURCE tokens
+    ///   3. `[writable]` token_(A|B) SOURCE Account
+    ///   4. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
+    ///   5. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
+    ///   6. `[writable]` token_(A|B) DESTINATION Account
+    ///   7. '[]` Token program id
+    ///   8. `[]` Pool token mint, for the checkpoint's supply
+    RebalanceSwap(Swap),
+
+    ///   Sets or removes the pool's rebalancer.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetRebalancer(SetRebalancer),
+
+    ///   Labels a pool with a name, URI and tags, creating its metadata
+    ///   account on first use.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[writable]` Pool metadata account, derived from `[METADATA_SEED, Token-swap]`
+    ///   4. `[writable, signer]` Payer funding the pool metadata account
+    ///   5. `[]` System program
+    SetPoolMetadata(SetPoolMetadata),
+
+    ///   Puts the pool in or takes it out of emergency mode, in which LPs can
+    ///   exit through `EmergencyWithdraw` when the curve math is broken.
+    ///   Nothing trades against the curve meanwhile: swaps, flash swaps,
+    ///   rebalances, deposits and single-sided withdrawals are refused.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetEmergency(SetEmergency),
+
+    ///   Withdraw both types of tokens from a pool in emergency mode, strictly
+    ///   pro-rata to the swap accounts' balances.  Skips the curve and charges
+    ///   no withdrawal fee.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[]` user transfer authority
+    ///   3. `[writable]` Pool mint account, swap authority is the owner
+    ///   4. `[writable]` SOURCE Pool account, amount is transferable by user transfer authority.
+    ///   5. `[writable]` token_a Swap Account to withdraw FROM.
+    ///   6. `[writable]` token_b Swap Account to withdraw FROM.
+    ///   7. `[writable]` token_a user Account to credit.
+    ///   8. `[writable]` token_b user Account to credit.
+    ///   9. '[]` Token program id
+    EmergencyWithdraw(WithdrawAllTokenTypes),
+
+    ///   Configures the deposit bonus window.  During it,
+    ///   `DepositAndLockLiquidity` locking the pool tokens it mints for at
+    ///   least `lockup_slots` gets a bonus on them in pool tokens out of the
+    ///   pool fee account, up to what the pool admin has approved the swap
+    ///   authority to spend.  The bonus is paid into the lock, so it vests
+    ///   with the liquidity it rewards, and depositing to withdraw straight
+    ///   away earns nothing.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetDepositBonus(SetDepositBonus),
+
+    ///   Replaces the treasury accounts exempt from the owner's fees.  Swaps,
+    ///   deposits and withdrawals out of them, or out of the pool fee account,
+    ///   only pay the LP trade fee.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetFeeExempt(SetFeeExempt),
+
+    ///   Records the program's current upgrade authority in the program
+    ///   config, creating it on first use.  Production builds only initialize
+    ///   pools once it's documented.
+    ///
+    ///   0. `[writable]` Program config account, derived from `[PROGRAM_CONFIG_SEED]`
+    ///   1. `[]` Program data account of this program, owned by the upgradeable BPF loader
+    ///   2. `[signer]` Upgrade authority, any account once the program is immutable
+    ///   3. `[writable, signer]` Payer funding the program config account
+    ///   4. `[]` System program
+    DocumentUpgradeAuthority,
+
+    ///   Chooses whether withdraw fees accrue as pool tokens in the pool fee
+    ///   account, or are burned and paid out as their share of token A and B
+    ///   into the fee vaults.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[optional]` token_a fee vault, required when paying in underlying tokens
+    ///   4. `[optional]` token_b fee vault, required when paying in underlying tokens
+    SetWithdrawFeeDestination(SetWithdrawFeeDestination),
+
+    ///   Replaces the size tiers of the trade fee.  Swaps of at least a tier's
+    ///   threshold in source tokens pay that tier's lower fees.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetFeeTiers(SetFeeTiers),
+
+    ///   Quotes a swap with the exact fees and rounding of `Swap`, as a packed
+    ///   `SwapQuote` in the return data, then fails with `SimulationComplete`
+    ///   so the transaction is rolled back.  Meant for `simulateTransaction`.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` token_(A|B) SOURCE Account of the user, deciding fee exemption
+    ///   2. `[]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
+    ///   3. `[]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
+    SimulateSwap(SimulateSwap),
+
+    ///   Optimistic swap: pays `amount_out` to the user first, invokes the
+    ///   callback program, then checks the vaults were repaid at least what
+    ///   `Swap` would charge for that output.  Repayment is in SOURCE tokens,
+    ///   or in kind in DESTINATION tokens plus the fees on `amount_out`.  The
+    ///   owner fee is minted as in `Swap`, from the token the fees were repaid
+    ///   in, with the default host taking its share.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` token_(A|B) Base Account to swap INTO.  Must be the SOURCE token.
+    ///   3. `[writable]` token_(A|B) Base Account to swap FROM.  Must be the DESTINATION token.
+    ///   4. `[writable]` token_(A|B) DESTINATION Account assigned to USER as the owner.
+    ///   5. `[writable]` Pool token mint, to generate trading fees
+    ///   6. `[writable]` Fee account, to receive trading fees
+    ///   7. `[]` Token program id
+    ///   8. `[]` Callback program, anything but the token swap program
+    ///   9. ..9+N. The callback's accounts, passed on with their signer and writable flags, `limits::MAX_CALLBACK_ACCOUNTS` at most
+    ///
+    ///   When the pool converts owner fees paid in the SOURCE token, the fee
+    ///   conversion accounts of `Swap` come before the callback program, at
+    ///   8-13, moving the rest up six.
+    FlashSwap(FlashSwap),
+
+    ///   Chooses whether swaps consume exactly `amount_in`, the source tokens
+    ///   the curve's rounding doesn't need going to the reserves, or leave
+    ///   them with the user.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetExactInput(SetExactInput),
+
+    ///   Pegs a constant price pool of wrapped SOL and a stake pool's LST to
+    ///   the stake pool's exchange rate, or unlinks it.  A linked pool only
+    ///   trades in the slot of a `RefreshStakePoolRate`, so it is usually
+    ///   sent in the same transaction.
+    ///
+    ///   0. `[writable]` Token-swap, using the constant price curve
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[]` Stake pool account, when linking
+    SetStakePool(SetStakePool),
+
+    ///   Sets the price of a linked pool to its stake pool's current rate.
+    ///   Anyone can send it, unless the pool takes cranks from bonded keepers
+    ///   only.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` Stake pool account
+    ///   2. `[]` Keeper config account, for a pool taking bonded keepers only
+    ///   3. `[writable]` Keeper bond account, likewise
+    ///   4. `[signer]` Keeper, likewise
+    RefreshStakePoolRate,
+
+    ///   Reports a pool's imbalance, reserve depletion, trade staleness and
+    ///   pause flags through the return data, as a packed `PoolHealth`, so
+    ///   monitoring can alert off a single simulation.  Nothing is written.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[]` token_a Swap Account
+    ///   2. `[]` token_b Swap Account
+    GetHealth,
+
+    ///   Sets the pool token account earning the host fee of swaps that
+    ///   don't bring a host fee account, for integrators that can't pass one.
+    ///   Fees it hasn't claimed yet go to the new account.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[]` Default host fee account, when setting one
+    SetDefaultHost(SetDefaultHost),
+
+    ///   Mints the host fees the default host earned to its fee account.
+    ///   Anyone can send it.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[]` swap authority
+    ///   2. `[writable]` Pool token mint
+    ///   3. `[writable]` Default host fee account
+    ///   4. `[]` Token program id
+    ClaimHostFees,
+
+    ///   Moves the pool's fee destination to a new pool token account, for
+    ///   rotating a compromised fee account or handing it to a multisig.
+    ///   The new account's owner becomes the pool admin.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[]` New pool fee account
+    SetFeeAccount,
+
+    ///   Splits the owner fee of swaps by direction, minting the fee of A to
+    ///   B trades, paid in token A, to one pool token account and of B to A
+    ///   trades to another, so a treasury can handle each side's fees on its
+    ///   own.  Either side left unset goes to the pool fee account, as do
+    ///   both on V1 pools.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[]` Owner fee account for A to B trades, when setting one
+    ///   4. `[]` Owner fee account for B to A trades, when setting one
+    SetOwnerFeeAccounts(SetOwnerFeeAccounts),
+
+    ///   Grows a swap account written by an older version of the program to
+    ///   the latest layout in place, keeping the pool's address.  The payer
+    ///   tops the account up to rent exemption at its new size, and the new
+    ///   fields start zeroed, their defaults.  V1 pools are migrated to V2,
+    ///   recording their mints' decimals.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[writable, signer]` Payer of the rent top-up
+    ///   4. `[]` System program
+    ///   5. `[]` Pool token mint, when migrating a V1 pool
+    ///   6. `[]` Token A mint, when migrating a V1 pool
+    ///   7. `[]` Token B mint, when migrating a V1 pool
+    ResizeSwapAccount,
+
+    ///   Collects the owner fee of swaps in a fee mint, a protocol token say,
+    ///   rather than in pool tokens.  The fee conversion pool, another pool
+    ///   of this program, trades one of the pool's tokens for the fee mint:
+    ///   the owner's share of the fees of swaps paid in that token is sold
+    ///   through it at swap time, without its owner fee, and paid to the fee
+    ///   conversion account.  Owner fees paid in the other token, and the
+    ///   host's share of all of them, are still minted as pool tokens.
+    ///
+    ///   The sale takes the conversion pool's checks like any swap, and
+    ///   gets no less than `max_slippage_bps` below its spot price.  It's
+    ///   skipped, the owner fee minted as pool tokens instead, when it
+    ///   can't, or when the conversion pool was already traded in the slot,
+    ///   so a trade moving its price just before can't set the sale's.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[]` Fee conversion pool, when setting one
+    ///   4. `[]` Fee conversion account, when setting one
+    SetFeeConversion(SetFeeConversion),
+
+    ///   Sets the slots single-sided withdrawals from a pool token account
+    ///   wait after the last single-sided deposit into it, so a deposit and
+    ///   withdrawal can't be paired in one slot to move the price the pool
+    ///   quotes.  The deposit is recorded in the account's LP position, so
+    ///   other holders' withdrawals go through.  Deposits and withdrawals of
+    ///   both tokens aren't affected.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetWithdrawCooldown(SetWithdrawCooldown),
+
+    ///   Writes an M-of-N list of signers into a new account, which can then
+    ///   own a pool fee account in place of a single admin key.  Admin
+    ///   instructions of the pool take the multisig account as the admin,
+    ///   not signing, and `m` of its signers anywhere in their accounts.
+    ///   spl-token multisigs work the same way.
+    ///
+    ///   0. `[writable]` Admin multisig account, uninitialized and owned by the program
+    InitializeAdminMultisig(InitializeAdminMultisig),
+
+    ///   Initializes a new swap with the fees and curve of a pool template
+    ///   set by `SetPoolTemplate`.  Production builds only create pools this
+    ///   way.
+    ///
+    ///   0. `[]` Pool template account, derived from `[POOL_TEMPLATE_SEED, template]`
+    ///   1. ..  The accounts of `Initialize`
+    InitializeFromTemplate(InitializeFromTemplate),
+
+    ///   Gates the pool, so only traders holding a token of a mint, or a
+    ///   credential from an issuing program, can swap or deposit into it.
+    ///   Withdrawals stay open to every LP, and flash swaps, which have no
+    ///   trader to check, are refused.  Swaps and deposits then take the
+    ///   trader's gate account right after their fixed accounts, see
+    ///   `with_gate_account`.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetGate(SetGate),
+
+    ///   Routes the owner's withdraw fee on single-sided withdrawals, which
+    ///   skew the pool, to the LPs who stay in it and carry the imbalance.
+    ///   The fee is burned with the withdrawn pool tokens, leaving the tokens
+    ///   it stands for in the pool, so every remaining pool token is worth
+    ///   more.  Withdrawals of both tokens still pay the owner.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetSingleWithdrawFeeToLps(SetSingleWithdrawFeeToLps),
+
+    ///   Guards the pool's traders against sandwiches bundled in one
+    ///   transaction.  Swaps then take the instructions sysvar and are
+    ///   refused when other traders trade the pool both ways elsewhere in
+    ///   the same transaction, through `Swap`, `RebalanceSwap`, `FlashSwap`
+    ///   or single sided deposits and withdrawals, the latter counting as
+    ///   either way.  Legs spending from the trader's own source or
+    ///   destination account, or signed by their owners, are its own.  A
+    ///   cheap heuristic for one transaction only: sandwiches spread over
+    ///   several transactions of a block, or trading through other
+    ///   programs, aren't seen.  Swaps take the sysvar right after their
+    ///   fixed accounts and the gate account, see `with_instructions_sysvar`.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetSandwichGuard(SetSandwichGuard),
+
+    ///   `WithdrawAllTokenTypes` of the source account's whole balance, read
+    ///   when the instruction runs, so a balance changing after the client
+    ///   read it neither fails the withdrawal nor leaves dust behind.  The
+    ///   transfer authority must be able to move the whole balance.
+    ///
+    ///   0. .. the `WithdrawAllTokenTypes` accounts
+    WithdrawMax(WithdrawMax),
+
+    ///   Lets swaps whose owner fee the curve fails to convert into pool
+    ///   tokens go through without minting it, leaving the fee with the LPs
+    ///   and logging `OwnerFeeSkipped`, or makes them fail again.  Only the
+    ///   pool admin can set it.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetOwnerFeeFallback(SetOwnerFeeFallback),
+
+    ///   Sets the earliest slot the pool's liquidity snapshot can be taken
+    ///   in, creating the snapshot account, or moves it while the snapshot
+    ///   isn't taken.  Only the pool admin can set it.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[writable]` Liquidity snapshot account, derived from `[SNAPSHOT_SEED, Token-swap]`
+    ///   4. `[writable, signer]` Payer funding the snapshot account
+    ///   5. `[]` System program
+    ConfigureLiquiditySnapshot(ConfigureLiquiditySnapshot),
+
+    ///   Takes the pool's liquidity snapshot, from its slot on, recording the
+    ///   pool token supply and both reserves as they are in this one
+    ///   instruction, and the slot it lands in.  Anyone can crank it, once.
+    ///
+    ///   0. `[]` Token-swap
+    ///   1. `[writable]` Liquidity snapshot account
+    ///   2. `[]` token_a Swap Account
+    ///   3. `[]` token_b Swap Account
+    ///   4. `[]` Pool mint
+    CrankLiquiditySnapshot,
+
+    ///   Links token B to the pool token of another pool of this program, or
+    ///   unlinks it.  Linking checks that token B's mint is that pool's, so
+    ///   minted by it alone, with the decimals both pools recorded, and that
+    ///   the other pool isn't nested itself.  Only the pool admin can set it.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    ///   3. `[]` Nested pool's token-swap, when linking
+    ///   4. `[]` Token B mint, when linking
+    SetNestedPool(SetNestedPool),
+
+    ///   Sets the terms keepers bond on, creating the keeper config on first
+    ///   use.  Only the upgrade authority documented in the program config
+    ///   can set them, and it becomes the authority slashing bonds.
+    ///
+    ///   0. `[writable]` Keeper config account, derived from `[KEEPER_CONFIG_SEED]`
+    ///   1. `[]` Program config account
+    ///   2. `[signer]` Upgrade authority
+    ///   3. `[writable, signer]` Payer funding the keeper config account
+    ///   4. `[]` System program
+    SetKeeperConfig(SetKeeperConfig),
+
+    ///   Bonds lamports of the keeper, creating its bond account on first use,
+    ///   or adds to its bond.  A keeper that was unbonding is bonded again.
+    ///
+    ///   0. `[writable]` Keeper bond account, derived from `[KEEPER_SEED, keeper]`
+    ///   1. `[]` Keeper config account
+    ///   2. `[writable, signer]` Keeper, paying the bond
+    ///   3. `[]` System program
+    RegisterKeeper(RegisterKeeper),
+
+    ///   Stops the keeper's cranks and starts the unbonding period, after
+    ///   which its bond can be withdrawn.
+    ///
+    ///   0. `[writable]` Keeper bond account
+    ///   1. `[]` Keeper config account
+    ///   2. `[signer]` Keeper
+    UnbondKeeper,
+
+    ///   Pays an unbonded keeper's bond back to it.
+    ///
+    ///   0. `[writable]` Keeper bond account
+    ///   1. `[writable, signer]` Keeper
+    WithdrawKeeperBond,
+
+    ///   Takes lamports of a keeper's bond for a bad crank, bonded or
+    ///   unbonding.  Only the keeper config's authority can slash, and only
+    ///   in the slot of the keeper's last oracle price crank, with the oracle
+    ///   account holding another price than the one the crank read.  A
+    ///   crank costs at most `MAX_SLASH_BPS` of the bond, once, and the
+    ///   lamports stay in the keeper config account.
+    ///
+    ///   0. `[writable]` Keeper bond account
+    ///   1. `[writable]` Keeper config account, receiving the lamports
+    ///   2. `[signer]` Keeper config authority
+    ///   3. `[]` Oracle config account of the pool the keeper last cranked
+    ///   4. `[]` Oracle account
+    SlashKeeper(SlashKeeper),
+
+    ///   Restricts the pool's oracle price and stake pool rate cranks to
+    ///   keepers bonded in the keeper config, or opens them to anyone again.
+    ///   Only the pool admin can set it.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetBondedKeepersOnly(SetBondedKeepersOnly),
+
+    ///   Pauses the pool during an incident, halting swaps and deposits while
+    ///   LPs can still withdraw, or unpauses it.  Only the pool admin can set
+    ///   it.
+    ///
+    ///   0. `[writable]` Token-swap
+    ///   1. `[signer]` Pool admin, owner of the pool fee account
+    ///   2. `[]` Pool fee account
+    SetPaused(SetPaused),
+
+    ///   Sets the SPL Governance program whose governances can be pool
+    ///   admins.  A pool admin that is a governance of it can sign admin
+    ///   instructions through its native treasury, as a proposal executes
+    ///   them, with the program config and the treasury after the
+    ///   instruction's accounts.  Only the upgrade authority documented in the
+    ///   program config can set it.
+    ///
+    ///   0. `[writable]` Program config account
+    ///   1. `[signer]` Upgrade authority
+    ///   2. `[writable, signer]` Payer funding the program config's growth
+    ///   3. `[]` System program
+    SetGovernanceProgram(SetGovernanceProgram),
+
+    ///   Sets the fees and curve of the pool template at an index, creating
+    ///   its account on first use.  Only the upgrade authority documented in
+    ///   the program config can set them, and they must pass the program's
+    ///   constraints.
+    ///
+    ///   0. `[writable]` Pool template account, derived from `[POOL_TEMPLATE_SEED, index]`
+    ///   1. `[]` Program config account
+    ///   2. `[signer]` Upgrade authority
+    ///   3. `[writable, signer]` Payer funding the pool template account
+    ///   4. `[]` System program
+    SetPoolTemplate(SetPoolTemplate),
+
+    ///   `DepositAllTokenTypes`, then `LockLiquidity` of the pool tokens it
+    ///   minted, the lock owner being the user transfer authority.  During
+    ///   the deposit bonus window, a lock lasting the bonus lockup gets the
+    ///   bonus on the minted tokens paid into it from the pool fee account,
+    ///   none on what the pool account held already.
+    ///
+    ///   0. `[writable]` Lock account, derived from `[LOCK_SEED, Token-swap, user transfer authority, unlock_slot]`
+    ///   1. `[writable]` Pool Token Account owned by the swap authority, holding only this lock's tokens
+    ///   2. `[writable, signer]` Payer funding the lock account
+    ///   3. `[]` System program
+    ///   4. `[writable]` Pool fee account, to pay the deposit bonus from
+    ///   5. .. the `DepositAllTokenTypes` accounts, its pool account
+    ///      transferable by the user transfer authority
+    DepositAndLockLiquidity(DepositAndLockLiquidity),
+}
+
+impl SwapInstruction {
+    /// Unpacks a byte buffer into a [SwapInstruction](enum.SwapInstruction.html).
+    pub fn unpack(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&tag, rest) = input.split_first().ok_or(SwapError::InvalidInstruction)?;
+        Ok(match tag {
+            0 => {
+                let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                if rest.len() >= Fees::LEN + SwapCurve::LEN {
+                    let (fees, rest) = rest.split_at(Fees::LEN);
+                    let fees = Fees::unpack_unchecked(fees)?;
+                    let (swap_curve, rest) = rest.split_at(SwapCurve::LEN);
+                    let swap_curve = SwapCurve::unpack_unchecked(swap_curve)?;
+
+                    msg!("swap curve is: {:?}", swap_curve);
+
+                    // vesting, the volume cap and the fee holiday are optional,
+                    // so instructions packed without them stay valid
+                    let (vesting_slots, rest) = if rest.is_empty() {
+                        (0, rest)
+                    } else {
+                        Self::unpack_u64(rest)?
+                    };
+                    let (max_slot_volume, rest) = if rest.is_empty() {
+                        (0, rest)
+                    } else {
+                        Self::unpack_u64(rest)?
+                    };
+                    let (fee_holiday_end_slot, rest) = if rest.is_empty() {
+                        (0, rest)
+                    } else {
+                        Self::unpack_u64(rest)?
+                    };
+                    let (initial_price_band_bps, oracle_price_offset) = if rest.is_empty() {
+                        (0, 0)
+                    } else {
+                        let (initial_price_band_bps, rest) = Self::unpack_u16(rest)?;
+                        (initial_price_band_bps, Self::unpack_u32(rest)?.0)
+                    };
+
+                    Self::Initialize(Initialize {
+                        nonce,
+                        fees,
+                        swap_curve,
+                        vesting_slots,
+                        max_slot_volume,
+                        fee_holiday_end_slot,
+                        initial_price_band_bps,
+                        oracle_price_offset,
+                    })
+                } else {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+            }
+            1 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                // the limit price is optional, so swaps packed without it stay valid
+                let (min_price_numerator, min_price_denominator) = if rest.is_empty() {
+                    (0, 0)
+                } else {
+                    let (min_price_numerator, rest) = Self::unpack_u64(rest)?;
+                    (min_price_numerator, Self::unpack_u64(rest)?.0)
+                };
+                Self::Swap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                    min_price_numerator,
+                    min_price_denominator,
+                })
+            }
+            2 => {
+                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositAllTokenTypes(DepositAllTokenTypes {
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                })
+            }
+            3 => {
+                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            4 => {
+                let (source_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                })
+            }
             5 => {
                 let (destination_token_amount, rest) = Self::unpack_u64(rest)?;
                 let (maximum_pool_token_amount, _rest) = Self::unpack_u64(rest)?;
@@ -257,74 +1393,488 @@ impl SwapInstruction {
                     maximum_pool_token_amount,
                 })
             }
-            _ => return Err(SwapError::InvalidInstruction.into()),
-        })
-    }
-
-    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
-        if input.len() >= 8 {
-            let (amount, rest) = input.split_at(8);
-            let amount = amount
-                .get(..8)
-                .and_then(|slice| slice.try_into().ok())
-                .map(u64::from_le_bytes)
-                .ok_or(SwapError::InvalidInstruction)?;
-            Ok((amount, rest))
-        } else {
-            Err(SwapError::InvalidInstruction.into())
-        }
-    }
-
-    /// Packs a [SwapInstruction](enum.SwapInstruction.html) into a byte buffer.
-    pub fn pack(&self) -> Vec<u8> {
-        let mut buf = Vec::with_capacity(size_of::<Self>());
-        match &*self {
-            Self::Initialize(Initialize {
-                nonce,
-                fees,
-                swap_curve,
-            }) => {
-                buf.push(0);
-                buf.push(*nonce);
-                let mut fees_slice = [0u8; Fees::LEN];
-                Pack::pack_into_slice(fees, &mut fees_slice[..]);
-                buf.extend_from_slice(&fees_slice);
-                let mut swap_curve_slice = [0u8; SwapCurve::LEN];
-                Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
-                buf.extend_from_slice(&swap_curve_slice);
+            6 => {
+                let (host_fee_numerator, rest) = Self::unpack_u64(rest)?;
+                let (host_fee_denominator, _rest) = Self::unpack_u64(rest)?;
+                Self::RegisterReferrer(RegisterReferrer {
+                    host_fee_numerator,
+                    host_fee_denominator,
+                })
             }
-            Self::Swap(Swap {
-                amount_in,
-                minimum_amount_out,
-            }) => {
-                buf.push(1);
-                buf.extend_from_slice(&amount_in.to_le_bytes());
-                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+            7 => Self::ClaimVestedPoolTokens,
+            8 => {
+                let swap_curve = SwapCurve::unpack_unchecked(rest)?;
+                Self::ReinitializeCurve(ReinitializeCurve { swap_curve })
             }
-            Self::DepositAllTokenTypes(DepositAllTokenTypes {
-                pool_token_amount,
-                maximum_token_a_amount,
-                maximum_token_b_amount,
-            }) => {
-                buf.push(2);
-                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
-                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
-                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+            9 => Self::GetMultiplePoolStates,
+            10 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let (unlock_slot, _rest) = Self::unpack_u64(rest)?;
+                Self::LockLiquidity(LockLiquidity {
+                    amount,
+                    unlock_slot,
+                })
             }
-            Self::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
-                pool_token_amount,
-                minimum_token_a_amount,
-                minimum_token_b_amount,
-            }) => {
-                buf.push(3);
-                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
-                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
-                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            11 => {
+                let (unlock_slot, _rest) = Self::unpack_u64(rest)?;
+                Self::UnlockLiquidity(UnlockLiquidity { unlock_slot })
             }
-            Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
-                source_token_amount,
-                minimum_pool_token_amount,
-            }) => {
+            12 => {
+                let (price_offset, rest) = Self::unpack_u32(rest)?;
+                let (min_price, rest) = Self::unpack_u64(rest)?;
+                let (max_price, rest) = Self::unpack_u64(rest)?;
+                let (max_step_bps, _rest) = Self::unpack_u16(rest)?;
+                Self::SetOracleConfig(SetOracleConfig {
+                    price_offset,
+                    min_price,
+                    max_price,
+                    max_step_bps,
+                })
+            }
+            13 => Self::CrankOraclePrice,
+            14 => {
+                let (pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::AdminDepositAllTokenTypes(AdminMoveLiquidity { pool_token_amount })
+            }
+            15 => {
+                let (pool_token_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::AdminWithdrawAllTokenTypes(AdminMoveLiquidity { pool_token_amount })
+            }
+            16 => {
+                let (amount_in, rest) = Self::unpack_u64(rest)?;
+                let (minimum_amount_out, rest) = Self::unpack_u64(rest)?;
+                // the limit price is optional, so swaps packed without it stay valid
+                let (min_price_numerator, min_price_denominator) = if rest.is_empty() {
+                    (0, 0)
+                } else {
+                    let (min_price_numerator, rest) = Self::unpack_u64(rest)?;
+                    (min_price_numerator, Self::unpack_u64(rest)?.0)
+                };
+                Self::RebalanceSwap(Swap {
+                    amount_in,
+                    minimum_amount_out,
+                    min_price_numerator,
+                    min_price_denominator,
+                })
+            }
+            17 => {
+                let rebalancer = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                Self::SetRebalancer(SetRebalancer { rebalancer })
+            }
+            18 => {
+                if rest.len() < MAX_NAME_LEN + MAX_URI_LEN + TAGS_LEN {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+                let (name, rest) = rest.split_at(MAX_NAME_LEN);
+                let (uri, rest) = rest.split_at(MAX_URI_LEN);
+                let tags = &rest[..TAGS_LEN];
+                Self::SetPoolMetadata(SetPoolMetadata {
+                    name: name.try_into().unwrap(),
+                    uri: uri.try_into().unwrap(),
+                    tags: tags.try_into().unwrap(),
+                })
+            }
+            19 => {
+                let enabled = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetEmergency(SetEmergency { enabled })
+            }
+            20 => {
+                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::EmergencyWithdraw(WithdrawAllTokenTypes {
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            21 => {
+                let (start_slot, rest) = Self::unpack_u64(rest)?;
+                let (end_slot, rest) = Self::unpack_u64(rest)?;
+                let (bonus_bps, rest) = Self::unpack_u16(rest)?;
+                let (lockup_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetDepositBonus(SetDepositBonus {
+                    start_slot,
+                    end_slot,
+                    bonus_bps,
+                    lockup_slots,
+                })
+            }
+            22 => {
+                if rest.len() < 32 * MAX_FEE_EXEMPT {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+                let mut fee_exempt = [Pubkey::default(); MAX_FEE_EXEMPT];
+                for (account, input) in fee_exempt.iter_mut().zip(rest.chunks_exact(32)) {
+                    *account = Pubkey::new_from_array(input.try_into().unwrap());
+                }
+                Self::SetFeeExempt(SetFeeExempt { fee_exempt })
+            }
+            23 => Self::DocumentUpgradeAuthority,
+            24 => {
+                let underlying = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetWithdrawFeeDestination(SetWithdrawFeeDestination { underlying })
+            }
+            25 => {
+                if rest.len() < FeeTiers::LEN {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+                let fee_tiers = FeeTiers::unpack_from_slice(&rest[..FeeTiers::LEN])?;
+                Self::SetFeeTiers(SetFeeTiers { fee_tiers })
+            }
+            26 => {
+                let (amount_in, _rest) = Self::unpack_u64(rest)?;
+                Self::SimulateSwap(SimulateSwap { amount_in })
+            }
+            27 => {
+                let (amount_out, rest) = Self::unpack_u64(rest)?;
+                Self::FlashSwap(FlashSwap {
+                    amount_out,
+                    callback_data: rest.to_vec(),
+                })
+            }
+            28 => {
+                let exact_input = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetExactInput(SetExactInput { exact_input })
+            }
+            29 => {
+                let stake_pool = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                Self::SetStakePool(SetStakePool { stake_pool })
+            }
+            30 => Self::RefreshStakePoolRate,
+            31 => Self::GetHealth,
+            32 => {
+                let host_fee_account = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                Self::SetDefaultHost(SetDefaultHost { host_fee_account })
+            }
+            33 => Self::ClaimHostFees,
+            34 => Self::SetFeeAccount,
+            35 => {
+                let owner_fee_account_a = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                let owner_fee_account_b = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                Self::SetOwnerFeeAccounts(SetOwnerFeeAccounts {
+                    owner_fee_account_a,
+                    owner_fee_account_b,
+                })
+            }
+            36 => Self::ResizeSwapAccount,
+            37 => {
+                let fee_conversion_pool = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                let fee_conversion_account = rest
+                    .get(32..64)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                let (max_slippage_bps, _rest) =
+                    Self::unpack_u16(rest.get(64..).ok_or(SwapError::InvalidInstruction)?)?;
+                Self::SetFeeConversion(SetFeeConversion {
+                    fee_conversion_pool,
+                    fee_conversion_account,
+                    max_slippage_bps,
+                })
+            }
+            38 => {
+                let (cooldown_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetWithdrawCooldown(SetWithdrawCooldown { cooldown_slots })
+            }
+            39 => {
+                let (&m, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                if rest.len() < 32 * MAX_ADMIN_SIGNERS {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+                let mut signers = [Pubkey::default(); MAX_ADMIN_SIGNERS];
+                for (signer, input) in signers.iter_mut().zip(rest.chunks_exact(32)) {
+                    *signer = Pubkey::new_from_array(input.try_into().unwrap());
+                }
+                Self::InitializeAdminMultisig(InitializeAdminMultisig { m, signers })
+            }
+            40 => {
+                let (&nonce, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                let (&template, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                let (vesting_slots, rest) = Self::unpack_u64(rest)?;
+                let (max_slot_volume, rest) = Self::unpack_u64(rest)?;
+                let (fee_holiday_end_slot, _rest) = Self::unpack_u64(rest)?;
+                Self::InitializeFromTemplate(InitializeFromTemplate {
+                    nonce,
+                    template,
+                    vesting_slots,
+                    max_slot_volume,
+                    fee_holiday_end_slot,
+                })
+            }
+            41 => {
+                let gate = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                let credential = match rest.get(32) {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetGate(SetGate { gate, credential })
+            }
+            42 => {
+                let to_lps = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetSingleWithdrawFeeToLps(SetSingleWithdrawFeeToLps { to_lps })
+            }
+            43 => {
+                let enabled = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetSandwichGuard(SetSandwichGuard { enabled })
+            }
+            44 => {
+                let (minimum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (minimum_token_b_amount, _rest) = Self::unpack_u64(rest)?;
+                Self::WithdrawMax(WithdrawMax {
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                })
+            }
+            45 => {
+                let enabled = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetOwnerFeeFallback(SetOwnerFeeFallback { enabled })
+            }
+            46 => {
+                let (slot, _rest) = Self::unpack_u64(rest)?;
+                Self::ConfigureLiquiditySnapshot(ConfigureLiquiditySnapshot { slot })
+            }
+            47 => Self::CrankLiquiditySnapshot,
+            48 => {
+                let nested_pool = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                Self::SetNestedPool(SetNestedPool { nested_pool })
+            }
+            49 => {
+                let (min_bond, rest) = Self::unpack_u64(rest)?;
+                let (unbonding_slots, _rest) = Self::unpack_u64(rest)?;
+                Self::SetKeeperConfig(SetKeeperConfig {
+                    min_bond,
+                    unbonding_slots,
+                })
+            }
+            50 => {
+                let (amount, _rest) = Self::unpack_u64(rest)?;
+                Self::RegisterKeeper(RegisterKeeper { amount })
+            }
+            51 => Self::UnbondKeeper,
+            52 => Self::WithdrawKeeperBond,
+            53 => {
+                let (amount, rest) = Self::unpack_u64(rest)?;
+                let reason = *rest.first().ok_or(SwapError::InvalidInstruction)?;
+                Self::SlashKeeper(SlashKeeper { amount, reason })
+            }
+            54 => {
+                let enabled = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetBondedKeepersOnly(SetBondedKeepersOnly { enabled })
+            }
+            55 => {
+                let paused = match rest.first() {
+                    Some(0) => false,
+                    Some(1) => true,
+                    _ => return Err(SwapError::InvalidInstruction.into()),
+                };
+                Self::SetPaused(SetPaused { paused })
+            }
+            56 => {
+                let governance_program = rest
+                    .get(..32)
+                    .and_then(|slice| slice.try_into().ok())
+                    .map(Pubkey::new_from_array)
+                    .ok_or(SwapError::InvalidInstruction)?;
+                Self::SetGovernanceProgram(SetGovernanceProgram { governance_program })
+            }
+            57 => {
+                let (&index, rest) = rest.split_first().ok_or(SwapError::InvalidInstruction)?;
+                if rest.len() < Fees::LEN + SwapCurve::LEN {
+                    return Err(SwapError::InvalidInstruction.into());
+                }
+                let (fees, rest) = rest.split_at(Fees::LEN);
+                let fees = Fees::unpack_unchecked(fees)?;
+                let swap_curve = SwapCurve::unpack_unchecked(rest)?;
+                Self::SetPoolTemplate(SetPoolTemplate {
+                    index,
+                    fees,
+                    swap_curve,
+                })
+            }
+            58 => {
+                let (pool_token_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_a_amount, rest) = Self::unpack_u64(rest)?;
+                let (maximum_token_b_amount, rest) = Self::unpack_u64(rest)?;
+                let (unlock_slot, _rest) = Self::unpack_u64(rest)?;
+                Self::DepositAndLockLiquidity(DepositAndLockLiquidity {
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    unlock_slot,
+                })
+            }
+            _ => return Err(SwapError::InvalidInstruction.into()),
+        })
+    }
+
+    fn unpack_u64(input: &[u8]) -> Result<(u64, &[u8]), ProgramError> {
+        if input.len() >= 8 {
+            let (amount, rest) = input.split_at(8);
+            let amount = amount
+                .get(..8)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u64::from_le_bytes)
+                .ok_or(SwapError::InvalidInstruction)?;
+            Ok((amount, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_u32(input: &[u8]) -> Result<(u32, &[u8]), ProgramError> {
+        if input.len() >= 4 {
+            let (amount, rest) = input.split_at(4);
+            let amount = amount
+                .get(..4)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u32::from_le_bytes)
+                .ok_or(SwapError::InvalidInstruction)?;
+            Ok((amount, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    fn unpack_u16(input: &[u8]) -> Result<(u16, &[u8]), ProgramError> {
+        if input.len() >= 2 {
+            let (amount, rest) = input.split_at(2);
+            let amount = amount
+                .get(..2)
+                .and_then(|slice| slice.try_into().ok())
+                .map(u16::from_le_bytes)
+                .ok_or(SwapError::InvalidInstruction)?;
+            Ok((amount, rest))
+        } else {
+            Err(SwapError::InvalidInstruction.into())
+        }
+    }
+
+    /// Packs a [SwapInstruction](enum.SwapInstruction.html) into a byte buffer.
+    pub fn pack(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(size_of::<Self>());
+        match &*self {
+            Self::Initialize(Initialize {
+                nonce,
+                fees,
+                swap_curve,
+                vesting_slots,
+                max_slot_volume,
+                fee_holiday_end_slot,
+                initial_price_band_bps,
+                oracle_price_offset,
+            }) => {
+                buf.push(0);
+                buf.push(*nonce);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                let mut swap_curve_slice = [0u8; SwapCurve::LEN];
+                Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
+                buf.extend_from_slice(&swap_curve_slice);
+                buf.extend_from_slice(&vesting_slots.to_le_bytes());
+                buf.extend_from_slice(&max_slot_volume.to_le_bytes());
+                buf.extend_from_slice(&fee_holiday_end_slot.to_le_bytes());
+                buf.extend_from_slice(&initial_price_band_bps.to_le_bytes());
+                buf.extend_from_slice(&oracle_price_offset.to_le_bytes());
+            }
+            Self::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+                min_price_numerator,
+                min_price_denominator,
+            }) => {
+                buf.push(1);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&min_price_numerator.to_le_bytes());
+                buf.extend_from_slice(&min_price_denominator.to_le_bytes());
+            }
+            Self::DepositAllTokenTypes(DepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                buf.push(2);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+            }
+            Self::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                buf.push(3);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::DepositSingleTokenTypeExactAmountIn(DepositSingleTokenTypeExactAmountIn {
+                source_token_amount,
+                minimum_pool_token_amount,
+            }) => {
                 buf.push(4);
                 buf.extend_from_slice(&source_token_amount.to_le_bytes());
                 buf.extend_from_slice(&minimum_pool_token_amount.to_le_bytes());
@@ -339,42 +1889,1939 @@ impl SwapInstruction {
                 buf.extend_from_slice(&destination_token_amount.to_le_bytes());
                 buf.extend_from_slice(&maximum_pool_token_amount.to_le_bytes());
             }
-        }
-        buf
-    }
+            Self::RegisterReferrer(RegisterReferrer {
+                host_fee_numerator,
+                host_fee_denominator,
+            }) => {
+                buf.push(6);
+                buf.extend_from_slice(&host_fee_numerator.to_le_bytes());
+                buf.extend_from_slice(&host_fee_denominator.to_le_bytes());
+            }
+            Self::ClaimVestedPoolTokens => {
+                buf.push(7);
+            }
+            Self::ReinitializeCurve(ReinitializeCurve { swap_curve }) => {
+                buf.push(8);
+                let mut swap_curve_slice = [0u8; SwapCurve::LEN];
+                Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
+                buf.extend_from_slice(&swap_curve_slice);
+            }
+            Self::GetMultiplePoolStates => {
+                buf.push(9);
+            }
+            Self::LockLiquidity(LockLiquidity {
+                amount,
+                unlock_slot,
+            }) => {
+                buf.push(10);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_slot.to_le_bytes());
+            }
+            Self::UnlockLiquidity(UnlockLiquidity { unlock_slot }) => {
+                buf.push(11);
+                buf.extend_from_slice(&unlock_slot.to_le_bytes());
+            }
+            Self::SetOracleConfig(SetOracleConfig {
+                price_offset,
+                min_price,
+                max_price,
+                max_step_bps,
+            }) => {
+                buf.push(12);
+                buf.extend_from_slice(&price_offset.to_le_bytes());
+                buf.extend_from_slice(&min_price.to_le_bytes());
+                buf.extend_from_slice(&max_price.to_le_bytes());
+                buf.extend_from_slice(&max_step_bps.to_le_bytes());
+            }
+            Self::CrankOraclePrice => {
+                buf.push(13);
+            }
+            Self::AdminDepositAllTokenTypes(AdminMoveLiquidity { pool_token_amount }) => {
+                buf.push(14);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+            }
+            Self::AdminWithdrawAllTokenTypes(AdminMoveLiquidity { pool_token_amount }) => {
+                buf.push(15);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+            }
+            Self::RebalanceSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+                min_price_numerator,
+                min_price_denominator,
+            }) => {
+                buf.push(16);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+                buf.extend_from_slice(&minimum_amount_out.to_le_bytes());
+                buf.extend_from_slice(&min_price_numerator.to_le_bytes());
+                buf.extend_from_slice(&min_price_denominator.to_le_bytes());
+            }
+            Self::SetRebalancer(SetRebalancer { rebalancer }) => {
+                buf.push(17);
+                buf.extend_from_slice(rebalancer.as_ref());
+            }
+            Self::SetPoolMetadata(SetPoolMetadata { name, uri, tags }) => {
+                buf.push(18);
+                buf.extend_from_slice(name);
+                buf.extend_from_slice(uri);
+                buf.extend_from_slice(tags);
+            }
+            Self::SetEmergency(SetEmergency { enabled }) => {
+                buf.push(19);
+                buf.push(*enabled as u8);
+            }
+            Self::EmergencyWithdraw(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                buf.push(20);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::SetDepositBonus(SetDepositBonus {
+                start_slot,
+                end_slot,
+                bonus_bps,
+                lockup_slots,
+            }) => {
+                buf.push(21);
+                buf.extend_from_slice(&start_slot.to_le_bytes());
+                buf.extend_from_slice(&end_slot.to_le_bytes());
+                buf.extend_from_slice(&bonus_bps.to_le_bytes());
+                buf.extend_from_slice(&lockup_slots.to_le_bytes());
+            }
+            Self::SetFeeExempt(SetFeeExempt { fee_exempt }) => {
+                buf.push(22);
+                for account in fee_exempt {
+                    buf.extend_from_slice(account.as_ref());
+                }
+            }
+            Self::DocumentUpgradeAuthority => buf.push(23),
+            Self::SetWithdrawFeeDestination(SetWithdrawFeeDestination { underlying }) => {
+                buf.push(24);
+                buf.push(*underlying as u8);
+            }
+            Self::SetFeeTiers(SetFeeTiers { fee_tiers }) => {
+                buf.push(25);
+                let mut fee_tiers_slice = [0u8; FeeTiers::LEN];
+                fee_tiers.pack_into_slice(&mut fee_tiers_slice[..]);
+                buf.extend_from_slice(&fee_tiers_slice);
+            }
+            Self::SimulateSwap(SimulateSwap { amount_in }) => {
+                buf.push(26);
+                buf.extend_from_slice(&amount_in.to_le_bytes());
+            }
+            Self::FlashSwap(FlashSwap {
+                amount_out,
+                callback_data,
+            }) => {
+                buf.push(27);
+                buf.extend_from_slice(&amount_out.to_le_bytes());
+                buf.extend_from_slice(callback_data);
+            }
+            Self::SetExactInput(SetExactInput { exact_input }) => {
+                buf.push(28);
+                buf.push(*exact_input as u8);
+            }
+            Self::SetStakePool(SetStakePool { stake_pool }) => {
+                buf.push(29);
+                buf.extend_from_slice(stake_pool.as_ref());
+            }
+            Self::RefreshStakePoolRate => {
+                buf.push(30);
+            }
+            Self::GetHealth => {
+                buf.push(31);
+            }
+            Self::SetDefaultHost(SetDefaultHost { host_fee_account }) => {
+                buf.push(32);
+                buf.extend_from_slice(host_fee_account.as_ref());
+            }
+            Self::ClaimHostFees => {
+                buf.push(33);
+            }
+            Self::SetFeeAccount => {
+                buf.push(34);
+            }
+            Self::SetOwnerFeeAccounts(SetOwnerFeeAccounts {
+                owner_fee_account_a,
+                owner_fee_account_b,
+            }) => {
+                buf.push(35);
+                buf.extend_from_slice(owner_fee_account_a.as_ref());
+                buf.extend_from_slice(owner_fee_account_b.as_ref());
+            }
+            Self::ResizeSwapAccount => {
+                buf.push(36);
+            }
+            Self::SetFeeConversion(SetFeeConversion {
+                fee_conversion_pool,
+                fee_conversion_account,
+                max_slippage_bps,
+            }) => {
+                buf.push(37);
+                buf.extend_from_slice(fee_conversion_pool.as_ref());
+                buf.extend_from_slice(fee_conversion_account.as_ref());
+                buf.extend_from_slice(&max_slippage_bps.to_le_bytes());
+            }
+            Self::SetWithdrawCooldown(SetWithdrawCooldown { cooldown_slots }) => {
+                buf.push(38);
+                buf.extend_from_slice(&cooldown_slots.to_le_bytes());
+            }
+            Self::InitializeAdminMultisig(InitializeAdminMultisig { m, signers }) => {
+                buf.push(39);
+                buf.push(*m);
+                for signer in signers {
+                    buf.extend_from_slice(signer.as_ref());
+                }
+            }
+            Self::InitializeFromTemplate(InitializeFromTemplate {
+                nonce,
+                template,
+                vesting_slots,
+                max_slot_volume,
+                fee_holiday_end_slot,
+            }) => {
+                buf.push(40);
+                buf.push(*nonce);
+                buf.push(*template);
+                buf.extend_from_slice(&vesting_slots.to_le_bytes());
+                buf.extend_from_slice(&max_slot_volume.to_le_bytes());
+                buf.extend_from_slice(&fee_holiday_end_slot.to_le_bytes());
+            }
+            Self::SetGate(SetGate { gate, credential }) => {
+                buf.push(41);
+                buf.extend_from_slice(gate.as_ref());
+                buf.push(*credential as u8);
+            }
+            Self::SetSingleWithdrawFeeToLps(SetSingleWithdrawFeeToLps { to_lps }) => {
+                buf.push(42);
+                buf.push(*to_lps as u8);
+            }
+            Self::SetSandwichGuard(SetSandwichGuard { enabled }) => {
+                buf.push(43);
+                buf.push(*enabled as u8);
+            }
+            Self::WithdrawMax(WithdrawMax {
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                buf.push(44);
+                buf.extend_from_slice(&minimum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&minimum_token_b_amount.to_le_bytes());
+            }
+            Self::SetOwnerFeeFallback(SetOwnerFeeFallback { enabled }) => {
+                buf.push(45);
+                buf.push(*enabled as u8);
+            }
+            Self::ConfigureLiquiditySnapshot(ConfigureLiquiditySnapshot { slot }) => {
+                buf.push(46);
+                buf.extend_from_slice(&slot.to_le_bytes());
+            }
+            Self::CrankLiquiditySnapshot => {
+                buf.push(47);
+            }
+            Self::SetNestedPool(SetNestedPool { nested_pool }) => {
+                buf.push(48);
+                buf.extend_from_slice(nested_pool.as_ref());
+            }
+            Self::SetKeeperConfig(SetKeeperConfig {
+                min_bond,
+                unbonding_slots,
+            }) => {
+                buf.push(49);
+                buf.extend_from_slice(&min_bond.to_le_bytes());
+                buf.extend_from_slice(&unbonding_slots.to_le_bytes());
+            }
+            Self::RegisterKeeper(RegisterKeeper { amount }) => {
+                buf.push(50);
+                buf.extend_from_slice(&amount.to_le_bytes());
+            }
+            Self::UnbondKeeper => buf.push(51),
+            Self::WithdrawKeeperBond => buf.push(52),
+            Self::SlashKeeper(SlashKeeper { amount, reason }) => {
+                buf.push(53);
+                buf.extend_from_slice(&amount.to_le_bytes());
+                buf.push(*reason);
+            }
+            Self::SetBondedKeepersOnly(SetBondedKeepersOnly { enabled }) => {
+                buf.push(54);
+                buf.push(*enabled as u8);
+            }
+            Self::SetPaused(SetPaused { paused }) => {
+                buf.push(55);
+                buf.push(*paused as u8);
+            }
+            Self::SetGovernanceProgram(SetGovernanceProgram { governance_program }) => {
+                buf.push(56);
+                buf.extend_from_slice(governance_program.as_ref());
+            }
+            Self::SetPoolTemplate(SetPoolTemplate {
+                index,
+                fees,
+                swap_curve,
+            }) => {
+                buf.push(57);
+                buf.push(*index);
+                let mut fees_slice = [0u8; Fees::LEN];
+                Pack::pack_into_slice(fees, &mut fees_slice[..]);
+                buf.extend_from_slice(&fees_slice);
+                let mut swap_curve_slice = [0u8; SwapCurve::LEN];
+                Pack::pack_into_slice(swap_curve, &mut swap_curve_slice[..]);
+                buf.extend_from_slice(&swap_curve_slice);
+            }
+            Self::DepositAndLockLiquidity(DepositAndLockLiquidity {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+                unlock_slot,
+            }) => {
+                buf.push(58);
+                buf.extend_from_slice(&pool_token_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_a_amount.to_le_bytes());
+                buf.extend_from_slice(&maximum_token_b_amount.to_le_bytes());
+                buf.extend_from_slice(&unlock_slot.to_le_bytes());
+            }
+        }
+        buf
+    }
+}
+
+/// Creates an 'initialize' instruction.
+pub fn initialize(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    token_a_mint_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+    nonce: u8,
+    fees: Fees,
+    swap_curve: SwapCurve,
+    vesting_slots: u64,
+    max_slot_volume: u64,
+    fee_holiday_end_slot: u64,
+    vesting_beneficiary_pubkey: Option<&Pubkey>,
+    payer_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let init_data = SwapInstruction::Initialize(Initialize {
+        nonce,
+        fees,
+        swap_curve,
+        vesting_slots,
+        max_slot_volume,
+        fee_holiday_end_slot,
+        initial_price_band_bps: 0,
+        oracle_price_offset: 0,
+    });
+
+    initialize_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        fee_pubkey,
+        destination_pubkey,
+        token_a_mint_pubkey,
+        token_b_mint_pubkey,
+        init_data.pack(),
+        vesting_slots,
+        vesting_beneficiary_pubkey,
+        payer_pubkey,
+    )
+}
+
+/// Has an 'initialize' instruction check the price its deposits open the
+/// pool at against the oracle's price, at `price_offset` in the oracle
+/// account, refusing to open it more than `band_bps` away.
+pub fn with_initial_price_oracle(
+    mut instruction: Instruction,
+    oracle_pubkey: &Pubkey,
+    price_offset: u32,
+    band_bps: u16,
+) -> Result<Instruction, ProgramError> {
+    let mut initialize = match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::Initialize(initialize) => initialize,
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    };
+    if band_bps == 0 || initialize.initial_price_band_bps != 0 {
+        return Err(SwapError::InvalidInstruction.into());
+    }
+    // after the vesting accounts, when the initial supply vests
+    let index = if initialize.vesting_slots > 0 { 14 } else { 10 };
+    if instruction.accounts.len() < index {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    initialize.initial_price_band_bps = band_bps;
+    initialize.oracle_price_offset = price_offset;
+    instruction.data = SwapInstruction::Initialize(initialize).pack();
+    instruction
+        .accounts
+        .insert(index, AccountMeta::new_readonly(*oracle_pubkey, false));
+    Ok(instruction)
+}
+
+/// Creates an 'initialize_from_template' instruction.
+pub fn initialize_from_template(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    token_a_mint_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+    instruction: InitializeFromTemplate,
+    vesting_beneficiary_pubkey: Option<&Pubkey>,
+    payer_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let vesting_slots = instruction.vesting_slots;
+    let (template_pubkey, _bump_seed) =
+        PoolTemplate::find_address(instruction.template, program_id);
+    let mut initialize = initialize_instruction(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        token_a_pubkey,
+        token_b_pubkey,
+        pool_pubkey,
+        fee_pubkey,
+        destination_pubkey,
+        token_a_mint_pubkey,
+        token_b_mint_pubkey,
+        SwapInstruction::InitializeFromTemplate(instruction).pack(),
+        vesting_slots,
+        vesting_beneficiary_pubkey,
+        payer_pubkey,
+    )?;
+    initialize
+        .accounts
+        .insert(0, AccountMeta::new_readonly(template_pubkey, false));
+    Ok(initialize)
+}
+
+// accounts of both initialize instructions
+fn initialize_instruction(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    token_a_pubkey: &Pubkey,
+    token_b_pubkey: &Pubkey,
+    pool_pubkey: &Pubkey,
+    fee_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    token_a_mint_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+    data: Vec<u8>,
+    vesting_slots: u64,
+    vesting_beneficiary_pubkey: Option<&Pubkey>,
+    payer_pubkey: Option<&Pubkey>,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, true),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*token_a_pubkey, false),
+        AccountMeta::new_readonly(*token_b_pubkey, false),
+        AccountMeta::new(*pool_pubkey, false),
+        AccountMeta::new_readonly(*fee_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*token_a_mint_pubkey, false),
+        AccountMeta::new_readonly(*token_b_mint_pubkey, false),
+    ];
+    if vesting_slots > 0 {
+        let beneficiary_pubkey = vesting_beneficiary_pubkey.ok_or(SwapError::InvalidInstruction)?;
+        let payer_pubkey = payer_pubkey.ok_or(SwapError::InvalidInstruction)?;
+        let (vesting_pubkey, _bump_seed) = Vesting::find_address(swap_pubkey, program_id);
+        accounts.push(AccountMeta::new(vesting_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(*beneficiary_pubkey, false));
+        accounts.push(AccountMeta::new(*payer_pubkey, true));
+        accounts.push(AccountMeta::new_readonly(system_program::id(), false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'claim_vested_pool_tokens' instruction.
+pub fn claim_vested_pool_tokens(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    vault_pubkey: &Pubkey,
+    beneficiary_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::ClaimVestedPoolTokens.pack();
+    let (vesting_pubkey, _bump_seed) = Vesting::find_address(swap_pubkey, program_id);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(vesting_pubkey, false),
+        AccountMeta::new(*vault_pubkey, false),
+        AccountMeta::new(*beneficiary_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'reinitialize_curve' instruction.
+pub fn reinitialize_curve(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_holder_pubkey: &Pubkey,
+    swap_curve: SwapCurve,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::ReinitializeCurve(ReinitializeCurve { swap_curve }).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*swap_token_a_pubkey, false),
+        AccountMeta::new_readonly(*swap_token_b_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_holder_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'get_multiple_pool_states' instruction, taking for each pool its
+/// swap, token A, token B and pool mint accounts.
+pub fn get_multiple_pool_states(
+    program_id: &Pubkey,
+    pools: &[(&Pubkey, &Pubkey, &Pubkey, &Pubkey)],
+) -> Result<Instruction, ProgramError> {
+    if pools.is_empty() || pools.len() > MAX_POOL_STATE_VIEWS {
+        return Err(SwapError::InvalidInstruction.into());
+    }
+    let data = SwapInstruction::GetMultiplePoolStates.pack();
+
+    let mut accounts = Vec::with_capacity(pools.len() * 4);
+    for (swap_pubkey, swap_token_a_pubkey, swap_token_b_pubkey, pool_mint_pubkey) in pools {
+        accounts.push(AccountMeta::new_readonly(**swap_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(**swap_token_a_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(**swap_token_b_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(**pool_mint_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_all_token_types' instruction.
+pub fn deposit_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    deposit_token_a_pubkey: &Pubkey,
+    deposit_token_b_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DepositAllTokenTypes(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*deposit_token_a_pubkey, false),
+        AccountMeta::new(*deposit_token_b_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_all_token_types' instruction.
+pub fn withdraw_all_token_types(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    instruction: WithdrawAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WithdrawAllTokenTypes(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_max' instruction.
+pub fn withdraw_max(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    instruction: WithdrawMax,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WithdrawMax(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'deposit_single_token_type_exact_amount_in' instruction.
+pub fn deposit_single_token_type_exact_amount_in(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_token_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: DepositSingleTokenTypeExactAmountIn,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_token_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction.
+pub fn withdraw_single_token_type_exact_amount_out(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    fee_account_pubkey: &Pubkey,
+    pool_token_source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: WithdrawSingleTokenTypeExactAmountOut,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_token_source_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*fee_account_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'swap' instruction.
+pub fn swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    host_fee_pubkey: Option<&Pubkey>,
+    referrer_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    swap_with_fee_conversion(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        user_transfer_authority_pubkey,
+        source_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        pool_fee_pubkey,
+        None,
+        host_fee_pubkey,
+        referrer_pubkey,
+        instruction,
+    )
+}
+
+/// Accounts of the fee conversion pool a swap sells the owner fee through
+#[derive(Clone, Debug, PartialEq)]
+pub struct FeeConversionAccounts {
+    pub fee_conversion_pool: Pubkey,
+    pub fee_conversion_authority: Pubkey,
+    /// The conversion pool's account of the swap's source token
+    pub fee_conversion_source: Pubkey,
+    /// The conversion pool's account of the fee mint
+    pub fee_conversion_destination: Pubkey,
+    pub fee_conversion_pool_mint: Pubkey,
+    pub fee_conversion_account: Pubkey,
+}
+
+impl FeeConversionAccounts {
+    /// The accounts in the order `Swap` and `FlashSwap` take them
+    fn account_metas(&self) -> [AccountMeta; 6] {
+        [
+            AccountMeta::new(self.fee_conversion_pool, false),
+            AccountMeta::new_readonly(self.fee_conversion_authority, false),
+            AccountMeta::new(self.fee_conversion_source, false),
+            AccountMeta::new(self.fee_conversion_destination, false),
+            AccountMeta::new_readonly(self.fee_conversion_pool_mint, false),
+            AccountMeta::new(self.fee_conversion_account, false),
+        ]
+    }
+}
+
+/// Creates a 'swap' instruction on a pool converting the owner fees paid in
+/// the source token, with the fee conversion accounts.
+pub fn swap_with_fee_conversion(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    fee_conversion: Option<&FeeConversionAccounts>,
+    host_fee_pubkey: Option<&Pubkey>,
+    referrer_pubkey: Option<&Pubkey>,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::Swap(instruction).pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(fee_conversion) = fee_conversion {
+        accounts.extend(fee_conversion.account_metas());
+    }
+    if let Some(host_fee_pubkey) = host_fee_pubkey {
+        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
+        if let Some(referrer_pubkey) = referrer_pubkey {
+            accounts.push(AccountMeta::new_readonly(*referrer_pubkey, false));
+        }
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'register_referrer' instruction.
+pub fn register_referrer(
+    program_id: &Pubkey,
+    owner_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    program_owner_pubkey: Option<&Pubkey>,
+    instruction: RegisterReferrer,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RegisterReferrer(instruction).pack();
+    let (referrer_pubkey, _bump_seed) = Referrer::find_address(owner_pubkey, program_id);
+
+    let mut accounts = vec![
+        AccountMeta::new(referrer_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some(program_owner_pubkey) = program_owner_pubkey {
+        accounts.push(AccountMeta::new_readonly(*program_owner_pubkey, true));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'lock_liquidity' instruction.
+pub fn lock_liquidity(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    vault_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: LockLiquidity,
+) -> Result<Instruction, ProgramError> {
+    let (lock_pubkey, _bump_seed) = LiquidityLock::find_address(
+        swap_pubkey,
+        owner_pubkey,
+        instruction.unlock_slot,
+        program_id,
+    );
+    let data = SwapInstruction::LockLiquidity(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(lock_pubkey, false),
+        AccountMeta::new_readonly(*owner_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*vault_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Wraps a 'deposit_all_token_types' instruction into a
+/// 'deposit_and_lock_liquidity' one, locking the pool tokens it mints until
+/// `unlock_slot` for its user transfer authority.
+pub fn lock_deposit(
+    vault_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    unlock_slot: u64,
+    instruction: Instruction,
+) -> Result<Instruction, ProgramError> {
+    let data = match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
+            pool_token_amount,
+            maximum_token_a_amount,
+            maximum_token_b_amount,
+        }) => SwapInstruction::DepositAndLockLiquidity(DepositAndLockLiquidity {
+            pool_token_amount,
+            maximum_token_a_amount,
+            maximum_token_b_amount,
+            unlock_slot,
+        }),
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    .pack();
+    let (swap_pubkey, owner_pubkey) = match instruction.accounts.as_slice() {
+        [swap, _authority, owner, ..] => (swap.pubkey, owner.pubkey),
+        _ => return Err(ProgramError::NotEnoughAccountKeys),
+    };
+    let (lock_pubkey, _bump_seed) = LiquidityLock::find_address(
+        &swap_pubkey,
+        &owner_pubkey,
+        unlock_slot,
+        &instruction.program_id,
+    );
+
+    let mut accounts = vec![
+        AccountMeta::new(lock_pubkey, false),
+        AccountMeta::new(*vault_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+    ];
+    accounts.extend(instruction.accounts);
+
+    Ok(Instruction {
+        program_id: instruction.program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'unlock_liquidity' instruction.
+pub fn unlock_liquidity(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    owner_pubkey: &Pubkey,
+    vault_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    instruction: UnlockLiquidity,
+) -> Result<Instruction, ProgramError> {
+    let (lock_pubkey, _bump_seed) = LiquidityLock::find_address(
+        swap_pubkey,
+        owner_pubkey,
+        instruction.unlock_slot,
+        program_id,
+    );
+    let data = SwapInstruction::UnlockLiquidity(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(lock_pubkey, false),
+        AccountMeta::new(*owner_pubkey, true),
+        AccountMeta::new(*vault_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_oracle_config' instruction.
+pub fn set_oracle_config(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: SetOracleConfig,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetOracleConfig(instruction).pack();
+    let (oracle_config_pubkey, _bump_seed) = OracleConfig::find_address(swap_pubkey, program_id);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+        AccountMeta::new(oracle_config_pubkey, false),
+        AccountMeta::new_readonly(*oracle_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'crank_oracle_price' instruction.
+pub fn crank_oracle_price(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::CrankOraclePrice.pack();
+    let (oracle_config_pubkey, _bump_seed) = OracleConfig::find_address(swap_pubkey, program_id);
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new(oracle_config_pubkey, false),
+        AccountMeta::new_readonly(*oracle_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Turns a 'deposit_all_token_types' or 'withdraw_all_token_types'
+/// instruction into its admin variant, dropping its slippage bounds.
+pub fn admin_override_slippage(
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: Instruction,
+) -> Result<Instruction, ProgramError> {
+    let data = match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
+            pool_token_amount, ..
+        }) => SwapInstruction::AdminDepositAllTokenTypes(AdminMoveLiquidity { pool_token_amount }),
+        SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+            pool_token_amount, ..
+        }) => SwapInstruction::AdminWithdrawAllTokenTypes(AdminMoveLiquidity { pool_token_amount }),
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    accounts.extend(instruction.accounts);
+
+    Ok(Instruction {
+        program_id: instruction.program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'rebalance_swap' instruction.
+pub fn rebalance_swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    rebalancer_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    instruction: Swap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RebalanceSwap(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*rebalancer_pubkey, true),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*pool_mint_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_rebalancer' instruction.
+pub fn set_rebalancer(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetRebalancer,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetRebalancer(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_pool_metadata' instruction.
+pub fn set_pool_metadata(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: SetPoolMetadata,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetPoolMetadata(instruction).pack();
+    let (metadata_pubkey, _bump_seed) = PoolMetadata::find_address(swap_pubkey, program_id);
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+        AccountMeta::new(metadata_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_emergency' instruction.
+pub fn set_emergency(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetEmergency,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetEmergency(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'emergency_withdraw' instruction.
+pub fn emergency_withdraw(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    user_transfer_authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+    destination_token_a_pubkey: &Pubkey,
+    destination_token_b_pubkey: &Pubkey,
+    instruction: WithdrawAllTokenTypes,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::EmergencyWithdraw(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*source_pubkey, false),
+        AccountMeta::new(*swap_token_a_pubkey, false),
+        AccountMeta::new(*swap_token_b_pubkey, false),
+        AccountMeta::new(*destination_token_a_pubkey, false),
+        AccountMeta::new(*destination_token_b_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_deposit_bonus' instruction.
+pub fn set_deposit_bonus(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetDepositBonus,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetDepositBonus(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_fee_exempt' instruction.
+pub fn set_fee_exempt(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetFeeExempt,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetFeeExempt(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'document_upgrade_authority' instruction.
+pub fn document_upgrade_authority(
+    program_id: &Pubkey,
+    upgrade_authority_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::DocumentUpgradeAuthority.pack();
+
+    let (config_pubkey, _bump_seed) = ProgramConfig::find_address(program_id);
+    let (program_data_pubkey, _bump_seed) =
+        Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+    let accounts = vec![
+        AccountMeta::new(config_pubkey, false),
+        AccountMeta::new_readonly(program_data_pubkey, false),
+        AccountMeta::new_readonly(*upgrade_authority_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_withdraw_fee_destination' instruction, paying withdraw fees
+/// into the given token A and B vaults, or as pool tokens without them.
+pub fn set_withdraw_fee_destination(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    fee_vaults: Option<(&Pubkey, &Pubkey)>,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetWithdrawFeeDestination(SetWithdrawFeeDestination {
+        underlying: fee_vaults.is_some(),
+    })
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    if let Some((fee_vault_a_pubkey, fee_vault_b_pubkey)) = fee_vaults {
+        accounts.push(AccountMeta::new_readonly(*fee_vault_a_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(*fee_vault_b_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_fee_tiers' instruction.
+pub fn set_fee_tiers(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetFeeTiers,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetFeeTiers(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'simulate_swap' instruction, to be simulated rather than sent.
+pub fn simulate_swap(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    source_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    instruction: SimulateSwap,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SimulateSwap(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*source_pubkey, false),
+        AccountMeta::new_readonly(*swap_source_pubkey, false),
+        AccountMeta::new_readonly(*swap_destination_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'flash_swap' instruction, appending the callback's program,
+/// accounts and data.
+#[allow(clippy::too_many_arguments)]
+pub fn flash_swap(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    amount_out: u64,
+    callback: Instruction,
+) -> Result<Instruction, ProgramError> {
+    flash_swap_with_fee_conversion(
+        program_id,
+        token_program_id,
+        swap_pubkey,
+        authority_pubkey,
+        swap_source_pubkey,
+        swap_destination_pubkey,
+        destination_pubkey,
+        pool_mint_pubkey,
+        pool_fee_pubkey,
+        None,
+        amount_out,
+        callback,
+    )
+}
+
+/// Creates a 'flash_swap' instruction on a pool converting the owner fees
+/// paid in the source token, with the fee conversion accounts.
+#[allow(clippy::too_many_arguments)]
+pub fn flash_swap_with_fee_conversion(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    swap_source_pubkey: &Pubkey,
+    swap_destination_pubkey: &Pubkey,
+    destination_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    fee_conversion: Option<&FeeConversionAccounts>,
+    amount_out: u64,
+    callback: Instruction,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::FlashSwap(FlashSwap {
+        amount_out,
+        callback_data: callback.data,
+    })
+    .pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*swap_source_pubkey, false),
+        AccountMeta::new(*swap_destination_pubkey, false),
+        AccountMeta::new(*destination_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+    if let Some(fee_conversion) = fee_conversion {
+        accounts.extend(fee_conversion.account_metas());
+    }
+    accounts.push(AccountMeta::new_readonly(callback.program_id, false));
+    accounts.extend(callback.accounts);
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_exact_input' instruction.
+pub fn set_exact_input(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetExactInput,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetExactInput(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_stake_pool' instruction.
+pub fn set_stake_pool(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetStakePool,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    if instruction.stake_pool != Pubkey::default() {
+        accounts.push(AccountMeta::new_readonly(instruction.stake_pool, false));
+    }
+    let data = SwapInstruction::SetStakePool(instruction).pack();
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'refresh_stake_pool_rate' instruction.
+pub fn refresh_stake_pool_rate(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    stake_pool_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RefreshStakePoolRate.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*stake_pool_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'get_health' instruction.
+pub fn get_health(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    swap_token_a_pubkey: &Pubkey,
+    swap_token_b_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::GetHealth.pack();
+
+    let accounts = vec![
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*swap_token_a_pubkey, false),
+        AccountMeta::new_readonly(*swap_token_b_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_default_host' instruction.
+pub fn set_default_host(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetDefaultHost,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    if instruction.host_fee_account != Pubkey::default() {
+        accounts.push(AccountMeta::new_readonly(
+            instruction.host_fee_account,
+            false,
+        ));
+    }
+    let data = SwapInstruction::SetDefaultHost(instruction).pack();
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'claim_host_fees' instruction.
+pub fn claim_host_fees(
+    program_id: &Pubkey,
+    token_program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    authority_pubkey: &Pubkey,
+    pool_mint_pubkey: &Pubkey,
+    host_fee_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::ClaimHostFees.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, false),
+        AccountMeta::new(*pool_mint_pubkey, false),
+        AccountMeta::new(*host_fee_pubkey, false),
+        AccountMeta::new_readonly(*token_program_id, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_fee_account' instruction.
+pub fn set_fee_account(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    new_pool_fee_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetFeeAccount.pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+        AccountMeta::new_readonly(*new_pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_owner_fee_accounts' instruction.
+pub fn set_owner_fee_accounts(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetOwnerFeeAccounts,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    for owner_fee_account in [
+        instruction.owner_fee_account_a,
+        instruction.owner_fee_account_b,
+    ] {
+        if owner_fee_account != Pubkey::default() {
+            accounts.push(AccountMeta::new_readonly(owner_fee_account, false));
+        }
+    }
+    let data = SwapInstruction::SetOwnerFeeAccounts(instruction).pack();
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'resize_swap_account' instruction.  `mints`, the pool token,
+/// token A and token B mints, are only needed to migrate a V1 pool.
+pub fn resize_swap_account(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    mints: Option<(&Pubkey, &Pubkey, &Pubkey)>,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::ResizeSwapAccount.pack();
+
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+    if let Some((pool_mint_pubkey, token_a_mint_pubkey, token_b_mint_pubkey)) = mints {
+        accounts.push(AccountMeta::new_readonly(*pool_mint_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(*token_a_mint_pubkey, false));
+        accounts.push(AccountMeta::new_readonly(*token_b_mint_pubkey, false));
+    }
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_fee_conversion' instruction.
+pub fn set_fee_conversion(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetFeeConversion,
+) -> Result<Instruction, ProgramError> {
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    if instruction.fee_conversion_pool != Pubkey::default() {
+        accounts.push(AccountMeta::new_readonly(
+            instruction.fee_conversion_pool,
+            false,
+        ));
+        accounts.push(AccountMeta::new_readonly(
+            instruction.fee_conversion_account,
+            false,
+        ));
+    }
+    let data = SwapInstruction::SetFeeConversion(instruction).pack();
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_withdraw_cooldown' instruction.
+pub fn set_withdraw_cooldown(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetWithdrawCooldown,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetWithdrawCooldown(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'initialize_admin_multisig' instruction.
+pub fn initialize_admin_multisig(
+    program_id: &Pubkey,
+    multisig_pubkey: &Pubkey,
+    instruction: InitializeAdminMultisig,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::InitializeAdminMultisig(instruction).pack();
+
+    let accounts = vec![AccountMeta::new(*multisig_pubkey, false)];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Has an admin instruction signed by a multisig admin's signers instead of
+/// the admin, which can't sign itself.
+pub fn sign_as_multisig_admin(
+    mut instruction: Instruction,
+    admin_pubkey: &Pubkey,
+    signer_pubkeys: &[&Pubkey],
+) -> Instruction {
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == *admin_pubkey {
+            account.is_signer = false;
+        }
+    }
+    for signer_pubkey in signer_pubkeys {
+        instruction
+            .accounts
+            .push(AccountMeta::new_readonly(**signer_pubkey, true));
+    }
+    instruction
+}
+
+/// Has an admin instruction signed by the native treasury of a governance
+/// admin, which can't sign itself, as a proposal of the governance executes
+/// it.
+pub fn sign_as_governance_admin(
+    mut instruction: Instruction,
+    governance_program_id: &Pubkey,
+    admin_pubkey: &Pubkey,
+) -> Instruction {
+    for account in instruction.accounts.iter_mut() {
+        if account.pubkey == *admin_pubkey {
+            account.is_signer = false;
+        }
+    }
+    let (program_config_pubkey, _bump_seed) = ProgramConfig::find_address(&instruction.program_id);
+    let (native_treasury_pubkey, _bump_seed) = Pubkey::find_program_address(
+        &[NATIVE_TREASURY_SEED, admin_pubkey.as_ref()],
+        governance_program_id,
+    );
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(program_config_pubkey, false));
+    instruction
+        .accounts
+        .push(AccountMeta::new_readonly(native_treasury_pubkey, true));
+    instruction
+}
+
+/// Creates a 'set_gate' instruction.
+pub fn set_gate(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetGate,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetGate(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_single_withdraw_fee_to_lps' instruction.
+pub fn set_single_withdraw_fee_to_lps(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetSingleWithdrawFeeToLps,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetSingleWithdrawFeeToLps(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_sandwich_guard' instruction.
+pub fn set_sandwich_guard(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetSandwichGuard,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetSandwichGuard(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_owner_fee_fallback' instruction.
+pub fn set_owner_fee_fallback(
+    program_id: &Pubkey,
+    swap_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetOwnerFeeFallback,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetOwnerFeeFallback(instruction).pack();
+
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
 }
 
-/// Creates an 'initialize' instruction.
-pub fn initialize(
+/// Creates a 'configure_liquidity_snapshot' instruction.
+pub fn configure_liquidity_snapshot(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    token_a_pubkey: &Pubkey,
-    token_b_pubkey: &Pubkey,
-    pool_pubkey: &Pubkey,
-    fee_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    nonce: u8,
-    fees: Fees,
-    swap_curve: SwapCurve,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: ConfigureLiquiditySnapshot,
 ) -> Result<Instruction, ProgramError> {
-    let init_data = SwapInstruction::Initialize(Initialize {
-        nonce,
-        fees,
-        swap_curve,
-    });
-    let data = init_data.pack();
+    let data = SwapInstruction::ConfigureLiquiditySnapshot(instruction).pack();
+    let (snapshot_pubkey, _bump_seed) = LiquiditySnapshot::find_address(swap_pubkey, program_id);
 
     let accounts = vec![
-        AccountMeta::new(*swap_pubkey, true),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*token_a_pubkey, false),
-        AccountMeta::new_readonly(*token_b_pubkey, false),
-        AccountMeta::new(*pool_pubkey, false),
-        AccountMeta::new_readonly(*fee_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new_readonly(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+        AccountMeta::new(snapshot_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
 
     Ok(Instruction {
@@ -384,34 +3831,23 @@ pub fn initialize(
     })
 }
 
-/// Creates a 'deposit_all_token_types' instruction.
-pub fn deposit_all_token_types(
+/// Creates a 'crank_liquidity_snapshot' instruction.
+pub fn crank_liquidity_snapshot(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    deposit_token_a_pubkey: &Pubkey,
-    deposit_token_b_pubkey: &Pubkey,
     swap_token_a_pubkey: &Pubkey,
     swap_token_b_pubkey: &Pubkey,
     pool_mint_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: DepositAllTokenTypes,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::DepositAllTokenTypes(instruction).pack();
+    let data = SwapInstruction::CrankLiquiditySnapshot.pack();
+    let (snapshot_pubkey, _bump_seed) = LiquiditySnapshot::find_address(swap_pubkey, program_id);
 
     let accounts = vec![
         AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*deposit_token_a_pubkey, false),
-        AccountMeta::new(*deposit_token_b_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(snapshot_pubkey, false),
+        AccountMeta::new_readonly(*swap_token_a_pubkey, false),
+        AccountMeta::new_readonly(*swap_token_b_pubkey, false),
+        AccountMeta::new_readonly(*pool_mint_pubkey, false),
     ];
 
     Ok(Instruction {
@@ -421,36 +3857,50 @@ pub fn deposit_all_token_types(
     })
 }
 
-/// Creates a 'withdraw_all_token_types' instruction.
-pub fn withdraw_all_token_types(
+/// Creates a 'set_nested_pool' instruction.
+pub fn set_nested_pool(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    fee_account_pubkey: &Pubkey,
-    source_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    destination_token_a_pubkey: &Pubkey,
-    destination_token_b_pubkey: &Pubkey,
-    instruction: WithdrawAllTokenTypes,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    token_b_mint_pubkey: &Pubkey,
+    instruction: SetNestedPool,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::WithdrawAllTokenTypes(instruction).pack();
+    let mut accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+    if instruction.nested_pool != Pubkey::default() {
+        accounts.push(AccountMeta::new_readonly(instruction.nested_pool, false));
+        accounts.push(AccountMeta::new_readonly(*token_b_mint_pubkey, false));
+    }
+    let data = SwapInstruction::SetNestedPool(instruction).pack();
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_keeper_config' instruction.
+pub fn set_keeper_config(
+    program_id: &Pubkey,
+    upgrade_authority_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: SetKeeperConfig,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetKeeperConfig(instruction).pack();
 
+    let (keeper_config_pubkey, _bump_seed) = KeeperConfig::find_address(program_id);
+    let (program_config_pubkey, _bump_seed) = ProgramConfig::find_address(program_id);
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*source_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*destination_token_a_pubkey, false),
-        AccountMeta::new(*destination_token_b_pubkey, false),
-        AccountMeta::new(*fee_account_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(keeper_config_pubkey, false),
+        AccountMeta::new_readonly(program_config_pubkey, false),
+        AccountMeta::new_readonly(*upgrade_authority_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
 
     Ok(Instruction {
@@ -460,32 +3910,92 @@ pub fn withdraw_all_token_types(
     })
 }
 
-/// Creates a 'deposit_single_token_type_exact_amount_in' instruction.
-pub fn deposit_single_token_type_exact_amount_in(
+/// Creates a 'register_keeper' instruction.
+pub fn register_keeper(
+    program_id: &Pubkey,
+    keeper_pubkey: &Pubkey,
+    instruction: RegisterKeeper,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::RegisterKeeper(instruction).pack();
+
+    let (keeper_bond_pubkey, _bump_seed) = KeeperBond::find_address(keeper_pubkey, program_id);
+    let (keeper_config_pubkey, _bump_seed) = KeeperConfig::find_address(program_id);
+    let accounts = vec![
+        AccountMeta::new(keeper_bond_pubkey, false),
+        AccountMeta::new_readonly(keeper_config_pubkey, false),
+        AccountMeta::new(*keeper_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates an 'unbond_keeper' instruction.
+pub fn unbond_keeper(
+    program_id: &Pubkey,
+    keeper_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::UnbondKeeper.pack();
+
+    let (keeper_bond_pubkey, _bump_seed) = KeeperBond::find_address(keeper_pubkey, program_id);
+    let (keeper_config_pubkey, _bump_seed) = KeeperConfig::find_address(program_id);
+    let accounts = vec![
+        AccountMeta::new(keeper_bond_pubkey, false),
+        AccountMeta::new_readonly(keeper_config_pubkey, false),
+        AccountMeta::new_readonly(*keeper_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'withdraw_keeper_bond' instruction.
+pub fn withdraw_keeper_bond(
+    program_id: &Pubkey,
+    keeper_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::WithdrawKeeperBond.pack();
+
+    let (keeper_bond_pubkey, _bump_seed) = KeeperBond::find_address(keeper_pubkey, program_id);
+    let accounts = vec![
+        AccountMeta::new(keeper_bond_pubkey, false),
+        AccountMeta::new(*keeper_pubkey, true),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'slash_keeper' instruction.
+pub fn slash_keeper(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
-    swap_pubkey: &Pubkey,
     authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    source_token_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: DepositSingleTokenTypeExactAmountIn,
+    keeper_pubkey: &Pubkey,
+    swap_pubkey: &Pubkey,
+    oracle_pubkey: &Pubkey,
+    instruction: SlashKeeper,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::DepositSingleTokenTypeExactAmountIn(instruction).pack();
+    let data = SwapInstruction::SlashKeeper(instruction).pack();
 
+    let (keeper_bond_pubkey, _bump_seed) = KeeperBond::find_address(keeper_pubkey, program_id);
+    let (keeper_config_pubkey, _bump_seed) = KeeperConfig::find_address(program_id);
+    let (oracle_config_pubkey, _bump_seed) = OracleConfig::find_address(swap_pubkey, program_id);
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*source_token_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(keeper_bond_pubkey, false),
+        AccountMeta::new(keeper_config_pubkey, false),
+        AccountMeta::new_readonly(*authority_pubkey, true),
+        AccountMeta::new_readonly(oracle_config_pubkey, false),
+        AccountMeta::new_readonly(*oracle_pubkey, false),
     ];
 
     Ok(Instruction {
@@ -495,34 +4005,20 @@ pub fn deposit_single_token_type_exact_amount_in(
     })
 }
 
-/// Creates a 'withdraw_single_token_type_exact_amount_out' instruction.
-pub fn withdraw_single_token_type_exact_amount_out(
+/// Creates a 'set_bonded_keepers_only' instruction.
+pub fn set_bonded_keepers_only(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
-    fee_account_pubkey: &Pubkey,
-    pool_token_source_pubkey: &Pubkey,
-    swap_token_a_pubkey: &Pubkey,
-    swap_token_b_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    instruction: WithdrawSingleTokenTypeExactAmountOut,
+    admin_pubkey: &Pubkey,
+    pool_fee_pubkey: &Pubkey,
+    instruction: SetBondedKeepersOnly,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(instruction).pack();
+    let data = SwapInstruction::SetBondedKeepersOnly(instruction).pack();
 
     let accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*pool_token_source_pubkey, false),
-        AccountMeta::new(*swap_token_a_pubkey, false),
-        AccountMeta::new(*swap_token_b_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new(*fee_account_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
     ];
 
     Ok(Instruction {
@@ -532,39 +4028,71 @@ pub fn withdraw_single_token_type_exact_amount_out(
     })
 }
 
-/// Creates a 'swap' instruction.
-pub fn swap(
+/// Creates a 'set_paused' instruction.
+pub fn set_paused(
     program_id: &Pubkey,
-    token_program_id: &Pubkey,
     swap_pubkey: &Pubkey,
-    authority_pubkey: &Pubkey,
-    user_transfer_authority_pubkey: &Pubkey,
-    source_pubkey: &Pubkey,
-    swap_source_pubkey: &Pubkey,
-    swap_destination_pubkey: &Pubkey,
-    destination_pubkey: &Pubkey,
-    pool_mint_pubkey: &Pubkey,
+    admin_pubkey: &Pubkey,
     pool_fee_pubkey: &Pubkey,
-    host_fee_pubkey: Option<&Pubkey>,
-    instruction: Swap,
+    instruction: SetPaused,
 ) -> Result<Instruction, ProgramError> {
-    let data = SwapInstruction::Swap(instruction).pack();
+    let data = SwapInstruction::SetPaused(instruction).pack();
 
-    let mut accounts = vec![
-        AccountMeta::new_readonly(*swap_pubkey, false),
-        AccountMeta::new_readonly(*authority_pubkey, false),
-        AccountMeta::new_readonly(*user_transfer_authority_pubkey, true),
-        AccountMeta::new(*source_pubkey, false),
-        AccountMeta::new(*swap_source_pubkey, false),
-        AccountMeta::new(*swap_destination_pubkey, false),
-        AccountMeta::new(*destination_pubkey, false),
-        AccountMeta::new(*pool_mint_pubkey, false),
-        AccountMeta::new(*pool_fee_pubkey, false),
-        AccountMeta::new_readonly(*token_program_id, false),
+    let accounts = vec![
+        AccountMeta::new(*swap_pubkey, false),
+        AccountMeta::new_readonly(*admin_pubkey, true),
+        AccountMeta::new_readonly(*pool_fee_pubkey, false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_governance_program' instruction.
+pub fn set_governance_program(
+    program_id: &Pubkey,
+    upgrade_authority_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: SetGovernanceProgram,
+) -> Result<Instruction, ProgramError> {
+    let data = SwapInstruction::SetGovernanceProgram(instruction).pack();
+
+    let (program_config_pubkey, _bump_seed) = ProgramConfig::find_address(program_id);
+    let accounts = vec![
+        AccountMeta::new(program_config_pubkey, false),
+        AccountMeta::new_readonly(*upgrade_authority_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ];
+
+    Ok(Instruction {
+        program_id: *program_id,
+        accounts,
+        data,
+    })
+}
+
+/// Creates a 'set_pool_template' instruction.
+pub fn set_pool_template(
+    program_id: &Pubkey,
+    upgrade_authority_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+    instruction: SetPoolTemplate,
+) -> Result<Instruction, ProgramError> {
+    let (template_pubkey, _bump_seed) = PoolTemplate::find_address(instruction.index, program_id);
+    let data = SwapInstruction::SetPoolTemplate(instruction).pack();
+
+    let (program_config_pubkey, _bump_seed) = ProgramConfig::find_address(program_id);
+    let accounts = vec![
+        AccountMeta::new(template_pubkey, false),
+        AccountMeta::new_readonly(program_config_pubkey, false),
+        AccountMeta::new_readonly(*upgrade_authority_pubkey, true),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
     ];
-    if let Some(host_fee_pubkey) = host_fee_pubkey {
-        accounts.push(AccountMeta::new(*host_fee_pubkey, false));
-    }
 
     Ok(Instruction {
         program_id: *program_id,
@@ -573,6 +4101,152 @@ pub fn swap(
     })
 }
 
+/// Has a crank of a pool taking bonded keepers only present the keeper
+/// signing it, with its bond and the keeper config, after the crank's
+/// accounts.
+pub fn with_keeper(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    keeper_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::CrankOraclePrice | SwapInstruction::RefreshStakePoolRate => {}
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    let (keeper_config_pubkey, _bump_seed) = KeeperConfig::find_address(program_id);
+    let (keeper_bond_pubkey, _bump_seed) = KeeperBond::find_address(keeper_pubkey, program_id);
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(keeper_config_pubkey, false),
+        AccountMeta::new(keeper_bond_pubkey, false),
+        AccountMeta::new_readonly(*keeper_pubkey, true),
+    ]);
+    Ok(instruction)
+}
+
+/// Has a swap on a pool guarding against sandwiches present the instructions
+/// sysvar, right after the instruction's fixed accounts.  On a gated pool,
+/// apply `with_gate_account` afterwards, the gate account coming first.
+pub fn with_instructions_sysvar(mut instruction: Instruction) -> Result<Instruction, ProgramError> {
+    match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::Swap(_) => {}
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    if instruction.accounts.len() < 10 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    instruction.accounts.insert(
+        10,
+        AccountMeta::new_readonly(sysvar::instructions::id(), false),
+    );
+    Ok(instruction)
+}
+
+/// Has a swap or deposit on a gated pool present the trader's gate account,
+/// their token account of the gating mint or their credential, right after
+/// the instruction's fixed accounts.
+pub fn with_gate_account(
+    mut instruction: Instruction,
+    gate_account_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    let index = match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::Swap(_) | SwapInstruction::DepositAllTokenTypes(_) => 10,
+        SwapInstruction::DepositSingleTokenTypeExactAmountIn(_) => 9,
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    };
+    if instruction.accounts.len() < index {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    instruction.accounts.insert(
+        index,
+        AccountMeta::new_readonly(*gate_account_pubkey, false),
+    );
+    Ok(instruction)
+}
+
+/// Has a single-sided deposit into a pool with a withdraw cooldown record its
+/// slot in the LP position of the pool token account it mints into, the
+/// payer funding the position on the account's first.  Applied after any
+/// gate account, before a referrer.
+pub fn with_lp_position(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::DepositSingleTokenTypeExactAmountIn(_) => {}
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    if instruction.accounts.len() < 9 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let (position_pubkey, _) = LpPosition::find_address(
+        &instruction.accounts[0].pubkey,
+        &instruction.accounts[7].pubkey,
+        program_id,
+    );
+    instruction.accounts.extend([
+        AccountMeta::new(position_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    Ok(instruction)
+}
+
+/// Has a single-sided withdrawal from a pool with a withdraw cooldown present
+/// the LP position of the pool token account it burns from, right after the
+/// instruction's fixed accounts.
+pub fn with_source_lp_position(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_) => {}
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    if instruction.accounts.len() < 10 {
+        return Err(ProgramError::NotEnoughAccountKeys);
+    }
+    let (position_pubkey, _) = LpPosition::find_address(
+        &instruction.accounts[0].pubkey,
+        &instruction.accounts[4].pubkey,
+        program_id,
+    );
+    instruction
+        .accounts
+        .insert(10, AccountMeta::new_readonly(position_pubkey, false));
+    Ok(instruction)
+}
+
+/// Attributes a deposit to a registered referrer, presenting the referrer and
+/// its deposits into the pool.  Applied last, after any gate account.
+pub fn with_referrer(
+    mut instruction: Instruction,
+    program_id: &Pubkey,
+    referrer_wallet_pubkey: &Pubkey,
+    payer_pubkey: &Pubkey,
+) -> Result<Instruction, ProgramError> {
+    match SwapInstruction::unpack(&instruction.data)? {
+        SwapInstruction::DepositAllTokenTypes(_)
+        | SwapInstruction::DepositSingleTokenTypeExactAmountIn(_) => {}
+        _ => return Err(SwapError::InvalidInstruction.into()),
+    }
+    let swap_pubkey = instruction
+        .accounts
+        .first()
+        .ok_or(ProgramError::NotEnoughAccountKeys)?
+        .pubkey;
+    let (referrer_pubkey, _) = Referrer::find_address(referrer_wallet_pubkey, program_id);
+    let (referred_pubkey, _) =
+        ReferredDeposits::find_address(&swap_pubkey, referrer_wallet_pubkey, program_id);
+    instruction.accounts.extend([
+        AccountMeta::new_readonly(referrer_pubkey, false),
+        AccountMeta::new(referred_pubkey, false),
+        AccountMeta::new(*payer_pubkey, true),
+        AccountMeta::new_readonly(system_program::id(), false),
+    ]);
+    Ok(instruction)
+}
+
 /// Unpacks a reference from a bytes buffer.
 pub fn unpack<T>(input: &[u8]) -> Result<&T, ProgramError> {
     if input.len() < size_of::<u8>() + size_of::<T>() {
@@ -1,7 +1,35 @@
-pub mod curve;
+pub mod amount;
+#[cfg(feature = "client")]
+pub mod analysis;
+#[cfg(feature = "client")]
+pub mod associated_token;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod compute_budget;
+pub mod constants;
 pub mod constraints;
+pub mod curve;
+#[cfg(all(feature = "program", not(feature = "no-entrypoint")))]
 pub mod entrypoint;
 pub mod error;
+pub mod events;
 pub mod instruction;
+pub mod limits;
+#[cfg(feature = "client")]
+pub mod lookup_table;
+#[cfg(feature = "client")]
+pub mod pool_builder;
+#[cfg(feature = "client")]
+pub mod pricing;
+#[cfg(feature = "program")]
 pub mod processor;
+#[cfg(all(feature = "python", not(target_arch = "bpf")))]
+pub mod python;
+#[cfg(feature = "client")]
+pub mod shadow;
+#[cfg(feature = "client")]
+pub mod snapshot;
 pub mod state;
+pub mod token_ops;
+#[cfg(kani)]
+mod verification;
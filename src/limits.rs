@@ -0,0 +1,272 @@
+//! Account and call depth limits of the program's instructions.
+//!
+//! Aggregators route trades through their own program, which the
+//! transaction invokes or another program does, a vault's say, so this
+//! program runs at stack height 2 or 3 of the runtime's 5.  That leaves its
+//! instructions two levels of CPI below them: the token program, or a flash
+//! swap's callback and the token program under it.  A route spans several
+//! pools in one transaction, so each instruction takes at most half the
+//! accounts a transaction can lock.  The layouts below are asserted against
+//! both at compile time, and `check_limits` refuses an instruction past them
+//! before it runs.
+
+use crate::constants::{MAX_ADMIN_SIGNERS, MAX_POOL_STATE_VIEWS};
+use crate::error::SwapError;
+use crate::instruction::SwapInstruction;
+use solana_program::{
+    account_info::AccountInfo, entrypoint::ProgramResult, instruction::get_stack_height,
+};
+
+/// Deepest stack height the runtime runs an instruction at, the
+/// transaction's own instructions being at height 1
+pub const MAX_INSTRUCTION_STACK_HEIGHT: usize = 5;
+
+/// Deepest the program is meant to be invoked from, by an aggregator itself
+/// invoked by another program
+pub const MAX_CALLER_STACK_HEIGHT: usize = 3;
+
+/// Levels of CPI an instruction of the program may go below it
+pub const MAX_CPI_DEPTH: usize = MAX_INSTRUCTION_STACK_HEIGHT - MAX_CALLER_STACK_HEIGHT;
+
+/// Accounts a transaction can lock
+pub const MAX_TRANSACTION_ACCOUNTS: usize = 64;
+
+/// Most accounts an instruction of the program takes, the rest of the
+/// transaction's are the route's around it
+pub const MAX_INSTRUCTION_ACCOUNTS: usize = MAX_TRANSACTION_ACCOUNTS / 2;
+
+/// The token program, or the system program creating an account
+pub const TOKEN_CPI_DEPTH: usize = 1;
+
+/// The callback, and the transfer repaying the pool under it
+pub const FLASH_SWAP_CPI_DEPTH: usize = 2;
+
+/// Fixed accounts, vesting, oracle and program config
+pub const INITIALIZE_ACCOUNTS: usize = 10 + 4 + 1 + 2;
+
+/// The pool template, then an initialize's accounts
+pub const INITIALIZE_FROM_TEMPLATE_ACCOUNTS: usize = 1 + INITIALIZE_ACCOUNTS;
+
+/// Fixed accounts, gate, instructions sysvar, fee conversion, host fee
+/// account and its referrer
+pub const SWAP_ACCOUNTS: usize = 10 + 1 + 1 + 6 + 1 + 1;
+
+/// Fixed accounts, gate and referral
+pub const DEPOSIT_ALL_TOKEN_TYPES_ACCOUNTS: usize = 10 + 1 + 4;
+
+/// The lock's accounts and the pool fee account, then a deposit's
+pub const DEPOSIT_AND_LOCK_ACCOUNTS: usize = 5 + DEPOSIT_ALL_TOKEN_TYPES_ACCOUNTS;
+
+/// Fixed accounts, gate, LP position and referral
+pub const DEPOSIT_SINGLE_TOKEN_TYPE_ACCOUNTS: usize = 9 + 1 + 3 + 4;
+
+/// Fixed accounts and fee vaults
+pub const WITHDRAW_ACCOUNTS: usize = 11 + 2;
+
+/// Fixed accounts, LP position and fee vaults
+pub const WITHDRAW_SINGLE_TOKEN_TYPE_ACCOUNTS: usize = 10 + 1 + 2;
+
+/// The pool admin and fee account, a deposit's accounts, and the multisig
+/// admin's signers
+pub const ADMIN_MOVE_LIQUIDITY_ACCOUNTS: usize =
+    2 + DEPOSIT_ALL_TOKEN_TYPES_ACCOUNTS + MAX_ADMIN_SIGNERS;
+
+/// Keeper config, keeper bond and keeper, signing a crank of a pool taking
+/// bonded keepers only
+pub const KEEPER_ACCOUNTS: usize = 3;
+
+/// Program config and native treasury, signing for a governance admin
+pub const GOVERNANCE_ADMIN_ACCOUNTS: usize = 2;
+
+/// Fixed accounts of a flash swap, before its callback program
+pub const FLASH_SWAP_ACCOUNTS: usize = 8;
+
+/// Accounts a flash swap passes on to its callback.  The fee conversion
+/// accounts, when the pool takes them, count against these.
+pub const MAX_CALLBACK_ACCOUNTS: usize = MAX_INSTRUCTION_ACCOUNTS - FLASH_SWAP_ACCOUNTS - 1;
+
+const _: () = assert!(FLASH_SWAP_CPI_DEPTH <= MAX_CPI_DEPTH);
+const _: () = assert!(INITIALIZE_FROM_TEMPLATE_ACCOUNTS <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(SWAP_ACCOUNTS <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(DEPOSIT_SINGLE_TOKEN_TYPE_ACCOUNTS <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(DEPOSIT_AND_LOCK_ACCOUNTS <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(ADMIN_MOVE_LIQUIDITY_ACCOUNTS <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(4 * MAX_POOL_STATE_VIEWS <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(admin(9) <= MAX_INSTRUCTION_ACCOUNTS);
+const _: () = assert!(GOVERNANCE_ADMIN_ACCOUNTS <= MAX_ADMIN_SIGNERS);
+
+/// An admin instruction's accounts, and its multisig admin's signers, more
+/// than a governance admin's accounts
+const fn admin(accounts: usize) -> usize {
+    accounts + MAX_ADMIN_SIGNERS
+}
+
+/// Most accounts the instruction takes, with all its optional accounts
+pub fn max_accounts(instruction: &SwapInstruction) -> usize {
+    match instruction {
+        SwapInstruction::Initialize(_) => INITIALIZE_ACCOUNTS,
+        // the pool template, then initialize's
+        SwapInstruction::InitializeFromTemplate(_) => INITIALIZE_FROM_TEMPLATE_ACCOUNTS,
+        SwapInstruction::Swap(_) => SWAP_ACCOUNTS,
+        SwapInstruction::DepositAllTokenTypes(_) => DEPOSIT_ALL_TOKEN_TYPES_ACCOUNTS,
+        SwapInstruction::DepositSingleTokenTypeExactAmountIn(_) => {
+            DEPOSIT_SINGLE_TOKEN_TYPE_ACCOUNTS
+        }
+        SwapInstruction::WithdrawAllTokenTypes(_) | SwapInstruction::WithdrawMax(_) => {
+            WITHDRAW_ACCOUNTS
+        }
+        SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_) => {
+            WITHDRAW_SINGLE_TOKEN_TYPE_ACCOUNTS
+        }
+        SwapInstruction::EmergencyWithdraw(_) => 10,
+        SwapInstruction::AdminDepositAllTokenTypes(_)
+        | SwapInstruction::AdminWithdrawAllTokenTypes(_) => ADMIN_MOVE_LIQUIDITY_ACCOUNTS,
+        SwapInstruction::RebalanceSwap(_) => 9,
+        SwapInstruction::FlashSwap(_) => FLASH_SWAP_ACCOUNTS + 1 + MAX_CALLBACK_ACCOUNTS,
+        SwapInstruction::GetMultiplePoolStates => 4 * MAX_POOL_STATE_VIEWS,
+        SwapInstruction::SimulateSwap(_) => 4,
+        SwapInstruction::GetHealth => 3,
+        SwapInstruction::RegisterReferrer(_) => 5,
+        SwapInstruction::ClaimVestedPoolTokens => 6,
+        SwapInstruction::LockLiquidity(_) => 9,
+        SwapInstruction::DepositAndLockLiquidity(_) => DEPOSIT_AND_LOCK_ACCOUNTS,
+        SwapInstruction::UnlockLiquidity(_) => 7,
+        // with the keeper's accounts, for a pool taking bonded keepers only
+        SwapInstruction::CrankOraclePrice => 3 + KEEPER_ACCOUNTS,
+        SwapInstruction::RefreshStakePoolRate => 2 + KEEPER_ACCOUNTS,
+        SwapInstruction::ClaimHostFees => 5,
+        SwapInstruction::DocumentUpgradeAuthority => 5,
+        SwapInstruction::InitializeAdminMultisig(_) => 1,
+        SwapInstruction::SetKeeperConfig(_) => 5,
+        SwapInstruction::SetGovernanceProgram(_) => 4,
+        SwapInstruction::SetPoolTemplate(_) => 5,
+        SwapInstruction::RegisterKeeper(_) => 4,
+        SwapInstruction::SlashKeeper(_) => 5,
+        SwapInstruction::UnbondKeeper => 3,
+        SwapInstruction::WithdrawKeeperBond => 2,
+        SwapInstruction::CrankLiquiditySnapshot => 5,
+        SwapInstruction::ReinitializeCurve(_) => admin(9),
+        SwapInstruction::ResizeSwapAccount => admin(8),
+        SwapInstruction::SetOracleConfig(_) => admin(7),
+        SwapInstruction::SetPoolMetadata(_) | SwapInstruction::ConfigureLiquiditySnapshot(_) => {
+            admin(6)
+        }
+        SwapInstruction::SetWithdrawFeeDestination(_)
+        | SwapInstruction::SetOwnerFeeAccounts(_)
+        | SwapInstruction::SetFeeConversion(_)
+        | SwapInstruction::SetNestedPool(_) => admin(5),
+        SwapInstruction::SetStakePool(_)
+        | SwapInstruction::SetDefaultHost(_)
+        | SwapInstruction::SetFeeAccount => admin(4),
+        SwapInstruction::SetRebalancer(_)
+        | SwapInstruction::SetEmergency(_)
+        | SwapInstruction::SetDepositBonus(_)
+        | SwapInstruction::SetFeeExempt(_)
+        | SwapInstruction::SetFeeTiers(_)
+        | SwapInstruction::SetExactInput(_)
+        | SwapInstruction::SetWithdrawCooldown(_)
+        | SwapInstruction::SetGate(_)
+        | SwapInstruction::SetSingleWithdrawFeeToLps(_)
+        | SwapInstruction::SetSandwichGuard(_)
+        | SwapInstruction::SetOwnerFeeFallback(_)
+        | SwapInstruction::SetBondedKeepersOnly(_)
+        | SwapInstruction::SetPaused(_) => admin(3),
+    }
+}
+
+/// Levels of CPI the instruction goes below the program
+pub fn cpi_depth(instruction: &SwapInstruction) -> usize {
+    match instruction {
+        SwapInstruction::FlashSwap(_) => FLASH_SWAP_CPI_DEPTH,
+        // reads, reporting through the return data
+        SwapInstruction::SimulateSwap(_)
+        | SwapInstruction::GetHealth
+        | SwapInstruction::GetMultiplePoolStates => 0,
+        _ => TOKEN_CPI_DEPTH,
+    }
+}
+
+/// Refuses an instruction given more accounts than it takes, or invoked too
+/// deep for its CPIs to run
+pub fn check_limits(instruction: &SwapInstruction, accounts: &[AccountInfo]) -> ProgramResult {
+    if accounts.len() > max_accounts(instruction) {
+        return Err(SwapError::TooManyAccounts.into());
+    }
+    if get_stack_height() + cpi_depth(instruction) > MAX_INSTRUCTION_STACK_HEIGHT {
+        return Err(SwapError::CallTooDeep.into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::{
+        self, DepositSingleTokenTypeExactAmountIn, FeeConversionAccounts, Swap,
+    };
+    use solana_program::pubkey::Pubkey;
+
+    #[test]
+    fn builders_with_every_optional_account_fill_the_layouts() {
+        let program_id = Pubkey::new_unique();
+        let keys: Vec<Pubkey> = (0..12).map(|_| Pubkey::new_unique()).collect();
+        let fee_conversion = FeeConversionAccounts {
+            fee_conversion_pool: Pubkey::new_unique(),
+            fee_conversion_authority: Pubkey::new_unique(),
+            fee_conversion_source: Pubkey::new_unique(),
+            fee_conversion_destination: Pubkey::new_unique(),
+            fee_conversion_pool_mint: Pubkey::new_unique(),
+            fee_conversion_account: Pubkey::new_unique(),
+        };
+        let swap = instruction::swap_with_fee_conversion(
+            &program_id,
+            &spl_token::id(),
+            &keys[0],
+            &keys[1],
+            &keys[2],
+            &keys[3],
+            &keys[4],
+            &keys[5],
+            &keys[6],
+            &keys[7],
+            &keys[8],
+            Some(&fee_conversion),
+            Some(&keys[9]),
+            Some(&keys[10]),
+            Swap {
+                amount_in: 1,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .and_then(|swap| instruction::with_gate_account(swap, &keys[11]))
+        .and_then(instruction::with_instructions_sysvar)
+        .unwrap();
+        let deposit = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id,
+            &spl_token::id(),
+            &keys[0],
+            &keys[1],
+            &keys[2],
+            &keys[3],
+            &keys[4],
+            &keys[5],
+            &keys[6],
+            &keys[7],
+            DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 1,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .and_then(|deposit| instruction::with_gate_account(deposit, &keys[8]))
+        .and_then(|deposit| instruction::with_lp_position(deposit, &program_id, &keys[2]))
+        .and_then(|deposit| instruction::with_referrer(deposit, &program_id, &keys[9], &keys[2]))
+        .unwrap();
+
+        for instruction in [swap, deposit] {
+            let swap_instruction = SwapInstruction::unpack(&instruction.data).unwrap();
+            assert_eq!(instruction.accounts.len(), max_accounts(&swap_instruction));
+        }
+    }
+}
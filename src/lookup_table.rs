@@ -0,0 +1,202 @@
+//! Address lookup tables for routing through several pools in one
+//! transaction, behind the `client` feature.
+//!
+//! A swap names eleven accounts and a legacy transaction spends 32 of its
+//! 1232 bytes on each, so a route runs out of room after two or three hops.
+//! With every pool the route may take in a lookup table, v0 transactions
+//! name the pools' static accounts by a one byte index instead.
+//!
+//! A table is made once with `create`, filled with `extend` from the
+//! `pool_addresses` of each pool, and can be used from the slot after it was
+//! last extended: fetch it, read it with `account`, and pass it to
+//! `compile_v0` with the route's instructions.
+
+use crate::{
+    curve::calculator::TradeDirection,
+    state::{SwapState, SwapVersion},
+};
+use solana_program::{
+    address_lookup_table::{instruction, state::AddressLookupTable, AddressLookupTableAccount},
+    clock::Slot,
+    hash::Hash,
+    instruction::Instruction,
+    message::{v0, CompileError, VersionedMessage},
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+
+/// Most addresses added by one extend instruction, so that it fits a
+/// transaction of its own with room to spare
+pub const MAX_ADDRESSES_PER_EXTEND: usize = 20;
+
+/// Accounts of a pool that are the same for every user: the token program,
+/// the pool's state, authority, vaults, mint and fee account, its stake pool
+/// if it's linked to one, its owner fee accounts if split by direction, and
+/// its fee conversion pool and account if it converts owner fees.  The
+/// program itself is left out, programs being invoked can't be looked up.
+pub fn pool_addresses(
+    program_id: &Pubkey,
+    swap: &Pubkey,
+    pool: &dyn SwapState,
+) -> Result<Vec<Pubkey>, ProgramError> {
+    let mut addresses = vec![
+        *pool.token_program_id(),
+        *swap,
+        SwapVersion::authority_id(program_id, swap, pool.nonce())?,
+        *pool.token_a_account(),
+        *pool.token_b_account(),
+        *pool.pool_mint(),
+        *pool.pool_fee_account(),
+    ];
+    addresses.extend(pool.stake_pool());
+    for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+        let owner_fee_account = pool.owner_fee_account(trade_direction);
+        if !addresses.contains(owner_fee_account) {
+            addresses.push(*owner_fee_account);
+        }
+        if let Some((fee_conversion_pool, fee_conversion_account)) =
+            pool.fee_conversion(trade_direction)
+        {
+            addresses.extend([fee_conversion_pool, fee_conversion_account]);
+        }
+    }
+    Ok(addresses)
+}
+
+/// Creates a 'create_lookup_table' instruction, returning it with the new
+/// table's address.  `recent_slot` has to be a recent slot, it seeds the
+/// address.
+pub fn create(authority: &Pubkey, payer: &Pubkey, recent_slot: Slot) -> (Instruction, Pubkey) {
+    instruction::create_lookup_table(*authority, *payer, recent_slot)
+}
+
+/// Creates the 'extend_lookup_table' instructions adding `addresses` to
+/// `table`, each to be sent in a transaction of its own.  Addresses already
+/// in the table, or given twice, are only added once.
+pub fn extend(
+    table: &AddressLookupTableAccount,
+    authority: &Pubkey,
+    payer: &Pubkey,
+    addresses: &[Pubkey],
+) -> Vec<Instruction> {
+    let mut new_addresses: Vec<Pubkey> = vec![];
+    for address in addresses {
+        if !table.addresses.contains(address) && !new_addresses.contains(address) {
+            new_addresses.push(*address);
+        }
+    }
+    new_addresses
+        .chunks(MAX_ADDRESSES_PER_EXTEND)
+        .map(|chunk| {
+            instruction::extend_lookup_table(table.key, *authority, Some(*payer), chunk.to_vec())
+        })
+        .collect()
+}
+
+/// Reads a lookup table from its account data
+pub fn account(key: Pubkey, data: &[u8]) -> Result<AddressLookupTableAccount, ProgramError> {
+    let table =
+        AddressLookupTable::deserialize(data).map_err(|_| ProgramError::InvalidAccountData)?;
+    Ok(AddressLookupTableAccount {
+        key,
+        addresses: table.addresses.to_vec(),
+    })
+}
+
+/// Compiles a v0 message, naming every account found in `tables` through
+/// them rather than in full
+pub fn compile_v0(
+    payer: &Pubkey,
+    instructions: &[Instruction],
+    tables: &[AddressLookupTableAccount],
+    recent_blockhash: Hash,
+) -> Result<VersionedMessage, CompileError> {
+    v0::Message::try_compile(payer, instructions, tables, recent_blockhash)
+        .map(VersionedMessage::V0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::{base::SwapCurve, fees::Fees},
+        instruction::{swap, Swap},
+        state::SwapV2,
+    };
+    use solana_program::message::Message;
+
+    #[test]
+    fn routes_name_pool_accounts_through_the_table() {
+        let program_id = Pubkey::new_unique();
+        let (user, payer) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pools: Vec<(Pubkey, SwapV2)> = (0..4)
+            .map(|_| {
+                let swap = Pubkey::new_unique();
+                let (_, nonce) = Pubkey::find_program_address(&[swap.as_ref()], &program_id);
+                let pool = SwapV2 {
+                    is_initialized: true,
+                    nonce,
+                    token_program_id: spl_token::id(),
+                    token_a: Pubkey::new_unique(),
+                    token_b: Pubkey::new_unique(),
+                    pool_mint: Pubkey::new_unique(),
+                    pool_fee_account: Pubkey::new_unique(),
+                    fees: Fees::standard_30bps(),
+                    swap_curve: SwapCurve::default(),
+                    ..SwapV2::default()
+                };
+                (swap, pool)
+            })
+            .collect();
+
+        let mut table = AddressLookupTableAccount {
+            key: Pubkey::new_unique(),
+            addresses: vec![],
+        };
+        let mut addresses = vec![];
+        for (swap, pool) in &pools {
+            addresses.extend(pool_addresses(&program_id, swap, pool).unwrap());
+        }
+        // 25 addresses, the token program being shared by every pool
+        assert_eq!(extend(&table, &user, &payer, &addresses).len(), 2);
+        table.addresses = addresses.clone();
+        assert!(extend(&table, &user, &payer, &addresses).is_empty());
+
+        // a four hop route through fresh intermediate accounts of the user's
+        let route: Vec<Instruction> = pools
+            .iter()
+            .map(|(swap_key, pool)| {
+                swap(
+                    &program_id,
+                    &spl_token::id(),
+                    swap_key,
+                    &SwapVersion::authority_id(&program_id, swap_key, pool.nonce).unwrap(),
+                    &user,
+                    &Pubkey::new_unique(),
+                    &pool.token_a,
+                    &pool.token_b,
+                    &Pubkey::new_unique(),
+                    &pool.pool_mint,
+                    &pool.pool_fee_account,
+                    None,
+                    None,
+                    Swap {
+                        amount_in: 1,
+                        minimum_amount_out: 0,
+                        min_price_numerator: 0,
+                        min_price_denominator: 0,
+                    },
+                )
+                .unwrap()
+            })
+            .collect();
+        let legacy = Message::new(&route, Some(&payer));
+        let compiled = compile_v0(&payer, &route, &[table], Hash::default()).unwrap();
+        // only the payer, the user, their token accounts and the program are
+        // left in full
+        assert_eq!(compiled.static_account_keys().len(), 3 + 2 * pools.len());
+        // a transaction is 1232 bytes, 64 of them the payer's signature
+        assert!(legacy.serialize().len() > 1232 - 64);
+        assert!(compiled.serialize().len() < 1232 - 64);
+    }
+}
@@ -0,0 +1,371 @@
+//! Client-side building of a pool's 'initialize' instruction, behind the
+//! `client` feature.
+//!
+//! `InitializePoolBuilder` derives the pool's authority and nonce from the
+//! swap account's address, and checks the accounts the pool is made from,
+//! as the client fetched them, the way the program will: vaults owned by the
+//! authority and holding the pool's opening reserves, an empty pool mint only
+//! the authority can mint, pool token accounts of that mint.  A mistake
+//! comes back as the `SwapError` the program would fail with, before any
+//! rent is spent on the swap account.
+
+use crate::{
+    constants::MAX_TOKEN_DECIMALS,
+    curve::{base::SwapCurve, fees::Fees},
+    error::SwapError,
+    instruction::{self, InitializeFromTemplate},
+    state::PoolTemplate,
+};
+use solana_program::{
+    instruction::Instruction, program_error::ProgramError, program_option::COption, pubkey::Pubkey,
+};
+use spl_token::state::{Account, Mint};
+
+/// Fees and curve of the new pool
+enum PoolParameters {
+    Custom(Fees, SwapCurve),
+    Template(PoolTemplate),
+}
+
+/// Builds a pool's 'initialize' or 'initialize_from_template' instruction
+/// from the accounts' addresses and fetched states, checking them on
+/// `build()`
+pub struct InitializePoolBuilder {
+    program_id: Pubkey,
+    token_program_id: Pubkey,
+    swap: Pubkey,
+    token_a: Option<(Pubkey, Account)>,
+    token_b: Option<(Pubkey, Account)>,
+    mint_a: Option<(Pubkey, Mint)>,
+    mint_b: Option<(Pubkey, Mint)>,
+    pool_mint: Option<(Pubkey, Mint)>,
+    fee_account: Option<(Pubkey, Account)>,
+    destination: Option<(Pubkey, Account)>,
+    parameters: Option<PoolParameters>,
+    // slots, beneficiary pool token account and payer of the vesting account
+    vesting: Option<(u64, (Pubkey, Account), Pubkey)>,
+    max_slot_volume: u64,
+    fee_holiday_end_slot: u64,
+}
+
+impl InitializePoolBuilder {
+    /// A pool at the `swap` address, trading tokens of the spl-token program
+    pub fn new(program_id: &Pubkey, swap: &Pubkey) -> Self {
+        Self {
+            program_id: *program_id,
+            token_program_id: spl_token::id(),
+            swap: *swap,
+            token_a: None,
+            token_b: None,
+            mint_a: None,
+            mint_b: None,
+            pool_mint: None,
+            fee_account: None,
+            destination: None,
+            parameters: None,
+            vesting: None,
+            max_slot_volume: 0,
+            fee_holiday_end_slot: 0,
+        }
+    }
+
+    /// The pool's authority and its nonce, which the vaults have to be owned
+    /// by and the pool mint minted by
+    pub fn authority(&self) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[self.swap.as_ref()], &self.program_id)
+    }
+
+    pub fn token_program(mut self, token_program_id: &Pubkey) -> Self {
+        self.token_program_id = *token_program_id;
+        self
+    }
+
+    /// The token A vault and its mint
+    pub fn token_a(mut self, vault: (Pubkey, Account), mint: (Pubkey, Mint)) -> Self {
+        self.token_a = Some(vault);
+        self.mint_a = Some(mint);
+        self
+    }
+
+    /// The token B vault and its mint
+    pub fn token_b(mut self, vault: (Pubkey, Account), mint: (Pubkey, Mint)) -> Self {
+        self.token_b = Some(vault);
+        self.mint_b = Some(mint);
+        self
+    }
+
+    pub fn pool_mint(mut self, pool_mint: (Pubkey, Mint)) -> Self {
+        self.pool_mint = Some(pool_mint);
+        self
+    }
+
+    /// Pool token account the owner's fees are paid to
+    pub fn fee_account(mut self, fee_account: (Pubkey, Account)) -> Self {
+        self.fee_account = Some(fee_account);
+        self
+    }
+
+    /// Pool token account the initial supply is minted to
+    pub fn destination(mut self, destination: (Pubkey, Account)) -> Self {
+        self.destination = Some(destination);
+        self
+    }
+
+    pub fn fees_and_curve(mut self, fees: Fees, swap_curve: SwapCurve) -> Self {
+        self.parameters = Some(PoolParameters::Custom(fees, swap_curve));
+        self
+    }
+
+    /// Fees and curve of a pool template, as fetched from its account
+    pub fn template(mut self, template: PoolTemplate) -> Self {
+        self.parameters = Some(PoolParameters::Template(template));
+        self
+    }
+
+    /// Vests the initial supply to `beneficiary` over `slots`.  The
+    /// destination becomes the vault then, and has to be owned by the pool's
+    /// authority.
+    pub fn vesting(mut self, slots: u64, beneficiary: (Pubkey, Account), payer: &Pubkey) -> Self {
+        self.vesting = Some((slots, beneficiary, *payer));
+        self
+    }
+
+    pub fn max_slot_volume(mut self, max_slot_volume: u64) -> Self {
+        self.max_slot_volume = max_slot_volume;
+        self
+    }
+
+    pub fn fee_holiday_end_slot(mut self, fee_holiday_end_slot: u64) -> Self {
+        self.fee_holiday_end_slot = fee_holiday_end_slot;
+        self
+    }
+
+    pub fn build(self) -> Result<Instruction, ProgramError> {
+        let (authority, nonce) = self.authority();
+        let missing = || ProgramError::NotEnoughAccountKeys;
+        let (token_a_key, token_a) = self.token_a.as_ref().ok_or_else(missing)?;
+        let (token_b_key, token_b) = self.token_b.as_ref().ok_or_else(missing)?;
+        let (mint_a_key, mint_a) = self.mint_a.as_ref().ok_or_else(missing)?;
+        let (mint_b_key, mint_b) = self.mint_b.as_ref().ok_or_else(missing)?;
+        let (pool_mint_key, pool_mint) = self.pool_mint.as_ref().ok_or_else(missing)?;
+        let (fee_account_key, fee_account) = self.fee_account.as_ref().ok_or_else(missing)?;
+        let (destination_key, destination) = self.destination.as_ref().ok_or_else(missing)?;
+
+        if pool_mint.mint_authority != COption::Some(authority) {
+            return Err(SwapError::InvalidMintAuthority.into());
+        }
+        if pool_mint.supply != 0 {
+            return Err(SwapError::InvalidSupply.into());
+        }
+        if pool_mint.freeze_authority.is_some() {
+            return Err(SwapError::InvalidFreezeAuthority.into());
+        }
+
+        if mint_a_key == mint_b_key {
+            return Err(SwapError::RepeatedMint.into());
+        }
+        for (vault, mint_key, mint) in
+            [(token_a, mint_a_key, mint_a), (token_b, mint_b_key, mint_b)]
+        {
+            if vault.mint != *mint_key {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            // anyone else able to move or close the vault could take the reserves
+            if vault.owner != authority {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            if vault.delegate.is_some() {
+                return Err(SwapError::InvalidDelegate.into());
+            }
+            if vault.close_authority.is_some() {
+                return Err(SwapError::InvalidCloseAuthority.into());
+            }
+            if mint.decimals > MAX_TOKEN_DECIMALS {
+                return Err(SwapError::UnsupportedMintDecimals.into());
+            }
+        }
+
+        if fee_account.mint != *pool_mint_key || destination.mint != *pool_mint_key {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if destination.is_frozen() {
+            return Err(SwapError::AccountFrozen.into());
+        }
+        // without vesting nobody could move pool tokens minted to the authority
+        match &self.vesting {
+            Some((_, (_, beneficiary), _)) => {
+                if destination.owner != authority {
+                    return Err(SwapError::InvalidOwner.into());
+                }
+                if beneficiary.mint != *pool_mint_key {
+                    return Err(SwapError::IncorrectPoolMint.into());
+                }
+                if beneficiary.is_frozen() {
+                    return Err(SwapError::AccountFrozen.into());
+                }
+            }
+            None => {
+                if destination.owner == authority {
+                    return Err(SwapError::InvalidOutputOwner.into());
+                }
+            }
+        }
+
+        let (vesting_slots, beneficiary, payer) = match &self.vesting {
+            Some((slots, (beneficiary, _), payer)) => (*slots, Some(beneficiary), Some(payer)),
+            None => (0, None, None),
+        };
+        match self.parameters {
+            Some(PoolParameters::Custom(fees, swap_curve)) => {
+                fees.validate()?;
+                swap_curve.calculator.validate()?;
+                swap_curve
+                    .calculator
+                    .validate_supply(token_a.amount, token_b.amount)?;
+                instruction::initialize(
+                    &self.program_id,
+                    &self.token_program_id,
+                    &self.swap,
+                    &authority,
+                    token_a_key,
+                    token_b_key,
+                    pool_mint_key,
+                    fee_account_key,
+                    destination_key,
+                    mint_a_key,
+                    mint_b_key,
+                    nonce,
+                    fees,
+                    swap_curve,
+                    vesting_slots,
+                    self.max_slot_volume,
+                    self.fee_holiday_end_slot,
+                    beneficiary,
+                    payer,
+                )
+            }
+            Some(PoolParameters::Template(template)) => {
+                if !template.is_initialized {
+                    return Err(SwapError::InvalidPoolTemplate.into());
+                }
+                template
+                    .swap_curve
+                    .calculator
+                    .validate_supply(token_a.amount, token_b.amount)?;
+                instruction::initialize_from_template(
+                    &self.program_id,
+                    &self.token_program_id,
+                    &self.swap,
+                    &authority,
+                    token_a_key,
+                    token_b_key,
+                    pool_mint_key,
+                    fee_account_key,
+                    destination_key,
+                    mint_a_key,
+                    mint_b_key,
+                    InitializeFromTemplate {
+                        nonce,
+                        template: template.index,
+                        vesting_slots,
+                        max_slot_volume: self.max_slot_volume,
+                        fee_holiday_end_slot: self.fee_holiday_end_slot,
+                    },
+                    beneficiary,
+                    payer,
+                )
+            }
+            None => Err(SwapError::InvalidInstruction.into()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{curve::constant_product::ConstantProductCurve, instruction::SwapInstruction};
+
+    fn vault(mint: &Pubkey, owner: &Pubkey, amount: u64) -> Account {
+        Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Account::default()
+        }
+    }
+
+    fn mint(authority: Option<&Pubkey>, decimals: u8) -> Mint {
+        Mint {
+            mint_authority: authority.copied().into(),
+            decimals,
+            is_initialized: true,
+            ..Mint::default()
+        }
+    }
+
+    #[test]
+    fn pool_accounts_are_checked_before_sending() {
+        let (program_id, swap) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mint_a, mint_b, pool_mint) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let creator = Pubkey::new_unique();
+        let (authority, nonce) = InitializePoolBuilder::new(&program_id, &swap).authority();
+        let builder = |vault_owner: &Pubkey, pool_mint_authority: &Pubkey| {
+            InitializePoolBuilder::new(&program_id, &swap)
+                .token_a(
+                    (Pubkey::new_unique(), vault(&mint_a, vault_owner, 1_000)),
+                    (mint_a, mint(None, 6)),
+                )
+                .token_b(
+                    (Pubkey::new_unique(), vault(&mint_b, vault_owner, 2_000)),
+                    (mint_b, mint(None, 9)),
+                )
+                .pool_mint((pool_mint, mint(Some(pool_mint_authority), 6)))
+                .fee_account((Pubkey::new_unique(), vault(&pool_mint, &creator, 0)))
+                .destination((Pubkey::new_unique(), vault(&pool_mint, &creator, 0)))
+                .fees_and_curve(
+                    Fees::standard_30bps(),
+                    SwapCurve {
+                        calculator: Box::new(ConstantProductCurve {}),
+                        ..SwapCurve::default()
+                    },
+                )
+        };
+
+        let initialize = builder(&authority, &authority).build().unwrap();
+        assert_eq!(initialize.accounts[1].pubkey, authority);
+        match SwapInstruction::unpack(&initialize.data).unwrap() {
+            SwapInstruction::Initialize(initialize) => assert_eq!(initialize.nonce, nonce),
+            _ => panic!("not an initialize instruction"),
+        }
+
+        assert_eq!(
+            builder(&creator, &authority).build(),
+            Err(SwapError::InvalidOwner.into())
+        );
+        assert_eq!(
+            builder(&authority, &creator).build(),
+            Err(SwapError::InvalidMintAuthority.into())
+        );
+        assert_eq!(
+            builder(&authority, &authority)
+                .destination((Pubkey::new_unique(), vault(&mint_a, &creator, 0)))
+                .build(),
+            Err(SwapError::IncorrectPoolMint.into())
+        );
+        assert_eq!(
+            builder(&authority, &authority)
+                .template(PoolTemplate::default())
+                .build(),
+            Err(SwapError::InvalidPoolTemplate.into())
+        );
+        assert_eq!(
+            InitializePoolBuilder::new(&program_id, &swap).build(),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+    }
+}
@@ -0,0 +1,466 @@
+//! Display prices of a pool for frontends, behind the `client` feature.
+//!
+//! `pool_prices` quotes a pool with the program's own curve and fee math, the
+//! same checks and `SwapCurve::swap` a trade runs, so every UI quoting from
+//! here shows the numbers the chain enforces.  Prices are in raw token A per
+//! raw token B, the units of `ConstantPriceCurve::token_b_price`, and trade
+//! sizes in raw token B.
+//!
+//! `minimum_amounts` gives the smallest deposits and withdrawals that move
+//! any tokens, below which the program refuses with `AmountTooSmall`.
+//!
+//! `pool_token_price` values a pool's own pool token from its reserves, which
+//! is how a pool trading another pool's pool token as its token B, linked
+//! with `SetNestedPool`, gets priced in that other pool's token A.
+
+use crate::curve::{
+    base::SwapResult,
+    calculator::{RoundDirection, TradeDirection},
+    fees::{FeeTiers, Fees},
+};
+use crate::error::SwapError;
+use crate::state::SwapState;
+use serde::Serialize;
+use spl_math::precise_number::PreciseNumber;
+
+/// How far the price moves for `PoolPrices` depths, in basis points
+pub const DEPTH_BPS: u64 = 100;
+
+/// Fixed point scale prices are read out of the curve's math with
+const PRICE_SCALE: u128 = 1_000_000_000_000;
+
+/// What a frontend shows for a pool, prices in raw token A per raw token B
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct PoolPrices {
+    /// Marginal price before any trade, fees aside
+    pub mid_price: f64,
+    /// Token A received per token B selling the quoted size, fees included,
+    /// `None` if the pool can't fill it
+    pub bid: Option<f64>,
+    /// Token A paid per token B buying the quoted size, fees included, `None`
+    /// if the pool can't fill it
+    pub ask: Option<f64>,
+    /// Most token B a trade can sell before the price falls `DEPTH_BPS` below
+    /// the mid price
+    pub bid_depth: u64,
+    /// Most token A a trade can pay before the price rises `DEPTH_BPS` above
+    /// the mid price
+    pub ask_depth: u64,
+}
+
+/// Quotes a pool holding the given reserves at the given slot, buying and
+/// selling `size` token B.  `None` if the curve can't price the reserves.
+pub fn pool_prices(
+    pool: &dyn SwapState,
+    reserve_a: u64,
+    reserve_b: u64,
+    size: u64,
+    slot: u64,
+) -> Option<PoolPrices> {
+    let quoter = Quoter::new(pool, reserve_a, reserve_b, slot);
+    let mid_price = quoter.price(quoter.reserve_a, quoter.reserve_b)?;
+
+    let bid = quoter.swap(size, TradeDirection::BtoA).map(|result| {
+        result.destination_amount_swapped as f64 / result.source_amount_swapped as f64
+    });
+    // the least token A that buys at least `size` token B
+    let max_a_in = quoter.max_amount_in(TradeDirection::AtoB);
+    let buys_size = |amount_in| {
+        quoter
+            .swap(amount_in, TradeDirection::AtoB)
+            .is_some_and(|result| result.destination_amount_swapped >= u128::from(size))
+    };
+    let ask = if buys_size(max_a_in) {
+        let amount_in = last_true(max_a_in, |amount_in| !buys_size(amount_in)) + 1;
+        quoter.swap(amount_in, TradeDirection::AtoB).map(|result| {
+            result.source_amount_swapped as f64 / result.destination_amount_swapped as f64
+        })
+    } else {
+        None
+    };
+
+    let band = DEPTH_BPS as f64 / 10_000.0;
+    let bid_depth = quoter.depth(TradeDirection::BtoA, |price| {
+        price >= mid_price * (1.0 - band)
+    });
+    let ask_depth = quoter.depth(TradeDirection::AtoB, |price| {
+        price <= mid_price * (1.0 + band)
+    });
+
+    Some(PoolPrices {
+        mid_price,
+        bid,
+        ask,
+        bid_depth,
+        ask_depth,
+    })
+}
+
+/// Worth of one raw pool token of a pool holding the given reserves and pool
+/// token supply, in raw token A, its share of token B counted at the mid
+/// price.  `None` if the pool is empty or the curve can't price the reserves.
+pub fn pool_token_price(
+    pool: &dyn SwapState,
+    reserve_a: u64,
+    reserve_b: u64,
+    pool_supply: u64,
+) -> Option<f64> {
+    if pool_supply == 0 {
+        return None;
+    }
+    let quoter = Quoter::new(pool, reserve_a, reserve_b, 0);
+    let mid_price = quoter.price(quoter.reserve_a, quoter.reserve_b)?;
+    Some((reserve_a as f64 + reserve_b as f64 * mid_price) / pool_supply as f64)
+}
+
+/// Smallest deposits and withdrawals of a pool that move any tokens, for an
+/// account paying the pool's fees
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MinimumAmounts {
+    /// Least pool tokens a deposit of both tokens can ask for and pay some of
+    /// each
+    pub deposit_pool_tokens: u64,
+    /// Least token A a single-sided deposit takes to mint a pool token,
+    /// `None` if depositing as much again as the pool holds doesn't
+    pub deposit_single_token_a: Option<u64>,
+    /// Least token B a single-sided deposit takes to mint a pool token
+    pub deposit_single_token_b: Option<u64>,
+    /// Least pool tokens a withdrawal of both tokens burns to get some of
+    /// each, the withdraw fee included
+    pub withdraw_pool_tokens: u64,
+}
+
+/// The smallest amounts of a pool holding the given reserves and pool token
+/// supply that go through, with the program's rounding.  `EmptyPool` if the
+/// pool holds none of a token or has no pool tokens out.
+pub fn minimum_amounts(
+    pool: &dyn SwapState,
+    reserve_a: u64,
+    reserve_b: u64,
+    pool_supply: u64,
+) -> Result<MinimumAmounts, SwapError> {
+    if reserve_a == 0 || reserve_b == 0 || pool_supply == 0 {
+        return Err(SwapError::EmptyPool);
+    }
+    let swap_curve = pool.swap_curve();
+
+    // whether the pool tokens, less any withdraw fee, come to nothing of a
+    // token when rounded
+    let comes_to_nothing = |pool_tokens: u64, withdraw_fee: bool, round_direction| {
+        let pool_tokens = u128::from(pool_tokens);
+        let fee = if withdraw_fee {
+            pool.fees().owner_withdraw_fee(pool_tokens)
+        } else {
+            Some(0)
+        };
+        fee.and_then(|fee| pool_tokens.checked_sub(fee))
+            .and_then(|pool_tokens| {
+                swap_curve.calculator.pool_tokens_to_trading_tokens(
+                    pool_tokens,
+                    u128::from(pool_supply),
+                    u128::from(reserve_a),
+                    u128::from(reserve_b),
+                    round_direction,
+                )
+            })
+            .is_none_or(|results| results.token_a_amount == 0 || results.token_b_amount == 0)
+    };
+    let least_pool_tokens = |withdraw_fee, round_direction| {
+        if comes_to_nothing(pool_supply, withdraw_fee, round_direction) {
+            return Err(SwapError::CalculationFailure);
+        }
+        Ok(last_true(pool_supply, |pool_tokens| {
+            comes_to_nothing(pool_tokens, withdraw_fee, round_direction)
+        }) + 1)
+    };
+
+    let deposit_single = |trade_direction, reserve| {
+        let mints = |source_amount| {
+            swap_curve
+                .deposit_single_token_type(
+                    u128::from(source_amount),
+                    u128::from(reserve_a),
+                    u128::from(reserve_b),
+                    u128::from(pool_supply),
+                    trade_direction,
+                    pool.fees(),
+                )
+                .is_some_and(|pool_tokens| pool_tokens > 0)
+        };
+        if mints(reserve) {
+            Some(last_true(reserve, |source_amount| !mints(source_amount)) + 1)
+        } else {
+            None
+        }
+    };
+
+    Ok(MinimumAmounts {
+        deposit_pool_tokens: least_pool_tokens(false, RoundDirection::Ceiling)?,
+        deposit_single_token_a: deposit_single(TradeDirection::AtoB, reserve_a),
+        deposit_single_token_b: deposit_single(TradeDirection::BtoA, reserve_b),
+        withdraw_pool_tokens: least_pool_tokens(true, RoundDirection::Floor)?,
+    })
+}
+
+/// A pool's reserves and the fees a trade at the quoted slot pays
+pub(crate) struct Quoter<'a> {
+    pool: &'a dyn SwapState,
+    fees: Fees,
+    fee_tiers: FeeTiers,
+    reserve_a: u128,
+    reserve_b: u128,
+}
+
+impl<'a> Quoter<'a> {
+    pub(crate) fn new(pool: &'a dyn SwapState, reserve_a: u64, reserve_b: u64, slot: u64) -> Self {
+        Self {
+            pool,
+            fees: if pool.fee_holiday(slot) {
+                pool.fees().without_trade_fees()
+            } else {
+                pool.fees().clone()
+            },
+            fee_tiers: pool.fee_tiers(),
+            reserve_a: u128::from(reserve_a),
+            reserve_b: u128::from(reserve_b),
+        }
+    }
+
+    /// The trade the program would make, `None` where it refuses it
+    pub(crate) fn swap(
+        &self,
+        amount_in: u64,
+        trade_direction: TradeDirection,
+    ) -> Option<SwapResult> {
+        let (swap_source_amount, swap_destination_amount) = match trade_direction {
+            TradeDirection::AtoB => (self.reserve_a, self.reserve_b),
+            TradeDirection::BtoA => (self.reserve_b, self.reserve_a),
+        };
+        if self.pool.paused() {
+            return None;
+        }
+        let swap_curve = self.pool.swap_curve();
+        swap_curve.check_direction(trade_direction).ok()?;
+        swap_curve
+            .check_trade_size(
+                u128::from(amount_in),
+                swap_source_amount,
+                swap_destination_amount,
+                trade_direction,
+                &self.fees,
+                &self.fee_tiers,
+            )
+            .ok()?;
+        let result = swap_curve.swap(
+            u128::from(amount_in),
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            &self.fees,
+            &self.fee_tiers,
+        )?;
+        if self.pool.exact_input() {
+            result.with_exact_input(u128::from(amount_in))
+        } else {
+            Some(result)
+        }
+    }
+
+    /// Marginal price of the curve at the given reserves
+    fn price(&self, reserve_a: u128, reserve_b: u128) -> Option<f64> {
+        let price = self.pool.swap_curve().calculator.spot_price(
+            reserve_b,
+            reserve_a,
+            TradeDirection::BtoA,
+        )?;
+        let scaled = price
+            .checked_mul(&PreciseNumber::new(PRICE_SCALE)?)?
+            .floor()?
+            .to_imprecise()?;
+        Some(scaled as f64 / PRICE_SCALE as f64)
+    }
+
+    /// Largest amount in the program doesn't refuse as too large to fill,
+    /// none in a direction the curve doesn't trade or while the pool is
+    /// paused
+    fn max_amount_in(&self, trade_direction: TradeDirection) -> u64 {
+        let (swap_source_amount, swap_destination_amount) = match trade_direction {
+            TradeDirection::AtoB => (self.reserve_a, self.reserve_b),
+            TradeDirection::BtoA => (self.reserve_b, self.reserve_a),
+        };
+        if self.pool.paused()
+            || self
+                .pool
+                .swap_curve()
+                .check_direction(trade_direction)
+                .is_err()
+        {
+            return 0;
+        }
+        last_true(u64::MAX, |amount_in| {
+            self.pool
+                .swap_curve()
+                .check_trade_size(
+                    u128::from(amount_in),
+                    swap_source_amount,
+                    swap_destination_amount,
+                    trade_direction,
+                    &self.fees,
+                    &self.fee_tiers,
+                )
+                .is_ok()
+        })
+    }
+
+    /// Largest trade leaving the price within the band, trades too small to
+    /// go through leaving it where it is
+    fn depth(&self, trade_direction: TradeDirection, in_band: impl Fn(f64) -> bool) -> u64 {
+        let leaves_in_band = |amount_in| {
+            let result = match self.swap(amount_in, trade_direction) {
+                Some(result) => result,
+                None => return true,
+            };
+            let (reserve_a, reserve_b) = match trade_direction {
+                TradeDirection::AtoB => (
+                    result.new_swap_source_amount,
+                    result.new_swap_destination_amount,
+                ),
+                TradeDirection::BtoA => (
+                    result.new_swap_destination_amount,
+                    result.new_swap_source_amount,
+                ),
+            };
+            self.price(reserve_a, reserve_b).is_some_and(&in_band)
+        };
+        last_true(self.max_amount_in(trade_direction), leaves_in_band)
+    }
+}
+
+/// Largest amount up to `high` the predicate holds for, which holds at 0 and
+/// stops holding at most once
+fn last_true(high: u64, predicate: impl Fn(u64) -> bool) -> u64 {
+    let (mut low, mut high) = (0, high);
+    while low < high {
+        let middle = low + (high - low) / 2 + 1;
+        if predicate(middle) {
+            low = middle;
+        } else {
+            high = middle - 1;
+        }
+    }
+    low
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{
+        base::{CurveType, SwapCurve},
+        constant_price::ConstantPriceCurve,
+        constant_product::ConstantProductCurve,
+    };
+    use crate::state::SwapV2;
+
+    #[test]
+    fn prices_match_the_curve() {
+        let reserve = 1_000_000_000_000;
+        let pool = SwapV2 {
+            fees: Fees::standard_30bps(),
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve),
+            },
+            ..SwapV2::default()
+        };
+        let prices = pool_prices(&pool, reserve, reserve, 1_000_000, 0).unwrap();
+        assert_eq!(prices.mid_price, 1.0);
+        // the spread is the 0.3% fee each way
+        let (bid, ask) = (prices.bid.unwrap(), prices.ask.unwrap());
+        assert!(bid < 1.0 && bid > 0.996);
+        assert!(ask > 1.0 && ask < 1.004);
+        // moving x * y = k by 1% takes about half a percent of the reserves
+        for depth in [prices.bid_depth, prices.ask_depth] {
+            assert!(depth > reserve / 250 && depth < reserve / 150);
+        }
+
+        let pool = SwapV2 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve {
+                    token_b_price: 7,
+                    token_b_price_denominator: 0,
+                }),
+            },
+            ..pool
+        };
+        let prices = pool_prices(&pool, 7 * reserve, reserve, 1_000_000, 0).unwrap();
+        assert_eq!(prices.mid_price, 7.0);
+        // the price never moves, only the reserves limit the depth
+        assert!(prices.bid_depth >= reserve);
+        assert!(prices.ask_depth >= 7 * reserve);
+        // nobody can buy more token B than the pool holds
+        let prices = pool_prices(&pool, 7 * reserve, reserve, reserve + 1, 0).unwrap();
+        assert_eq!(prices.ask, None);
+    }
+
+    #[test]
+    fn minimums_are_the_least_that_moves_tokens() {
+        let pool = SwapV2 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve),
+            },
+            ..SwapV2::default()
+        };
+        // a pool token is worth a hundredth of a token
+        let minimums = minimum_amounts(&pool, 10, 20, 1_000).unwrap();
+        assert_eq!(minimums.deposit_pool_tokens, 100);
+        assert_eq!(minimums.withdraw_pool_tokens, 100);
+        let deposit_single = minimums.deposit_single_token_a.unwrap();
+        let minted = |source_amount| {
+            pool.swap_curve
+                .deposit_single_token_type(
+                    source_amount,
+                    10,
+                    20,
+                    1_000,
+                    TradeDirection::AtoB,
+                    &pool.fees,
+                )
+                .unwrap()
+        };
+        assert!(minted(u128::from(deposit_single)) > 0);
+        assert_eq!(minted(u128::from(deposit_single) - 1), 0);
+
+        assert_eq!(
+            minimum_amounts(&pool, 0, 20, 1_000),
+            Err(SwapError::EmptyPool)
+        );
+    }
+
+    #[test]
+    fn pool_tokens_are_worth_their_share_of_the_reserves() {
+        let pool = SwapV2 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantProduct,
+                calculator: Box::new(ConstantProductCurve),
+            },
+            ..SwapV2::default()
+        };
+        // token B at 4 A, so the reserves come to 8_000 A over 1_000 pool tokens
+        assert_eq!(pool_token_price(&pool, 4_000, 1_000, 1_000), Some(8.0));
+        assert_eq!(pool_token_price(&pool, 4_000, 1_000, 0), None);
+
+        let pool = SwapV2 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve {
+                    token_b_price: 3,
+                    token_b_price_denominator: 0,
+                }),
+            },
+            ..pool
+        };
+        assert_eq!(pool_token_price(&pool, 0, 1_000, 500), Some(6.0));
+    }
+}
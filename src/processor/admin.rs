@@ -0,0 +1,2154 @@
+//! Instructions of the pool admin, and the cranks keeping pools priced.
+
+use crate::amount::PoolTokenAmount;
+use crate::constants::{
+    MAX_ADMIN_SIGNERS, MAX_FEE_EXEMPT, MAX_FEE_TIERS, MAX_NAME_LEN, MAX_URI_LEN, METADATA_SEED,
+    ORACLE_SEED, POOL_TEMPLATE_SEED, PROGRAM_CONFIG_SEED, SNAPSHOT_SEED, TAGS_LEN,
+};
+use crate::constraints::SwapConstraints;
+use crate::curve::base::{CurveType, SwapCurve};
+use crate::curve::fees::{FeeTiers, Fees};
+use crate::error::SwapError;
+use crate::events::{
+    AdminLiquidityMovedEvent, AdminMultisigInitializedEvent, BondedKeepersOnlySetEvent,
+    CurveReinitializedEvent, DefaultHostSetEvent, DepositBonusSetEvent, EmergencySetEvent, Event,
+    ExactInputSetEvent, FeeAccountSetEvent, FeeConversionSetEvent, FeeExemptSetEvent,
+    FeeTiersSetEvent, GateSetEvent, GovernanceProgramSetEvent, LiquiditySnapshotConfiguredEvent,
+    LiquiditySnapshotFinalizedEvent, NestedPoolSetEvent, OwnerFeeAccountsSetEvent,
+    OwnerFeeFallbackSetEvent, PausedSetEvent, PoolMetadataSetEvent, PoolTemplateSetEvent,
+    RebalancerSetEvent, SandwichGuardSetEvent, SingleWithdrawFeeToLpsSetEvent, StakePoolSetEvent,
+    SwapAccountResizedEvent, UpgradeAuthorityDocumentedEvent, WithdrawCooldownSetEvent,
+    WithdrawFeeDestinationSetEvent,
+};
+use crate::state::{
+    stake_pool_program, AdminMultisig, LiquiditySnapshot, OracleConfig, PoolMetadata, PoolTemplate,
+    ProgramConfig, StakePoolRate, SwapState, SwapV2, SwapVersion,
+};
+use crate::token_ops;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program::{invoke, invoke_signed};
+use solana_program::program_error::ProgramError;
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_instruction;
+use solana_program::sysvar::{clock::Clock, rent::Rent, Sysvar};
+
+use super::Processor;
+
+impl Processor {
+    // swaps the curve of a pool nobody else has a stake in, keeping its address
+    pub fn process_reinitialize_curve(
+        program_id: &Pubkey,
+        swap_curve: SwapCurve,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?; //owner of the pool fee account
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_holder_info = next_account_info(account_info_iter)?; //receives the new initial supply
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        let token_program_id = *token_swap.token_program_id();
+
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        if *token_a_info.key != *token_swap.token_a_account()
+            || *token_b_info.key != *token_swap.token_b_account()
+        {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if *token_program_info.key != token_program_id {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+
+        let fee_account = Self::unpack_token_account(pool_fee_account_info, &token_program_id)?;
+        Self::check_admin(program_id, admin_info, &fee_account.owner, accounts)?;
+
+        let token_a = Self::unpack_token_account(token_a_info, &token_program_id)?;
+        let token_b = Self::unpack_token_account(token_b_info, &token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_program_id)?;
+        let pool_holder = Self::unpack_user_token_account(pool_holder_info, &token_program_id)?;
+        if pool_holder.mint != *pool_mint_info.key {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+
+        // nobody can be affected by the new curve: with pool tokens out, even
+        // ones the admin holds could be locked or vesting for someone else
+        if pool_mint.supply > 0 {
+            return Err(SwapError::InvalidSupply.into());
+        }
+
+        // same checks as initialize
+        swap_curve
+            .calculator
+            .validate_supply(token_a.amount, token_b.amount)?;
+        if let Some(swap_constraints) = swap_constraints {
+            swap_constraints.validate_curve(&swap_curve)?;
+        }
+        swap_curve.calculator.validate()?;
+
+        token_ops::mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            pool_holder_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            PoolTokenAmount::new(swap_curve.calculator.new_pool_supply()).to_u64()?,
+        )?;
+
+        Event::CurveReinitialized(CurveReinitializedEvent {
+            swap: *swap_info.key,
+            curve_type: swap_curve.curve_type as u8,
+        })
+        .emit();
+
+        token_swap.set_swap_curve(swap_curve);
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    // soft-pegs a constant price pool: the admin picks the oracle and how far and fast the crank can move the price
+    pub fn process_set_oracle_config(
+        program_id: &Pubkey,
+        price_offset: u32,
+        min_price: u64,
+        max_price: u64,
+        max_step_bps: u16,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?; //owner of the pool fee account
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let oracle_config_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let fee_account =
+            Self::unpack_token_account(pool_fee_account_info, token_swap.token_program_id())?;
+        Self::check_admin(program_id, admin_info, &fee_account.owner, accounts)?;
+        // a pool pegged to a stake pool already has its price source
+        if token_swap.swap_curve().curve_type != CurveType::ConstantPrice
+            || token_swap.stake_pool().is_some()
+        {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        if min_price == 0 || min_price > max_price || max_step_bps == 0 || max_step_bps > 10_000 {
+            return Err(SwapError::InvalidCurve.into());
+        }
+
+        let (oracle_config_key, bump_seed) = OracleConfig::find_address(swap_info.key, program_id);
+        if *oracle_config_info.key != oracle_config_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if oracle_config_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    oracle_config_info.key,
+                    rent.minimum_balance(OracleConfig::LEN),
+                    OracleConfig::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    oracle_config_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[ORACLE_SEED, swap_info.key.as_ref(), &[bump_seed]]],
+            )?;
+        } else if oracle_config_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+
+        let oracle_config = OracleConfig {
+            is_initialized: true,
+            bump_seed,
+            swap: *swap_info.key,
+            oracle: *oracle_info.key,
+            price_offset,
+            min_price,
+            max_price,
+            max_step_bps,
+            last_update_slot: Clock::get()?.slot,
+        };
+        // reject an oracle we can't read straight away, rather than on the first crank
+        oracle_config
+            .read_price(&oracle_info.data.borrow())
+            .ok_or(SwapError::InvalidOracle)?;
+        OracleConfig::pack(oracle_config, &mut oracle_config_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    // migrations: the pool admin moves liquidity at whatever the current ratio is, with no slippage bounds
+    pub fn process_admin_move_liquidity(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        deposit: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let admin_info = next_account_info(account_info_iter)?; //owner of the pool fee account
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let swap_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let fee_account =
+            Self::unpack_token_account(pool_fee_account_info, token_swap.token_program_id())?;
+        Self::check_admin(program_id, admin_info, &fee_account.owner, accounts)?;
+
+        let accounts = &accounts[2..];
+        if deposit {
+            Self::process_deposit_all_token_types(
+                program_id,
+                pool_token_amount,
+                u64::MAX,
+                u64::MAX,
+                accounts,
+            )?;
+        } else {
+            Self::process_withdraw_all_token_types(program_id, pool_token_amount, 0, 0, accounts)?;
+        }
+
+        Event::AdminLiquidityMoved(AdminLiquidityMovedEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            deposit,
+            pool_token_amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin designates, or removes, the key allowed to rebalance without the owner fee
+    pub fn process_set_rebalancer(
+        program_id: &Pubkey,
+        rebalancer: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.rebalancer = rebalancer,
+            // V1 pools have no room to record a rebalancer
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::RebalancerSet(RebalancerSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            rebalancer,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin sets, or removes, who earns the host fee of swaps without a host
+    pub fn process_set_default_host(
+        program_id: &Pubkey,
+        host_fee_account: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+        if host_fee_account != Pubkey::default() {
+            let host_fee_account_info = next_account_info(account_info_iter)?;
+            if *host_fee_account_info.key != host_fee_account {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+            let host_fee_account = Self::unpack_user_token_account(
+                host_fee_account_info,
+                token_swap.token_program_id(),
+            )?;
+            if host_fee_account.mint != *token_swap.pool_mint() {
+                return Err(SwapError::IncorrectPoolMint.into());
+            }
+        }
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.default_host_fee_account = host_fee_account,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::DefaultHostSet(DefaultHostSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            host_fee_account,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // rotates the fee account, and with it the pool admin
+    pub fn process_set_fee_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let new_pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        // same requirements as the fee account given at initialization
+        let new_fee_account =
+            Self::unpack_token_account(new_pool_fee_account_info, token_swap.token_program_id())?;
+        if new_fee_account.mint != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        // fees paid to the pool's own authority could never be claimed
+        if new_fee_account.owner
+            == Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if new_fee_account.owner != owner_key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+        }
+
+        match &mut token_swap {
+            SwapVersion::SwapV1(swap_v1) => {
+                swap_v1.pool_fee_account = *new_pool_fee_account_info.key
+            }
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.pool_fee_account = *new_pool_fee_account_info.key
+            }
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::FeeAccountSet(FeeAccountSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            old_fee_account: *pool_fee_account_info.key,
+            new_fee_account: *new_pool_fee_account_info.key,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin splits the owner fee by trade direction, or merges it back
+    pub fn process_set_owner_fee_accounts(
+        program_id: &Pubkey,
+        owner_fee_account_a: Pubkey,
+        owner_fee_account_b: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+        let authority = Self::authority_id(program_id, swap_info.key, token_swap.nonce())?;
+        for owner_fee_account in [owner_fee_account_a, owner_fee_account_b] {
+            if owner_fee_account == Pubkey::default() {
+                continue;
+            }
+            let owner_fee_account_info = next_account_info(account_info_iter)?;
+            if *owner_fee_account_info.key != owner_fee_account {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+            let owner_fee_account = Self::unpack_user_token_account(
+                owner_fee_account_info,
+                token_swap.token_program_id(),
+            )?;
+            if owner_fee_account.mint != *token_swap.pool_mint() {
+                return Err(SwapError::IncorrectPoolMint.into());
+            }
+            // fees paid to the pool's own authority could never be claimed
+            if owner_fee_account.owner == authority {
+                return Err(SwapError::InvalidOwner.into());
+            }
+        }
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.owner_fee_account_a = owner_fee_account_a;
+                swap_v2.owner_fee_account_b = owner_fee_account_b;
+            }
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::OwnerFeeAccountsSet(OwnerFeeAccountsSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            owner_fee_account_a,
+            owner_fee_account_b,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin has the owner fee sold for a fee mint through another
+    // pool, or minted as pool tokens again
+    pub fn process_set_fee_conversion(
+        program_id: &Pubkey,
+        fee_conversion_pool: Pubkey,
+        fee_conversion_account: Pubkey,
+        max_slippage_bps: u16,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        if max_slippage_bps > 10_000 {
+            return Err(SwapError::InvalidInstruction.into());
+        }
+        let mut fee_conversion_from_token_b = false;
+        if fee_conversion_pool != Pubkey::default() {
+            let fee_conversion_pool_info = next_account_info(account_info_iter)?;
+            let fee_conversion_account_info = next_account_info(account_info_iter)?;
+            if *fee_conversion_pool_info.key != fee_conversion_pool
+                || fee_conversion_pool_info.owner != program_id
+                || fee_conversion_pool == *swap_info.key
+            {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            let conversion_swap = SwapVersion::unpack(&fee_conversion_pool_info.data.borrow())?;
+            if conversion_swap.token_program_id() != token_swap.token_program_id() {
+                return Err(SwapError::IncorrectTokenProgramId.into());
+            }
+            // it trades exactly one of the pool's tokens, for the fee mint
+            let trades = |mint: &Pubkey| {
+                conversion_swap.token_a_mint() == mint || conversion_swap.token_b_mint() == mint
+            };
+            let shared_mint = match (
+                trades(token_swap.token_a_mint()),
+                trades(token_swap.token_b_mint()),
+            ) {
+                (true, false) => token_swap.token_a_mint(),
+                (false, true) => {
+                    fee_conversion_from_token_b = true;
+                    token_swap.token_b_mint()
+                }
+                _ => return Err(SwapError::IncorrectSwapAccount.into()),
+            };
+            let fee_mint = if conversion_swap.token_a_mint() == shared_mint {
+                conversion_swap.token_b_mint()
+            } else {
+                conversion_swap.token_a_mint()
+            };
+
+            if *fee_conversion_account_info.key != fee_conversion_account {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+            let fee_conversion_account = Self::unpack_user_token_account(
+                fee_conversion_account_info,
+                token_swap.token_program_id(),
+            )?;
+            if fee_conversion_account.mint != *fee_mint {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+            // fees paid to either pool's own authority could never be claimed
+            let authority = Self::authority_id(program_id, swap_info.key, token_swap.nonce())?;
+            let conversion_authority = Self::authority_id(
+                program_id,
+                fee_conversion_pool_info.key,
+                conversion_swap.nonce(),
+            )?;
+            if fee_conversion_account.owner == authority
+                || fee_conversion_account.owner == conversion_authority
+            {
+                return Err(SwapError::InvalidOwner.into());
+            }
+        }
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.fee_conversion_pool = fee_conversion_pool;
+                swap_v2.fee_conversion_account = fee_conversion_account;
+                swap_v2.fee_conversion_from_token_b = fee_conversion_from_token_b;
+                swap_v2.fee_conversion_max_slippage_bps = max_slippage_bps;
+            }
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::FeeConversionSet(FeeConversionSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            fee_conversion_pool,
+            fee_conversion_account,
+            fee_conversion_from_token_b,
+            max_slippage_bps,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // grows a pool made by an older version of the program to the latest
+    // layout, migrating V1 pools to V2, without moving it to a new address
+    pub fn process_resize_swap_account(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack_outdated(&swap_info.data.borrow())?;
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let fee_account =
+            Self::unpack_token_account(pool_fee_account_info, token_swap.token_program_id())?;
+        Self::check_admin(program_id, admin_info, &fee_account.owner, accounts)?;
+
+        let (old_version, swap_v2) = match token_swap {
+            SwapVersion::SwapV1(swap_v1) => {
+                let pool_mint_info = next_account_info(account_info_iter)?;
+                let token_a_mint_info = next_account_info(account_info_iter)?;
+                let token_b_mint_info = next_account_info(account_info_iter)?;
+                if *pool_mint_info.key != swap_v1.pool_mint {
+                    return Err(SwapError::IncorrectPoolMint.into());
+                }
+                if *token_a_mint_info.key != swap_v1.token_a_mint
+                    || *token_b_mint_info.key != swap_v1.token_b_mint
+                {
+                    return Err(SwapError::IncorrectSwapAccount.into());
+                }
+                let token_program_id = swap_v1.token_program_id;
+                let pool_mint = Self::unpack_mint(pool_mint_info, &token_program_id)?;
+                let token_a_mint = Self::unpack_mint(token_a_mint_info, &token_program_id)?;
+                let token_b_mint = Self::unpack_mint(token_b_mint_info, &token_program_id)?;
+                (
+                    1,
+                    Box::new(SwapV2::from_v1(
+                        *swap_v1,
+                        pool_mint.decimals,
+                        token_a_mint.decimals,
+                        token_b_mint.decimals,
+                    )),
+                )
+            }
+            SwapVersion::SwapV2(swap_v2) => (2, swap_v2),
+        };
+
+        // stays rent exempt at its new size
+        let old_len = swap_info.data_len();
+        let top_up = Rent::get()?
+            .minimum_balance(SwapVersion::LATEST_LEN)
+            .saturating_sub(swap_info.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, swap_info.key, top_up),
+                &[
+                    payer_info.clone(),
+                    swap_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        if old_len != SwapVersion::LATEST_LEN {
+            swap_info.realloc(SwapVersion::LATEST_LEN, true)?;
+        }
+        SwapVersion::pack(
+            SwapVersion::SwapV2(swap_v2),
+            &mut swap_info.data.borrow_mut(),
+        )?;
+
+        Event::SwapAccountResized(SwapAccountResizedEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            old_len: old_len as u64,
+            new_len: SwapVersion::LATEST_LEN as u64,
+            old_version,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // creates or overwrites the pool's metadata, so explorers can label it
+    pub fn process_set_pool_metadata(
+        program_id: &Pubkey,
+        name: [u8; MAX_NAME_LEN],
+        uri: [u8; MAX_URI_LEN],
+        tags: [u8; TAGS_LEN],
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?; //owner of the pool fee account
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let metadata_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let fee_account =
+            Self::unpack_token_account(pool_fee_account_info, token_swap.token_program_id())?;
+        Self::check_admin(program_id, admin_info, &fee_account.owner, accounts)?;
+
+        let (metadata_key, bump_seed) = PoolMetadata::find_address(swap_info.key, program_id);
+        if *metadata_info.key != metadata_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let metadata = PoolMetadata {
+            is_initialized: true,
+            bump_seed,
+            swap: *swap_info.key,
+            name,
+            uri,
+            tags,
+        };
+        // explorers display these as text
+        if metadata.name().is_none() || metadata.uri().is_none() {
+            return Err(SwapError::InvalidInstruction.into());
+        }
+
+        if metadata_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    metadata_info.key,
+                    rent.minimum_balance(PoolMetadata::LEN),
+                    PoolMetadata::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    metadata_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[METADATA_SEED, swap_info.key.as_ref(), &[bump_seed]]],
+            )?;
+        } else if metadata_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        PoolMetadata::pack(metadata, &mut metadata_info.data.borrow_mut())?;
+
+        Event::PoolMetadataSet(PoolMetadataSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_set_emergency(
+        program_id: &Pubkey,
+        enabled: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.emergency = enabled,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::EmergencySet(EmergencySetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            enabled,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // halts trades and deposits during an incident, withdrawals stay open
+    pub fn process_set_paused(
+        program_id: &Pubkey,
+        paused: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.paused = paused,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::PausedSet(PausedSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            paused,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_set_deposit_bonus(
+        program_id: &Pubkey,
+        start_slot: u64,
+        end_slot: u64,
+        bonus_bps: u16,
+        lockup_slots: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+        // a bonus on locks of no duration could be farmed by locking and
+        // unlocking again
+        if start_slot > end_slot || bonus_bps > 10_000 || (bonus_bps > 0 && lockup_slots == 0) {
+            return Err(SwapError::InvalidInstruction.into());
+        }
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.bonus_start_slot = start_slot;
+                swap_v2.bonus_end_slot = end_slot;
+                swap_v2.bonus_bps = bonus_bps;
+                swap_v2.bonus_lockup_slots = lockup_slots;
+            }
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::DepositBonusSet(DepositBonusSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            start_slot,
+            end_slot,
+            bonus_bps,
+            lockup_slots,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_set_fee_exempt(
+        program_id: &Pubkey,
+        fee_exempt: [Pubkey; MAX_FEE_EXEMPT],
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.fee_exempt = fee_exempt,
+            // V1 pools only exempt the pool fee account
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::FeeExemptSet(FeeExemptSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            fee_exempt,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // lets anyone see who can upgrade the program, without trusting off-chain docs
+    pub fn process_document_upgrade_authority(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let program_data_info = next_account_info(account_info_iter)?;
+        let upgrade_authority_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let upgrade_authority = Self::upgrade_authority(program_id, program_data_info)?;
+        if let Some(upgrade_authority) = upgrade_authority {
+            if *upgrade_authority_info.key != upgrade_authority || !upgrade_authority_info.is_signer
+            {
+                return Err(SwapError::InvalidOwner.into());
+            }
+        }
+
+        let (program_config_key, bump_seed) = ProgramConfig::find_address(program_id);
+        if *program_config_info.key != program_config_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if program_config_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    program_config_info.key,
+                    rent.minimum_balance(ProgramConfig::LEN),
+                    ProgramConfig::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    program_config_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[PROGRAM_CONFIG_SEED, &[bump_seed]]],
+            )?;
+        } else if program_config_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        } else {
+            Self::resize_program_config(program_config_info, payer_info, system_program_info)?;
+        }
+
+        let governance_program =
+            match ProgramConfig::unpack_outdated(&program_config_info.data.borrow()) {
+                Ok(program_config) => program_config.governance_program,
+                Err(_) => Pubkey::default(),
+            };
+        let upgrade_authority = upgrade_authority.unwrap_or_default();
+        let program_config = ProgramConfig {
+            is_initialized: true,
+            bump_seed,
+            upgrade_authority,
+            governance_program,
+        };
+        ProgramConfig::pack(program_config, &mut program_config_info.data.borrow_mut())?;
+
+        Event::UpgradeAuthorityDocumented(UpgradeAuthorityDocumentedEvent { upgrade_authority })
+            .emit();
+        Ok(())
+    }
+
+    // configs written before the last fields were added grow to the current
+    // size, topped up to stay rent exempt
+    fn resize_program_config<'a>(
+        program_config_info: &AccountInfo<'a>,
+        payer_info: &AccountInfo<'a>,
+        system_program_info: &AccountInfo<'a>,
+    ) -> ProgramResult {
+        if program_config_info.data_len() >= ProgramConfig::LEN {
+            return Ok(());
+        }
+        let top_up = Rent::get()?
+            .minimum_balance(ProgramConfig::LEN)
+            .saturating_sub(program_config_info.lamports());
+        if top_up > 0 {
+            invoke(
+                &system_instruction::transfer(payer_info.key, program_config_info.key, top_up),
+                &[
+                    payer_info.clone(),
+                    program_config_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+        }
+        program_config_info.realloc(ProgramConfig::LEN, true)
+    }
+
+    // lets pools governed by a realm of this governance program take admin
+    // instructions signed by the governance's native treasury
+    pub fn process_set_governance_program(
+        program_id: &Pubkey,
+        governance_program: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let program_config_info = next_account_info(account_info_iter)?;
+        let upgrade_authority_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (program_config_key, _bump_seed) = ProgramConfig::find_address(program_id);
+        if *program_config_info.key != program_config_key || program_config_info.owner != program_id
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let mut program_config =
+            ProgramConfig::unpack_outdated(&program_config_info.data.borrow())?;
+        // an immutable program has no one left to trust with its admins
+        if program_config.upgrade_authority == Pubkey::default()
+            || program_config.upgrade_authority != *upgrade_authority_info.key
+            || !upgrade_authority_info.is_signer
+        {
+            return Err(SwapError::InvalidOwner.into());
+        }
+
+        Self::resize_program_config(program_config_info, payer_info, system_program_info)?;
+        program_config.governance_program = governance_program;
+        ProgramConfig::pack(program_config, &mut program_config_info.data.borrow_mut())?;
+
+        Event::GovernanceProgramSet(GovernanceProgramSetEvent {
+            upgrade_authority: *upgrade_authority_info.key,
+            governance_program,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // vets the fees and curve new pools can be created from by index
+    pub fn process_set_pool_template(
+        program_id: &Pubkey,
+        index: u8,
+        fees: Fees,
+        swap_curve: SwapCurve,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let template_info = next_account_info(account_info_iter)?;
+        let program_config_info = next_account_info(account_info_iter)?;
+        let upgrade_authority_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (program_config_key, _bump_seed) = ProgramConfig::find_address(program_id);
+        if *program_config_info.key != program_config_key || program_config_info.owner != program_id
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let program_config = ProgramConfig::unpack_outdated(&program_config_info.data.borrow())?;
+        // an immutable program keeps the templates it has
+        if program_config.upgrade_authority == Pubkey::default()
+            || program_config.upgrade_authority != *upgrade_authority_info.key
+            || !upgrade_authority_info.is_signer
+        {
+            return Err(SwapError::InvalidOwner.into());
+        }
+
+        // same checks as initialize, bar the accounts
+        if let Some(swap_constraints) = swap_constraints {
+            swap_constraints.validate_curve(&swap_curve)?;
+            swap_constraints.validate_fees(&fees)?;
+        }
+        fees.validate()?;
+        swap_curve.calculator.validate()?;
+
+        let (template_key, bump_seed) = PoolTemplate::find_address(index, program_id);
+        if *template_info.key != template_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if template_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    template_info.key,
+                    rent.minimum_balance(PoolTemplate::LEN),
+                    PoolTemplate::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    template_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[POOL_TEMPLATE_SEED, &[index], &[bump_seed]]],
+            )?;
+        } else if template_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+
+        let template = PoolTemplate {
+            is_initialized: true,
+            bump_seed,
+            index,
+            fees,
+            swap_curve,
+        };
+        PoolTemplate::pack(template, &mut template_info.data.borrow_mut())?;
+
+        Event::PoolTemplateSet(PoolTemplateSetEvent {
+            upgrade_authority: *upgrade_authority_info.key,
+            template: *template_info.key,
+            index,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_set_withdraw_fee_destination(
+        program_id: &Pubkey,
+        underlying: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        let (fee_vault_a, fee_vault_b) = if underlying {
+            let fee_vault_a_info = next_account_info(account_info_iter)?;
+            let fee_vault_b_info = next_account_info(account_info_iter)?;
+            let fee_vault_a =
+                Self::unpack_token_account(fee_vault_a_info, token_swap.token_program_id())?;
+            let fee_vault_b =
+                Self::unpack_token_account(fee_vault_b_info, token_swap.token_program_id())?;
+            if fee_vault_a.mint != *token_swap.token_a_mint()
+                || fee_vault_b.mint != *token_swap.token_b_mint()
+            {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+            (*fee_vault_a_info.key, *fee_vault_b_info.key)
+        } else {
+            (Pubkey::default(), Pubkey::default())
+        };
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.withdraw_fee_in_underlying = underlying;
+                swap_v2.withdraw_fee_vault_a = fee_vault_a;
+                swap_v2.withdraw_fee_vault_b = fee_vault_b;
+            }
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::WithdrawFeeDestinationSet(WithdrawFeeDestinationSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            fee_vault_a,
+            fee_vault_b,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_set_fee_tiers(
+        program_id: &Pubkey,
+        fee_tiers: FeeTiers,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+        fee_tiers.validate(token_swap.fees())?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.fee_tiers = fee_tiers,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        let mut event = FeeTiersSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            thresholds: [0; MAX_FEE_TIERS],
+            trade_fee_numerators: [0; MAX_FEE_TIERS],
+            owner_trade_fee_numerators: [0; MAX_FEE_TIERS],
+        };
+        for (i, tier) in fee_tiers.tiers.iter().enumerate() {
+            event.thresholds[i] = tier.threshold;
+            event.trade_fee_numerators[i] = tier.trade_fee_numerator;
+            event.owner_trade_fee_numerators[i] = tier.owner_trade_fee_numerator;
+        }
+        Event::FeeTiersSet(event).emit();
+        Ok(())
+    }
+
+    pub fn process_set_exact_input(
+        program_id: &Pubkey,
+        exact_input: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.exact_input = exact_input,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::ExactInputSet(ExactInputSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            exact_input,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // writes an M-of-N signer list into an account the program owns, to own pool fee accounts
+    pub fn process_initialize_admin_multisig(
+        program_id: &Pubkey,
+        m: u8,
+        signers: [Pubkey; MAX_ADMIN_SIGNERS],
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let multisig_info = next_account_info(account_info_iter)?;
+
+        if multisig_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if multisig_info.data_len() != AdminMultisig::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if AdminMultisig::unpack_unchecked(&multisig_info.data.borrow())?.is_initialized {
+            return Err(SwapError::AlreadyInUse.into());
+        }
+
+        // the signers fill the first slots, each once
+        let n = signers
+            .iter()
+            .take_while(|signer| **signer != Pubkey::default())
+            .count();
+        if signers[n..]
+            .iter()
+            .any(|signer| *signer != Pubkey::default())
+            || (1..n).any(|i| signers[..i].contains(&signers[i]))
+            || m == 0
+            || usize::from(m) > n
+        {
+            return Err(SwapError::InvalidInstruction.into());
+        }
+
+        let multisig = AdminMultisig {
+            is_initialized: true,
+            m,
+            n: n as u8,
+            signers,
+        };
+        AdminMultisig::pack(multisig, &mut multisig_info.data.borrow_mut())?;
+
+        Event::AdminMultisigInitialized(AdminMultisigInitializedEvent {
+            multisig: *multisig_info.key,
+            m,
+            signers: signers[..n].to_vec(),
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin sets how long single-sided deposits wait before withdrawing single-sided
+    pub fn process_set_withdraw_cooldown(
+        program_id: &Pubkey,
+        cooldown_slots: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.withdraw_cooldown_slots = cooldown_slots,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::WithdrawCooldownSet(WithdrawCooldownSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            cooldown_slots,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin leaves the owner's fee on single-sided withdrawals in
+    // the pool for the remaining LPs, or pays it to the owner again
+    pub fn process_set_single_withdraw_fee_to_lps(
+        program_id: &Pubkey,
+        to_lps: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.single_withdraw_fee_to_lps = to_lps,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::SingleWithdrawFeeToLpsSet(SingleWithdrawFeeToLpsSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            to_lps,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin turns the sandwich guard on swaps on or off
+    pub fn process_set_sandwich_guard(
+        program_id: &Pubkey,
+        enabled: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.sandwich_guard = enabled,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::SandwichGuardSet(SandwichGuardSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            enabled,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin lets swaps through without their owner fee when the
+    // curve can't convert it into pool tokens, or makes them fail again
+    pub fn process_set_owner_fee_fallback(
+        program_id: &Pubkey,
+        enabled: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.owner_fee_fallback = enabled,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::OwnerFeeFallbackSet(OwnerFeeFallbackSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            enabled,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_set_bonded_keepers_only(
+        program_id: &Pubkey,
+        enabled: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.bonded_keepers_only = enabled,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::BondedKeepersOnlySet(BondedKeepersOnlySetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            enabled,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin gates the pool to holders of a token or a credential,
+    // or opens it again
+    pub fn process_set_gate(
+        program_id: &Pubkey,
+        gate: Pubkey,
+        credential: bool,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        // an open pool has nothing to tell apart
+        let credential = credential && gate != Pubkey::default();
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.gate = gate;
+                swap_v2.gate_credential = credential;
+            }
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::GateSet(GateSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            gate,
+            credential,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // LST/SOL pools: the stake pool's redemption rate becomes the price
+    pub fn process_set_stake_pool(
+        program_id: &Pubkey,
+        stake_pool: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        if stake_pool != Pubkey::default() {
+            let stake_pool_info = next_account_info(account_info_iter)?;
+            if token_swap.swap_curve().curve_type != CurveType::ConstantPrice {
+                return Err(SwapError::UnsupportedCurveOperation.into());
+            }
+            if *stake_pool_info.key != stake_pool
+                || *stake_pool_info.owner != stake_pool_program::id()
+            {
+                return Err(SwapError::InvalidOracle.into());
+            }
+            let rate = StakePoolRate::read(&stake_pool_info.data.borrow())
+                .ok_or(SwapError::InvalidOracle)?;
+            // the rate is lamports per LST, so token A must be SOL itself
+            if rate.pool_mint != *token_swap.token_b_mint()
+                || *token_swap.token_a_mint() != spl_token::native_mint::id()
+            {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+        }
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.stake_pool = stake_pool;
+                // stale until the first refresh
+                swap_v2.rate_update_slot = 0;
+            }
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::StakePoolSet(StakePoolSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            stake_pool,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // pools trading another pool's LP token: token B must be minted by that
+    // pool alone, so its reserves back every token this pool holds
+    pub fn process_set_nested_pool(
+        program_id: &Pubkey,
+        nested_pool: Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+
+        let mut token_swap = Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        if nested_pool != Pubkey::default() {
+            let nested_swap_info = next_account_info(account_info_iter)?;
+            let token_b_mint_info = next_account_info(account_info_iter)?;
+            if *nested_swap_info.key != nested_pool
+                || nested_swap_info.owner != program_id
+                || nested_pool == *swap_info.key
+            {
+                return Err(SwapError::InvalidNestedPool.into());
+            }
+            let nested_swap = SwapVersion::unpack_versioned(&nested_swap_info.data.borrow())?;
+            // one level only, pricing a nested pool token walks a single pool
+            if nested_swap.nested_pool().is_some() {
+                return Err(SwapError::InvalidNestedPool.into());
+            }
+            if nested_swap.pool_mint() != token_swap.token_b_mint() {
+                return Err(SwapError::IncorrectPoolMint.into());
+            }
+            if token_b_mint_info.key != token_swap.token_b_mint() {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+
+            let token_b_mint = Self::unpack_mint(token_b_mint_info, token_swap.token_program_id())?;
+            let nested_authority =
+                Self::authority_id(program_id, &nested_pool, nested_swap.nonce())?;
+            if token_b_mint.mint_authority != COption::Some(nested_authority) {
+                return Err(SwapError::InvalidMintAuthority.into());
+            }
+            if token_b_mint.freeze_authority.is_some() {
+                return Err(SwapError::InvalidFreezeAuthority.into());
+            }
+            let recorded = token_swap
+                .token_decimals()
+                .map(|(_, token_b_decimals)| token_b_decimals)
+                .into_iter()
+                .chain(nested_swap.pool_mint_decimals());
+            for decimals in recorded {
+                if decimals != token_b_mint.decimals {
+                    return Err(SwapError::UnsupportedMintDecimals.into());
+                }
+            }
+        }
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.nested_pool = nested_pool,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::NestedPoolSet(NestedPoolSetEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            nested_pool,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the pool admin picks the earliest slot the liquidity snapshot is taken
+    // in, until it's taken
+    pub fn process_configure_liquidity_snapshot(
+        program_id: &Pubkey,
+        slot: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let admin_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let snapshot_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        Self::unpack_admin_swap(
+            program_id,
+            swap_info,
+            admin_info,
+            pool_fee_account_info,
+            accounts,
+        )?;
+
+        let (snapshot_key, bump_seed) = LiquiditySnapshot::find_address(swap_info.key, program_id);
+        if *snapshot_info.key != snapshot_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if snapshot_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    snapshot_info.key,
+                    rent.minimum_balance(LiquiditySnapshot::LEN),
+                    LiquiditySnapshot::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    snapshot_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[SNAPSHOT_SEED, swap_info.key.as_ref(), &[bump_seed]]],
+            )?;
+        } else if snapshot_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        } else if LiquiditySnapshot::unpack_unchecked(&snapshot_info.data.borrow())?.taken {
+            // airdrops rely on a snapshot once taken, it isn't retaken
+            return Err(SwapError::SnapshotFinalized.into());
+        }
+
+        let snapshot = LiquiditySnapshot {
+            is_initialized: true,
+            bump_seed,
+            swap: *swap_info.key,
+            slot,
+            ..LiquiditySnapshot::default()
+        };
+        LiquiditySnapshot::pack(snapshot, &mut snapshot_info.data.borrow_mut())?;
+
+        Event::LiquiditySnapshotConfigured(LiquiditySnapshotConfiguredEvent {
+            swap: *swap_info.key,
+            admin: *admin_info.key,
+            slot,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // anyone takes the snapshot from its slot on, reading the supply and
+    // reserves together
+    pub fn process_crank_liquidity_snapshot(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let snapshot_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if *token_a_info.key != *token_swap.token_a_account()
+            || *token_b_info.key != *token_swap.token_b_account()
+        {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+
+        if snapshot_info.owner != program_id
+            || *snapshot_info.key != LiquiditySnapshot::find_address(swap_info.key, program_id).0
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let mut snapshot = LiquiditySnapshot::unpack(&snapshot_info.data.borrow())?;
+        if snapshot.taken {
+            return Err(SwapError::SnapshotFinalized.into());
+        }
+        let slot = Clock::get()?.slot;
+        if slot < snapshot.slot {
+            return Err(SwapError::SnapshotNotDue.into());
+        }
+
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+        snapshot.taken = true;
+        snapshot.taken_slot = slot;
+        snapshot.pool_token_supply = pool_mint.supply;
+        snapshot.token_a_amount = token_a.amount;
+        snapshot.token_b_amount = token_b.amount;
+
+        Event::LiquiditySnapshotFinalized(LiquiditySnapshotFinalizedEvent {
+            swap: *swap_info.key,
+            slot,
+            pool_token_supply: snapshot.pool_token_supply,
+            token_a_amount: snapshot.token_a_amount,
+            token_b_amount: snapshot.token_b_amount,
+        })
+        .emit();
+        LiquiditySnapshot::pack(snapshot, &mut snapshot_info.data.borrow_mut())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::base::{CurveType, SwapCurve};
+    use crate::curve::constant_product::ConstantProductCurve;
+    use crate::error::SwapError;
+    use crate::events::Event;
+    use crate::instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes};
+    use crate::processor::test::{
+        program_id, TestAccount, World, EVENTS, INITIAL_USER_BALANCE, SLOT,
+    };
+    use crate::state::{LiquiditySnapshot, SwapVersion};
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    #[test]
+    fn curves_are_only_replaced_once_no_pool_tokens_are_out() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        // however the supply is held, even all of it by the admin, it stays
+        let supply = world.bank.mint(&pool_mint).supply;
+        let holder = world.bank.add_token_account(&pool_mint, &admin, supply);
+        let reinitialize = |authority: &Pubkey| {
+            instruction::reinitialize_curve(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                authority,
+                &admin,
+                &fee_account,
+                &token_a,
+                &token_b,
+                &pool_mint,
+                &holder,
+                SwapCurve {
+                    curve_type: CurveType::ConstantProduct,
+                    calculator: Box::new(ConstantProductCurve {}),
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&reinitialize(&authority)),
+            Err(SwapError::InvalidSupply.into())
+        );
+
+        // with every pool token burnt, the curve can be replaced, through the
+        // pool's own authority only
+        let mut mint = world.bank.mint(&pool_mint);
+        mint.supply = 0;
+        mint.pack_into_slice(&mut world.bank.accounts.get_mut(&pool_mint).unwrap().data);
+        let mut holder_account = world.bank.token_account(&holder);
+        holder_account.amount = 0;
+        holder_account.pack_into_slice(&mut world.bank.accounts.get_mut(&holder).unwrap().data);
+        assert_eq!(
+            world.bank.process(&reinitialize(&admin)),
+            Err(SwapError::InvalidProgramAddress.into())
+        );
+        assert_eq!(world.bank.process(&reinitialize(&authority)), Ok(()));
+        let new_supply = world.bank.mint(&pool_mint).supply;
+        assert!(new_supply > 0);
+        assert_eq!(world.bank.token_account(&holder).amount, new_supply);
+    }
+
+    #[test]
+    fn fee_accounts_can_be_rotated() {
+        let mut world = World::new();
+        let (swap, old_admin, old_fee_account, pool_mint, mint_a) = {
+            let pool = &world.pools[0];
+            (
+                pool.swap,
+                pool.admin,
+                pool.fee_account,
+                pool.pool_mint,
+                pool.mint_a,
+            )
+        };
+        let new_admin = Pubkey::new_unique();
+        let new_fee_account = world.bank.add_token_account(&pool_mint, &new_admin, 0);
+        let wrong_mint = world.bank.add_token_account(&mint_a, &new_admin, 0);
+        let set_fee_account = |admin: &Pubkey, fee_account: &Pubkey, new_fee_account: &Pubkey| {
+            instruction::set_fee_account(&program_id(), &swap, admin, fee_account, new_fee_account)
+                .unwrap()
+        };
+
+        assert_eq!(
+            world
+                .bank
+                .process(&set_fee_account(&old_admin, &old_fee_account, &wrong_mint)),
+            Err(SwapError::IncorrectPoolMint.into())
+        );
+        assert_eq!(
+            world.bank.process(&set_fee_account(
+                &new_admin,
+                &old_fee_account,
+                &new_fee_account
+            )),
+            Err(SwapError::InvalidOwner.into())
+        );
+        world
+            .bank
+            .process(&set_fee_account(
+                &old_admin,
+                &old_fee_account,
+                &new_fee_account,
+            ))
+            .unwrap();
+        let state = SwapVersion::unpack(&world.bank.accounts[&swap].data).unwrap();
+        assert_eq!(*state.pool_fee_account(), new_fee_account);
+
+        // the old admin is out, and swaps pay the new fee account
+        assert_eq!(
+            world.bank.process(&set_fee_account(
+                &old_admin,
+                &old_fee_account,
+                &old_fee_account
+            )),
+            Err(SwapError::IncorrectFeeAccount.into())
+        );
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let swap_paying = |fee_account: &Pubkey| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user.tokens[&pool.mint_a],
+                &pool.token_a,
+                &pool.token_b,
+                &user.tokens[&pool.mint_b],
+                &pool.pool_mint,
+                fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: INITIAL_USER_BALANCE / 10,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let (to_old, to_new) = (swap_paying(&old_fee_account), swap_paying(&new_fee_account));
+        assert_eq!(
+            world.bank.process(&to_old),
+            Err(SwapError::IncorrectFeeAccount.into())
+        );
+        world.bank.process(&to_new).unwrap();
+        assert!(world.bank.token_account(&new_fee_account).amount > 0);
+    }
+
+    #[test]
+    fn paused_pools_refuse_swaps_and_deposits_but_not_withdrawals() {
+        let mut world = World::new();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (user_a, user_b) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let user_pool = user.pool_tokens[0];
+        let amount = INITIAL_USER_BALANCE / 100;
+        let pool_tokens = world.bank.mint(&pool.pool_mint).supply / 1_000;
+        let set_paused = |admin: &Pubkey, paused| {
+            instruction::set_paused(
+                &program_id(),
+                &pool.swap,
+                admin,
+                &pool.fee_account,
+                instruction::SetPaused { paused },
+            )
+            .unwrap()
+        };
+        let swap = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            &pool.pool_mint,
+            &pool.fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in: amount,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &user_b,
+            &pool.token_a,
+            &pool.token_b,
+            &pool.pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: pool_tokens,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        let deposit_single = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &pool.token_a,
+            &pool.token_b,
+            &pool.pool_mint,
+            &user_pool,
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: amount,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &pool.pool_mint,
+            &pool.fee_account,
+            &user_pool,
+            &pool.token_a,
+            &pool.token_b,
+            &user_a,
+            &user_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: pool_tokens,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        let (admin, swap_key) = (pool.admin, pool.swap);
+        let paused = |world: &World| {
+            SwapVersion::unpack(&world.bank.accounts[&swap_key].data)
+                .unwrap()
+                .paused()
+        };
+
+        world.bank.process(&deposit).unwrap();
+        // only the pool admin can pause the pool
+        assert_eq!(
+            world.bank.process(&set_paused(&user.wallet, true)),
+            Err(SwapError::InvalidOwner.into())
+        );
+        world.bank.process(&set_paused(&admin, true)).unwrap();
+        assert!(paused(&world));
+
+        for halted in [&swap, &deposit, &deposit_single] {
+            assert_eq!(
+                world.bank.process(halted),
+                Err(SwapError::PoolPaused.into())
+            );
+        }
+        // LPs can still get out
+        assert_eq!(world.bank.process(&withdraw), Ok(()));
+
+        world.bank.process(&set_paused(&admin, false)).unwrap();
+        assert!(!paused(&world));
+        assert_eq!(world.bank.process(&swap), Ok(()));
+        world.assert_conserved(&trading_supplies);
+    }
+
+    #[test]
+    fn liquidity_snapshots_record_supply_and_reserves_together() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, token_a, token_b) = (pool.swap, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+
+        // the harness doesn't run the system program, so the snapshot account is
+        // created up front
+        let (snapshot, _) = LiquiditySnapshot::find_address(&swap, &program_id());
+        world.bank.accounts.insert(
+            snapshot,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data: vec![0; LiquiditySnapshot::LEN],
+                owner: program_id(),
+            },
+        );
+        let configure = instruction::configure_liquidity_snapshot(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            &admin,
+            instruction::ConfigureLiquiditySnapshot { slot: 1_000 },
+        )
+        .unwrap();
+        world.bank.process(&configure).unwrap();
+        // anyone can crank it, no signature needed
+        let crank = instruction::crank_liquidity_snapshot(
+            &program_id(),
+            &swap,
+            &token_a,
+            &token_b,
+            &pool_mint,
+        )
+        .unwrap();
+        assert!(crank.accounts.iter().all(|meta| !meta.is_signer));
+
+        SLOT.with(|slot| slot.set(999));
+        assert_eq!(
+            world.bank.process(&crank),
+            Err(SwapError::SnapshotNotDue.into())
+        );
+
+        SLOT.with(|slot| slot.set(1_003));
+        EVENTS.with(|events| events.borrow_mut().clear());
+        world.bank.process(&crank).unwrap();
+        let events = EVENTS.with(|events| events.take());
+        let taken = events
+            .iter()
+            .find_map(|event| match event {
+                Event::LiquiditySnapshotFinalized(taken) => Some(taken.clone()),
+                _ => None,
+            })
+            .unwrap();
+        let expected = (
+            1_003,
+            world.bank.mint(&pool_mint).supply,
+            world.bank.token_account(&token_a).amount,
+            world.bank.token_account(&token_b).amount,
+        );
+        assert_eq!(
+            (
+                taken.slot,
+                taken.pool_token_supply,
+                taken.token_a_amount,
+                taken.token_b_amount
+            ),
+            expected
+        );
+        let stored = LiquiditySnapshot::unpack(&world.bank.accounts[&snapshot].data).unwrap();
+        assert!(stored.taken);
+        assert_eq!(
+            (
+                stored.taken_slot,
+                stored.pool_token_supply,
+                stored.token_a_amount,
+                stored.token_b_amount
+            ),
+            expected
+        );
+
+        // it's taken once, neither later cranks nor the admin can move it to
+        // another slot's state
+        SLOT.with(|slot| slot.set(1_004));
+        assert_eq!(
+            world.bank.process(&crank),
+            Err(SwapError::SnapshotFinalized.into())
+        );
+        assert_eq!(
+            world.bank.process(&configure),
+            Err(SwapError::SnapshotFinalized.into())
+        );
+        let other = &world.pools[1];
+        let mismatched = instruction::crank_liquidity_snapshot(
+            &program_id(),
+            &swap,
+            &other.token_a,
+            &other.token_b,
+            &pool_mint,
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&mismatched),
+            Err(SwapError::IncorrectSwapAccount.into())
+        );
+    }
+}
@@ -0,0 +1,1559 @@
+//! Deposits, and locking the pool tokens they mint.
+
+use crate::amount::{PoolTokenAmount, TokenAmount};
+use crate::constants::{LOCK_SEED, LP_POSITION_SEED, REFERRAL_SEED};
+use crate::curve::calculator::{RoundDirection, TradeDirection};
+use crate::error::SwapError;
+use crate::events::{
+    DepositBonusPaidEvent, DepositEvent, DepositReferredEvent, Event, LiquidityLockEvent,
+};
+use crate::state::{LiquidityLock, LpPosition, ReferredDeposits, Referrer, SwapState, SwapVersion};
+use crate::token_ops;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::msg;
+use solana_program::program::invoke_signed;
+use solana_program::program_error::ProgramError;
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_instruction;
+use solana_program::sysvar::{clock::Clock, rent::Rent, Sysvar};
+
+use super::Processor;
+
+impl Processor {
+    // on pools with a withdraw cooldown, records a single-sided deposit's
+    // slot in the LP position of the account it minted into, created on its
+    // first, holding up single-sided withdrawals from that account alone
+    fn record_single_sided_deposit<'a, 'b>(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo<'a>,
+        token_swap: &dyn SwapState,
+        destination_info: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    ) -> ProgramResult {
+        if token_swap.withdraw_cooldown_slots() == 0 {
+            return Ok(());
+        }
+        let position_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (position_key, bump_seed) =
+            LpPosition::find_address(swap_info.key, destination_info.key, program_id);
+        if *position_info.key != position_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if position_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    position_info.key,
+                    rent.minimum_balance(LpPosition::LEN),
+                    LpPosition::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    position_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[
+                    LP_POSITION_SEED,
+                    swap_info.key.as_ref(),
+                    destination_info.key.as_ref(),
+                    &[bump_seed],
+                ]],
+            )?;
+        } else if position_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        LpPosition::pack(
+            LpPosition {
+                is_initialized: true,
+                bump_seed,
+                swap: *swap_info.key,
+                pool_account: *destination_info.key,
+                last_single_deposit_slot: Clock::get()?.slot,
+            },
+            &mut position_info.data.borrow_mut(),
+        )
+    }
+
+    // growth incentive, tops up a lock lasting the bonus lockup with pool
+    // tokens the admin let the swap authority spend from the pool fee account,
+    // in proportion to the pool tokens just minted into it, returning the
+    // bonus paid into the vault
+    #[allow(clippy::too_many_arguments)]
+    fn pay_deposit_bonus<'a>(
+        swap_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        token_swap: &dyn SwapState,
+        pool_fee_account_info: Option<&AccountInfo<'a>>,
+        source_info: &AccountInfo<'a>,
+        vault_info: &AccountInfo<'a>,
+        amount: u64,
+        unlock_slot: u64,
+    ) -> Result<u64, ProgramError> {
+        let pool_fee_account_info = match pool_fee_account_info {
+            Some(info) if info.key != source_info.key => info,
+            _ => return Ok(0),
+        };
+        let bonus_bps = token_swap.deposit_bonus_bps(Clock::get()?.slot, unlock_slot);
+        if bonus_bps == 0 {
+            return Ok(0);
+        }
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let fee_account =
+            Self::unpack_token_account(pool_fee_account_info, token_swap.token_program_id())?;
+        let available = if fee_account.delegate == COption::Some(*authority_info.key) {
+            std::cmp::min(fee_account.amount, fee_account.delegated_amount)
+        } else {
+            0
+        };
+        let bonus = PoolTokenAmount::from(amount)
+            .get()
+            .checked_mul(bonus_bps.into())
+            .map(|bonus| bonus / 10_000)
+            .map(PoolTokenAmount::new)
+            .ok_or(SwapError::CalculationFailure)?;
+        let bonus = std::cmp::min(bonus.to_u64()?, available);
+        if bonus == 0 {
+            return Ok(0);
+        }
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_fee_account_info.clone(),
+            vault_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            bonus,
+        )?;
+        Event::DepositBonusPaid(DepositBonusPaidEvent {
+            swap: *swap_info.key,
+            destination: *vault_info.key,
+            pool_token_amount: bonus,
+        })
+        .emit();
+        Ok(bonus)
+    }
+
+    // referral campaigns, attributes a deposit to the registered referrer
+    // presented after any gate account and counts it in the referrer's
+    // deposits into the pool, created on its first
+    fn record_referred_deposit<'a, 'b>(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo<'a>,
+        depositor: &Pubkey,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        token_a_amount: u64,
+        token_b_amount: u64,
+        pool_token_amount: u64,
+    ) -> ProgramResult {
+        let referrer_info = match account_info_iter.next() {
+            Some(info) => info,
+            None => return Ok(()),
+        };
+        let referred_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if referrer_info.owner != program_id {
+            return Err(SwapError::InvalidReferrer.into());
+        }
+        let referrer = Referrer::unpack(&referrer_info.data.borrow())
+            .map_err(|_| SwapError::InvalidReferrer)?;
+        // no referring yourself
+        if *referrer_info.key != Referrer::find_address(&referrer.owner, program_id).0
+            || referrer.owner == *depositor
+        {
+            return Err(SwapError::InvalidReferrer.into());
+        }
+
+        let (referred_key, bump_seed) =
+            ReferredDeposits::find_address(swap_info.key, &referrer.owner, program_id);
+        if *referred_info.key != referred_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if referred_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    referred_info.key,
+                    rent.minimum_balance(ReferredDeposits::LEN),
+                    ReferredDeposits::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    referred_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[
+                    REFERRAL_SEED,
+                    swap_info.key.as_ref(),
+                    referrer.owner.as_ref(),
+                    &[bump_seed],
+                ]],
+            )?;
+        } else if referred_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let mut referred = ReferredDeposits::unpack_unchecked(&referred_info.data.borrow())?;
+        if !referred.is_initialized {
+            referred = ReferredDeposits {
+                is_initialized: true,
+                bump_seed,
+                swap: *swap_info.key,
+                referrer: referrer.owner,
+                ..ReferredDeposits::default()
+            };
+        }
+        referred.record(token_a_amount, token_b_amount, pool_token_amount);
+        let (deposits, total_pool_token_amount) = (referred.deposits, referred.pool_token_amount);
+        ReferredDeposits::pack(referred, &mut referred_info.data.borrow_mut())?;
+
+        Event::DepositReferred(DepositReferredEvent {
+            swap: *swap_info.key,
+            depositor: *depositor,
+            referrer: referrer.owner,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+            deposits,
+            total_pool_token_amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    pub fn process_deposit_all_token_types(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        maximum_token_a_amount: u64, //for the purposes of slippage
+        maximum_token_b_amount: u64, //for the purposes of slippage
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+
+        let user_transfer_authority_info = next_account_info(account_info_iter)?; //authority over user's A and B accounts
+        let source_a_info = next_account_info(account_info_iter)?; //user's A account
+        let source_b_info = next_account_info(account_info_iter)?; //user's B account
+        let token_a_info = next_account_info(account_info_iter)?; //exchange A account
+        let token_b_info = next_account_info(account_info_iter)?; //exchange B account
+
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let dest_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        let calculator = &token_swap.swap_curve().calculator;
+
+        if !calculator.allows_deposits() {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        Self::check_accounts(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            token_a_info,
+            token_b_info,
+            pool_mint_info,
+            token_program_info,
+            None,
+        )?;
+        Self::check_not_paused(token_swap.as_ref())?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        Self::check_gate(
+            token_swap.as_ref(),
+            user_transfer_authority_info,
+            account_info_iter,
+        )?;
+        Self::check_user_token_accounts(
+            token_swap.token_program_id(),
+            &[source_a_info, source_b_info, dest_info],
+        )?;
+
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+        let current_pool_mint_supply = PoolTokenAmount::from(pool_mint.supply);
+
+        //get the outstanding + max pool token supply
+        let (pool_token_amount, pool_mint_supply) = if !current_pool_mint_supply.is_zero() {
+            (
+                PoolTokenAmount::from(pool_token_amount),
+                current_pool_mint_supply,
+            )
+        } else {
+            //if the current supply is 0, means we're funding a new pool, then by definition we're going to have 100% of it, so the two values are the same
+            let new_pool_supply = PoolTokenAmount::new(calculator.new_pool_supply());
+            (new_pool_supply, new_pool_supply)
+        };
+
+        // ----------------------------------------------------------------------------- calc
+
+        // token X amount to deposit, token Y amount to deposit
+        // this is based on balancer - " If a deposit of assets increases the pool Value Function by
+        // 10%, then the outstanding supply of pool tokens also increases by 10%. This happens because the depositor
+        // is issued 10% of new pool tokens in return for the deposit."
+        let results = calculator
+            .pool_tokens_to_trading_tokens(
+                pool_token_amount.get(),                 //outstanding pool token amount
+                pool_mint_supply.get(),                  //total pool token amount
+                TokenAmount::from(token_a.amount).get(), //exchange A tokens
+                TokenAmount::from(token_b.amount).get(), //exchange B tokens
+                RoundDirection::Ceiling,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        msg!(
+            "{}, {}, {}, {}",
+            results.token_a_amount,
+            maximum_token_a_amount,
+            results.token_b_amount,
+            maximum_token_b_amount,
+        );
+
+        let token_a_amount = TokenAmount::new(results.token_a_amount).to_u64()?;
+        if token_a_amount > maximum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if token_a_amount == 0 {
+            return Err(Self::zero_amount_error(token_a.amount).into());
+        }
+
+        let token_b_amount = TokenAmount::new(results.token_b_amount).to_u64()?;
+        if token_b_amount > maximum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if token_b_amount == 0 {
+            return Err(Self::zero_amount_error(token_b.amount).into());
+        }
+
+        // ----------------------------------------------------------------------------- execute
+
+        let pool_token_amount = pool_token_amount.to_u64()?;
+
+        Self::check_transfer_authority(
+            source_a_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            token_a_amount,
+        )?;
+        Self::check_transfer_authority(
+            source_b_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            token_b_amount,
+        )?;
+
+        // transfer token X into the exchange
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_a_info.clone(),
+            token_a_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce(),
+            token_a_amount,
+        )?;
+        // transfer token Y into the exchange
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_b_info.clone(),
+            token_b_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce(),
+            token_b_amount,
+        )?;
+
+        // mint POOL tokens back to the user, that he'll be able to stake in the LP farm
+        token_ops::mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            dest_info.clone(), //user's destination addr
+            authority_info.clone(),
+            token_swap.nonce(),
+            pool_token_amount, //we started this function call by specifying how many we'd like to get back
+        )?;
+
+        Event::Deposit(DepositEvent {
+            swap: *swap_info.key,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+        })
+        .emit();
+        Self::record_referred_deposit(
+            program_id,
+            swap_info,
+            user_transfer_authority_info.key,
+            account_info_iter,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+        )?;
+        Self::record_interaction(swap_info, token_a_info, token_b_info, pool_mint_info, 0)
+    }
+
+    pub fn process_deposit_single_token_type_exact_amount_in(
+        program_id: &Pubkey,
+        source_token_amount: u64,
+        minimum_pool_token_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if source_token_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_token_a_info = next_account_info(account_info_iter)?;
+        let swap_token_b_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if !token_swap.swap_curve().calculator.allows_deposits() {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        Self::check_accounts(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            token_program_info,
+            None,
+        )?;
+        Self::check_not_paused(token_swap.as_ref())?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        Self::check_rate_fresh(token_swap.as_ref())?;
+        Self::check_gate(
+            token_swap.as_ref(),
+            user_transfer_authority_info,
+            account_info_iter,
+        )?;
+        let source_account =
+            Self::unpack_user_token_account(source_info, token_swap.token_program_id())?;
+        Self::check_user_token_accounts(token_swap.token_program_id(), &[destination_info])?;
+        let swap_token_a =
+            Self::unpack_token_account(swap_token_a_info, token_swap.token_program_id())?;
+        let swap_token_b =
+            Self::unpack_token_account(swap_token_b_info, token_swap.token_program_id())?;
+
+        //figure out if user wants to deposit token A or token B
+        let trade_direction = if source_account.mint == swap_token_a.mint {
+            TradeDirection::AtoB
+        } else if source_account.mint == swap_token_b.mint {
+            TradeDirection::BtoA
+        } else {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        };
+
+        // ----------------------------------------------------------------------------- calc
+
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+        let pool_mint_supply = PoolTokenAmount::from(pool_mint.supply);
+
+        // deposit single token = perform a swap followed by a deposit
+        let pool_token_amount = if !pool_mint_supply.is_zero() {
+            token_swap
+                .swap_curve()
+                .deposit_single_token_type(
+                    TokenAmount::from(source_token_amount).get(),
+                    TokenAmount::from(swap_token_a.amount).get(),
+                    TokenAmount::from(swap_token_b.amount).get(),
+                    pool_mint_supply.get(),
+                    trade_direction,
+                    &Self::fees_for(token_swap.as_ref(), source_info.key)?,
+                )
+                .ok_or(SwapError::ZeroTradingTokens)?
+        } else {
+            token_swap.swap_curve().calculator.new_pool_supply()
+        };
+
+        let pool_token_amount = PoolTokenAmount::new(pool_token_amount).to_u64()?;
+        if pool_token_amount < minimum_pool_token_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if pool_token_amount == 0 {
+            return Err(SwapError::AmountTooSmall.into());
+        }
+
+        // ----------------------------------------------------------------------------- execute
+
+        Self::check_transfer_authority(
+            source_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            source_token_amount,
+        )?;
+        match trade_direction {
+            //move token from user's account to exchange account
+            TradeDirection::AtoB => {
+                token_ops::transfer(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    source_info.clone(),
+                    swap_token_a_info.clone(),
+                    user_transfer_authority_info.clone(),
+                    token_swap.nonce(),
+                    source_token_amount,
+                )?;
+            }
+            TradeDirection::BtoA => {
+                token_ops::transfer(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    source_info.clone(),
+                    swap_token_b_info.clone(),
+                    user_transfer_authority_info.clone(),
+                    token_swap.nonce(),
+                    source_token_amount,
+                )?;
+            }
+        }
+        //mint the appropriate number of LP tokens to the user's token account
+        token_ops::mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            pool_token_amount,
+        )?;
+        Self::record_single_sided_deposit(
+            program_id,
+            swap_info,
+            token_swap.as_ref(),
+            destination_info,
+            account_info_iter,
+        )?;
+        Self::record_single_sided_volume(
+            swap_info,
+            trade_direction,
+            source_token_amount,
+            swap_token_a.amount,
+            swap_token_b.amount,
+        )?;
+
+        let (token_a_amount, token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (source_token_amount, 0),
+            TradeDirection::BtoA => (0, source_token_amount),
+        };
+        Event::Deposit(DepositEvent {
+            swap: *swap_info.key,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+        })
+        .emit();
+        Self::record_referred_deposit(
+            program_id,
+            swap_info,
+            user_transfer_authority_info.key,
+            account_info_iter,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+        )?;
+        Self::record_interaction(
+            swap_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            0,
+        )
+    }
+
+    // moves pool tokens into an authority-owned vault, recording them against the owner until unlock_slot
+    pub fn process_lock_liquidity(
+        program_id: &Pubkey,
+        amount: u64,
+        unlock_slot: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        Self::lock_liquidity(program_id, amount, unlock_slot, accounts, None)
+    }
+
+    // deposits both tokens and locks the pool tokens the deposit minted, the
+    // deposit bonus being paid on those alone
+    pub fn process_deposit_and_lock_liquidity(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        maximum_token_a_amount: u64,
+        maximum_token_b_amount: u64,
+        unlock_slot: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let lock_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?; //holds the locked tokens
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let deposit_accounts = account_info_iter.as_slice();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?; //user transfer authority
+        let (dest_info, token_program_info) = match deposit_accounts.get(8..10) {
+            Some([dest_info, token_program_info]) => (dest_info, token_program_info),
+            _ => return Err(ProgramError::NotEnoughAccountKeys),
+        };
+
+        // whatever the pool account held already isn't locked nor earns a bonus
+        let pool_balance = || {
+            Self::unpack_token_account(dest_info, token_program_info.key)
+                .map(|account| account.amount)
+        };
+        let balance_before = pool_balance()?;
+        Self::process_deposit_all_token_types(
+            program_id,
+            pool_token_amount,
+            maximum_token_a_amount,
+            maximum_token_b_amount,
+            deposit_accounts,
+        )?;
+        let minted = pool_balance()?
+            .checked_sub(balance_before)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        Self::lock_liquidity(
+            program_id,
+            minted,
+            unlock_slot,
+            &[
+                swap_info.clone(),
+                authority_info.clone(),
+                lock_info.clone(),
+                owner_info.clone(),
+                dest_info.clone(),
+                vault_info.clone(),
+                payer_info.clone(),
+                system_program_info.clone(),
+                token_program_info.clone(),
+            ],
+            Some(pool_fee_account_info),
+        )
+    }
+
+    // locks pool tokens, paying the deposit bonus on them out of the pool fee
+    // account when given it, which only a deposit minting them does
+    fn lock_liquidity<'a>(
+        program_id: &Pubkey,
+        amount: u64,
+        unlock_slot: u64,
+        accounts: &[AccountInfo<'a>],
+        pool_fee_account_info: Option<&AccountInfo<'a>>,
+    ) -> ProgramResult {
+        if amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let lock_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?; //owner's pool token account
+        let vault_info = next_account_info(account_info_iter)?; //holds the locked tokens
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        let vault = Self::unpack_token_account(vault_info, token_swap.token_program_id())?;
+        if vault.mint != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if vault.owner != *authority_info.key {
+            return Err(SwapError::InvalidOutputOwner.into());
+        }
+        Self::check_user_token_accounts(token_swap.token_program_id(), &[source_info])?;
+        Self::check_transfer_authority(
+            source_info,
+            token_swap.token_program_id(),
+            authority_info,
+            owner_info,
+            amount,
+        )?;
+        if unlock_slot <= Clock::get()?.slot {
+            return Err(SwapError::LiquidityLocked.into());
+        }
+
+        let (lock_key, bump_seed) =
+            LiquidityLock::find_address(swap_info.key, owner_info.key, unlock_slot, program_id);
+        if *lock_info.key != lock_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let mut lock = if lock_info.data_is_empty() {
+            // each lock gets a vault of its own, so the one closing it on unlock
+            // hands its rent back to the lock's owner rather than another's
+            if vault.amount != 0 {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    lock_info.key,
+                    rent.minimum_balance(LiquidityLock::LEN),
+                    LiquidityLock::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    lock_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[
+                    LOCK_SEED,
+                    swap_info.key.as_ref(),
+                    owner_info.key.as_ref(),
+                    &unlock_slot.to_le_bytes(),
+                    &[bump_seed],
+                ]],
+            )?;
+            LiquidityLock {
+                is_initialized: true,
+                bump_seed,
+                swap: *swap_info.key,
+                owner: *owner_info.key,
+                vault: *vault_info.key,
+                amount: 0,
+                unlock_slot,
+            }
+        } else {
+            if lock_info.owner != program_id {
+                return Err(SwapError::InvalidProgramAddress.into());
+            }
+            let lock = LiquidityLock::unpack(&lock_info.data.borrow())?;
+            if lock.vault != *vault_info.key {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            lock
+        };
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            vault_info.clone(),
+            owner_info.clone(),
+            token_swap.nonce(),
+            amount,
+        )?;
+        let bonus = Self::pay_deposit_bonus(
+            swap_info,
+            authority_info,
+            token_program_info,
+            &token_swap,
+            pool_fee_account_info,
+            source_info,
+            vault_info,
+            amount,
+            unlock_slot,
+        )?;
+        let locked = amount
+            .checked_add(bonus)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        lock.amount = lock
+            .amount
+            .checked_add(locked)
+            .ok_or(SwapError::CalculationFailure)?;
+        LiquidityLock::pack(lock, &mut lock_info.data.borrow_mut())?;
+
+        if let SwapVersion::SwapV2(swap_v2) = &mut token_swap {
+            swap_v2.locked_pool_tokens = swap_v2
+                .locked_pool_tokens
+                .checked_add(locked)
+                .ok_or(SwapError::CalculationFailure)?;
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::LiquidityLocked(LiquidityLockEvent {
+            swap: *swap_info.key,
+            owner: *owner_info.key,
+            amount: locked,
+            unlock_slot,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // hands a lock's tokens back to its owner once unlock_slot is reached, and closes the lock
+    pub fn process_unlock_liquidity(
+        program_id: &Pubkey,
+        unlock_slot: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let lock_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let mut token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+
+        let (lock_key, _bump_seed) =
+            LiquidityLock::find_address(swap_info.key, owner_info.key, unlock_slot, program_id);
+        if *lock_info.key != lock_key || lock_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let lock = LiquidityLock::unpack(&lock_info.data.borrow())?;
+        if lock.vault != *vault_info.key {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if Clock::get()?.slot < lock.unlock_slot {
+            return Err(SwapError::LiquidityLocked.into());
+        }
+        Self::check_user_token_accounts(token_swap.token_program_id(), &[destination_info])?;
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            vault_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            lock.amount,
+        )?;
+        // the lock's vault is emptied with it, so it goes too, its rent with the owner
+        let vault = Self::unpack_token_account(vault_info, token_swap.token_program_id())?;
+        if vault.amount == 0 {
+            token_ops::close_account(
+                swap_info.key,
+                token_program_info.clone(),
+                vault_info.clone(),
+                owner_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+            )?;
+        }
+
+        if let SwapVersion::SwapV2(swap_v2) = &mut token_swap {
+            swap_v2.locked_pool_tokens = swap_v2
+                .locked_pool_tokens
+                .checked_sub(lock.amount)
+                .ok_or(SwapError::CalculationFailure)?;
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        // close the receipt, refunding its rent to the owner
+        let lock_lamports = lock_info.lamports();
+        **owner_info.lamports.borrow_mut() = owner_info
+            .lamports()
+            .checked_add(lock_lamports)
+            .ok_or(SwapError::CalculationFailure)?;
+        **lock_info.lamports.borrow_mut() = 0;
+        lock_info.data.borrow_mut().fill(0);
+
+        Event::LiquidityUnlocked(LiquidityLockEvent {
+            swap: *swap_info.key,
+            owner: *owner_info.key,
+            amount: lock.amount,
+            unlock_slot: lock.unlock_slot,
+        })
+        .emit();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::base::CurveType;
+    use crate::error::SwapError;
+    use crate::events::Event;
+    use crate::instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes};
+    use crate::processor::test::{
+        program_id, TestAccount, User, World, EVENTS, INITIAL_RESERVE, SLOT,
+    };
+    use crate::state::{LiquidityLock, ReferredDeposits, Referrer};
+    use solana_program::program_option::COption;
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use std::cell::Cell;
+    use std::collections::HashMap;
+
+    #[test]
+    fn locked_liquidity_only_goes_back_to_its_owner() {
+        let mut world = World::new();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account) = (pool.pool_mint, pool.fee_account);
+        let (owner, owner_a, owner_b, owner_pool) = (
+            world.users[0].wallet,
+            world.users[0].tokens[&pool.mint_a],
+            world.users[0].tokens[&pool.mint_b],
+            world.users[0].pool_tokens[0],
+        );
+        let (thief, thief_pool) = (world.users[1].wallet, world.users[1].pool_tokens[0]);
+        let locked = 5_000;
+        world.fund(&pool_mint, &owner_pool, locked);
+        let vault = world.bank.add_token_account(&pool_mint, &authority, 0);
+
+        // the harness doesn't run the system program, so the lock is created up
+        // front, empty
+        let unlock_slot = SLOT.with(Cell::get) + 100;
+        let (lock, bump_seed) =
+            LiquidityLock::find_address(&swap, &owner, unlock_slot, &program_id());
+        let mut data = vec![0; LiquidityLock::LEN];
+        LiquidityLock::pack(
+            LiquidityLock {
+                is_initialized: true,
+                bump_seed,
+                swap,
+                owner,
+                vault,
+                amount: 0,
+                unlock_slot,
+            },
+            &mut data,
+        )
+        .unwrap();
+        world.bank.accounts.insert(
+            lock,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data,
+                owner: program_id(),
+            },
+        );
+        let lock_instruction = instruction::lock_liquidity(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &owner,
+            &owner_pool,
+            &vault,
+            &owner,
+            instruction::LockLiquidity {
+                amount: locked,
+                unlock_slot,
+            },
+        )
+        .unwrap();
+        assert_eq!(world.bank.process(&lock_instruction), Ok(()));
+        assert_eq!(world.bank.token_account(&vault).amount, locked);
+
+        // nor can another lock share the vault, taking its rent on unlock
+        world.fund(&pool_mint, &thief_pool, locked);
+        let shared_vault = instruction::lock_liquidity(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &thief,
+            &thief_pool,
+            &vault,
+            &thief,
+            instruction::LockLiquidity {
+                amount: locked,
+                unlock_slot,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&shared_vault),
+            Err(SwapError::IncorrectSwapAccount.into())
+        );
+
+        // the vault can't be withdrawn from nor relocked in someone else's name
+        // through the swap authority
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &authority,
+            &pool_mint,
+            &fee_account,
+            &vault,
+            &token_a,
+            &token_b,
+            &owner_a,
+            &owner_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: locked,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        let relock = instruction::lock_liquidity(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &authority,
+            &vault,
+            &vault,
+            &thief,
+            instruction::LockLiquidity {
+                amount: locked,
+                unlock_slot,
+            },
+        )
+        .unwrap();
+        for stolen in [&withdraw, &relock] {
+            assert_eq!(
+                world.bank.process(stolen),
+                Err(SwapError::SwapAuthorityTransfer.into())
+            );
+        }
+
+        // once unlockable, the unlock must name the pool's own authority
+        SLOT.with(|slot| slot.set(unlock_slot));
+        let unlock = |authority: &Pubkey| {
+            instruction::unlock_liquidity(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                authority,
+                &owner,
+                &vault,
+                &owner_pool,
+                instruction::UnlockLiquidity { unlock_slot },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&unlock(&thief)),
+            Err(SwapError::InvalidProgramAddress.into())
+        );
+        assert_eq!(world.bank.process(&unlock(&authority)), Ok(()));
+        assert_eq!(world.bank.token_account(&owner_pool).amount, locked);
+        // the emptied vault is closed, its rent and the lock's with the owner
+        assert_eq!(world.bank.accounts[&vault].lamports, 0);
+        assert_eq!(world.bank.accounts[&owner].lamports, 2_000_000_000);
+        world.assert_conserved(&trading_supplies);
+    }
+
+    #[test]
+    fn deposit_bonus_only_vests_in_locks_of_freshly_minted_pool_tokens() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        let user = &world.users[0];
+        let (wallet, user_a, user_b, user_pool) = (
+            user.wallet,
+            user.tokens[&pool.mint_a],
+            user.tokens[&pool.mint_b],
+            user.pool_tokens[0],
+        );
+
+        // the admin lets the swap authority pay bonuses out of the fee account
+        world.fund(&pool_mint, &fee_account, 1_000_000);
+        let mut fee_state = world.bank.token_account(&fee_account);
+        fee_state.delegate = COption::Some(authority);
+        fee_state.delegated_amount = 1_000_000;
+        fee_state.pack_into_slice(&mut world.bank.accounts.get_mut(&fee_account).unwrap().data);
+
+        SLOT.with(|slot| slot.set(100));
+        let set_deposit_bonus = |lockup_slots| {
+            instruction::set_deposit_bonus(
+                &program_id(),
+                &swap,
+                &admin,
+                &fee_account,
+                instruction::SetDepositBonus {
+                    start_slot: 100,
+                    end_slot: 200,
+                    bonus_bps: 1_000,
+                    lockup_slots,
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&set_deposit_bonus(0)),
+            Err(SwapError::InvalidInstruction.into())
+        );
+        world.bank.process(&set_deposit_bonus(1_000)).unwrap();
+
+        // depositing and withdrawing again during the window earns nothing
+        let deposited = 1_000_000;
+        let pool_tokens = world.bank.token_account(&user_pool).amount;
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &user_a,
+            &user_b,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: deposited,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &pool_mint,
+            &fee_account,
+            &user_pool,
+            &token_a,
+            &token_b,
+            &user_a,
+            &user_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: deposited,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&deposit).unwrap();
+        world.bank.process(&withdraw).unwrap();
+        assert_eq!(world.bank.token_account(&user_pool).amount, pool_tokens);
+        // the fee account only gains the withdraw fee
+        let fees = world.bank.token_account(&fee_account).amount;
+        assert!(fees >= 1_000_000);
+
+        // the harness doesn't run the system program, so the locks are created
+        // up front, empty, each with a vault of its own
+        let new_lock = |world: &mut World, unlock_slot| {
+            let vault = world.bank.add_token_account(&pool_mint, &authority, 0);
+            let (lock, bump_seed) =
+                LiquidityLock::find_address(&swap, &wallet, unlock_slot, &program_id());
+            let mut data = vec![0; LiquidityLock::LEN];
+            LiquidityLock::pack(
+                LiquidityLock {
+                    is_initialized: true,
+                    bump_seed,
+                    swap,
+                    owner: wallet,
+                    vault,
+                    amount: 0,
+                    unlock_slot,
+                },
+                &mut data,
+            )
+            .unwrap();
+            world.bank.accounts.insert(
+                lock,
+                TestAccount {
+                    lamports: 1_000_000_000,
+                    data,
+                    owner: program_id(),
+                },
+            );
+            (lock, vault)
+        };
+        let locked = |world: &World, lock: &Pubkey| {
+            LiquidityLock::unpack(&world.bank.accounts[lock].data)
+                .unwrap()
+                .amount
+        };
+
+        // locking pool tokens minted before, or bought, gets no bonus however
+        // long the lock lasts
+        world.bank.process(&deposit).unwrap();
+        let (held_lock, held_vault) = new_lock(&mut world, 1_200);
+        let lock_held = instruction::lock_liquidity(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &user_pool,
+            &held_vault,
+            &wallet,
+            instruction::LockLiquidity {
+                amount: deposited,
+                unlock_slot: 1_200,
+            },
+        )
+        .unwrap();
+        world.bank.process(&lock_held).unwrap();
+        assert_eq!(locked(&world, &held_lock), deposited);
+        assert_eq!(world.bank.token_account(&fee_account).amount, fees);
+
+        // depositing and locking what the deposit minted does, when the lock
+        // lasts the lockup
+        let deposit_and_lock = |world: &mut World, unlock_slot| {
+            let (lock, vault) = new_lock(world, unlock_slot);
+            let deposit_and_lock = instruction::lock_deposit(
+                &vault,
+                &wallet,
+                &fee_account,
+                unlock_slot,
+                deposit.clone(),
+            )
+            .unwrap();
+            world.bank.process(&deposit_and_lock).unwrap();
+            (locked(world, &lock), vault)
+        };
+        let (short_locked, short_vault) = deposit_and_lock(&mut world, 1_099);
+        assert_eq!(short_locked, deposited);
+        // the bonus is paid into the lock, not to the depositor, on the minted
+        // tokens alone
+        let (long_locked, long_vault) = deposit_and_lock(&mut world, 1_100);
+        assert_eq!(long_locked, deposited + deposited / 10);
+        assert_eq!(
+            world.bank.token_account(&long_vault).amount,
+            deposited + deposited / 10
+        );
+        assert_eq!(
+            world.bank.token_account(&fee_account).amount,
+            fees - deposited / 10
+        );
+        assert_eq!(world.bank.token_account(&user_pool).amount, pool_tokens);
+
+        let unlock = |unlock_slot, vault: &Pubkey| {
+            instruction::unlock_liquidity(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                &authority,
+                &wallet,
+                vault,
+                &user_pool,
+                instruction::UnlockLiquidity { unlock_slot },
+            )
+            .unwrap()
+        };
+        SLOT.with(|slot| slot.set(1_099));
+        world.bank.process(&unlock(1_099, &short_vault)).unwrap();
+        assert_eq!(
+            world.bank.process(&unlock(1_100, &long_vault)),
+            Err(SwapError::LiquidityLocked.into())
+        );
+        SLOT.with(|slot| slot.set(1_100));
+        world.bank.process(&unlock(1_100, &long_vault)).unwrap();
+        assert_eq!(
+            world.bank.token_account(&user_pool).amount,
+            pool_tokens + 2 * deposited + deposited / 10
+        );
+    }
+
+    #[test]
+    fn deposits_minting_nothing_are_too_small() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let deposit = |source_token_amount| {
+            instruction::deposit_single_token_type_exact_amount_in(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user.tokens[&pool.mint_a],
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user.pool_tokens[0],
+                instruction::DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount: 0,
+                },
+            )
+            .unwrap()
+        };
+        // a pool token is worth a hundred of each token, one token mints nothing
+        let (too_small, mints) = (deposit(1), deposit(INITIAL_RESERVE / 1_000));
+
+        assert_eq!(
+            world.bank.process(&too_small),
+            Err(SwapError::AmountTooSmall.into())
+        );
+        assert_eq!(world.bank.process(&mints), Ok(()));
+    }
+
+    #[test]
+    fn single_sided_deposits_and_withdrawals_count_towards_the_volume_cap() {
+        let mut world = World::new();
+        // pool tokens to withdraw, without a deposit counting towards the cap
+        let (pool_mint, user_pool) = (world.pools[1].pool_mint, world.users[0].pool_tokens[1]);
+        let supply = world.bank.mint(&pool_mint).supply;
+        world.fund(&pool_mint, &user_pool, supply / 10);
+        // pool 1 caps its volume at INITIAL_RESERVE / 100 of token A a slot
+        let pool = &world.pools[1];
+        let user = &world.users[0];
+        let cap = INITIAL_RESERVE / 100;
+        let deposit_single = |mint: &Pubkey, amount| {
+            instruction::deposit_single_token_type_exact_amount_in(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user.tokens[mint],
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user_pool,
+                instruction::DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount: amount,
+                    minimum_pool_token_amount: 0,
+                },
+            )
+            .unwrap()
+        };
+        let withdraw_single = |amount| {
+            instruction::withdraw_single_token_type_exact_amount_out(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &pool.pool_mint,
+                &pool.fee_account,
+                &user_pool,
+                &pool.token_a,
+                &pool.token_b,
+                &user.tokens[&pool.mint_a],
+                instruction::WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount: amount,
+                    maximum_pool_token_amount: u64::MAX,
+                },
+            )
+            .unwrap()
+        };
+        let (deposit_a, deposit_b) = (
+            deposit_single(&pool.mint_a, cap / 2),
+            deposit_single(&pool.mint_b, cap * 6 / 10),
+        );
+        let (withdraw_over, withdraw) = (withdraw_single(cap * 6 / 10), withdraw_single(cap / 10));
+
+        world.bank.process(&deposit_a).unwrap();
+        // token B counts at the reserves' ratio, near one to one here
+        assert_eq!(
+            world.bank.process(&deposit_b),
+            Err(SwapError::RateLimited.into())
+        );
+        assert_eq!(
+            world.bank.process(&withdraw_over),
+            Err(SwapError::RateLimited.into())
+        );
+        SLOT.with(|slot| slot.set(slot.get() + 1));
+        world.bank.process(&deposit_b).unwrap();
+        world.bank.process(&withdraw).unwrap();
+    }
+
+    #[test]
+    fn single_sided_deposits_into_an_empty_reserve_skip_the_volume_cap() {
+        let mut world = World::new();
+        // a concentrated pool opening at the bottom of its range holds no token B
+        // to value a deposit of it against
+        let reserve = INITIAL_RESERVE / 200;
+        world
+            .add_pool(
+                world.mints[0],
+                world.mints[1],
+                CurveType::Concentrated,
+                reserve / 100,
+                (reserve, 0),
+            )
+            .unwrap();
+        let pool = world.pools.last().unwrap();
+        let user = &world.users[0];
+        let user_pool = world
+            .bank
+            .add_token_account(&pool.pool_mint, &user.wallet, 0);
+        let deposit_b = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user.tokens[&pool.mint_b],
+            &pool.token_a,
+            &pool.token_b,
+            &pool.pool_mint,
+            &user_pool,
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 1_000,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&deposit_b).unwrap();
+        assert!(world.bank.token_account(&user_pool).amount > 0);
+    }
+
+    #[test]
+    fn referred_deposits_are_counted_per_referrer_and_pool() {
+        let mut world = World::new();
+        let referrer = world.users[1].wallet;
+        let unregistered = world.users[2].wallet;
+        let swap = world.pools[0].swap;
+        // the harness doesn't run the system program, so the accounts are
+        // created up front, as registering and the first referred deposit would
+        let (referrer_account, _) = Referrer::find_address(&referrer, &program_id());
+        let (referred, _) = ReferredDeposits::find_address(&swap, &referrer, &program_id());
+        for (key, len) in [
+            (referrer_account, Referrer::LEN),
+            (referred, ReferredDeposits::LEN),
+        ] {
+            world.bank.accounts.insert(
+                key,
+                TestAccount {
+                    lamports: 1_000_000_000,
+                    data: vec![0; len],
+                    owner: program_id(),
+                },
+            );
+        }
+        let register = |host_fee_numerator| {
+            instruction::register_referrer(
+                &program_id(),
+                &referrer,
+                &referrer,
+                None,
+                instruction::RegisterReferrer {
+                    host_fee_numerator,
+                    host_fee_denominator: 10,
+                },
+            )
+            .unwrap()
+        };
+
+        let pool = &world.pools[0];
+        let deposit = |depositor: &User, referrer: &Pubkey| {
+            let deposit = instruction::deposit_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &depositor.wallet,
+                &depositor.tokens[&pool.mint_a],
+                &depositor.tokens[&pool.mint_b],
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &depositor.pool_tokens[0],
+                DepositAllTokenTypes {
+                    pool_token_amount: 1_000_000,
+                    maximum_token_a_amount: u64::MAX,
+                    maximum_token_b_amount: u64::MAX,
+                },
+            )
+            .unwrap();
+            instruction::with_referrer(deposit, &program_id(), referrer, &depositor.wallet).unwrap()
+        };
+        let referred_deposit = deposit(&world.users[0], &referrer);
+        let self_referred = deposit(&world.users[1], &referrer);
+        let unregistered_referrer = deposit(&world.users[0], &unregistered);
+
+        // the referrer's account exists, but isn't registered yet
+        assert_eq!(
+            world.bank.process(&referred_deposit),
+            Err(SwapError::InvalidReferrer.into())
+        );
+        // without constraints, a referrer still can't take more than the default
+        // ceiling of the owner fee
+        assert_eq!(
+            world.bank.process(&register(6)),
+            Err(SwapError::InvalidFee.into())
+        );
+        world.bank.process(&register(1)).unwrap();
+
+        assert_eq!(
+            world.bank.process(&self_referred),
+            Err(SwapError::InvalidReferrer.into())
+        );
+        assert_eq!(
+            world.bank.process(&unregistered_referrer),
+            Err(SwapError::InvalidReferrer.into())
+        );
+        world.bank.process(&referred_deposit).unwrap();
+        EVENTS.with(|events| events.borrow_mut().clear());
+        world.bank.process(&referred_deposit).unwrap();
+
+        let counted = ReferredDeposits::unpack(&world.bank.accounts[&referred].data).unwrap();
+        assert_eq!(counted.swap, swap);
+        assert_eq!(counted.referrer, referrer);
+        assert_eq!(counted.deposits, 2);
+        assert_eq!(counted.pool_token_amount, 2_000_000);
+        // a pool token is worth a hundred of each token, rounded up
+        assert!(counted.token_a_amount >= 200_000_000 && counted.token_b_amount >= 200_000_000);
+        let events = EVENTS.with(|events| events.take());
+        match events.last() {
+            Some(Event::DepositReferred(event)) => {
+                assert_eq!(event.depositor, world.users[0].wallet);
+                assert_eq!(event.referrer, referrer);
+                assert_eq!(event.pool_token_amount, 1_000_000);
+                assert_eq!(event.deposits, 2);
+                assert_eq!(event.total_pool_token_amount, 2_000_000);
+            }
+            other => panic!("expected a referred deposit, got {:?}", other),
+        }
+    }
+}
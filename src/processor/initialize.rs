@@ -0,0 +1,744 @@
+//! Creating pools, and releasing the vested initial supply to their creators.
+
+use crate::amount::PoolTokenAmount;
+use crate::constants::{MAX_FEE_EXEMPT, MAX_TOKEN_DECIMALS, VESTING_SEED};
+use crate::constraints::SwapConstraints;
+use crate::curve::base::SwapCurve;
+use crate::curve::calculator::TradeDirection;
+use crate::curve::fees::{FeeTiers, Fees};
+use crate::error::SwapError;
+use crate::events::{Event, InitialPriceEvent, PoolInitializedEvent, VestedTokensClaimedEvent};
+use crate::state::{read_oracle_price, PoolTemplate, ProgramConfig, SwapV2, SwapVersion, Vesting};
+use crate::token_ops;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program::invoke_signed;
+use solana_program::program_error::ProgramError;
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_instruction;
+use solana_program::sysvar::{clock::Clock, rent::Rent, Sysvar};
+use spl_math::precise_number::PreciseNumber;
+
+use super::Processor;
+
+impl Processor {
+    // 1)checks a bunch, 2)mints tokens into dest acc, 3)saves state into swap_info acc
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_initialize(
+        program_id: &Pubkey,
+        nonce: u8,
+        fees: Fees,
+        swap_curve: SwapCurve,
+        vesting_slots: u64,
+        max_slot_volume: u64,
+        fee_holiday_end_slot: u64,
+        initial_price_band_bps: u16,
+        oracle_price_offset: u32,
+        from_template: bool,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?; //this will hold the state for a given pool, eg RAY-SOL
+        let authority_info = next_account_info(account_info_iter)?; //authority over pool tokens, YES, derived from the token swap program
+        let token_a_info = next_account_info(account_info_iter)?; //account that will hold token A for the pool
+        let token_b_info = next_account_info(account_info_iter)?; //account that will hold token B for the pool
+        let pool_mint_info = next_account_info(account_info_iter)?; //this is the mint address for the pool tokens - eg lpRAYSOL whatever
+        let fee_account_info = next_account_info(account_info_iter)?; //this is where the fees will accrue
+        let destination_info = next_account_info(account_info_iter)?; //this is where the pool tokens will be initially minted into
+        let token_program_info = next_account_info(account_info_iter)?;
+        let token_a_mint_info = next_account_info(account_info_iter)?;
+        let token_b_mint_info = next_account_info(account_info_iter)?;
+
+        let token_program_id = *token_program_info.key;
+
+        let token_a = Self::unpack_token_account(token_a_info, &token_program_id)?;
+        let token_b = Self::unpack_token_account(token_b_info, &token_program_id)?;
+        let fee_account = Self::unpack_token_account(fee_account_info, &token_program_id)?;
+        let destination = Self::unpack_user_token_account(destination_info, &token_program_id)?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, &token_program_id)?;
+
+        // pool tokens can only ever be minted against the reserves: any supply
+        // out there already, or anyone else able to mint or freeze, and the
+        // LPs' shares stop adding up to the pool
+        let authority = Self::authority_id(program_id, swap_info.key, nonce)?;
+        if *authority_info.key != authority {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if pool_mint.mint_authority != COption::Some(authority) {
+            return Err(SwapError::InvalidMintAuthority.into());
+        }
+        if pool_mint.supply != 0 {
+            return Err(SwapError::InvalidSupply.into());
+        }
+        if pool_mint.freeze_authority.is_some() {
+            return Err(SwapError::InvalidFreezeAuthority.into());
+        }
+
+        // past 18 decimals the raw unit prices between the two sides get too
+        // far apart for the curves' fixed point math.  They're recorded for
+        // clients to format amounts with, without reading the mints
+        let read_decimals = |token: &spl_token::state::Account, mint_info: &AccountInfo| {
+            if token.mint != *mint_info.key {
+                return Err(SwapError::IncorrectSwapAccount);
+            }
+            let decimals = Self::unpack_mint(mint_info, &token_program_id)?.decimals;
+            if decimals > MAX_TOKEN_DECIMALS {
+                return Err(SwapError::UnsupportedMintDecimals);
+            }
+            Ok(decimals)
+        };
+        let token_a_decimals = read_decimals(&token_a, token_a_mint_info)?;
+        let token_b_decimals = read_decimals(&token_b, token_b_mint_info)?;
+
+        // check that both accounts A and B have some initial tokens in them
+        // (!) newly created pool has to be immediately available for trading, which is why it can't be started with 0 balances in either/both
+        swap_curve
+            .calculator
+            .validate_supply(token_a.amount, token_b.amount)?;
+
+        // check that
+        // 1)curve is one of allowed types and
+        // 2)fees are reasonable (numerator has to be higher or above)
+        if let Some(swap_constraints) = swap_constraints {
+            let owner_key = swap_constraints
+                .owner_key
+                .parse::<Pubkey>()
+                .map_err(|_| SwapError::InvalidOwner)?;
+            if fee_account.owner != owner_key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            swap_constraints.validate_curve(&swap_curve)?;
+            swap_constraints.validate_fees(&fees)?;
+            swap_constraints.validate_template(from_template)?;
+            swap_constraints.validate_pool_mint_decimals(pool_mint.decimals)?;
+            if fee_holiday_end_slot != 0 {
+                swap_constraints.validate_fee_holiday(fee_holiday_end_slot, Clock::get()?.slot)?;
+            }
+        }
+
+        //checks fee denominators aren't 0 and that numerator < denominator
+        fees.validate()?;
+
+        //validates that the given curve has no invalid params
+        swap_curve.calculator.validate()?;
+
+        //initial amount of tokens in pool is a constant of 1_000_000_000
+        //(!) My understanding is that this initial supply is never actually withdrawn, it's simply sitting there to be used as a denominator for calculating how many tokens to issue to users
+        let initial_amount =
+            PoolTokenAmount::new(swap_curve.calculator.new_pool_supply()).to_u64()?;
+
+        // optionally lock the initial supply up, to be released linearly to the creator
+        if vesting_slots > 0 {
+            let vesting_info = next_account_info(account_info_iter)?;
+            let beneficiary_info = next_account_info(account_info_iter)?; //creator's pool token account
+            let payer_info = next_account_info(account_info_iter)?;
+            let system_program_info = next_account_info(account_info_iter)?;
+
+            // the destination becomes the vault, only the swap authority can move tokens out
+            if destination.owner != *authority_info.key {
+                return Err(SwapError::InvalidOwner.into());
+            }
+            let beneficiary = Self::unpack_user_token_account(beneficiary_info, &token_program_id)?;
+            if beneficiary.mint != *pool_mint_info.key {
+                return Err(SwapError::IncorrectPoolMint.into());
+            }
+
+            let (vesting_key, bump_seed) = Vesting::find_address(swap_info.key, program_id);
+            if *vesting_info.key != vesting_key {
+                return Err(SwapError::InvalidProgramAddress.into());
+            }
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    vesting_info.key,
+                    rent.minimum_balance(Vesting::LEN),
+                    Vesting::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    vesting_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[VESTING_SEED, swap_info.key.as_ref(), &[bump_seed]]],
+            )?;
+
+            let vesting = Vesting {
+                is_initialized: true,
+                bump_seed,
+                swap: *swap_info.key,
+                vault: *destination_info.key,
+                beneficiary: *beneficiary_info.key,
+                total_amount: initial_amount,
+                claimed_amount: 0,
+                start_slot: Clock::get()?.slot,
+                duration_slots: vesting_slots,
+            };
+            Vesting::pack(vesting, &mut vesting_info.data.borrow_mut())?;
+        }
+
+        // the price the deposits open the pool at, token A per token B.  a
+        // lopsided seed opens it far from the market, handing the difference
+        // to the first arbitrageur, so the creator can have it checked
+        // against an oracle
+        let initial_price = swap_curve
+            .calculator
+            .spot_price(
+                u128::from(token_b.amount),
+                u128::from(token_a.amount),
+                TradeDirection::BtoA,
+            )
+            .ok_or(SwapError::CalculationFailure)?;
+        let oracle_price = if initial_price_band_bps > 0 {
+            let oracle_info = next_account_info(account_info_iter)?;
+            let oracle_price = read_oracle_price(&oracle_info.data.borrow(), oracle_price_offset)
+                .filter(|price| *price > 0)
+                .ok_or(SwapError::InvalidOracle)?;
+            let oracle = PreciseNumber::new(u128::from(oracle_price))
+                .ok_or(SwapError::CalculationFailure)?;
+            let band = PreciseNumber::new(u128::from(initial_price_band_bps))
+                .and_then(|band_bps| oracle.checked_mul(&band_bps))
+                .and_then(|band| band.checked_div(&PreciseNumber::new(10_000)?))
+                .ok_or(SwapError::CalculationFailure)?;
+            let (deviation, _) = initial_price.unsigned_sub(&oracle);
+            if deviation.greater_than(&band) {
+                return Err(SwapError::InitialPriceOutsideBand.into());
+            }
+            oracle_price
+        } else {
+            0
+        };
+
+        // integrators get an on-chain record of who can change the program under them
+        if let Some(swap_constraints) = swap_constraints {
+            if swap_constraints.require_documented_upgrade_authority {
+                let program_config_info = next_account_info(account_info_iter)?;
+                let program_data_info = next_account_info(account_info_iter)?;
+                let upgrade_authority = Self::upgrade_authority(program_id, program_data_info)?;
+                if *program_config_info.key != ProgramConfig::find_address(program_id).0
+                    || program_config_info.owner != program_id
+                {
+                    return Err(SwapError::UndocumentedUpgradeAuthority.into());
+                }
+                let program_config =
+                    ProgramConfig::unpack_outdated(&program_config_info.data.borrow())?;
+                if !program_config.documents(upgrade_authority) {
+                    return Err(SwapError::UndocumentedUpgradeAuthority.into());
+                }
+            }
+        }
+
+        //invokes the spl program to mint tokens
+        token_ops::mint_to(
+            swap_info.key,
+            token_program_info.clone(),
+            pool_mint_info.clone(),
+            destination_info.clone(), //mints to destination addr
+            authority_info.clone(),
+            nonce,
+            initial_amount,
+        )?;
+
+        Event::PoolInitialized(PoolInitializedEvent {
+            swap: *swap_info.key,
+            pool_mint: *pool_mint_info.key,
+            token_a_amount: token_a.amount,
+            token_b_amount: token_b.amount,
+            pool_token_amount: initial_amount,
+        })
+        .emit();
+        Event::InitialPrice(
+            InitialPriceEvent::new(*swap_info.key, &initial_price, oracle_price)
+                .ok_or(SwapError::CalculationFailure)?,
+        )
+        .emit();
+
+        let zero_fee = fees.is_zero();
+        // create the state for the given pool
+        let obj = SwapVersion::SwapV2(Box::new(SwapV2 {
+            is_initialized: true,
+            nonce,
+            token_program_id,
+            token_a: *token_a_info.key,
+            token_b: *token_b_info.key,
+            pool_mint: *pool_mint_info.key,
+            token_a_mint: token_a.mint,
+            token_b_mint: token_b.mint,
+            pool_fee_account: *fee_account_info.key,
+            fees,
+            swap_curve,
+            pool_mint_decimals: pool_mint.decimals,
+            max_slot_volume,
+            last_slot: 0,
+            slot_volume: 0,
+            locked_pool_tokens: 0,
+            zero_fee,
+            rebalancer: Pubkey::default(),
+            emergency: false,
+            bonus_start_slot: 0,
+            bonus_end_slot: 0,
+            bonus_bps: 0,
+            fee_exempt: [Pubkey::default(); MAX_FEE_EXEMPT],
+            withdraw_fee_in_underlying: false,
+            withdraw_fee_vault_a: Pubkey::default(),
+            withdraw_fee_vault_b: Pubkey::default(),
+            fee_tiers: FeeTiers::default(),
+            exact_input: false,
+            fee_holiday_end_slot,
+            stake_pool: Pubkey::default(),
+            rate_update_slot: 0,
+            last_trade_slot: 0,
+            default_host_fee_account: Pubkey::default(),
+            unclaimed_host_fees: 0,
+            token_a_decimals,
+            token_b_decimals,
+            interactions: 0,
+            trade_fees_a: 0,
+            trade_fees_b: 0,
+            withdraw_fees: 0,
+            last_checkpoint_hash: [0; 32],
+            owner_fee_account_a: Pubkey::default(),
+            owner_fee_account_b: Pubkey::default(),
+            fee_conversion_pool: Pubkey::default(),
+            fee_conversion_account: Pubkey::default(),
+            fee_conversion_from_token_b: false,
+            withdraw_cooldown_slots: 0,
+            gate: Pubkey::default(),
+            gate_credential: false,
+            single_withdraw_fee_to_lps: false,
+            sandwich_guard: false,
+            owner_fee_fallback: false,
+            nested_pool: Pubkey::default(),
+            bonded_keepers_only: false,
+            paused: false,
+            bonus_lockup_slots: 0,
+            fee_conversion_max_slippage_bps: 0,
+        }));
+        // packs that state into the data of the swap_info account
+        SwapVersion::pack(obj, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    // releases whatever vested since the last claim to the pool creator
+    pub fn process_claim_vested_pool_tokens(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let vesting_info = next_account_info(account_info_iter)?;
+        let vault_info = next_account_info(account_info_iter)?; //holds the unvested supply
+        let beneficiary_info = next_account_info(account_info_iter)?; //creator's pool token account
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+
+        if vesting_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let mut vesting = Vesting::unpack(&vesting_info.data.borrow())?;
+        if vesting.swap != *swap_info.key
+            || vesting.vault != *vault_info.key
+            || vesting.beneficiary != *beneficiary_info.key
+        {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+
+        Self::check_user_token_accounts(token_swap.token_program_id(), &[beneficiary_info])?;
+
+        let claimable_amount = vesting
+            .claimable_amount(Clock::get()?.slot)
+            .ok_or(SwapError::CalculationFailure)?;
+        if claimable_amount == 0 {
+            return Err(SwapError::ZeroTradingTokens.into());
+        }
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            vault_info.clone(),
+            beneficiary_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            claimable_amount,
+        )?;
+
+        vesting.claimed_amount = vesting
+            .claimed_amount
+            .checked_add(claimable_amount)
+            .ok_or(SwapError::CalculationFailure)?;
+        Vesting::pack(vesting, &mut vesting_info.data.borrow_mut())?;
+
+        Event::VestedTokensClaimed(VestedTokensClaimedEvent {
+            swap: *swap_info.key,
+            beneficiary: *beneficiary_info.key,
+            amount: claimable_amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // initializes a pool with the fees and curve of a vetted template
+    #[allow(clippy::too_many_arguments)]
+    pub fn process_initialize_from_template(
+        program_id: &Pubkey,
+        nonce: u8,
+        template: u8,
+        vesting_slots: u64,
+        max_slot_volume: u64,
+        fee_holiday_end_slot: u64,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let (template_info, accounts) = accounts
+            .split_first()
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let (template_key, _bump_seed) = PoolTemplate::find_address(template, program_id);
+        if *template_info.key != template_key || template_info.owner != program_id {
+            return Err(SwapError::InvalidPoolTemplate.into());
+        }
+        let template = PoolTemplate::unpack(&template_info.data.borrow())
+            .map_err(|_| SwapError::InvalidPoolTemplate)?;
+        Self::process_initialize(
+            program_id,
+            nonce,
+            template.fees,
+            template.swap_curve,
+            vesting_slots,
+            max_slot_volume,
+            fee_holiday_end_slot,
+            0,
+            0,
+            true,
+            accounts,
+            swap_constraints,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::base::{CurveType, SwapCurve};
+    use crate::curve::constant_product::ConstantProductCurve;
+    use crate::curve::fees::Fees;
+    use crate::error::SwapError;
+    use crate::events::{Event, PRICE_SCALE};
+    use crate::instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes};
+    use crate::processor::test::{program_id, TestAccount, World, EVENTS, INITIAL_RESERVE};
+    use crate::state::{PoolTemplate, SwapVersion};
+    use solana_program::program_option::COption;
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use spl_token::state::Mint;
+    use std::collections::HashMap;
+
+    #[test]
+    fn vested_supply_cant_be_withdrawn_through_the_swap_authority() {
+        let mut world = World::new();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account) = (pool.pool_mint, pool.fee_account);
+        let user = &world.users[0];
+        let (user_a, user_b, user_pool) = (
+            user.tokens[&pool.mint_a],
+            user.tokens[&pool.mint_b],
+            user.pool_tokens[0],
+        );
+        // the vault a vesting pool's initial supply is minted into, owned by the
+        // swap authority until it vests
+        let vault = world.bank.add_token_account(&pool_mint, &authority, 0);
+        let vested = world.bank.mint(&pool_mint).supply;
+        world.fund(&pool_mint, &vault, vested);
+
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &authority,
+            &pool_mint,
+            &fee_account,
+            &vault,
+            &token_a,
+            &token_b,
+            &user_a,
+            &user_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: vested,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        let withdraw_max = instruction::withdraw_max(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &authority,
+            &pool_mint,
+            &fee_account,
+            &vault,
+            &token_a,
+            &token_b,
+            &user_a,
+            &user_b,
+            instruction::WithdrawMax {
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        let withdraw_single = instruction::withdraw_single_token_type_exact_amount_out(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &authority,
+            &pool_mint,
+            &fee_account,
+            &vault,
+            &token_a,
+            &token_b,
+            &user_a,
+            instruction::WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount: INITIAL_RESERVE / 10,
+                maximum_pool_token_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        // nor can the reserves be deposited as someone's own
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &authority,
+            &token_a,
+            &token_b,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: vested / 10,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+
+        for stolen in [&withdraw, &withdraw_max, &withdraw_single, &deposit] {
+            assert_eq!(
+                world.bank.process(stolen),
+                Err(SwapError::SwapAuthorityTransfer.into())
+            );
+        }
+        assert_eq!(world.bank.token_account(&vault).amount, vested);
+        world.assert_conserved(&trading_supplies);
+    }
+
+    #[test]
+    fn pools_can_be_created_from_a_template() {
+        let mut world = World::new();
+        let (mint_a, mint_b) = (world.mints[0], world.mints[1]);
+        let pool = world.new_pool(mint_a, mint_b, (INITIAL_RESERVE, INITIAL_RESERVE), |_| {});
+
+        // the upgrade authority documented, template accounts already created
+        let upgrade_authority = Pubkey::new_unique();
+        world.set_program_config(upgrade_authority, Pubkey::default());
+        for index in 0..2 {
+            let (template, _bump_seed) = PoolTemplate::find_address(index, &program_id());
+            world.allocate(template, PoolTemplate::LEN);
+        }
+
+        let fees = Fees {
+            trade_fee_numerator: 25,
+            trade_fee_denominator: 10000,
+            owner_trade_fee_numerator: 5,
+            owner_trade_fee_denominator: 10000,
+            owner_withdraw_fee_numerator: 0,
+            owner_withdraw_fee_denominator: 0,
+            host_fee_numerator: 20,
+            host_fee_denominator: 100,
+        };
+        let swap_curve = || SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        };
+        let set_pool_template = |signer: &Pubkey| {
+            instruction::set_pool_template(
+                &program_id(),
+                signer,
+                signer,
+                instruction::SetPoolTemplate {
+                    index: 1,
+                    fees: fees.clone(),
+                    swap_curve: swap_curve(),
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&set_pool_template(&pool.admin)),
+            Err(SwapError::InvalidOwner.into())
+        );
+        world
+            .bank
+            .process(&set_pool_template(&upgrade_authority))
+            .unwrap();
+
+        let initialize_from_template = |template: u8| {
+            instruction::initialize_from_template(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &pool.fee_account,
+                &pool.destination,
+                &pool.mint_a,
+                &pool.mint_b,
+                instruction::InitializeFromTemplate {
+                    nonce: pool.nonce,
+                    template,
+                    vesting_slots: 0,
+                    max_slot_volume: 0,
+                    fee_holiday_end_slot: 0,
+                },
+                None,
+                None,
+            )
+            .unwrap()
+        };
+
+        // never set, or never created
+        for template in [0, 2] {
+            assert_eq!(
+                world.bank.process(&initialize_from_template(template)),
+                Err(SwapError::InvalidPoolTemplate.into())
+            );
+        }
+
+        world.bank.process(&initialize_from_template(1)).unwrap();
+        let state =
+            match SwapVersion::unpack_versioned(&world.bank.accounts[&pool.swap].data).unwrap() {
+                SwapVersion::SwapV2(state) => state,
+                _ => unreachable!(),
+            };
+        assert_eq!(state.fees, fees);
+        assert_eq!(state.swap_curve, swap_curve());
+        assert!(world.bank.token_account(&pool.destination).amount > 0);
+    }
+
+    #[test]
+    fn pools_open_within_the_oracle_price_band() {
+        let mut world = World::new();
+        let (mint_a, mint_b) = (world.mints[0], world.mints[1]);
+        // seeded 3 A per B
+        let pool = world.new_pool(
+            mint_a,
+            mint_b,
+            (3 * INITIAL_RESERVE, INITIAL_RESERVE),
+            |_| {},
+        );
+        // the price sits after an 8 byte header
+        let oracle = Pubkey::new_unique();
+
+        let initialize_against = |world: &mut World, oracle_price: u64| {
+            let mut data = vec![0; 16];
+            data[8..].copy_from_slice(&oracle_price.to_le_bytes());
+            world.bank.accounts.insert(
+                oracle,
+                TestAccount {
+                    lamports: 1_000_000,
+                    data,
+                    owner: Pubkey::new_unique(),
+                },
+            );
+            let initialize = pool.initialize(
+                Fees::default(),
+                SwapCurve {
+                    curve_type: CurveType::ConstantProduct,
+                    calculator: Box::new(ConstantProductCurve {}),
+                },
+                0,
+            );
+            let initialize =
+                instruction::with_initial_price_oracle(initialize, &oracle, 8, 1_000).unwrap();
+            world.bank.process(&initialize)
+        };
+
+        // 3 is 50% above an oracle price of 2, outside a 10% band
+        assert_eq!(
+            initialize_against(&mut world, 2),
+            Err(SwapError::InitialPriceOutsideBand.into())
+        );
+        assert_eq!(
+            initialize_against(&mut world, 0),
+            Err(SwapError::InvalidOracle.into())
+        );
+
+        EVENTS.with(|events| events.borrow_mut().clear());
+        initialize_against(&mut world, 3).unwrap();
+        let initial_price = EVENTS.with(|events| {
+            events.take().into_iter().find_map(|event| match event {
+                Event::InitialPrice(initial_price) => Some(initial_price),
+                _ => None,
+            })
+        });
+        let initial_price = initial_price.unwrap();
+        assert_eq!(initial_price.price, 3 * PRICE_SCALE);
+        assert_eq!(initial_price.oracle_price, 3);
+    }
+
+    #[test]
+    fn pool_mints_must_start_empty_and_the_swaps_alone() {
+        let mut world = World::new();
+        let (mint_a, mint_b) = (world.pools[0].mint_a, world.pools[0].mint_b);
+        let mut add_pool = |edit_pool_mint: fn(&mut Mint)| {
+            world.add_pool_with_mint(
+                mint_a,
+                mint_b,
+                CurveType::ConstantProduct,
+                0,
+                (1_000_000, 1_000_000),
+                edit_pool_mint,
+            )
+        };
+
+        assert_eq!(
+            add_pool(|mint| mint.supply = 1),
+            Err(SwapError::InvalidSupply.into())
+        );
+        assert_eq!(
+            add_pool(|mint| mint.mint_authority = COption::Some(Pubkey::new_unique())),
+            Err(SwapError::InvalidMintAuthority.into())
+        );
+        assert_eq!(
+            add_pool(|mint| mint.mint_authority = COption::None),
+            Err(SwapError::InvalidMintAuthority.into())
+        );
+        assert_eq!(
+            add_pool(|mint| mint.freeze_authority = COption::Some(Pubkey::new_unique())),
+            Err(SwapError::InvalidFreezeAuthority.into())
+        );
+        add_pool(|_| {}).unwrap();
+    }
+}
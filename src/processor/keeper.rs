@@ -0,0 +1,733 @@
+//! Keepers bonding lamports to crank pools that only take bonded keepers,
+//! and the check those pools' cranks go through.
+
+use crate::constants::{KEEPER_CONFIG_SEED, KEEPER_SEED};
+use crate::error::SwapError;
+use crate::events::{
+    Event, KeeperBondWithdrawnEvent, KeeperBondedEvent, KeeperConfigSetEvent, KeeperCrankedEvent,
+    KeeperSlashedEvent, KeeperUnbondingEvent,
+};
+use crate::state::{KeeperBond, KeeperConfig, OracleConfig, ProgramConfig, SwapState};
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program::{invoke, invoke_signed};
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_instruction;
+use solana_program::sysvar::{clock::Clock, rent::Rent, Sysvar};
+
+use super::Processor;
+
+impl Processor {
+    fn unpack_keeper_config(
+        program_id: &Pubkey,
+        keeper_config_info: &AccountInfo,
+    ) -> Result<KeeperConfig, ProgramError> {
+        let (keeper_config_key, _bump_seed) = KeeperConfig::find_address(program_id);
+        if *keeper_config_info.key != keeper_config_key || keeper_config_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        KeeperConfig::unpack(&keeper_config_info.data.borrow())
+    }
+
+    // the bond's address is checked against its recorded keeper and bump, so
+    // no other account of the program passes for one
+    fn unpack_keeper_bond(
+        program_id: &Pubkey,
+        keeper_bond_info: &AccountInfo,
+    ) -> Result<KeeperBond, ProgramError> {
+        if keeper_bond_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let keeper_bond = KeeperBond::unpack(&keeper_bond_info.data.borrow())?;
+        let keeper_bond_key = Pubkey::create_program_address(
+            &[
+                KEEPER_SEED,
+                keeper_bond.keeper.as_ref(),
+                &[keeper_bond.bump_seed],
+            ],
+            program_id,
+        )
+        .map_err(|_| SwapError::InvalidProgramAddress)?;
+        if *keeper_bond_info.key != keeper_bond_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        Ok(keeper_bond)
+    }
+
+    // moves lamports out of an account of the program, which the runtime
+    // lets it debit without the system program
+    fn move_lamports(from_info: &AccountInfo, to_info: &AccountInfo, amount: u64) -> ProgramResult {
+        let from_lamports = from_info
+            .lamports()
+            .checked_sub(amount)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        let to_lamports = to_info
+            .lamports()
+            .checked_add(amount)
+            .ok_or(SwapError::CalculationFailure)?;
+        **from_info.try_borrow_mut_lamports()? = from_lamports;
+        **to_info.try_borrow_mut_lamports()? = to_lamports;
+        Ok(())
+    }
+
+    // pools taking bonded keepers only need their cranks signed by a keeper
+    // bonded at least the minimum and not unbonding, whose bond then keeps
+    // the receipt, with the oracle price read if any.  the keeper's accounts
+    // come after the crank's own
+    pub(super) fn check_bonded_keeper(
+        program_id: &Pubkey,
+        token_swap: &dyn SwapState,
+        swap_info: &AccountInfo,
+        oracle_price: u64,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+    ) -> ProgramResult {
+        if !token_swap.bonded_keepers_only() {
+            return Ok(());
+        }
+        let keeper_config_info =
+            next_account_info(account_info_iter).map_err(|_| SwapError::KeeperNotBonded)?;
+        let keeper_bond_info =
+            next_account_info(account_info_iter).map_err(|_| SwapError::KeeperNotBonded)?;
+        let keeper_info =
+            next_account_info(account_info_iter).map_err(|_| SwapError::KeeperNotBonded)?;
+
+        let keeper_config = Self::unpack_keeper_config(program_id, keeper_config_info)?;
+        let mut keeper_bond = Self::unpack_keeper_bond(program_id, keeper_bond_info)?;
+        if keeper_bond.keeper != *keeper_info.key
+            || !keeper_info.is_signer
+            || !keeper_bond.is_bonded(&keeper_config)
+        {
+            return Err(SwapError::KeeperNotBonded.into());
+        }
+
+        let slot = Clock::get()?.slot;
+        keeper_bond.record_crank(*swap_info.key, slot, oracle_price);
+        KeeperBond::pack(keeper_bond, &mut keeper_bond_info.data.borrow_mut())?;
+
+        Event::KeeperCranked(KeeperCrankedEvent {
+            keeper: *keeper_info.key,
+            swap: *swap_info.key,
+            slot,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the documented upgrade authority sets the bonding terms, and slashes
+    pub fn process_set_keeper_config(
+        program_id: &Pubkey,
+        min_bond: u64,
+        unbonding_slots: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let keeper_config_info = next_account_info(account_info_iter)?;
+        let program_config_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        let (program_config_key, _bump_seed) = ProgramConfig::find_address(program_id);
+        if *program_config_info.key != program_config_key || program_config_info.owner != program_id
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let program_config = ProgramConfig::unpack_outdated(&program_config_info.data.borrow())?;
+        // an immutable program has no one left to adjudicate slashing
+        if program_config.upgrade_authority == Pubkey::default()
+            || program_config.upgrade_authority != *authority_info.key
+            || !authority_info.is_signer
+        {
+            return Err(SwapError::InvalidOwner.into());
+        }
+
+        let (keeper_config_key, bump_seed) = KeeperConfig::find_address(program_id);
+        if *keeper_config_info.key != keeper_config_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if keeper_config_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    keeper_config_info.key,
+                    rent.minimum_balance(KeeperConfig::LEN),
+                    KeeperConfig::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    keeper_config_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[KEEPER_CONFIG_SEED, &[bump_seed]]],
+            )?;
+        } else if keeper_config_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+
+        let keeper_config = KeeperConfig {
+            is_initialized: true,
+            bump_seed,
+            authority: *authority_info.key,
+            min_bond,
+            unbonding_slots,
+        };
+        KeeperConfig::pack(keeper_config, &mut keeper_config_info.data.borrow_mut())?;
+
+        Event::KeeperConfigSet(KeeperConfigSetEvent {
+            authority: *authority_info.key,
+            min_bond,
+            unbonding_slots,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // creates the keeper's bond on first use, funded on top of its rent,
+    // then adds to it.  bonding again calls off an unbonding
+    pub fn process_register_keeper(
+        program_id: &Pubkey,
+        amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let keeper_bond_info = next_account_info(account_info_iter)?;
+        let keeper_config_info = next_account_info(account_info_iter)?;
+        let keeper_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !keeper_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+        let keeper_config = Self::unpack_keeper_config(program_id, keeper_config_info)?;
+
+        let (keeper_bond_key, bump_seed) = KeeperBond::find_address(keeper_info.key, program_id);
+        if *keeper_bond_info.key != keeper_bond_key {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let mut keeper_bond = if keeper_bond_info.data_is_empty() {
+            let rent = Rent::get()?;
+            let lamports = rent
+                .minimum_balance(KeeperBond::LEN)
+                .checked_add(amount)
+                .ok_or(SwapError::CalculationFailure)?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    keeper_info.key,
+                    keeper_bond_info.key,
+                    lamports,
+                    KeeperBond::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    keeper_info.clone(),
+                    keeper_bond_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[KEEPER_SEED, keeper_info.key.as_ref(), &[bump_seed]]],
+            )?;
+            KeeperBond {
+                is_initialized: true,
+                bump_seed,
+                keeper: *keeper_info.key,
+                ..KeeperBond::default()
+            }
+        } else {
+            if keeper_bond_info.owner != program_id {
+                return Err(SwapError::InvalidProgramAddress.into());
+            }
+            invoke(
+                &system_instruction::transfer(keeper_info.key, keeper_bond_info.key, amount),
+                &[
+                    keeper_info.clone(),
+                    keeper_bond_info.clone(),
+                    system_program_info.clone(),
+                ],
+            )?;
+            // the account may have been allocated to the program up front
+            KeeperBond {
+                is_initialized: true,
+                bump_seed,
+                keeper: *keeper_info.key,
+                ..KeeperBond::unpack_unchecked(&keeper_bond_info.data.borrow())?
+            }
+        };
+
+        keeper_bond.bond = keeper_bond
+            .bond
+            .checked_add(amount)
+            .ok_or(SwapError::CalculationFailure)?;
+        keeper_bond.unbonding_slot = 0;
+        if !keeper_bond.is_bonded(&keeper_config) {
+            return Err(SwapError::KeeperNotBonded.into());
+        }
+        let bond = keeper_bond.bond;
+        KeeperBond::pack(keeper_bond, &mut keeper_bond_info.data.borrow_mut())?;
+
+        Event::KeeperBonded(KeeperBondedEvent {
+            keeper: *keeper_info.key,
+            amount,
+            bond,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // stops the keeper's cranks, its bond staying slashable until the
+    // unbonding period is over
+    pub fn process_unbond_keeper(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let keeper_bond_info = next_account_info(account_info_iter)?;
+        let keeper_config_info = next_account_info(account_info_iter)?;
+        let keeper_info = next_account_info(account_info_iter)?;
+
+        let keeper_config = Self::unpack_keeper_config(program_id, keeper_config_info)?;
+        let mut keeper_bond = Self::unpack_keeper_bond(program_id, keeper_bond_info)?;
+        if keeper_bond.keeper != *keeper_info.key || !keeper_info.is_signer {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        if keeper_bond.unbonding_slot != 0 {
+            return Err(SwapError::KeeperBondLocked.into());
+        }
+
+        keeper_bond.unbonding_slot = Clock::get()?
+            .slot
+            .saturating_add(keeper_config.unbonding_slots)
+            .max(1);
+        let (bond, unbonding_slot) = (keeper_bond.bond, keeper_bond.unbonding_slot);
+        KeeperBond::pack(keeper_bond, &mut keeper_bond_info.data.borrow_mut())?;
+
+        Event::KeeperUnbonding(KeeperUnbondingEvent {
+            keeper: *keeper_info.key,
+            bond,
+            unbonding_slot,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // pays the bond back once the unbonding period is over, the account
+    // staying for the keeper to bond again
+    pub fn process_withdraw_keeper_bond(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let keeper_bond_info = next_account_info(account_info_iter)?;
+        let keeper_info = next_account_info(account_info_iter)?;
+
+        let mut keeper_bond = Self::unpack_keeper_bond(program_id, keeper_bond_info)?;
+        if keeper_bond.keeper != *keeper_info.key || !keeper_info.is_signer {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        if keeper_bond.unbonding_slot == 0 || Clock::get()?.slot < keeper_bond.unbonding_slot {
+            return Err(SwapError::KeeperBondLocked.into());
+        }
+
+        let amount = keeper_bond.bond;
+        keeper_bond.bond = 0;
+        KeeperBond::pack(keeper_bond, &mut keeper_bond_info.data.borrow_mut())?;
+        Self::move_lamports(keeper_bond_info, keeper_info, amount)?;
+
+        Event::KeeperBondWithdrawn(KeeperBondWithdrawnEvent {
+            keeper: *keeper_info.key,
+            amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // the keeper config authority takes a bad crank's price out of the bond,
+    // bonded or unbonding.  the oracle account has to show the crank pushed
+    // a price it no longer held in the crank's own slot, and the lamports go
+    // to the keeper config rather than wherever the authority likes
+    pub fn process_slash_keeper(
+        program_id: &Pubkey,
+        amount: u64,
+        reason: u8,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let keeper_bond_info = next_account_info(account_info_iter)?;
+        let keeper_config_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let oracle_config_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+
+        let keeper_config = Self::unpack_keeper_config(program_id, keeper_config_info)?;
+        if keeper_config.authority != *authority_info.key || !authority_info.is_signer {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        let mut keeper_bond = Self::unpack_keeper_bond(program_id, keeper_bond_info)?;
+        if oracle_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let oracle_config = OracleConfig::unpack(&oracle_config_info.data.borrow())?;
+        if oracle_config.swap != keeper_bond.last_crank_swap {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if oracle_config.oracle != *oracle_info.key {
+            return Err(SwapError::InvalidOracle.into());
+        }
+        let oracle_price = oracle_config
+            .read_price(&oracle_info.data.borrow())
+            .ok_or(SwapError::InvalidOracle)?;
+        let crank_oracle_price = keeper_bond.last_crank_oracle_price;
+        if crank_oracle_price == 0
+            || keeper_bond.last_crank_slot != Clock::get()?.slot
+            || crank_oracle_price == oracle_price
+        {
+            return Err(SwapError::UnprovenSlash.into());
+        }
+        if amount > keeper_bond.max_slash() {
+            return Err(SwapError::InvalidInput.into());
+        }
+
+        keeper_bond.bond = keeper_bond
+            .bond
+            .checked_sub(amount)
+            .ok_or(ProgramError::InsufficientFunds)?;
+        // one slashing per crank
+        keeper_bond.last_crank_oracle_price = 0;
+        let keeper = keeper_bond.keeper;
+        let (last_crank_swap, last_crank_slot) =
+            (keeper_bond.last_crank_swap, keeper_bond.last_crank_slot);
+        KeeperBond::pack(keeper_bond, &mut keeper_bond_info.data.borrow_mut())?;
+        Self::move_lamports(keeper_bond_info, keeper_config_info, amount)?;
+
+        Event::KeeperSlashed(KeeperSlashedEvent {
+            keeper,
+            authority: *authority_info.key,
+            amount,
+            reason,
+            last_crank_swap,
+            last_crank_slot,
+            crank_oracle_price,
+            oracle_price,
+        })
+        .emit();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::base::CurveType;
+    use crate::error::SwapError;
+    use crate::events::Event;
+    use crate::instruction;
+    use crate::processor::test::{program_id, TestAccount, World, EVENTS, INITIAL_RESERVE, SLOT};
+    use crate::state::{stake_pool_program, KeeperBond, KeeperConfig, OracleConfig, SwapVersion};
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use solana_program::system_program;
+    use solana_program::sysvar::clock::Clock;
+    use spl_token::state::Mint;
+
+    #[test]
+    fn bonded_keepers_only_pools_take_cranks_from_bonded_keepers() {
+        // a wrapped SOL - LST pool linked to its stake pool, cranked by refreshing
+        // the rate
+        let mut world = World::new();
+        let native_mint = spl_token::native_mint::id();
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals: 9,
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        world.bank.accounts.insert(
+            native_mint,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data,
+                owner: spl_token::id(),
+            },
+        );
+        let lst_mint = world.bank.add_mint(&Pubkey::new_unique(), 9);
+        world
+            .add_pool(
+                native_mint,
+                lst_mint,
+                CurveType::ConstantPrice,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE),
+            )
+            .unwrap();
+        let mut data = vec![0; 282];
+        data[0] = 1;
+        data[162..194].copy_from_slice(lst_mint.as_ref());
+        data[258..266].copy_from_slice(&1_050_000_000_u64.to_le_bytes());
+        data[266..274].copy_from_slice(&1_000_000_000_u64.to_le_bytes());
+        data[274..282].copy_from_slice(&Clock::default().epoch.to_le_bytes());
+        let stake_pool = world.bank.add(data, stake_pool_program::id());
+        let pool = world.pools.last().unwrap();
+        let (swap, admin, fee_account) = (pool.swap, pool.admin, pool.fee_account);
+        let set_stake_pool = instruction::set_stake_pool(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            instruction::SetStakePool { stake_pool },
+        )
+        .unwrap();
+        world.bank.process(&set_stake_pool).unwrap();
+
+        // and a pool pegged to an oracle publishing its price after an 8 byte
+        // header, whose oracle cranks can be slashed
+        let peg_mint = world.bank.add_mint(&Pubkey::new_unique(), 9);
+        world
+            .add_pool(
+                native_mint,
+                peg_mint,
+                CurveType::ConstantPrice,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE),
+            )
+            .unwrap();
+        let pegged = world.pools.last().unwrap();
+        let (pegged_swap, pegged_admin, pegged_fee_account) =
+            (pegged.swap, pegged.admin, pegged.fee_account);
+        let oracle = world.bank.add(vec![0; 16], Pubkey::new_unique());
+        let publish = |world: &mut World, price: u64| {
+            world.bank.accounts.get_mut(&oracle).unwrap().data[8..]
+                .copy_from_slice(&price.to_le_bytes())
+        };
+        publish(&mut world, 1_000_000_000);
+        let (oracle_config, _) = OracleConfig::find_address(&pegged_swap, &program_id());
+        world.bank.accounts.insert(
+            oracle_config,
+            TestAccount {
+                lamports: 1_000_000,
+                data: vec![0; OracleConfig::LEN],
+                owner: program_id(),
+            },
+        );
+        let set_oracle_config = instruction::set_oracle_config(
+            &program_id(),
+            &pegged_swap,
+            &pegged_admin,
+            &pegged_fee_account,
+            &oracle,
+            &pegged_admin,
+            instruction::SetOracleConfig {
+                price_offset: 8,
+                min_price: 1,
+                max_price: u64::MAX,
+                max_step_bps: 10_000,
+            },
+        )
+        .unwrap();
+        world.bank.process(&set_oracle_config).unwrap();
+
+        // the upgrade authority documented, and the accounts the harness can't
+        // create allocated up front
+        let upgrade_authority = Pubkey::new_unique();
+        let keeper = Pubkey::new_unique();
+        world.set_program_config(upgrade_authority, Pubkey::default());
+        let (keeper_config, _) = KeeperConfig::find_address(&program_id());
+        world.allocate(keeper_config, KeeperConfig::LEN);
+        let (keeper_bond, _) = KeeperBond::find_address(&keeper, &program_id());
+        world.allocate(keeper_bond, KeeperBond::LEN);
+        world.bank.accounts.insert(
+            keeper,
+            TestAccount {
+                lamports: 10_000_000,
+                data: vec![],
+                owner: system_program::id(),
+            },
+        );
+
+        let set_keeper_config = |authority: &Pubkey| {
+            instruction::set_keeper_config(
+                &program_id(),
+                authority,
+                authority,
+                instruction::SetKeeperConfig {
+                    min_bond: 1_000_000,
+                    unbonding_slots: 100,
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&set_keeper_config(&keeper)),
+            Err(SwapError::InvalidOwner.into())
+        );
+        world
+            .bank
+            .process(&set_keeper_config(&upgrade_authority))
+            .unwrap();
+
+        let register = |amount| {
+            instruction::register_keeper(
+                &program_id(),
+                &keeper,
+                instruction::RegisterKeeper { amount },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&register(999_999)),
+            Err(SwapError::KeeperNotBonded.into())
+        );
+        world.bank.process(&register(1_000_000)).unwrap();
+        assert_eq!(world.bank.accounts[&keeper].lamports, 9_000_000);
+        assert_eq!(world.bank.accounts[&keeper_bond].lamports, 2_000_000);
+
+        let set_bonded_keepers_only = instruction::set_bonded_keepers_only(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            instruction::SetBondedKeepersOnly { enabled: true },
+        )
+        .unwrap();
+        world.bank.process(&set_bonded_keepers_only).unwrap();
+        assert!(SwapVersion::unpack(&world.bank.accounts[&swap].data)
+            .unwrap()
+            .bonded_keepers_only());
+        let set_bonded_keepers_only = instruction::set_bonded_keepers_only(
+            &program_id(),
+            &pegged_swap,
+            &pegged_admin,
+            &pegged_fee_account,
+            instruction::SetBondedKeepersOnly { enabled: true },
+        )
+        .unwrap();
+        world.bank.process(&set_bonded_keepers_only).unwrap();
+
+        // anyone's refresh is refused, the keeper's goes through with a receipt
+        let refresh =
+            instruction::refresh_stake_pool_rate(&program_id(), &swap, &stake_pool).unwrap();
+        let refresh_by = |keeper: &Pubkey| {
+            instruction::with_keeper(refresh.clone(), &program_id(), keeper).unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&refresh),
+            Err(SwapError::KeeperNotBonded.into())
+        );
+        assert_eq!(
+            world.bank.process(&refresh_by(&Pubkey::new_unique())),
+            Err(SwapError::InvalidProgramAddress.into())
+        );
+        EVENTS.with(|events| events.borrow_mut().clear());
+        world.bank.process(&refresh_by(&keeper)).unwrap();
+        let bond = KeeperBond::unpack(&world.bank.accounts[&keeper_bond].data).unwrap();
+        assert_eq!(bond.cranks, 1);
+        assert_eq!(bond.last_crank_swap, swap);
+        let events = EVENTS.with(|events| events.take());
+        assert!(matches!(
+            events.first(),
+            Some(Event::KeeperCranked(event)) if event.keeper == keeper && event.swap == swap
+        ));
+
+        // a slashing needs the oracle to contradict the keeper's last crank in
+        // the crank's slot, which a rate refresh can't be
+        let slash = |authority: &Pubkey, amount| {
+            instruction::slash_keeper(
+                &program_id(),
+                authority,
+                &keeper,
+                &pegged_swap,
+                &oracle,
+                instruction::SlashKeeper {
+                    amount,
+                    reason: instruction::SLASH_STALE_ORACLE,
+                },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&slash(&upgrade_authority, 400_000)),
+            Err(SwapError::IncorrectSwapAccount.into())
+        );
+        let crank = instruction::with_keeper(
+            instruction::crank_oracle_price(&program_id(), &pegged_swap, &oracle).unwrap(),
+            &program_id(),
+            &keeper,
+        )
+        .unwrap();
+        world.bank.process(&crank).unwrap();
+        let bond = KeeperBond::unpack(&world.bank.accounts[&keeper_bond].data).unwrap();
+        assert_eq!(bond.last_crank_swap, pegged_swap);
+        assert_eq!(bond.last_crank_oracle_price, 1_000_000_000);
+        assert_eq!(
+            world.bank.process(&slash(&upgrade_authority, 400_000)),
+            Err(SwapError::UnprovenSlash.into())
+        );
+        publish(&mut world, 1_100_000_000);
+        SLOT.with(|slot| slot.set(2));
+        assert_eq!(
+            world.bank.process(&slash(&upgrade_authority, 400_000)),
+            Err(SwapError::UnprovenSlash.into())
+        );
+        SLOT.with(|slot| slot.set(1));
+
+        // slashed below the minimum, at most half the bond, into the keeper
+        // config, the keeper can't crank until it tops up
+        assert_eq!(
+            world.bank.process(&slash(&keeper, 400_000)),
+            Err(SwapError::InvalidOwner.into())
+        );
+        assert_eq!(
+            world.bank.process(&slash(&upgrade_authority, 500_001)),
+            Err(SwapError::InvalidInput.into())
+        );
+        EVENTS.with(|events| events.borrow_mut().clear());
+        world
+            .bank
+            .process(&slash(&upgrade_authority, 400_000))
+            .unwrap();
+        assert_eq!(world.bank.accounts[&keeper_config].lamports, 1_400_000);
+        assert_eq!(world.bank.accounts[&keeper_bond].lamports, 1_600_000);
+        match EVENTS.with(|events| events.take()).last() {
+            Some(Event::KeeperSlashed(event)) => {
+                assert_eq!(event.keeper, keeper);
+                assert_eq!(event.amount, 400_000);
+                assert_eq!(event.last_crank_swap, pegged_swap);
+                assert_eq!(event.crank_oracle_price, 1_000_000_000);
+                assert_eq!(event.oracle_price, 1_100_000_000);
+            }
+            other => panic!("expected a slashing, got {:?}", other),
+        }
+        // once per crank
+        assert_eq!(
+            world.bank.process(&slash(&upgrade_authority, 100_000)),
+            Err(SwapError::UnprovenSlash.into())
+        );
+        assert_eq!(
+            world.bank.process(&refresh_by(&keeper)),
+            Err(SwapError::KeeperNotBonded.into())
+        );
+        world.bank.process(&register(400_000)).unwrap();
+        world.bank.process(&refresh_by(&keeper)).unwrap();
+
+        // unbonding stops the cranks at once, the bond leaves after the period
+        let unbond = instruction::unbond_keeper(&program_id(), &keeper).unwrap();
+        let withdraw = instruction::withdraw_keeper_bond(&program_id(), &keeper).unwrap();
+        assert_eq!(
+            world.bank.process(&withdraw),
+            Err(SwapError::KeeperBondLocked.into())
+        );
+        SLOT.with(|slot| slot.set(1_000));
+        world.bank.process(&unbond).unwrap();
+        assert_eq!(
+            world.bank.process(&refresh_by(&keeper)),
+            Err(SwapError::KeeperNotBonded.into())
+        );
+        SLOT.with(|slot| slot.set(1_099));
+        assert_eq!(
+            world.bank.process(&withdraw),
+            Err(SwapError::KeeperBondLocked.into())
+        );
+        SLOT.with(|slot| slot.set(1_100));
+        world.bank.process(&withdraw).unwrap();
+        assert_eq!(world.bank.accounts[&keeper].lamports, 10_000_000 - 400_000);
+        assert_eq!(world.bank.accounts[&keeper_bond].lamports, 1_000_000);
+    }
+}
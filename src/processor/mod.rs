@@ -0,0 +1,946 @@
+use crate::amount::TokenAmount;
+use crate::constraints::{SwapConstraints, SWAP_CONSTRAINTS};
+use crate::curve::calculator::TradeDirection;
+use crate::error::SwapError;
+use crate::events::{CheckpointEvent, Event};
+use crate::instruction::{
+    AdminMoveLiquidity, ConfigureLiquiditySnapshot, DepositAllTokenTypes, DepositAndLockLiquidity,
+    DepositSingleTokenTypeExactAmountIn, FlashSwap, Initialize, InitializeAdminMultisig,
+    InitializeFromTemplate, LockLiquidity, RegisterKeeper, RegisterReferrer, ReinitializeCurve,
+    SetBondedKeepersOnly, SetDefaultHost, SetDepositBonus, SetEmergency, SetExactInput,
+    SetFeeConversion, SetFeeExempt, SetFeeTiers, SetGate, SetGovernanceProgram, SetKeeperConfig,
+    SetNestedPool, SetOracleConfig, SetOwnerFeeAccounts, SetOwnerFeeFallback, SetPaused,
+    SetPoolMetadata, SetPoolTemplate, SetRebalancer, SetSandwichGuard, SetSingleWithdrawFeeToLps,
+    SetStakePool, SetWithdrawCooldown, SetWithdrawFeeDestination, SimulateSwap, SlashKeeper, Swap,
+    SwapInstruction, UnlockLiquidity, WithdrawAllTokenTypes, WithdrawMax,
+    WithdrawSingleTokenTypeExactAmountOut,
+};
+use crate::limits::check_limits;
+use crate::state::SwapVersion;
+use solana_program::account_info::AccountInfo;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::msg;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::{clock::Clock, Sysvar};
+use std::convert::TryFrom;
+
+mod admin;
+mod deposit;
+mod initialize;
+mod keeper;
+mod swap;
+#[cfg(test)]
+pub mod test;
+mod validation;
+mod withdraw;
+
+pub struct Processor {}
+
+impl Processor {
+    // blunt brake on manipulation, capping the token A traded per slot, and
+    // the last trade's slot for GetHealth and its fees for checkpoints
+    fn record_slot_volume(
+        swap_info: &AccountInfo,
+        trade_direction: TradeDirection,
+        source_amount_swapped: TokenAmount,
+        destination_amount_swapped: TokenAmount,
+        (source_fee, destination_fee): (TokenAmount, TokenAmount),
+    ) -> ProgramResult {
+        let (volume, fee_a, fee_b) = match trade_direction {
+            TradeDirection::AtoB => (source_amount_swapped, source_fee, destination_fee),
+            TradeDirection::BtoA => (destination_amount_swapped, destination_fee, source_fee),
+        };
+        let mut versioned_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        match &mut versioned_swap {
+            SwapVersion::SwapV2(swap_v2) => {
+                swap_v2.record_slot_volume(Clock::get()?.slot, volume.to_u64()?)?;
+                swap_v2.record_trade_fees(fee_a.to_u64()?, fee_b.to_u64()?);
+            }
+            // only V2 pools carry a cap, Initialize making no other, so a V1
+            // pool has nothing to enforce and its account isn't written
+            SwapVersion::SwapV1(_) => return Ok(()),
+        }
+        SwapVersion::pack(versioned_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    // single-sided deposits and withdrawals trade part of their token against
+    // the curve, so they count towards the cap as a swap of the token A they
+    // move, token B valued at the reserves' ratio.  AtoB moves token A.  With
+    // either reserve empty there's no ratio and nothing traded to count
+    fn record_single_sided_volume(
+        swap_info: &AccountInfo,
+        trade_direction: TradeDirection,
+        amount: u64,
+        swap_token_a_amount: u64,
+        swap_token_b_amount: u64,
+    ) -> ProgramResult {
+        if swap_token_a_amount == 0 || swap_token_b_amount == 0 {
+            return Ok(());
+        }
+        let volume = match trade_direction {
+            TradeDirection::AtoB => amount,
+            TradeDirection::BtoA => u128::from(amount)
+                .checked_mul(u128::from(swap_token_a_amount))
+                .and_then(|volume| volume.checked_div(u128::from(swap_token_b_amount)))
+                .and_then(|volume| u64::try_from(volume).ok())
+                .ok_or(SwapError::CalculationFailure)?,
+        };
+        Self::record_slot_volume(
+            swap_info,
+            TradeDirection::AtoB,
+            TokenAmount::from(volume),
+            TokenAmount::ZERO,
+            (TokenAmount::ZERO, TokenAmount::ZERO),
+        )
+    }
+
+    // counts a swap, deposit or withdrawal towards the next checkpoint,
+    // emitting it once due with the reserves and supply after the interaction
+    fn record_interaction(
+        swap_info: &AccountInfo,
+        token_a_info: &AccountInfo,
+        token_b_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        withdraw_fee: u64,
+    ) -> ProgramResult {
+        let mut versioned_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if let SwapVersion::SwapV2(swap_v2) = &mut versioned_swap {
+            if swap_v2.record_interaction(withdraw_fee) {
+                let token_program_id = swap_v2.token_program_id;
+                let checkpoint = CheckpointEvent {
+                    swap: *swap_info.key,
+                    interactions: swap_v2.interactions,
+                    token_a_amount: Self::unpack_token_account(token_a_info, &token_program_id)?
+                        .amount,
+                    token_b_amount: Self::unpack_token_account(token_b_info, &token_program_id)?
+                        .amount,
+                    pool_token_supply: Self::unpack_mint(pool_mint_info, &token_program_id)?.supply,
+                    trade_fees_a: swap_v2.trade_fees_a,
+                    trade_fees_b: swap_v2.trade_fees_b,
+                    withdraw_fees: swap_v2.withdraw_fees,
+                    previous_hash: swap_v2.last_checkpoint_hash,
+                };
+                swap_v2.last_checkpoint_hash = checkpoint.hash();
+                Event::Checkpoint(checkpoint).emit();
+            }
+        }
+        SwapVersion::pack(versioned_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    pub fn process(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+    ) -> ProgramResult {
+        Self::process_with_constraints(program_id, accounts, instruction_data, &SWAP_CONSTRAINTS)
+    }
+
+    pub fn process_with_constraints(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+        instruction_data: &[u8],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let ix = SwapInstruction::unpack(instruction_data)?;
+        check_limits(&ix, accounts)?;
+        match ix {
+            SwapInstruction::Initialize(Initialize {
+                nonce,
+                fees,
+                swap_curve,
+                vesting_slots,
+                max_slot_volume,
+                fee_holiday_end_slot,
+                initial_price_band_bps,
+                oracle_price_offset,
+            }) => {
+                msg!("Instruction: Init");
+                Self::process_initialize(
+                    program_id,
+                    nonce,
+                    fees,
+                    swap_curve,
+                    vesting_slots,
+                    max_slot_volume,
+                    fee_holiday_end_slot,
+                    initial_price_band_bps,
+                    oracle_price_offset,
+                    false,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::Swap(Swap {
+                amount_in,
+                minimum_amount_out,
+                min_price_numerator,
+                min_price_denominator,
+            }) => {
+                msg!("Instruction: Swap");
+                Self::process_swap(
+                    program_id,
+                    amount_in,
+                    minimum_amount_out,
+                    (min_price_numerator, min_price_denominator),
+                    accounts,
+                )
+            }
+            SwapInstruction::DepositAllTokenTypes(DepositAllTokenTypes {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+            }) => {
+                msg!("Instruction: DepositAllTokenTypes");
+                Self::process_deposit_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawAllTokenTypes(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: WithdrawAllTokenTypes");
+                Self::process_withdraw_all_token_types(
+                    program_id,
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::DepositSingleTokenTypeExactAmountIn(
+                DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: DepositSingleTokenTypeExactAmountIn");
+                Self::process_deposit_single_token_type_exact_amount_in(
+                    program_id,
+                    source_token_amount,
+                    minimum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(
+                WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                },
+            ) => {
+                msg!("Instruction: WithdrawSingleTokenTypeExactAmountOut");
+                Self::process_withdraw_single_token_type_exact_amount_out(
+                    program_id,
+                    destination_token_amount,
+                    maximum_pool_token_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::RegisterReferrer(RegisterReferrer {
+                host_fee_numerator,
+                host_fee_denominator,
+            }) => {
+                msg!("Instruction: RegisterReferrer");
+                Self::process_register_referrer(
+                    program_id,
+                    host_fee_numerator,
+                    host_fee_denominator,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::ClaimVestedPoolTokens => {
+                msg!("Instruction: ClaimVestedPoolTokens");
+                Self::process_claim_vested_pool_tokens(program_id, accounts)
+            }
+            SwapInstruction::ReinitializeCurve(ReinitializeCurve { swap_curve }) => {
+                msg!("Instruction: ReinitializeCurve");
+                Self::process_reinitialize_curve(program_id, swap_curve, accounts, swap_constraints)
+            }
+            SwapInstruction::GetMultiplePoolStates => {
+                msg!("Instruction: GetMultiplePoolStates");
+                Self::process_get_multiple_pool_states(program_id, accounts)
+            }
+            SwapInstruction::LockLiquidity(LockLiquidity {
+                amount,
+                unlock_slot,
+            }) => {
+                msg!("Instruction: LockLiquidity");
+                Self::process_lock_liquidity(program_id, amount, unlock_slot, accounts)
+            }
+            SwapInstruction::DepositAndLockLiquidity(DepositAndLockLiquidity {
+                pool_token_amount,
+                maximum_token_a_amount,
+                maximum_token_b_amount,
+                unlock_slot,
+            }) => {
+                msg!("Instruction: DepositAndLockLiquidity");
+                Self::process_deposit_and_lock_liquidity(
+                    program_id,
+                    pool_token_amount,
+                    maximum_token_a_amount,
+                    maximum_token_b_amount,
+                    unlock_slot,
+                    accounts,
+                )
+            }
+            SwapInstruction::UnlockLiquidity(UnlockLiquidity { unlock_slot }) => {
+                msg!("Instruction: UnlockLiquidity");
+                Self::process_unlock_liquidity(program_id, unlock_slot, accounts)
+            }
+            SwapInstruction::SetOracleConfig(SetOracleConfig {
+                price_offset,
+                min_price,
+                max_price,
+                max_step_bps,
+            }) => {
+                msg!("Instruction: SetOracleConfig");
+                Self::process_set_oracle_config(
+                    program_id,
+                    price_offset,
+                    min_price,
+                    max_price,
+                    max_step_bps,
+                    accounts,
+                )
+            }
+            SwapInstruction::CrankOraclePrice => {
+                msg!("Instruction: CrankOraclePrice");
+                Self::process_crank_oracle_price(program_id, accounts)
+            }
+            SwapInstruction::AdminDepositAllTokenTypes(AdminMoveLiquidity {
+                pool_token_amount,
+            }) => {
+                msg!("Instruction: AdminDepositAllTokenTypes");
+                Self::process_admin_move_liquidity(program_id, pool_token_amount, true, accounts)
+            }
+            SwapInstruction::AdminWithdrawAllTokenTypes(AdminMoveLiquidity {
+                pool_token_amount,
+            }) => {
+                msg!("Instruction: AdminWithdrawAllTokenTypes");
+                Self::process_admin_move_liquidity(program_id, pool_token_amount, false, accounts)
+            }
+            SwapInstruction::RebalanceSwap(Swap {
+                amount_in,
+                minimum_amount_out,
+                min_price_numerator,
+                min_price_denominator,
+            }) => {
+                msg!("Instruction: RebalanceSwap");
+                Self::process_rebalance_swap(
+                    program_id,
+                    amount_in,
+                    minimum_amount_out,
+                    (min_price_numerator, min_price_denominator),
+                    accounts,
+                )
+            }
+            SwapInstruction::SetRebalancer(SetRebalancer { rebalancer }) => {
+                msg!("Instruction: SetRebalancer");
+                Self::process_set_rebalancer(program_id, rebalancer, accounts)
+            }
+            SwapInstruction::SetPoolMetadata(SetPoolMetadata { name, uri, tags }) => {
+                msg!("Instruction: SetPoolMetadata");
+                Self::process_set_pool_metadata(program_id, name, uri, tags, accounts)
+            }
+            SwapInstruction::SetEmergency(SetEmergency { enabled }) => {
+                msg!("Instruction: SetEmergency");
+                Self::process_set_emergency(program_id, enabled, accounts)
+            }
+            SwapInstruction::SetPaused(SetPaused { paused }) => {
+                msg!("Instruction: SetPaused");
+                Self::process_set_paused(program_id, paused, accounts)
+            }
+            SwapInstruction::SetGovernanceProgram(SetGovernanceProgram { governance_program }) => {
+                msg!("Instruction: SetGovernanceProgram");
+                Self::process_set_governance_program(program_id, governance_program, accounts)
+            }
+            SwapInstruction::SetPoolTemplate(SetPoolTemplate {
+                index,
+                fees,
+                swap_curve,
+            }) => {
+                msg!("Instruction: SetPoolTemplate");
+                Self::process_set_pool_template(
+                    program_id,
+                    index,
+                    fees,
+                    swap_curve,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::EmergencyWithdraw(WithdrawAllTokenTypes {
+                pool_token_amount,
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: EmergencyWithdraw");
+                Self::process_emergency_withdraw(
+                    program_id,
+                    pool_token_amount,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+            SwapInstruction::SetDepositBonus(SetDepositBonus {
+                start_slot,
+                end_slot,
+                bonus_bps,
+                lockup_slots,
+            }) => {
+                msg!("Instruction: SetDepositBonus");
+                Self::process_set_deposit_bonus(
+                    program_id,
+                    start_slot,
+                    end_slot,
+                    bonus_bps,
+                    lockup_slots,
+                    accounts,
+                )
+            }
+            SwapInstruction::SetFeeExempt(SetFeeExempt { fee_exempt }) => {
+                msg!("Instruction: SetFeeExempt");
+                Self::process_set_fee_exempt(program_id, fee_exempt, accounts)
+            }
+            SwapInstruction::DocumentUpgradeAuthority => {
+                msg!("Instruction: DocumentUpgradeAuthority");
+                Self::process_document_upgrade_authority(program_id, accounts)
+            }
+            SwapInstruction::SetWithdrawFeeDestination(SetWithdrawFeeDestination {
+                underlying,
+            }) => {
+                msg!("Instruction: SetWithdrawFeeDestination");
+                Self::process_set_withdraw_fee_destination(program_id, underlying, accounts)
+            }
+            SwapInstruction::SetFeeTiers(SetFeeTiers { fee_tiers }) => {
+                msg!("Instruction: SetFeeTiers");
+                Self::process_set_fee_tiers(program_id, fee_tiers, accounts)
+            }
+            SwapInstruction::SimulateSwap(SimulateSwap { amount_in }) => {
+                msg!("Instruction: SimulateSwap");
+                Self::process_simulate_swap(program_id, amount_in, accounts)
+            }
+            SwapInstruction::FlashSwap(FlashSwap {
+                amount_out,
+                callback_data,
+            }) => {
+                msg!("Instruction: FlashSwap");
+                Self::process_flash_swap(program_id, amount_out, &callback_data, accounts)
+            }
+            SwapInstruction::SetExactInput(SetExactInput { exact_input }) => {
+                msg!("Instruction: SetExactInput");
+                Self::process_set_exact_input(program_id, exact_input, accounts)
+            }
+            SwapInstruction::SetStakePool(SetStakePool { stake_pool }) => {
+                msg!("Instruction: SetStakePool");
+                Self::process_set_stake_pool(program_id, stake_pool, accounts)
+            }
+            SwapInstruction::RefreshStakePoolRate => {
+                msg!("Instruction: RefreshStakePoolRate");
+                Self::process_refresh_stake_pool_rate(program_id, accounts)
+            }
+            SwapInstruction::GetHealth => {
+                msg!("Instruction: GetHealth");
+                Self::process_get_health(program_id, accounts)
+            }
+            SwapInstruction::SetDefaultHost(SetDefaultHost { host_fee_account }) => {
+                msg!("Instruction: SetDefaultHost");
+                Self::process_set_default_host(program_id, host_fee_account, accounts)
+            }
+            SwapInstruction::ClaimHostFees => {
+                msg!("Instruction: ClaimHostFees");
+                Self::process_claim_host_fees(program_id, accounts)
+            }
+            SwapInstruction::SetFeeAccount => {
+                msg!("Instruction: SetFeeAccount");
+                Self::process_set_fee_account(program_id, accounts, swap_constraints)
+            }
+            SwapInstruction::SetOwnerFeeAccounts(SetOwnerFeeAccounts {
+                owner_fee_account_a,
+                owner_fee_account_b,
+            }) => {
+                msg!("Instruction: SetOwnerFeeAccounts");
+                Self::process_set_owner_fee_accounts(
+                    program_id,
+                    owner_fee_account_a,
+                    owner_fee_account_b,
+                    accounts,
+                )
+            }
+            SwapInstruction::ResizeSwapAccount => {
+                msg!("Instruction: ResizeSwapAccount");
+                Self::process_resize_swap_account(program_id, accounts)
+            }
+            SwapInstruction::SetFeeConversion(SetFeeConversion {
+                fee_conversion_pool,
+                fee_conversion_account,
+                max_slippage_bps,
+            }) => {
+                msg!("Instruction: SetFeeConversion");
+                Self::process_set_fee_conversion(
+                    program_id,
+                    fee_conversion_pool,
+                    fee_conversion_account,
+                    max_slippage_bps,
+                    accounts,
+                )
+            }
+            SwapInstruction::SetWithdrawCooldown(SetWithdrawCooldown { cooldown_slots }) => {
+                msg!("Instruction: SetWithdrawCooldown");
+                Self::process_set_withdraw_cooldown(program_id, cooldown_slots, accounts)
+            }
+            SwapInstruction::InitializeAdminMultisig(InitializeAdminMultisig { m, signers }) => {
+                msg!("Instruction: InitializeAdminMultisig");
+                Self::process_initialize_admin_multisig(program_id, m, signers, accounts)
+            }
+            SwapInstruction::InitializeFromTemplate(InitializeFromTemplate {
+                nonce,
+                template,
+                vesting_slots,
+                max_slot_volume,
+                fee_holiday_end_slot,
+            }) => {
+                msg!("Instruction: InitializeFromTemplate");
+                Self::process_initialize_from_template(
+                    program_id,
+                    nonce,
+                    template,
+                    vesting_slots,
+                    max_slot_volume,
+                    fee_holiday_end_slot,
+                    accounts,
+                    swap_constraints,
+                )
+            }
+            SwapInstruction::SetGate(SetGate { gate, credential }) => {
+                msg!("Instruction: SetGate");
+                Self::process_set_gate(program_id, gate, credential, accounts)
+            }
+            SwapInstruction::SetSingleWithdrawFeeToLps(SetSingleWithdrawFeeToLps { to_lps }) => {
+                msg!("Instruction: SetSingleWithdrawFeeToLps");
+                Self::process_set_single_withdraw_fee_to_lps(program_id, to_lps, accounts)
+            }
+            SwapInstruction::SetSandwichGuard(SetSandwichGuard { enabled }) => {
+                msg!("Instruction: SetSandwichGuard");
+                Self::process_set_sandwich_guard(program_id, enabled, accounts)
+            }
+            SwapInstruction::SetOwnerFeeFallback(SetOwnerFeeFallback { enabled }) => {
+                msg!("Instruction: SetOwnerFeeFallback");
+                Self::process_set_owner_fee_fallback(program_id, enabled, accounts)
+            }
+            SwapInstruction::ConfigureLiquiditySnapshot(ConfigureLiquiditySnapshot { slot }) => {
+                msg!("Instruction: ConfigureLiquiditySnapshot");
+                Self::process_configure_liquidity_snapshot(program_id, slot, accounts)
+            }
+            SwapInstruction::CrankLiquiditySnapshot => {
+                msg!("Instruction: CrankLiquiditySnapshot");
+                Self::process_crank_liquidity_snapshot(program_id, accounts)
+            }
+            SwapInstruction::SetNestedPool(SetNestedPool { nested_pool }) => {
+                msg!("Instruction: SetNestedPool");
+                Self::process_set_nested_pool(program_id, nested_pool, accounts)
+            }
+            SwapInstruction::SetKeeperConfig(SetKeeperConfig {
+                min_bond,
+                unbonding_slots,
+            }) => {
+                msg!("Instruction: SetKeeperConfig");
+                Self::process_set_keeper_config(program_id, min_bond, unbonding_slots, accounts)
+            }
+            SwapInstruction::RegisterKeeper(RegisterKeeper { amount }) => {
+                msg!("Instruction: RegisterKeeper");
+                Self::process_register_keeper(program_id, amount, accounts)
+            }
+            SwapInstruction::UnbondKeeper => {
+                msg!("Instruction: UnbondKeeper");
+                Self::process_unbond_keeper(program_id, accounts)
+            }
+            SwapInstruction::WithdrawKeeperBond => {
+                msg!("Instruction: WithdrawKeeperBond");
+                Self::process_withdraw_keeper_bond(program_id, accounts)
+            }
+            SwapInstruction::SlashKeeper(SlashKeeper { amount, reason }) => {
+                msg!("Instruction: SlashKeeper");
+                Self::process_slash_keeper(program_id, amount, reason, accounts)
+            }
+            SwapInstruction::SetBondedKeepersOnly(SetBondedKeepersOnly { enabled }) => {
+                msg!("Instruction: SetBondedKeepersOnly");
+                Self::process_set_bonded_keepers_only(program_id, enabled, accounts)
+            }
+            SwapInstruction::WithdrawMax(WithdrawMax {
+                minimum_token_a_amount,
+                minimum_token_b_amount,
+            }) => {
+                msg!("Instruction: WithdrawMax");
+                Self::process_withdraw_max(
+                    program_id,
+                    minimum_token_a_amount,
+                    minimum_token_b_amount,
+                    accounts,
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::CHECKPOINT_INTERVAL;
+    use crate::curve::calculator::TradeDirection;
+    use crate::error::SwapError;
+    use crate::events::Event;
+    use crate::instruction;
+    use crate::limits::MAX_INSTRUCTION_ACCOUNTS;
+    use crate::processor::test::{
+        borrower_id, program_id, routed, Rng, TestAccount, World, EVENTS, INITIAL_USER_BALANCE,
+        RETURN_DATA, SLOT,
+    };
+    use crate::state::{SwapQuote, SwapVersion};
+    use solana_program::instruction::{AccountMeta, Instruction};
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    /// Workload length, at up to 32 slots per step this covers a few hours
+    const STEPS: usize = 4_000;
+
+    // randomized multi-user workloads over several pools sharing their mints,
+    // checking after every instruction that no token is created or destroyed
+    // other than pool tokens, and those only through their mint's supply
+    #[test]
+    fn value_is_conserved_across_pools() {
+        for seed in [0x1234_5678_u64, 0xdead_beef, 0x0bad_cafe] {
+            let mut rng = Rng(seed);
+            let mut world = World::new();
+            let trading_supplies: HashMap<Pubkey, u64> = world
+                .mints
+                .iter()
+                .map(|mint| (*mint, world.bank.mint(mint).supply))
+                .collect();
+            world.assert_conserved(&trading_supplies);
+
+            let mut succeeded = [0; 6];
+            for _ in 0..STEPS {
+                SLOT.with(|slot| slot.set(slot.get() + rng.below(32)));
+                let (kind, ok) = world.step(&mut rng);
+                if ok {
+                    succeeded[kind] += 1;
+                }
+                world.assert_conserved(&trading_supplies);
+            }
+            // the workload has to actually exercise every kind of operation
+            assert!(
+                succeeded.iter().all(|count| *count > 0),
+                "seed {:x}: {:?}",
+                seed,
+                succeeded
+            );
+        }
+
+        // a failed instruction leaves every account untouched
+        let mut world = World::new();
+        let before: Vec<(Pubkey, TestAccount)> = world
+            .bank
+            .accounts
+            .iter()
+            .map(|(key, account)| (*key, account.clone()))
+            .collect();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let too_much = pool.swap(user, TradeDirection::AtoB, INITIAL_USER_BALANCE + 1);
+        assert!(world.bank.process(&too_much).is_err());
+        for (key, account) in before {
+            assert_eq!(world.bank.accounts[&key], account);
+        }
+    }
+
+    #[test]
+    fn checkpoints_chain_up_to_the_state() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let swap = |trade_direction| {
+            let (source, swap_source, swap_destination, destination) = match trade_direction {
+                TradeDirection::AtoB => (pool.mint_a, pool.token_a, pool.token_b, pool.mint_b),
+                TradeDirection::BtoA => (pool.mint_b, pool.token_b, pool.token_a, pool.mint_a),
+            };
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user.tokens[&source],
+                &swap_source,
+                &swap_destination,
+                &user.tokens[&destination],
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: 1_000_000,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let swaps = [swap(TradeDirection::AtoB), swap(TradeDirection::BtoA)];
+        let (swap_key, token_a, token_b, pool_mint) =
+            (pool.swap, pool.token_a, pool.token_b, pool.pool_mint);
+
+        EVENTS.with(|events| events.borrow_mut().clear());
+        for step in 0..2 * CHECKPOINT_INTERVAL {
+            world.bank.process(&swaps[step as usize % 2]).unwrap();
+        }
+        let events = EVENTS.with(|events| events.take());
+        let checkpoints: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Checkpoint(checkpoint) => Some(checkpoint),
+                _ => None,
+            })
+            .collect();
+        let (fees_a, fees_b) = events
+            .iter()
+            .fold((0, 0), |(fees_a, fees_b), event| match event {
+                Event::Swap(swap) => match swap.trade_direction {
+                    TradeDirection::AtoB => (fees_a + swap.trade_fee + swap.owner_fee, fees_b),
+                    TradeDirection::BtoA => (fees_a, fees_b + swap.trade_fee + swap.owner_fee),
+                },
+                _ => (fees_a, fees_b),
+            });
+
+        assert_eq!(checkpoints.len(), 2);
+        assert_eq!(checkpoints[0].previous_hash, [0; 32]);
+        assert_eq!(checkpoints[1].previous_hash, checkpoints[0].hash());
+        let last = checkpoints[1];
+        assert_eq!(last.interactions, 2 * CHECKPOINT_INTERVAL);
+        // the last swap was the checkpoint's, it saw the pool as it is now
+        assert_eq!(
+            (
+                last.token_a_amount,
+                last.token_b_amount,
+                last.pool_token_supply
+            ),
+            (
+                world.bank.token_account(&token_a).amount,
+                world.bank.token_account(&token_b).amount,
+                world.bank.mint(&pool_mint).supply,
+            )
+        );
+        assert_eq!(
+            (u128::from(last.trade_fees_a), u128::from(last.trade_fees_b)),
+            (fees_a, fees_b)
+        );
+        match SwapVersion::unpack_versioned(&world.bank.accounts[&swap_key].data).unwrap() {
+            SwapVersion::SwapV2(state) => assert_eq!(state.last_checkpoint_hash, last.hash()),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn rebalance_and_flash_swaps_count_towards_checkpoints() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (user_a, user_b) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let set_rebalancer = instruction::set_rebalancer(
+            &program_id(),
+            &pool.swap,
+            &pool.admin,
+            &pool.fee_account,
+            instruction::SetRebalancer {
+                rebalancer: user.wallet,
+            },
+        )
+        .unwrap();
+        let rebalance = instruction::rebalance_swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            &pool.pool_mint,
+            instruction::Swap {
+                amount_in: 1_000_000,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        // repaid in token B, well over the fees on what it takes
+        let flash = instruction::flash_swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            &pool.pool_mint,
+            &pool.fee_account,
+            1_000,
+            Instruction {
+                program_id: borrower_id(),
+                accounts: vec![
+                    AccountMeta::new(user_b, false),
+                    AccountMeta::new(pool.token_b, false),
+                    AccountMeta::new_readonly(user.wallet, true),
+                    AccountMeta::new_readonly(spl_token::id(), false),
+                ],
+                data: 2_000u64.to_le_bytes().to_vec(),
+            },
+        )
+        .unwrap();
+        let (token_a, token_b, pool_mint) = (pool.token_a, pool.token_b, pool.pool_mint);
+        world.bank.process(&set_rebalancer).unwrap();
+
+        EVENTS.with(|events| events.borrow_mut().clear());
+        for step in 0..CHECKPOINT_INTERVAL {
+            let instruction = if step % 2 == 0 { &rebalance } else { &flash };
+            world.bank.process(instruction).unwrap();
+        }
+        let events = EVENTS.with(|events| events.take());
+        let checkpoints: Vec<_> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Checkpoint(checkpoint) => Some(checkpoint),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(checkpoints.len(), 1);
+        let checkpoint = checkpoints[0];
+        assert_eq!(checkpoint.interactions, CHECKPOINT_INTERVAL);
+        assert_eq!(
+            (
+                checkpoint.token_a_amount,
+                checkpoint.token_b_amount,
+                checkpoint.pool_token_supply
+            ),
+            (
+                world.bank.token_account(&token_a).amount,
+                world.bank.token_account(&token_b).amount,
+                world.bank.mint(&pool_mint).supply,
+            )
+        );
+        // both paths count their fees, token A's from the rebalances and token
+        // B's from the flash swaps repaid in kind
+        assert!(checkpoint.trade_fees_a > 0 && checkpoint.trade_fees_b > 0);
+    }
+
+    #[test]
+    fn swaps_compose_under_two_levels_of_aggregators() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (source, destination) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let amount_in = INITIAL_USER_BALANCE / 4;
+        let swap = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &source,
+            &pool.token_a,
+            &pool.token_b,
+            &destination,
+            &pool.pool_mint,
+            &pool.fee_account,
+            Some(&pool.host_fee_account),
+            None,
+            instruction::Swap {
+                amount_in,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let simulate = instruction::simulate_swap(
+            &program_id(),
+            &pool.swap,
+            &source,
+            &pool.token_a,
+            &pool.token_b,
+            instruction::SimulateSwap { amount_in },
+        )
+        .unwrap();
+        let callback = |repaid: u64| Instruction {
+            program_id: borrower_id(),
+            accounts: vec![
+                AccountMeta::new(source, false),
+                AccountMeta::new(pool.token_a, false),
+                AccountMeta::new_readonly(user.wallet, true),
+                AccountMeta::new_readonly(spl_token::id(), false),
+            ],
+            data: repaid.to_le_bytes().to_vec(),
+        };
+        let flash_swap = |quote: &SwapQuote| {
+            instruction::flash_swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &pool.token_a,
+                &pool.token_b,
+                &destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                quote.destination_amount_swapped,
+                callback(quote.source_amount_swapped),
+            )
+            .unwrap()
+        };
+
+        // an aggregator invoked by a vault's program: the swap runs at height 3
+        let before = world.bank.token_account(&destination).amount;
+        world.bank.process(&routed(swap.clone(), 2)).unwrap();
+        assert!(world.bank.token_account(&destination).amount > before);
+
+        // the callback at 4, its repayment at 5, the deepest the runtime goes
+        assert_eq!(
+            world.bank.process(&routed(simulate.clone(), 2)),
+            Err(SwapError::SimulationComplete.into())
+        );
+        let quote = SwapQuote::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap();
+        let before = world.bank.token_account(&destination).amount;
+        world.bank.process(&routed(flash_swap(&quote), 2)).unwrap();
+        assert_eq!(
+            world.bank.token_account(&destination).amount,
+            before + quote.destination_amount_swapped
+        );
+        // a level deeper, the repayment couldn't run
+        assert_eq!(
+            world.bank.process(&routed(flash_swap(&quote), 3)),
+            Err(SwapError::CallTooDeep.into())
+        );
+
+        let mut padded = swap;
+        padded.accounts.resize(
+            MAX_INSTRUCTION_ACCOUNTS,
+            AccountMeta::new_readonly(user.wallet, false),
+        );
+        assert_eq!(
+            world.bank.process(&routed(padded, 1)),
+            Err(SwapError::TooManyAccounts.into())
+        );
+    }
+}
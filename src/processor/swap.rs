@@ -0,0 +1,2879 @@
+//! Swaps and the fees they earn, quotes, and the pool views routers and
+//! monitoring read.
+
+use crate::amount::{PoolTokenAmount, TokenAmount};
+use crate::constants::{MAX_POOL_STATE_VIEWS, REFERRER_SEED};
+use crate::constraints::{validate_default_referrer_fee, SwapConstraints};
+use crate::curve::base::{CurveType, SwapCurve, SwapResult};
+use crate::curve::calculator::TradeDirection;
+use crate::curve::constant_price::ConstantPriceCurve;
+use crate::curve::fees::validate_fraction;
+use crate::error::SwapError;
+use crate::events::{
+    Event, FlashSwapEvent, HostFeesClaimedEvent, OraclePriceUpdatedEvent, OwnerFeeConvertedEvent,
+    OwnerFeeSkippedEvent, ReferrerRegisteredEvent, StakePoolRateRefreshedEvent, SwapEvent,
+};
+use crate::state::{
+    stake_pool_program, OracleConfig, PoolHealth, PoolStateView, Referrer, StakePoolRate,
+    SwapQuote, SwapState, SwapVersion,
+};
+use crate::token_ops;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::instruction::{AccountMeta, Instruction};
+use solana_program::program::{invoke, invoke_signed, set_return_data};
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::system_instruction;
+use solana_program::sysvar::{clock::Clock, rent::Rent, Sysvar};
+use spl_math::precise_number::PreciseNumber;
+
+use super::Processor;
+
+// the fee conversion pool a swap sells its owner fee through, and the
+// direction the fee trades in it
+struct FeeConversion<'a, 'b> {
+    swap_info: &'b AccountInfo<'a>,
+    authority_info: &'b AccountInfo<'a>,
+    source_info: &'b AccountInfo<'a>,
+    destination_info: &'b AccountInfo<'a>,
+    pool_mint_info: &'b AccountInfo<'a>,
+    fee_conversion_account_info: &'b AccountInfo<'a>,
+    token_swap: Box<dyn SwapState>,
+    trade_direction: TradeDirection,
+}
+
+// the owner's share of a trade's fee, in the token of the side
+// `trade_direction` trades in, and whether the trader is exempt from it
+struct OwnerFee {
+    amount: TokenAmount,
+    trade_direction: TradeDirection,
+    exempt: bool,
+}
+
+impl Processor {
+    // the fee conversion pool's accounts, for a swap selling its owner fee
+    // paid in `source_mint`
+    fn unpack_fee_conversion<'a, 'b>(
+        program_id: &Pubkey,
+        token_swap: &dyn SwapState,
+        source_mint: &Pubkey,
+        (fee_conversion_pool, fee_conversion_account): (Pubkey, Pubkey),
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    ) -> Result<FeeConversion<'a, 'b>, ProgramError> {
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let fee_conversion_account_info = next_account_info(account_info_iter)?;
+
+        if *swap_info.key != fee_conversion_pool || swap_info.owner != program_id {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        let conversion_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *authority_info.key
+            != Self::authority_id(program_id, swap_info.key, conversion_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if conversion_swap.token_program_id() != token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        let (trade_direction, swap_source, swap_destination) =
+            if conversion_swap.token_a_mint() == source_mint {
+                (
+                    TradeDirection::AtoB,
+                    conversion_swap.token_a_account(),
+                    conversion_swap.token_b_account(),
+                )
+            } else if conversion_swap.token_b_mint() == source_mint {
+                (
+                    TradeDirection::BtoA,
+                    conversion_swap.token_b_account(),
+                    conversion_swap.token_a_account(),
+                )
+            } else {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            };
+        if *source_info.key != *swap_source || *destination_info.key != *swap_destination {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *pool_mint_info.key != *conversion_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if *fee_conversion_account_info.key != fee_conversion_account {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+
+        Ok(FeeConversion {
+            swap_info,
+            authority_info,
+            source_info,
+            destination_info,
+            pool_mint_info,
+            fee_conversion_account_info,
+            token_swap: conversion_swap,
+            trade_direction,
+        })
+    }
+
+    // sells the owner's share of a swap's fee, still in the reserves, through
+    // the fee conversion pool at its LP fee, and pays the fee mint tokens out
+    // to the fee conversion account.  false when the conversion pool can't
+    // take the trade right now, for the owner fee to be minted as usual: it
+    // takes the checks of a swap on the conversion pool, and a price no more
+    // than the pool's slippage bound below the spot price.  a trade moving
+    // that price just before, in the same slot, could set the sale's, so a
+    // conversion pool already traded in the slot sells nothing
+    fn convert_owner_fee<'a>(
+        swap_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        token_swap: &dyn SwapState,
+        swap_source_info: &AccountInfo<'a>,
+        fee_conversion: &FeeConversion<'a, '_>,
+        owner_fee: TokenAmount,
+    ) -> Result<bool, ProgramError> {
+        let conversion_swap = fee_conversion.token_swap.as_ref();
+        if conversion_swap.emergency()
+            || conversion_swap.paused()
+            || conversion_swap.gate().is_some()
+            || conversion_swap.last_trade_slot() == Clock::get()?.slot
+            || Self::check_rate_fresh(conversion_swap).is_err()
+            || conversion_swap
+                .swap_curve()
+                .check_direction(fee_conversion.trade_direction)
+                .is_err()
+        {
+            return Ok(false);
+        }
+        let source = Self::unpack_token_account(
+            fee_conversion.source_info,
+            conversion_swap.token_program_id(),
+        )?;
+        let destination = Self::unpack_token_account(
+            fee_conversion.destination_info,
+            conversion_swap.token_program_id(),
+        )?;
+        let result = match conversion_swap.swap_curve().swap(
+            owner_fee.get(),
+            TokenAmount::from(source.amount).get(),
+            TokenAmount::from(destination.amount).get(),
+            fee_conversion.trade_direction,
+            &conversion_swap.fees().without_owner_fees(),
+            &conversion_swap.fee_tiers(),
+        ) {
+            Some(result) => result,
+            None => return Ok(false),
+        };
+        let max_slippage_bps = token_swap.fee_conversion_max_slippage_bps();
+        let minimum_fee_mint_amount = conversion_swap
+            .swap_curve()
+            .calculator
+            .spot_price(
+                TokenAmount::from(source.amount).get(),
+                TokenAmount::from(destination.amount).get(),
+                fee_conversion.trade_direction,
+            )
+            .and_then(|spot_price| {
+                spot_price
+                    .checked_mul(&PreciseNumber::new(owner_fee.get())?)?
+                    .checked_mul(&PreciseNumber::new(u128::from(
+                        10_000u16.saturating_sub(max_slippage_bps),
+                    ))?)?
+                    .checked_div(&PreciseNumber::new(10_000)?)?
+                    .ceiling()?
+                    .to_imprecise()
+            });
+        match minimum_fee_mint_amount {
+            Some(minimum) if result.destination_amount_swapped >= minimum => {}
+            _ => return Ok(false),
+        }
+
+        let source_amount_swapped = TokenAmount::new(result.source_amount_swapped);
+        let fee_mint_amount_swapped = TokenAmount::new(result.destination_amount_swapped);
+        let fee = result
+            .total_fees()
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        match Self::record_slot_volume(
+            fee_conversion.swap_info,
+            fee_conversion.trade_direction,
+            source_amount_swapped,
+            fee_mint_amount_swapped,
+            (TokenAmount::new(fee), TokenAmount::ZERO),
+        ) {
+            Err(err) if err == SwapError::RateLimited.into() => return Ok(false),
+            recorded => recorded?,
+        }
+        let source_amount = source_amount_swapped.to_u64()?;
+        let fee_mint_amount = fee_mint_amount_swapped.to_u64()?;
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_source_info.clone(),
+            fee_conversion.source_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            source_amount,
+        )?;
+        token_ops::transfer(
+            fee_conversion.swap_info.key,
+            token_program_info.clone(),
+            fee_conversion.destination_info.clone(),
+            fee_conversion.fee_conversion_account_info.clone(),
+            fee_conversion.authority_info.clone(),
+            conversion_swap.nonce(),
+            fee_mint_amount,
+        )?;
+
+        Event::OwnerFeeConverted(OwnerFeeConvertedEvent {
+            swap: *swap_info.key,
+            fee_conversion_pool: *fee_conversion.swap_info.key,
+            source_amount,
+            fee_mint_amount,
+        })
+        .emit();
+
+        let (conversion_token_a_info, conversion_token_b_info) = match fee_conversion
+            .trade_direction
+        {
+            TradeDirection::AtoB => (fee_conversion.source_info, fee_conversion.destination_info),
+            TradeDirection::BtoA => (fee_conversion.destination_info, fee_conversion.source_info),
+        };
+        Self::record_interaction(
+            fee_conversion.swap_info,
+            conversion_token_a_info,
+            conversion_token_b_info,
+            fee_conversion.pool_mint_info,
+            0,
+        )?;
+        Ok(true)
+    }
+
+    // pools set to exact input take all of amount_in, not just what the
+    // curve's rounding needs for the same output
+    fn consume_input(
+        token_swap: &dyn SwapState,
+        result: SwapResult,
+        amount_in: u64,
+    ) -> Option<SwapResult> {
+        if token_swap.exact_input() {
+            result.with_exact_input(TokenAmount::from(amount_in).get())
+        } else {
+            Some(result)
+        }
+    }
+
+    // host fees of the default host, minted later by ClaimHostFees
+    fn accrue_host_fees(swap_info: &AccountInfo, host_fee: u64) -> ProgramResult {
+        let mut versioned_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if let SwapVersion::SwapV2(swap_v2) = &mut versioned_swap {
+            swap_v2.unclaimed_host_fees = swap_v2
+                .unclaimed_host_fees
+                .checked_add(host_fee)
+                .ok_or(SwapError::FeeCalculationFailure)?;
+        }
+        SwapVersion::pack(versioned_swap, &mut swap_info.data.borrow_mut())?;
+        Ok(())
+    }
+
+    // mints the owner's share of a trade's fee as pool tokens, less the
+    // host's: to the host fee account when the trade passes one, else
+    // accrued for the default host.  a pool converting the side's owner fee
+    // sells the owner's share, still in the vault it was paid into, for its
+    // fee mint instead
+    #[allow(clippy::too_many_arguments)]
+    fn mint_owner_fee<'a>(
+        swap_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        token_swap: &dyn SwapState,
+        (pool_mint_info, pool_fee_account_info): (&AccountInfo<'a>, &AccountInfo<'a>),
+        swap_fee_info: &AccountInfo<'a>,
+        host: Option<(&AccountInfo<'a>, Option<Referrer>)>,
+        fee_conversion: Option<&FeeConversion<'a, '_>>,
+        owner_fee: OwnerFee,
+        (swap_token_a_amount, swap_token_b_amount): (u128, u128),
+        pool_supply: PoolTokenAmount,
+    ) -> ProgramResult {
+        // we earned a fee as an exchange for performing the swap
+        // however the fee is denominated in X tokens
+        // we don't want to withdraw X tokens, we want to withdraw POOL tokens
+        // so we convert X tokens to pool tokens using a special ratio from the balancer paper
+        // now this pool token amount can be split between all the parties that deserve it
+        // zero-fee pools have nothing to split, and skip the conversion and all fee CPIs
+        let mut pool_token_amount = if token_swap.zero_fee() || owner_fee.exempt {
+            PoolTokenAmount::ZERO
+        } else {
+            match token_swap.swap_curve().owner_fee_to_pool_tokens(
+                owner_fee.amount.get(),
+                swap_token_a_amount,
+                swap_token_b_amount,
+                pool_supply.get(),
+                owner_fee.trade_direction,
+                token_swap.fees(),
+            ) {
+                Some(pool_token_amount) => PoolTokenAmount::new(pool_token_amount),
+                // the fee stays in the reserves, with the LPs, for this trade only
+                None if token_swap.owner_fee_fallback() => {
+                    Event::OwnerFeeSkipped(OwnerFeeSkippedEvent {
+                        swap: *swap_info.key,
+                        trade_direction: owner_fee.trade_direction,
+                        owner_fee: owner_fee.amount.get(),
+                    })
+                    .emit();
+                    PoolTokenAmount::ZERO
+                }
+                None => return Err(SwapError::FeeCalculationFailure.into()),
+            }
+        };
+        let owner_fee_pool_token_amount = pool_token_amount;
+
+        if !pool_token_amount.is_zero() {
+            // if host is present
+            if let Some((host_fee_account_info, referrer)) = host {
+                // a registered referrer carries its own share of the owner fee
+                let host_fee = match referrer {
+                    Some(referrer) => referrer.host_fee(pool_token_amount.get()),
+                    None => token_swap.fees().host_fee(pool_token_amount.get()),
+                }
+                .map(PoolTokenAmount::new)
+                .ok_or(SwapError::FeeCalculationFailure)?;
+                if !host_fee.is_zero() {
+                    //the first fee we subtract and send to the pool host (the UI)
+                    pool_token_amount = pool_token_amount
+                        .checked_sub(host_fee)
+                        .ok_or(SwapError::FeeCalculationFailure)?;
+                    //mint tokens to host (20% of the 0.05%)
+                    token_ops::mint_to(
+                        swap_info.key,
+                        token_program_info.clone(),
+                        pool_mint_info.clone(),
+                        host_fee_account_info.clone(),
+                        authority_info.clone(),
+                        token_swap.nonce(),
+                        host_fee.to_u64()?,
+                    )?;
+                }
+            } else if token_swap.default_host_fee_account().is_some() {
+                // integrators that can't pass a host fee account still earn
+                // the host share, accrued until ClaimHostFees mints it
+                let host_fee = token_swap
+                    .fees()
+                    .host_fee(pool_token_amount.get())
+                    .map(PoolTokenAmount::new)
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                if !host_fee.is_zero() {
+                    pool_token_amount = pool_token_amount
+                        .checked_sub(host_fee)
+                        .ok_or(SwapError::FeeCalculationFailure)?;
+                    Self::accrue_host_fees(swap_info, host_fee.to_u64()?)?;
+                }
+            }
+            // a pool converting this side's owner fee sells the owner's share
+            // of it, in the token it was paid in, for the fee mint instead
+            let converted = match fee_conversion {
+                Some(fee_conversion) => {
+                    let owner_share = owner_fee
+                        .amount
+                        .get()
+                        .checked_mul(pool_token_amount.get())
+                        .and_then(|amount| amount.checked_div(owner_fee_pool_token_amount.get()))
+                        .map(TokenAmount::new)
+                        .ok_or(SwapError::FeeCalculationFailure)?;
+                    Self::convert_owner_fee(
+                        swap_info,
+                        authority_info,
+                        token_program_info,
+                        token_swap,
+                        swap_fee_info,
+                        fee_conversion,
+                        owner_share,
+                    )?
+                }
+                None => false,
+            };
+            if !converted {
+                //mint tokens to owner (80% of the 0.05%)
+                token_ops::mint_to(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    pool_mint_info.clone(),
+                    pool_fee_account_info.clone(),
+                    authority_info.clone(),
+                    token_swap.nonce(),
+                    pool_token_amount.to_u64()?, //this is original pool_token_amont LESS host fees
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn process_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        min_price: (u64, u64),
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if amount_in == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+
+        let swap_info = next_account_info(account_info_iter)?; //state of the pool
+        let authority_info = next_account_info(account_info_iter)?; //authority over pool tokens that can mint and move them around
+        let user_transfer_authority_info = next_account_info(account_info_iter)?; //temp pubkey created by the user that has the right to move a precise amount of tokens from their acc
+
+        let source_info = next_account_info(account_info_iter)?; //A token account, belongs to USER (will lose balance)
+        let swap_source_info = next_account_info(account_info_iter)?; //A Token account, belongs to exchange (will gain balance)
+        let swap_destination_info = next_account_info(account_info_iter)?; //B Token account, belongs to exchange (will lose balance)
+        let destination_info = next_account_info(account_info_iter)?; //B Token account, belongs to USER (will gain balance)
+
+        let pool_mint_info = next_account_info(account_info_iter)?; //mint addr of the pool token
+        let pool_fee_account_info = next_account_info(account_info_iter)?; //where fees accrue
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        //unpack the state of the pool
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+
+        //if the exchange's A account = the A account stored in the state, then A->B, else B->A
+        let (trade_direction, token_a_info, token_b_info) =
+            if *swap_source_info.key == *token_swap.token_a_account() {
+                (
+                    TradeDirection::AtoB,
+                    swap_source_info,
+                    swap_destination_info,
+                )
+            } else {
+                (
+                    TradeDirection::BtoA,
+                    swap_destination_info,
+                    swap_source_info,
+                )
+            };
+        Self::check_accounts(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            token_a_info,
+            token_b_info,
+            pool_mint_info,
+            token_program_info,
+            None,
+        )?;
+        // the owner fee goes to the account set for the trade's direction
+        if *pool_fee_account_info.key != *token_swap.owner_fee_account(trade_direction) {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        Self::check_not_paused(token_swap.as_ref())?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        token_swap.swap_curve().check_direction(trade_direction)?;
+        Self::check_rate_fresh(token_swap.as_ref())?;
+        Self::check_gate(
+            token_swap.as_ref(),
+            user_transfer_authority_info,
+            account_info_iter,
+        )?;
+        // the user's accounts must hold the pool's tokens, caught here rather
+        // than deep in the token program's transfer
+        let (source_mint, destination_mint) = match trade_direction {
+            TradeDirection::AtoB => (token_swap.token_a_mint(), token_swap.token_b_mint()),
+            TradeDirection::BtoA => (token_swap.token_b_mint(), token_swap.token_a_mint()),
+        };
+        let user_source_account =
+            Self::unpack_user_token_account(source_info, token_swap.token_program_id())?;
+        if user_source_account.mint != *source_mint {
+            return Err(SwapError::IncorrectSourceMint.into());
+        }
+        let user_destination_account =
+            Self::unpack_user_token_account(destination_info, token_swap.token_program_id())?;
+        if user_destination_account.mint != *destination_mint {
+            return Err(SwapError::IncorrectDestinationMint.into());
+        }
+        Self::check_sandwich(
+            program_id,
+            swap_info,
+            token_swap.as_ref(),
+            (source_info.key, destination_info.key),
+            (&user_source_account.owner, &user_destination_account.owner),
+            account_info_iter,
+        )?;
+
+        //unpack exchange's accounts
+        let source_account =
+            Self::unpack_token_account(swap_source_info, token_swap.token_program_id())?;
+        let dest_account =
+            Self::unpack_token_account(swap_destination_info, token_swap.token_program_id())?;
+
+        // unpack the mint token account for the pool token
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+
+        // the fee conversion accounts come first, when the pool sells this
+        // side's owner fee for its fee mint
+        let fee_conversion = match token_swap.fee_conversion(trade_direction) {
+            Some(fee_conversion) => Some(Self::unpack_fee_conversion(
+                program_id,
+                token_swap.as_ref(),
+                source_mint,
+                fee_conversion,
+                account_info_iter,
+            )?),
+            None => None,
+        };
+
+        // the optional host fee account, and the referrer overriding its share
+        let host = match next_account_info(account_info_iter) {
+            Ok(host_fee_account_info) => {
+                let host_fee_account = Self::unpack_user_token_account(
+                    host_fee_account_info,
+                    token_swap.token_program_id(),
+                )?;
+                if *pool_mint_info.key != host_fee_account.mint {
+                    return Err(SwapError::IncorrectPoolMint.into());
+                }
+                let referrer = match next_account_info(account_info_iter) {
+                    Ok(referrer_info) => Some(Self::unpack_referrer(
+                        program_id,
+                        referrer_info,
+                        &host_fee_account.owner,
+                    )?),
+                    Err(_) => None,
+                };
+                Some((host_fee_account_info, referrer))
+            }
+            Err(_) => None,
+        };
+
+        // ----------------------------------------------------------------------------- calculation
+
+        //treasury accounts trade without the owner's fee
+        let fee_exempt = token_swap.is_fee_exempt(source_info.key);
+        let fees = Self::fees_for(token_swap.as_ref(), source_info.key)?;
+
+        //refuse trades the reserves can't fill before doing the math
+        token_swap.swap_curve().check_trade_size(
+            TokenAmount::from(amount_in).get(),
+            TokenAmount::from(source_account.amount).get(),
+            TokenAmount::from(dest_account.amount).get(),
+            trade_direction,
+            &fees,
+            &token_swap.fee_tiers(),
+        )?;
+
+        //do the actual swap
+        let result = token_swap
+            .swap_curve()
+            .swap(
+                TokenAmount::from(amount_in).get(),
+                TokenAmount::from(source_account.amount).get(),
+                TokenAmount::from(dest_account.amount).get(),
+                trade_direction,
+                &fees,
+                &token_swap.fee_tiers(),
+            )
+            .and_then(|result| Self::consume_input(token_swap.as_ref(), result, amount_in))
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        // check for slippage
+        let source_amount_swapped = TokenAmount::new(result.source_amount_swapped);
+        let destination_amount_swapped = TokenAmount::new(result.destination_amount_swapped);
+        if destination_amount_swapped < TokenAmount::from(minimum_amount_out) {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        Self::check_min_price(min_price, source_amount_swapped, destination_amount_swapped)?;
+
+        let fee = result
+            .total_fees()
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        Self::record_slot_volume(
+            swap_info,
+            trade_direction,
+            source_amount_swapped,
+            destination_amount_swapped,
+            (TokenAmount::new(fee), TokenAmount::ZERO),
+        )?;
+
+        // price before the trade, for the swap event; a curve that can't quote
+        // it doesn't fail the swap
+        let spot_price = token_swap.swap_curve().calculator.spot_price(
+            TokenAmount::from(source_account.amount).get(),
+            TokenAmount::from(dest_account.amount).get(),
+            trade_direction,
+        );
+
+        // depending on trade direction, these are the new balance of X and Y tokens in the pool
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (
+                result.new_swap_source_amount,
+                result.new_swap_destination_amount,
+            ),
+            TradeDirection::BtoA => (
+                result.new_swap_destination_amount,
+                result.new_swap_source_amount,
+            ),
+        };
+
+        // ----------------------------------------------------------------------------- execution
+
+        Self::check_transfer_authority(
+            source_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            source_amount_swapped.to_u64()?,
+        )?;
+
+        // move X token from USER -> exchange
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_source_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce(),
+            source_amount_swapped.to_u64()?,
+        )?;
+
+        Self::mint_owner_fee(
+            swap_info,
+            authority_info,
+            token_program_info,
+            token_swap.as_ref(),
+            (pool_mint_info, pool_fee_account_info),
+            swap_source_info,
+            host,
+            fee_conversion.as_ref(),
+            OwnerFee {
+                amount: TokenAmount::new(result.owner_fee),
+                trade_direction,
+                exempt: fee_exempt,
+            },
+            (swap_token_a_amount, swap_token_b_amount),
+            PoolTokenAmount::from(pool_mint.supply),
+        )?;
+
+        //finally in the end send the user their Y tokens
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_destination_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            destination_amount_swapped.to_u64()?,
+        )?;
+
+        Event::Swap(SwapEvent::new(
+            *swap_info.key,
+            trade_direction,
+            &result,
+            spot_price.as_ref(),
+        ))
+        .emit();
+
+        let (swap_token_a_info, swap_token_b_info) = match trade_direction {
+            TradeDirection::AtoB => (swap_source_info, swap_destination_info),
+            TradeDirection::BtoA => (swap_destination_info, swap_source_info),
+        };
+        Self::record_interaction(
+            swap_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            0,
+        )
+    }
+
+    // creates the referrer's program address on first use, then (re)sets its share of the owner fee
+    pub fn process_register_referrer(
+        program_id: &Pubkey,
+        host_fee_numerator: u64,
+        host_fee_denominator: u64,
+        accounts: &[AccountInfo],
+        swap_constraints: &Option<SwapConstraints>,
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let referrer_info = next_account_info(account_info_iter)?;
+        let owner_info = next_account_info(account_info_iter)?; //wallet owning the host fee accounts
+        let payer_info = next_account_info(account_info_iter)?;
+        let system_program_info = next_account_info(account_info_iter)?;
+
+        if !owner_info.is_signer {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // with constraints in place, only the program owner can approve a referrer's share
+        match swap_constraints {
+            Some(swap_constraints) => {
+                let program_owner_info = next_account_info(account_info_iter)?;
+                let owner_key = swap_constraints
+                    .owner_key
+                    .parse::<Pubkey>()
+                    .map_err(|_| SwapError::InvalidOwner)?;
+                if *program_owner_info.key != owner_key || !program_owner_info.is_signer {
+                    return Err(SwapError::InvalidOwner.into());
+                }
+                swap_constraints.validate_referrer_fee(host_fee_numerator, host_fee_denominator)?;
+            }
+            None => validate_default_referrer_fee(host_fee_numerator, host_fee_denominator)?,
+        }
+        validate_fraction(host_fee_numerator, host_fee_denominator)?;
+
+        let (referrer_key, bump_seed) = Referrer::find_address(owner_info.key, program_id);
+        if *referrer_info.key != referrer_key {
+            return Err(SwapError::InvalidReferrer.into());
+        }
+
+        if referrer_info.data_is_empty() {
+            let rent = Rent::get()?;
+            invoke_signed(
+                &system_instruction::create_account(
+                    payer_info.key,
+                    referrer_info.key,
+                    rent.minimum_balance(Referrer::LEN),
+                    Referrer::LEN as u64,
+                    program_id,
+                ),
+                &[
+                    payer_info.clone(),
+                    referrer_info.clone(),
+                    system_program_info.clone(),
+                ],
+                &[&[REFERRER_SEED, owner_info.key.as_ref(), &[bump_seed]]],
+            )?;
+        } else if referrer_info.owner != program_id {
+            return Err(SwapError::InvalidReferrer.into());
+        }
+
+        let referrer = Referrer {
+            is_initialized: true,
+            bump_seed,
+            owner: *owner_info.key,
+            host_fee_numerator,
+            host_fee_denominator,
+        };
+        Referrer::pack(referrer, &mut referrer_info.data.borrow_mut())?;
+
+        Event::ReferrerRegistered(ReferrerRegisteredEvent {
+            owner: *owner_info.key,
+            host_fee_numerator,
+            host_fee_denominator,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // permissionless unless the pool takes bonded keepers only, steps
+    // token_b_price toward the oracle price
+    pub fn process_crank_oracle_price(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let oracle_config_info = next_account_info(account_info_iter)?;
+        let oracle_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id || oracle_config_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut oracle_config = OracleConfig::unpack(&oracle_config_info.data.borrow())?;
+        if oracle_config.swap != *swap_info.key {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if oracle_config.oracle != *oracle_info.key {
+            return Err(SwapError::InvalidOracle.into());
+        }
+        let oracle_price = oracle_config
+            .read_price(&oracle_info.data.borrow())
+            .ok_or(SwapError::InvalidOracle)?;
+
+        let mut token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        Self::check_bonded_keeper(
+            program_id,
+            &token_swap,
+            swap_info,
+            oracle_price,
+            account_info_iter,
+        )?;
+        if token_swap.swap_curve().curve_type != CurveType::ConstantPrice
+            || token_swap.stake_pool().is_some()
+        {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        let mut calculator = [0u8; ConstantPriceCurve::LEN];
+        token_swap
+            .swap_curve()
+            .calculator
+            .pack_into_slice(&mut calculator);
+        let curve = ConstantPriceCurve::unpack_from_slice(&calculator)?;
+
+        let slot = Clock::get()?.slot;
+        let token_b_price = oracle_config
+            .next_price(curve.token_b_price, oracle_price, slot)
+            .ok_or(SwapError::CalculationFailure)?;
+        oracle_config.last_update_slot = slot;
+        OracleConfig::pack(oracle_config, &mut oracle_config_info.data.borrow_mut())?;
+
+        token_swap.set_swap_curve(SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price,
+                ..curve
+            }),
+        });
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::OraclePriceUpdated(OraclePriceUpdatedEvent {
+            swap: *swap_info.key,
+            oracle_price,
+            token_b_price,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // read-only, lets routers refresh many pools with a single simulation
+    pub fn process_get_multiple_pool_states(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if accounts.is_empty()
+            || !accounts.len().is_multiple_of(4)
+            || accounts.len() / 4 > MAX_POOL_STATE_VIEWS
+        {
+            return Err(SwapError::InvalidInstruction.into());
+        }
+
+        let mut return_data = vec![0u8; accounts.len() / 4 * PoolStateView::LEN];
+        let account_info_iter = &mut accounts.iter();
+        for output in return_data.chunks_mut(PoolStateView::LEN) {
+            let swap_info = next_account_info(account_info_iter)?;
+            let token_a_info = next_account_info(account_info_iter)?;
+            let token_b_info = next_account_info(account_info_iter)?;
+            let pool_mint_info = next_account_info(account_info_iter)?;
+
+            if swap_info.owner != program_id {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+            if *token_a_info.key != *token_swap.token_a_account()
+                || *token_b_info.key != *token_swap.token_b_account()
+            {
+                return Err(SwapError::IncorrectSwapAccount.into());
+            }
+            if *pool_mint_info.key != *token_swap.pool_mint() {
+                return Err(SwapError::IncorrectPoolMint.into());
+            }
+
+            let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+            let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+            let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+
+            let (token_a_decimals, token_b_decimals) =
+                token_swap.token_decimals().unwrap_or((u8::MAX, u8::MAX));
+            PoolStateView {
+                swap: *swap_info.key,
+                token_a_amount: token_a.amount,
+                token_b_amount: token_b.amount,
+                pool_token_supply: pool_mint.supply,
+                pool_mint_decimals: pool_mint.decimals,
+                token_a_decimals,
+                token_b_decimals,
+                locked_pool_tokens: token_swap.locked_pool_tokens(),
+                fees: token_swap.fees().clone(),
+                capabilities: token_swap.swap_curve().calculator.capabilities(),
+            }
+            .pack_into_slice(output);
+        }
+
+        set_return_data(&return_data);
+        Ok(())
+    }
+
+    // read-only, one simulation gives monitoring everything it alerts on
+    pub fn process_get_health(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *token_a_info.key != *token_swap.token_a_account()
+            || *token_b_info.key != *token_swap.token_b_account()
+        {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+
+        let health = PoolHealth::measure(
+            *swap_info.key,
+            token_swap.as_ref(),
+            token_a.amount,
+            token_b.amount,
+            Clock::get()?.slot,
+        )
+        .ok_or(SwapError::CalculationFailure)?;
+        let mut return_data = [0u8; PoolHealth::LEN];
+        health.pack_into_slice(&mut return_data);
+        set_return_data(&return_data);
+        Ok(())
+    }
+
+    // protocol-run arb: the rebalancer pays the LP trade fee into the reserves, and skips the owner fee and its mint CPIs
+    pub fn process_rebalance_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        minimum_amount_out: u64,
+        min_price: (u64, u64),
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if amount_in == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let rebalancer_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if token_swap.rebalancer() != Some(*rebalancer_info.key) || !rebalancer_info.is_signer {
+            return Err(SwapError::InvalidOwner.into());
+        }
+        let trade_direction = if *swap_source_info.key == *token_swap.token_a_account()
+            && *swap_destination_info.key == *token_swap.token_b_account()
+        {
+            TradeDirection::AtoB
+        } else if *swap_source_info.key == *token_swap.token_b_account()
+            && *swap_destination_info.key == *token_swap.token_a_account()
+        {
+            TradeDirection::BtoA
+        } else {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        };
+        Self::check_not_paused(token_swap.as_ref())?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        token_swap.swap_curve().check_direction(trade_direction)?;
+        Self::check_rate_fresh(token_swap.as_ref())?;
+
+        Self::check_user_token_accounts(
+            token_swap.token_program_id(),
+            &[source_info, destination_info],
+        )?;
+
+        let source_account =
+            Self::unpack_token_account(swap_source_info, token_swap.token_program_id())?;
+        let dest_account =
+            Self::unpack_token_account(swap_destination_info, token_swap.token_program_id())?;
+
+        // without the owner's share, the whole trade fee stays in the reserves
+        let fees = if token_swap.fee_holiday(Clock::get()?.slot) {
+            token_swap.fees().without_trade_fees()
+        } else {
+            token_swap.fees().without_owner_fees()
+        };
+        token_swap.swap_curve().check_trade_size(
+            TokenAmount::from(amount_in).get(),
+            TokenAmount::from(source_account.amount).get(),
+            TokenAmount::from(dest_account.amount).get(),
+            trade_direction,
+            &fees,
+            &token_swap.fee_tiers(),
+        )?;
+        let result = token_swap
+            .swap_curve()
+            .swap(
+                TokenAmount::from(amount_in).get(),
+                TokenAmount::from(source_account.amount).get(),
+                TokenAmount::from(dest_account.amount).get(),
+                trade_direction,
+                &fees,
+                &token_swap.fee_tiers(),
+            )
+            .and_then(|result| Self::consume_input(token_swap.as_ref(), result, amount_in))
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        let source_amount_swapped = TokenAmount::new(result.source_amount_swapped);
+        let destination_amount_swapped = TokenAmount::new(result.destination_amount_swapped);
+        if destination_amount_swapped < TokenAmount::from(minimum_amount_out) {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        Self::check_min_price(min_price, source_amount_swapped, destination_amount_swapped)?;
+        let fee = result
+            .total_fees()
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        Self::record_slot_volume(
+            swap_info,
+            trade_direction,
+            source_amount_swapped,
+            destination_amount_swapped,
+            (TokenAmount::new(fee), TokenAmount::ZERO),
+        )?;
+
+        let spot_price = token_swap.swap_curve().calculator.spot_price(
+            TokenAmount::from(source_account.amount).get(),
+            TokenAmount::from(dest_account.amount).get(),
+            trade_direction,
+        );
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            swap_source_info.clone(),
+            rebalancer_info.clone(),
+            token_swap.nonce(),
+            source_amount_swapped.to_u64()?,
+        )?;
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_destination_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            destination_amount_swapped.to_u64()?,
+        )?;
+
+        Event::Swap(SwapEvent::new(
+            *swap_info.key,
+            trade_direction,
+            &result,
+            spot_price.as_ref(),
+        ))
+        .emit();
+
+        let (swap_token_a_info, swap_token_b_info) = match trade_direction {
+            TradeDirection::AtoB => (swap_source_info, swap_destination_info),
+            TradeDirection::BtoA => (swap_destination_info, swap_source_info),
+        };
+        Self::record_interaction(
+            swap_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            0,
+        )
+    }
+
+    // permissionless, mints the default host's accrued fees
+    pub fn process_claim_host_fees(program_id: &Pubkey, accounts: &[AccountInfo]) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let host_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if token_swap.default_host_fee_account() != Some(*host_fee_account_info.key) {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+
+        let pool_token_amount = match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => std::mem::take(&mut swap_v2.unclaimed_host_fees),
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        };
+        let nonce = token_swap.nonce();
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+        if pool_token_amount > 0 {
+            token_ops::mint_to(
+                swap_info.key,
+                token_program_info.clone(),
+                pool_mint_info.clone(),
+                host_fee_account_info.clone(),
+                authority_info.clone(),
+                nonce,
+                pool_token_amount,
+            )?;
+        }
+
+        Event::HostFeesClaimed(HostFeesClaimedEvent {
+            swap: *swap_info.key,
+            host_fee_account: *host_fee_account_info.key,
+            pool_token_amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // permissionless unless the pool takes bonded keepers only, prices the
+    // pool at its stake pool's current rate
+    pub fn process_refresh_stake_pool_rate(
+        program_id: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let stake_pool_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let mut token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        Self::check_bonded_keeper(program_id, &token_swap, swap_info, 0, account_info_iter)?;
+        if token_swap.stake_pool() != Some(*stake_pool_info.key)
+            || *stake_pool_info.owner != stake_pool_program::id()
+        {
+            return Err(SwapError::InvalidOracle.into());
+        }
+        let rate =
+            StakePoolRate::read(&stake_pool_info.data.borrow()).ok_or(SwapError::InvalidOracle)?;
+        if rate.pool_token_supply == 0 || rate.total_lamports == 0 {
+            return Err(SwapError::InvalidOracle.into());
+        }
+        // balances from a past epoch miss its rewards, and the stake pool's
+        // own update crank is permissionless
+        let clock = Clock::get()?;
+        if rate.last_update_epoch != clock.epoch {
+            return Err(SwapError::StaleRate.into());
+        }
+
+        if token_swap.swap_curve().curve_type != CurveType::ConstantPrice {
+            return Err(SwapError::UnsupportedCurveOperation.into());
+        }
+        token_swap.set_swap_curve(SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price: rate.total_lamports,
+                token_b_price_denominator: rate.pool_token_supply,
+            }),
+        });
+        match &mut token_swap {
+            SwapVersion::SwapV2(swap_v2) => swap_v2.rate_update_slot = clock.slot,
+            SwapVersion::SwapV1(_) => return Err(ProgramError::InvalidAccountData),
+        }
+        SwapVersion::pack(token_swap, &mut swap_info.data.borrow_mut())?;
+
+        Event::StakePoolRateRefreshed(StakePoolRateRefreshedEvent {
+            swap: *swap_info.key,
+            stake_pool: *stake_pool_info.key,
+            total_lamports: rate.total_lamports,
+            pool_token_supply: rate.pool_token_supply,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // read-only preflight quote, always fails so it can't land by accident
+    pub fn process_simulate_swap(
+        program_id: &Pubkey,
+        amount_in: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if amount_in == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?;
+        let swap_destination_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        let trade_direction = if *swap_source_info.key == *token_swap.token_a_account()
+            && *swap_destination_info.key == *token_swap.token_b_account()
+        {
+            TradeDirection::AtoB
+        } else if *swap_source_info.key == *token_swap.token_b_account()
+            && *swap_destination_info.key == *token_swap.token_a_account()
+        {
+            TradeDirection::BtoA
+        } else {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        };
+        Self::check_not_paused(token_swap.as_ref())?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        token_swap.swap_curve().check_direction(trade_direction)?;
+        Self::check_rate_fresh(token_swap.as_ref())?;
+
+        let source_account =
+            Self::unpack_token_account(swap_source_info, token_swap.token_program_id())?;
+        let dest_account =
+            Self::unpack_token_account(swap_destination_info, token_swap.token_program_id())?;
+
+        // same calculation as process_swap
+        let fees = Self::fees_for(token_swap.as_ref(), source_info.key)?;
+        token_swap.swap_curve().check_trade_size(
+            TokenAmount::from(amount_in).get(),
+            TokenAmount::from(source_account.amount).get(),
+            TokenAmount::from(dest_account.amount).get(),
+            trade_direction,
+            &fees,
+            &token_swap.fee_tiers(),
+        )?;
+        let result = token_swap
+            .swap_curve()
+            .swap(
+                TokenAmount::from(amount_in).get(),
+                TokenAmount::from(source_account.amount).get(),
+                TokenAmount::from(dest_account.amount).get(),
+                trade_direction,
+                &fees,
+                &token_swap.fee_tiers(),
+            )
+            .and_then(|result| Self::consume_input(token_swap.as_ref(), result, amount_in))
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        let quote = SwapQuote {
+            source_amount_swapped: TokenAmount::new(result.source_amount_swapped).to_u64()?,
+            destination_amount_swapped: TokenAmount::new(result.destination_amount_swapped)
+                .to_u64()?,
+            trade_fee: TokenAmount::new(result.trade_fee).to_u64()?,
+            owner_fee: TokenAmount::new(result.owner_fee).to_u64()?,
+        };
+        let mut return_data = [0u8; SwapQuote::LEN];
+        quote.pack_into_slice(&mut return_data);
+        set_return_data(&return_data);
+
+        Err(SwapError::SimulationComplete.into())
+    }
+
+    // optimistic swap, the output goes out before the callback pays for it
+    pub fn process_flash_swap(
+        program_id: &Pubkey,
+        amount_out: u64,
+        callback_data: &[u8],
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if amount_out == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let swap_source_info = next_account_info(account_info_iter)?; //gains the repayment
+        let swap_destination_info = next_account_info(account_info_iter)?; //lends amount_out
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        let trade_direction = if *swap_source_info.key == *token_swap.token_a_account()
+            && *swap_destination_info.key == *token_swap.token_b_account()
+        {
+            TradeDirection::AtoB
+        } else if *swap_source_info.key == *token_swap.token_b_account()
+            && *swap_destination_info.key == *token_swap.token_a_account()
+        {
+            TradeDirection::BtoA
+        } else {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        };
+        Self::check_not_paused(token_swap.as_ref())?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        token_swap.swap_curve().check_direction(trade_direction)?;
+        Self::check_rate_fresh(token_swap.as_ref())?;
+        // there's no trader to check the gate of
+        if token_swap.gate().is_some() {
+            return Err(SwapError::GateNotPassed.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        Self::check_user_token_accounts(token_swap.token_program_id(), &[destination_info])?;
+
+        // the fee conversion accounts come before the callback's, when the
+        // pool sells the owner fee paid in source tokens
+        let source_mint = match trade_direction {
+            TradeDirection::AtoB => token_swap.token_a_mint(),
+            TradeDirection::BtoA => token_swap.token_b_mint(),
+        };
+        let fee_conversion = match token_swap.fee_conversion(trade_direction) {
+            Some(fee_conversion) => Some(Self::unpack_fee_conversion(
+                program_id,
+                token_swap.as_ref(),
+                source_mint,
+                fee_conversion,
+                account_info_iter,
+            )?),
+            None => None,
+        };
+        let callback_program_info = next_account_info(account_info_iter)?;
+        let callback_accounts = account_info_iter.as_slice();
+        // the vault balances below are only meaningful if nothing else can
+        // trade against the pool in the middle of the flash swap
+        if *callback_program_info.key == *program_id {
+            return Err(SwapError::InvalidInput.into());
+        }
+
+        let source_before =
+            Self::unpack_token_account(swap_source_info, token_swap.token_program_id())?.amount;
+        let destination_before =
+            Self::unpack_token_account(swap_destination_info, token_swap.token_program_id())?
+                .amount;
+
+        // ----------------------------------------------------------------------------- execution
+
+        token_ops::transfer(
+            swap_info.key,
+            token_program_info.clone(),
+            swap_destination_info.clone(),
+            destination_info.clone(),
+            authority_info.clone(),
+            token_swap.nonce(),
+            amount_out,
+        )?;
+
+        // never signed for by the swap authority, the callback only gets what
+        // its own signers can do
+        let callback = Instruction {
+            program_id: *callback_program_info.key,
+            accounts: callback_accounts
+                .iter()
+                .map(|info| AccountMeta {
+                    pubkey: *info.key,
+                    is_signer: info.is_signer,
+                    is_writable: info.is_writable,
+                })
+                .collect(),
+            data: callback_data.to_vec(),
+        };
+        let mut callback_infos = callback_accounts.to_vec();
+        callback_infos.push(callback_program_info.clone());
+        invoke(&callback, &callback_infos)?;
+
+        // ----------------------------------------------------------------------------- repayment
+
+        let source_after =
+            Self::unpack_token_account(swap_source_info, token_swap.token_program_id())?.amount;
+        let destination_after =
+            Self::unpack_token_account(swap_destination_info, token_swap.token_program_id())?
+                .amount;
+        let source_amount_paid = source_after
+            .checked_sub(source_before)
+            .ok_or(SwapError::FlashSwapNotRepaid)?;
+
+        // no user source account, treasury destinations trade without the owner's fee
+        let fee_exempt = token_swap.is_fee_exempt(destination_info.key);
+        let fees = Self::fees_for(token_swap.as_ref(), destination_info.key)?;
+        let (destination_amount_swapped, trade_fees, owner_fee) =
+            if destination_after < destination_before {
+                // paid in source tokens, worth at least what left the pool at
+                // the pre-trade reserves, fees and tiers included
+                let destination_amount_swapped = destination_before - destination_after;
+                let covered = token_swap
+                    .swap_curve()
+                    .swap(
+                        TokenAmount::from(source_amount_paid).get(),
+                        TokenAmount::from(source_before).get(),
+                        TokenAmount::from(destination_before).get(),
+                        trade_direction,
+                        &fees,
+                        &token_swap.fee_tiers(),
+                    )
+                    .ok_or(SwapError::FlashSwapNotRepaid)?;
+                if covered.destination_amount_swapped
+                    < TokenAmount::from(destination_amount_swapped).get()
+                {
+                    return Err(SwapError::FlashSwapNotRepaid.into());
+                }
+                let fee = covered
+                    .total_fees()
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                (
+                    destination_amount_swapped,
+                    (TokenAmount::new(fee), TokenAmount::ZERO),
+                    OwnerFee {
+                        amount: TokenAmount::new(covered.owner_fee),
+                        trade_direction,
+                        exempt: fee_exempt,
+                    },
+                )
+            } else {
+                // repaid in kind, plus the fees a swap of amount_out would pay
+                let trade_fees = fees
+                    .apply(TokenAmount::from(amount_out).get())
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                let owed = trade_fees
+                    .trade_fee
+                    .checked_add(trade_fees.owner_fee)
+                    .ok_or(SwapError::FeeCalculationFailure)?;
+                if TokenAmount::from(destination_after - destination_before).get() < owed {
+                    return Err(SwapError::FlashSwapNotRepaid.into());
+                }
+                (
+                    0,
+                    (TokenAmount::ZERO, TokenAmount::new(owed)),
+                    // in destination tokens, traded the other way
+                    OwnerFee {
+                        amount: TokenAmount::new(trade_fees.owner_fee),
+                        trade_direction: trade_direction.opposite(),
+                        exempt: fee_exempt,
+                    },
+                )
+            };
+
+        Self::record_slot_volume(
+            swap_info,
+            trade_direction,
+            TokenAmount::from(source_amount_paid),
+            TokenAmount::from(destination_amount_swapped),
+            trade_fees,
+        )?;
+
+        // the owner fee is minted as for a swap, to the fee account of the
+        // token it was repaid in, and sold for the fee mint only when paid in
+        // source tokens, the conversion pool's
+        if *pool_fee_account_info.key != *token_swap.owner_fee_account(owner_fee.trade_direction) {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let (swap_token_a_amount, swap_token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (source_after, destination_after),
+            TradeDirection::BtoA => (destination_after, source_after),
+        };
+        let (swap_fee_info, fee_conversion) = if owner_fee.trade_direction == trade_direction {
+            (swap_source_info, fee_conversion.as_ref())
+        } else {
+            (swap_destination_info, None)
+        };
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+        Self::mint_owner_fee(
+            swap_info,
+            authority_info,
+            token_program_info,
+            token_swap.as_ref(),
+            (pool_mint_info, pool_fee_account_info),
+            swap_fee_info,
+            None,
+            fee_conversion,
+            owner_fee,
+            (
+                TokenAmount::from(swap_token_a_amount).get(),
+                TokenAmount::from(swap_token_b_amount).get(),
+            ),
+            PoolTokenAmount::from(pool_mint.supply),
+        )?;
+
+        Event::FlashSwap(FlashSwapEvent {
+            swap: *swap_info.key,
+            trade_direction,
+            amount_out,
+            source_amount_paid,
+            destination_amount_swapped,
+        })
+        .emit();
+
+        let (swap_token_a_info, swap_token_b_info) = match trade_direction {
+            TradeDirection::AtoB => (swap_source_info, swap_destination_info),
+            TradeDirection::BtoA => (swap_destination_info, swap_source_info),
+        };
+        Self::record_interaction(
+            swap_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            0,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::curve::base::CurveType;
+    use crate::curve::calculator::TradeDirection;
+    use crate::curve::fees::{FeeTier, FeeTiers};
+    use crate::error::SwapError;
+    use crate::events::Event;
+    use crate::instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes};
+    use crate::processor::test::{
+        borrower_id, program_id, TestAccount, User, World, EVENTS, INITIAL_RESERVE,
+        INITIAL_USER_BALANCE, RETURN_DATA, SLOT,
+    };
+    use crate::state::{stake_pool_program, PoolHealth, PoolStateView, SwapQuote, SwapVersion};
+    use solana_program::instruction::{AccountMeta, Instruction};
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use solana_program::sysvar;
+    use solana_program::sysvar::clock::Clock;
+    use solana_program::sysvar::instructions::{
+        construct_instructions_data, store_current_index, BorrowedAccountMeta, BorrowedInstruction,
+    };
+    use spl_token::state::Mint;
+
+    #[test]
+    fn simulated_swap_quotes_the_real_swap() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (source, destination) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let amount_in = INITIAL_USER_BALANCE / 3;
+
+        let simulate = instruction::simulate_swap(
+            &program_id(),
+            &pool.swap,
+            &source,
+            &pool.token_a,
+            &pool.token_b,
+            instruction::SimulateSwap { amount_in },
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&simulate),
+            Err(SwapError::SimulationComplete.into())
+        );
+        let quote = SwapQuote::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap();
+
+        let swap = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &source,
+            &pool.token_a,
+            &pool.token_b,
+            &destination,
+            &pool.pool_mint,
+            &pool.fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in,
+                minimum_amount_out: quote.destination_amount_swapped,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&swap).unwrap();
+        assert_eq!(
+            world.bank.token_account(&source).amount,
+            INITIAL_USER_BALANCE - quote.source_amount_swapped
+        );
+        assert_eq!(
+            world.bank.token_account(&destination).amount,
+            INITIAL_USER_BALANCE + quote.destination_amount_swapped
+        );
+    }
+
+    #[test]
+    fn limit_prices_bound_the_price_paid() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (source, destination) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let amount_in = INITIAL_USER_BALANCE / 3;
+
+        let simulate = instruction::simulate_swap(
+            &program_id(),
+            &pool.swap,
+            &source,
+            &pool.token_a,
+            &pool.token_b,
+            instruction::SimulateSwap { amount_in },
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&simulate),
+            Err(SwapError::SimulationComplete.into())
+        );
+        let quote = SwapQuote::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap();
+
+        let swap = |min_price_numerator| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &source,
+                &pool.token_a,
+                &pool.token_b,
+                &destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                    min_price_numerator,
+                    min_price_denominator: quote.source_amount_swapped,
+                },
+            )
+            .unwrap()
+        };
+        // a hair above the quoted price is out of reach, the quoted price isn't
+        assert_eq!(
+            world
+                .bank
+                .process(&swap(quote.destination_amount_swapped + 1)),
+            Err(SwapError::ExceededSlippage.into())
+        );
+        world
+            .bank
+            .process(&swap(quote.destination_amount_swapped))
+            .unwrap();
+        assert_eq!(
+            world.bank.token_account(&destination).amount,
+            INITIAL_USER_BALANCE + quote.destination_amount_swapped
+        );
+    }
+
+    #[test]
+    fn trades_past_the_reserves_are_too_large() {
+        let mut world = World::new();
+        // the constant price pool pays out one for one until it runs dry
+        let (mint_a, mint_b) = (world.pools[2].mint_a, world.pools[2].mint_b);
+        let (source, destination) = (
+            world.users[0].tokens[&mint_a],
+            world.users[0].tokens[&mint_b],
+        );
+        world.fund(&mint_a, &source, INITIAL_RESERVE * 2);
+        let pool = &world.pools[2];
+        let wallet = world.users[0].wallet;
+        let swap = |amount_in| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &wallet,
+                &source,
+                &pool.token_a,
+                &pool.token_b,
+                &destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let (too_large, fills) = (swap(INITIAL_RESERVE * 2), swap(INITIAL_RESERVE));
+
+        assert_eq!(
+            world.bank.process(&too_large),
+            Err(SwapError::TradeTooLarge.into())
+        );
+        assert_eq!(world.bank.process(&fills), Ok(()));
+    }
+
+    #[test]
+    fn fee_holidays_waive_trade_fees() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let destination = user.tokens[&pool.mint_b];
+        let amount_in = INITIAL_USER_BALANCE / 3;
+
+        // only settable at initialization, so put the pool on a holiday directly
+        let data = &mut world.bank.accounts.get_mut(&pool.swap).unwrap().data;
+        let mut state = match SwapVersion::unpack_versioned(data).unwrap() {
+            SwapVersion::SwapV2(state) => state,
+            _ => unreachable!(),
+        };
+        state.fee_holiday_end_slot = u64::MAX;
+        let expected = state
+            .swap_curve
+            .swap(
+                u128::from(amount_in),
+                u128::from(INITIAL_RESERVE),
+                u128::from(INITIAL_RESERVE),
+                TradeDirection::AtoB,
+                &state.fees.without_trade_fees(),
+                &FeeTiers::default(),
+            )
+            .unwrap();
+        assert!(expected.destination_amount_swapped > 0);
+        SwapVersion::pack(SwapVersion::SwapV2(state), data).unwrap();
+
+        let swap = pool.swap(user, TradeDirection::AtoB, amount_in);
+        let fee_account_before = world.bank.token_account(&pool.fee_account).amount;
+        world.bank.process(&swap).unwrap();
+        assert_eq!(
+            world.bank.token_account(&destination).amount,
+            INITIAL_USER_BALANCE + expected.destination_amount_swapped as u64
+        );
+        assert_eq!(
+            world.bank.token_account(&pool.fee_account).amount,
+            fee_account_before
+        );
+    }
+
+    #[test]
+    fn owner_fee_fallbacks_let_swaps_through_without_the_fee() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (swap_key, admin, fee_account) = (pool.swap, pool.admin, pool.fee_account);
+
+        // a schedule the owner fee's conversion can't handle: the swap charges
+        // the tier's fees, the conversion charges the base trade fee of 300%
+        // on half the owner fee
+        let data = &mut world.bank.accounts.get_mut(&pool.swap).unwrap().data;
+        let mut state = match SwapVersion::unpack_versioned(data).unwrap() {
+            SwapVersion::SwapV2(state) => state,
+            _ => unreachable!(),
+        };
+        state.fees.trade_fee_numerator = 3 * state.fees.trade_fee_denominator;
+        state.fee_tiers.tiers[0] = FeeTier {
+            threshold: 1,
+            trade_fee_numerator: 0,
+            owner_trade_fee_numerator: state.fees.owner_trade_fee_numerator,
+        };
+        SwapVersion::pack(SwapVersion::SwapV2(state), data).unwrap();
+
+        let swap = pool.swap(user, TradeDirection::AtoB, INITIAL_USER_BALANCE / 10);
+        let set_fallback = |enabled| {
+            instruction::set_owner_fee_fallback(
+                &program_id(),
+                &swap_key,
+                &admin,
+                &fee_account,
+                instruction::SetOwnerFeeFallback { enabled },
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            world.bank.process(&swap),
+            Err(SwapError::FeeCalculationFailure.into())
+        );
+
+        world.bank.process(&set_fallback(true)).unwrap();
+        let fee_account_before = world.bank.token_account(&fee_account).amount;
+        EVENTS.with(|events| events.borrow_mut().clear());
+        world.bank.process(&swap).unwrap();
+        let events = EVENTS.with(|events| events.take());
+        assert_eq!(
+            world.bank.token_account(&fee_account).amount,
+            fee_account_before
+        );
+        let skipped = events.iter().find_map(|event| match event {
+            Event::OwnerFeeSkipped(skipped) => Some(skipped),
+            _ => None,
+        });
+        assert_eq!(skipped.unwrap().trade_direction, TradeDirection::AtoB);
+        assert!(skipped.unwrap().owner_fee > 0);
+
+        world.bank.process(&set_fallback(false)).unwrap();
+        assert_eq!(
+            world.bank.process(&swap),
+            Err(SwapError::FeeCalculationFailure.into())
+        );
+    }
+
+    #[test]
+    fn stake_pool_pools_trade_at_a_freshly_refreshed_rate() {
+        let mut world = World::new();
+        let mint_authority = Pubkey::new_unique();
+        let native_mint = spl_token::native_mint::id();
+        let mut data = vec![0; Mint::LEN];
+        Mint {
+            decimals: 9,
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut data);
+        world.bank.accounts.insert(
+            native_mint,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data,
+                owner: spl_token::id(),
+            },
+        );
+        let lst_mint = world.bank.add_mint(&mint_authority, 9);
+        world
+            .add_pool(
+                native_mint,
+                lst_mint,
+                CurveType::ConstantPrice,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE),
+            )
+            .unwrap();
+        let wallet = Pubkey::new_unique();
+        let user_sol = world.bank.add_token_account(&native_mint, &wallet, 0);
+        let user_lst = world.bank.add_token_account(&lst_mint, &wallet, 0);
+        world.fund(&lst_mint, &user_lst, INITIAL_USER_BALANCE);
+
+        // 1.05 SOL per LST, updated in the clock's epoch
+        let mut data = vec![0; 282];
+        data[0] = 1;
+        data[162..194].copy_from_slice(lst_mint.as_ref());
+        data[258..266].copy_from_slice(&1_050_000_000_u64.to_le_bytes());
+        data[266..274].copy_from_slice(&1_000_000_000_u64.to_le_bytes());
+        data[274..282].copy_from_slice(&Clock::default().epoch.to_le_bytes());
+        let stake_pool = world.bank.add(data, stake_pool_program::id());
+
+        let set_stake_pool = |pool_index: usize| {
+            let pool = &world.pools[pool_index];
+            instruction::set_stake_pool(
+                &program_id(),
+                &pool.swap,
+                &pool.admin,
+                &pool.fee_account,
+                instruction::SetStakePool { stake_pool },
+            )
+            .unwrap()
+        };
+        // the rate is lamports per LST, so only a SOL pool of that LST can link
+        let other_constant_price = set_stake_pool(2);
+        let set_stake_pool = set_stake_pool(world.pools.len() - 1);
+        assert_eq!(
+            world.bank.process(&other_constant_price),
+            Err(SwapError::IncorrectSwapAccount.into())
+        );
+        world.bank.process(&set_stake_pool).unwrap();
+
+        let pool = world.pools.last().unwrap();
+        let amount_in = INITIAL_USER_BALANCE / 10;
+        let swap = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &wallet,
+            &user_lst,
+            &pool.token_b,
+            &pool.token_a,
+            &user_sol,
+            &pool.pool_mint,
+            &pool.fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let refresh =
+            instruction::refresh_stake_pool_rate(&program_id(), &pool.swap, &stake_pool).unwrap();
+        assert_eq!(world.bank.process(&swap), Err(SwapError::StaleRate.into()));
+
+        world.bank.process(&refresh).unwrap();
+        let state = SwapVersion::unpack(&world.bank.accounts[&pool.swap].data).unwrap();
+        let expected = state
+            .swap_curve()
+            .swap(
+                u128::from(amount_in),
+                u128::from(INITIAL_RESERVE),
+                u128::from(INITIAL_RESERVE),
+                TradeDirection::BtoA,
+                state.fees(),
+                &FeeTiers::default(),
+            )
+            .unwrap();
+        // 1.05 SOL per LST, less the fees
+        assert_eq!(
+            expected.destination_amount_swapped,
+            (u128::from(amount_in) - expected.trade_fee - expected.owner_fee) * 105 / 100
+        );
+        world.bank.process(&swap).unwrap();
+        assert_eq!(
+            u128::from(world.bank.token_account(&user_sol).amount),
+            expected.destination_amount_swapped
+        );
+
+        // a slot later the rate has to be refreshed again
+        SLOT.with(|slot| slot.set(slot.get() + 1));
+        assert_eq!(world.bank.process(&swap), Err(SwapError::StaleRate.into()));
+        world.bank.process(&refresh).unwrap();
+        world.bank.process(&swap).unwrap();
+
+        // nor is a rate the stake pool hasn't brought up to date this epoch used
+        let data = &mut world.bank.accounts.get_mut(&stake_pool).unwrap().data;
+        data[274..282].copy_from_slice(&(Clock::default().epoch + 1).to_le_bytes());
+        SLOT.with(|slot| slot.set(slot.get() + 1));
+        assert_eq!(
+            world.bank.process(&refresh),
+            Err(SwapError::StaleRate.into())
+        );
+    }
+
+    #[test]
+    fn health_tracks_the_last_trade() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let get_health =
+            instruction::get_health(&program_id(), &pool.swap, &pool.token_a, &pool.token_b)
+                .unwrap();
+        let health = |world: &mut World| {
+            world.bank.process(&get_health).unwrap();
+            PoolHealth::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap()
+        };
+
+        let swap = pool.swap(user, TradeDirection::AtoB, INITIAL_USER_BALANCE);
+        SLOT.with(|slot| slot.set(1_000));
+        let before = health(&mut world);
+        assert_eq!(before.swap, get_health.accounts[0].pubkey);
+        assert_eq!(before.last_trade_slot, 0);
+        assert_eq!(before.slots_since_last_trade, 1_000);
+        assert_eq!(before.imbalance_ratio_bps, 10_000);
+
+        world.bank.process(&swap).unwrap();
+        SLOT.with(|slot| slot.set(1_010));
+        let after = health(&mut world);
+        assert_eq!(after.last_trade_slot, 1_000);
+        assert_eq!(after.slots_since_last_trade, 10);
+        assert!(!after.emergency);
+    }
+
+    #[test]
+    fn default_host_earns_the_host_fee_of_swaps_without_one() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let unclaimed_host_fees = |world: &World| match SwapVersion::unpack_versioned(
+            &world.bank.accounts[&world.pools[0].swap].data,
+        )
+        .unwrap()
+        {
+            SwapVersion::SwapV2(state) => state.unclaimed_host_fees,
+            _ => unreachable!(),
+        };
+
+        let set_default_host = instruction::set_default_host(
+            &program_id(),
+            &pool.swap,
+            &pool.admin,
+            &pool.fee_account,
+            instruction::SetDefaultHost {
+                host_fee_account: pool.host_fee_account,
+            },
+        )
+        .unwrap();
+        let swap = pool.swap(user, TradeDirection::AtoB, INITIAL_USER_BALANCE);
+        let claim = instruction::claim_host_fees(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &pool.pool_mint,
+            &pool.host_fee_account,
+        )
+        .unwrap();
+        let (fee_account, host_fee_account, pool_mint) =
+            (pool.fee_account, pool.host_fee_account, pool.pool_mint);
+
+        world.bank.process(&set_default_host).unwrap();
+        let supply_before = world.bank.mint(&pool_mint).supply;
+        world.bank.process(&swap).unwrap();
+        // the host's fifth of the owner fee, rounded up, is held back until
+        // claimed
+        let owner_fee = world.bank.token_account(&fee_account).amount;
+        let host_fee = unclaimed_host_fees(&world);
+        assert!(host_fee > 0);
+        assert_eq!(host_fee, ((owner_fee + host_fee) * 20).div_ceil(100));
+        assert_eq!(
+            world.bank.mint(&pool_mint).supply,
+            supply_before + owner_fee
+        );
+
+        world.bank.process(&claim).unwrap();
+        assert_eq!(world.bank.token_account(&host_fee_account).amount, host_fee);
+        assert_eq!(unclaimed_host_fees(&world), 0);
+        assert_eq!(
+            world.bank.mint(&pool_mint).supply,
+            supply_before + owner_fee + host_fee
+        );
+        // nothing left to mint the second time
+        world.bank.process(&claim).unwrap();
+        assert_eq!(world.bank.token_account(&host_fee_account).amount, host_fee);
+    }
+
+    #[test]
+    fn owner_fees_can_be_split_by_direction() {
+        let mut world = World::new();
+        let (admin, fee_account, pool_mint) = {
+            let pool = &world.pools[0];
+            (pool.admin, pool.fee_account, pool.pool_mint)
+        };
+        let fee_account_a = world.bank.add_token_account(&pool_mint, &admin, 0);
+        let fee_account_b = world.bank.add_token_account(&pool_mint, &admin, 0);
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let split_fees = |owner_fee_account_a, owner_fee_account_b| {
+            instruction::set_owner_fee_accounts(
+                &program_id(),
+                &pool.swap,
+                &pool.admin,
+                &pool.fee_account,
+                instruction::SetOwnerFeeAccounts {
+                    owner_fee_account_a,
+                    owner_fee_account_b,
+                },
+            )
+            .unwrap()
+        };
+        let swap_paying = |a_to_b: bool, fee_account: &Pubkey| {
+            let (source_mint, destination_mint, swap_source, swap_destination) = if a_to_b {
+                (&pool.mint_a, &pool.mint_b, &pool.token_a, &pool.token_b)
+            } else {
+                (&pool.mint_b, &pool.mint_a, &pool.token_b, &pool.token_a)
+            };
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user.tokens[source_mint],
+                swap_source,
+                swap_destination,
+                &user.tokens[destination_mint],
+                &pool.pool_mint,
+                fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: INITIAL_USER_BALANCE / 10,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let (split, merge_b) = (
+            split_fees(fee_account_a, fee_account_b),
+            split_fees(fee_account_a, Pubkey::default()),
+        );
+        let (a_to_b, a_to_b_unsplit) = (
+            swap_paying(true, &fee_account_a),
+            swap_paying(true, &fee_account),
+        );
+        let (b_to_a, b_to_a_unsplit) = (
+            swap_paying(false, &fee_account_b),
+            swap_paying(false, &fee_account),
+        );
+        let balance = |world: &World, account| world.bank.token_account(account).amount;
+
+        world.bank.process(&split).unwrap();
+        assert_eq!(
+            world.bank.process(&a_to_b_unsplit),
+            Err(SwapError::IncorrectFeeAccount.into())
+        );
+        assert_eq!(
+            world.bank.process(&swap_paying(true, &fee_account_b)),
+            Err(SwapError::IncorrectFeeAccount.into())
+        );
+        let fees_before = balance(&world, &fee_account);
+        world.bank.process(&a_to_b).unwrap();
+        assert!(balance(&world, &fee_account_a) > 0);
+        assert_eq!(balance(&world, &fee_account_b), 0);
+        world.bank.process(&b_to_a).unwrap();
+        assert!(balance(&world, &fee_account_b) > 0);
+        assert_eq!(balance(&world, &fee_account), fees_before);
+
+        // a side left unset goes back to the pool fee account
+        world.bank.process(&merge_b).unwrap();
+        assert_eq!(
+            world.bank.process(&b_to_a),
+            Err(SwapError::IncorrectFeeAccount.into())
+        );
+        world.bank.process(&b_to_a_unsplit).unwrap();
+        assert!(balance(&world, &fee_account) > fees_before);
+    }
+
+    #[test]
+    fn owner_fees_can_be_converted_into_a_fee_mint() {
+        let mut world = World::new();
+        // pool 1 trades pool 0's token B for the fee mint
+        let (admin, fee_mint) = (world.pools[0].admin, world.pools[1].mint_b);
+        let fee_conversion_account = world.bank.add_token_account(&fee_mint, &admin, 0);
+        let (pool, conversion_pool) = (&world.pools[0], &world.pools[1]);
+        let user = &world.users[0];
+        let set_fee_conversion = |fee_conversion_pool, max_slippage_bps| {
+            instruction::set_fee_conversion(
+                &program_id(),
+                &pool.swap,
+                &pool.admin,
+                &pool.fee_account,
+                instruction::SetFeeConversion {
+                    fee_conversion_pool,
+                    fee_conversion_account,
+                    max_slippage_bps,
+                },
+            )
+            .unwrap()
+        };
+        let fee_conversion = instruction::FeeConversionAccounts {
+            fee_conversion_pool: conversion_pool.swap,
+            fee_conversion_authority: conversion_pool.authority,
+            fee_conversion_source: conversion_pool.token_a,
+            fee_conversion_destination: conversion_pool.token_b,
+            fee_conversion_pool_mint: conversion_pool.pool_mint,
+            fee_conversion_account,
+        };
+        let swap = |a_to_b: bool, fee_conversion: Option<&instruction::FeeConversionAccounts>| {
+            let (source_mint, destination_mint, swap_source, swap_destination) = if a_to_b {
+                (&pool.mint_a, &pool.mint_b, &pool.token_a, &pool.token_b)
+            } else {
+                (&pool.mint_b, &pool.mint_a, &pool.token_b, &pool.token_a)
+            };
+            instruction::swap_with_fee_conversion(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user.tokens[source_mint],
+                swap_source,
+                swap_destination,
+                &user.tokens[destination_mint],
+                &pool.pool_mint,
+                &pool.fee_account,
+                fee_conversion,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: INITIAL_USER_BALANCE / 10,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        // the conversion pool's LP fee alone is more than a 0 bps bound allows
+        let (convert, convert_at_spot, stop_converting) = (
+            set_fee_conversion(conversion_pool.swap, 100),
+            set_fee_conversion(conversion_pool.swap, 0),
+            set_fee_conversion(Pubkey::default(), 0),
+        );
+        // trading the conversion pool, to move the sale's price
+        let front_run = conversion_pool.swap(user, TradeDirection::AtoB, INITIAL_USER_BALANCE / 10);
+        let (a_to_b, b_to_a, b_to_a_converted) = (
+            swap(true, None),
+            swap(false, None),
+            swap(false, Some(&fee_conversion)),
+        );
+        let (fee_account, conversion_token_a, conversion_token_b) = (
+            pool.fee_account,
+            conversion_pool.token_a,
+            conversion_pool.token_b,
+        );
+        let balance = |world: &World, account| world.bank.token_account(account).amount;
+        let supplies = world.bank.balances_by_mint();
+
+        // the sale needs a price no worse than the bound below the spot price
+        assert_eq!(
+            world
+                .bank
+                .process(&set_fee_conversion(conversion_pool.swap, 10_001)),
+            Err(SwapError::InvalidInstruction.into())
+        );
+        world.bank.process(&convert_at_spot).unwrap();
+        world.bank.process(&b_to_a_converted).unwrap();
+        let fees = balance(&world, &fee_account);
+        assert!(fees > 0);
+        assert_eq!(balance(&world, &fee_conversion_account), 0);
+
+        // nor is the fee sold right after a trade on the conversion pool
+        world.bank.process(&convert).unwrap();
+        world.bank.process(&front_run).unwrap();
+        world.bank.process(&b_to_a_converted).unwrap();
+        assert!(balance(&world, &fee_account) > fees);
+        assert_eq!(balance(&world, &fee_conversion_account), 0);
+        world.bank.process(&stop_converting).unwrap();
+        world.bank.process(&b_to_a).unwrap();
+        world.bank.process(&convert).unwrap();
+        let fees = balance(&world, &fee_account);
+        SLOT.with(|slot| slot.set(slot.get() + 1));
+
+        // the owner fee of B to A trades, paid in token B, is sold for the fee mint
+        assert!(world.bank.process(&b_to_a).is_err());
+        let reserves = (
+            balance(&world, &conversion_token_a),
+            balance(&world, &conversion_token_b),
+        );
+        world.bank.process(&b_to_a_converted).unwrap();
+        assert_eq!(balance(&world, &fee_account), fees);
+        let fee_mint_paid = balance(&world, &fee_conversion_account);
+        assert!(fee_mint_paid > 0);
+        assert!(balance(&world, &conversion_token_a) > reserves.0);
+        assert_eq!(
+            balance(&world, &conversion_token_b),
+            reserves.1 - fee_mint_paid
+        );
+        // A to B trades pay their owner fee in token A, still minted as pool tokens
+        world.bank.process(&a_to_b).unwrap();
+        let fees_before = fees;
+        let fees = balance(&world, &fee_account);
+        assert!(fees > fees_before);
+
+        world.bank.process(&stop_converting).unwrap();
+        world.bank.process(&b_to_a).unwrap();
+        assert!(balance(&world, &fee_account) > fees);
+        assert_eq!(balance(&world, &fee_conversion_account), fee_mint_paid);
+
+        // the conversion moves tokens between pools, it doesn't create any
+        let supplies_after = world.bank.balances_by_mint();
+        for mint in &world.mints {
+            assert_eq!(supplies_after[mint], supplies[mint]);
+        }
+    }
+
+    #[test]
+    fn sandwich_guards_refuse_swaps_another_trader_brackets() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        let (mint_a, mint_b) = (pool.mint_a, pool.mint_b);
+
+        let set_sandwich_guard = instruction::set_sandwich_guard(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            instruction::SetSandwichGuard { enabled: true },
+        )
+        .unwrap();
+        world.bank.process(&set_sandwich_guard).unwrap();
+
+        let swap_by = |user: &User, a_to_b| {
+            let (source, swap_source, swap_destination, destination) = if a_to_b {
+                (user.tokens[&mint_a], token_a, token_b, user.tokens[&mint_b])
+            } else {
+                (user.tokens[&mint_b], token_b, token_a, user.tokens[&mint_a])
+            };
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                &authority,
+                &user.wallet,
+                &source,
+                &swap_source,
+                &swap_destination,
+                &destination,
+                &pool_mint,
+                &fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: 1_000_000,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let (trader, attacker) = (&world.users[0], &world.users[1]);
+        let front_run = swap_by(attacker, true);
+        let trade = swap_by(trader, true);
+        let back_run = swap_by(attacker, false);
+        let trade_back = swap_by(trader, false);
+        let guarded = instruction::with_instructions_sysvar(trade.clone()).unwrap();
+
+        // the sysvar as the runtime lays it out, for a transaction running its
+        // instruction at `current`
+        let run_in = |world: &mut World, transaction: &[&Instruction], current: u16| {
+            let borrowed: Vec<BorrowedInstruction> = transaction
+                .iter()
+                .map(|instruction| BorrowedInstruction {
+                    program_id: &instruction.program_id,
+                    accounts: instruction
+                        .accounts
+                        .iter()
+                        .map(|meta| BorrowedAccountMeta {
+                            pubkey: &meta.pubkey,
+                            is_signer: meta.is_signer,
+                            is_writable: meta.is_writable,
+                        })
+                        .collect(),
+                    data: &instruction.data,
+                })
+                .collect();
+            let mut data = construct_instructions_data(&borrowed);
+            store_current_index(&mut data, current);
+            world.bank.accounts.insert(
+                sysvar::instructions::id(),
+                TestAccount {
+                    lamports: 1_000_000_000,
+                    data,
+                    owner: sysvar::id(),
+                },
+            );
+            world.bank.process(transaction[current as usize])
+        };
+
+        // guarded pools need the sysvar to look at the transaction
+        assert!(world.bank.process(&trade).is_err());
+        assert_eq!(
+            run_in(&mut world, &[&front_run, &guarded, &back_run], 1),
+            Err(SwapError::SandwichSuspected.into())
+        );
+        // swapping one way, or only their own way both ways, is fine
+        run_in(&mut world, &[&front_run, &guarded], 1).unwrap();
+        run_in(&mut world, &[&guarded, &trade_back], 0).unwrap();
+
+        // the legs are the pool's, whichever wallets sign them
+        let back_run_elsewhere = swap_by(&world.users[2], false);
+        let accomplice_destination = world.users[2].tokens[&mint_a];
+        assert_eq!(
+            run_in(&mut world, &[&front_run, &guarded, &back_run_elsewhere], 1),
+            Err(SwapError::SandwichSuspected.into())
+        );
+        // single sided deposits move the price too, which way unknown
+        let attacker = &world.users[1];
+        let deposit = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &attacker.wallet,
+            &attacker.tokens[&mint_a],
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &attacker.pool_tokens[0],
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 1_000_000,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            run_in(&mut world, &[&front_run, &guarded, &deposit], 1),
+            Err(SwapError::SandwichSuspected.into())
+        );
+        // and flash swaps trade like swaps
+        let flash_back = instruction::flash_swap(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &token_b,
+            &token_a,
+            &accomplice_destination,
+            &pool_mint,
+            &fee_account,
+            1_000_000,
+            Instruction {
+                program_id: borrower_id(),
+                accounts: vec![],
+                data: vec![],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            run_in(&mut world, &[&front_run, &guarded, &flash_back], 1),
+            Err(SwapError::SandwichSuspected.into())
+        );
+
+        let set_sandwich_guard = instruction::set_sandwich_guard(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            instruction::SetSandwichGuard { enabled: false },
+        )
+        .unwrap();
+        world.bank.process(&set_sandwich_guard).unwrap();
+        world.bank.process(&trade).unwrap();
+    }
+
+    #[test]
+    fn flash_swaps_are_repaid_at_the_swap_price() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (source, destination) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let amount_in = INITIAL_USER_BALANCE / 3;
+
+        let simulate = instruction::simulate_swap(
+            &program_id(),
+            &pool.swap,
+            &source,
+            &pool.token_a,
+            &pool.token_b,
+            instruction::SimulateSwap { amount_in },
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&simulate),
+            Err(SwapError::SimulationComplete.into())
+        );
+        let quote = SwapQuote::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap();
+
+        let flash_swap = |amount_out: u64, repay_from: &Pubkey, repay_to: &Pubkey, repaid: u64| {
+            let callback = Instruction {
+                program_id: borrower_id(),
+                accounts: vec![
+                    AccountMeta::new(*repay_from, false),
+                    AccountMeta::new(*repay_to, false),
+                    AccountMeta::new_readonly(user.wallet, true),
+                    AccountMeta::new_readonly(spl_token::id(), false),
+                ],
+                data: repaid.to_le_bytes().to_vec(),
+            };
+            instruction::flash_swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &pool.token_a,
+                &pool.token_b,
+                &destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                amount_out,
+                callback,
+            )
+            .unwrap()
+        };
+        let not_repaid = Err(SwapError::FlashSwapNotRepaid.into());
+
+        // one token more than the payment buys through Swap
+        let overdrawn = flash_swap(
+            quote.destination_amount_swapped + 1,
+            &source,
+            &pool.token_a,
+            quote.source_amount_swapped,
+        );
+        assert_eq!(world.bank.process(&overdrawn), not_repaid);
+
+        // in kind, without the fees
+        let fee_free = flash_swap(
+            quote.destination_amount_swapped,
+            &destination,
+            &pool.token_b,
+            quote.destination_amount_swapped,
+        );
+        assert_eq!(world.bank.process(&fee_free), not_repaid);
+
+        let repaid = flash_swap(
+            quote.destination_amount_swapped,
+            &source,
+            &pool.token_a,
+            quote.source_amount_swapped,
+        );
+        world.bank.process(&repaid).unwrap();
+        assert_eq!(
+            world.bank.token_account(&source).amount,
+            INITIAL_USER_BALANCE - quote.source_amount_swapped
+        );
+        assert_eq!(
+            world.bank.token_account(&destination).amount,
+            INITIAL_USER_BALANCE + quote.destination_amount_swapped
+        );
+    }
+
+    #[test]
+    fn flash_swaps_mint_the_owner_fee_like_swaps() {
+        let amount_in = INITIAL_USER_BALANCE / 3;
+        let swapped = {
+            let mut world = World::new();
+            let pool = &world.pools[0];
+            let user = &world.users[0];
+            let swap = pool.swap(user, TradeDirection::AtoB, amount_in);
+            world.bank.process(&swap).unwrap();
+            world.bank.token_account(&pool.fee_account).amount
+        };
+        assert!(swapped > 0);
+
+        let flash = |world: &mut World, amount_out: u64, in_kind: bool, repaid: u64| {
+            let pool = &world.pools[0];
+            let user = &world.users[0];
+            let (source, destination) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+            let (repay_from, repay_to) = if in_kind {
+                (destination, pool.token_b)
+            } else {
+                (source, pool.token_a)
+            };
+            let flash_swap = instruction::flash_swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &pool.token_a,
+                &pool.token_b,
+                &destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                amount_out,
+                Instruction {
+                    program_id: borrower_id(),
+                    accounts: vec![
+                        AccountMeta::new(repay_from, false),
+                        AccountMeta::new(repay_to, false),
+                        AccountMeta::new_readonly(user.wallet, true),
+                        AccountMeta::new_readonly(spl_token::id(), false),
+                    ],
+                    data: repaid.to_le_bytes().to_vec(),
+                },
+            )
+            .unwrap();
+            let fee_account = pool.fee_account;
+            world.bank.process(&flash_swap).unwrap();
+            world.bank.token_account(&fee_account).amount
+        };
+
+        // repaid in source tokens, the owner gets what the same swap mints
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let simulate = instruction::simulate_swap(
+            &program_id(),
+            &pool.swap,
+            &world.users[0].tokens[&pool.mint_a],
+            &pool.token_a,
+            &pool.token_b,
+            instruction::SimulateSwap { amount_in },
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&simulate),
+            Err(SwapError::SimulationComplete.into())
+        );
+        let quote = SwapQuote::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap();
+        assert_eq!(
+            flash(
+                &mut world,
+                quote.destination_amount_swapped,
+                false,
+                quote.source_amount_swapped
+            ),
+            swapped
+        );
+
+        // repaid in kind, the owner fee is paid in destination tokens
+        let mut world = World::new();
+        assert!(flash(&mut world, 1_000_000, true, 1_000_000 + 10_000) > 0);
+    }
+
+    #[test]
+    fn exact_input_pools_take_the_whole_amount_in() {
+        let mut world = World::new();
+        let (mint_a, mint_b) = (world.pools[0].mint_a, world.pools[0].mint_b);
+        let (source, destination) = (
+            world.users[0].tokens[&mint_a],
+            world.users[0].tokens[&mint_b],
+        );
+        // trades the size of the reserves round by several tokens
+        world.fund(&mint_a, &source, INITIAL_RESERVE * 2);
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        let wallet = world.users[0].wallet;
+
+        let quote = |world: &mut World, amount_in| {
+            let simulate = instruction::simulate_swap(
+                &program_id(),
+                &swap,
+                &source,
+                &token_a,
+                &token_b,
+                instruction::SimulateSwap { amount_in },
+            )
+            .unwrap();
+            assert_eq!(
+                world.bank.process(&simulate),
+                Err(SwapError::SimulationComplete.into())
+            );
+            SwapQuote::unpack_from_slice(&RETURN_DATA.lock().unwrap()).unwrap()
+        };
+        let (amount_in, rounded) = (INITIAL_RESERVE..)
+            .map(|amount_in| (amount_in, quote(&mut world, amount_in)))
+            .find(|(amount_in, quote)| quote.source_amount_swapped < *amount_in)
+            .unwrap();
+
+        let set_exact_input = instruction::set_exact_input(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            instruction::SetExactInput { exact_input: true },
+        )
+        .unwrap();
+        world.bank.process(&set_exact_input).unwrap();
+        let exact = quote(&mut world, amount_in);
+        assert_eq!(exact.source_amount_swapped, amount_in);
+        assert_eq!(
+            exact.destination_amount_swapped,
+            rounded.destination_amount_swapped
+        );
+
+        let source_before = world.bank.token_account(&source).amount;
+        let swap = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &source,
+            &token_a,
+            &token_b,
+            &destination,
+            &pool_mint,
+            &fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in,
+                minimum_amount_out: exact.destination_amount_swapped,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&swap).unwrap();
+        assert_eq!(
+            world.bank.token_account(&source).amount,
+            source_before - amount_in
+        );
+    }
+
+    #[test]
+    fn tokens_of_0_and_18_decimals_trade_both_ways() {
+        let mut world = World::new();
+        let mint_authority = Pubkey::new_unique();
+        let whole = world.bank.add_mint(&mint_authority, 0);
+        let fine = world.bank.add_mint(&mint_authority, 18);
+        let finer = world.bank.add_mint(&mint_authority, 19);
+        let one_fine = 10u64.pow(18);
+
+        assert_eq!(
+            world.add_pool(whole, finer, CurveType::ConstantProduct, 0, (1_000, 1)),
+            Err(SwapError::UnsupportedMintDecimals.into())
+        );
+        // a raw unit of one side is worth 10^16 of the other
+        world
+            .add_pool(
+                whole,
+                fine,
+                CurveType::ConstantProduct,
+                0,
+                (1_000, 10 * one_fine),
+            )
+            .unwrap();
+        let pool = world.pools.last().unwrap();
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account) = (pool.pool_mint, pool.fee_account);
+        let wallet = Pubkey::new_unique();
+        let user_whole = world.bank.add_token_account(&whole, &wallet, 0);
+        world.fund(&whole, &user_whole, 100);
+        let user_fine = world.bank.add_token_account(&fine, &wallet, 0);
+        world.fund(&fine, &user_fine, one_fine);
+        let user_pool = world.bank.add_token_account(&pool_mint, &wallet, 0);
+
+        // quotes can be formatted from the view alone, without reading the mints
+        let view = instruction::get_multiple_pool_states(
+            &program_id(),
+            &[(&swap, &token_a, &token_b, &pool_mint)],
+        )
+        .unwrap();
+        world.bank.process(&view).unwrap();
+        let views = PoolStateView::unpack_many(&RETURN_DATA.lock().unwrap()).unwrap();
+        assert_eq!(
+            (views[0].token_a_decimals, views[0].token_b_decimals),
+            (0, 18)
+        );
+        assert!(views[0].capabilities.supports_deposits);
+        assert!(!views[0].capabilities.needs_oracle);
+
+        for (source, swap_source, swap_destination, destination, amount_in) in [
+            (user_whole, token_a, token_b, user_fine, 10),
+            (user_fine, token_b, token_a, user_whole, one_fine / 10),
+        ] {
+            let destination_before = world.bank.token_account(&destination).amount;
+            let swap = instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                &authority,
+                &wallet,
+                &source,
+                &swap_source,
+                &swap_destination,
+                &destination,
+                &pool_mint,
+                &fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in,
+                    minimum_amount_out: 1,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap();
+            world.bank.process(&swap).unwrap();
+            assert!(world.bank.token_account(&destination).amount > destination_before);
+        }
+
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &user_whole,
+            &user_fine,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: 10_000_000,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        world.bank.process(&deposit).unwrap();
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &pool_mint,
+            &fee_account,
+            &user_pool,
+            &token_a,
+            &token_b,
+            &user_whole,
+            &user_fine,
+            WithdrawAllTokenTypes {
+                pool_token_amount: world.bank.token_account(&user_pool).amount,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 1,
+            },
+        )
+        .unwrap();
+        world.bank.process(&withdraw).unwrap();
+    }
+
+    #[test]
+    fn nested_pools_trade_another_pools_pool_token() {
+        let mut world = World::new();
+        let inner = world.pools[0].swap;
+        let inner_pool_mint = world.pools[0].pool_mint;
+        world
+            .add_pool(
+                world.mints[2],
+                inner_pool_mint,
+                CurveType::ConstantProduct,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE),
+            )
+            .unwrap();
+        let outer_index = world.pools.len() - 1;
+        let outer_pool_mint = world.pools[outer_index].pool_mint;
+        // a pool trading the outer pool's pool token in turn
+        world
+            .add_pool(
+                world.mints[0],
+                outer_pool_mint,
+                CurveType::ConstantProduct,
+                0,
+                (INITIAL_RESERVE, INITIAL_RESERVE),
+            )
+            .unwrap();
+
+        let set_nested_pool = |pool_index: usize, nested_pool: Pubkey| {
+            let pool = &world.pools[pool_index];
+            instruction::set_nested_pool(
+                &program_id(),
+                &pool.swap,
+                &pool.admin,
+                &pool.fee_account,
+                &pool.mint_b,
+                instruction::SetNestedPool { nested_pool },
+            )
+            .unwrap()
+        };
+        let outer = world.pools[outer_index].swap;
+        let link_itself = set_nested_pool(outer_index, outer);
+        let link_other_pool = set_nested_pool(outer_index, world.pools[1].swap);
+        let link = set_nested_pool(outer_index, inner);
+        let unlink = set_nested_pool(outer_index, Pubkey::default());
+        let link_nested = set_nested_pool(outer_index + 1, outer);
+
+        assert_eq!(
+            world.bank.process(&link_itself),
+            Err(SwapError::InvalidNestedPool.into())
+        );
+        // token B has to be the nested pool's own pool token
+        assert_eq!(
+            world.bank.process(&link_other_pool),
+            Err(SwapError::IncorrectPoolMint.into())
+        );
+        world.bank.process(&link).unwrap();
+        let state = SwapVersion::unpack(&world.bank.accounts[&outer].data).unwrap();
+        assert_eq!(state.nested_pool(), Some(inner));
+
+        // one level only
+        assert_eq!(
+            world.bank.process(&link_nested),
+            Err(SwapError::InvalidNestedPool.into())
+        );
+        world.bank.process(&unlink).unwrap();
+        world.bank.process(&link_nested).unwrap();
+        let state = SwapVersion::unpack(&world.bank.accounts[&outer].data).unwrap();
+        assert_eq!(state.nested_pool(), None);
+    }
+}
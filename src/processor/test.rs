@@ -0,0 +1,921 @@
+//! Harness running the program in memory, with the token program serving
+//! its CPIs, over pools and users sharing a few mints.  Each instruction's
+//! tests build their fixture from `World`.
+
+use crate::{
+    curve::{
+        base::{CurveType, SwapCurve},
+        calculator::{CurveCalculator, TradeDirection},
+        concentrated::ConcentratedCurve,
+        constant_price::ConstantPriceCurve,
+        constant_product::ConstantProductCurve,
+        fees::Fees,
+        offset::OffsetCurve,
+        stable::StableCurve,
+        weighted::WeightedCurve,
+    },
+    events::Event,
+    instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes},
+    limits::MAX_INSTRUCTION_STACK_HEIGHT,
+    processor::Processor,
+    state::{ProgramConfig, SwapVersion},
+};
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program_error::ProgramError,
+    program_option::COption,
+    program_pack::Pack,
+    program_stubs::{self, SyscallStubs},
+    pubkey::Pubkey,
+    system_program,
+    sysvar::clock::Clock,
+};
+use spl_token::state::{Account, AccountState, Mint};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::convert::TryInto;
+use std::sync::Mutex;
+
+const USERS: usize = 4;
+pub const INITIAL_USER_BALANCE: u64 = 1_000_000_000;
+pub const INITIAL_RESERVE: u64 = 100_000_000_000;
+
+thread_local! {
+    /// Clock slot, per thread so tests moving it can run side by side
+    pub static SLOT: Cell<u64> = const { Cell::new(1) };
+    /// Events the program logged, per thread like the slot
+    pub static EVENTS: RefCell<Vec<Event>> = const { RefCell::new(Vec::new()) };
+    /// Stack height of the instruction running, the transaction's being 1
+    static STACK_HEIGHT: Cell<usize> = const { Cell::new(1) };
+}
+pub static RETURN_DATA: Mutex<Vec<u8>> = Mutex::new(Vec::new());
+
+pub fn program_id() -> Pubkey {
+    Pubkey::new_from_array([0x5a; 32])
+}
+
+/// Flash swap callback, see `repay_flash_swap`
+pub fn borrower_id() -> Pubkey {
+    Pubkey::new_from_array([0xb0; 32])
+}
+
+/// Aggregator routing through the swap program, see `route`
+fn aggregator_id() -> Pubkey {
+    Pubkey::new_from_array([0xa9; 32])
+}
+
+/// Runs the token program for the swap program's CPIs, and serves the clock
+struct TestSyscallStubs;
+
+impl SyscallStubs for TestSyscallStubs {
+    fn sol_log(&self, _message: &str) {}
+
+    fn sol_log_data(&self, fields: &[&[u8]]) {
+        EVENTS.with(|events| {
+            let events = &mut events.borrow_mut();
+            events.extend(fields.iter().filter_map(|field| Event::unpack(field)));
+        });
+    }
+
+    fn sol_set_return_data(&self, data: &[u8]) {
+        *RETURN_DATA.lock().unwrap() = data.to_vec();
+    }
+
+    fn sol_invoke_signed(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        let height = STACK_HEIGHT.with(Cell::get) + 1;
+        assert!(
+            height <= MAX_INSTRUCTION_STACK_HEIGHT,
+            "call depth exceeded"
+        );
+        STACK_HEIGHT.with(|stack_height| stack_height.set(height));
+        let result = self.invoke_at_height(instruction, account_infos, signers_seeds);
+        STACK_HEIGHT.with(|stack_height| stack_height.set(height - 1));
+        result
+    }
+
+    fn sol_get_stack_height(&self) -> u64 {
+        STACK_HEIGHT.with(Cell::get) as u64
+    }
+
+    fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+        let clock = Clock {
+            slot: SLOT.with(Cell::get),
+            ..Clock::default()
+        };
+        unsafe { *(var_addr as *mut Clock) = clock };
+        solana_program::entrypoint::SUCCESS
+    }
+}
+
+impl TestSyscallStubs {
+    fn invoke_at_height(
+        &self,
+        instruction: &Instruction,
+        account_infos: &[AccountInfo],
+        signers_seeds: &[&[&[u8]]],
+    ) -> ProgramResult {
+        if instruction.program_id == borrower_id() {
+            return repay_flash_swap(instruction, account_infos);
+        }
+        if instruction.program_id == system_program::id() {
+            return transfer_lamports(instruction, account_infos);
+        }
+        if instruction.program_id == aggregator_id() || instruction.program_id == program_id() {
+            let accounts: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    account_infos
+                        .iter()
+                        .find(|info| *info.key == meta.pubkey)
+                        .unwrap()
+                        .clone()
+                })
+                .collect();
+            return run_program(&instruction.program_id, &accounts, &instruction.data);
+        }
+        assert_eq!(instruction.program_id, spl_token::id());
+        let signers: Vec<Pubkey> = signers_seeds
+            .iter()
+            .map(|seeds| Pubkey::create_program_address(seeds, &program_id()).unwrap())
+            .collect();
+        let accounts: Vec<AccountInfo> = instruction
+            .accounts
+            .iter()
+            .map(|meta| {
+                let mut info = account_infos
+                    .iter()
+                    .find(|info| *info.key == meta.pubkey)
+                    .unwrap()
+                    .clone();
+                info.is_signer = info.is_signer || signers.contains(info.key);
+                info
+            })
+            .collect();
+        spl_token::processor::Processor::process(&spl_token::id(), &accounts, &instruction.data)
+    }
+}
+
+/// The swap program, or the aggregator routing through it
+fn run_program(program_id: &Pubkey, accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    if *program_id == aggregator_id() {
+        route(accounts, data)
+    } else {
+        Processor::process(program_id, accounts, data)
+    }
+}
+
+/// Aggregator invoking the swap program with its accounts and the data after
+/// the first byte, through as many more of its own levels as that byte says
+fn route(accounts: &[AccountInfo], data: &[u8]) -> ProgramResult {
+    let (&levels, swap_data) = data.split_first().unwrap();
+    let instruction = Instruction {
+        program_id: if levels > 0 {
+            aggregator_id()
+        } else {
+            program_id()
+        },
+        accounts: accounts
+            .iter()
+            .map(|info| AccountMeta {
+                pubkey: *info.key,
+                is_signer: info.is_signer,
+                is_writable: info.is_writable,
+            })
+            .collect(),
+        data: if levels > 0 {
+            [&[levels - 1], swap_data].concat()
+        } else {
+            swap_data.to_vec()
+        },
+    };
+    solana_program::program::invoke(&instruction, accounts)
+}
+
+/// The swap program's instruction sent through `levels` aggregators, so it
+/// runs at stack height `levels + 1`
+pub fn routed(instruction: Instruction, levels: u8) -> Instruction {
+    Instruction {
+        program_id: aggregator_id(),
+        accounts: instruction.accounts,
+        data: [&[levels - 1], &instruction.data[..]].concat(),
+    }
+}
+
+/// Flash swap callback paying the amount in its data out of its first account
+/// into its second, signed by its third
+fn repay_flash_swap(instruction: &Instruction, account_infos: &[AccountInfo]) -> ProgramResult {
+    let amount = u64::from_le_bytes(instruction.data[..8].try_into().unwrap());
+    let transfer = spl_token::instruction::transfer(
+        &spl_token::id(),
+        &instruction.accounts[0].pubkey,
+        &instruction.accounts[1].pubkey,
+        &instruction.accounts[2].pubkey,
+        &[],
+        amount,
+    )?;
+    TestSyscallStubs.sol_invoke_signed(&transfer, account_infos, &[])
+}
+
+/// The system program's `Transfer`, the only system instruction the program
+/// sends to accounts that already exist
+fn transfer_lamports(instruction: &Instruction, account_infos: &[AccountInfo]) -> ProgramResult {
+    assert_eq!(instruction.data[..4], 2u32.to_le_bytes(), "not a transfer");
+    let lamports = u64::from_le_bytes(instruction.data[4..12].try_into().unwrap());
+    let info = |index: usize| {
+        account_infos
+            .iter()
+            .find(|info| *info.key == instruction.accounts[index].pubkey)
+            .unwrap()
+    };
+    let (from, to) = (info(0), info(1));
+    assert!(from.is_signer, "transfer not signed");
+    **from.try_borrow_mut_lamports()? = from
+        .lamports()
+        .checked_sub(lamports)
+        .ok_or(ProgramError::InsufficientFunds)?;
+    **to.try_borrow_mut_lamports()? += lamports;
+    Ok(())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestAccount {
+    pub lamports: u64,
+    pub data: Vec<u8>,
+    pub owner: Pubkey,
+}
+
+/// Every account of the workload.  Instructions run against copies of their
+/// accounts, which are only written back when the instruction succeeds.
+#[derive(Default)]
+pub struct Bank {
+    pub accounts: HashMap<Pubkey, TestAccount>,
+}
+
+impl Bank {
+    pub fn add_mint(&mut self, authority: &Pubkey, decimals: u8) -> Pubkey {
+        let mint = Mint {
+            mint_authority: COption::Some(*authority),
+            supply: 0,
+            decimals,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        };
+        let mut data = vec![0; Mint::LEN];
+        mint.pack_into_slice(&mut data);
+        self.add(data, spl_token::id())
+    }
+
+    pub fn add_token_account(&mut self, mint: &Pubkey, owner: &Pubkey, amount: u64) -> Pubkey {
+        let account = Account {
+            mint: *mint,
+            owner: *owner,
+            amount,
+            state: AccountState::Initialized,
+            ..Account::default()
+        };
+        let mut data = vec![0; Account::LEN];
+        account.pack_into_slice(&mut data);
+        self.add(data, spl_token::id())
+    }
+
+    pub fn add(&mut self, data: Vec<u8>, owner: Pubkey) -> Pubkey {
+        let key = Pubkey::new_unique();
+        let account = TestAccount {
+            lamports: 1_000_000_000,
+            data,
+            owner,
+        };
+        self.accounts.insert(key, account);
+        key
+    }
+
+    pub fn token_account(&self, key: &Pubkey) -> Account {
+        Account::unpack(&self.accounts[key].data).unwrap()
+    }
+
+    pub fn mint(&self, key: &Pubkey) -> Mint {
+        Mint::unpack(&self.accounts[key].data).unwrap()
+    }
+
+    pub fn process(&mut self, instruction: &Instruction) -> ProgramResult {
+        let mut scratch: Vec<(Pubkey, TestAccount)> = vec![];
+        for meta in &instruction.accounts {
+            if !scratch.iter().any(|(key, _)| *key == meta.pubkey) {
+                let account = self
+                    .accounts
+                    .get(&meta.pubkey)
+                    .cloned()
+                    .unwrap_or(TestAccount {
+                        lamports: 0,
+                        data: vec![],
+                        owner: Pubkey::default(),
+                    });
+                scratch.push((meta.pubkey, account));
+            }
+        }
+
+        let result = {
+            let infos: Vec<AccountInfo> = scratch
+                .iter_mut()
+                .map(|(key, account)| {
+                    let TestAccount {
+                        lamports,
+                        data,
+                        owner,
+                    } = account;
+                    AccountInfo::new(key, false, true, lamports, data, owner, false, 0)
+                })
+                .collect();
+            let accounts: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let mut info = infos
+                        .iter()
+                        .find(|info| *info.key == meta.pubkey)
+                        .unwrap()
+                        .clone();
+                    info.is_signer = meta.is_signer;
+                    info.is_writable = meta.is_writable;
+                    info
+                })
+                .collect();
+            run_program(&instruction.program_id, &accounts, &instruction.data)
+        };
+
+        if result.is_ok() {
+            // as the runtime, which fails the instruction instead
+            for (key, account) in &scratch {
+                let writable = instruction
+                    .accounts
+                    .iter()
+                    .any(|meta| meta.pubkey == *key && meta.is_writable);
+                if !writable {
+                    let before = self.accounts.get(key);
+                    assert!(
+                        before.map_or(account.data.is_empty() && account.lamports == 0, |before| {
+                            before == account
+                        }),
+                        "read-only account {} modified",
+                        key
+                    );
+                }
+            }
+            self.accounts.extend(scratch);
+        }
+        result
+    }
+
+    /// Sum of the balances of all token accounts of each mint
+    pub fn balances_by_mint(&self) -> HashMap<Pubkey, u128> {
+        let mut balances = HashMap::new();
+        for account in self.accounts.values() {
+            if account.owner == spl_token::id() && account.data.len() == Account::LEN {
+                let account = Account::unpack(&account.data).unwrap();
+                *balances.entry(account.mint).or_insert(0) += u128::from(account.amount);
+            }
+        }
+        balances
+    }
+}
+
+/// xorshift, so every run replays the same workload
+pub struct Rng(pub u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, bound: u64) -> u64 {
+        if bound == 0 {
+            0
+        } else {
+            self.next() % bound
+        }
+    }
+}
+
+pub struct Pool {
+    pub swap: Pubkey,
+    pub authority: Pubkey,
+    pub nonce: u8,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub token_a: Pubkey,
+    pub token_b: Pubkey,
+    pub pool_mint: Pubkey,
+    pub fee_account: Pubkey,
+    /// Admin's account the initial supply is minted to
+    pub destination: Pubkey,
+    pub admin: Pubkey,
+    /// Admin's trading token accounts, receiving the withdrawn fees
+    pub admin_a: Pubkey,
+    pub admin_b: Pubkey,
+    pub host_fee_account: Pubkey,
+}
+
+impl Pool {
+    pub fn initialize(
+        &self,
+        fees: Fees,
+        swap_curve: SwapCurve,
+        max_slot_volume: u64,
+    ) -> Instruction {
+        instruction::initialize(
+            &program_id(),
+            &spl_token::id(),
+            &self.swap,
+            &self.authority,
+            &self.token_a,
+            &self.token_b,
+            &self.pool_mint,
+            &self.fee_account,
+            &self.destination,
+            &self.mint_a,
+            &self.mint_b,
+            self.nonce,
+            fees,
+            swap_curve,
+            0,
+            max_slot_volume,
+            0,
+            None,
+            None,
+        )
+        .unwrap()
+    }
+
+    /// The user's swap of `amount_in` in the direction, between their own
+    /// accounts of the pool's mints, with no host and no limits
+    pub fn swap(
+        &self,
+        user: &User,
+        trade_direction: TradeDirection,
+        amount_in: u64,
+    ) -> Instruction {
+        let (source, swap_source, swap_destination, destination) = match trade_direction {
+            TradeDirection::AtoB => (self.mint_a, self.token_a, self.token_b, self.mint_b),
+            TradeDirection::BtoA => (self.mint_b, self.token_b, self.token_a, self.mint_a),
+        };
+        instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &self.swap,
+            &self.authority,
+            &user.wallet,
+            &user.tokens[&source],
+            &swap_source,
+            &swap_destination,
+            &user.tokens[&destination],
+            &self.pool_mint,
+            &self.fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap()
+    }
+}
+
+pub struct User {
+    pub wallet: Pubkey,
+    /// Trading token account per mint
+    pub tokens: HashMap<Pubkey, Pubkey>,
+    /// Pool token account per pool
+    pub pool_tokens: Vec<Pubkey>,
+}
+
+pub struct World {
+    pub bank: Bank,
+    pub mints: Vec<Pubkey>,
+    pub pools: Vec<Pool>,
+    pub users: Vec<User>,
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Three pools over three mints, funded users trading them, and the
+    /// program's CPIs and clock served by `TestSyscallStubs`
+    pub fn new() -> Self {
+        program_stubs::set_syscall_stubs(Box::new(TestSyscallStubs));
+
+        let mut bank = Bank::default();
+        let mint_authority = Pubkey::new_unique();
+        // three mints, each traded in two of the three pools
+        let mints: Vec<Pubkey> = (0..3).map(|_| bank.add_mint(&mint_authority, 6)).collect();
+        let curves = vec![
+            (mints[0], mints[1], CurveType::ConstantProduct, 0),
+            (
+                mints[1],
+                mints[2],
+                CurveType::ConstantProduct,
+                INITIAL_RESERVE / 100,
+            ),
+            (mints[0], mints[2], CurveType::ConstantPrice, 0),
+        ];
+
+        let mut world = Self {
+            bank,
+            mints: mints.clone(),
+            pools: vec![],
+            users: vec![],
+        };
+        for (mint_a, mint_b, curve_type, max_slot_volume) in curves {
+            world
+                .add_pool(
+                    mint_a,
+                    mint_b,
+                    curve_type,
+                    max_slot_volume,
+                    (INITIAL_RESERVE, INITIAL_RESERVE),
+                )
+                .unwrap();
+        }
+        for _ in 0..USERS {
+            let wallet = Pubkey::new_unique();
+            let tokens = mints
+                .iter()
+                .map(|mint| {
+                    let account = world.bank.add_token_account(mint, &wallet, 0);
+                    world.fund(mint, &account, INITIAL_USER_BALANCE);
+                    (*mint, account)
+                })
+                .collect();
+            let pool_mints: Vec<Pubkey> = world.pools.iter().map(|pool| pool.pool_mint).collect();
+            let pool_tokens = pool_mints
+                .iter()
+                .map(|pool_mint| world.bank.add_token_account(pool_mint, &wallet, 0))
+                .collect();
+            world.users.push(User {
+                wallet,
+                tokens,
+                pool_tokens,
+            });
+        }
+        world
+    }
+
+    /// Credits an account, growing its mint's supply to match
+    pub fn fund(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) {
+        let mut token_account = self.bank.token_account(account);
+        token_account.amount += amount;
+        token_account.pack_into_slice(&mut self.bank.accounts.get_mut(account).unwrap().data);
+        let mut mint_state = self.bank.mint(mint);
+        mint_state.supply += amount;
+        mint_state.pack_into_slice(&mut self.bank.accounts.get_mut(mint).unwrap().data);
+    }
+
+    pub fn add_pool(
+        &mut self,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        curve_type: CurveType,
+        max_slot_volume: u64,
+        reserves: (u64, u64),
+    ) -> ProgramResult {
+        self.add_pool_with_mint(
+            mint_a,
+            mint_b,
+            curve_type,
+            max_slot_volume,
+            reserves,
+            |_| {},
+        )
+    }
+
+    /// `add_pool`, with the pool mint edited before the pool is initialized
+    pub fn add_pool_with_mint(
+        &mut self,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        curve_type: CurveType,
+        max_slot_volume: u64,
+        reserves: (u64, u64),
+        edit_pool_mint: impl FnOnce(&mut Mint),
+    ) -> ProgramResult {
+        let pool = self.new_pool(mint_a, mint_b, reserves, edit_pool_mint);
+        let calculator: Box<dyn CurveCalculator> = match curve_type {
+            CurveType::ConstantProduct => Box::new(ConstantProductCurve {}),
+            CurveType::ConstantPrice => Box::new(ConstantPriceCurve {
+                token_b_price: 1,
+                token_b_price_denominator: 0,
+            }),
+            CurveType::Stable => Box::new(StableCurve { amp: 100 }),
+            CurveType::Offset => Box::new(OffsetCurve {
+                token_b_offset: INITIAL_RESERVE,
+                buy_only: false,
+            }),
+            CurveType::Weighted => Box::new(WeightedCurve {
+                token_a_weight: 80,
+                token_b_weight: 20,
+            }),
+            // within 20% of 1:1 either way
+            CurveType::Concentrated => Box::new(ConcentratedCurve {
+                min_token_b_price: 5,
+                min_token_b_price_denominator: 6,
+                max_token_b_price: 6,
+                max_token_b_price_denominator: 5,
+            }),
+        };
+        let fees = Fees::builder()
+            .trade_fee(25, 10_000)
+            .owner_trade_fee(5, 10_000)
+            .owner_withdraw_fee(1, 1_000)
+            .host_fee(20, 100)
+            .build()
+            .unwrap();
+        let initialize = pool.initialize(
+            fees,
+            SwapCurve {
+                curve_type,
+                calculator,
+            },
+            max_slot_volume,
+        );
+        self.bank.process(&initialize)?;
+        self.pools.push(pool);
+        Ok(())
+    }
+
+    /// Accounts of a pool funded with its reserves, left for the test to
+    /// initialize
+    pub fn new_pool(
+        &mut self,
+        mint_a: Pubkey,
+        mint_b: Pubkey,
+        (reserve_a, reserve_b): (u64, u64),
+        edit_pool_mint: impl FnOnce(&mut Mint),
+    ) -> Pool {
+        let swap = self
+            .bank
+            .add(vec![0; SwapVersion::LATEST_LEN], program_id());
+        let (authority, nonce) =
+            Pubkey::find_program_address(&[&swap.to_bytes()[..]], &program_id());
+        let admin = Pubkey::new_unique();
+
+        let token_a = self.bank.add_token_account(&mint_a, &authority, 0);
+        self.fund(&mint_a, &token_a, reserve_a);
+        let token_b = self.bank.add_token_account(&mint_b, &authority, 0);
+        self.fund(&mint_b, &token_b, reserve_b);
+        let pool_mint = self.bank.add_mint(&authority, 6);
+        let mut mint = self.bank.mint(&pool_mint);
+        edit_pool_mint(&mut mint);
+        mint.pack_into_slice(&mut self.bank.accounts.get_mut(&pool_mint).unwrap().data);
+        let fee_account = self.bank.add_token_account(&pool_mint, &admin, 0);
+        let destination = self.bank.add_token_account(&pool_mint, &admin, 0);
+        let admin_a = self.bank.add_token_account(&mint_a, &admin, 0);
+        let admin_b = self.bank.add_token_account(&mint_b, &admin, 0);
+        let host_fee_account = self
+            .bank
+            .add_token_account(&pool_mint, &Pubkey::new_unique(), 0);
+
+        Pool {
+            swap,
+            authority,
+            nonce,
+            mint_a,
+            mint_b,
+            token_a,
+            token_b,
+            pool_mint,
+            fee_account,
+            destination,
+            admin,
+            admin_a,
+            admin_b,
+            host_fee_account,
+        }
+    }
+
+    /// Documents the program's upgrade authority, and the governance program
+    /// whose governances may be pool admins
+    pub fn set_program_config(&mut self, upgrade_authority: Pubkey, governance_program: Pubkey) {
+        let (program_config, bump_seed) = ProgramConfig::find_address(&program_id());
+        let mut data = vec![0; ProgramConfig::LEN];
+        ProgramConfig {
+            is_initialized: true,
+            bump_seed,
+            upgrade_authority,
+            governance_program,
+        }
+        .pack_into_slice(&mut data);
+        self.bank.accounts.insert(
+            program_config,
+            TestAccount {
+                lamports: 1_000_000,
+                data,
+                owner: program_id(),
+            },
+        );
+    }
+
+    /// Zeroed program account at `key`, for the program addresses the
+    /// harness can't have the system program create
+    pub fn allocate(&mut self, key: Pubkey, len: usize) {
+        self.bank.accounts.insert(
+            key,
+            TestAccount {
+                lamports: 1_000_000,
+                data: vec![0; len],
+                owner: program_id(),
+            },
+        );
+    }
+
+    /// One random instruction by a random user, or the pool admin claiming
+    /// fees.  Returns the kind of operation and whether it succeeded.
+    pub fn step(&mut self, rng: &mut Rng) -> (usize, bool) {
+        let pool = &self.pools[rng.below(self.pools.len() as u64) as usize];
+        let user = &self.users[rng.below(USERS as u64) as usize];
+        let user_a = user.tokens[&pool.mint_a];
+        let user_b = user.tokens[&pool.mint_b];
+        let user_pool =
+            user.pool_tokens[self.pools.iter().position(|p| p.swap == pool.swap).unwrap()];
+        let balance = |key: &Pubkey| self.bank.token_account(key).amount;
+        let pool_supply = self.bank.mint(&pool.pool_mint).supply;
+
+        let kind = rng.below(6) as usize;
+        let instruction = match kind {
+            0 => {
+                let (source, swap_source, swap_destination, destination) = if rng.below(2) == 0 {
+                    (user_a, pool.token_a, pool.token_b, user_b)
+                } else {
+                    (user_b, pool.token_b, pool.token_a, user_a)
+                };
+                let amount_in = rng.below(balance(&source) / 4 + 1);
+                let host_fee_account = if rng.below(2) == 0 {
+                    Some(&pool.host_fee_account)
+                } else {
+                    None
+                };
+                instruction::swap(
+                    &program_id(),
+                    &spl_token::id(),
+                    &pool.swap,
+                    &pool.authority,
+                    &user.wallet,
+                    &source,
+                    &swap_source,
+                    &swap_destination,
+                    &destination,
+                    &pool.pool_mint,
+                    &pool.fee_account,
+                    host_fee_account,
+                    None,
+                    instruction::Swap {
+                        amount_in,
+                        minimum_amount_out: 0,
+                        min_price_numerator: 0,
+                        min_price_denominator: 0,
+                    },
+                )
+            }
+            1 => instruction::deposit_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user_a,
+                &user_b,
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user_pool,
+                DepositAllTokenTypes {
+                    pool_token_amount: rng.below(pool_supply / 100 + 1),
+                    maximum_token_a_amount: u64::MAX,
+                    maximum_token_b_amount: u64::MAX,
+                },
+            ),
+            2 => instruction::withdraw_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &pool.pool_mint,
+                &pool.fee_account,
+                &user_pool,
+                &pool.token_a,
+                &pool.token_b,
+                &user_a,
+                &user_b,
+                WithdrawAllTokenTypes {
+                    pool_token_amount: rng.below(balance(&user_pool) + 1),
+                    minimum_token_a_amount: 0,
+                    minimum_token_b_amount: 0,
+                },
+            ),
+            3 => {
+                let source = if rng.below(2) == 0 { user_a } else { user_b };
+                instruction::deposit_single_token_type_exact_amount_in(
+                    &program_id(),
+                    &spl_token::id(),
+                    &pool.swap,
+                    &pool.authority,
+                    &user.wallet,
+                    &source,
+                    &pool.token_a,
+                    &pool.token_b,
+                    &pool.pool_mint,
+                    &user_pool,
+                    instruction::DepositSingleTokenTypeExactAmountIn {
+                        source_token_amount: rng.below(balance(&source) / 4 + 1),
+                        minimum_pool_token_amount: 0,
+                    },
+                )
+            }
+            4 => {
+                let (destination, reserve) = if rng.below(2) == 0 {
+                    (user_a, pool.token_a)
+                } else {
+                    (user_b, pool.token_b)
+                };
+                instruction::withdraw_single_token_type_exact_amount_out(
+                    &program_id(),
+                    &spl_token::id(),
+                    &pool.swap,
+                    &pool.authority,
+                    &user.wallet,
+                    &pool.pool_mint,
+                    &pool.fee_account,
+                    &user_pool,
+                    &pool.token_a,
+                    &pool.token_b,
+                    &destination,
+                    instruction::WithdrawSingleTokenTypeExactAmountOut {
+                        destination_token_amount: rng.below(balance(&reserve) / 1_000 + 1),
+                        maximum_pool_token_amount: u64::MAX,
+                    },
+                )
+            }
+            _ => {
+                // the admin cashes out the accrued fees into their own accounts
+                instruction::withdraw_all_token_types(
+                    &program_id(),
+                    &spl_token::id(),
+                    &pool.swap,
+                    &pool.authority,
+                    &pool.admin,
+                    &pool.pool_mint,
+                    &pool.fee_account,
+                    &pool.fee_account,
+                    &pool.token_a,
+                    &pool.token_b,
+                    &pool.admin_a,
+                    &pool.admin_b,
+                    WithdrawAllTokenTypes {
+                        pool_token_amount: balance(&pool.fee_account),
+                        minimum_token_a_amount: 0,
+                        minimum_token_b_amount: 0,
+                    },
+                )
+            }
+        }
+        .unwrap();
+        (kind, self.bank.process(&instruction).is_ok())
+    }
+
+    pub fn assert_conserved(&self, trading_supplies: &HashMap<Pubkey, u64>) {
+        let balances = self.bank.balances_by_mint();
+        for mint in &self.mints {
+            let supply = self.bank.mint(mint).supply;
+            assert_eq!(
+                supply, trading_supplies[mint],
+                "trading token supply changed"
+            );
+            assert_eq!(balances[mint], u128::from(supply), "trading tokens leaked");
+        }
+        for pool in &self.pools {
+            let supply = self.bank.mint(&pool.pool_mint).supply;
+            assert_eq!(
+                balances[&pool.pool_mint],
+                u128::from(supply),
+                "pool tokens moved without a mint or burn"
+            );
+        }
+    }
+}
@@ -0,0 +1,1167 @@
+//! Checked accessors shared by the processors: unpacking and checking the
+//! accounts an instruction is given, before anything moves.
+
+use crate::amount::TokenAmount;
+use crate::curve::calculator::TradeDirection;
+use crate::curve::fees::Fees;
+use crate::error::SwapError;
+use crate::instruction::SwapInstruction;
+use crate::state::{
+    unpack_upgrade_authority, AdminMultisig, Gate, ProgramConfig, Referrer, SwapState, SwapVersion,
+};
+use crate::token_ops;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::bpf_loader_upgradeable;
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_program::program_option::COption;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::{self, clock::Clock, Sysvar};
+
+use super::Processor;
+
+impl Processor {
+    pub fn unpack_token_account(
+        account_info: &AccountInfo,
+        token_program_id: &Pubkey,
+    ) -> Result<spl_token::state::Account, SwapError> {
+        if account_info.owner != token_program_id {
+            Err(SwapError::IncorrectTokenProgramId)
+        } else {
+            spl_token::state::Account::unpack(&account_info.data.borrow())
+                .map_err(|_| SwapError::ExpectedAccount)
+        }
+    }
+
+    // user token accounts: a frozen one fails here with a clear error, rather
+    // than with a generic one from inside the token program
+    pub fn unpack_user_token_account(
+        account_info: &AccountInfo,
+        token_program_id: &Pubkey,
+    ) -> Result<spl_token::state::Account, SwapError> {
+        let account = Self::unpack_token_account(account_info, token_program_id)?;
+        if account.is_frozen() {
+            Err(SwapError::AccountFrozen)
+        } else {
+            Ok(account)
+        }
+    }
+
+    // the transfer authority moves the user's tokens either as the account's
+    // owner or as its delegate.  a delegate that isn't this authority, or is
+    // approved for less than the instruction moves, fails here with a clear
+    // error, rather than with an owner mismatch or insufficient funds from
+    // inside the token program.  the token CPIs are signed for the swap
+    // authority, so it's never a user's transfer authority, nor does a user
+    // instruction move tokens it owns: the reserves, the vesting and lock
+    // vaults
+    pub fn check_transfer_authority(
+        source_info: &AccountInfo,
+        token_program_id: &Pubkey,
+        authority_info: &AccountInfo,
+        user_transfer_authority_info: &AccountInfo,
+        amount: u64,
+    ) -> Result<(), SwapError> {
+        let source = Self::unpack_token_account(source_info, token_program_id)?;
+        if *user_transfer_authority_info.key == *authority_info.key
+            || source.owner == *authority_info.key
+        {
+            return Err(SwapError::SwapAuthorityTransfer);
+        }
+        if source.owner == *user_transfer_authority_info.key {
+            return Ok(());
+        }
+        if source.delegate != COption::Some(*user_transfer_authority_info.key) {
+            return Err(SwapError::IncorrectDelegate);
+        }
+        if source.delegated_amount < amount {
+            return Err(SwapError::InsufficientDelegatedAmount);
+        }
+        Ok(())
+    }
+
+    // the pool admin, the owner of the pool fee account, either signs itself
+    // or is a multisig, an spl-token one or an `AdminMultisig` of this
+    // program, whose signers sign for it, or a governance whose native
+    // treasury signs for it.  they can be anywhere in the instruction's
+    // accounts, so admin instructions with optional accounts just take them
+    // at the end
+    pub fn check_admin(
+        program_id: &Pubkey,
+        admin_info: &AccountInfo,
+        fee_account_owner: &Pubkey,
+        accounts: &[AccountInfo],
+    ) -> Result<(), SwapError> {
+        if *admin_info.key != *fee_account_owner {
+            return Err(SwapError::InvalidOwner);
+        }
+        if admin_info.is_signer {
+            return Ok(());
+        }
+        let data = admin_info.data.borrow();
+        let (m, signers) = if token_ops::token_interface(admin_info.owner).is_ok()
+            && data.len() == spl_token::state::Multisig::LEN
+        {
+            let multisig =
+                spl_token::state::Multisig::unpack(&data).map_err(|_| SwapError::InvalidOwner)?;
+            let n = usize::from(multisig.n).min(multisig.signers.len());
+            (multisig.m, multisig.signers[..n].to_vec())
+        } else if admin_info.owner == program_id && data.len() == AdminMultisig::LEN {
+            let multisig = AdminMultisig::unpack(&data).map_err(|_| SwapError::InvalidOwner)?;
+            (multisig.m, multisig.signers().to_vec())
+        } else {
+            return Self::check_governance_admin(program_id, admin_info, accounts);
+        };
+        // each signer counts once, however often it's listed
+        let signed = signers
+            .iter()
+            .enumerate()
+            .filter(|(i, signer)| {
+                !signers[..*i].contains(signer)
+                    && accounts
+                        .iter()
+                        .any(|info| info.is_signer && info.key == *signer)
+            })
+            .count();
+        if signed < usize::from(m) {
+            return Err(SwapError::MissingAdminSignatures);
+        }
+        Ok(())
+    }
+
+    // a governance of the governance program set in the program config signs
+    // through its native treasury, as SPL Governance does executing a
+    // proposal, the program config being among the accounts
+    fn check_governance_admin(
+        program_id: &Pubkey,
+        admin_info: &AccountInfo,
+        accounts: &[AccountInfo],
+    ) -> Result<(), SwapError> {
+        let (program_config_key, _bump_seed) = ProgramConfig::find_address(program_id);
+        let program_config_info = accounts
+            .iter()
+            .find(|info| *info.key == program_config_key && info.owner == program_id)
+            .ok_or(SwapError::InvalidOwner)?;
+        let program_config = ProgramConfig::unpack_outdated(&program_config_info.data.borrow())
+            .map_err(|_| SwapError::InvalidOwner)?;
+        let native_treasury = program_config
+            .native_treasury(admin_info.key, admin_info.owner)
+            .ok_or(SwapError::InvalidOwner)?;
+        if !accounts
+            .iter()
+            .any(|info| info.is_signer && *info.key == native_treasury)
+        {
+            return Err(SwapError::MissingAdminSignatures);
+        }
+        Ok(())
+    }
+
+    // the swap an admin setter changes, once it's checked to be this
+    // program's, with its pool fee account given and its admin signing
+    pub(super) fn unpack_admin_swap(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        admin_info: &AccountInfo,
+        pool_fee_account_info: &AccountInfo,
+        accounts: &[AccountInfo],
+    ) -> Result<SwapVersion, ProgramError> {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack_versioned(&swap_info.data.borrow())?;
+        if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+        let fee_account =
+            Self::unpack_token_account(pool_fee_account_info, token_swap.token_program_id())?;
+        Self::check_admin(program_id, admin_info, &fee_account.owner, accounts)?;
+        Ok(token_swap)
+    }
+
+    pub fn check_user_token_accounts(
+        token_program_id: &Pubkey,
+        account_infos: &[&AccountInfo],
+    ) -> Result<(), SwapError> {
+        for account_info in account_infos {
+            Self::unpack_user_token_account(account_info, token_program_id)?;
+        }
+        Ok(())
+    }
+
+    pub fn unpack_mint(
+        account_info: &AccountInfo,
+        token_program_id: &Pubkey,
+    ) -> Result<spl_token::state::Mint, SwapError> {
+        if account_info.owner != token_program_id {
+            Err(SwapError::IncorrectTokenProgramId)
+        } else {
+            spl_token::state::Mint::unpack(&account_info.data.borrow())
+                .map_err(|_| SwapError::ExpectedAccount)
+        }
+    }
+
+    pub fn unpack_referrer(
+        program_id: &Pubkey,
+        referrer_info: &AccountInfo,
+        host_owner: &Pubkey,
+    ) -> Result<Referrer, SwapError> {
+        if referrer_info.owner != program_id {
+            return Err(SwapError::InvalidReferrer);
+        }
+        let referrer = Referrer::unpack(&referrer_info.data.borrow())
+            .map_err(|_| SwapError::InvalidReferrer)?;
+        if referrer.owner != *host_owner {
+            return Err(SwapError::InvalidReferrer);
+        }
+        Ok(referrer)
+    }
+
+    // address of the swap authority, derived from the swap account and its nonce
+    pub fn authority_id(
+        program_id: &Pubkey,
+        my_info: &Pubkey,
+        nonce: u8,
+    ) -> Result<Pubkey, SwapError> {
+        SwapVersion::authority_id(program_id, my_info, nonce)
+    }
+
+    // upgrade authority of this program, read from its program data account
+    pub(super) fn upgrade_authority(
+        program_id: &Pubkey,
+        program_data_info: &AccountInfo,
+    ) -> Result<Option<Pubkey>, ProgramError> {
+        let (program_data_key, _bump_seed) =
+            Pubkey::find_program_address(&[program_id.as_ref()], &bpf_loader_upgradeable::id());
+        if *program_data_info.key != program_data_key
+            || *program_data_info.owner != bpf_loader_upgradeable::id()
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        unpack_upgrade_authority(&program_data_info.data.borrow())
+    }
+
+    // fee schedule for an operation out of the given source account, without
+    // trade fees while the pool is on its fee holiday
+    pub(super) fn fees_for(
+        token_swap: &dyn SwapState,
+        source: &Pubkey,
+    ) -> Result<Fees, ProgramError> {
+        Ok(if token_swap.fee_holiday(Clock::get()?.slot) {
+            token_swap.fees().without_trade_fees()
+        } else if token_swap.is_fee_exempt(source) {
+            token_swap.fees().without_owner_fees()
+        } else {
+            token_swap.fees().clone()
+        })
+    }
+
+    // an amount of pool tokens coming to nothing of a token is too small,
+    // unless the pool holds none of that token
+    pub(super) fn zero_amount_error(reserve: u64) -> SwapError {
+        if reserve == 0 {
+            SwapError::EmptyPool
+        } else {
+            SwapError::AmountTooSmall
+        }
+    }
+
+    // pools pegged to a stake pool only trade at a rate refreshed in the same
+    // slot, so nobody can trade against a rate the stake pool has moved on from
+    pub(super) fn check_rate_fresh(token_swap: &dyn SwapState) -> ProgramResult {
+        if token_swap.stake_pool().is_some() && token_swap.rate_update_slot() != Clock::get()?.slot
+        {
+            return Err(SwapError::StaleRate.into());
+        }
+        Ok(())
+    }
+
+    // a paused pool takes no trades or deposits, LPs can still get out
+    pub(super) fn check_not_paused(token_swap: &dyn SwapState) -> ProgramResult {
+        if token_swap.paused() {
+            return Err(SwapError::PoolPaused.into());
+        }
+        Ok(())
+    }
+
+    // a pool in emergency mode has a curve that can't be trusted, so nothing
+    // trades against it, single-sided withdrawals included, while LPs exit
+    // pro rata through EmergencyWithdraw
+    pub(super) fn check_not_in_emergency(token_swap: &dyn SwapState) -> ProgramResult {
+        if token_swap.emergency() {
+            return Err(SwapError::PoolInEmergency.into());
+        }
+        Ok(())
+    }
+
+    // gated pools only serve traders passing the gate, with the gating token
+    // in an account that isn't frozen, or a credential from the gate's
+    // issuer.  the gate account comes right after the fixed accounts
+    pub(super) fn check_gate(
+        token_swap: &dyn SwapState,
+        trader_info: &AccountInfo,
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+    ) -> ProgramResult {
+        let gate = match token_swap.gate() {
+            Some(gate) => gate,
+            None => return Ok(()),
+        };
+        let gate_info =
+            next_account_info(account_info_iter).map_err(|_| SwapError::GateNotPassed)?;
+        let passed = match gate {
+            Gate::Token(mint) => {
+                // spl-token-2022 accounts start with the same layout
+                token_ops::token_interface(gate_info.owner).is_ok()
+                    && gate_info
+                        .data
+                        .borrow()
+                        .get(..spl_token::state::Account::LEN)
+                        .and_then(|data| spl_token::state::Account::unpack(data).ok())
+                        .is_some_and(|account| {
+                            account.mint == mint
+                                && account.owner == *trader_info.key
+                                && account.amount > 0
+                                && !account.is_frozen()
+                        })
+            }
+            Gate::Credential(issuer) => {
+                *gate_info.owner == issuer
+                    && !gate_info.data_is_empty()
+                    && *gate_info.key == Gate::find_credential_address(&issuer, trader_info.key).0
+            }
+        };
+        if passed {
+            Ok(())
+        } else {
+            Err(SwapError::GateNotPassed.into())
+        }
+    }
+
+    // pools guarding against sandwiches refuse a swap when other traders
+    // trade the pool both ways elsewhere in the same transaction, read from
+    // the instructions sysvar coming after the gate account.  Swap,
+    // RebalanceSwap and FlashSwap legs move the price one known way, single
+    // sided deposits and withdrawals an unknown one, so any of those paired
+    // with another leg counts as both ways.  the other instructions only show
+    // their keys: the trader's own legs are the ones spending from its source
+    // or destination account, or signed by one of their owners.  trades
+    // through other programs, or in other transactions of the same block,
+    // don't show up there, so it only catches bundled sandwiches
+    pub(super) fn check_sandwich(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        token_swap: &dyn SwapState,
+        (source, destination): (&Pubkey, &Pubkey),
+        (source_owner, destination_owner): (&Pubkey, &Pubkey),
+        account_info_iter: &mut std::slice::Iter<AccountInfo>,
+    ) -> ProgramResult {
+        if !token_swap.sandwich_guard() {
+            return Ok(());
+        }
+        let instructions_info = next_account_info(account_info_iter)?;
+        if *instructions_info.key != sysvar::instructions::id() {
+            return Err(ProgramError::UnsupportedSysvar);
+        }
+        let current = sysvar::instructions::load_current_index_checked(instructions_info)?;
+        let count = {
+            let data = instructions_info.data.borrow();
+            data.get(..2)
+                .map(|count| u16::from_le_bytes([count[0], count[1]]))
+                .ok_or(ProgramError::InvalidAccountData)?
+        };
+        let direction_into = |swap_source: &Pubkey| {
+            if *swap_source == *token_swap.token_a_account() {
+                TradeDirection::AtoB
+            } else {
+                TradeDirection::BtoA
+            }
+        };
+
+        // the directions other traders move the pool in, None when unknown
+        let mut directions: Vec<Option<TradeDirection>> = vec![];
+        for index in (0..count).filter(|index| *index != current) {
+            let instruction = sysvar::instructions::load_instruction_at_checked(
+                index as usize,
+                instructions_info,
+            )?;
+            if instruction.program_id != *program_id {
+                continue;
+            }
+            let keys: Vec<&Pubkey> = instruction
+                .accounts
+                .iter()
+                .map(|meta| &meta.pubkey)
+                .collect();
+            // (authority, user account spent from, direction) of each leg
+            let leg = match (SwapInstruction::unpack(&instruction.data), keys.as_slice()) {
+                (
+                    Ok(SwapInstruction::Swap(_)) | Ok(SwapInstruction::RebalanceSwap(_)),
+                    [swap, _, authority, user_source, swap_source, ..],
+                ) => Some((
+                    *swap,
+                    Some(*authority),
+                    Some(*user_source),
+                    Some(direction_into(swap_source)),
+                )),
+                (Ok(SwapInstruction::FlashSwap(_)), [swap, _, swap_source, ..]) => {
+                    Some((*swap, None, None, Some(direction_into(swap_source))))
+                }
+                (
+                    Ok(SwapInstruction::DepositSingleTokenTypeExactAmountIn(_)),
+                    [swap, _, authority, user_source, ..],
+                )
+                | (
+                    Ok(SwapInstruction::WithdrawSingleTokenTypeExactAmountOut(_)),
+                    [swap, _, authority, _, user_source, ..],
+                ) => Some((*swap, Some(*authority), Some(*user_source), None)),
+                _ => None,
+            };
+            let (authority, user_source, direction) = match leg {
+                Some((swap, authority, user_source, direction)) if swap == swap_info.key => {
+                    (authority, user_source, direction)
+                }
+                _ => continue,
+            };
+            let own = user_source
+                .is_some_and(|user_source| user_source == source || user_source == destination)
+                || authority.is_some_and(|authority| {
+                    authority == source_owner || authority == destination_owner
+                });
+            if own {
+                continue;
+            }
+            if directions
+                .iter()
+                .any(|other| other.is_none() || direction.is_none() || *other != direction)
+            {
+                return Err(SwapError::SandwichSuspected.into());
+            }
+            directions.push(direction);
+        }
+        Ok(())
+    }
+
+    // the limit price of a swap, (numerator, denominator) DESTINATION per
+    // SOURCE token, a zero denominator setting no limit
+    pub(super) fn check_min_price(
+        (numerator, denominator): (u64, u64),
+        source_amount_swapped: TokenAmount,
+        destination_amount_swapped: TokenAmount,
+    ) -> Result<(), SwapError> {
+        if denominator == 0 {
+            return Ok(());
+        }
+        let paid = destination_amount_swapped
+            .get()
+            .checked_mul(u128::from(denominator))
+            .ok_or(SwapError::CalculationFailure)?;
+        let min_paid = source_amount_swapped
+            .get()
+            .checked_mul(u128::from(numerator))
+            .ok_or(SwapError::CalculationFailure)?;
+        if paid < min_paid {
+            Err(SwapError::ExceededSlippage)
+        } else {
+            Ok(())
+        }
+    }
+
+    // key checks shared by the trading processors, run before any token account
+    // is unpacked or any curve math, so bad accounts fail for as little compute
+    // as possible - plain key comparisons first, the authority's hash last
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn check_accounts(
+        token_swap: &dyn SwapState,
+        program_id: &Pubkey,
+        swap_info: &AccountInfo,
+        authority_info: &AccountInfo,
+        token_a_info: &AccountInfo,
+        token_b_info: &AccountInfo,
+        pool_mint_info: &AccountInfo,
+        token_program_info: &AccountInfo,
+        pool_fee_account_info: Option<&AccountInfo>,
+    ) -> ProgramResult {
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        if *token_a_info.key != *token_swap.token_a_account()
+            || *token_b_info.key != *token_swap.token_b_account()
+        {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        if let Some(pool_fee_account_info) = pool_fee_account_info {
+            if *pool_fee_account_info.key != *token_swap.pool_fee_account() {
+                return Err(SwapError::IncorrectFeeAccount.into());
+            }
+        }
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::MAX_ADMIN_SIGNERS;
+    use crate::curve::calculator::TradeDirection;
+    use crate::error::SwapError;
+    use crate::instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes};
+    use crate::processor::test::{program_id, TestAccount, World};
+    use crate::state::{AdminMultisig, Gate, SwapVersion};
+    use solana_program::program_option::COption;
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use spl_token::state::{AccountState, Multisig};
+
+    #[test]
+    fn frozen_user_accounts_are_rejected_up_front() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let destination = user.tokens[&pool.mint_b];
+        let mut frozen = world.bank.token_account(&destination);
+        frozen.state = AccountState::Frozen;
+        frozen.pack_into_slice(&mut world.bank.accounts.get_mut(&destination).unwrap().data);
+
+        let swap = pool.swap(user, TradeDirection::AtoB, 1_000);
+        assert_eq!(
+            world.bank.process(&swap),
+            Err(SwapError::AccountFrozen.into())
+        );
+    }
+
+    #[test]
+    fn delegated_transfer_authorities_are_checked_up_front() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (source, destination) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let delegate = Pubkey::new_unique();
+        let mut approved = world.bank.token_account(&source);
+        approved.delegate = COption::Some(delegate);
+        approved.delegated_amount = 1_000;
+        approved.pack_into_slice(&mut world.bank.accounts.get_mut(&source).unwrap().data);
+
+        let swap = |authority: &Pubkey, amount_in| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                authority,
+                &source,
+                &pool.token_a,
+                &pool.token_b,
+                &destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+        let (stranger, over_allowance, within_allowance) = (
+            swap(&Pubkey::new_unique(), 1_000),
+            swap(&delegate, 1_001),
+            swap(&delegate, 1_000),
+        );
+
+        assert_eq!(
+            world.bank.process(&stranger),
+            Err(SwapError::IncorrectDelegate.into())
+        );
+        assert_eq!(
+            world.bank.process(&over_allowance),
+            Err(SwapError::InsufficientDelegatedAmount.into())
+        );
+        assert_eq!(world.bank.process(&within_allowance), Ok(()));
+        assert_eq!(world.bank.token_account(&source).delegated_amount, 0);
+    }
+
+    #[test]
+    fn user_accounts_of_the_wrong_mint_are_rejected_up_front() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        // the pool trades the first two mints, the third belongs to other pools
+        let other_mint = world.mints[2];
+        let swap = |source: &Pubkey, destination: &Pubkey| {
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                source,
+                &pool.token_a,
+                &pool.token_b,
+                destination,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: 1_000,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            )
+            .unwrap()
+        };
+
+        let wrong_source = swap(&user.tokens[&other_mint], &user.tokens[&pool.mint_b]);
+        let wrong_destination = swap(&user.tokens[&pool.mint_a], &user.tokens[&other_mint]);
+        let reversed = swap(&user.tokens[&pool.mint_b], &user.tokens[&pool.mint_a]);
+        assert_eq!(
+            world.bank.process(&wrong_source),
+            Err(SwapError::IncorrectSourceMint.into())
+        );
+        assert_eq!(
+            world.bank.process(&wrong_destination),
+            Err(SwapError::IncorrectDestinationMint.into())
+        );
+        assert_eq!(
+            world.bank.process(&reversed),
+            Err(SwapError::IncorrectSourceMint.into())
+        );
+    }
+
+    #[test]
+    fn zero_amounts_are_rejected_up_front() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (user_a, user_b, user_pool) = (
+            user.tokens[&pool.mint_a],
+            user.tokens[&pool.mint_b],
+            user.pool_tokens[0],
+        );
+        let instructions = [
+            instruction::swap(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user_a,
+                &pool.token_a,
+                &pool.token_b,
+                &user_b,
+                &pool.pool_mint,
+                &pool.fee_account,
+                None,
+                None,
+                instruction::Swap {
+                    amount_in: 0,
+                    minimum_amount_out: 0,
+                    min_price_numerator: 0,
+                    min_price_denominator: 0,
+                },
+            ),
+            instruction::deposit_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user_a,
+                &user_b,
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user_pool,
+                DepositAllTokenTypes {
+                    pool_token_amount: 0,
+                    maximum_token_a_amount: u64::MAX,
+                    maximum_token_b_amount: u64::MAX,
+                },
+            ),
+            instruction::withdraw_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &pool.pool_mint,
+                &pool.fee_account,
+                &user_pool,
+                &pool.token_a,
+                &pool.token_b,
+                &user_a,
+                &user_b,
+                WithdrawAllTokenTypes {
+                    pool_token_amount: 0,
+                    minimum_token_a_amount: 0,
+                    minimum_token_b_amount: 0,
+                },
+            ),
+            instruction::deposit_single_token_type_exact_amount_in(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &user_a,
+                &pool.token_a,
+                &pool.token_b,
+                &pool.pool_mint,
+                &user_pool,
+                instruction::DepositSingleTokenTypeExactAmountIn {
+                    source_token_amount: 0,
+                    minimum_pool_token_amount: 0,
+                },
+            ),
+            instruction::withdraw_single_token_type_exact_amount_out(
+                &program_id(),
+                &spl_token::id(),
+                &pool.swap,
+                &pool.authority,
+                &user.wallet,
+                &pool.pool_mint,
+                &pool.fee_account,
+                &user_pool,
+                &pool.token_a,
+                &pool.token_b,
+                &user_a,
+                instruction::WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount: 0,
+                    maximum_pool_token_amount: u64::MAX,
+                },
+            ),
+        ];
+        for instruction in instructions {
+            assert_eq!(
+                world.bank.process(&instruction.unwrap()),
+                Err(SwapError::ZeroAmount.into())
+            );
+        }
+    }
+
+    #[test]
+    fn gated_pools_only_serve_traders_passing_the_gate() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        let user = &world.users[0];
+        let (wallet, user_a, user_b, user_pool) = (
+            user.wallet,
+            user.tokens[&pool.mint_a],
+            user.tokens[&pool.mint_b],
+            user.pool_tokens[0],
+        );
+
+        let gate_mint = world.bank.add_mint(&admin, 0);
+        let set_gate = |gate, credential| {
+            instruction::set_gate(
+                &program_id(),
+                &swap,
+                &admin,
+                &fee_account,
+                instruction::SetGate { gate, credential },
+            )
+            .unwrap()
+        };
+        world.bank.process(&set_gate(gate_mint, false)).unwrap();
+
+        let swap_ix = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &user_a,
+            &token_a,
+            &token_b,
+            &user_b,
+            &pool_mint,
+            &fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in: 1_000_000,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let gated =
+            |gate_account| instruction::with_gate_account(swap_ix.clone(), &gate_account).unwrap();
+        let empty = world.bank.add_token_account(&gate_mint, &wallet, 0);
+        let others = world
+            .bank
+            .add_token_account(&gate_mint, &Pubkey::new_unique(), 1);
+        let holding = world.bank.add_token_account(&gate_mint, &wallet, 1);
+
+        for refused in [swap_ix.clone(), gated(empty), gated(others)] {
+            assert_eq!(
+                world.bank.process(&refused),
+                Err(SwapError::GateNotPassed.into())
+            );
+        }
+        assert_eq!(world.bank.process(&gated(holding)), Ok(()));
+
+        // credentials live at the issuer's address for the trader
+        let issuer = Pubkey::new_unique();
+        world.bank.process(&set_gate(issuer, true)).unwrap();
+        assert_eq!(
+            SwapVersion::unpack(&world.bank.accounts[&swap].data)
+                .unwrap()
+                .gate(),
+            Some(Gate::Credential(issuer))
+        );
+        let (credential, _) = Gate::find_credential_address(&issuer, &wallet);
+        world.bank.accounts.insert(
+            credential,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data: vec![1],
+                owner: issuer,
+            },
+        );
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &user_a,
+            &user_b,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: 1_000,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            world
+                .bank
+                .process(&instruction::with_gate_account(deposit.clone(), &holding).unwrap()),
+            Err(SwapError::GateNotPassed.into())
+        );
+        assert_eq!(
+            world
+                .bank
+                .process(&instruction::with_gate_account(deposit, &credential).unwrap()),
+            Ok(())
+        );
+
+        // LPs can always leave
+        let withdraw = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &pool_mint,
+            &fee_account,
+            &user_pool,
+            &token_a,
+            &token_b,
+            &user_a,
+            &user_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: 1_000,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        assert_eq!(world.bank.process(&withdraw), Ok(()));
+
+        world
+            .bank
+            .process(&set_gate(Pubkey::default(), true))
+            .unwrap();
+        assert_eq!(world.bank.process(&swap_ix), Ok(()));
+    }
+
+    #[test]
+    fn multisig_admins_sign_through_their_signers() {
+        let mut world = World::new();
+        let (swap, admin, fee_account, pool_mint) = {
+            let pool = &world.pools[0];
+            (pool.swap, pool.admin, pool.fee_account, pool.pool_mint)
+        };
+        let signers = [
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        ];
+
+        // a 2 of 3 admin multisig of the program
+        let multisig = Pubkey::new_unique();
+        world.bank.accounts.insert(
+            multisig,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data: vec![0; AdminMultisig::LEN],
+                owner: program_id(),
+            },
+        );
+        let mut listed = [Pubkey::default(); MAX_ADMIN_SIGNERS];
+        listed[..3].copy_from_slice(&signers);
+        let initialize = |m| {
+            instruction::initialize_admin_multisig(
+                &program_id(),
+                &multisig,
+                instruction::InitializeAdminMultisig { m, signers: listed },
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            world.bank.process(&initialize(4)),
+            Err(SwapError::InvalidInstruction.into())
+        );
+        world.bank.process(&initialize(2)).unwrap();
+        assert_eq!(
+            world.bank.process(&initialize(2)),
+            Err(SwapError::AlreadyInUse.into())
+        );
+
+        let multisig_fee_account = world.bank.add_token_account(&pool_mint, &multisig, 0);
+        let set_fee_account = instruction::set_fee_account(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            &multisig_fee_account,
+        )
+        .unwrap();
+        world.bank.process(&set_fee_account).unwrap();
+
+        let pause = |admin: &Pubkey, fee_account: &Pubkey, enabled| {
+            instruction::set_emergency(
+                &program_id(),
+                &swap,
+                admin,
+                fee_account,
+                instruction::SetEmergency { enabled },
+            )
+            .unwrap()
+        };
+        let signed_by = |instruction, admin: &Pubkey, signers: &[&Pubkey]| {
+            instruction::sign_as_multisig_admin(instruction, admin, signers)
+        };
+        let emergency =
+            |world: &World| match SwapVersion::unpack_versioned(&world.bank.accounts[&swap].data)
+                .unwrap()
+            {
+                SwapVersion::SwapV2(swap_v2) => swap_v2.emergency,
+                SwapVersion::SwapV1(_) => panic!("pool is V1"),
+            };
+
+        // the old admin is out, and one signer, however often it signs, isn't enough
+        assert_eq!(
+            world
+                .bank
+                .process(&pause(&admin, &multisig_fee_account, true)),
+            Err(SwapError::InvalidOwner.into())
+        );
+        assert_eq!(
+            world.bank.process(&signed_by(
+                pause(&multisig, &multisig_fee_account, true),
+                &multisig,
+                &[&signers[0], &signers[0]]
+            )),
+            Err(SwapError::MissingAdminSignatures.into())
+        );
+        assert_eq!(
+            world.bank.process(&signed_by(
+                pause(&multisig, &multisig_fee_account, true),
+                &multisig,
+                &[&signers[0], &Pubkey::new_unique()]
+            )),
+            Err(SwapError::MissingAdminSignatures.into())
+        );
+        world
+            .bank
+            .process(&signed_by(
+                pause(&multisig, &multisig_fee_account, true),
+                &multisig,
+                &[&signers[0], &signers[2]],
+            ))
+            .unwrap();
+        assert!(emergency(&world));
+
+        // spl-token multisigs work the same way
+        let token_multisig = Pubkey::new_unique();
+        let mut data = vec![0; Multisig::LEN];
+        let mut token_signers = [Pubkey::default(); 11];
+        token_signers[..2].copy_from_slice(&signers[1..]);
+        Multisig {
+            m: 1,
+            n: 2,
+            is_initialized: true,
+            signers: token_signers,
+        }
+        .pack_into_slice(&mut data);
+        world.bank.accounts.insert(
+            token_multisig,
+            TestAccount {
+                lamports: 1_000_000_000,
+                data,
+                owner: spl_token::id(),
+            },
+        );
+        let token_multisig_fee_account =
+            world.bank.add_token_account(&pool_mint, &token_multisig, 0);
+        let set_fee_account = instruction::set_fee_account(
+            &program_id(),
+            &swap,
+            &multisig,
+            &multisig_fee_account,
+            &token_multisig_fee_account,
+        )
+        .unwrap();
+        world
+            .bank
+            .process(&signed_by(
+                set_fee_account,
+                &multisig,
+                &[&signers[1], &signers[2]],
+            ))
+            .unwrap();
+        assert_eq!(
+            world.bank.process(&signed_by(
+                pause(&token_multisig, &token_multisig_fee_account, false),
+                &token_multisig,
+                &[&signers[0]]
+            )),
+            Err(SwapError::MissingAdminSignatures.into())
+        );
+        world
+            .bank
+            .process(&signed_by(
+                pause(&token_multisig, &token_multisig_fee_account, false),
+                &token_multisig,
+                &[&signers[2]],
+            ))
+            .unwrap();
+        assert!(!emergency(&world));
+    }
+
+    #[test]
+    fn governance_admins_sign_through_their_native_treasury() {
+        let mut world = World::new();
+        let (swap, admin, fee_account, pool_mint) = {
+            let pool = &world.pools[0];
+            (pool.swap, pool.admin, pool.fee_account, pool.pool_mint)
+        };
+
+        // the upgrade authority documented, governance not configured yet
+        let upgrade_authority = Pubkey::new_unique();
+        world.set_program_config(upgrade_authority, Pubkey::default());
+
+        // a governance of a realm, made the pool admin
+        let governance_program = Pubkey::new_unique();
+        let governance = world.bank.add(vec![0; 236], governance_program);
+        let (native_treasury, _) = Pubkey::find_program_address(
+            &[b"native-treasury", governance.as_ref()],
+            &governance_program,
+        );
+        let governance_fee_account = world.bank.add_token_account(&pool_mint, &governance, 0);
+        let set_fee_account = instruction::set_fee_account(
+            &program_id(),
+            &swap,
+            &admin,
+            &fee_account,
+            &governance_fee_account,
+        )
+        .unwrap();
+        world.bank.process(&set_fee_account).unwrap();
+
+        let pause = instruction::sign_as_governance_admin(
+            instruction::set_paused(
+                &program_id(),
+                &swap,
+                &governance,
+                &governance_fee_account,
+                instruction::SetPaused { paused: true },
+            )
+            .unwrap(),
+            &governance_program,
+            &governance,
+        );
+        let set_governance_program = |upgrade_authority: &Pubkey| {
+            instruction::set_governance_program(
+                &program_id(),
+                upgrade_authority,
+                &admin,
+                instruction::SetGovernanceProgram { governance_program },
+            )
+            .unwrap()
+        };
+        let paused = |world: &World| {
+            SwapVersion::unpack(&world.bank.accounts[&swap].data)
+                .unwrap()
+                .paused()
+        };
+
+        // the program doesn't know the governance program yet
+        assert_eq!(
+            world.bank.process(&pause),
+            Err(SwapError::InvalidOwner.into())
+        );
+        // only the upgrade authority can trust a governance program
+        assert_eq!(
+            world.bank.process(&set_governance_program(&admin)),
+            Err(SwapError::InvalidOwner.into())
+        );
+        world
+            .bank
+            .process(&set_governance_program(&upgrade_authority))
+            .unwrap();
+
+        // the treasury has to sign, as the proposal executes
+        let mut unsigned = pause.clone();
+        for account in unsigned.accounts.iter_mut() {
+            if account.pubkey == native_treasury {
+                account.is_signer = false;
+            }
+        }
+        assert_eq!(
+            world.bank.process(&unsigned),
+            Err(SwapError::MissingAdminSignatures.into())
+        );
+        assert!(!paused(&world));
+        world.bank.process(&pause).unwrap();
+        assert!(paused(&world));
+
+        // the old admin is out
+        let unpause = instruction::set_paused(
+            &program_id(),
+            &swap,
+            &admin,
+            &governance_fee_account,
+            instruction::SetPaused { paused: false },
+        )
+        .unwrap();
+        assert_eq!(
+            world.bank.process(&unpause),
+            Err(SwapError::InvalidOwner.into())
+        );
+    }
+}
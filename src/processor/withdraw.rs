@@ -0,0 +1,1245 @@
+//! Withdrawals, by LPs and out of bricked pools.
+
+use crate::amount::{PoolTokenAmount, TokenAmount};
+use crate::curve::calculator::{RoundDirection, TradeDirection};
+use crate::error::SwapError;
+use crate::events::{Event, WithdrawEvent, WithdrawFeeConvertedEvent};
+use crate::state::{LpPosition, SwapState, SwapVersion};
+use crate::token_ops;
+use solana_program::account_info::{next_account_info, AccountInfo};
+use solana_program::entrypoint::ProgramResult;
+use solana_program::program_error::ProgramError;
+use solana_program::program_pack::Pack;
+use solana_program::pubkey::Pubkey;
+use solana_program::sysvar::{clock::Clock, Sysvar};
+
+use super::Processor;
+
+impl Processor {
+    // pays an already burned withdraw fee into the fee vaults, as its share of
+    // the reserves left after the withdrawal itself
+    #[allow(clippy::too_many_arguments)]
+    fn pay_withdraw_fee_to_vaults<'a, 'b>(
+        swap_info: &AccountInfo<'a>,
+        authority_info: &AccountInfo<'a>,
+        token_program_info: &AccountInfo<'a>,
+        token_swap: &dyn SwapState,
+        pool_mint_info: &AccountInfo<'a>,
+        token_a_info: &AccountInfo<'a>,
+        token_b_info: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+        withdraw_fee: PoolTokenAmount,
+    ) -> ProgramResult {
+        let fee_vault_a_info = next_account_info(account_info_iter)?;
+        let fee_vault_b_info = next_account_info(account_info_iter)?;
+        let (fee_vault_a, fee_vault_b) = token_swap
+            .withdraw_fee_vaults()
+            .ok_or(SwapError::IncorrectFeeAccount)?;
+        if *fee_vault_a_info.key != fee_vault_a || *fee_vault_b_info.key != fee_vault_b {
+            return Err(SwapError::IncorrectFeeAccount.into());
+        }
+
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+        // the fee is burned already, count it back into the supply it's a share of
+        let pool_mint_supply = PoolTokenAmount::from(pool_mint.supply)
+            .checked_add(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+        let results = token_swap
+            .swap_curve()
+            .calculator
+            .pool_tokens_to_trading_tokens(
+                withdraw_fee.get(),
+                pool_mint_supply.get(),
+                TokenAmount::from(token_a.amount).get(),
+                TokenAmount::from(token_b.amount).get(),
+                RoundDirection::Floor,
+            )
+            .ok_or(SwapError::FeeCalculationFailure)?;
+        let token_a_amount = std::cmp::min(
+            token_a.amount,
+            TokenAmount::new(results.token_a_amount).to_u64()?,
+        );
+        let token_b_amount = std::cmp::min(
+            token_b.amount,
+            TokenAmount::new(results.token_b_amount).to_u64()?,
+        );
+
+        if token_a_amount > 0 {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_a_info.clone(),
+                fee_vault_a_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_b_info.clone(),
+                fee_vault_b_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_b_amount,
+            )?;
+        }
+
+        Event::WithdrawFeeConverted(WithdrawFeeConvertedEvent {
+            swap: *swap_info.key,
+            pool_token_amount: withdraw_fee.to_u64()?,
+            token_a_amount,
+            token_b_amount,
+        })
+        .emit();
+        Ok(())
+    }
+
+    // on pools with a withdraw cooldown, a single-sided withdrawal waits out
+    // the cooldown after the last single-sided deposit into the account it
+    // burns from, as recorded in the account's LP position
+    fn check_withdraw_cooldown<'a, 'b>(
+        program_id: &Pubkey,
+        swap_info: &AccountInfo<'a>,
+        token_swap: &dyn SwapState,
+        source_info: &AccountInfo<'a>,
+        account_info_iter: &mut std::slice::Iter<'b, AccountInfo<'a>>,
+    ) -> ProgramResult {
+        let cooldown_slots = token_swap.withdraw_cooldown_slots();
+        if cooldown_slots == 0 {
+            return Ok(());
+        }
+        let position_info = next_account_info(account_info_iter)?;
+        if *position_info.key
+            != LpPosition::find_address(swap_info.key, source_info.key, program_id).0
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        // no single-sided deposit ever minted into the account
+        if position_info.data_is_empty() {
+            return Ok(());
+        }
+        if position_info.owner != program_id {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        let position = LpPosition::unpack(&position_info.data.borrow())?;
+        if !position.cooled_down(cooldown_slots, Clock::get()?.slot) {
+            return Err(SwapError::WithdrawCooldown.into());
+        }
+        Ok(())
+    }
+
+    pub fn process_withdraw_all_token_types(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?; // users' pool token account
+        let token_a_info = next_account_info(account_info_iter)?; //exchange's a account
+        let token_b_info = next_account_info(account_info_iter)?; //exchange's b account
+        let dest_token_a_info = next_account_info(account_info_iter)?; //user's token a
+        let dest_token_b_info = next_account_info(account_info_iter)?; //user's token b
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        Self::check_accounts(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            token_a_info,
+            token_b_info,
+            pool_mint_info,
+            token_program_info,
+            Some(pool_fee_account_info),
+        )?;
+        Self::check_user_token_accounts(
+            token_swap.token_program_id(),
+            &[source_info, dest_token_a_info, dest_token_b_info],
+        )?;
+        Self::check_transfer_authority(
+            source_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            pool_token_amount,
+        )?;
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+
+        let calculator = &token_swap.swap_curve().calculator;
+        // ----------------------------------------------------------------------------- fees
+
+        // if we're withdrawing from the pool fee account or another treasury account then no fee
+        let pool_token_amount = PoolTokenAmount::from(pool_token_amount);
+        let withdraw_fee = if token_swap.is_fee_exempt(source_info.key) {
+            PoolTokenAmount::ZERO
+        } else {
+            //this will always be 0 in prod, because we're validating fees during pool creation and one of the constraints is for the denom to be 0
+            token_swap
+                .fees()
+                .owner_withdraw_fee(pool_token_amount.get())
+                .map(PoolTokenAmount::new)
+                .ok_or(SwapError::FeeCalculationFailure)?
+        };
+
+        //sub fee from pool token amount to withdraw
+        let pool_token_amount = pool_token_amount
+            .checked_sub(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        // ----------------------------------------------------------------------------- calc A and B tokens, similar to deposit
+
+        let results = calculator
+            .pool_tokens_to_trading_tokens(
+                pool_token_amount.get(), //(!) NOTE the value we're passing into this formula is POST fee subtraction. This means that eg if fee is 16%, then not only are we gonna send 16% of lp tokens to the owner, but also there's gonna be 16% more tokens left in the A and B token accouns belonging to the exchange
+                PoolTokenAmount::from(pool_mint.supply).get(),
+                TokenAmount::from(token_a.amount).get(),
+                TokenAmount::from(token_b.amount).get(),
+                RoundDirection::Floor,
+            )
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        let token_a_amount = TokenAmount::new(results.token_a_amount).to_u64()?;
+        let token_a_amount = std::cmp::min(token_a.amount, token_a_amount); //to prevent token balance going negative
+        if token_a_amount < minimum_token_a_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if token_a_amount == 0 && token_a.amount != 0 {
+            return Err(SwapError::AmountTooSmall.into());
+        }
+
+        let token_b_amount = TokenAmount::new(results.token_b_amount).to_u64()?;
+        let token_b_amount = std::cmp::min(token_b.amount, token_b_amount); //to prevent token balance going negative
+        if token_b_amount < minimum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+        if token_b_amount == 0 && token_b.amount != 0 {
+            return Err(SwapError::AmountTooSmall.into());
+        }
+
+        // ----------------------------------------------------------------------------- execution
+
+        // first move the withdraw fee from source account to owner's fee account
+        let fee_in_underlying =
+            !withdraw_fee.is_zero() && token_swap.withdraw_fee_vaults().is_some();
+        if !withdraw_fee.is_zero() && !fee_in_underlying {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_info.clone(), //we're paying the pool withdrawal fee in pool tokens...
+                pool_fee_account_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce(),
+                withdraw_fee.to_u64()?,
+            )?;
+        }
+        //then we burn the remaining lp tokens in user's token account, plus the fee when it's paid in underlying tokens
+        let burn_pool_token_amount = if fee_in_underlying {
+            pool_token_amount
+                .checked_add(withdraw_fee)
+                .ok_or(SwapError::CalculationFailure)?
+        } else {
+            pool_token_amount
+        };
+        token_ops::burn(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(), //must have the authority over burn_account
+            token_swap.nonce(),
+            burn_pool_token_amount.to_u64()?,
+        )?;
+
+        //move A and B tokens from exchange to user
+        if token_a_amount > 0 {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_a_info.clone(),
+                dest_token_a_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_b_info.clone(),
+                dest_token_b_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_b_amount,
+            )?;
+        }
+        if fee_in_underlying {
+            Self::pay_withdraw_fee_to_vaults(
+                swap_info,
+                authority_info,
+                token_program_info,
+                token_swap.as_ref(),
+                pool_mint_info,
+                token_a_info,
+                token_b_info,
+                account_info_iter,
+                withdraw_fee,
+            )?;
+        }
+
+        Event::Withdraw(WithdrawEvent {
+            swap: *swap_info.key,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount: pool_token_amount.to_u64()?,
+            withdraw_fee: withdraw_fee.to_u64()?,
+        })
+        .emit();
+        Self::record_interaction(
+            swap_info,
+            token_a_info,
+            token_b_info,
+            pool_mint_info,
+            withdraw_fee.to_u64()?,
+        )
+    }
+
+    // the balance is read here rather than by the client, so the withdrawal
+    // burns whatever the account holds when it lands
+    pub fn process_withdraw_max(
+        program_id: &Pubkey,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        let swap_info = accounts.first().ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let source_info = accounts.get(4).ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        let source = Self::unpack_token_account(source_info, token_swap.token_program_id())?;
+        Self::process_withdraw_all_token_types(
+            program_id,
+            source.amount,
+            minimum_token_a_amount,
+            minimum_token_b_amount,
+            accounts,
+        )
+    }
+
+    pub fn process_withdraw_single_token_type_exact_amount_out(
+        program_id: &Pubkey,
+        destination_token_amount: u64,
+        maximum_pool_token_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if destination_token_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let swap_token_a_info = next_account_info(account_info_iter)?;
+        let swap_token_b_info = next_account_info(account_info_iter)?;
+        let destination_info = next_account_info(account_info_iter)?;
+        let pool_fee_account_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        Self::check_accounts(
+            token_swap.as_ref(),
+            program_id,
+            swap_info,
+            authority_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            token_program_info,
+            Some(pool_fee_account_info),
+        )?;
+        Self::check_not_in_emergency(token_swap.as_ref())?;
+        Self::check_rate_fresh(token_swap.as_ref())?;
+        let destination_account =
+            Self::unpack_user_token_account(destination_info, token_swap.token_program_id())?;
+        Self::check_user_token_accounts(token_swap.token_program_id(), &[source_info])?;
+        Self::check_withdraw_cooldown(
+            program_id,
+            swap_info,
+            token_swap.as_ref(),
+            source_info,
+            account_info_iter,
+        )?;
+        let swap_token_a =
+            Self::unpack_token_account(swap_token_a_info, token_swap.token_program_id())?;
+        let swap_token_b =
+            Self::unpack_token_account(swap_token_b_info, token_swap.token_program_id())?;
+
+        let trade_direction = if destination_account.mint == swap_token_a.mint {
+            TradeDirection::AtoB
+        } else if destination_account.mint == swap_token_b.mint {
+            TradeDirection::BtoA
+        } else {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        };
+
+        // ----------------------------------------------------------------------------- calc
+
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+        let pool_mint_supply = PoolTokenAmount::from(pool_mint.supply);
+        let swap_token_a_amount = TokenAmount::from(swap_token_a.amount);
+        let swap_token_b_amount = TokenAmount::from(swap_token_b.amount);
+
+        //calc lp tokens to burn
+        let burn_pool_token_amount = token_swap
+            .swap_curve()
+            .withdraw_single_token_type_exact_out(
+                TokenAmount::from(destination_token_amount).get(),
+                swap_token_a_amount.get(),
+                swap_token_b_amount.get(),
+                pool_mint_supply.get(),
+                trade_direction,
+                &Self::fees_for(token_swap.as_ref(), source_info.key)?,
+            )
+            .map(PoolTokenAmount::new)
+            .ok_or(SwapError::ZeroTradingTokens)?;
+
+        //calc withdrawal fee
+        let withdraw_fee = if token_swap.is_fee_exempt(source_info.key) {
+            // withdrawing from a treasury account, don't assess withdraw fee
+            PoolTokenAmount::ZERO
+        } else {
+            token_swap
+                .fees()
+                .owner_withdraw_fee(burn_pool_token_amount.get())
+                .map(PoolTokenAmount::new)
+                .ok_or(SwapError::FeeCalculationFailure)?
+        };
+
+        //subtract the fee
+        let pool_token_amount = burn_pool_token_amount
+            .checked_add(withdraw_fee)
+            .ok_or(SwapError::CalculationFailure)?;
+
+        //check slippage ok
+        if pool_token_amount.to_u64()? > maximum_pool_token_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+
+        Self::check_transfer_authority(
+            source_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            pool_token_amount.to_u64()?,
+        )?;
+
+        // the fee stays in the pool for the remaining LPs when routed to them,
+        // burned with the rest, else goes to the owner's fee account
+        let fee_to_lps = !withdraw_fee.is_zero() && token_swap.single_withdraw_fee_to_lps();
+        let fee_in_underlying =
+            !withdraw_fee.is_zero() && !fee_to_lps && token_swap.withdraw_fee_vaults().is_some();
+        if !withdraw_fee.is_zero() && !fee_to_lps && !fee_in_underlying {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                source_info.clone(),
+                pool_fee_account_info.clone(),
+                user_transfer_authority_info.clone(),
+                token_swap.nonce(),
+                withdraw_fee.to_u64()?,
+            )?;
+        }
+        //burn the rest of LP tokens, with the fee when it's not paid as pool tokens
+        token_ops::burn(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce(),
+            if fee_to_lps || fee_in_underlying {
+                pool_token_amount.to_u64()?
+            } else {
+                burn_pool_token_amount.to_u64()?
+            },
+        )?;
+
+        //finally send the one sided token back to the user
+        match trade_direction {
+            TradeDirection::AtoB => {
+                token_ops::transfer(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    swap_token_a_info.clone(),
+                    destination_info.clone(),
+                    authority_info.clone(),
+                    token_swap.nonce(),
+                    destination_token_amount,
+                )?;
+            }
+            TradeDirection::BtoA => {
+                token_ops::transfer(
+                    swap_info.key,
+                    token_program_info.clone(),
+                    swap_token_b_info.clone(),
+                    destination_info.clone(),
+                    authority_info.clone(),
+                    token_swap.nonce(),
+                    destination_token_amount,
+                )?;
+            }
+        }
+        Self::record_single_sided_volume(
+            swap_info,
+            trade_direction,
+            destination_token_amount,
+            swap_token_a.amount,
+            swap_token_b.amount,
+        )?;
+        if fee_in_underlying {
+            Self::pay_withdraw_fee_to_vaults(
+                swap_info,
+                authority_info,
+                token_program_info,
+                token_swap.as_ref(),
+                pool_mint_info,
+                swap_token_a_info,
+                swap_token_b_info,
+                account_info_iter,
+                withdraw_fee,
+            )?;
+        }
+
+        let (token_a_amount, token_b_amount) = match trade_direction {
+            TradeDirection::AtoB => (destination_token_amount, 0),
+            TradeDirection::BtoA => (0, destination_token_amount),
+        };
+        Event::Withdraw(WithdrawEvent {
+            swap: *swap_info.key,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount: burn_pool_token_amount.to_u64()?,
+            withdraw_fee: withdraw_fee.to_u64()?,
+        })
+        .emit();
+        Self::record_interaction(
+            swap_info,
+            swap_token_a_info,
+            swap_token_b_info,
+            pool_mint_info,
+            withdraw_fee.to_u64()?,
+        )
+    }
+
+    // last resort exit from a bricked pool, pays out a share of the raw balances
+    // without ever touching the curve
+    pub fn process_emergency_withdraw(
+        program_id: &Pubkey,
+        pool_token_amount: u64,
+        minimum_token_a_amount: u64,
+        minimum_token_b_amount: u64,
+        accounts: &[AccountInfo],
+    ) -> ProgramResult {
+        if pool_token_amount == 0 {
+            return Err(SwapError::ZeroAmount.into());
+        }
+        let account_info_iter = &mut accounts.iter();
+        let swap_info = next_account_info(account_info_iter)?;
+        let authority_info = next_account_info(account_info_iter)?;
+        let user_transfer_authority_info = next_account_info(account_info_iter)?;
+        let pool_mint_info = next_account_info(account_info_iter)?;
+        let source_info = next_account_info(account_info_iter)?;
+        let token_a_info = next_account_info(account_info_iter)?;
+        let token_b_info = next_account_info(account_info_iter)?;
+        let dest_token_a_info = next_account_info(account_info_iter)?;
+        let dest_token_b_info = next_account_info(account_info_iter)?;
+        let token_program_info = next_account_info(account_info_iter)?;
+
+        if swap_info.owner != program_id {
+            return Err(ProgramError::IncorrectProgramId);
+        }
+        let token_swap = SwapVersion::unpack(&swap_info.data.borrow())?;
+        if !token_swap.emergency() {
+            return Err(SwapError::NotInEmergency.into());
+        }
+        if *token_program_info.key != *token_swap.token_program_id() {
+            return Err(SwapError::IncorrectTokenProgramId.into());
+        }
+        if *authority_info.key != Self::authority_id(program_id, swap_info.key, token_swap.nonce())?
+        {
+            return Err(SwapError::InvalidProgramAddress.into());
+        }
+        if *token_a_info.key != *token_swap.token_a_account()
+            || *token_b_info.key != *token_swap.token_b_account()
+        {
+            return Err(SwapError::IncorrectSwapAccount.into());
+        }
+        if *pool_mint_info.key != *token_swap.pool_mint() {
+            return Err(SwapError::IncorrectPoolMint.into());
+        }
+
+        Self::check_user_token_accounts(
+            token_swap.token_program_id(),
+            &[source_info, dest_token_a_info, dest_token_b_info],
+        )?;
+        Self::check_transfer_authority(
+            source_info,
+            token_swap.token_program_id(),
+            authority_info,
+            user_transfer_authority_info,
+            pool_token_amount,
+        )?;
+
+        let token_a = Self::unpack_token_account(token_a_info, token_swap.token_program_id())?;
+        let token_b = Self::unpack_token_account(token_b_info, token_swap.token_program_id())?;
+        let pool_mint = Self::unpack_mint(pool_mint_info, token_swap.token_program_id())?;
+
+        let pool_token_amount = PoolTokenAmount::from(pool_token_amount);
+        let pool_supply = PoolTokenAmount::from(pool_mint.supply);
+        if pool_token_amount.is_zero() || pool_token_amount > pool_supply {
+            return Err(SwapError::CalculationFailure.into());
+        }
+        // floor(vault * pool_tokens / supply), never more than the vault holds
+        let pro_rata = |vault: u64| {
+            TokenAmount::from(vault)
+                .get()
+                .checked_mul(pool_token_amount.get())
+                .and_then(|amount| amount.checked_div(pool_supply.get()))
+                .map(TokenAmount::new)
+                .ok_or(SwapError::CalculationFailure)
+        };
+        let token_a_amount = pro_rata(token_a.amount)?.to_u64()?;
+        let token_b_amount = pro_rata(token_b.amount)?.to_u64()?;
+        if token_a_amount < minimum_token_a_amount || token_b_amount < minimum_token_b_amount {
+            return Err(SwapError::ExceededSlippage.into());
+        }
+
+        token_ops::burn(
+            swap_info.key,
+            token_program_info.clone(),
+            source_info.clone(),
+            pool_mint_info.clone(),
+            user_transfer_authority_info.clone(),
+            token_swap.nonce(),
+            pool_token_amount.to_u64()?,
+        )?;
+        if token_a_amount > 0 {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_a_info.clone(),
+                dest_token_a_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_a_amount,
+            )?;
+        }
+        if token_b_amount > 0 {
+            token_ops::transfer(
+                swap_info.key,
+                token_program_info.clone(),
+                token_b_info.clone(),
+                dest_token_b_info.clone(),
+                authority_info.clone(),
+                token_swap.nonce(),
+                token_b_amount,
+            )?;
+        }
+
+        Event::EmergencyWithdraw(WithdrawEvent {
+            swap: *swap_info.key,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount: pool_token_amount.to_u64()?,
+            withdraw_fee: 0,
+        })
+        .emit();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::SwapError;
+    use crate::instruction::{self, DepositAllTokenTypes, WithdrawAllTokenTypes};
+    use crate::processor::test::{borrower_id, program_id, World, INITIAL_USER_BALANCE, SLOT};
+    use crate::state::{LpPosition, SwapVersion};
+    use solana_program::instruction::Instruction;
+    use solana_program::program_error::ProgramError;
+    use solana_program::program_pack::Pack;
+    use solana_program::pubkey::Pubkey;
+    use std::collections::HashMap;
+
+    #[test]
+    fn single_sided_withdrawals_wait_out_the_cooldown() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        let (mint_a, mint_b) = (pool.mint_a, pool.mint_b);
+        let depositor = &world.users[0];
+        let (depositor_wallet, depositor_a, depositor_b, depositor_pool) = (
+            depositor.wallet,
+            depositor.tokens[&mint_a],
+            depositor.tokens[&mint_b],
+            depositor.pool_tokens[0],
+        );
+        let holder = &world.users[1];
+        let (wallet, user_a, user_b) = (
+            holder.wallet,
+            holder.tokens[&mint_a],
+            holder.tokens[&mint_b],
+        );
+        let user_pool = holder.pool_tokens[0];
+
+        // both wallets hold pool tokens from deposits of both tokens
+        for (wallet, user_a, user_b, user_pool) in [
+            (depositor_wallet, depositor_a, depositor_b, depositor_pool),
+            (wallet, user_a, user_b, user_pool),
+        ] {
+            let deposit_all = instruction::deposit_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                &authority,
+                &wallet,
+                &user_a,
+                &user_b,
+                &token_a,
+                &token_b,
+                &pool_mint,
+                &user_pool,
+                DepositAllTokenTypes {
+                    pool_token_amount: 1_000_000,
+                    maximum_token_a_amount: u64::MAX,
+                    maximum_token_b_amount: u64::MAX,
+                },
+            )
+            .unwrap();
+            world.bank.process(&deposit_all).unwrap();
+        }
+
+        let set_withdraw_cooldown = |cooldown_slots| {
+            instruction::set_withdraw_cooldown(
+                &program_id(),
+                &swap,
+                &admin,
+                &fee_account,
+                instruction::SetWithdrawCooldown { cooldown_slots },
+            )
+            .unwrap()
+        };
+        world.bank.process(&set_withdraw_cooldown(2)).unwrap();
+
+        let deposit = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &depositor_wallet,
+            &depositor_a,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &depositor_pool,
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 1_000_000,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        let withdraw = |wallet: &Pubkey, source: &Pubkey, destination: &Pubkey| {
+            instruction::withdraw_single_token_type_exact_amount_out(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                &authority,
+                wallet,
+                &pool_mint,
+                &fee_account,
+                source,
+                &token_a,
+                &token_b,
+                destination,
+                instruction::WithdrawSingleTokenTypeExactAmountOut {
+                    destination_token_amount: 100_000,
+                    maximum_pool_token_amount: u64::MAX,
+                },
+            )
+            .unwrap()
+        };
+        let depositor_withdraw = instruction::with_source_lp_position(
+            withdraw(&depositor_wallet, &depositor_pool, &depositor_a),
+            &program_id(),
+        )
+        .unwrap();
+        let holder_withdraw = instruction::with_source_lp_position(
+            withdraw(&wallet, &user_pool, &user_a),
+            &program_id(),
+        )
+        .unwrap();
+
+        // the cooldown needs the LP position of the account minted into, which
+        // the harness creates up front as it doesn't run the system program
+        assert_eq!(
+            world.bank.process(&deposit),
+            Err(ProgramError::NotEnoughAccountKeys)
+        );
+        let (position, _) = LpPosition::find_address(&swap, &depositor_pool, &program_id());
+        world.allocate(position, LpPosition::LEN);
+        let deposit =
+            instruction::with_lp_position(deposit, &program_id(), &depositor_wallet).unwrap();
+
+        SLOT.with(|slot| slot.set(500));
+        world.bank.process(&deposit).unwrap();
+        let recorded = LpPosition::unpack(&world.bank.accounts[&position].data).unwrap();
+        assert_eq!(recorded.pool_account, depositor_pool);
+        assert_eq!(recorded.last_single_deposit_slot, 500);
+
+        // only the account minted into waits, another holder withdraws at once
+        world.bank.process(&holder_withdraw).unwrap();
+        assert_eq!(
+            world.bank.process(&depositor_withdraw),
+            Err(SwapError::WithdrawCooldown.into())
+        );
+        // nor can the depositor present another account's position
+        let mut elsewhere = depositor_withdraw.clone();
+        elsewhere.accounts[10] = holder_withdraw.accounts[10].clone();
+        assert_eq!(
+            world.bank.process(&elsewhere),
+            Err(SwapError::InvalidProgramAddress.into())
+        );
+        SLOT.with(|slot| slot.set(501));
+        assert_eq!(
+            world.bank.process(&depositor_withdraw),
+            Err(SwapError::WithdrawCooldown.into())
+        );
+        // withdrawing both tokens isn't held up
+        let withdraw_all = instruction::withdraw_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &depositor_wallet,
+            &pool_mint,
+            &fee_account,
+            &depositor_pool,
+            &token_a,
+            &token_b,
+            &depositor_a,
+            &depositor_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: 1_000,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&withdraw_all).unwrap();
+
+        SLOT.with(|slot| slot.set(502));
+        world.bank.process(&depositor_withdraw).unwrap();
+
+        // without a cooldown single-sided withdrawals follow deposits at once,
+        // with no LP position
+        world.bank.process(&set_withdraw_cooldown(0)).unwrap();
+        let deposit = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &depositor_wallet,
+            &depositor_a,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &depositor_pool,
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: 1_000_000,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        world.bank.process(&deposit).unwrap();
+        world
+            .bank
+            .process(&withdraw(&depositor_wallet, &depositor_pool, &depositor_a))
+            .unwrap();
+    }
+
+    #[test]
+    fn single_sided_withdraw_fees_can_stay_with_the_lps() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account, admin) = (pool.pool_mint, pool.fee_account, pool.admin);
+        let user = &world.users[0];
+        let (wallet, user_a, user_b) = (
+            user.wallet,
+            user.tokens[&pool.mint_a],
+            user.tokens[&pool.mint_b],
+        );
+        let user_pool = user.pool_tokens[0];
+
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &user_a,
+            &user_b,
+            &token_a,
+            &token_b,
+            &pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: 1_000_000,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        world.bank.process(&deposit).unwrap();
+
+        let set_to_lps = |to_lps| {
+            instruction::set_single_withdraw_fee_to_lps(
+                &program_id(),
+                &swap,
+                &admin,
+                &fee_account,
+                instruction::SetSingleWithdrawFeeToLps { to_lps },
+            )
+            .unwrap()
+        };
+        let withdraw = instruction::withdraw_single_token_type_exact_amount_out(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &pool_mint,
+            &fee_account,
+            &user_pool,
+            &token_a,
+            &token_b,
+            &user_a,
+            instruction::WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount: 1_000_000,
+                maximum_pool_token_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        // pool tokens the withdrawal takes from the user, burned, and paid to the owner
+        let withdraw_once = |world: &mut World| {
+            let bank = &world.bank;
+            let (user_before, supply_before, fee_before) = (
+                bank.token_account(&user_pool).amount,
+                bank.mint(&pool_mint).supply,
+                bank.token_account(&fee_account).amount,
+            );
+            world.bank.process(&withdraw).unwrap();
+            let bank = &world.bank;
+            (
+                user_before - bank.token_account(&user_pool).amount,
+                supply_before - bank.mint(&pool_mint).supply,
+                bank.token_account(&fee_account).amount - fee_before,
+            )
+        };
+
+        let mut signed_by_someone_else = set_to_lps(true);
+        signed_by_someone_else.accounts[1].pubkey = wallet;
+        assert!(world.bank.process(&signed_by_someone_else).is_err());
+
+        let (taken, burned, paid) = withdraw_once(&mut world);
+        assert!(paid > 0);
+        assert_eq!(taken, burned + paid);
+
+        // routed to the LPs, the fee is burned with the rest, the tokens it stands
+        // for staying in the pool
+        world.bank.process(&set_to_lps(true)).unwrap();
+        let swap_state = SwapVersion::unpack(&world.bank.accounts[&swap].data).unwrap();
+        assert!(swap_state.single_withdraw_fee_to_lps());
+        let (taken_for_lps, burned, paid) = withdraw_once(&mut world);
+        assert_eq!((burned, paid), (taken_for_lps, 0));
+        assert!(taken_for_lps >= taken);
+
+        world.bank.process(&set_to_lps(false)).unwrap();
+        let (_, _, paid) = withdraw_once(&mut world);
+        assert!(paid > 0);
+    }
+
+    #[test]
+    fn withdraw_max_burns_the_balance_as_it_lands() {
+        let mut world = World::new();
+        let pool = &world.pools[0];
+        let (swap, authority, token_a, token_b) =
+            (pool.swap, pool.authority, pool.token_a, pool.token_b);
+        let (pool_mint, fee_account) = (pool.pool_mint, pool.fee_account);
+        let user = &world.users[0];
+        let (wallet, user_a, user_b, user_pool) = (
+            user.wallet,
+            user.tokens[&pool.mint_a],
+            user.tokens[&pool.mint_b],
+            user.pool_tokens[0],
+        );
+
+        let deposit = |pool_token_amount| {
+            instruction::deposit_all_token_types(
+                &program_id(),
+                &spl_token::id(),
+                &swap,
+                &authority,
+                &wallet,
+                &user_a,
+                &user_b,
+                &token_a,
+                &token_b,
+                &pool_mint,
+                &user_pool,
+                DepositAllTokenTypes {
+                    pool_token_amount,
+                    maximum_token_a_amount: u64::MAX,
+                    maximum_token_b_amount: u64::MAX,
+                },
+            )
+            .unwrap()
+        };
+        let withdraw_max = instruction::withdraw_max(
+            &program_id(),
+            &spl_token::id(),
+            &swap,
+            &authority,
+            &wallet,
+            &pool_mint,
+            &fee_account,
+            &user_pool,
+            &token_a,
+            &token_b,
+            &user_a,
+            &user_b,
+            instruction::WithdrawMax {
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+
+        // the balance grows after the client would have read it
+        world.bank.process(&deposit(1_000_000)).unwrap();
+        world.bank.process(&deposit(234_567)).unwrap();
+        let (supply_before, fee_before) = (
+            world.bank.mint(&pool_mint).supply,
+            world.bank.token_account(&fee_account).amount,
+        );
+        world.bank.process(&withdraw_max).unwrap();
+
+        // all of it leaves the account, the owner's withdraw fee included
+        let paid = world.bank.token_account(&fee_account).amount - fee_before;
+        assert_eq!(world.bank.token_account(&user_pool).amount, 0);
+        assert!(paid > 0);
+        assert_eq!(
+            supply_before - world.bank.mint(&pool_mint).supply,
+            1_234_567 - paid
+        );
+
+        assert_eq!(
+            world.bank.process(&withdraw_max),
+            Err(SwapError::ZeroAmount.into())
+        );
+    }
+
+    #[test]
+    fn pools_in_emergency_refuse_trades_and_deposits() {
+        let mut world = World::new();
+        let trading_supplies: HashMap<Pubkey, u64> = world
+            .mints
+            .iter()
+            .map(|mint| (*mint, world.bank.mint(mint).supply))
+            .collect();
+        let pool = &world.pools[0];
+        let user = &world.users[0];
+        let (user_a, user_b) = (user.tokens[&pool.mint_a], user.tokens[&pool.mint_b]);
+        let user_pool = user.pool_tokens[0];
+        let amount = INITIAL_USER_BALANCE / 100;
+        let pool_tokens = world.bank.mint(&pool.pool_mint).supply / 1_000;
+        let set_emergency = |enabled| {
+            instruction::set_emergency(
+                &program_id(),
+                &pool.swap,
+                &pool.admin,
+                &pool.fee_account,
+                instruction::SetEmergency { enabled },
+            )
+            .unwrap()
+        };
+        let swap = instruction::swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            &pool.pool_mint,
+            &pool.fee_account,
+            None,
+            None,
+            instruction::Swap {
+                amount_in: amount,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let flash_swap = instruction::flash_swap(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &pool.token_a,
+            &pool.token_b,
+            &user_b,
+            &pool.pool_mint,
+            &pool.fee_account,
+            amount,
+            Instruction {
+                program_id: borrower_id(),
+                accounts: vec![],
+                data: vec![],
+            },
+        )
+        .unwrap();
+        let deposit = instruction::deposit_all_token_types(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &user_b,
+            &pool.token_a,
+            &pool.token_b,
+            &pool.pool_mint,
+            &user_pool,
+            DepositAllTokenTypes {
+                pool_token_amount: pool_tokens,
+                maximum_token_a_amount: u64::MAX,
+                maximum_token_b_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        let deposit_single = instruction::deposit_single_token_type_exact_amount_in(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &user_a,
+            &pool.token_a,
+            &pool.token_b,
+            &pool.pool_mint,
+            &user_pool,
+            instruction::DepositSingleTokenTypeExactAmountIn {
+                source_token_amount: amount,
+                minimum_pool_token_amount: 0,
+            },
+        )
+        .unwrap();
+        let withdraw_single = instruction::withdraw_single_token_type_exact_amount_out(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &pool.pool_mint,
+            &pool.fee_account,
+            &user_pool,
+            &pool.token_a,
+            &pool.token_b,
+            &user_a,
+            instruction::WithdrawSingleTokenTypeExactAmountOut {
+                destination_token_amount: amount / 10,
+                maximum_pool_token_amount: u64::MAX,
+            },
+        )
+        .unwrap();
+        let emergency_withdraw = instruction::emergency_withdraw(
+            &program_id(),
+            &spl_token::id(),
+            &pool.swap,
+            &pool.authority,
+            &user.wallet,
+            &pool.pool_mint,
+            &user_pool,
+            &pool.token_a,
+            &pool.token_b,
+            &user_a,
+            &user_b,
+            WithdrawAllTokenTypes {
+                pool_token_amount: pool_tokens,
+                minimum_token_a_amount: 0,
+                minimum_token_b_amount: 0,
+            },
+        )
+        .unwrap();
+
+        world.bank.process(&deposit).unwrap();
+        world.bank.process(&set_emergency(true)).unwrap();
+        // nothing trades against the curve that triggered the emergency
+        for halted in [
+            &swap,
+            &flash_swap,
+            &deposit,
+            &deposit_single,
+            &withdraw_single,
+        ] {
+            assert_eq!(
+                world.bank.process(halted),
+                Err(SwapError::PoolInEmergency.into())
+            );
+        }
+        // LPs exit pro rata, through the pool's token program only
+        let mut other_token_program = emergency_withdraw.clone();
+        let token_program = other_token_program
+            .accounts
+            .iter_mut()
+            .find(|meta| meta.pubkey == spl_token::id())
+            .unwrap();
+        token_program.pubkey = Pubkey::new_unique();
+        assert_eq!(
+            world.bank.process(&other_token_program),
+            Err(SwapError::IncorrectTokenProgramId.into())
+        );
+        assert_eq!(world.bank.process(&emergency_withdraw), Ok(()));
+
+        world.bank.process(&set_emergency(false)).unwrap();
+        assert_eq!(world.bank.process(&swap), Ok(()));
+        world.assert_conserved(&trading_supplies);
+    }
+}
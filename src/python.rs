@@ -0,0 +1,312 @@
+//! Python bindings for the curve and fee math, so off-chain simulations run
+//! the exact on-chain arithmetic.  Build with `--features python`, e.g. via
+//! maturin, and `import rebuild_token_swap`.
+//!
+//! Every calculation returns `None` where the program would fail with
+//! `CalculationFailure`.
+
+use crate::curve::{
+    base::{CurveType, SwapCurve},
+    calculator::{RoundDirection, TradeDirection, TradingTokenResult},
+    concentrated::ConcentratedCurve,
+    constant_price::ConstantPriceCurve,
+    constant_product::ConstantProductCurve,
+    fees::{FeeTiers, Fees, TradeFees},
+    offset::OffsetCurve,
+    stable::StableCurve,
+    weighted::WeightedCurve,
+};
+use pyo3::prelude::*;
+
+/// Fee schedule of a pool, see `curve::fees::Fees`
+#[pyclass(name = "Fees")]
+#[derive(Clone)]
+pub struct PyFees(Fees);
+
+#[pymethods]
+impl PyFees {
+    #[new]
+    #[pyo3(signature = (
+        trade_fee_numerator = 0,
+        trade_fee_denominator = 0,
+        owner_trade_fee_numerator = 0,
+        owner_trade_fee_denominator = 0,
+        owner_withdraw_fee_numerator = 0,
+        owner_withdraw_fee_denominator = 0,
+        host_fee_numerator = 0,
+        host_fee_denominator = 0,
+    ))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        trade_fee_numerator: u64,
+        trade_fee_denominator: u64,
+        owner_trade_fee_numerator: u64,
+        owner_trade_fee_denominator: u64,
+        owner_withdraw_fee_numerator: u64,
+        owner_withdraw_fee_denominator: u64,
+        host_fee_numerator: u64,
+        host_fee_denominator: u64,
+    ) -> Self {
+        Self(Fees {
+            trade_fee_numerator,
+            trade_fee_denominator,
+            owner_trade_fee_numerator,
+            owner_trade_fee_denominator,
+            owner_withdraw_fee_numerator,
+            owner_withdraw_fee_denominator,
+            host_fee_numerator,
+            host_fee_denominator,
+        })
+    }
+
+    #[staticmethod]
+    fn standard_30bps() -> Self {
+        Self(Fees::standard_30bps())
+    }
+
+    /// Raises `ValueError` when the program would reject the schedule
+    fn validate(&self) -> PyResult<()> {
+        self.0
+            .validate()
+            .map_err(|err| pyo3::exceptions::PyValueError::new_err(err.to_string()))
+    }
+
+    fn trading_fee(&self, trading_tokens: u128) -> Option<u128> {
+        self.0.trading_fee(trading_tokens)
+    }
+
+    fn owner_trading_fee(&self, trading_tokens: u128) -> Option<u128> {
+        self.0.owner_trading_fee(trading_tokens)
+    }
+
+    fn owner_withdraw_fee(&self, pool_tokens: u128) -> Option<u128> {
+        self.0.owner_withdraw_fee(pool_tokens)
+    }
+
+    fn host_fee(&self, owner_fee: u128) -> Option<u128> {
+        self.0.host_fee(owner_fee)
+    }
+
+    /// `(trade_fee, owner_fee, amount_less_fees)` of a trade
+    fn apply(&self, source_amount: u128) -> Option<(u128, u128, u128)> {
+        let TradeFees {
+            trade_fee,
+            owner_fee,
+            amount_less_fees,
+        } = self.0.apply(source_amount)?;
+        Some((trade_fee, owner_fee, amount_less_fees))
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+/// Outcome of `SwapCurve.swap`, see `curve::base::SwapResult`
+#[pyclass(name = "SwapResult")]
+pub struct PySwapResult {
+    #[pyo3(get)]
+    new_swap_source_amount: u128,
+    #[pyo3(get)]
+    new_swap_destination_amount: u128,
+    #[pyo3(get)]
+    source_amount_swapped: u128,
+    #[pyo3(get)]
+    destination_amount_swapped: u128,
+    #[pyo3(get)]
+    trade_fee: u128,
+    #[pyo3(get)]
+    owner_fee: u128,
+}
+
+/// Curve of a pool, see `curve::base::SwapCurve`
+#[pyclass(name = "SwapCurve", unsendable)]
+pub struct PySwapCurve(SwapCurve);
+
+#[pymethods]
+impl PySwapCurve {
+    #[staticmethod]
+    fn constant_product() -> Self {
+        Self(SwapCurve {
+            curve_type: CurveType::ConstantProduct,
+            calculator: Box::new(ConstantProductCurve {}),
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (token_b_price, token_b_price_denominator = 0))]
+    fn constant_price(token_b_price: u64, token_b_price_denominator: u64) -> Self {
+        Self(SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price,
+                token_b_price_denominator,
+            }),
+        })
+    }
+
+    #[staticmethod]
+    fn stable(amp: u64) -> Self {
+        Self(SwapCurve {
+            curve_type: CurveType::Stable,
+            calculator: Box::new(StableCurve { amp }),
+        })
+    }
+
+    #[staticmethod]
+    #[pyo3(signature = (token_b_offset, buy_only = false))]
+    fn offset(token_b_offset: u64, buy_only: bool) -> Self {
+        Self(SwapCurve {
+            curve_type: CurveType::Offset,
+            calculator: Box::new(OffsetCurve {
+                token_b_offset,
+                buy_only,
+            }),
+        })
+    }
+
+    #[staticmethod]
+    fn weighted(token_a_weight: u64, token_b_weight: u64) -> Self {
+        Self(SwapCurve {
+            curve_type: CurveType::Weighted,
+            calculator: Box::new(WeightedCurve {
+                token_a_weight,
+                token_b_weight,
+            }),
+        })
+    }
+
+    #[staticmethod]
+    fn concentrated(
+        min_token_b_price: u64,
+        min_token_b_price_denominator: u64,
+        max_token_b_price: u64,
+        max_token_b_price_denominator: u64,
+    ) -> Self {
+        Self(SwapCurve {
+            curve_type: CurveType::Concentrated,
+            calculator: Box::new(ConcentratedCurve {
+                min_token_b_price,
+                min_token_b_price_denominator,
+                max_token_b_price,
+                max_token_b_price_denominator,
+            }),
+        })
+    }
+
+    /// Raises `ValueError` when the program would reject the curve
+    fn validate(&self) -> PyResult<()> {
+        self.0
+            .calculator
+            .validate()
+            .map_err(|err| pyo3::exceptions::PyValueError::new_err(err.to_string()))
+    }
+
+    /// Trade at the base fee schedule, ignoring any size tiers of the pool
+    fn swap(
+        &self,
+        source_amount: u128,
+        swap_source_amount: u128,
+        swap_destination_amount: u128,
+        trade_direction: TradeDirection,
+        fees: &PyFees,
+    ) -> Option<PySwapResult> {
+        let result = self.0.swap(
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            trade_direction,
+            &fees.0,
+            &FeeTiers::default(),
+        )?;
+        Some(PySwapResult {
+            new_swap_source_amount: result.new_swap_source_amount,
+            new_swap_destination_amount: result.new_swap_destination_amount,
+            source_amount_swapped: result.source_amount_swapped,
+            destination_amount_swapped: result.destination_amount_swapped,
+            trade_fee: result.trade_fee,
+            owner_fee: result.owner_fee,
+        })
+    }
+
+    /// Pool tokens minted for a single sided deposit
+    fn deposit_single_token_type(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        fees: &PyFees,
+    ) -> Option<u128> {
+        self.0.deposit_single_token_type(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            &fees.0,
+        )
+    }
+
+    /// Pool tokens burned for a single sided withdrawal, before the owner
+    /// withdraw fee
+    fn withdraw_single_token_type_exact_out(
+        &self,
+        source_amount: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        pool_supply: u128,
+        trade_direction: TradeDirection,
+        fees: &PyFees,
+    ) -> Option<u128> {
+        self.0.withdraw_single_token_type_exact_out(
+            source_amount,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            pool_supply,
+            trade_direction,
+            &fees.0,
+        )
+    }
+
+    /// `(token_a_amount, token_b_amount)` backing the given pool tokens
+    fn pool_tokens_to_trading_tokens(
+        &self,
+        pool_tokens: u128,
+        pool_token_supply: u128,
+        swap_token_a_amount: u128,
+        swap_token_b_amount: u128,
+        round_direction: RoundDirection,
+    ) -> Option<(u128, u128)> {
+        let TradingTokenResult {
+            token_a_amount,
+            token_b_amount,
+        } = self.0.calculator.pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )?;
+        Some((token_a_amount, token_b_amount))
+    }
+
+    fn new_pool_supply(&self) -> u128 {
+        self.0.calculator.new_pool_supply()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.0)
+    }
+}
+
+#[pymodule]
+fn rebuild_token_swap(_py: Python, m: &PyModule) -> PyResult<()> {
+    m.add_class::<TradeDirection>()?;
+    m.add_class::<RoundDirection>()?;
+    m.add_class::<PyFees>()?;
+    m.add_class::<PySwapCurve>()?;
+    m.add_class::<PySwapResult>()?;
+    Ok(())
+}
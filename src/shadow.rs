@@ -0,0 +1,226 @@
+//! Shadow testing of curve and fee changes against real flow, behind the
+//! `client` feature.
+//!
+//! A pool's past swaps are read off the cluster as `ObservedSwap`s: the
+//! instruction's amount in, the reserves it traded against and what the
+//! trader got out.  `replay` runs each of them through a candidate pool, the
+//! live one with a new curve or fee schedule, using the local build's math,
+//! and reports how every output would have differed.  Swaps replay from
+//! their own observed reserves, so one changed output doesn't shift the rest.
+
+use crate::curve::calculator::TradeDirection;
+use crate::instruction::{Swap, SwapInstruction};
+use crate::pricing::Quoter;
+use crate::state::SwapState;
+use serde::Serialize;
+use solana_program::pubkey::Pubkey;
+use std::convert::TryFrom;
+use std::fmt;
+
+/// A swap as it happened on the cluster
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ObservedSwap {
+    pub signature: String,
+    pub slot: u64,
+    pub trade_direction: TradeDirection,
+    pub amount_in: u64,
+    /// Reserves before the swap
+    pub reserve_a: u64,
+    pub reserve_b: u64,
+    /// What the trader received
+    pub amount_out: u64,
+}
+
+/// An observed swap and what the candidate pool would have paid out
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReplayedSwap {
+    #[serde(flatten)]
+    pub observed: ObservedSwap,
+    /// `None` where the program would refuse the swap
+    pub replayed_out: Option<u64>,
+}
+
+impl ReplayedSwap {
+    /// Change in what the trader receives, `None` for refused swaps
+    pub fn delta(&self) -> Option<i128> {
+        self.replayed_out
+            .map(|replayed_out| i128::from(replayed_out) - i128::from(self.observed.amount_out))
+    }
+}
+
+/// Every swap replayed, in the order observed
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ShadowReport {
+    pub swaps: Vec<ReplayedSwap>,
+}
+
+impl ShadowReport {
+    /// Swaps the candidate pool would refuse
+    pub fn refused(&self) -> usize {
+        self.swaps
+            .iter()
+            .filter(|swap| swap.replayed_out.is_none())
+            .count()
+    }
+
+    /// Total change in output of the replayed swaps in one direction, and
+    /// their total observed output, both in the destination token
+    pub fn output_delta(&self, trade_direction: TradeDirection) -> (i128, u128) {
+        self.swaps
+            .iter()
+            .filter(|swap| swap.observed.trade_direction == trade_direction)
+            .filter_map(|swap| Some((swap.delta()?, u128::from(swap.observed.amount_out))))
+            .fold((0, 0), |(delta, observed), (swap_delta, swap_observed)| {
+                (delta + swap_delta, observed + swap_observed)
+            })
+    }
+}
+
+impl fmt::Display for ShadowReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "swaps replayed  {}", self.swaps.len())?;
+        writeln!(f, "refused         {}", self.refused())?;
+        for (trade_direction, label) in [
+            (TradeDirection::AtoB, "A to B output  "),
+            (TradeDirection::BtoA, "B to A output  "),
+        ] {
+            let (delta, observed) = self.output_delta(trade_direction);
+            let bps = if observed == 0 {
+                0.0
+            } else {
+                delta as f64 * 10_000.0 / observed as f64
+            };
+            write!(f, "\n{} {:+} ({:+.2} bps)", label, delta, bps)?;
+        }
+        Ok(())
+    }
+}
+
+/// Direction and amount in of a `Swap` instruction on the given pool, read
+/// from its data and accounts, `None` for any other instruction
+pub fn decode_swap(
+    swap: &Pubkey,
+    pool: &dyn SwapState,
+    data: &[u8],
+    accounts: &[Pubkey],
+) -> Option<(TradeDirection, u64)> {
+    let amount_in = match SwapInstruction::unpack(data).ok()? {
+        SwapInstruction::Swap(Swap { amount_in, .. }) => amount_in,
+        _ => return None,
+    };
+    if accounts.first() != Some(swap) {
+        return None;
+    }
+    // the pool vault the trader pays into
+    let swap_source = accounts.get(4)?;
+    if swap_source == pool.token_a_account() {
+        Some((TradeDirection::AtoB, amount_in))
+    } else if swap_source == pool.token_b_account() {
+        Some((TradeDirection::BtoA, amount_in))
+    } else {
+        None
+    }
+}
+
+/// Replays the observed swaps against the candidate pool.  Fee exemptions
+/// aren't replayed, every swap pays the candidate's fees.
+pub fn replay(candidate: &dyn SwapState, swaps: Vec<ObservedSwap>) -> ShadowReport {
+    let swaps = swaps
+        .into_iter()
+        .map(|observed| {
+            let replayed_out = Quoter::new(
+                candidate,
+                observed.reserve_a,
+                observed.reserve_b,
+                observed.slot,
+            )
+            .swap(observed.amount_in, observed.trade_direction)
+            .and_then(|result| u64::try_from(result.destination_amount_swapped).ok());
+            ReplayedSwap {
+                observed,
+                replayed_out,
+            }
+        })
+        .collect();
+    ShadowReport { swaps }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::curve::{base::SwapCurve, fees::Fees};
+    use crate::instruction;
+    use crate::state::SwapV2;
+
+    #[test]
+    fn fee_changes_show_in_the_replayed_output() {
+        let (program_id, swap) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let live = SwapV2 {
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            fees: Fees::standard_30bps(),
+            swap_curve: SwapCurve::default(),
+            ..SwapV2::default()
+        };
+        let user = Pubkey::new_unique();
+        let ix = instruction::swap(
+            &program_id,
+            &spl_token::id(),
+            &swap,
+            &Pubkey::new_unique(),
+            &user,
+            &Pubkey::new_unique(),
+            &live.token_b,
+            &live.token_a,
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            &Pubkey::new_unique(),
+            None,
+            None,
+            instruction::Swap {
+                amount_in: 1_000_000,
+                minimum_amount_out: 0,
+                min_price_numerator: 0,
+                min_price_denominator: 0,
+            },
+        )
+        .unwrap();
+        let accounts: Vec<Pubkey> = ix.accounts.iter().map(|meta| meta.pubkey).collect();
+        let (trade_direction, amount_in) = decode_swap(&swap, &live, &ix.data, &accounts).unwrap();
+        assert_eq!(trade_direction, TradeDirection::BtoA);
+        assert_eq!(
+            decode_swap(&Pubkey::new_unique(), &live, &ix.data, &accounts),
+            None
+        );
+
+        // what the live pool paid out, replayed against itself, doesn't change
+        let reserve = 1_000_000_000_000;
+        let amount_out = Quoter::new(&live, reserve, reserve, 0)
+            .swap(amount_in, trade_direction)
+            .unwrap()
+            .destination_amount_swapped as u64;
+        let observed = vec![ObservedSwap {
+            signature: "1".to_string(),
+            slot: 0,
+            trade_direction,
+            amount_in,
+            reserve_a: reserve,
+            reserve_b: reserve,
+            amount_out,
+        }];
+        let report = replay(&live, observed.clone());
+        assert_eq!(report.swaps[0].delta(), Some(0));
+
+        // tripling the LP fee pays traders less
+        let mut fees = Fees::standard_30bps();
+        fees.trade_fee_numerator *= 3;
+        let candidate = SwapV2 { fees, ..live };
+        let report = replay(&candidate, observed);
+        assert!(report.swaps[0].delta().unwrap() < 0);
+        assert_eq!(report.refused(), 0);
+        let (delta, observed_out) = report.output_delta(TradeDirection::BtoA);
+        assert_eq!(observed_out, u128::from(amount_out));
+        assert_eq!(Some(delta), report.swaps[0].delta());
+        assert_eq!(report.output_delta(TradeDirection::AtoB), (0, 0));
+    }
+}
@@ -0,0 +1,202 @@
+//! Pool snapshots for reproducing a cluster's pools locally, behind the
+//! `client` feature.
+//!
+//! A snapshot holds every account a pool's instructions read, byte for byte,
+//! each in the JSON shape `solana account --output json` writes and
+//! `solana-test-validator --account <pubkey> <file>` loads.  Loaded into a
+//! local validator running the program under its cluster address, the pool
+//! behaves exactly as it did when the snapshot was taken; decoded with
+//! `AccountFixture::data`, the same accounts seed a regression test.
+
+use crate::curve::calculator::TradeDirection;
+use crate::state::{OracleConfig, PoolMetadata, ProgramConfig, SwapState, SwapVersion, Vesting};
+use serde::{Deserialize, Serialize};
+use solana_program::{program_error::ProgramError, pubkey::Pubkey};
+use std::str::FromStr;
+
+/// Accounts every pool has: its state, vaults, pool mint, fee account and
+/// token mints, its stake pool if it's linked to one, its owner fee accounts
+/// if split by direction, and its fee conversion pool and account if it
+/// converts owner fees.  The fee conversion pool's own accounts are its
+/// `pool_accounts`.
+pub fn pool_accounts(swap: &Pubkey, pool: &dyn SwapState) -> Vec<Pubkey> {
+    let mut accounts = vec![
+        *swap,
+        *pool.token_a_account(),
+        *pool.token_b_account(),
+        *pool.pool_mint(),
+        *pool.pool_fee_account(),
+        *pool.token_a_mint(),
+        *pool.token_b_mint(),
+    ];
+    accounts.extend(pool.stake_pool());
+    for trade_direction in [TradeDirection::AtoB, TradeDirection::BtoA] {
+        let owner_fee_account = pool.owner_fee_account(trade_direction);
+        if !accounts.contains(owner_fee_account) {
+            accounts.push(*owner_fee_account);
+        }
+        if let Some((fee_conversion_pool, fee_conversion_account)) =
+            pool.fee_conversion(trade_direction)
+        {
+            accounts.extend([fee_conversion_pool, fee_conversion_account]);
+        }
+    }
+    accounts
+}
+
+/// Accounts the program derives for a pool, or for all of them, which only
+/// exist once created
+pub fn derived_accounts(program_id: &Pubkey, swap: &Pubkey) -> Vec<Pubkey> {
+    vec![
+        Vesting::find_address(swap, program_id).0,
+        OracleConfig::find_address(swap, program_id).0,
+        PoolMetadata::find_address(swap, program_id).0,
+        ProgramConfig::find_address(program_id).0,
+    ]
+}
+
+/// An account as `solana account --output json` writes it
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AccountFixture {
+    pub pubkey: String,
+    pub account: FixtureAccount,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FixtureAccount {
+    pub lamports: u64,
+    /// The data and its encoding, always base64
+    pub data: (String, String),
+    pub owner: String,
+    pub executable: bool,
+    pub rent_epoch: u64,
+    pub space: u64,
+}
+
+impl AccountFixture {
+    pub fn new(
+        pubkey: &Pubkey,
+        lamports: u64,
+        data: &[u8],
+        owner: &Pubkey,
+        executable: bool,
+        rent_epoch: u64,
+    ) -> Self {
+        Self {
+            pubkey: pubkey.to_string(),
+            account: FixtureAccount {
+                lamports,
+                data: (base64::encode(data), "base64".to_string()),
+                owner: owner.to_string(),
+                executable,
+                rent_epoch,
+                space: data.len() as u64,
+            },
+        }
+    }
+
+    pub fn pubkey(&self) -> Result<Pubkey, ProgramError> {
+        Pubkey::from_str(&self.pubkey).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    pub fn owner(&self) -> Result<Pubkey, ProgramError> {
+        Pubkey::from_str(&self.account.owner).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// The account's data, exactly as it was on the cluster
+    pub fn data(&self) -> Result<Vec<u8>, ProgramError> {
+        let (data, encoding) = &self.account.data;
+        if encoding != "base64" {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        base64::decode(data).map_err(|_| ProgramError::InvalidAccountData)
+    }
+}
+
+/// Every account of one pool, as of one slot
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PoolSnapshot {
+    /// The program has to run under this address locally too, the pool's
+    /// authority is derived from it
+    pub program_id: String,
+    pub swap: String,
+    pub slot: u64,
+    pub accounts: Vec<AccountFixture>,
+}
+
+impl PoolSnapshot {
+    /// The pool's state, read from its account in the snapshot
+    pub fn pool(&self) -> Result<SwapVersion, ProgramError> {
+        let swap = self
+            .accounts
+            .iter()
+            .find(|account| account.pubkey == self.swap)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        SwapVersion::unpack_versioned(&swap.data()?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        curve::{base::SwapCurve, fees::Fees},
+        state::SwapV2,
+    };
+
+    #[test]
+    fn snapshots_keep_accounts_byte_identical() {
+        let (program_id, swap) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pool = SwapVersion::SwapV2(Box::new(SwapV2 {
+            is_initialized: true,
+            token_program_id: spl_token::id(),
+            token_a: Pubkey::new_unique(),
+            token_b: Pubkey::new_unique(),
+            pool_mint: Pubkey::new_unique(),
+            token_a_mint: Pubkey::new_unique(),
+            token_b_mint: Pubkey::new_unique(),
+            pool_fee_account: Pubkey::new_unique(),
+            fees: Fees::standard_30bps(),
+            swap_curve: SwapCurve::default(),
+            ..SwapV2::default()
+        }));
+        let mut data = vec![0; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(pool, &mut data).unwrap();
+
+        let state = SwapVersion::unpack_versioned(&data).unwrap();
+        let accounts = pool_accounts(&swap, &state);
+        assert_eq!(accounts.len(), 7);
+        assert_eq!(accounts[0], swap);
+        assert!(derived_accounts(&program_id, &swap)
+            .iter()
+            .all(|derived| !accounts.contains(derived)));
+
+        let snapshot = PoolSnapshot {
+            program_id: program_id.to_string(),
+            swap: swap.to_string(),
+            slot: 42,
+            accounts: vec![AccountFixture::new(
+                &swap,
+                1_000_000,
+                &data,
+                &program_id,
+                false,
+                u64::MAX,
+            )],
+        };
+        let json = serde_json::to_value(&snapshot).unwrap();
+        // the shape solana-test-validator --account loads
+        let account = &json["accounts"][0];
+        assert_eq!(account["pubkey"], swap.to_string());
+        assert_eq!(account["account"]["data"][1], "base64");
+        assert_eq!(account["account"]["rentEpoch"], u64::MAX);
+        assert_eq!(account["account"]["space"], data.len());
+
+        let loaded: PoolSnapshot = serde_json::from_value(json).unwrap();
+        assert_eq!(loaded, snapshot);
+        assert_eq!(loaded.accounts[0].data().unwrap(), data);
+        assert_eq!(loaded.accounts[0].owner().unwrap(), program_id);
+        assert_eq!(loaded.pool().unwrap().pool_mint(), state.pool_mint());
+    }
+}
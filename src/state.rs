@@ -1,4 +1,15 @@
-use crate::curve::{base::SwapCurve, fees::Fees};
+use crate::constants::{
+    CHECKPOINT_INTERVAL, CREDENTIAL_SEED, KEEPER_CONFIG_SEED, KEEPER_SEED, LOCK_SEED,
+    LP_POSITION_SEED, MAX_ADMIN_SIGNERS, MAX_FEE_EXEMPT, MAX_NAME_LEN, MAX_SLASH_BPS, MAX_URI_LEN,
+    METADATA_SEED, NATIVE_TREASURY_SEED, ORACLE_SEED, POOL_TEMPLATE_SEED, PROGRAM_CONFIG_SEED,
+    REFERRAL_SEED, REFERRER_SEED, SNAPSHOT_SEED, TAGS_LEN, VESTING_SEED,
+};
+use crate::curve::{
+    base::SwapCurve,
+    calculator::{CurveCapabilities, TradeDirection},
+    fees::{calculate_fee, FeeTiers, Fees},
+};
+use crate::error::SwapError;
 use arrayref::{array_mut_ref, array_ref, array_refs, mut_array_refs};
 use enum_dispatch::enum_dispatch;
 use solana_program::{
@@ -6,6 +17,8 @@ use solana_program::{
     program_pack::{IsInitialized, Pack, Sealed},
     pubkey::Pubkey,
 };
+use spl_math::precise_number::PreciseNumber;
+use std::convert::{TryFrom, TryInto};
 
 // ----------------------------------------------------------------------------- swap state trait
 
@@ -37,13 +50,227 @@ pub trait SwapState {
     fn fees(&self) -> &Fees;
     /// Curve associated with swap
     fn swap_curve(&self) -> &SwapCurve;
+
+    /// Decimals of the pool token mint, recorded at initialization (V2 onwards)
+    fn pool_mint_decimals(&self) -> Option<u8>;
+    /// Most token A traded per slot, 0 when uncapped
+    fn max_slot_volume(&self) -> u64;
+    /// Pool tokens currently held in liquidity locks (V2 onwards)
+    fn locked_pool_tokens(&self) -> u64;
+    /// Pool charges no fees at all, so fee handling can be skipped entirely
+    fn zero_fee(&self) -> bool;
+    /// Key allowed to swap through `RebalanceSwap`, paying only the LP trade fee
+    fn rebalancer(&self) -> Option<Pubkey>;
+    /// Pool admin flagged the pool as bricked, allowing `EmergencyWithdraw`
+    fn emergency(&self) -> bool;
+    /// Bonus paid at the given slot on pool tokens locked until `unlock_slot`,
+    /// in basis points of the pool tokens locked.  Only locks lasting the
+    /// bonus lockup earn it, so it can't be farmed by depositing and
+    /// withdrawing straight away
+    fn deposit_bonus_bps(&self, slot: u64, unlock_slot: u64) -> u16;
+    /// Account whose operations skip the owner's fees, the pool fee account
+    /// itself or one of the pool admin's exempt accounts
+    fn is_fee_exempt(&self, account: &Pubkey) -> bool;
+    /// Token A and B accounts the withdraw fee is paid into, in underlying
+    /// tokens, `None` while it's paid as pool tokens to the pool fee account
+    fn withdraw_fee_vaults(&self) -> Option<(Pubkey, Pubkey)>;
+    /// Lower trade fees for large trades, by source amount (V2 onwards)
+    fn fee_tiers(&self) -> FeeTiers;
+    /// Swaps take all of `amount_in`, the rounding dust going to the reserves
+    fn exact_input(&self) -> bool;
+    /// New pool waives its trade fees at the given slot, to bootstrap volume
+    fn fee_holiday(&self, slot: u64) -> bool;
+    /// Stake pool whose exchange rate is the pool's constant price (V2 onwards)
+    fn stake_pool(&self) -> Option<Pubkey>;
+    /// Slot the price was last refreshed from the stake pool
+    fn rate_update_slot(&self) -> u64;
+    /// Slot of the pool's last swap, 0 before the first (V2 onwards)
+    fn last_trade_slot(&self) -> u64;
+    /// Pool token account earning the host fee of swaps that don't bring a
+    /// host fee account of their own (V2 onwards)
+    fn default_host_fee_account(&self) -> Option<Pubkey>;
+    /// Decimals of the token A and B mints, recorded at initialization (V2
+    /// onwards)
+    fn token_decimals(&self) -> Option<(u8, u8)>;
+    /// Pool token account the owner fee of a trade in the given direction is
+    /// minted to, the pool fee account unless the admin split them (V2
+    /// onwards)
+    fn owner_fee_account(&self, trade_direction: TradeDirection) -> &Pubkey;
+    /// Pool the owner fee of trades in the given direction is sold through
+    /// for the fee mint, and the fee mint account it's paid to, `None` while
+    /// it's minted as pool tokens (V2 onwards)
+    fn fee_conversion(&self, trade_direction: TradeDirection) -> Option<(Pubkey, Pubkey)>;
+    /// Most the fee conversion may get below the conversion pool's spot
+    /// price, in basis points of it (V2 onwards)
+    fn fee_conversion_max_slippage_bps(&self) -> u16;
+    /// Slots after a single-sided deposit before the pool tokens it minted
+    /// can be withdrawn single-sided, 0 for no cooldown (V2 onwards)
+    fn withdraw_cooldown_slots(&self) -> u64;
+    /// What a trader must hold to swap or deposit, `None` for a pool open to
+    /// everyone (V2 onwards)
+    fn gate(&self) -> Option<Gate>;
+    /// Single-sided withdrawals leave the owner's withdraw fee in the pool,
+    /// for the LPs who stay, rather than paying it to the owner (V2 onwards)
+    fn single_withdraw_fee_to_lps(&self) -> bool;
+    /// Swaps are refused in transactions where another trader swaps the pool
+    /// both ways (V2 onwards)
+    fn sandwich_guard(&self) -> bool;
+    /// Swaps whose owner fee can't be converted into pool tokens go through
+    /// without it rather than failing (V2 onwards)
+    fn owner_fee_fallback(&self) -> bool;
+    /// Pool whose pool token is this pool's token B (V2 onwards)
+    fn nested_pool(&self) -> Option<Pubkey>;
+    /// Only keepers bonded in the program's keeper config can crank the
+    /// pool's oracle price and stake pool rate (V2 onwards)
+    fn bonded_keepers_only(&self) -> bool;
+    /// Trades and deposits are halted by the pool admin, withdrawals still
+    /// go through (V2 onwards)
+    fn paused(&self) -> bool;
+}
+
+/// Boxed versions, as `SwapVersion` holds the large ones
+impl<T: SwapState + ?Sized> SwapState for Box<T> {
+    fn is_initialized(&self) -> bool {
+        (**self).is_initialized()
+    }
+    fn nonce(&self) -> u8 {
+        (**self).nonce()
+    }
+    fn token_program_id(&self) -> &Pubkey {
+        (**self).token_program_id()
+    }
+    fn token_a_account(&self) -> &Pubkey {
+        (**self).token_a_account()
+    }
+    fn token_b_account(&self) -> &Pubkey {
+        (**self).token_b_account()
+    }
+    fn pool_mint(&self) -> &Pubkey {
+        (**self).pool_mint()
+    }
+    fn token_a_mint(&self) -> &Pubkey {
+        (**self).token_a_mint()
+    }
+    fn token_b_mint(&self) -> &Pubkey {
+        (**self).token_b_mint()
+    }
+    fn pool_fee_account(&self) -> &Pubkey {
+        (**self).pool_fee_account()
+    }
+    fn fees(&self) -> &Fees {
+        (**self).fees()
+    }
+    fn swap_curve(&self) -> &SwapCurve {
+        (**self).swap_curve()
+    }
+    fn pool_mint_decimals(&self) -> Option<u8> {
+        (**self).pool_mint_decimals()
+    }
+    fn max_slot_volume(&self) -> u64 {
+        (**self).max_slot_volume()
+    }
+    fn locked_pool_tokens(&self) -> u64 {
+        (**self).locked_pool_tokens()
+    }
+    fn zero_fee(&self) -> bool {
+        (**self).zero_fee()
+    }
+    fn rebalancer(&self) -> Option<Pubkey> {
+        (**self).rebalancer()
+    }
+    fn emergency(&self) -> bool {
+        (**self).emergency()
+    }
+    fn deposit_bonus_bps(&self, slot: u64, unlock_slot: u64) -> u16 {
+        (**self).deposit_bonus_bps(slot, unlock_slot)
+    }
+    fn is_fee_exempt(&self, account: &Pubkey) -> bool {
+        (**self).is_fee_exempt(account)
+    }
+    fn withdraw_fee_vaults(&self) -> Option<(Pubkey, Pubkey)> {
+        (**self).withdraw_fee_vaults()
+    }
+    fn fee_tiers(&self) -> FeeTiers {
+        (**self).fee_tiers()
+    }
+    fn exact_input(&self) -> bool {
+        (**self).exact_input()
+    }
+    fn fee_holiday(&self, slot: u64) -> bool {
+        (**self).fee_holiday(slot)
+    }
+    fn stake_pool(&self) -> Option<Pubkey> {
+        (**self).stake_pool()
+    }
+    fn rate_update_slot(&self) -> u64 {
+        (**self).rate_update_slot()
+    }
+    fn last_trade_slot(&self) -> u64 {
+        (**self).last_trade_slot()
+    }
+    fn default_host_fee_account(&self) -> Option<Pubkey> {
+        (**self).default_host_fee_account()
+    }
+    fn token_decimals(&self) -> Option<(u8, u8)> {
+        (**self).token_decimals()
+    }
+    fn owner_fee_account(&self, trade_direction: TradeDirection) -> &Pubkey {
+        (**self).owner_fee_account(trade_direction)
+    }
+    fn fee_conversion(&self, trade_direction: TradeDirection) -> Option<(Pubkey, Pubkey)> {
+        (**self).fee_conversion(trade_direction)
+    }
+    fn fee_conversion_max_slippage_bps(&self) -> u16 {
+        (**self).fee_conversion_max_slippage_bps()
+    }
+    fn withdraw_cooldown_slots(&self) -> u64 {
+        (**self).withdraw_cooldown_slots()
+    }
+    fn gate(&self) -> Option<Gate> {
+        (**self).gate()
+    }
+    fn single_withdraw_fee_to_lps(&self) -> bool {
+        (**self).single_withdraw_fee_to_lps()
+    }
+    fn sandwich_guard(&self) -> bool {
+        (**self).sandwich_guard()
+    }
+    fn owner_fee_fallback(&self) -> bool {
+        (**self).owner_fee_fallback()
+    }
+    fn nested_pool(&self) -> Option<Pubkey> {
+        (**self).nested_pool()
+    }
+    fn bonded_keepers_only(&self) -> bool {
+        (**self).bonded_keepers_only()
+    }
+    fn paused(&self) -> bool {
+        (**self).paused()
+    }
+}
+
+/// Gate of a permissioned pool, which only serves traders passing it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gate {
+    /// The trader holds a token of this mint, in an account that isn't frozen
+    Token(Pubkey),
+    /// The trader has a credential issued by this program
+    Credential(Pubkey),
+}
+
+impl Gate {
+    /// Address of the trader's credential from the given issuing program
+    pub fn find_credential_address(issuer: &Pubkey, trader: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[CREDENTIAL_SEED, trader.as_ref()], issuer)
+    }
 }
 
 // ----------------------------------------------------------------------------- swap version
 
 #[enum_dispatch(SwapState)]
 pub enum SwapVersion {
-    SwapV1(SwapV1),
+    SwapV1(Box<SwapV1>),
+    SwapV2(Box<SwapV2>),
 }
 
 /// SwapVersion does not implement program_pack::Pack because there are size
@@ -51,14 +278,18 @@ pub enum SwapVersion {
 /// special implementations are provided here
 impl SwapVersion {
     /// Size of the latest version of the SwapState
-    pub const LATEST_LEN: usize = 1 + SwapV1::LEN; // add one for the version enum
+    pub const LATEST_LEN: usize = 1 + SwapV2::LEN; // add one for the version enum
 
     /// Pack a swap into a byte array, based on its version
     pub fn pack(src: Self, dst: &mut [u8]) -> Result<(), ProgramError> {
         match src {
             Self::SwapV1(swap_info) => {
                 dst[0] = 1;
-                SwapV1::pack(swap_info, &mut dst[1..])
+                SwapV1::pack(*swap_info, &mut dst[1..])
+            }
+            Self::SwapV2(swap_info) => {
+                dst[0] = 2;
+                SwapV2::pack(*swap_info, &mut dst[1..])
             }
         }
     }
@@ -71,10 +302,62 @@ impl SwapVersion {
             .ok_or(ProgramError::InvalidAccountData)?;
         match version {
             1 => Ok(Box::new(SwapV1::unpack(rest)?)),
+            2 => Ok(Box::new(SwapV2::unpack(rest)?)),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Unpack the swap account into its concrete version, for instructions
+    /// that need to modify the state before packing it back
+    pub fn unpack_versioned(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            1 => Ok(Self::SwapV1(Box::new(SwapV1::unpack(rest)?))),
+            2 => Ok(Self::SwapV2(Box::new(SwapV2::unpack(rest)?))),
+            _ => Err(ProgramError::UninitializedAccount),
+        }
+    }
+
+    /// Unpack a swap account that may predate the latest layout, for
+    /// `ResizeSwapAccount`: V1 as it is, and V2 written before its last fields
+    /// were added, those reading as zero, their default
+    pub fn unpack_outdated(input: &[u8]) -> Result<Self, ProgramError> {
+        let (&version, rest) = input
+            .split_first()
+            .ok_or(ProgramError::InvalidAccountData)?;
+        match version {
+            1 => Ok(Self::SwapV1(Box::new(SwapV1::unpack(rest)?))),
+            2 if rest.len() <= SwapV2::LEN => {
+                let mut padded = rest.to_vec();
+                padded.resize(SwapV2::LEN, 0);
+                Ok(Self::SwapV2(Box::new(SwapV2::unpack(&padded)?)))
+            }
+            2 => Err(ProgramError::InvalidAccountData),
             _ => Err(ProgramError::UninitializedAccount),
         }
     }
 
+    /// Address of the swap authority, derived from the swap account and its
+    /// nonce
+    pub fn authority_id(
+        program_id: &Pubkey,
+        swap: &Pubkey,
+        nonce: u8,
+    ) -> Result<Pubkey, SwapError> {
+        Pubkey::create_program_address(&[&swap.to_bytes()[..32], &[nonce]], program_id)
+            .or(Err(SwapError::InvalidProgramAddress))
+    }
+
+    /// Replace the swap curve, whatever the version
+    pub fn set_swap_curve(&mut self, swap_curve: SwapCurve) {
+        match self {
+            Self::SwapV1(swap_info) => swap_info.swap_curve = swap_curve,
+            Self::SwapV2(swap_info) => swap_info.swap_curve = swap_curve,
+        }
+    }
+
     /// Special check to be done before any instruction processing, works for
     /// all versions
     pub fn is_initialized(input: &[u8]) -> bool {
@@ -170,6 +453,118 @@ impl SwapState for SwapV1 {
     fn swap_curve(&self) -> &SwapCurve {
         &self.swap_curve
     }
+
+    fn pool_mint_decimals(&self) -> Option<u8> {
+        None
+    }
+
+    fn max_slot_volume(&self) -> u64 {
+        0
+    }
+
+    fn locked_pool_tokens(&self) -> u64 {
+        0
+    }
+
+    fn zero_fee(&self) -> bool {
+        self.fees.is_zero()
+    }
+
+    fn rebalancer(&self) -> Option<Pubkey> {
+        None
+    }
+
+    fn emergency(&self) -> bool {
+        false
+    }
+
+    fn deposit_bonus_bps(&self, _slot: u64, _unlock_slot: u64) -> u16 {
+        0
+    }
+
+    fn is_fee_exempt(&self, account: &Pubkey) -> bool {
+        *account == self.pool_fee_account
+    }
+
+    fn withdraw_fee_vaults(&self) -> Option<(Pubkey, Pubkey)> {
+        None
+    }
+
+    fn fee_tiers(&self) -> FeeTiers {
+        FeeTiers::default()
+    }
+
+    fn exact_input(&self) -> bool {
+        false
+    }
+
+    fn fee_holiday(&self, _slot: u64) -> bool {
+        false
+    }
+
+    fn stake_pool(&self) -> Option<Pubkey> {
+        None
+    }
+
+    fn rate_update_slot(&self) -> u64 {
+        0
+    }
+
+    fn last_trade_slot(&self) -> u64 {
+        0
+    }
+
+    fn default_host_fee_account(&self) -> Option<Pubkey> {
+        None
+    }
+
+    fn token_decimals(&self) -> Option<(u8, u8)> {
+        None
+    }
+
+    fn owner_fee_account(&self, _trade_direction: TradeDirection) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn fee_conversion(&self, _trade_direction: TradeDirection) -> Option<(Pubkey, Pubkey)> {
+        None
+    }
+
+    fn fee_conversion_max_slippage_bps(&self) -> u16 {
+        0
+    }
+
+    fn withdraw_cooldown_slots(&self) -> u64 {
+        0
+    }
+
+    fn gate(&self) -> Option<Gate> {
+        None
+    }
+
+    fn single_withdraw_fee_to_lps(&self) -> bool {
+        false
+    }
+
+    fn sandwich_guard(&self) -> bool {
+        false
+    }
+
+    fn owner_fee_fallback(&self) -> bool {
+        false
+    }
+
+    fn nested_pool(&self) -> Option<Pubkey> {
+        None
+    }
+
+    fn bonded_keepers_only(&self) -> bool {
+        false
+    }
+
+    fn paused(&self) -> bool {
+        false
+    }
 }
 
 // ----------------------------------------------------------------------------- program pack
@@ -248,3 +643,3100 @@ impl Pack for SwapV1 {
         })
     }
 }
+
+// ----------------------------------------------------------------------------- swap v2
+
+/// Same as SwapV1, followed by the fields added since
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapV2 {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Nonce used in program address.
+    pub nonce: u8,
+
+    /// Program ID of the tokens being exchanged.
+    pub token_program_id: Pubkey,
+
+    /// Token A
+    pub token_a: Pubkey,
+    /// Token B
+    pub token_b: Pubkey,
+
+    /// Pool token mint
+    pub pool_mint: Pubkey,
+
+    /// Mint information for token A
+    pub token_a_mint: Pubkey,
+    /// Mint information for token B
+    pub token_b_mint: Pubkey,
+
+    /// Pool token account to receive trading and / or withdrawal fees
+    pub pool_fee_account: Pubkey,
+
+    /// All fee information
+    pub fees: Fees,
+
+    /// Swap curve parameters
+    pub swap_curve: SwapCurve,
+
+    /// Decimals of the pool token mint
+    pub pool_mint_decimals: u8,
+
+    /// Most token A traded per slot, in both directions combined, 0 for no cap
+    pub max_slot_volume: u64,
+    /// Slot of the last capped swap
+    pub last_slot: u64,
+    /// Token A traded so far in `last_slot`
+    pub slot_volume: u64,
+
+    /// Pool tokens currently held in liquidity locks
+    pub locked_pool_tokens: u64,
+
+    /// Pool was created without any fees
+    pub zero_fee: bool,
+
+    /// Pool admin's rebalancer, `Pubkey::default()` when there is none
+    pub rebalancer: Pubkey,
+
+    /// Curve math can't be trusted, LPs may only exit pro-rata through
+    /// `EmergencyWithdraw`
+    pub emergency: bool,
+
+    /// First slot of the deposit bonus window
+    pub bonus_start_slot: u64,
+    /// Slot the deposit bonus window ends, exclusive
+    pub bonus_end_slot: u64,
+    /// Deposit bonus, in basis points of the pool tokens locked, paid out of
+    /// the pool fee account into the lock
+    pub bonus_bps: u16,
+
+    /// Treasury accounts exempt from the owner's fees, besides the pool fee
+    /// account, `Pubkey::default()` for an unused slot
+    pub fee_exempt: [Pubkey; MAX_FEE_EXEMPT],
+
+    /// Withdraw fees are converted to token A and B, instead of accruing as
+    /// pool tokens
+    pub withdraw_fee_in_underlying: bool,
+    /// Token A account receiving converted withdraw fees
+    pub withdraw_fee_vault_a: Pubkey,
+    /// Token B account receiving converted withdraw fees
+    pub withdraw_fee_vault_b: Pubkey,
+
+    /// Size tiers of the trade fee, above the base schedule in `fees`
+    pub fee_tiers: FeeTiers,
+
+    /// Swaps consume exactly `amount_in`, instead of leaving what the curve's
+    /// rounding didn't use with the user
+    pub exact_input: bool,
+
+    /// Slot the fee holiday set at initialization ends, exclusive.  Until
+    /// then trades pay no fees, LP or owner
+    pub fee_holiday_end_slot: u64,
+
+    /// SPL stake pool whose exchange rate is the constant price, token B
+    /// being its LST and token A wrapped SOL, `Pubkey::default()` for none
+    pub stake_pool: Pubkey,
+    /// Slot the price was last refreshed from the stake pool, trades need it
+    /// refreshed in their own slot
+    pub rate_update_slot: u64,
+
+    /// Slot of the last swap, 0 before the first
+    pub last_trade_slot: u64,
+
+    /// Pool token account earning the host fee of swaps without a host fee
+    /// account, `Pubkey::default()` to leave that share with the owner
+    pub default_host_fee_account: Pubkey,
+    /// Host fees the default host earned, in pool tokens, minted to it by
+    /// `ClaimHostFees`.  Until then they aren't part of the supply
+    pub unclaimed_host_fees: u64,
+
+    /// Decimals of the token A mint, for clients formatting amounts
+    pub token_a_decimals: u8,
+    /// Decimals of the token B mint
+    pub token_b_decimals: u8,
+
+    /// Swaps, deposits and withdrawals the pool has processed, a
+    /// `CheckpointEvent` being emitted every `CHECKPOINT_INTERVAL` of them
+    pub interactions: u64,
+    /// Trade fees, LP and owner, charged in token A
+    pub trade_fees_a: u64,
+    /// Trade fees charged in token B
+    pub trade_fees_b: u64,
+    /// Withdraw fees charged, in pool tokens
+    pub withdraw_fees: u64,
+    /// Hash of the last `CheckpointEvent`, all zeroes before the first
+    pub last_checkpoint_hash: [u8; 32],
+
+    /// Pool token account the owner fee of A to B trades, paid in token A,
+    /// is minted to, `Pubkey::default()` for the pool fee account
+    pub owner_fee_account_a: Pubkey,
+    /// Pool token account the owner fee of B to A trades is minted to
+    pub owner_fee_account_b: Pubkey,
+
+    /// Pool of this program trading one of the pool's tokens for a fee mint,
+    /// owner fees paid in that token being sold through it, and
+    /// `Pubkey::default()` to mint them all as pool tokens
+    pub fee_conversion_pool: Pubkey,
+    /// Fee mint token account the converted owner fees are paid to
+    pub fee_conversion_account: Pubkey,
+    /// The fee conversion pool trades token B, converting the owner fee of B
+    /// to A trades, rather than token A
+    pub fee_conversion_from_token_b: bool,
+
+    /// Slots after a single-sided deposit into a pool token account before
+    /// it can be withdrawn from single-sided, 0 for no cooldown
+    pub withdraw_cooldown_slots: u64,
+
+    /// Mint of the token traders must hold to swap or deposit, or the program
+    /// issuing the credentials they must have, `Pubkey::default()` for a pool
+    /// open to everyone
+    pub gate: Pubkey,
+    /// `gate` is a credential issuing program rather than a mint
+    pub gate_credential: bool,
+
+    /// Single-sided withdrawals burn the owner's withdraw fee along with the
+    /// pool tokens they redeem, so the tokens the fee stands for stay in the
+    /// pool and accrue pro rata to the remaining LPs, who took on the
+    /// imbalance
+    pub single_withdraw_fee_to_lps: bool,
+
+    /// Swaps check the transaction's other instructions, through the
+    /// instructions sysvar, and are refused when another trader swaps the
+    /// pool both ways around them
+    pub sandwich_guard: bool,
+
+    /// Swaps whose owner fee the curve can't convert into pool tokens skip
+    /// minting it, leaving the fee with the LPs, instead of failing, so an
+    /// edge case in the fee math can't halt trading
+    pub owner_fee_fallback: bool,
+
+    /// Pool of this program whose pool token is token B, checked when linked
+    /// to be minted by that pool alone, `Pubkey::default()` for none
+    pub nested_pool: Pubkey,
+
+    /// Cranks of the pool's oracle price and stake pool rate must be signed
+    /// by a keeper with a bond in the program's keeper config, so a bad crank
+    /// costs its keeper the bond
+    pub bonded_keepers_only: bool,
+
+    /// Trades and deposits are halted during an incident, while LPs can still
+    /// withdraw
+    pub paused: bool,
+
+    /// Slots pool tokens must stay locked for to earn the deposit bonus
+    pub bonus_lockup_slots: u64,
+
+    /// Most the fee conversion may get below the conversion pool's spot
+    /// price, in basis points of it, the owner fee being minted as pool
+    /// tokens instead of sold any cheaper
+    pub fee_conversion_max_slippage_bps: u16,
+}
+
+impl SwapV2 {
+    /// Migrates a V1 pool, keeping its accounts, fees and curve, with every
+    /// V2 feature off.  V1 didn't record the mints' decimals, so they're
+    /// passed in.
+    pub fn from_v1(
+        swap_v1: SwapV1,
+        pool_mint_decimals: u8,
+        token_a_decimals: u8,
+        token_b_decimals: u8,
+    ) -> Self {
+        Self {
+            is_initialized: swap_v1.is_initialized,
+            nonce: swap_v1.nonce,
+            token_program_id: swap_v1.token_program_id,
+            token_a: swap_v1.token_a,
+            token_b: swap_v1.token_b,
+            pool_mint: swap_v1.pool_mint,
+            token_a_mint: swap_v1.token_a_mint,
+            token_b_mint: swap_v1.token_b_mint,
+            pool_fee_account: swap_v1.pool_fee_account,
+            fees: swap_v1.fees,
+            swap_curve: swap_v1.swap_curve,
+            pool_mint_decimals,
+            token_a_decimals,
+            token_b_decimals,
+            ..Self::default()
+        }
+    }
+
+    /// Adds a swap's trade fees to the fee counters, saturating rather than
+    /// failing trades over a counter
+    pub fn record_trade_fees(&mut self, fee_a: u64, fee_b: u64) {
+        self.trade_fees_a = self.trade_fees_a.saturating_add(fee_a);
+        self.trade_fees_b = self.trade_fees_b.saturating_add(fee_b);
+    }
+
+    /// Counts a swap, deposit or withdrawal, returning whether a checkpoint
+    /// is due after it
+    pub fn record_interaction(&mut self, withdraw_fee: u64) -> bool {
+        self.withdraw_fees = self.withdraw_fees.saturating_add(withdraw_fee);
+        self.interactions += 1;
+        self.interactions.is_multiple_of(CHECKPOINT_INTERVAL)
+    }
+
+    /// Records a swap in `slot`, adding its token A volume to the slot's
+    /// total and failing once that would exceed the pool's cap
+    pub fn record_slot_volume(&mut self, slot: u64, volume: u64) -> Result<(), SwapError> {
+        self.last_trade_slot = slot;
+        if self.max_slot_volume == 0 {
+            return Ok(());
+        }
+        if slot != self.last_slot {
+            self.last_slot = slot;
+            self.slot_volume = 0;
+        }
+        let slot_volume = self
+            .slot_volume
+            .checked_add(volume)
+            .ok_or(SwapError::RateLimited)?;
+        if slot_volume > self.max_slot_volume {
+            return Err(SwapError::RateLimited);
+        }
+        self.slot_volume = slot_volume;
+        Ok(())
+    }
+}
+
+impl SwapState for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+
+    fn nonce(&self) -> u8 {
+        self.nonce
+    }
+
+    fn token_program_id(&self) -> &Pubkey {
+        &self.token_program_id
+    }
+
+    fn token_a_account(&self) -> &Pubkey {
+        &self.token_a
+    }
+
+    fn token_b_account(&self) -> &Pubkey {
+        &self.token_b
+    }
+
+    fn pool_mint(&self) -> &Pubkey {
+        &self.pool_mint
+    }
+
+    fn token_a_mint(&self) -> &Pubkey {
+        &self.token_a_mint
+    }
+
+    fn token_b_mint(&self) -> &Pubkey {
+        &self.token_b_mint
+    }
+
+    fn pool_fee_account(&self) -> &Pubkey {
+        &self.pool_fee_account
+    }
+
+    fn fees(&self) -> &Fees {
+        &self.fees
+    }
+
+    fn swap_curve(&self) -> &SwapCurve {
+        &self.swap_curve
+    }
+
+    fn pool_mint_decimals(&self) -> Option<u8> {
+        Some(self.pool_mint_decimals)
+    }
+
+    fn max_slot_volume(&self) -> u64 {
+        self.max_slot_volume
+    }
+
+    fn locked_pool_tokens(&self) -> u64 {
+        self.locked_pool_tokens
+    }
+
+    fn zero_fee(&self) -> bool {
+        self.zero_fee
+    }
+
+    fn rebalancer(&self) -> Option<Pubkey> {
+        if self.rebalancer == Pubkey::default() {
+            None
+        } else {
+            Some(self.rebalancer)
+        }
+    }
+
+    fn emergency(&self) -> bool {
+        self.emergency
+    }
+
+    fn deposit_bonus_bps(&self, slot: u64, unlock_slot: u64) -> u16 {
+        if (self.bonus_start_slot..self.bonus_end_slot).contains(&slot)
+            && unlock_slot >= slot.saturating_add(self.bonus_lockup_slots)
+        {
+            self.bonus_bps
+        } else {
+            0
+        }
+    }
+
+    fn is_fee_exempt(&self, account: &Pubkey) -> bool {
+        *account == self.pool_fee_account
+            || (*account != Pubkey::default() && self.fee_exempt.contains(account))
+    }
+
+    fn withdraw_fee_vaults(&self) -> Option<(Pubkey, Pubkey)> {
+        if self.withdraw_fee_in_underlying {
+            Some((self.withdraw_fee_vault_a, self.withdraw_fee_vault_b))
+        } else {
+            None
+        }
+    }
+
+    fn fee_tiers(&self) -> FeeTiers {
+        self.fee_tiers
+    }
+
+    fn exact_input(&self) -> bool {
+        self.exact_input
+    }
+
+    fn fee_holiday(&self, slot: u64) -> bool {
+        slot < self.fee_holiday_end_slot
+    }
+
+    fn stake_pool(&self) -> Option<Pubkey> {
+        if self.stake_pool == Pubkey::default() {
+            None
+        } else {
+            Some(self.stake_pool)
+        }
+    }
+
+    fn rate_update_slot(&self) -> u64 {
+        self.rate_update_slot
+    }
+
+    fn last_trade_slot(&self) -> u64 {
+        self.last_trade_slot
+    }
+
+    fn default_host_fee_account(&self) -> Option<Pubkey> {
+        if self.default_host_fee_account == Pubkey::default() {
+            None
+        } else {
+            Some(self.default_host_fee_account)
+        }
+    }
+
+    fn token_decimals(&self) -> Option<(u8, u8)> {
+        Some((self.token_a_decimals, self.token_b_decimals))
+    }
+
+    fn owner_fee_account(&self, trade_direction: TradeDirection) -> &Pubkey {
+        let account = match trade_direction {
+            TradeDirection::AtoB => &self.owner_fee_account_a,
+            TradeDirection::BtoA => &self.owner_fee_account_b,
+        };
+        if *account == Pubkey::default() {
+            &self.pool_fee_account
+        } else {
+            account
+        }
+    }
+
+    fn fee_conversion(&self, trade_direction: TradeDirection) -> Option<(Pubkey, Pubkey)> {
+        let from_token_b = trade_direction == TradeDirection::BtoA;
+        if self.fee_conversion_pool == Pubkey::default()
+            || self.fee_conversion_from_token_b != from_token_b
+        {
+            None
+        } else {
+            Some((self.fee_conversion_pool, self.fee_conversion_account))
+        }
+    }
+
+    fn fee_conversion_max_slippage_bps(&self) -> u16 {
+        self.fee_conversion_max_slippage_bps
+    }
+
+    fn withdraw_cooldown_slots(&self) -> u64 {
+        self.withdraw_cooldown_slots
+    }
+
+    fn gate(&self) -> Option<Gate> {
+        if self.gate == Pubkey::default() {
+            None
+        } else if self.gate_credential {
+            Some(Gate::Credential(self.gate))
+        } else {
+            Some(Gate::Token(self.gate))
+        }
+    }
+
+    fn single_withdraw_fee_to_lps(&self) -> bool {
+        self.single_withdraw_fee_to_lps
+    }
+
+    fn sandwich_guard(&self) -> bool {
+        self.sandwich_guard
+    }
+
+    fn owner_fee_fallback(&self) -> bool {
+        self.owner_fee_fallback
+    }
+
+    fn nested_pool(&self) -> Option<Pubkey> {
+        if self.nested_pool == Pubkey::default() {
+            None
+        } else {
+            Some(self.nested_pool)
+        }
+    }
+
+    fn bonded_keepers_only(&self) -> bool {
+        self.bonded_keepers_only
+    }
+
+    fn paused(&self) -> bool {
+        self.paused
+    }
+}
+
+fn unpack_fee_exempt(input: &[u8; 32 * MAX_FEE_EXEMPT]) -> [Pubkey; MAX_FEE_EXEMPT] {
+    let mut accounts = [Pubkey::default(); MAX_FEE_EXEMPT];
+    for (account, input) in accounts.iter_mut().zip(input.chunks_exact(32)) {
+        *account = Pubkey::new_from_array(input.try_into().unwrap());
+    }
+    accounts
+}
+
+impl Sealed for SwapV2 {}
+impl IsInitialized for SwapV2 {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for SwapV2 {
+    const LEN: usize = 965;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 965];
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            pool_mint_decimals,
+            max_slot_volume,
+            last_slot,
+            slot_volume,
+            locked_pool_tokens,
+            zero_fee,
+            rebalancer,
+            emergency,
+            bonus_start_slot,
+            bonus_end_slot,
+            bonus_bps,
+            fee_exempt,
+            withdraw_fee_in_underlying,
+            withdraw_fee_vault_a,
+            withdraw_fee_vault_b,
+            fee_tiers,
+            exact_input,
+            fee_holiday_end_slot,
+            stake_pool,
+            rate_update_slot,
+            last_trade_slot,
+            default_host_fee_account,
+            unclaimed_host_fees,
+            token_a_decimals,
+            token_b_decimals,
+            interactions,
+            trade_fees_a,
+            trade_fees_b,
+            withdraw_fees,
+            last_checkpoint_hash,
+            owner_fee_account_a,
+            owner_fee_account_b,
+            fee_conversion_pool,
+            fee_conversion_account,
+            fee_conversion_from_token_b,
+            withdraw_cooldown_slots,
+            gate,
+            gate_credential,
+            single_withdraw_fee_to_lps,
+            sandwich_guard,
+            owner_fee_fallback,
+            nested_pool,
+            bonded_keepers_only,
+            paused,
+            bonus_lockup_slots,
+            fee_conversion_max_slippage_bps,
+        ) = mut_array_refs![
+            output, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 1, 8, 8, 8, 8, 1, 32, 1, 8, 8, 2, 64,
+            1, 32, 32, 48, 1, 8, 32, 8, 8, 32, 8, 1, 1, 8, 8, 8, 8, 32, 32, 32, 32, 32, 1, 8, 32,
+            1, 1, 1, 1, 32, 1, 1, 8, 2
+        ];
+        is_initialized[0] = self.is_initialized as u8;
+        nonce[0] = self.nonce;
+        token_program_id.copy_from_slice(self.token_program_id.as_ref());
+        token_a.copy_from_slice(self.token_a.as_ref());
+        token_b.copy_from_slice(self.token_b.as_ref());
+        pool_mint.copy_from_slice(self.pool_mint.as_ref());
+        token_a_mint.copy_from_slice(self.token_a_mint.as_ref());
+        token_b_mint.copy_from_slice(self.token_b_mint.as_ref());
+        pool_fee_account.copy_from_slice(self.pool_fee_account.as_ref());
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+        pool_mint_decimals[0] = self.pool_mint_decimals;
+        *max_slot_volume = self.max_slot_volume.to_le_bytes();
+        *last_slot = self.last_slot.to_le_bytes();
+        *slot_volume = self.slot_volume.to_le_bytes();
+        *locked_pool_tokens = self.locked_pool_tokens.to_le_bytes();
+        zero_fee[0] = self.zero_fee as u8;
+        rebalancer.copy_from_slice(self.rebalancer.as_ref());
+        emergency[0] = self.emergency as u8;
+        *bonus_start_slot = self.bonus_start_slot.to_le_bytes();
+        *bonus_end_slot = self.bonus_end_slot.to_le_bytes();
+        *bonus_bps = self.bonus_bps.to_le_bytes();
+        for (output, account) in fee_exempt.chunks_exact_mut(32).zip(self.fee_exempt.iter()) {
+            output.copy_from_slice(account.as_ref());
+        }
+        withdraw_fee_in_underlying[0] = self.withdraw_fee_in_underlying as u8;
+        withdraw_fee_vault_a.copy_from_slice(self.withdraw_fee_vault_a.as_ref());
+        withdraw_fee_vault_b.copy_from_slice(self.withdraw_fee_vault_b.as_ref());
+        self.fee_tiers.pack_into_slice(&mut fee_tiers[..]);
+        exact_input[0] = self.exact_input as u8;
+        *fee_holiday_end_slot = self.fee_holiday_end_slot.to_le_bytes();
+        stake_pool.copy_from_slice(self.stake_pool.as_ref());
+        *rate_update_slot = self.rate_update_slot.to_le_bytes();
+        *last_trade_slot = self.last_trade_slot.to_le_bytes();
+        default_host_fee_account.copy_from_slice(self.default_host_fee_account.as_ref());
+        *unclaimed_host_fees = self.unclaimed_host_fees.to_le_bytes();
+        token_a_decimals[0] = self.token_a_decimals;
+        token_b_decimals[0] = self.token_b_decimals;
+        *interactions = self.interactions.to_le_bytes();
+        *trade_fees_a = self.trade_fees_a.to_le_bytes();
+        *trade_fees_b = self.trade_fees_b.to_le_bytes();
+        *withdraw_fees = self.withdraw_fees.to_le_bytes();
+        *last_checkpoint_hash = self.last_checkpoint_hash;
+        owner_fee_account_a.copy_from_slice(self.owner_fee_account_a.as_ref());
+        owner_fee_account_b.copy_from_slice(self.owner_fee_account_b.as_ref());
+        fee_conversion_pool.copy_from_slice(self.fee_conversion_pool.as_ref());
+        fee_conversion_account.copy_from_slice(self.fee_conversion_account.as_ref());
+        fee_conversion_from_token_b[0] = self.fee_conversion_from_token_b as u8;
+        *withdraw_cooldown_slots = self.withdraw_cooldown_slots.to_le_bytes();
+        gate.copy_from_slice(self.gate.as_ref());
+        gate_credential[0] = self.gate_credential as u8;
+        single_withdraw_fee_to_lps[0] = self.single_withdraw_fee_to_lps as u8;
+        sandwich_guard[0] = self.sandwich_guard as u8;
+        owner_fee_fallback[0] = self.owner_fee_fallback as u8;
+        nested_pool.copy_from_slice(self.nested_pool.as_ref());
+        bonded_keepers_only[0] = self.bonded_keepers_only as u8;
+        paused[0] = self.paused as u8;
+        *bonus_lockup_slots = self.bonus_lockup_slots.to_le_bytes();
+        *fee_conversion_max_slippage_bps = self.fee_conversion_max_slippage_bps.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [SwapV2](struct.SwapV2.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 965];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            nonce,
+            token_program_id,
+            token_a,
+            token_b,
+            pool_mint,
+            token_a_mint,
+            token_b_mint,
+            pool_fee_account,
+            fees,
+            swap_curve,
+            pool_mint_decimals,
+            max_slot_volume,
+            last_slot,
+            slot_volume,
+            locked_pool_tokens,
+            zero_fee,
+            rebalancer,
+            emergency,
+            bonus_start_slot,
+            bonus_end_slot,
+            bonus_bps,
+            fee_exempt,
+            withdraw_fee_in_underlying,
+            withdraw_fee_vault_a,
+            withdraw_fee_vault_b,
+            fee_tiers,
+            exact_input,
+            fee_holiday_end_slot,
+            stake_pool,
+            rate_update_slot,
+            last_trade_slot,
+            default_host_fee_account,
+            unclaimed_host_fees,
+            token_a_decimals,
+            token_b_decimals,
+            interactions,
+            trade_fees_a,
+            trade_fees_b,
+            withdraw_fees,
+            last_checkpoint_hash,
+            owner_fee_account_a,
+            owner_fee_account_b,
+            fee_conversion_pool,
+            fee_conversion_account,
+            fee_conversion_from_token_b,
+            withdraw_cooldown_slots,
+            gate,
+            gate_credential,
+            single_withdraw_fee_to_lps,
+            sandwich_guard,
+            owner_fee_fallback,
+            nested_pool,
+            bonded_keepers_only,
+            paused,
+            bonus_lockup_slots,
+            fee_conversion_max_slippage_bps,
+        ) = array_refs![
+            input, 1, 1, 32, 32, 32, 32, 32, 32, 32, 64, 33, 1, 8, 8, 8, 8, 1, 32, 1, 8, 8, 2, 64,
+            1, 32, 32, 48, 1, 8, 32, 8, 8, 32, 8, 1, 1, 8, 8, 8, 8, 32, 32, 32, 32, 32, 1, 8, 32,
+            1, 1, 1, 1, 32, 1, 1, 8, 2
+        ];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nonce: nonce[0],
+            token_program_id: Pubkey::new_from_array(*token_program_id),
+            token_a: Pubkey::new_from_array(*token_a),
+            token_b: Pubkey::new_from_array(*token_b),
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            token_a_mint: Pubkey::new_from_array(*token_a_mint),
+            token_b_mint: Pubkey::new_from_array(*token_b_mint),
+            pool_fee_account: Pubkey::new_from_array(*pool_fee_account),
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+            pool_mint_decimals: pool_mint_decimals[0],
+            max_slot_volume: u64::from_le_bytes(*max_slot_volume),
+            last_slot: u64::from_le_bytes(*last_slot),
+            slot_volume: u64::from_le_bytes(*slot_volume),
+            locked_pool_tokens: u64::from_le_bytes(*locked_pool_tokens),
+            zero_fee: match zero_fee {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            rebalancer: Pubkey::new_from_array(*rebalancer),
+            emergency: match emergency {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bonus_start_slot: u64::from_le_bytes(*bonus_start_slot),
+            bonus_end_slot: u64::from_le_bytes(*bonus_end_slot),
+            bonus_bps: u16::from_le_bytes(*bonus_bps),
+            fee_exempt: unpack_fee_exempt(fee_exempt),
+            withdraw_fee_in_underlying: match withdraw_fee_in_underlying {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            withdraw_fee_vault_a: Pubkey::new_from_array(*withdraw_fee_vault_a),
+            withdraw_fee_vault_b: Pubkey::new_from_array(*withdraw_fee_vault_b),
+            fee_tiers: FeeTiers::unpack_from_slice(fee_tiers)?,
+            exact_input: match exact_input {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            fee_holiday_end_slot: u64::from_le_bytes(*fee_holiday_end_slot),
+            stake_pool: Pubkey::new_from_array(*stake_pool),
+            rate_update_slot: u64::from_le_bytes(*rate_update_slot),
+            last_trade_slot: u64::from_le_bytes(*last_trade_slot),
+            default_host_fee_account: Pubkey::new_from_array(*default_host_fee_account),
+            unclaimed_host_fees: u64::from_le_bytes(*unclaimed_host_fees),
+            token_a_decimals: token_a_decimals[0],
+            token_b_decimals: token_b_decimals[0],
+            interactions: u64::from_le_bytes(*interactions),
+            trade_fees_a: u64::from_le_bytes(*trade_fees_a),
+            trade_fees_b: u64::from_le_bytes(*trade_fees_b),
+            withdraw_fees: u64::from_le_bytes(*withdraw_fees),
+            last_checkpoint_hash: *last_checkpoint_hash,
+            owner_fee_account_a: Pubkey::new_from_array(*owner_fee_account_a),
+            owner_fee_account_b: Pubkey::new_from_array(*owner_fee_account_b),
+            fee_conversion_pool: Pubkey::new_from_array(*fee_conversion_pool),
+            fee_conversion_account: Pubkey::new_from_array(*fee_conversion_account),
+            fee_conversion_from_token_b: match fee_conversion_from_token_b {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            withdraw_cooldown_slots: u64::from_le_bytes(*withdraw_cooldown_slots),
+            gate: Pubkey::new_from_array(*gate),
+            gate_credential: match gate_credential {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            single_withdraw_fee_to_lps: match single_withdraw_fee_to_lps {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            sandwich_guard: match sandwich_guard {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            owner_fee_fallback: match owner_fee_fallback {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            nested_pool: Pubkey::new_from_array(*nested_pool),
+            bonded_keepers_only: match bonded_keepers_only {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            paused: match paused {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bonus_lockup_slots: u64::from_le_bytes(*bonus_lockup_slots),
+            fee_conversion_max_slippage_bps: u16::from_le_bytes(*fee_conversion_max_slippage_bps),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- referrer
+
+/// Registered referrer, carrying its own share of the owner fee instead of the
+/// pool-wide `host_fee_numerator` / `host_fee_denominator`
+#[derive(Debug, Default, PartialEq)]
+pub struct Referrer {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the referrer's program address
+    pub bump_seed: u8,
+    /// Wallet owning the host fee accounts this referrer applies to
+    pub owner: Pubkey,
+    /// Share of the owner fee going to the referrer
+    pub host_fee_numerator: u64,
+    pub host_fee_denominator: u64,
+}
+
+impl Referrer {
+    /// Program address of the referrer record for the given wallet
+    pub fn find_address(owner: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[REFERRER_SEED, owner.as_ref()], program_id)
+    }
+
+    /// Calculate the host fee based on the owner fee, using the referrer's share
+    pub fn host_fee(&self, owner_fee: u128) -> Option<u128> {
+        calculate_fee(
+            owner_fee,
+            u128::from(self.host_fee_numerator),
+            u128::from(self.host_fee_denominator),
+        )
+    }
+}
+
+impl Sealed for Referrer {}
+impl IsInitialized for Referrer {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Referrer {
+    const LEN: usize = 50;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 50];
+        let (is_initialized, bump_seed, owner, host_fee_numerator, host_fee_denominator) =
+            mut_array_refs![output, 1, 1, 32, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        owner.copy_from_slice(self.owner.as_ref());
+        *host_fee_numerator = self.host_fee_numerator.to_le_bytes();
+        *host_fee_denominator = self.host_fee_denominator.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [Referrer](struct.Referrer.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 50];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, owner, host_fee_numerator, host_fee_denominator) =
+            array_refs![input, 1, 1, 32, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            owner: Pubkey::new_from_array(*owner),
+            host_fee_numerator: u64::from_le_bytes(*host_fee_numerator),
+            host_fee_denominator: u64::from_le_bytes(*host_fee_denominator),
+        })
+    }
+}
+
+/// Deposits a referrer brought into a pool, counted as they land so referral
+/// campaigns can be settled from the account alone.  Created on the
+/// referrer's first referred deposit into the pool, the counters saturate
+/// rather than fail a deposit.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReferredDeposits {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the account's program address
+    pub bump_seed: u8,
+    /// Pool deposited into
+    pub swap: Pubkey,
+    /// Wallet of the registered referrer
+    pub referrer: Pubkey,
+    /// Deposits referred
+    pub deposits: u64,
+    pub token_a_amount: u64,
+    pub token_b_amount: u64,
+    /// Pool tokens minted to the referred depositors
+    pub pool_token_amount: u64,
+}
+
+impl ReferredDeposits {
+    /// Program address of the counters for the given pool and referrer
+    pub fn find_address(swap: &Pubkey, referrer: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[REFERRAL_SEED, swap.as_ref(), referrer.as_ref()],
+            program_id,
+        )
+    }
+
+    /// Counts a deposit
+    pub fn record(&mut self, token_a_amount: u64, token_b_amount: u64, pool_token_amount: u64) {
+        self.deposits = self.deposits.saturating_add(1);
+        self.token_a_amount = self.token_a_amount.saturating_add(token_a_amount);
+        self.token_b_amount = self.token_b_amount.saturating_add(token_b_amount);
+        self.pool_token_amount = self.pool_token_amount.saturating_add(pool_token_amount);
+    }
+}
+
+impl Sealed for ReferredDeposits {}
+impl IsInitialized for ReferredDeposits {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ReferredDeposits {
+    const LEN: usize = 98;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 98];
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            referrer,
+            deposits,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 8, 8, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        referrer.copy_from_slice(self.referrer.as_ref());
+        *deposits = self.deposits.to_le_bytes();
+        *token_a_amount = self.token_a_amount.to_le_bytes();
+        *token_b_amount = self.token_b_amount.to_le_bytes();
+        *pool_token_amount = self.pool_token_amount.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [ReferredDeposits](struct.ReferredDeposits.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 98];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            referrer,
+            deposits,
+            token_a_amount,
+            token_b_amount,
+            pool_token_amount,
+        ) = array_refs![input, 1, 1, 32, 32, 8, 8, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            referrer: Pubkey::new_from_array(*referrer),
+            deposits: u64::from_le_bytes(*deposits),
+            token_a_amount: u64::from_le_bytes(*token_a_amount),
+            token_b_amount: u64::from_le_bytes(*token_b_amount),
+            pool_token_amount: u64::from_le_bytes(*pool_token_amount),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- vesting
+
+/// Linear vesting of a pool's initial supply to its creator, so nobody has to
+/// trust that the creator won't pull the genesis liquidity straight away
+#[derive(Debug, Default, PartialEq)]
+pub struct Vesting {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the vesting program address
+    pub bump_seed: u8,
+    /// Pool whose initial supply is vesting
+    pub swap: Pubkey,
+    /// Pool token account holding the unvested tokens, owned by the swap authority
+    pub vault: Pubkey,
+    /// Pool token account of the creator, receiving the vested tokens
+    pub beneficiary: Pubkey,
+    /// Pool tokens vesting in total
+    pub total_amount: u64,
+    /// Pool tokens already released to the beneficiary
+    pub claimed_amount: u64,
+    /// Slot the vesting started at, ie. the pool's initialization
+    pub start_slot: u64,
+    /// Number of slots until everything is vested
+    pub duration_slots: u64,
+}
+
+impl Vesting {
+    /// Program address of the vesting record for the given pool
+    pub fn find_address(swap: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[VESTING_SEED, swap.as_ref()], program_id)
+    }
+
+    /// Pool tokens vested at the given slot, claimed or not
+    pub fn vested_amount(&self, slot: u64) -> Option<u64> {
+        let elapsed_slots = slot.saturating_sub(self.start_slot);
+        if elapsed_slots >= self.duration_slots {
+            return Some(self.total_amount);
+        }
+        let vested_amount = u128::from(self.total_amount)
+            .checked_mul(u128::from(elapsed_slots))?
+            .checked_div(u128::from(self.duration_slots))?;
+        u64::try_from(vested_amount).ok()
+    }
+
+    /// Pool tokens that can be released at the given slot
+    pub fn claimable_amount(&self, slot: u64) -> Option<u64> {
+        self.vested_amount(slot)?.checked_sub(self.claimed_amount)
+    }
+}
+
+impl Sealed for Vesting {}
+impl IsInitialized for Vesting {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for Vesting {
+    const LEN: usize = 130;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 130];
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            vault,
+            beneficiary,
+            total_amount,
+            claimed_amount,
+            start_slot,
+            duration_slots,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 32, 8, 8, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        vault.copy_from_slice(self.vault.as_ref());
+        beneficiary.copy_from_slice(self.beneficiary.as_ref());
+        *total_amount = self.total_amount.to_le_bytes();
+        *claimed_amount = self.claimed_amount.to_le_bytes();
+        *start_slot = self.start_slot.to_le_bytes();
+        *duration_slots = self.duration_slots.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [Vesting](struct.Vesting.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 130];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            vault,
+            beneficiary,
+            total_amount,
+            claimed_amount,
+            start_slot,
+            duration_slots,
+        ) = array_refs![input, 1, 1, 32, 32, 32, 8, 8, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            vault: Pubkey::new_from_array(*vault),
+            beneficiary: Pubkey::new_from_array(*beneficiary),
+            total_amount: u64::from_le_bytes(*total_amount),
+            claimed_amount: u64::from_le_bytes(*claimed_amount),
+            start_slot: u64::from_le_bytes(*start_slot),
+            duration_slots: u64::from_le_bytes(*duration_slots),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- liquidity lock
+
+/// Pool tokens escrowed until a given slot.  The record is the receipt: it is
+/// keyed by its owner and can't be transferred, so anyone can verify that the
+/// liquidity stays locked.
+#[derive(Debug, Default, PartialEq)]
+pub struct LiquidityLock {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the lock's program address
+    pub bump_seed: u8,
+    /// Pool whose tokens are locked
+    pub swap: Pubkey,
+    /// Wallet the tokens are released to
+    pub owner: Pubkey,
+    /// Pool token account holding the locked tokens, owned by the swap authority
+    pub vault: Pubkey,
+    /// Pool tokens locked
+    pub amount: u64,
+    /// First slot at which the tokens can be unlocked
+    pub unlock_slot: u64,
+}
+
+impl LiquidityLock {
+    /// Program address of the lock for the given pool, owner and unlock slot
+    pub fn find_address(
+        swap: &Pubkey,
+        owner: &Pubkey,
+        unlock_slot: u64,
+        program_id: &Pubkey,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                LOCK_SEED,
+                swap.as_ref(),
+                owner.as_ref(),
+                &unlock_slot.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+}
+
+impl Sealed for LiquidityLock {}
+impl IsInitialized for LiquidityLock {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for LiquidityLock {
+    const LEN: usize = 114;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 114];
+        let (is_initialized, bump_seed, swap, owner, vault, amount, unlock_slot) =
+            mut_array_refs![output, 1, 1, 32, 32, 32, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        owner.copy_from_slice(self.owner.as_ref());
+        vault.copy_from_slice(self.vault.as_ref());
+        *amount = self.amount.to_le_bytes();
+        *unlock_slot = self.unlock_slot.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [LiquidityLock](struct.LiquidityLock.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 114];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, swap, owner, vault, amount, unlock_slot) =
+            array_refs![input, 1, 1, 32, 32, 32, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            owner: Pubkey::new_from_array(*owner),
+            vault: Pubkey::new_from_array(*vault),
+            amount: u64::from_le_bytes(*amount),
+            unlock_slot: u64::from_le_bytes(*unlock_slot),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- lp position
+
+/// Slot a pool token account last had pool tokens minted into it by a
+/// single-sided deposit, on a pool with a withdraw cooldown.  Single-sided
+/// withdrawals burning from the account wait out the cooldown from it, other
+/// holders' go through.
+#[derive(Debug, Default, PartialEq)]
+pub struct LpPosition {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the position's program address
+    pub bump_seed: u8,
+    /// Pool the position is in
+    pub swap: Pubkey,
+    /// Pool token account holding the position
+    pub pool_account: Pubkey,
+    /// Slot of the account's last single-sided deposit
+    pub last_single_deposit_slot: u64,
+}
+
+impl LpPosition {
+    /// Program address of the position for the given pool and pool token
+    /// account
+    pub fn find_address(swap: &Pubkey, pool_account: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[LP_POSITION_SEED, swap.as_ref(), pool_account.as_ref()],
+            program_id,
+        )
+    }
+
+    /// Single-sided withdrawals from the account can go through at the given
+    /// slot, its last single-sided deposit being at least the cooldown ago
+    pub fn cooled_down(&self, cooldown_slots: u64, slot: u64) -> bool {
+        slot >= self.last_single_deposit_slot.saturating_add(cooldown_slots)
+    }
+}
+
+impl Sealed for LpPosition {}
+impl IsInitialized for LpPosition {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for LpPosition {
+    const LEN: usize = 74;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 74];
+        let (is_initialized, bump_seed, swap, pool_account, last_single_deposit_slot) =
+            mut_array_refs![output, 1, 1, 32, 32, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        pool_account.copy_from_slice(self.pool_account.as_ref());
+        *last_single_deposit_slot = self.last_single_deposit_slot.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [LpPosition](struct.LpPosition.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 74];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, swap, pool_account, last_single_deposit_slot) =
+            array_refs![input, 1, 1, 32, 32, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            pool_account: Pubkey::new_from_array(*pool_account),
+            last_single_deposit_slot: u64::from_le_bytes(*last_single_deposit_slot),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- liquidity snapshot
+
+/// A pool's pool token supply and reserves at a configured slot, for token
+/// projects to airdrop to LPs pro rata against on-chain state rather than an
+/// indexer's snapshot.
+///
+/// The first crank from the slot on records all three in one instruction, so
+/// they're consistent with each other, along with the slot they were read
+/// in.  LP balances at a past slot can't be read on chain, so the airdrop
+/// weighs its claimants' balances against the recorded supply.
+#[derive(Debug, Default, PartialEq)]
+pub struct LiquiditySnapshot {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the snapshot's program address
+    pub bump_seed: u8,
+    /// Pool being snapshotted
+    pub swap: Pubkey,
+    /// Earliest slot the snapshot can be taken in
+    pub slot: u64,
+    /// The snapshot was taken, the amounts below are final
+    pub taken: bool,
+    /// Slot the snapshot was taken in
+    pub taken_slot: u64,
+    /// Pool token supply
+    pub pool_token_supply: u64,
+    /// Amount of token A in the pool
+    pub token_a_amount: u64,
+    /// Amount of token B in the pool
+    pub token_b_amount: u64,
+}
+
+impl LiquiditySnapshot {
+    /// Program address of the liquidity snapshot for the given pool
+    pub fn find_address(swap: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[SNAPSHOT_SEED, swap.as_ref()], program_id)
+    }
+}
+
+impl Sealed for LiquiditySnapshot {}
+impl IsInitialized for LiquiditySnapshot {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for LiquiditySnapshot {
+    const LEN: usize = 75;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 75];
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            slot,
+            taken,
+            taken_slot,
+            pool_token_supply,
+            token_a_amount,
+            token_b_amount,
+        ) = mut_array_refs![output, 1, 1, 32, 8, 1, 8, 8, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        *slot = self.slot.to_le_bytes();
+        taken[0] = self.taken as u8;
+        *taken_slot = self.taken_slot.to_le_bytes();
+        *pool_token_supply = self.pool_token_supply.to_le_bytes();
+        *token_a_amount = self.token_a_amount.to_le_bytes();
+        *token_b_amount = self.token_b_amount.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [LiquiditySnapshot](struct.LiquiditySnapshot.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 75];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            slot,
+            taken,
+            taken_slot,
+            pool_token_supply,
+            token_a_amount,
+            token_b_amount,
+        ) = array_refs![input, 1, 1, 32, 8, 1, 8, 8, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            slot: u64::from_le_bytes(*slot),
+            taken: match taken {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            taken_slot: u64::from_le_bytes(*taken_slot),
+            pool_token_supply: u64::from_le_bytes(*pool_token_supply),
+            token_a_amount: u64::from_le_bytes(*token_a_amount),
+            token_b_amount: u64::from_le_bytes(*token_b_amount),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- oracle config
+
+/// Reads the little-endian u64 price at `price_offset` out of an oracle
+/// account's data
+pub fn read_oracle_price(oracle_data: &[u8], price_offset: u32) -> Option<u64> {
+    let start = usize::try_from(price_offset).ok()?;
+    let price = oracle_data.get(start..start.checked_add(8)?)?;
+    Some(u64::from_le_bytes(price.try_into().ok()?))
+}
+
+/// Oracle a constant price pool is soft-pegged to.  A permissionless crank
+/// moves `token_b_price` toward the oracle price, at most `max_step_bps` per
+/// elapsed slot, and never outside the admin-set bounds.
+#[derive(Debug, Default, PartialEq)]
+pub struct OracleConfig {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the oracle config program address
+    pub bump_seed: u8,
+    /// Pool being pegged
+    pub swap: Pubkey,
+    /// Account publishing the price
+    pub oracle: Pubkey,
+    /// Byte offset of the little-endian u64 price in the oracle account, in
+    /// the same units as `token_b_price`
+    pub price_offset: u32,
+    /// Lowest price the crank may set
+    pub min_price: u64,
+    /// Highest price the crank may set
+    pub max_price: u64,
+    /// Largest price move per slot, in basis points of the current price
+    pub max_step_bps: u16,
+    /// Slot of the last crank
+    pub last_update_slot: u64,
+}
+
+impl OracleConfig {
+    /// Program address of the oracle config for the given pool
+    pub fn find_address(swap: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[ORACLE_SEED, swap.as_ref()], program_id)
+    }
+
+    /// Reads the oracle price out of the oracle account's data
+    pub fn read_price(&self, oracle_data: &[u8]) -> Option<u64> {
+        read_oracle_price(oracle_data, self.price_offset)
+    }
+
+    /// Price after stepping from `current_price` toward `oracle_price` at `slot`
+    pub fn next_price(&self, current_price: u64, oracle_price: u64, slot: u64) -> Option<u64> {
+        let target = oracle_price.max(self.min_price).min(self.max_price);
+        let elapsed_slots = slot.saturating_sub(self.last_update_slot);
+        // always allow at least one unit, so low prices can still move
+        let max_step = u128::from(current_price)
+            .checked_mul(u128::from(self.max_step_bps))?
+            .checked_mul(u128::from(elapsed_slots))?
+            .checked_div(10_000)?
+            .max(u128::from(elapsed_slots.min(1)));
+        let max_step = u64::try_from(max_step).unwrap_or(u64::MAX);
+        Some(if target > current_price {
+            target.min(current_price.saturating_add(max_step))
+        } else {
+            target.max(current_price.saturating_sub(max_step))
+        })
+    }
+}
+
+impl Sealed for OracleConfig {}
+impl IsInitialized for OracleConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for OracleConfig {
+    const LEN: usize = 96;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 96];
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            oracle,
+            price_offset,
+            min_price,
+            max_price,
+            max_step_bps,
+            last_update_slot,
+        ) = mut_array_refs![output, 1, 1, 32, 32, 4, 8, 8, 2, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        oracle.copy_from_slice(self.oracle.as_ref());
+        *price_offset = self.price_offset.to_le_bytes();
+        *min_price = self.min_price.to_le_bytes();
+        *max_price = self.max_price.to_le_bytes();
+        *max_step_bps = self.max_step_bps.to_le_bytes();
+        *last_update_slot = self.last_update_slot.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [OracleConfig](struct.OracleConfig.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 96];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            swap,
+            oracle,
+            price_offset,
+            min_price,
+            max_price,
+            max_step_bps,
+            last_update_slot,
+        ) = array_refs![input, 1, 1, 32, 32, 4, 8, 8, 2, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            oracle: Pubkey::new_from_array(*oracle),
+            price_offset: u32::from_le_bytes(*price_offset),
+            min_price: u64::from_le_bytes(*min_price),
+            max_price: u64::from_le_bytes(*max_price),
+            max_step_bps: u16::from_le_bytes(*max_step_bps),
+            last_update_slot: u64::from_le_bytes(*last_update_slot),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- stake pool rate
+
+/// Owner of the SPL stake pool accounts a constant price pool can peg to
+pub mod stake_pool_program {
+    solana_program::declare_id!("SPoo1Ku8WFXoNDMHPsrGSTSG1Y47rzgn41SLUNakuHy");
+}
+
+/// What a pegged pool reads of an SPL stake pool account, the LST's
+/// redemption rate being `total_lamports / pool_token_supply`
+#[derive(Debug, Default, PartialEq)]
+pub struct StakePoolRate {
+    /// Mint of the stake pool's LST
+    pub pool_mint: Pubkey,
+    /// Lamports under management, as of `last_update_epoch`
+    pub total_lamports: u64,
+    /// LST supply, as of `last_update_epoch`
+    pub pool_token_supply: u64,
+    /// Epoch the stake pool's balances were last updated in
+    pub last_update_epoch: u64,
+}
+
+impl StakePoolRate {
+    /// Reads the rate out of a stake pool account's data, `None` for any
+    /// other account of the stake pool program
+    pub fn read(stake_pool_data: &[u8]) -> Option<Self> {
+        // account_type, manager, staker, stake_deposit_authority,
+        // stake_withdraw_bump_seed, validator_list, reserve_stake come first
+        let input = stake_pool_data.get(..282)?;
+        let input = array_ref![input, 0, 282];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (account_type, _, pool_mint, _, total_lamports, pool_token_supply, last_update_epoch) =
+            array_refs![input, 1, 161, 32, 64, 8, 8, 8];
+        // AccountType::StakePool
+        if account_type[0] != 1 {
+            return None;
+        }
+        Some(Self {
+            pool_mint: Pubkey::new_from_array(*pool_mint),
+            total_lamports: u64::from_le_bytes(*total_lamports),
+            pool_token_supply: u64::from_le_bytes(*pool_token_supply),
+            last_update_epoch: u64::from_le_bytes(*last_update_epoch),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- pool metadata
+
+/// Human readable label of a pool, set by its admin, so explorers don't need
+/// an off-chain mapping.  Name and URI are UTF-8, zero padded.
+#[derive(Debug, PartialEq)]
+pub struct PoolMetadata {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the metadata program address
+    pub bump_seed: u8,
+    /// Pool being labelled
+    pub swap: Pubkey,
+    /// Short display name
+    pub name: [u8; MAX_NAME_LEN],
+    /// Link to further details, e.g. an off-chain JSON document
+    pub uri: [u8; MAX_URI_LEN],
+    /// Free-form tag bytes, e.g. categories, 0 for none
+    pub tags: [u8; TAGS_LEN],
+}
+
+impl PoolMetadata {
+    /// Program address of the metadata for the given pool
+    pub fn find_address(swap: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[METADATA_SEED, swap.as_ref()], program_id)
+    }
+
+    /// Name without its zero padding, `None` when it isn't UTF-8
+    pub fn name(&self) -> Option<&str> {
+        trim_padding(&self.name)
+    }
+
+    /// URI without its zero padding, `None` when it isn't UTF-8
+    pub fn uri(&self) -> Option<&str> {
+        trim_padding(&self.uri)
+    }
+}
+
+fn trim_padding(bytes: &[u8]) -> Option<&str> {
+    let len = bytes
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |i| i + 1);
+    std::str::from_utf8(&bytes[..len]).ok()
+}
+
+impl Sealed for PoolMetadata {}
+impl IsInitialized for PoolMetadata {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PoolMetadata {
+    const LEN: usize = 202;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 202];
+        let (is_initialized, bump_seed, swap, name, uri, tags) =
+            mut_array_refs![output, 1, 1, 32, MAX_NAME_LEN, MAX_URI_LEN, TAGS_LEN];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        swap.copy_from_slice(self.swap.as_ref());
+        *name = self.name;
+        *uri = self.uri;
+        *tags = self.tags;
+    }
+
+    /// Unpacks a byte buffer into a [PoolMetadata](struct.PoolMetadata.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 202];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, swap, name, uri, tags) =
+            array_refs![input, 1, 1, 32, MAX_NAME_LEN, MAX_URI_LEN, TAGS_LEN];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            swap: Pubkey::new_from_array(*swap),
+            name: *name,
+            uri: *uri,
+            tags: *tags,
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- program config
+
+/// Program-wide record of who can upgrade the program, so integrators can
+/// check the governance posture on-chain.  Production builds refuse to
+/// initialize pools while the upgrade authority isn't recorded here.
+#[derive(Debug, Default, PartialEq)]
+pub struct ProgramConfig {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the config program address
+    pub bump_seed: u8,
+    /// Upgrade authority of the program, `Pubkey::default()` once it's immutable
+    pub upgrade_authority: Pubkey,
+    /// SPL Governance program whose governances can be pool admins, signing
+    /// through their native treasury, `Pubkey::default()` for none
+    pub governance_program: Pubkey,
+}
+
+impl ProgramConfig {
+    /// Program address of the program's config
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[PROGRAM_CONFIG_SEED], program_id)
+    }
+
+    /// Whether the config matches the upgrade authority read from the
+    /// program's data account
+    pub fn documents(&self, upgrade_authority: Option<Pubkey>) -> bool {
+        self.is_initialized && self.upgrade_authority == upgrade_authority.unwrap_or_default()
+    }
+
+    /// Native treasury signing for a governance, an account owned by the
+    /// governance program, `None` for any other account
+    pub fn native_treasury(&self, governance: &Pubkey, owner: &Pubkey) -> Option<Pubkey> {
+        if self.governance_program == Pubkey::default() || *owner != self.governance_program {
+            return None;
+        }
+        let (native_treasury, _bump_seed) = Pubkey::find_program_address(
+            &[NATIVE_TREASURY_SEED, governance.as_ref()],
+            &self.governance_program,
+        );
+        Some(native_treasury)
+    }
+
+    /// Unpacks a config written before its last fields were added, which
+    /// read as zeroes
+    pub fn unpack_outdated(input: &[u8]) -> Result<Self, ProgramError> {
+        if input.len() > Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        let mut padded = input.to_vec();
+        padded.resize(Self::LEN, 0);
+        Self::unpack(&padded)
+    }
+}
+
+/// Reads the upgrade authority out of a BPF upgradeable loader `ProgramData`
+/// account: a u32 state tag of 3, the deployment slot, then an optional key
+pub fn unpack_upgrade_authority(input: &[u8]) -> Result<Option<Pubkey>, ProgramError> {
+    if input.len() < 45 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    let input = array_ref![input, 0, 45];
+    #[allow(clippy::ptr_offset_with_cast)]
+    let (state, _slot, has_authority, authority) = array_refs![input, 4, 8, 1, 32];
+    if u32::from_le_bytes(*state) != 3 {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    match has_authority {
+        [0] => Ok(None),
+        [1] => Ok(Some(Pubkey::new_from_array(*authority))),
+        _ => Err(ProgramError::InvalidAccountData),
+    }
+}
+
+impl Sealed for ProgramConfig {}
+impl IsInitialized for ProgramConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for ProgramConfig {
+    const LEN: usize = 66;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 66];
+        let (is_initialized, bump_seed, upgrade_authority, governance_program) =
+            mut_array_refs![output, 1, 1, 32, 32];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        upgrade_authority.copy_from_slice(self.upgrade_authority.as_ref());
+        governance_program.copy_from_slice(self.governance_program.as_ref());
+    }
+
+    /// Unpacks a byte buffer into a [ProgramConfig](struct.ProgramConfig.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 66];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, upgrade_authority, governance_program) =
+            array_refs![input, 1, 1, 32, 32];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            upgrade_authority: Pubkey::new_from_array(*upgrade_authority),
+            governance_program: Pubkey::new_from_array(*governance_program),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- keepers
+
+/// Program-wide terms for keepers bonding to crank pools that restrict their
+/// cranks to bonded keepers.  Set by the upgrade authority documented in the
+/// program config, who also adjudicates slashing: the program can't tell a
+/// stale oracle price from a quiet market on its own.
+#[derive(Debug, Default, PartialEq)]
+pub struct KeeperConfig {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the config program address
+    pub bump_seed: u8,
+    /// Key allowed to change the terms and slash bonds
+    pub authority: Pubkey,
+    /// Lamports a keeper must have bonded to crank
+    pub min_bond: u64,
+    /// Slots between a keeper unbonding and withdrawing its bond, for bad
+    /// cranks to be slashed meanwhile
+    pub unbonding_slots: u64,
+}
+
+impl KeeperConfig {
+    /// Program address of the program's keeper config
+    pub fn find_address(program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[KEEPER_CONFIG_SEED], program_id)
+    }
+}
+
+impl Sealed for KeeperConfig {}
+impl IsInitialized for KeeperConfig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for KeeperConfig {
+    const LEN: usize = 50;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 50];
+        let (is_initialized, bump_seed, authority, min_bond, unbonding_slots) =
+            mut_array_refs![output, 1, 1, 32, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        authority.copy_from_slice(self.authority.as_ref());
+        *min_bond = self.min_bond.to_le_bytes();
+        *unbonding_slots = self.unbonding_slots.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [KeeperConfig](struct.KeeperConfig.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 50];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, authority, min_bond, unbonding_slots) =
+            array_refs![input, 1, 1, 32, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            authority: Pubkey::new_from_array(*authority),
+            min_bond: u64::from_le_bytes(*min_bond),
+            unbonding_slots: u64::from_le_bytes(*unbonding_slots),
+        })
+    }
+}
+
+/// Vetted fee and curve bundle new pools can be created from by index.  Set
+/// by the upgrade authority documented in the program config; production
+/// builds only create pools from them.
+#[derive(Debug, Default, PartialEq)]
+pub struct PoolTemplate {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the template's program address
+    pub bump_seed: u8,
+    /// Index pools pick the template by
+    pub index: u8,
+    /// Fees of the pools created from it
+    pub fees: Fees,
+    /// Curve of the pools created from it
+    pub swap_curve: SwapCurve,
+}
+
+impl PoolTemplate {
+    /// Program address of the template at the given index
+    pub fn find_address(index: u8, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[POOL_TEMPLATE_SEED, &[index]], program_id)
+    }
+}
+
+impl Sealed for PoolTemplate {}
+impl IsInitialized for PoolTemplate {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for PoolTemplate {
+    const LEN: usize = 100;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 100];
+        let (is_initialized, bump_seed, index, fees, swap_curve) =
+            mut_array_refs![output, 1, 1, 1, Fees::LEN, SwapCurve::LEN];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        index[0] = self.index;
+        self.fees.pack_into_slice(&mut fees[..]);
+        self.swap_curve.pack_into_slice(&mut swap_curve[..]);
+    }
+
+    /// Unpacks a byte buffer into a [PoolTemplate](struct.PoolTemplate.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 100];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, bump_seed, index, fees, swap_curve) =
+            array_refs![input, 1, 1, 1, Fees::LEN, SwapCurve::LEN];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            index: index[0],
+            fees: Fees::unpack_from_slice(fees)?,
+            swap_curve: SwapCurve::unpack_from_slice(swap_curve)?,
+        })
+    }
+}
+
+/// A keeper's bond, held as lamports of the account on top of its rent, and
+/// the receipt of its last crank, which a slashing is weighed against
+#[derive(Debug, Default, PartialEq)]
+pub struct KeeperBond {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Bump seed used to derive the bond's program address
+    pub bump_seed: u8,
+    /// Wallet signing the keeper's cranks
+    pub keeper: Pubkey,
+    /// Lamports bonded
+    pub bond: u64,
+    /// Slot from which the bond can be withdrawn, 0 while the keeper is
+    /// bonded rather than unbonding
+    pub unbonding_slot: u64,
+    /// Cranks signed, saturating
+    pub cranks: u64,
+    /// Pool and slot of the last crank
+    pub last_crank_swap: Pubkey,
+    pub last_crank_slot: u64,
+    /// Oracle price the last crank read, 0 for a stake pool rate refresh or
+    /// once the crank has been slashed
+    pub last_crank_oracle_price: u64,
+}
+
+impl KeeperBond {
+    /// Program address of the given keeper's bond
+    pub fn find_address(keeper: &Pubkey, program_id: &Pubkey) -> (Pubkey, u8) {
+        Pubkey::find_program_address(&[KEEPER_SEED, keeper.as_ref()], program_id)
+    }
+
+    /// Whether the keeper can crank pools restricted to bonded keepers
+    pub fn is_bonded(&self, config: &KeeperConfig) -> bool {
+        self.is_initialized && self.unbonding_slot == 0 && self.bond >= config.min_bond
+    }
+
+    /// Counts a crank of the pool
+    pub fn record_crank(&mut self, swap: Pubkey, slot: u64, oracle_price: u64) {
+        self.cranks = self.cranks.saturating_add(1);
+        self.last_crank_swap = swap;
+        self.last_crank_slot = slot;
+        self.last_crank_oracle_price = oracle_price;
+    }
+
+    /// Most lamports a single bad crank can cost the keeper
+    pub fn max_slash(&self) -> u64 {
+        (u128::from(self.bond) * u128::from(MAX_SLASH_BPS) / 10_000) as u64
+    }
+}
+
+impl Sealed for KeeperBond {}
+impl IsInitialized for KeeperBond {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for KeeperBond {
+    const LEN: usize = 106;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 106];
+        let (
+            is_initialized,
+            bump_seed,
+            keeper,
+            bond,
+            unbonding_slot,
+            cranks,
+            last_crank_swap,
+            last_crank_slot,
+            last_crank_oracle_price,
+        ) = mut_array_refs![output, 1, 1, 32, 8, 8, 8, 32, 8, 8];
+        is_initialized[0] = self.is_initialized as u8;
+        bump_seed[0] = self.bump_seed;
+        keeper.copy_from_slice(self.keeper.as_ref());
+        *bond = self.bond.to_le_bytes();
+        *unbonding_slot = self.unbonding_slot.to_le_bytes();
+        *cranks = self.cranks.to_le_bytes();
+        last_crank_swap.copy_from_slice(self.last_crank_swap.as_ref());
+        *last_crank_slot = self.last_crank_slot.to_le_bytes();
+        *last_crank_oracle_price = self.last_crank_oracle_price.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [KeeperBond](struct.KeeperBond.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 106];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            is_initialized,
+            bump_seed,
+            keeper,
+            bond,
+            unbonding_slot,
+            cranks,
+            last_crank_swap,
+            last_crank_slot,
+            last_crank_oracle_price,
+        ) = array_refs![input, 1, 1, 32, 8, 8, 8, 32, 8, 8];
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            bump_seed: bump_seed[0],
+            keeper: Pubkey::new_from_array(*keeper),
+            bond: u64::from_le_bytes(*bond),
+            unbonding_slot: u64::from_le_bytes(*unbonding_slot),
+            cranks: u64::from_le_bytes(*cranks),
+            last_crank_swap: Pubkey::new_from_array(*last_crank_swap),
+            last_crank_slot: u64::from_le_bytes(*last_crank_slot),
+            last_crank_oracle_price: u64::from_le_bytes(*last_crank_oracle_price),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- admin multisig
+
+/// M-of-N list of keys acting as a pool admin.  Made the owner of a pool fee
+/// account, its admin instructions need `m` of the signers to sign rather
+/// than a single key.
+#[derive(Debug, Default, PartialEq)]
+pub struct AdminMultisig {
+    /// Initialized state.
+    pub is_initialized: bool,
+    /// Signatures required
+    pub m: u8,
+    /// Signers in the list
+    pub n: u8,
+    /// Keys that can sign, the first `n` used
+    pub signers: [Pubkey; MAX_ADMIN_SIGNERS],
+}
+
+impl AdminMultisig {
+    /// Keys that can sign
+    pub fn signers(&self) -> &[Pubkey] {
+        &self.signers[..usize::from(self.n).min(MAX_ADMIN_SIGNERS)]
+    }
+}
+
+impl Sealed for AdminMultisig {}
+impl IsInitialized for AdminMultisig {
+    fn is_initialized(&self) -> bool {
+        self.is_initialized
+    }
+}
+
+impl Pack for AdminMultisig {
+    const LEN: usize = 3 + 32 * MAX_ADMIN_SIGNERS;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 3 + 32 * MAX_ADMIN_SIGNERS];
+        let (is_initialized, m, n, signers) =
+            mut_array_refs![output, 1, 1, 1, 32 * MAX_ADMIN_SIGNERS];
+        is_initialized[0] = self.is_initialized as u8;
+        m[0] = self.m;
+        n[0] = self.n;
+        for (output, signer) in signers.chunks_exact_mut(32).zip(self.signers.iter()) {
+            output.copy_from_slice(signer.as_ref());
+        }
+    }
+
+    /// Unpacks a byte buffer into an [AdminMultisig](struct.AdminMultisig.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 3 + 32 * MAX_ADMIN_SIGNERS];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (is_initialized, m, n, input_signers) =
+            array_refs![input, 1, 1, 1, 32 * MAX_ADMIN_SIGNERS];
+        let mut signers = [Pubkey::default(); MAX_ADMIN_SIGNERS];
+        for (signer, input) in signers.iter_mut().zip(input_signers.chunks_exact(32)) {
+            *signer = Pubkey::new_from_array(input.try_into().unwrap());
+        }
+        Ok(Self {
+            is_initialized: match is_initialized {
+                [0] => false,
+                [1] => true,
+                _ => return Err(ProgramError::InvalidAccountData),
+            },
+            m: m[0],
+            n: n[0],
+            signers,
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- pool state view
+
+/// Snapshot of a pool's reserves, supply and fees, as returned by
+/// `GetMultiplePoolStates`.  Not stored in any account.
+#[derive(Debug, Default, PartialEq)]
+pub struct PoolStateView {
+    /// Swap account the snapshot was taken from
+    pub swap: Pubkey,
+    /// Reserve of token A
+    pub token_a_amount: u64,
+    /// Reserve of token B
+    pub token_b_amount: u64,
+    /// Outstanding pool tokens
+    pub pool_token_supply: u64,
+    /// Decimals of the pool token mint
+    pub pool_mint_decimals: u8,
+    /// Decimals of the token A mint, `u8::MAX` for V1 pools, which don't
+    /// record them
+    pub token_a_decimals: u8,
+    /// Decimals of the token B mint, `u8::MAX` for V1 pools
+    pub token_b_decimals: u8,
+    /// Pool tokens held in liquidity locks
+    pub locked_pool_tokens: u64,
+    /// Fees charged by the pool
+    pub fees: Fees,
+    /// What the pool's curve supports
+    pub capabilities: CurveCapabilities,
+}
+
+impl PoolStateView {
+    /// Unpacks the return data of a `GetMultiplePoolStates`, in account order
+    pub fn unpack_many(input: &[u8]) -> Result<Vec<Self>, ProgramError> {
+        if !input.len().is_multiple_of(Self::LEN) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        input
+            .chunks(Self::LEN)
+            .map(Self::unpack_from_slice)
+            .collect()
+    }
+}
+
+impl Sealed for PoolStateView {}
+
+impl Pack for PoolStateView {
+    const LEN: usize = 132;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 132];
+        let (
+            swap,
+            token_a_amount,
+            token_b_amount,
+            pool_token_supply,
+            pool_mint_decimals,
+            token_a_decimals,
+            token_b_decimals,
+            locked_pool_tokens,
+            fees,
+            capabilities,
+        ) = mut_array_refs![output, 32, 8, 8, 8, 1, 1, 1, 8, Fees::LEN, 1];
+        swap.copy_from_slice(self.swap.as_ref());
+        *token_a_amount = self.token_a_amount.to_le_bytes();
+        *token_b_amount = self.token_b_amount.to_le_bytes();
+        *pool_token_supply = self.pool_token_supply.to_le_bytes();
+        pool_mint_decimals[0] = self.pool_mint_decimals;
+        token_a_decimals[0] = self.token_a_decimals;
+        token_b_decimals[0] = self.token_b_decimals;
+        *locked_pool_tokens = self.locked_pool_tokens.to_le_bytes();
+        self.fees.pack_into_slice(&mut fees[..]);
+        capabilities[0] = self.capabilities.to_byte();
+    }
+
+    /// Unpacks a byte buffer into a [PoolStateView](struct.PoolStateView.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 132];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            swap,
+            token_a_amount,
+            token_b_amount,
+            pool_token_supply,
+            pool_mint_decimals,
+            token_a_decimals,
+            token_b_decimals,
+            locked_pool_tokens,
+            fees,
+            capabilities,
+        ) = array_refs![input, 32, 8, 8, 8, 1, 1, 1, 8, Fees::LEN, 1];
+        Ok(Self {
+            swap: Pubkey::new_from_array(*swap),
+            token_a_amount: u64::from_le_bytes(*token_a_amount),
+            token_b_amount: u64::from_le_bytes(*token_b_amount),
+            pool_token_supply: u64::from_le_bytes(*pool_token_supply),
+            pool_mint_decimals: pool_mint_decimals[0],
+            token_a_decimals: token_a_decimals[0],
+            token_b_decimals: token_b_decimals[0],
+            locked_pool_tokens: u64::from_le_bytes(*locked_pool_tokens),
+            fees: Fees::unpack_from_slice(fees)?,
+            capabilities: CurveCapabilities::from_byte(capabilities[0]),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- pool health
+
+/// Health of a pool, as returned by `GetHealth` for monitoring to alert on.
+/// Not stored in any account.
+#[derive(Debug, Default, PartialEq)]
+pub struct PoolHealth {
+    /// Swap account the health was measured for
+    pub swap: Pubkey,
+    /// Value of the larger reserve over the smaller one's, at the pool's own
+    /// spot price, in basis points: 10_000 when balanced, `u64::MAX` when a
+    /// reserve is empty
+    pub imbalance_ratio_bps: u64,
+    /// How far the token A reserve is below half the pool's value, in basis
+    /// points of that half, 0 when it holds half or more
+    pub token_a_depletion_bps: u16,
+    /// How far the token B reserve is below half the pool's value
+    pub token_b_depletion_bps: u16,
+    /// Slot of the pool's last swap, 0 before the first
+    pub last_trade_slot: u64,
+    /// Slots since the last swap, or since slot 0 without one
+    pub slots_since_last_trade: u64,
+    /// Pool admin put the pool in emergency mode
+    pub emergency: bool,
+    /// Pool's curve takes no deposits
+    pub deposits_disabled: bool,
+    /// Pool admin paused trades and deposits
+    pub paused: bool,
+}
+
+impl PoolHealth {
+    /// Measures a pool's health at `slot` from its state and vault balances,
+    /// `None` if its curve can't price its reserves
+    pub fn measure(
+        swap: Pubkey,
+        token_swap: &dyn SwapState,
+        token_a_amount: u64,
+        token_b_amount: u64,
+        slot: u64,
+    ) -> Option<Self> {
+        let swap_curve = token_swap.swap_curve();
+        let token_a_value = u128::from(token_a_amount);
+        // token B valued in token A, at the price of its next marginal trade
+        let token_b_value = if token_b_amount == 0 {
+            0
+        } else {
+            swap_curve
+                .calculator
+                .spot_price(
+                    u128::from(token_b_amount),
+                    token_a_value,
+                    TradeDirection::BtoA,
+                )?
+                .checked_mul(&PreciseNumber::new(u128::from(token_b_amount))?)?
+                .floor()?
+                .to_imprecise()?
+        };
+        let (larger, smaller) = if token_a_value >= token_b_value {
+            (token_a_value, token_b_value)
+        } else {
+            (token_b_value, token_a_value)
+        };
+        let imbalance_ratio_bps = larger
+            .checked_mul(10_000)?
+            .checked_div(smaller)
+            .and_then(|ratio| u64::try_from(ratio).ok())
+            .unwrap_or(u64::MAX);
+        let total_value = token_a_value.checked_add(token_b_value)?;
+        // (half - reserve) / half, over the whole value so as not to lose a bit
+        let depletion_bps = |reserve: u128, other: u128| -> Option<u16> {
+            if total_value == 0 {
+                return Some(0);
+            }
+            let depletion = other.saturating_sub(reserve).checked_mul(10_000)? / total_value;
+            u16::try_from(depletion).ok()
+        };
+        Some(Self {
+            swap,
+            imbalance_ratio_bps,
+            token_a_depletion_bps: depletion_bps(token_a_value, token_b_value)?,
+            token_b_depletion_bps: depletion_bps(token_b_value, token_a_value)?,
+            last_trade_slot: token_swap.last_trade_slot(),
+            slots_since_last_trade: slot.saturating_sub(token_swap.last_trade_slot()),
+            emergency: token_swap.emergency(),
+            deposits_disabled: !swap_curve.calculator.allows_deposits(),
+            paused: token_swap.paused(),
+        })
+    }
+}
+
+impl Sealed for PoolHealth {}
+
+impl Pack for PoolHealth {
+    const LEN: usize = 63;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 63];
+        let (
+            swap,
+            imbalance_ratio_bps,
+            token_a_depletion_bps,
+            token_b_depletion_bps,
+            last_trade_slot,
+            slots_since_last_trade,
+            emergency,
+            deposits_disabled,
+            paused,
+        ) = mut_array_refs![output, 32, 8, 2, 2, 8, 8, 1, 1, 1];
+        swap.copy_from_slice(self.swap.as_ref());
+        *imbalance_ratio_bps = self.imbalance_ratio_bps.to_le_bytes();
+        *token_a_depletion_bps = self.token_a_depletion_bps.to_le_bytes();
+        *token_b_depletion_bps = self.token_b_depletion_bps.to_le_bytes();
+        *last_trade_slot = self.last_trade_slot.to_le_bytes();
+        *slots_since_last_trade = self.slots_since_last_trade.to_le_bytes();
+        emergency[0] = self.emergency as u8;
+        deposits_disabled[0] = self.deposits_disabled as u8;
+        paused[0] = self.paused as u8;
+    }
+
+    /// Unpacks a byte buffer into a [PoolHealth](struct.PoolHealth.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 63];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (
+            swap,
+            imbalance_ratio_bps,
+            token_a_depletion_bps,
+            token_b_depletion_bps,
+            last_trade_slot,
+            slots_since_last_trade,
+            emergency,
+            deposits_disabled,
+            paused,
+        ) = array_refs![input, 32, 8, 2, 2, 8, 8, 1, 1, 1];
+        Ok(Self {
+            swap: Pubkey::new_from_array(*swap),
+            imbalance_ratio_bps: u64::from_le_bytes(*imbalance_ratio_bps),
+            token_a_depletion_bps: u16::from_le_bytes(*token_a_depletion_bps),
+            token_b_depletion_bps: u16::from_le_bytes(*token_b_depletion_bps),
+            last_trade_slot: u64::from_le_bytes(*last_trade_slot),
+            slots_since_last_trade: u64::from_le_bytes(*slots_since_last_trade),
+            emergency: emergency[0] != 0,
+            deposits_disabled: deposits_disabled[0] != 0,
+            paused: paused[0] != 0,
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- swap quote
+
+/// Outcome of a swap as quoted by `SimulateSwap` through the return data,
+/// with the fees and rounding of the real swap.  Not stored in any account.
+#[derive(Debug, Default, PartialEq)]
+pub struct SwapQuote {
+    /// Source tokens the swap would take, fees included
+    pub source_amount_swapped: u64,
+    /// Destination tokens the swap would pay out
+    pub destination_amount_swapped: u64,
+    /// Source tokens of the trade fee, left in the reserves for LPs
+    pub trade_fee: u64,
+    /// Source tokens of the owner's fee, minted to the owner as pool tokens
+    pub owner_fee: u64,
+}
+
+impl Sealed for SwapQuote {}
+
+impl Pack for SwapQuote {
+    const LEN: usize = 32;
+
+    fn pack_into_slice(&self, output: &mut [u8]) {
+        let output = array_mut_ref![output, 0, 32];
+        let (source_amount_swapped, destination_amount_swapped, trade_fee, owner_fee) =
+            mut_array_refs![output, 8, 8, 8, 8];
+        *source_amount_swapped = self.source_amount_swapped.to_le_bytes();
+        *destination_amount_swapped = self.destination_amount_swapped.to_le_bytes();
+        *trade_fee = self.trade_fee.to_le_bytes();
+        *owner_fee = self.owner_fee.to_le_bytes();
+    }
+
+    /// Unpacks a byte buffer into a [SwapQuote](struct.SwapQuote.html).
+    fn unpack_from_slice(input: &[u8]) -> Result<Self, ProgramError> {
+        let input = array_ref![input, 0, 32];
+        #[allow(clippy::ptr_offset_with_cast)]
+        let (source_amount_swapped, destination_amount_swapped, trade_fee, owner_fee) =
+            array_refs![input, 8, 8, 8, 8];
+        Ok(Self {
+            source_amount_swapped: u64::from_le_bytes(*source_amount_swapped),
+            destination_amount_swapped: u64::from_le_bytes(*destination_amount_swapped),
+            trade_fee: u64::from_le_bytes(*trade_fee),
+            owner_fee: u64::from_le_bytes(*owner_fee),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_FEE_TIERS;
+    use crate::curve::{
+        base::CurveType, concentrated::ConcentratedCurve, constant_price::ConstantPriceCurve,
+        constant_product::ConstantProductCurve, fees::FeeTier, offset::OffsetCurve,
+        stable::StableCurve, weighted::WeightedCurve,
+    };
+    use proptest::{collection::vec, prelude::*};
+    use std::fmt::Debug;
+
+    #[test]
+    fn vesting_is_linear() {
+        let vesting = Vesting {
+            is_initialized: true,
+            total_amount: 1_000_000_000,
+            claimed_amount: 250_000_000,
+            start_slot: 100,
+            duration_slots: 1_000,
+            ..Vesting::default()
+        };
+        assert_eq!(vesting.vested_amount(0), Some(0));
+        assert_eq!(vesting.vested_amount(100), Some(0));
+        assert_eq!(vesting.vested_amount(600), Some(500_000_000));
+        assert_eq!(vesting.vested_amount(1_100), Some(1_000_000_000));
+        assert_eq!(vesting.vested_amount(u64::MAX), Some(1_000_000_000));
+        assert_eq!(vesting.claimable_amount(600), Some(250_000_000));
+        // nothing new vested since the last claim
+        assert_eq!(vesting.claimable_amount(350), Some(0));
+    }
+
+    #[test]
+    fn slot_volume_is_capped() {
+        let mut swap = SwapV2 {
+            max_slot_volume: 1_000,
+            ..SwapV2::default()
+        };
+        assert_eq!(swap.record_slot_volume(10, 600), Ok(()));
+        assert_eq!(
+            swap.record_slot_volume(10, 401),
+            Err(SwapError::RateLimited)
+        );
+        assert_eq!(swap.record_slot_volume(10, 400), Ok(()));
+        // the cap resets every slot
+        assert_eq!(swap.record_slot_volume(11, 1_000), Ok(()));
+        assert_eq!(swap.slot_volume, 1_000);
+    }
+
+    #[test]
+    fn health_measures_imbalance_at_the_pools_price() {
+        let constant_price = |token_b_price| SwapV2 {
+            swap_curve: SwapCurve {
+                curve_type: CurveType::ConstantPrice,
+                calculator: Box::new(ConstantPriceCurve {
+                    token_b_price,
+                    token_b_price_denominator: 0,
+                }),
+            },
+            last_trade_slot: 90,
+            ..SwapV2::default()
+        };
+        let swap = Pubkey::new_unique();
+
+        let health = PoolHealth::measure(swap, &constant_price(2), 1_000, 500, 100).unwrap();
+        assert_eq!(health.imbalance_ratio_bps, 10_000);
+        assert_eq!(health.token_a_depletion_bps, 0);
+        assert_eq!(health.token_b_depletion_bps, 0);
+        assert_eq!(health.slots_since_last_trade, 10);
+        assert!(!health.emergency && !health.deposits_disabled && !health.paused);
+
+        // 1_000 A against 3_000 A worth of B: A is at a quarter of the value
+        let health = PoolHealth::measure(swap, &constant_price(2), 1_000, 1_500, 100).unwrap();
+        assert_eq!(health.imbalance_ratio_bps, 30_000);
+        assert_eq!(health.token_a_depletion_bps, 5_000);
+        assert_eq!(health.token_b_depletion_bps, 0);
+
+        let health = PoolHealth::measure(swap, &constant_price(2), 0, 1_500, 100).unwrap();
+        assert_eq!(health.imbalance_ratio_bps, u64::MAX);
+        assert_eq!(health.token_a_depletion_bps, 10_000);
+
+        // a constant product pool always holds equal value at its own price
+        let health = PoolHealth::measure(swap, &SwapV2::default(), 1_000_000, 10_000, 100).unwrap();
+        assert_eq!(health.imbalance_ratio_bps, 10_000);
+        assert_eq!(health.slots_since_last_trade, 100);
+
+        let mut packed = [0u8; PoolHealth::LEN];
+        health.pack_into_slice(&mut packed);
+        assert_eq!(PoolHealth::unpack_from_slice(&packed).unwrap(), health);
+    }
+
+    #[test]
+    fn deposit_bonus_only_within_window() {
+        let swap = SwapV2 {
+            bonus_start_slot: 100,
+            bonus_end_slot: 200,
+            bonus_bps: 50,
+            bonus_lockup_slots: 1_000,
+            ..SwapV2::default()
+        };
+        assert_eq!(swap.deposit_bonus_bps(99, u64::MAX), 0);
+        assert_eq!(swap.deposit_bonus_bps(100, u64::MAX), 50);
+        assert_eq!(swap.deposit_bonus_bps(199, u64::MAX), 50);
+        assert_eq!(swap.deposit_bonus_bps(200, u64::MAX), 0);
+        // only on pool tokens locked for the whole lockup
+        assert_eq!(swap.deposit_bonus_bps(150, 1_149), 0);
+        assert_eq!(swap.deposit_bonus_bps(150, 1_150), 50);
+    }
+
+    #[test]
+    fn single_withdrawals_cool_down_after_the_last_single_deposit() {
+        let position = LpPosition {
+            last_single_deposit_slot: 100,
+            ..LpPosition::default()
+        };
+        assert!(position.cooled_down(0, 100));
+        assert!(!position.cooled_down(1, 100));
+        assert!(position.cooled_down(1, 101));
+        assert!(!position.cooled_down(10, 109));
+        assert!(position.cooled_down(10, 110));
+        assert!(!position.cooled_down(u64::MAX, u64::MAX - 1));
+    }
+
+    #[test]
+    fn fee_holiday_ends_at_its_end_slot() {
+        let swap = SwapV2 {
+            fee_holiday_end_slot: 200,
+            ..SwapV2::default()
+        };
+        assert!(swap.fee_holiday(0));
+        assert!(swap.fee_holiday(199));
+        assert!(!swap.fee_holiday(200));
+        // pools created without one never have a holiday
+        assert!(!SwapV2::default().fee_holiday(0));
+    }
+
+    #[test]
+    fn fee_exemption_covers_fee_account_and_list() {
+        let treasury = Pubkey::new_unique();
+        let swap = SwapV2 {
+            pool_fee_account: Pubkey::new_unique(),
+            fee_exempt: [treasury, Pubkey::default()],
+            ..SwapV2::default()
+        };
+        assert!(swap.is_fee_exempt(&swap.pool_fee_account));
+        assert!(swap.is_fee_exempt(&treasury));
+        assert!(!swap.is_fee_exempt(&Pubkey::new_unique()));
+        // an unused slot doesn't exempt the default key
+        assert!(!swap.is_fee_exempt(&Pubkey::default()));
+    }
+
+    #[test]
+    fn upgrade_authority_read_from_program_data() {
+        let authority = Pubkey::new_unique();
+        let mut program_data = vec![0u8; 45 + 16];
+        program_data[..4].copy_from_slice(&3u32.to_le_bytes());
+        program_data[4..12].copy_from_slice(&42u64.to_le_bytes());
+        assert_eq!(unpack_upgrade_authority(&program_data), Ok(None));
+        program_data[12] = 1;
+        program_data[13..45].copy_from_slice(authority.as_ref());
+        assert_eq!(unpack_upgrade_authority(&program_data), Ok(Some(authority)));
+        // a program account, not its data
+        program_data[0] = 2;
+        assert_eq!(
+            unpack_upgrade_authority(&program_data),
+            Err(ProgramError::InvalidAccountData)
+        );
+
+        let config = ProgramConfig {
+            is_initialized: true,
+            bump_seed: 255,
+            upgrade_authority: authority,
+            governance_program: Pubkey::default(),
+        };
+        assert!(config.documents(Some(authority)));
+        assert!(!config.documents(None));
+        assert!(!ProgramConfig::default().documents(None));
+    }
+
+    #[test]
+    fn governances_sign_through_their_native_treasury() {
+        let governance_program = Pubkey::new_unique();
+        let governance = Pubkey::new_unique();
+        let mut config = ProgramConfig {
+            is_initialized: true,
+            bump_seed: 255,
+            upgrade_authority: Pubkey::new_unique(),
+            governance_program: Pubkey::default(),
+        };
+        assert_eq!(
+            config.native_treasury(&governance, &governance_program),
+            None
+        );
+
+        config.governance_program = governance_program;
+        let (native_treasury, _bump_seed) = Pubkey::find_program_address(
+            &[b"native-treasury", governance.as_ref()],
+            &governance_program,
+        );
+        assert_eq!(
+            config.native_treasury(&governance, &governance_program),
+            Some(native_treasury)
+        );
+        // a wallet, or an account of another program, has no treasury
+        assert_eq!(
+            config.native_treasury(&governance, &Pubkey::new_unique()),
+            None
+        );
+
+        // a config from before governance was configurable reads as none
+        let mut data = vec![0; ProgramConfig::LEN];
+        config.pack_into_slice(&mut data);
+        data.truncate(34);
+        let outdated = ProgramConfig::unpack_outdated(&data).unwrap();
+        assert_eq!(outdated.upgrade_authority, config.upgrade_authority);
+        assert_eq!(outdated.governance_program, Pubkey::default());
+        assert!(ProgramConfig::unpack_outdated(&[0; ProgramConfig::LEN + 1]).is_err());
+    }
+
+    #[test]
+    fn oracle_price_steps_within_bounds() {
+        let config = OracleConfig {
+            min_price: 900,
+            max_price: 1_100,
+            max_step_bps: 10,
+            last_update_slot: 100,
+            ..OracleConfig::default()
+        };
+        // 0.1% of 1_000 per slot
+        assert_eq!(config.next_price(1_000, 1_050, 101), Some(1_001));
+        assert_eq!(config.next_price(1_000, 1_050, 110), Some(1_010));
+        assert_eq!(config.next_price(1_000, 950, 110), Some(990));
+        // never past the oracle price, nor outside the bounds
+        assert_eq!(config.next_price(1_000, 1_005, 200), Some(1_005));
+        assert_eq!(config.next_price(1_000, 5_000, 10_000), Some(1_100));
+        assert_eq!(config.next_price(1_000, 0, 10_000), Some(900));
+        // nothing moves within the same slot
+        assert_eq!(config.next_price(1_000, 1_050, 100), Some(1_000));
+    }
+
+    #[test]
+    fn pool_metadata_round_trips() {
+        let mut name = [0u8; MAX_NAME_LEN];
+        name[..7].copy_from_slice(b"SOL-USD");
+        let metadata = PoolMetadata {
+            is_initialized: true,
+            bump_seed: 255,
+            swap: Pubkey::new_unique(),
+            name,
+            uri: [0u8; MAX_URI_LEN],
+            tags: [1, 2, 0, 0, 0, 0, 0, 0],
+        };
+        let mut packed = [0u8; PoolMetadata::LEN];
+        PoolMetadata::pack_into_slice(&metadata, &mut packed);
+        let unpacked = PoolMetadata::unpack(&packed).unwrap();
+        assert_eq!(unpacked, metadata);
+        assert_eq!(unpacked.name(), Some("SOL-USD"));
+        assert_eq!(unpacked.uri(), Some(""));
+
+        let mut invalid = metadata;
+        invalid.name[0] = 0xff;
+        assert_eq!(invalid.name(), None);
+    }
+
+    #[test]
+    fn outdated_accounts_unpack_for_resizing() {
+        let swap_v2 = SwapV2 {
+            is_initialized: true,
+            nonce: 254,
+            pool_fee_account: Pubkey::new_unique(),
+            interactions: 7,
+            ..SwapV2::default()
+        };
+        let mut data = vec![0; SwapVersion::LATEST_LEN];
+        SwapVersion::pack(SwapVersion::SwapV2(Box::new(swap_v2)), &mut data).unwrap();
+
+        // a V2 pool from before the last fields were added
+        data.truncate(SwapVersion::LATEST_LEN - 1);
+        assert!(SwapVersion::unpack_versioned(&data).is_err());
+        match SwapVersion::unpack_outdated(&data).unwrap() {
+            SwapVersion::SwapV2(swap_v2) => {
+                assert_eq!(swap_v2.interactions, 7);
+                assert!(!swap_v2.paused());
+            }
+            SwapVersion::SwapV1(_) => panic!("read as V1"),
+        }
+        data.resize(SwapVersion::LATEST_LEN + 1, 0);
+        assert_eq!(
+            SwapVersion::unpack_outdated(&data).err(),
+            Some(ProgramError::InvalidAccountData)
+        );
+
+        let swap_v1 = SwapV1 {
+            is_initialized: true,
+            nonce: 254,
+            token_a: Pubkey::new_unique(),
+            pool_fee_account: Pubkey::new_unique(),
+            fees: Fees {
+                trade_fee_numerator: 25,
+                trade_fee_denominator: 10_000,
+                ..Fees::default()
+            },
+            ..SwapV1::default()
+        };
+        let mut data = vec![0; 1 + SwapV1::LEN];
+        data[0] = 1;
+        swap_v1.pack_into_slice(&mut data[1..]);
+        let swap_v1 = match SwapVersion::unpack_outdated(&data).unwrap() {
+            SwapVersion::SwapV1(swap_v1) => swap_v1,
+            SwapVersion::SwapV2(_) => panic!("read as V2"),
+        };
+        let (token_a, pool_fee_account) = (swap_v1.token_a, swap_v1.pool_fee_account);
+        let swap_v2 = SwapV2::from_v1(*swap_v1, 9, 6, 8);
+        assert_eq!(swap_v2.token_a, token_a);
+        assert_eq!(swap_v2.pool_fee_account, pool_fee_account);
+        assert_eq!(swap_v2.fees.trade_fee_numerator, 25);
+        assert_eq!(swap_v2.token_decimals(), Some((6, 8)));
+        assert_eq!(swap_v2.pool_mint_decimals, 9);
+        assert!(swap_v2.is_initialized && !swap_v2.emergency);
+    }
+
+    /// Reads the fields of arbitrary state off proptest's random bytes, so
+    /// every layout is filled without a strategy per field.  Past the end of
+    /// the bytes every field reads as zero.
+    struct Draw<'a>(std::slice::Iter<'a, u8>);
+
+    impl Draw<'_> {
+        fn fill(&mut self, output: &mut [u8]) {
+            for byte in output {
+                *byte = self.0.next().copied().unwrap_or(0);
+            }
+        }
+
+        fn u8(&mut self) -> u8 {
+            self.0.next().copied().unwrap_or(0)
+        }
+
+        fn bool(&mut self) -> bool {
+            self.u8() & 1 == 1
+        }
+
+        fn u16(&mut self) -> u16 {
+            let mut bytes = [0; 2];
+            self.fill(&mut bytes);
+            u16::from_le_bytes(bytes)
+        }
+
+        fn u32(&mut self) -> u32 {
+            let mut bytes = [0; 4];
+            self.fill(&mut bytes);
+            u32::from_le_bytes(bytes)
+        }
+
+        fn u64(&mut self) -> u64 {
+            let mut bytes = [0; 8];
+            self.fill(&mut bytes);
+            u64::from_le_bytes(bytes)
+        }
+
+        fn bytes32(&mut self) -> [u8; 32] {
+            let mut bytes = [0; 32];
+            self.fill(&mut bytes);
+            bytes
+        }
+
+        fn pubkey(&mut self) -> Pubkey {
+            Pubkey::new_from_array(self.bytes32())
+        }
+
+        fn fees(&mut self) -> Fees {
+            Fees {
+                trade_fee_numerator: self.u64(),
+                trade_fee_denominator: self.u64(),
+                owner_trade_fee_numerator: self.u64(),
+                owner_trade_fee_denominator: self.u64(),
+                owner_withdraw_fee_numerator: self.u64(),
+                owner_withdraw_fee_denominator: self.u64(),
+                host_fee_numerator: self.u64(),
+                host_fee_denominator: self.u64(),
+            }
+        }
+
+        fn swap_curve(&mut self) -> SwapCurve {
+            match self.u8() % 6 {
+                0 => SwapCurve {
+                    curve_type: CurveType::ConstantProduct,
+                    calculator: Box::new(ConstantProductCurve),
+                },
+                1 => SwapCurve {
+                    curve_type: CurveType::ConstantPrice,
+                    calculator: Box::new(ConstantPriceCurve {
+                        token_b_price: self.u64(),
+                        token_b_price_denominator: self.u64(),
+                    }),
+                },
+                2 => SwapCurve {
+                    curve_type: CurveType::Stable,
+                    calculator: Box::new(StableCurve { amp: self.u64() }),
+                },
+                3 => SwapCurve {
+                    curve_type: CurveType::Offset,
+                    calculator: Box::new(OffsetCurve {
+                        token_b_offset: self.u64(),
+                        buy_only: self.bool(),
+                    }),
+                },
+                4 => SwapCurve {
+                    curve_type: CurveType::Weighted,
+                    calculator: Box::new(WeightedCurve {
+                        token_a_weight: self.u64(),
+                        token_b_weight: self.u64(),
+                    }),
+                },
+                _ => SwapCurve {
+                    curve_type: CurveType::Concentrated,
+                    calculator: Box::new(ConcentratedCurve {
+                        min_token_b_price: self.u64(),
+                        min_token_b_price_denominator: self.u64(),
+                        max_token_b_price: self.u64(),
+                        max_token_b_price_denominator: self.u64(),
+                    }),
+                },
+            }
+        }
+
+        fn swap_v1(&mut self) -> SwapV1 {
+            SwapV1 {
+                is_initialized: true,
+                nonce: self.u8(),
+                token_program_id: self.pubkey(),
+                token_a: self.pubkey(),
+                token_b: self.pubkey(),
+                pool_mint: self.pubkey(),
+                token_a_mint: self.pubkey(),
+                token_b_mint: self.pubkey(),
+                pool_fee_account: self.pubkey(),
+                fees: self.fees(),
+                swap_curve: self.swap_curve(),
+            }
+        }
+
+        fn swap_v2(&mut self) -> SwapV2 {
+            SwapV2 {
+                is_initialized: true,
+                nonce: self.u8(),
+                token_program_id: self.pubkey(),
+                token_a: self.pubkey(),
+                token_b: self.pubkey(),
+                pool_mint: self.pubkey(),
+                token_a_mint: self.pubkey(),
+                token_b_mint: self.pubkey(),
+                pool_fee_account: self.pubkey(),
+                fees: self.fees(),
+                swap_curve: self.swap_curve(),
+                pool_mint_decimals: self.u8(),
+                max_slot_volume: self.u64(),
+                last_slot: self.u64(),
+                slot_volume: self.u64(),
+                locked_pool_tokens: self.u64(),
+                zero_fee: self.bool(),
+                rebalancer: self.pubkey(),
+                emergency: self.bool(),
+                bonus_start_slot: self.u64(),
+                bonus_end_slot: self.u64(),
+                bonus_bps: self.u16(),
+                fee_exempt: [self.pubkey(), self.pubkey()],
+                withdraw_fee_in_underlying: self.bool(),
+                withdraw_fee_vault_a: self.pubkey(),
+                withdraw_fee_vault_b: self.pubkey(),
+                fee_tiers: FeeTiers {
+                    tiers: [(); MAX_FEE_TIERS].map(|_| FeeTier {
+                        threshold: self.u64(),
+                        trade_fee_numerator: self.u64(),
+                        owner_trade_fee_numerator: self.u64(),
+                    }),
+                },
+                exact_input: self.bool(),
+                fee_holiday_end_slot: self.u64(),
+                stake_pool: self.pubkey(),
+                rate_update_slot: self.u64(),
+                last_trade_slot: self.u64(),
+                default_host_fee_account: self.pubkey(),
+                unclaimed_host_fees: self.u64(),
+                token_a_decimals: self.u8(),
+                token_b_decimals: self.u8(),
+                interactions: self.u64(),
+                trade_fees_a: self.u64(),
+                trade_fees_b: self.u64(),
+                withdraw_fees: self.u64(),
+                last_checkpoint_hash: self.bytes32(),
+                owner_fee_account_a: self.pubkey(),
+                owner_fee_account_b: self.pubkey(),
+                fee_conversion_pool: self.pubkey(),
+                fee_conversion_account: self.pubkey(),
+                fee_conversion_from_token_b: self.bool(),
+                withdraw_cooldown_slots: self.u64(),
+                gate: self.pubkey(),
+                gate_credential: self.bool(),
+                single_withdraw_fee_to_lps: self.bool(),
+                sandwich_guard: self.bool(),
+                owner_fee_fallback: self.bool(),
+                nested_pool: self.pubkey(),
+                bonded_keepers_only: self.bool(),
+                paused: self.bool(),
+                bonus_lockup_slots: self.u64(),
+                fee_conversion_max_slippage_bps: self.u16(),
+            }
+        }
+    }
+
+    /// Packs over a buffer of garbage and unpacks again, so a field packed
+    /// short, long or over its neighbour reads back wrong
+    fn round_trips<T: Pack + PartialEq + Debug>(state: T) -> Result<(), TestCaseError> {
+        let mut packed = vec![0xa5; T::LEN];
+        state.pack_into_slice(&mut packed);
+        prop_assert_eq!(T::unpack_unchecked(&packed)?, state);
+        Ok(())
+    }
+
+    /// Unpacking only ever errors, whatever the bytes and however many
+    fn unpacks_without_panicking<T: Pack>(bytes: &[u8]) {
+        let _ = T::unpack_unchecked(bytes);
+        let mut sized = bytes.to_vec();
+        sized.resize(T::LEN, 0);
+        let _ = T::unpack_unchecked(&sized);
+    }
+
+    proptest! {
+        #[test]
+        fn swap_versions_round_trip(bytes in vec(any::<u8>(), SwapVersion::LATEST_LEN)) {
+            let swap_v2 = Draw(bytes.iter()).swap_v2();
+            let mut packed = vec![0xa5; SwapVersion::LATEST_LEN];
+            SwapVersion::pack(SwapVersion::SwapV2(Box::new(swap_v2)), &mut packed)?;
+            match SwapVersion::unpack_versioned(&packed)? {
+                SwapVersion::SwapV2(unpacked) => {
+                    prop_assert_eq!(*unpacked, Draw(bytes.iter()).swap_v2())
+                }
+                SwapVersion::SwapV1(_) => prop_assert!(false, "read as V1"),
+            }
+
+            let swap_v1 = Draw(bytes.iter()).swap_v1();
+            let mut packed = vec![0xa5; 1 + SwapV1::LEN];
+            SwapVersion::pack(SwapVersion::SwapV1(Box::new(swap_v1)), &mut packed)?;
+            match SwapVersion::unpack_versioned(&packed)? {
+                SwapVersion::SwapV1(unpacked) => {
+                    prop_assert_eq!(*unpacked, Draw(bytes.iter()).swap_v1())
+                }
+                SwapVersion::SwapV2(_) => prop_assert!(false, "read as V2"),
+            }
+        }
+
+        #[test]
+        fn curves_and_fees_round_trip(bytes in vec(any::<u8>(), 128)) {
+            let mut draw = Draw(bytes.iter());
+            round_trips(draw.swap_curve())?;
+            round_trips(draw.fees())?;
+        }
+
+        #[test]
+        fn accounts_round_trip(bytes in vec(any::<u8>(), 1_024)) {
+            let mut draw = Draw(bytes.iter());
+            round_trips(Referrer {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                owner: draw.pubkey(),
+                host_fee_numerator: draw.u64(),
+                host_fee_denominator: draw.u64(),
+            })?;
+            round_trips(Vesting {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                vault: draw.pubkey(),
+                beneficiary: draw.pubkey(),
+                total_amount: draw.u64(),
+                claimed_amount: draw.u64(),
+                start_slot: draw.u64(),
+                duration_slots: draw.u64(),
+            })?;
+            round_trips(LiquidityLock {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                owner: draw.pubkey(),
+                vault: draw.pubkey(),
+                amount: draw.u64(),
+                unlock_slot: draw.u64(),
+            })?;
+            round_trips(LpPosition {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                pool_account: draw.pubkey(),
+                last_single_deposit_slot: draw.u64(),
+            })?;
+            round_trips(ReferredDeposits {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                referrer: draw.pubkey(),
+                deposits: draw.u64(),
+                token_a_amount: draw.u64(),
+                token_b_amount: draw.u64(),
+                pool_token_amount: draw.u64(),
+            })?;
+            round_trips(LiquiditySnapshot {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                slot: draw.u64(),
+                taken: draw.bool(),
+                taken_slot: draw.u64(),
+                pool_token_supply: draw.u64(),
+                token_a_amount: draw.u64(),
+                token_b_amount: draw.u64(),
+            })?;
+            round_trips(OracleConfig {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                oracle: draw.pubkey(),
+                price_offset: draw.u32(),
+                min_price: draw.u64(),
+                max_price: draw.u64(),
+                max_step_bps: draw.u16(),
+                last_update_slot: draw.u64(),
+            })?;
+            let mut metadata = PoolMetadata {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                swap: draw.pubkey(),
+                name: [0; MAX_NAME_LEN],
+                uri: [0; MAX_URI_LEN],
+                tags: [0; TAGS_LEN],
+            };
+            draw.fill(&mut metadata.name);
+            draw.fill(&mut metadata.uri);
+            draw.fill(&mut metadata.tags);
+            round_trips(metadata)?;
+            round_trips(ProgramConfig {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                upgrade_authority: draw.pubkey(),
+                governance_program: draw.pubkey(),
+            })?;
+            round_trips(KeeperConfig {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                authority: draw.pubkey(),
+                min_bond: draw.u64(),
+                unbonding_slots: draw.u64(),
+            })?;
+            round_trips(PoolTemplate {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                index: draw.u8(),
+                fees: draw.fees(),
+                swap_curve: draw.swap_curve(),
+            })?;
+            round_trips(KeeperBond {
+                is_initialized: draw.bool(),
+                bump_seed: draw.u8(),
+                keeper: draw.pubkey(),
+                bond: draw.u64(),
+                unbonding_slot: draw.u64(),
+                cranks: draw.u64(),
+                last_crank_swap: draw.pubkey(),
+                last_crank_slot: draw.u64(),
+                last_crank_oracle_price: draw.u64(),
+            })?;
+            round_trips(AdminMultisig {
+                is_initialized: draw.bool(),
+                m: draw.u8(),
+                n: draw.u8(),
+                signers: [(); MAX_ADMIN_SIGNERS].map(|_| draw.pubkey()),
+            })?;
+        }
+
+        #[test]
+        fn return_data_round_trips(bytes in vec(any::<u8>(), 256)) {
+            let mut draw = Draw(bytes.iter());
+            round_trips(PoolStateView {
+                swap: draw.pubkey(),
+                token_a_amount: draw.u64(),
+                token_b_amount: draw.u64(),
+                pool_token_supply: draw.u64(),
+                pool_mint_decimals: draw.u8(),
+                token_a_decimals: draw.u8(),
+                token_b_decimals: draw.u8(),
+                locked_pool_tokens: draw.u64(),
+                fees: draw.fees(),
+                capabilities: CurveCapabilities::from_byte(draw.u8()),
+            })?;
+            round_trips(PoolHealth {
+                swap: draw.pubkey(),
+                imbalance_ratio_bps: draw.u64(),
+                token_a_depletion_bps: draw.u16(),
+                token_b_depletion_bps: draw.u16(),
+                last_trade_slot: draw.u64(),
+                slots_since_last_trade: draw.u64(),
+                emergency: draw.bool(),
+                deposits_disabled: draw.bool(),
+                paused: draw.bool(),
+            })?;
+            round_trips(SwapQuote {
+                source_amount_swapped: draw.u64(),
+                destination_amount_swapped: draw.u64(),
+                trade_fee: draw.u64(),
+                owner_fee: draw.u64(),
+            })?;
+        }
+
+        #[test]
+        fn random_bytes_never_panic_on_unpack(
+            bytes in vec(any::<u8>(), 0..SwapVersion::LATEST_LEN + 2),
+        ) {
+            let _ = SwapVersion::unpack(&bytes);
+            let _ = SwapVersion::unpack_versioned(&bytes);
+            let _ = SwapVersion::unpack_outdated(&bytes);
+            let mut versioned = bytes.clone();
+            versioned.resize(SwapVersion::LATEST_LEN, 0);
+            let _ = SwapVersion::unpack_versioned(&versioned);
+            let _ = SwapVersion::unpack_outdated(&versioned);
+
+            unpacks_without_panicking::<SwapV1>(&bytes);
+            unpacks_without_panicking::<SwapV2>(&bytes);
+            unpacks_without_panicking::<SwapCurve>(&bytes);
+            unpacks_without_panicking::<Fees>(&bytes);
+            unpacks_without_panicking::<FeeTiers>(&bytes);
+            unpacks_without_panicking::<Referrer>(&bytes);
+            unpacks_without_panicking::<ReferredDeposits>(&bytes);
+            unpacks_without_panicking::<Vesting>(&bytes);
+            unpacks_without_panicking::<LiquidityLock>(&bytes);
+            unpacks_without_panicking::<LpPosition>(&bytes);
+            unpacks_without_panicking::<LiquiditySnapshot>(&bytes);
+            unpacks_without_panicking::<OracleConfig>(&bytes);
+            unpacks_without_panicking::<PoolMetadata>(&bytes);
+            unpacks_without_panicking::<ProgramConfig>(&bytes);
+            unpacks_without_panicking::<KeeperConfig>(&bytes);
+            unpacks_without_panicking::<PoolTemplate>(&bytes);
+            unpacks_without_panicking::<KeeperBond>(&bytes);
+            unpacks_without_panicking::<AdminMultisig>(&bytes);
+            unpacks_without_panicking::<PoolStateView>(&bytes);
+            unpacks_without_panicking::<PoolHealth>(&bytes);
+            unpacks_without_panicking::<SwapQuote>(&bytes);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Token program CPIs signed by the swap authority.  The instructions come
+//! from the `TokenInterface` of the pool's token program, so processors move
+//! tokens through `transfer`, `mint_to`, `burn` and `close_account` without
+//! branching on which token program a pool uses, and supporting another one
+//! only takes an implementation here.
+
+use solana_program::{
+    account_info::AccountInfo,
+    entrypoint::ProgramResult,
+    instruction::{AccountMeta, Instruction},
+    program::invoke_signed,
+    program_error::ProgramError,
+    pubkey::Pubkey,
+};
+use spl_token::instruction::TokenInstruction;
+
+/// Program id of spl-token-2022
+pub mod spl_token_2022 {
+    solana_program::declare_id!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+}
+
+/// Instructions of a token program the swap authority signs for
+pub trait TokenInterface {
+    /// Id of the token program
+    fn program_id(&self) -> Pubkey;
+
+    fn transfer(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError>;
+
+    fn mint_to(
+        &self,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError>;
+
+    fn burn(
+        &self,
+        account: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError>;
+
+    fn close_account(
+        &self,
+        account: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+    ) -> Result<Instruction, ProgramError>;
+}
+
+/// The original token program
+pub struct SplToken;
+
+impl TokenInterface for SplToken {
+    fn program_id(&self) -> Pubkey {
+        spl_token::id()
+    }
+
+    fn transfer(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        spl_token::instruction::transfer(
+            &spl_token::id(),
+            source,
+            destination,
+            authority,
+            &[],
+            amount,
+        )
+    }
+
+    fn mint_to(
+        &self,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        spl_token::instruction::mint_to(&spl_token::id(), mint, destination, authority, &[], amount)
+    }
+
+    fn burn(
+        &self,
+        account: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        spl_token::instruction::burn(&spl_token::id(), account, mint, authority, &[], amount)
+    }
+
+    fn close_account(
+        &self,
+        account: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        spl_token::instruction::close_account(
+            &spl_token::id(),
+            account,
+            destination,
+            authority,
+            &[],
+        )
+    }
+}
+
+/// spl-token-2022, whose base instructions are encoded like spl-token's.
+/// Mints with a transfer fee refuse a plain transfer, so pools of them aren't
+/// supported yet.
+pub struct SplToken2022;
+
+impl SplToken2022 {
+    fn instruction(&self, data: TokenInstruction, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction {
+            program_id: spl_token_2022::id(),
+            accounts,
+            data: data.pack(),
+        }
+    }
+}
+
+impl TokenInterface for SplToken2022 {
+    fn program_id(&self) -> Pubkey {
+        spl_token_2022::id()
+    }
+
+    fn transfer(
+        &self,
+        source: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(self.instruction(
+            TokenInstruction::Transfer { amount },
+            vec![
+                AccountMeta::new(*source, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+        ))
+    }
+
+    fn mint_to(
+        &self,
+        mint: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(self.instruction(
+            TokenInstruction::MintTo { amount },
+            vec![
+                AccountMeta::new(*mint, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+        ))
+    }
+
+    fn burn(
+        &self,
+        account: &Pubkey,
+        mint: &Pubkey,
+        authority: &Pubkey,
+        amount: u64,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(self.instruction(
+            TokenInstruction::Burn { amount },
+            vec![
+                AccountMeta::new(*account, false),
+                AccountMeta::new(*mint, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+        ))
+    }
+
+    fn close_account(
+        &self,
+        account: &Pubkey,
+        destination: &Pubkey,
+        authority: &Pubkey,
+    ) -> Result<Instruction, ProgramError> {
+        Ok(self.instruction(
+            TokenInstruction::CloseAccount,
+            vec![
+                AccountMeta::new(*account, false),
+                AccountMeta::new(*destination, false),
+                AccountMeta::new_readonly(*authority, true),
+            ],
+        ))
+    }
+}
+
+/// Interface of the given token program, failing for a program that isn't a
+/// token program
+pub fn token_interface(
+    token_program_id: &Pubkey,
+) -> Result<&'static dyn TokenInterface, ProgramError> {
+    if *token_program_id == spl_token::id() {
+        Ok(&SplToken)
+    } else if *token_program_id == spl_token_2022::id() {
+        Ok(&SplToken2022)
+    } else {
+        Err(ProgramError::IncorrectProgramId)
+    }
+}
+
+// invokes the token program, signing as the swap authority
+fn invoke_as_authority(
+    swap: &Pubkey,
+    nonce: u8,
+    ix: &Instruction,
+    account_infos: &[AccountInfo],
+) -> ProgramResult {
+    let swap_bytes = swap.to_bytes();
+    let authority_signature_seeds = [&swap_bytes[..32], &[nonce]];
+    let signers = &[&authority_signature_seeds[..]];
+    invoke_signed(ix, account_infos, signers)
+}
+
+pub fn mint_to<'a>(
+    swap: &Pubkey,
+    token_program: AccountInfo<'a>,
+    mint: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    nonce: u8,
+    amount: u64,
+) -> ProgramResult {
+    let ix = token_interface(token_program.key)?.mint_to(
+        mint.key,
+        destination.key,
+        authority.key,
+        amount,
+    )?;
+    invoke_as_authority(
+        swap,
+        nonce,
+        &ix,
+        &[mint, destination, authority, token_program],
+    )
+}
+
+pub fn transfer<'a>(
+    swap: &Pubkey,
+    token_program: AccountInfo<'a>,
+    source: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    nonce: u8,
+    amount: u64,
+) -> ProgramResult {
+    let ix = token_interface(token_program.key)?.transfer(
+        source.key,
+        destination.key,
+        authority.key,
+        amount,
+    )?;
+    invoke_as_authority(
+        swap,
+        nonce,
+        &ix,
+        &[source, destination, authority, token_program],
+    )
+}
+
+pub fn burn<'a>(
+    swap: &Pubkey,
+    token_program: AccountInfo<'a>,
+    burn_account: AccountInfo<'a>,
+    mint: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    nonce: u8,
+    amount: u64,
+) -> ProgramResult {
+    let ix = token_interface(token_program.key)?.burn(
+        burn_account.key,
+        mint.key,
+        authority.key,
+        amount,
+    )?;
+    invoke_as_authority(
+        swap,
+        nonce,
+        &ix,
+        &[burn_account, mint, authority, token_program],
+    )
+}
+
+// closes an account owned by the swap authority, e.g. a temporary WSOL vault,
+// unwrapping its lamports into the destination. Token accounts must be empty,
+// native ones excepted
+pub fn close_account<'a>(
+    swap: &Pubkey,
+    token_program: AccountInfo<'a>,
+    account: AccountInfo<'a>,
+    destination: AccountInfo<'a>,
+    authority: AccountInfo<'a>,
+    nonce: u8,
+) -> ProgramResult {
+    let ix = token_interface(token_program.key)?.close_account(
+        account.key,
+        destination.key,
+        authority.key,
+    )?;
+    invoke_as_authority(
+        swap,
+        nonce,
+        &ix,
+        &[account, destination, authority, token_program],
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_2022_instructions_are_encoded_like_spl_token() {
+        let (source, destination, authority) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let legacy = token_interface(&spl_token::id())
+            .unwrap()
+            .transfer(&source, &destination, &authority, 42)
+            .unwrap();
+        let token_2022 = token_interface(&spl_token_2022::id())
+            .unwrap()
+            .transfer(&source, &destination, &authority, 42)
+            .unwrap();
+        assert_eq!(legacy.program_id, spl_token::id());
+        assert_eq!(token_2022.program_id, spl_token_2022::id());
+        assert_eq!(legacy.accounts, token_2022.accounts);
+        assert_eq!(legacy.data, token_2022.data);
+
+        let legacy = SplToken
+            .close_account(&source, &destination, &authority)
+            .unwrap();
+        let token_2022 = SplToken2022
+            .close_account(&source, &destination, &authority)
+            .unwrap();
+        assert_eq!(legacy.accounts, token_2022.accounts);
+        assert_eq!(legacy.data, token_2022.data);
+    }
+
+    #[test]
+    fn other_programs_have_no_token_interface() {
+        assert_eq!(
+            token_interface(&Pubkey::new_unique()).err(),
+            Some(ProgramError::IncorrectProgramId)
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Kani proof harnesses for the curves' integer rounding, compiled only
+//! under `cargo kani`, which sets `cfg(kani)`.
+//!
+//! Kani checks every harness for all inputs within its bounds, including
+//! that nothing panics or overflows along the way.  Amounts are bounded to
+//! 32 bits to keep the solver's u128 multiplications and divisions
+//! tractable; none of the properties depend on magnitude, and the
+//! proptests in `curve` cover the full range by sampling.
+//!
+//!     cargo kani --harness swap_rounds_in_the_pools_favor
+
+use crate::curve::{
+    base::{CurveType, SwapCurve},
+    calculator::{CurveCalculator, RoundDirection, TradeDirection},
+    constant_price::ConstantPriceCurve,
+    constant_product::ConstantProductCurve,
+    fees::{FeeTiers, Fees},
+};
+
+/// Any amount up to `u32::MAX`
+fn any_amount() -> u128 {
+    u128::from(kani::any::<u32>())
+}
+
+/// Any amount in `1..=u32::MAX`, reserves and supplies of a live pool
+fn any_nonzero_amount() -> u128 {
+    let amount = any_amount();
+    kani::assume(amount > 0);
+    amount
+}
+
+fn any_trade_direction() -> TradeDirection {
+    if kani::any() {
+        TradeDirection::AtoB
+    } else {
+        TradeDirection::BtoA
+    }
+}
+
+fn any_round_direction() -> RoundDirection {
+    if kani::any() {
+        RoundDirection::Floor
+    } else {
+        RoundDirection::Ceiling
+    }
+}
+
+// ----------------------------------------------------------------------------- swap
+
+#[kani::proof]
+fn swap_never_panics() {
+    let curve = if kani::any() {
+        SwapCurve::default()
+    } else {
+        let token_b_price = kani::any::<u32>();
+        kani::assume(token_b_price > 0);
+        SwapCurve {
+            curve_type: CurveType::ConstantPrice,
+            calculator: Box::new(ConstantPriceCurve {
+                token_b_price: u64::from(token_b_price),
+                token_b_price_denominator: 0,
+            }),
+        }
+    };
+    let _ = curve.swap(
+        any_amount(),
+        any_nonzero_amount(),
+        any_nonzero_amount(),
+        any_trade_direction(),
+        &Fees::standard_30bps(),
+        &FeeTiers::default(),
+    );
+}
+
+/// The constant product never lets the product of the reserves shrink, nor
+/// pays out more than the exact `y * dx / (x + dx)` for the input after fees
+#[kani::proof]
+fn swap_rounds_in_the_pools_favor() {
+    let (source_amount, swap_source_amount, swap_destination_amount) =
+        (any_amount(), any_nonzero_amount(), any_nonzero_amount());
+    let result = match SwapCurve::default().swap(
+        source_amount,
+        swap_source_amount,
+        swap_destination_amount,
+        TradeDirection::AtoB,
+        &Fees::standard_30bps(),
+        &FeeTiers::default(),
+    ) {
+        Some(result) => result,
+        None => return,
+    };
+
+    assert!(result.source_amount_swapped <= source_amount);
+    assert!(
+        result.new_swap_source_amount * result.new_swap_destination_amount
+            >= swap_source_amount * swap_destination_amount
+    );
+    let source_amount_less_fees =
+        result.source_amount_swapped - result.trade_fee - result.owner_fee;
+    assert!(
+        result.destination_amount_swapped * (swap_source_amount + source_amount_less_fees)
+            <= swap_destination_amount * source_amount_less_fees
+    );
+}
+
+/// Paying more never gets less out
+#[kani::proof]
+fn swap_is_monotonic_in_input() {
+    let (smaller, larger) = (any_amount(), any_amount());
+    kani::assume(smaller <= larger);
+    let (swap_source_amount, swap_destination_amount) =
+        (any_nonzero_amount(), any_nonzero_amount());
+    let swap = |source_amount| {
+        ConstantProductCurve.swap_without_fees(
+            source_amount,
+            swap_source_amount,
+            swap_destination_amount,
+            TradeDirection::AtoB,
+        )
+    };
+    if let (Some(smaller), Some(larger)) = (swap(smaller), swap(larger)) {
+        assert!(smaller.destination_amount_swapped <= larger.destination_amount_swapped);
+    }
+}
+
+// ----------------------------------------------------------------------------- pool tokens
+
+/// Withdrawals, rounding down, never pay out more than the pool tokens'
+/// exact share of the reserves, and deposits, rounding up, never take less.
+/// A deposit worth less than one token rounds to zero instead, for the
+/// processor to reject.
+#[kani::proof]
+fn pool_tokens_to_trading_tokens_rounds_in_the_pools_favor() {
+    let pool_token_supply = any_nonzero_amount();
+    let pool_tokens = any_amount();
+    kani::assume(pool_tokens <= pool_token_supply);
+    let (swap_token_a_amount, swap_token_b_amount) = (any_amount(), any_amount());
+    let round_direction = any_round_direction();
+
+    let result = match ConstantProductCurve.pool_tokens_to_trading_tokens(
+        pool_tokens,
+        pool_token_supply,
+        swap_token_a_amount,
+        swap_token_b_amount,
+        round_direction,
+    ) {
+        Some(result) => result,
+        None => return,
+    };
+
+    for (amount, reserve) in [
+        (result.token_a_amount, swap_token_a_amount),
+        (result.token_b_amount, swap_token_b_amount),
+    ] {
+        let exact = pool_tokens * reserve;
+        match round_direction {
+            RoundDirection::Floor => {
+                assert!(amount * pool_token_supply <= exact);
+                assert!(exact < (amount + 1) * pool_token_supply);
+            }
+            RoundDirection::Ceiling => {
+                assert!(amount * pool_token_supply >= exact || exact < pool_token_supply);
+                assert!(exact + pool_token_supply > amount * pool_token_supply);
+            }
+        }
+        assert!(amount <= reserve);
+    }
+}
+
+/// More pool tokens are never worth less
+#[kani::proof]
+fn pool_tokens_to_trading_tokens_is_monotonic() {
+    let pool_token_supply = any_nonzero_amount();
+    let (smaller, larger) = (any_amount(), any_amount());
+    kani::assume(smaller <= larger && larger <= pool_token_supply);
+    let (swap_token_a_amount, swap_token_b_amount) = (any_amount(), any_amount());
+    let round_direction = any_round_direction();
+    let value = |pool_tokens| {
+        ConstantProductCurve.pool_tokens_to_trading_tokens(
+            pool_tokens,
+            pool_token_supply,
+            swap_token_a_amount,
+            swap_token_b_amount,
+            round_direction,
+        )
+    };
+    if let (Some(smaller), Some(larger)) = (value(smaller), value(larger)) {
+        assert!(smaller.token_a_amount <= larger.token_a_amount);
+        assert!(smaller.token_b_amount <= larger.token_b_amount);
+    }
+}
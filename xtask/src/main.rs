@@ -0,0 +1,365 @@
+//! Workspace tasks, run with `cargo xtask <task>`
+
+mod scenarios;
+mod typescript;
+
+use rebuild_token_swap::{
+    constants::{
+        MAX_ADMIN_SIGNERS, MAX_FEE_EXEMPT, MAX_FEE_TIERS, MAX_NAME_LEN, MAX_URI_LEN, TAGS_LEN,
+    },
+    curve::{
+        base::SwapCurve,
+        fees::{FeeTiers, Fees},
+    },
+    instruction::SwapInstruction,
+};
+use serde_json::{json, Value};
+use solana_program::program_pack::Pack;
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command},
+};
+use syn::{Attribute, Expr, Fields, GenericArgument, Item, Lit, Meta, PathArguments, Type};
+
+const USAGE: &str = "usage: cargo xtask gen-layouts [--out <path>]
+       cargo xtask gen-ts [--out <path>]
+       cargo xtask scenarios [--scenario <path>] [--ledger <dir>] [--no-wait]
+       cargo xtask check-features";
+
+/// Feature sets of the program crate that have to build on their own, each
+/// checked without the default features
+const FEATURE_SETS: &[&str] = &[
+    "",
+    "program",
+    "program,no-entrypoint",
+    "program,production",
+    "client",
+    "client,program",
+    "fuzz",
+];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let result = match args.first().map(String::as_str) {
+        Some("gen-layouts") => gen_layouts(&args[1..]),
+        Some("gen-ts") => typescript::gen_ts(&args[1..]),
+        Some("scenarios") => scenarios::scenarios(&workspace_root(), &args[1..]),
+        Some("check-features") => check_features(&workspace_root()),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        process::exit(1);
+    }
+}
+
+fn workspace_root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .parent()
+        .unwrap()
+        .to_path_buf()
+}
+
+/// Checks every feature set of the program crate, then the cli and keeper
+/// with theirs.  The `python` feature is left out, it needs an interpreter to
+/// link against.
+fn check_features(root: &Path) -> Result<(), String> {
+    for features in FEATURE_SETS {
+        println!("checking rebuild-token-swap with [{}]", features);
+        scenarios::run(Command::new("cargo").current_dir(root).args([
+            "check",
+            "--package",
+            "rebuild-token-swap",
+            "--no-default-features",
+            "--features",
+            features,
+        ]))?;
+    }
+    for (package, feature) in [
+        ("rebuild-balancer-cli", "cli"),
+        ("rebuild-balancer-keeper", "keeper"),
+    ] {
+        println!("checking {} with [{}]", package, feature);
+        scenarios::run(Command::new("cargo").current_dir(root).args([
+            "check",
+            "--package",
+            package,
+            "--features",
+            feature,
+        ]))?;
+    }
+    Ok(())
+}
+
+/// Writes the byte layout and account order of every `SwapInstruction`,
+/// read from the doc comments and data structs in `src/instruction.rs`, as
+/// JSON for the client SDKs.
+fn gen_layouts(args: &[String]) -> Result<(), String> {
+    let root = workspace_root();
+    let out = match args {
+        [] => root
+            .join("target")
+            .join("layouts")
+            .join("instructions.json"),
+        [flag, path] if flag == "--out" => PathBuf::from(path),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let source_path = root.join("src").join("instruction.rs");
+    let source = fs::read_to_string(&source_path)
+        .map_err(|err| format!("reading {}: {}", source_path.display(), err))?;
+    let file =
+        syn::parse_file(&source).map_err(|err| format!("parsing instruction.rs: {}", err))?;
+
+    let mut structs = HashMap::new();
+    let mut instructions = None;
+    for item in &file.items {
+        match item {
+            Item::Struct(item) => {
+                structs.insert(item.ident.to_string(), item);
+            }
+            Item::Enum(item) if item.ident == "SwapInstruction" => instructions = Some(item),
+            _ => {}
+        }
+    }
+    let instructions = instructions.ok_or("SwapInstruction not found")?;
+
+    let mut layouts = vec![];
+    for (tag, variant) in instructions.variants.iter().enumerate() {
+        let name = variant.ident.to_string();
+        let fields = match &variant.fields {
+            Fields::Unit => vec![],
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let data = type_name(&fields.unnamed[0].ty);
+                let item = structs
+                    .get(&data)
+                    .ok_or_else(|| format!("{}: data struct {} not found", name, data))?;
+                data_fields(&name, item)?
+            }
+            _ => return Err(format!("{}: expected a single data struct", name)),
+        };
+        // a trailing variable-length field counts for nothing, data_len is
+        // then the minimum length
+        let data_len = 1 + fields.iter().filter_map(|f| f.size).sum::<usize>();
+        check_layout(&name, tag, data_len)?;
+
+        let mut offset = 1;
+        let data: Vec<Value> = fields
+            .iter()
+            .map(|field| {
+                let value = json!({
+                    "name": field.name,
+                    "type": field.ty,
+                    "offset": offset,
+                    "size": field.size,
+                    "docs": field.docs,
+                });
+                offset += field.size.unwrap_or(0);
+                value
+            })
+            .collect();
+        let (docs, accounts) = parse_accounts(&doc_lines(&variant.attrs));
+        layouts.push(json!({
+            "name": name,
+            "tag": tag,
+            "docs": docs,
+            "data_len": data_len,
+            "data": data,
+            "accounts": accounts,
+        }));
+    }
+
+    let layouts = json!({
+        "program": "rebuild-token-swap",
+        "instructions": layouts,
+    });
+    if let Some(dir) = out.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("creating {}: {}", dir.display(), err))?;
+    }
+    let json = serde_json::to_string_pretty(&layouts).unwrap() + "\n";
+    fs::write(&out, json).map_err(|err| format!("writing {}: {}", out.display(), err))?;
+    println!(
+        "wrote {} instruction layouts to {}",
+        instructions.variants.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+struct DataField {
+    name: String,
+    ty: String,
+    /// `None` for the trailing bytes of the instruction
+    size: Option<usize>,
+    docs: String,
+}
+
+fn data_fields(instruction: &str, item: &syn::ItemStruct) -> Result<Vec<DataField>, String> {
+    let last = item.fields.len().saturating_sub(1);
+    item.fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let name = field.ident.as_ref().unwrap().to_string();
+            let ty = type_name(&field.ty);
+            let size = match type_size(&field.ty) {
+                Some(size) => Some(size),
+                None if ty == "Vec<u8>" && i == last => None,
+                None => return Err(format!("{}.{}: unknown size of {}", instruction, name, ty)),
+            };
+            Ok(DataField {
+                name,
+                ty,
+                size,
+                docs: doc_lines(&field.attrs).join(" "),
+            })
+        })
+        .collect()
+}
+
+/// Makes sure the program agrees with the computed layout, so a hand-written
+/// `pack` that drifts from its data struct fails the generator
+fn check_layout(name: &str, tag: usize, data_len: usize) -> Result<(), String> {
+    let mut data = vec![0u8; data_len];
+    data[0] = tag as u8;
+    let instruction = SwapInstruction::unpack(&data)
+        .map_err(|err| format!("{}: unpacking {} bytes: {}", name, data_len, err))?;
+    let unpacked = format!("{:?}", instruction);
+    if !unpacked.starts_with(name) || unpacked[name.len()..].starts_with(char::is_alphanumeric) {
+        return Err(format!("{}: tag {} unpacks as {}", name, tag, unpacked));
+    }
+    let packed = instruction.pack().len();
+    if packed != data_len {
+        return Err(format!(
+            "{}: packs to {} bytes, layout has {}",
+            name, packed, data_len
+        ));
+    }
+    Ok(())
+}
+
+fn type_name(ty: &Type) -> String {
+    match ty {
+        Type::Path(path) => {
+            let segment = path.path.segments.last().unwrap();
+            match &segment.arguments {
+                PathArguments::AngleBracketed(args) => {
+                    let args: Vec<String> = args
+                        .args
+                        .iter()
+                        .map(|arg| match arg {
+                            GenericArgument::Type(ty) => type_name(ty),
+                            _ => String::new(),
+                        })
+                        .collect();
+                    format!("{}<{}>", segment.ident, args.join(", "))
+                }
+                _ => segment.ident.to_string(),
+            }
+        }
+        Type::Array(array) => format!(
+            "[{}; {}]",
+            type_name(&array.elem),
+            array_len(&array.len).unwrap_or(0)
+        ),
+        _ => String::new(),
+    }
+}
+
+fn type_size(ty: &Type) -> Option<usize> {
+    match ty {
+        Type::Array(array) => type_size(&array.elem)?.checked_mul(array_len(&array.len)?),
+        _ => match type_name(ty).as_str() {
+            "u8" | "bool" => Some(1),
+            "u16" => Some(2),
+            "u32" => Some(4),
+            "u64" => Some(8),
+            "Fees" => Some(Fees::LEN),
+            "FeeTiers" => Some(FeeTiers::LEN),
+            "SwapCurve" => Some(SwapCurve::LEN),
+            "Pubkey" => Some(32),
+            _ => None,
+        },
+    }
+}
+
+/// Length of an array field, a literal or one of the program's constants
+fn array_len(len: &Expr) -> Option<usize> {
+    match len {
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Int(int) => int.base10_parse().ok(),
+            _ => None,
+        },
+        Expr::Path(path) => match path.path.segments.last()?.ident.to_string().as_str() {
+            "MAX_NAME_LEN" => Some(MAX_NAME_LEN),
+            "MAX_URI_LEN" => Some(MAX_URI_LEN),
+            "TAGS_LEN" => Some(TAGS_LEN),
+            "MAX_FEE_EXEMPT" => Some(MAX_FEE_EXEMPT),
+            "MAX_FEE_TIERS" => Some(MAX_FEE_TIERS),
+            "MAX_ADMIN_SIGNERS" => Some(MAX_ADMIN_SIGNERS),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn doc_lines(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(meta)) if meta.path.is_ident("doc") => match meta.lit {
+                Lit::Str(doc) => Some(doc.value().trim().to_string()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect()
+}
+
+/// Splits the doc comment of an instruction into its prose and the numbered
+/// account list, e.g. ``3. `[writable, signer]` Payer``.  Unnumbered lines
+/// directly below an account continue its description.
+fn parse_accounts(lines: &[String]) -> (String, Vec<Value>) {
+    let mut docs = vec![];
+    let mut accounts: Vec<Value> = vec![];
+    let mut in_account = false;
+    for line in lines {
+        let digits = line.chars().take_while(char::is_ascii_digit).count();
+        if digits == 0 {
+            match accounts.last_mut() {
+                Some(account) if in_account && !line.is_empty() => {
+                    let description = account["description"].as_str().unwrap();
+                    account["description"] = json!(format!("{} {}", description, line));
+                }
+                _ => {
+                    in_account = false;
+                    docs.push(line.as_str());
+                }
+            }
+            continue;
+        }
+
+        in_account = true;
+        let index: usize = line[..digits].parse().unwrap();
+        let rest = line[digits..].trim_start_matches('.').trim();
+        let (flags, description) = match (rest.find('['), rest.find(']')) {
+            (Some(open), Some(close)) if open < 3 && open < close => (
+                &rest[open + 1..close],
+                rest[close + 1..].trim_start_matches('`').trim(),
+            ),
+            _ => ("", rest),
+        };
+        let flags: Vec<&str> = flags.split(',').map(str::trim).collect();
+        accounts.push(json!({
+            "index": index,
+            "writable": flags.contains(&"writable"),
+            "signer": flags.contains(&"signer"),
+            "optional": flags.contains(&"optional"),
+            "description": description,
+        }));
+    }
+    let docs = docs.join("\n").trim().replace("\n\n\n", "\n\n");
+    (docs, accounts)
+}
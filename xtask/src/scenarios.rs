@@ -0,0 +1,470 @@
+//! `cargo xtask scenarios`: a local validator with the program deployed and
+//! pools seeded and traded as a scenario file describes, for frontends and
+//! indexers to develop against.
+//!
+//! Drives the Solana tool suite (`solana-test-validator`, `solana`,
+//! `spl-token`, `cargo build-bpf`) and `rebuild-balancer-cli`, so everything
+//! on-chain goes through the same instructions as production.  Amounts in the
+//! scenario file are whole tokens.  The addresses of everything created are
+//! written to `environment.json` in the scenario's ledger directory.
+
+use serde_json::{json, Map, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+const USAGE: &str = "usage: cargo xtask scenarios [--scenario <path>] [--ledger <dir>] [--no-wait]";
+
+const RPC_URL: &str = "http://127.0.0.1:8899";
+
+/// Lamports airdropped to the scenario's wallet, for rent and fees
+const AIRDROP_SOL: u64 = 100;
+
+struct Scenario {
+    mints: Vec<MintSpec>,
+    pools: Vec<PoolSpec>,
+    trades: Vec<TradeSpec>,
+}
+
+struct MintSpec {
+    name: String,
+    decimals: u32,
+    /// Whole tokens minted to the wallet
+    supply: u64,
+}
+
+struct PoolSpec {
+    name: String,
+    mint_a: String,
+    mint_b: String,
+    amount_a: u64,
+    amount_b: u64,
+    /// Constant price pools, constant product otherwise
+    token_b_price: Option<u64>,
+    /// The cli's default fees otherwise
+    trade_fee_bps: Option<u64>,
+}
+
+struct TradeSpec {
+    pool: String,
+    /// Mint sold into the pool
+    from: String,
+    amount_in: u64,
+    repeat: u64,
+}
+
+struct Mint {
+    address: String,
+    decimals: u32,
+    /// The wallet's associated token account
+    account: String,
+}
+
+pub fn scenarios(root: &Path, args: &[String]) -> Result<(), String> {
+    let mut scenario = root.join("xtask").join("scenarios").join("default.json");
+    let mut ledger = root.join("target").join("scenarios");
+    let mut wait = true;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--scenario" => scenario = PathBuf::from(args.next().ok_or(USAGE)?),
+            "--ledger" => ledger = PathBuf::from(args.next().ok_or(USAGE)?),
+            "--no-wait" => wait = false,
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    let spec = fs::read_to_string(&scenario)
+        .map_err(|err| format!("reading {}: {}", scenario.display(), err))?;
+    let spec: Value = serde_json::from_str(&spec)
+        .map_err(|err| format!("parsing {}: {}", scenario.display(), err))?;
+    let Scenario {
+        mints,
+        pools,
+        trades,
+    } = parse_scenario(&spec)?;
+
+    // a fresh ledger each run, so the environment only depends on the scenario
+    if ledger.exists() {
+        fs::remove_dir_all(&ledger)
+            .map_err(|err| format!("clearing {}: {}", ledger.display(), err))?;
+    }
+    fs::create_dir_all(&ledger).map_err(|err| format!("creating {}: {}", ledger.display(), err))?;
+    let wallet = ledger.join("wallet.json");
+    let program_keypair = ledger.join("program.json");
+    for keypair in [&wallet, &program_keypair] {
+        run(Command::new("solana-keygen")
+            .args(["new", "--no-bip39-passphrase", "--silent", "--outfile"])
+            .arg(keypair))?;
+    }
+
+    println!("building the program and the cli");
+    run(Command::new("cargo").current_dir(root).arg("build-bpf"))?;
+    run(Command::new("cargo").current_dir(root).args([
+        "build",
+        "--package",
+        "rebuild-balancer-cli",
+        "--features",
+        "cli",
+    ]))?;
+    let cli = root
+        .join("target")
+        .join("debug")
+        .join("rebuild-balancer-cli");
+
+    println!("starting solana-test-validator in {}", ledger.display());
+    let mut validator = Validator::start(&ledger.join("ledger"))?;
+
+    let solana = |args: &[&str]| {
+        let mut command = Command::new("solana");
+        command
+            .args(args)
+            .args(["--url", RPC_URL, "--keypair"])
+            .arg(&wallet);
+        output(&mut command)
+    };
+    solana(&["airdrop", &AIRDROP_SOL.to_string()])?;
+    let deployed = solana(&[
+        "program",
+        "deploy",
+        "--program-id",
+        &program_keypair.to_string_lossy(),
+        &root
+            .join("target")
+            .join("deploy")
+            .join("rebuild_token_swap.so")
+            .to_string_lossy(),
+    ])?;
+    let program_id = deployed
+        .lines()
+        .find_map(|line| line.strip_prefix("Program Id: "))
+        .ok_or_else(|| format!("no program id in: {}", deployed))?
+        .trim()
+        .to_string();
+    println!("program {}", program_id);
+
+    let spl_token = |args: &[&str]| {
+        let mut command = Command::new("spl-token");
+        command
+            .args(args)
+            .args(["--url", RPC_URL, "--fee-payer"])
+            .arg(&wallet)
+            .arg("--owner")
+            .arg(&wallet);
+        output(&mut command)
+    };
+    let mut created_mints = HashMap::new();
+    for mint in &mints {
+        let created: Value = serde_json::from_str(&spl_token(&[
+            "create-token",
+            "--decimals",
+            &mint.decimals.to_string(),
+            "--output",
+            "json",
+        ])?)
+        .map_err(|err| format!("create-token output: {}", err))?;
+        let address = created["commandOutput"]["address"]
+            .as_str()
+            .ok_or("create-token printed no address")?
+            .to_string();
+        spl_token(&["create-account", &address])?;
+        spl_token(&["mint", &address, &mint.supply.to_string()])?;
+        let account: Value = serde_json::from_str(&spl_token(&[
+            "address", "--token", &address, "--output", "json",
+        ])?)
+        .map_err(|err| format!("address output: {}", err))?;
+        let account = account["associatedTokenAddress"]
+            .as_str()
+            .ok_or("spl-token address printed no account")?
+            .to_string();
+        println!("mint {} {}", mint.name, address);
+        created_mints.insert(
+            mint.name.clone(),
+            Mint {
+                address,
+                decimals: mint.decimals,
+                account,
+            },
+        );
+    }
+
+    let balancer = |args: &[&str]| {
+        let mut command = Command::new(&cli);
+        command
+            .args(["--url", RPC_URL, "--program-id", &program_id, "--keypair"])
+            .arg(&wallet)
+            .args(args);
+        output(&mut command)
+    };
+    let mut created_pools = Map::new();
+    for pool in &pools {
+        let (mint_a, mint_b) = (&created_mints[&pool.mint_a], &created_mints[&pool.mint_b]);
+        let amount_a = raw_amount(pool.amount_a, mint_a.decimals)?;
+        let amount_b = raw_amount(pool.amount_b, mint_b.decimals)?;
+        let mut args = vec![
+            "create-pool",
+            "--mint-a",
+            &mint_a.address,
+            "--mint-b",
+            &mint_b.address,
+            "--source-a",
+            &mint_a.account,
+            "--source-b",
+            &mint_b.account,
+            "--amount-a",
+            &amount_a,
+            "--amount-b",
+            &amount_b,
+        ];
+        let price = pool.token_b_price.map(|price| price.to_string());
+        if let Some(price) = &price {
+            args.extend(["--token-b-price", price]);
+        }
+        let fee = pool.trade_fee_bps.map(|fee| fee.to_string());
+        if let Some(fee) = &fee {
+            args.extend(["--trade-fee-bps", fee]);
+        }
+        let created = balancer(&args)?;
+        let address = printed(&created, "pool ")?;
+        println!("pool {} {}", pool.name, address);
+        created_pools.insert(
+            pool.name.clone(),
+            json!({
+                "address": address,
+                "mint_a": mint_a.address,
+                "mint_b": mint_b.address,
+                "pool_tokens": printed(&created, "pool tokens ")?,
+            }),
+        );
+    }
+
+    for trade in &trades {
+        let pool = pools.iter().find(|pool| pool.name == trade.pool).unwrap();
+        let to = if trade.from == pool.mint_a {
+            &pool.mint_b
+        } else {
+            &pool.mint_a
+        };
+        let (from, to) = (&created_mints[&trade.from], &created_mints[to]);
+        let amount_in = raw_amount(trade.amount_in, from.decimals)?;
+        for _ in 0..trade.repeat {
+            balancer(&[
+                "swap",
+                "--pool",
+                created_pools[&trade.pool]["address"].as_str().unwrap(),
+                "--source",
+                &from.account,
+                "--destination",
+                &to.account,
+                "--amount-in",
+                &amount_in,
+            ])?;
+        }
+        println!(
+            "traded {} x {} {} on {}",
+            trade.repeat, trade.amount_in, trade.from, trade.pool
+        );
+    }
+
+    let environment = json!({
+        "rpc_url": RPC_URL,
+        "program_id": program_id,
+        "wallet": wallet.to_string_lossy(),
+        "mints": created_mints
+            .iter()
+            .map(|(name, mint)| {
+                (
+                    name.clone(),
+                    json!({
+                        "address": mint.address,
+                        "decimals": mint.decimals,
+                        "account": mint.account,
+                    }),
+                )
+            })
+            .collect::<Map<String, Value>>(),
+        "pools": created_pools,
+    });
+    let environment_path = ledger.join("environment.json");
+    fs::write(
+        &environment_path,
+        serde_json::to_string_pretty(&environment).unwrap() + "\n",
+    )
+    .map_err(|err| format!("writing {}: {}", environment_path.display(), err))?;
+    println!("wrote {}", environment_path.display());
+
+    if wait {
+        println!("validator running at {}, ctrl-c to stop", RPC_URL);
+        validator.wait()
+    } else {
+        Ok(())
+    }
+}
+
+fn parse_scenario(spec: &Value) -> Result<Scenario, String> {
+    let list = |key: &str| {
+        spec[key]
+            .as_array()
+            .cloned()
+            .ok_or_else(|| format!("scenario: {} must be a list", key))
+    };
+    let string = |value: &Value, key: &str| {
+        value[key]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| format!("scenario: {} needs a {}", value, key))
+    };
+    let number = |value: &Value, key: &str| {
+        value[key]
+            .as_u64()
+            .ok_or_else(|| format!("scenario: {} needs a {}", value, key))
+    };
+
+    let mut mints = vec![];
+    for mint in list("mints")? {
+        mints.push(MintSpec {
+            name: string(&mint, "name")?,
+            decimals: number(&mint, "decimals")? as u32,
+            supply: number(&mint, "supply")?,
+        });
+    }
+    let known_mint = |name: &str| {
+        if mints.iter().any(|mint| mint.name == name) {
+            Ok(())
+        } else {
+            Err(format!("scenario: unknown mint {}", name))
+        }
+    };
+    let mut pools = vec![];
+    for pool in list("pools")? {
+        let pool = PoolSpec {
+            name: string(&pool, "name")?,
+            mint_a: string(&pool, "mint_a")?,
+            mint_b: string(&pool, "mint_b")?,
+            amount_a: number(&pool, "amount_a")?,
+            amount_b: number(&pool, "amount_b")?,
+            token_b_price: pool["token_b_price"].as_u64(),
+            trade_fee_bps: pool["trade_fee_bps"].as_u64(),
+        };
+        known_mint(&pool.mint_a)?;
+        known_mint(&pool.mint_b)?;
+        pools.push(pool);
+    }
+    let mut trades = vec![];
+    for trade in list("trades")? {
+        let trade = TradeSpec {
+            pool: string(&trade, "pool")?,
+            from: string(&trade, "from")?,
+            amount_in: number(&trade, "amount_in")?,
+            repeat: trade["repeat"].as_u64().unwrap_or(1),
+        };
+        match pools.iter().find(|pool| pool.name == trade.pool) {
+            Some(pool) if trade.from == pool.mint_a || trade.from == pool.mint_b => {}
+            Some(_) => {
+                return Err(format!(
+                    "scenario: {} isn't traded on {}",
+                    trade.from, trade.pool
+                ))
+            }
+            None => return Err(format!("scenario: unknown pool {}", trade.pool)),
+        }
+        trades.push(trade);
+    }
+    Ok(Scenario {
+        mints,
+        pools,
+        trades,
+    })
+}
+
+/// Whole tokens in the mint's raw units
+fn raw_amount(tokens: u64, decimals: u32) -> Result<String, String> {
+    10u64
+        .checked_pow(decimals)
+        .and_then(|scale| tokens.checked_mul(scale))
+        .map(|amount| amount.to_string())
+        .ok_or_else(|| format!("{} tokens at {} decimals overflow", tokens, decimals))
+}
+
+/// The rest of the output line starting with `prefix`
+fn printed(output: &str, prefix: &str) -> Result<String, String> {
+    output
+        .lines()
+        .find_map(|line| line.strip_prefix(prefix))
+        .map(|rest| rest.trim().to_string())
+        .ok_or_else(|| format!("expected a `{}` line in: {}", prefix.trim(), output))
+}
+
+pub fn run(command: &mut Command) -> Result<(), String> {
+    let status = command
+        .status()
+        .map_err(|err| format!("running {:?}: {}", command, err))?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(format!("{:?} failed with {}", command, status))
+    }
+}
+
+fn output(command: &mut Command) -> Result<String, String> {
+    let output = command
+        .output()
+        .map_err(|err| format!("running {:?}: {}", command, err))?;
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(format!(
+            "{:?} failed: {}{}",
+            command,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// The test validator, killed when dropped so a failed scenario doesn't
+/// leave it running
+struct Validator(Child);
+
+impl Validator {
+    fn start(ledger: &Path) -> Result<Self, String> {
+        let child = Command::new("solana-test-validator")
+            .args(["--reset", "--quiet", "--ledger"])
+            .arg(ledger)
+            .stdout(Stdio::null())
+            .spawn()
+            .map_err(|err| format!("starting solana-test-validator: {}", err))?;
+        let validator = Self(child);
+        for _ in 0..60 {
+            let ready = Command::new("solana")
+                .args(["cluster-version", "--url", RPC_URL])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if ready {
+                return Ok(validator);
+            }
+            thread::sleep(Duration::from_secs(1));
+        }
+        Err("solana-test-validator didn't come up within a minute".to_string())
+    }
+
+    fn wait(&mut self) -> Result<(), String> {
+        self.0
+            .wait()
+            .map(|_| ())
+            .map_err(|err| format!("waiting for solana-test-validator: {}", err))
+    }
+}
+
+impl Drop for Validator {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+    }
+}
@@ -0,0 +1,286 @@
+//! `cargo xtask gen-ts`: TypeScript definitions of the program's
+//! instructions, account state and events, read from the Rust sources, so a
+//! web SDK can't drift from `instruction.rs`, `state.rs` and `events.rs`.
+//!
+//! The types describe the decoded values: pubkeys as base58 strings, 64 and
+//! 128 bit integers as `bigint`, byte arrays as `Uint8Array`.  Enums carrying
+//! data become unions discriminated by `kind`, with their tags in a `const`
+//! object alongside.  A field of a type the generator doesn't know fails it,
+//! rather than falling back to `any`.
+
+use crate::{array_len, doc_lines, type_name, workspace_root, USAGE};
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::PathBuf,
+};
+use syn::{Attribute, Fields, GenericArgument, Item, PathArguments, Type, Visibility};
+
+/// Sources the types are read from, relative to `src`
+const SOURCES: &[&str] = &[
+    "instruction.rs",
+    "state.rs",
+    "events.rs",
+    "curve/base.rs",
+    "curve/calculator.rs",
+    "curve/fees.rs",
+    "curve/constant_price.rs",
+    "curve/constant_product.rs",
+    "curve/stable.rs",
+    "curve/offset.rs",
+    "curve/weighted.rs",
+    "curve/concentrated.rs",
+];
+
+/// Rust types written as a TypeScript primitive
+fn primitive(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "u8" | "u16" | "u32" | "i8" | "i16" | "i32" => "number",
+        "u64" | "u128" | "i64" | "i128" => "bigint",
+        "bool" => "boolean",
+        "Pubkey" => "string",
+        _ => return None,
+    })
+}
+
+pub fn gen_ts(args: &[String]) -> Result<(), String> {
+    let root = workspace_root();
+    let out = match args {
+        [] => root.join("target").join("sdk").join("types.ts"),
+        [flag, path] if flag == "--out" => PathBuf::from(path),
+        _ => return Err(USAGE.to_string()),
+    };
+
+    let mut items = HashMap::new();
+    // every account type the program owns or returns, besides the roots
+    // reached through the instructions and events
+    let mut roots = vec!["SwapInstruction".to_string(), "Event".to_string()];
+    for source in SOURCES {
+        let path = root.join("src").join(source);
+        let text = fs::read_to_string(&path)
+            .map_err(|err| format!("reading {}: {}", path.display(), err))?;
+        let file = syn::parse_file(&text).map_err(|err| format!("parsing {}: {}", source, err))?;
+        for item in file.items {
+            let (name, public) = match &item {
+                Item::Struct(item) => (item.ident.to_string(), is_public(&item.vis)),
+                Item::Enum(item) => (item.ident.to_string(), is_public(&item.vis)),
+                _ => continue,
+            };
+            // `SwapVersion` is packed by hand, its structs are what's decoded
+            if *source == "state.rs" && public && matches!(item, Item::Struct(_)) {
+                roots.push(name.clone());
+            }
+            items.insert(name, item);
+        }
+    }
+
+    let mut generator = Generator {
+        items: &items,
+        queued: roots.iter().cloned().collect(),
+        pending: roots.into_iter().collect(),
+        output: vec![],
+    };
+    while let Some(name) = generator.pending.pop_front() {
+        generator.declare(&name)?;
+    }
+
+    let header =
+        "// Generated by `cargo xtask gen-ts` from the program's Rust sources, do not edit.\n";
+    let text = format!("{}\n{}", header, generator.output.join("\n"));
+    if let Some(dir) = out.parent() {
+        fs::create_dir_all(dir).map_err(|err| format!("creating {}: {}", dir.display(), err))?;
+    }
+    fs::write(&out, text).map_err(|err| format!("writing {}: {}", out.display(), err))?;
+    println!(
+        "wrote {} type definitions to {}",
+        generator.output.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+struct Generator<'a> {
+    items: &'a HashMap<String, Item>,
+    /// Every type declared or waiting to be, so each is declared once
+    queued: Vec<String>,
+    pending: VecDeque<String>,
+    output: Vec<String>,
+}
+
+impl Generator<'_> {
+    fn declare(&mut self, name: &str) -> Result<(), String> {
+        // the calculator is a trait object, its parameters follow the curve
+        // type, one `<CurveType>Curve` struct per variant
+        if name == "SwapCurve" {
+            return self.declare_swap_curve();
+        }
+        let declaration = match &self.items[name] {
+            Item::Struct(item) => {
+                let fields = self.fields(name, &item.fields)?;
+                format!(
+                    "{}export interface {} {{\n{}}}\n",
+                    docs(&item.attrs, ""),
+                    name,
+                    fields
+                )
+            }
+            Item::Enum(item) => {
+                let mut variants = vec![];
+                let mut tags = vec![];
+                let mut has_data = false;
+                for (tag, variant) in item.variants.iter().enumerate() {
+                    let variant_name = variant.ident.to_string();
+                    let data = match &variant.fields {
+                        Fields::Unit => None,
+                        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                            has_data = true;
+                            Some(self.ts_type(name, &fields.unnamed[0].ty)?)
+                        }
+                        _ => {
+                            return Err(format!("{}::{}: unsupported variant", name, variant_name))
+                        }
+                    };
+                    variants.push((variant_name.clone(), data));
+                    tags.push(format!("  {}: {},\n", variant_name, tag));
+                }
+                let union: Vec<String> = variants
+                    .iter()
+                    .map(|(variant, data)| match (has_data, data) {
+                        (false, _) => format!("\"{}\"", variant),
+                        (true, None) => format!("{{ kind: \"{}\" }}", variant),
+                        (true, Some(data)) => {
+                            format!("{{ kind: \"{}\"; data: {} }}", variant, data)
+                        }
+                    })
+                    .collect();
+                let mut declaration = format!(
+                    "{}export type {} =\n  | {};\n",
+                    docs(&item.attrs, ""),
+                    name,
+                    union.join("\n  | ")
+                );
+                if has_data {
+                    declaration += &format!(
+                        "\n/** Index of each `{}` variant, the byte it's encoded with */\nexport const {}Tag = {{\n{}}} as const;\n",
+                        name,
+                        name,
+                        tags.concat()
+                    );
+                }
+                declaration
+            }
+            _ => unreachable!(),
+        };
+        self.output.push(declaration);
+        Ok(())
+    }
+
+    fn declare_swap_curve(&mut self) -> Result<(), String> {
+        let curve_types = match self.items.get("CurveType") {
+            Some(Item::Enum(item)) => item.variants.clone(),
+            _ => return Err("CurveType not found".to_string()),
+        };
+        let mut variants = vec![];
+        for variant in curve_types {
+            let calculator = format!("{}Curve", variant.ident);
+            if !self.items.contains_key(&calculator) {
+                return Err(format!("SwapCurve: no {} for its curve type", calculator));
+            }
+            self.queue(&calculator);
+            variants.push(format!(
+                "({{ curve_type: \"{}\" }} & {})",
+                variant.ident, calculator
+            ));
+        }
+        self.output.push(format!(
+            "/** A curve type, flattened with its calculator's parameters */\nexport type SwapCurve =\n  | {};\n",
+            variants.join("\n  | ")
+        ));
+        Ok(())
+    }
+
+    fn fields(&mut self, name: &str, fields: &Fields) -> Result<String, String> {
+        let mut lines = String::new();
+        for field in fields {
+            let field_name = field
+                .ident
+                .as_ref()
+                .ok_or_else(|| format!("{}: tuple structs aren't supported", name))?;
+            lines += &docs(&field.attrs, "  ");
+            lines += &format!(
+                "  {}: {};\n",
+                field_name,
+                self.ts_type(&format!("{}.{}", name, field_name), &field.ty)?
+            );
+        }
+        Ok(lines)
+    }
+
+    fn ts_type(&mut self, context: &str, ty: &Type) -> Result<String, String> {
+        let unknown = || format!("{}: no TypeScript type for {}", context, type_name(ty));
+        match ty {
+            Type::Array(array) => {
+                array_len(&array.len).ok_or_else(unknown)?;
+                match type_name(&array.elem).as_str() {
+                    "u8" => Ok("Uint8Array".to_string()),
+                    _ => Ok(format!("{}[]", self.ts_type(context, &array.elem)?)),
+                }
+            }
+            Type::Path(path) => {
+                let segment = path.path.segments.last().unwrap();
+                let name = segment.ident.to_string();
+                let argument = match &segment.arguments {
+                    PathArguments::AngleBracketed(args) => match args.args.first() {
+                        Some(GenericArgument::Type(ty)) => Some(ty),
+                        _ => return Err(unknown()),
+                    },
+                    _ => None,
+                };
+                match (name.as_str(), argument) {
+                    ("Vec", Some(ty)) if type_name(ty) == "u8" => Ok("Uint8Array".to_string()),
+                    ("Vec", Some(ty)) => Ok(format!("{}[]", self.ts_type(context, ty)?)),
+                    ("Option", Some(ty)) => Ok(format!("{} | null", self.ts_type(context, ty)?)),
+                    (_, None) => match primitive(&name) {
+                        Some(primitive) => Ok(primitive.to_string()),
+                        None if self.items.contains_key(&name) => {
+                            self.queue(&name);
+                            Ok(name)
+                        }
+                        None => Err(unknown()),
+                    },
+                    _ => Err(unknown()),
+                }
+            }
+            _ => Err(unknown()),
+        }
+    }
+
+    fn queue(&mut self, name: &str) {
+        if !self.queued.iter().any(|queued| queued == name) {
+            self.queued.push(name.to_string());
+            self.pending.push_back(name.to_string());
+        }
+    }
+}
+
+fn is_public(vis: &Visibility) -> bool {
+    matches!(vis, Visibility::Public(_))
+}
+
+/// A JSDoc comment from the item's doc comment, empty without one
+fn docs(attrs: &[Attribute], indent: &str) -> String {
+    let lines = doc_lines(attrs);
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut comment = format!("{}/**\n", indent);
+    for line in lines {
+        let line = line.replace("*/", "*\\/");
+        if line.is_empty() {
+            comment += &format!("{} *\n", indent);
+        } else {
+            comment += &format!("{} * {}\n", indent, line);
+        }
+    }
+    comment + &format!("{} */\n", indent)
+}